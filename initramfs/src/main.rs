@@ -5,11 +5,24 @@
 use core::{ffi::c_char, fmt::Write};
 
 use crate::syscalls::{
-    LinuxDirent64, StatBuf, Timespec, UtsName, sys_access, sys_chdir, sys_clock_gettime, sys_close,
-    sys_dup, sys_dup2, sys_execve, sys_exit, sys_fork, sys_fstat, sys_ftruncate, sys_get_cwd,
-    sys_getdents64, sys_getpid, sys_getppid, sys_gettid, sys_lseek, sys_mkdir, sys_nanosleep,
-    sys_open, sys_read, sys_rename, sys_rmdir, sys_stat, sys_uname, sys_unlink, sys_waitpid,
-    sys_write, sys_yield,
+    CmsgHdr, EpollEvent, IoVec, Itimerspec, LinuxDirent64, MqAttr, MsgHdr, PollFd, SemBuf, ShmidDs,
+    SigEvent, SockAddrUn, StatBuf, StatFs, Timespec, UtsName, sys_accept4, sys_access, sys_bind,
+    sys_chdir, sys_chmod, sys_chown, sys_clock_gettime, sys_close, sys_connect, sys_dup, sys_dup2,
+    sys_epoll_create1, sys_epoll_ctl, sys_epoll_wait, sys_eventfd2, sys_execve, sys_exit,
+    sys_fchmod, sys_fdatasync, sys_fgetxattr, sys_flistxattr, sys_fork, sys_fremovexattr,
+    sys_fsetxattr, sys_fstat, sys_fstatfs, sys_fsync, sys_ftruncate, sys_get_cwd, sys_getdents64,
+    sys_getpid, sys_getppid, sys_gettid, sys_getxattr, sys_ioctl, sys_lgetxattr, sys_link,
+    sys_linkat, sys_listen, sys_listxattr, sys_llistxattr, sys_lremovexattr, sys_lseek,
+    sys_lsetxattr, sys_lstat, sys_memfd_create, sys_mkdir, sys_mknod, sys_mknodat, sys_mmap,
+    sys_mount, sys_mq_getsetattr, sys_mq_notify, sys_mq_open, sys_mq_timedreceive,
+    sys_mq_timedsend, sys_mq_unlink, sys_munmap, sys_nanosleep, sys_open, sys_pivot_root, sys_poll,
+    sys_pread64, sys_preadv, sys_pwrite64, sys_pwritev, sys_read, sys_readlink, sys_readlinkat,
+    sys_readv, sys_recvfrom, sys_recvmsg, sys_removexattr, sys_rename, sys_rmdir, sys_semctl,
+    sys_semget, sys_semop, sys_semtimedop, sys_sendmsg, sys_setxattr, sys_shmat, sys_shmctl,
+    sys_shmdt, sys_shmget, sys_shutdown, sys_signalfd4, sys_socket, sys_socketpair, sys_stat,
+    sys_statfs, sys_symlink, sys_symlinkat, sys_sync, sys_syncfs, sys_timerfd_create,
+    sys_timerfd_gettime, sys_timerfd_settime, sys_umount2, sys_uname, sys_unlink, sys_utimensat,
+    sys_waitpid, sys_write, sys_writev, sys_yield,
 };
 
 pub mod syscalls;
@@ -42,10 +55,64 @@ pub const SEEK_END: i32 = 2;
 pub const O_NOATIME: i32 = 0o1000000;
 pub const O_PATH: i32 = 0o10000000;
 pub const O_DIRECTORY: i32 = 0x10000;
+pub const O_NOFOLLOW: i32 = 0x20000;
 pub const O_TMPFILE: i32 = 0o20000000 | O_DIRECTORY;
 
 pub const MADV_SOFT_OFFLINE: i32 = 101;
 pub const MAP_GROWSDOWN: i32 = 0x0100;
+pub const PROT_READ: i32 = 0x1;
+pub const PROT_WRITE: i32 = 0x2;
+pub const MAP_SHARED: i32 = 0x01;
+pub const MAP_PRIVATE: i32 = 0x02;
+pub const MAP_FIXED: i32 = 0x10;
+pub const MAP_ANONYMOUS: i32 = 0x20;
+pub const IPC_PRIVATE: i32 = 0;
+pub const IPC_CREAT: i32 = 0o1000;
+pub const IPC_EXCL: i32 = 0o2000;
+pub const IPC_RMID: i32 = 0;
+pub const IPC_STAT: i32 = 2;
+
+pub const AF_UNIX: i32 = 1;
+pub const SOCK_STREAM: i32 = 1;
+pub const SOCK_DGRAM: i32 = 2;
+pub const SOCK_NONBLOCK: i32 = 0o4000;
+pub const SOL_SOCKET: i32 = 1;
+pub const SCM_RIGHTS: i32 = 1;
+pub const MSG_TRUNC: i32 = 0x20;
+pub const MSG_DONTWAIT: i32 = 0x40;
+pub const SHUT_WR: i32 = 1;
+
+pub const POLLIN: i16 = 0x001;
+pub const POLLOUT: i16 = 0x004;
+pub const POLLHUP: i16 = 0x010;
+pub const POLLNVAL: i16 = 0x020;
+pub const EPOLL_CTL_ADD: i32 = 1;
+pub const EPOLL_CTL_DEL: i32 = 2;
+pub const EPOLLIN: u32 = 0x001;
+pub const EFD_SEMAPHORE: i32 = 1;
+pub const EFD_NONBLOCK: i32 = 0o4000;
+pub const CLOCK_MONOTONIC: i32 = 1;
+pub const TFD_NONBLOCK: i32 = 0o4000;
+pub const SIGCHLD: i32 = 17;
+pub const SIGINFO_SIZE: usize = 128;
+pub const SIGUSR1: i32 = 10;
+pub const SIGEV_SIGNAL: i32 = 0;
+pub const IPC_NOWAIT: i16 = 0o4000;
+pub const SEM_UNDO: i16 = 0x1000;
+pub const GETPID: i32 = 11;
+pub const GETVAL: i32 = 12;
+pub const GETALL: i32 = 13;
+pub const SETVAL: i32 = 16;
+pub const SETALL: i32 = 17;
+pub const MS_RDONLY: u64 = 1;
+pub const MS_REMOUNT: u64 = 32;
+pub const LOOP_SET_FD: u64 = 0x4C00;
+pub const LOOP_CLR_FD: u64 = 0x4C01;
+pub const LOOP_CTL_GET_FREE: u64 = 0x4C82;
+pub const XATTR_CREATE: i32 = 1;
+pub const XATTR_REPLACE: i32 = 2;
+pub const AT_FDCWD: i32 = -100;
+pub const AT_EMPTY_PATH: i32 = 0x1000;
 
 pub const EDEADLK: i32 = 35;
 pub const ENAMETOOLONG: i32 = 36;
@@ -170,6 +237,46 @@ pub extern "C" fn _start(_argc: i32, _argv: *const *const c_char) -> ! {
     test_access();
     test_rename();
     test_clock_gettime();
+    test_readv_writev();
+    test_pread_pwrite();
+    test_preadv_pwritev();
+    test_socketpair();
+    test_socket_dgram();
+    test_socket_bind_connect();
+    test_scm_rights();
+    test_socket_fork();
+    test_eventfd();
+    test_timerfd();
+    test_poll();
+    test_epoll();
+    test_signalfd();
+    test_mmap_anon();
+    test_mmap_shared_anon();
+    test_memfd();
+    test_sysv_shm();
+    test_dev_shm();
+    test_mqueue();
+    test_sysv_sem();
+    test_mount();
+    test_link();
+    test_symlink();
+    test_devfs();
+    test_procfs();
+    test_tmpfs();
+    test_xattr();
+    test_tarfs();
+    test_initramfs_metadata();
+    test_initramfs_layers();
+    test_sync();
+    test_nvme();
+    test_ahci();
+    test_virtio();
+    test_partitions();
+    test_vfat();
+    test_ext2();
+    test_iso9660();
+    test_ramdisk();
+    test_loop();
     test_fork();
     test_fork_wait();
     test_execve();
@@ -869,6 +976,3620 @@ fn test_clock_gettime() {
     check("EINVAL on bad clock_id", r3 == -22, fmt_i32(r3));
 }
 
+fn test_readv_writev() {
+    println!("[readv/writev]");
+    let parts: [&[u8]; 3] = [b"  [PASS] ", b"writev to ", b"stdout\n"];
+    let iov = parts.map(|p| IoVec {
+        iov_base: p.as_ptr(),
+        iov_len: p.len(),
+    });
+    let n = sys_writev(1, iov.as_ptr(), iov.len() as i32);
+    check("writev returns total", n == 26, fmt_isize(n));
+
+    let fd = sys_open(
+        c"/tmp/vec_test.txt".as_ptr() as _,
+        O_RDWR | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    if fd < 0 {
+        check("open for writev", false, fmt_i32(fd));
+        return;
+    }
+
+    let file_parts: [&[u8]; 2] = [b"hello ", b"vectors"];
+    let iov = file_parts.map(|p| IoVec {
+        iov_base: p.as_ptr(),
+        iov_len: p.len(),
+    });
+    let n = sys_writev(fd, iov.as_ptr(), iov.len() as i32);
+    check("writev to file", n == 13, fmt_isize(n));
+
+    sys_lseek(fd, 0, SEEK_SET);
+    let mut a = [0u8; 6];
+    let mut b = [0u8; 16];
+    let iov = [
+        IoVec {
+            iov_base: a.as_mut_ptr(),
+            iov_len: a.len(),
+        },
+        IoVec {
+            iov_base: b.as_mut_ptr(),
+            iov_len: b.len(),
+        },
+    ];
+    let n = sys_readv(fd, iov.as_ptr(), iov.len() as i32);
+    check("readv returns file size", n == 13, fmt_isize(n));
+    check(
+        "readv scatters in order",
+        &a == b"hello " && &b[..7] == b"vectors",
+        "",
+    );
+
+    let n = sys_writev(fd, iov.as_ptr(), -1);
+    check("EINVAL on bad iovcnt", n == -22, fmt_isize(n));
+
+    let n = sys_readv(999, iov.as_ptr(), iov.len() as i32);
+    check("EBADF on bad fd", n == -9, fmt_isize(n));
+
+    sys_close(fd);
+}
+
+fn test_pread_pwrite() {
+    println!("[pread64/pwrite64]");
+    let fd = sys_open(
+        c"/tmp/pio_test.txt".as_ptr() as _,
+        O_RDWR | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    if fd < 0 {
+        check("open for pread", false, fmt_i32(fd));
+        return;
+    }
+
+    sys_write(fd, b"0123456789".as_ptr(), 10);
+
+    let n = sys_pwrite64(fd, b"ab".as_ptr(), 2, 4);
+    check("pwrite64 at offset 4", n == 2, fmt_isize(n));
+
+    let mut buf = [0u8; 4];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), 3);
+    check(
+        "pread64 sees pwrite64",
+        n == 4 && &buf == b"3ab6",
+        fmt_isize(n),
+    );
+
+    let pos = sys_lseek(fd, 0, SEEK_CUR);
+    check("file offset untouched", pos == 10, fmt_i64(pos));
+
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), 100);
+    check("pread64 past EOF returns 0", n == 0, fmt_isize(n));
+
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), -1);
+    check("EINVAL on negative offset", n == -22, fmt_isize(n));
+
+    let n = sys_pwrite64(1, b"x".as_ptr(), 1, 0);
+    check("ESPIPE on stdout", n == -29, fmt_isize(n));
+
+    sys_close(fd);
+}
+
+fn test_preadv_pwritev() {
+    println!("[preadv/pwritev]");
+    let fd = sys_open(c"/tmp/pio_test.txt".as_ptr() as _, O_RDWR, 0);
+    if fd < 0 {
+        check("open for preadv", false, fmt_i32(fd));
+        return;
+    }
+
+    let parts: [&[u8]; 2] = [b"X", b"YZ"];
+    let iov = parts.map(|p| IoVec {
+        iov_base: p.as_ptr(),
+        iov_len: p.len(),
+    });
+    let n = sys_pwritev(fd, iov.as_ptr(), iov.len() as i32, 7);
+    check("pwritev at offset 7", n == 3, fmt_isize(n));
+
+    let mut a = [0u8; 2];
+    let mut b = [0u8; 2];
+    let iov = [
+        IoVec {
+            iov_base: a.as_mut_ptr(),
+            iov_len: a.len(),
+        },
+        IoVec {
+            iov_base: b.as_mut_ptr(),
+            iov_len: b.len(),
+        },
+    ];
+    let n = sys_preadv(fd, iov.as_ptr(), iov.len() as i32, 6);
+    check(
+        "preadv at offset 6",
+        n == 4 && &a == b"6X" && &b == b"YZ",
+        fmt_isize(n),
+    );
+
+    let pos = sys_lseek(fd, 0, SEEK_CUR);
+    check("file offset untouched", pos == 0, fmt_i64(pos));
+
+    sys_close(fd);
+    sys_unlink(c"/tmp/pio_test.txt".as_ptr() as _);
+    sys_unlink(c"/tmp/vec_test.txt".as_ptr() as _);
+}
+
+const SOCK_ADDR_LEN: u32 = size_of::<SockAddrUn>() as u32;
+
+fn test_socketpair() {
+    println!("[socketpair]");
+    let mut sv = [0i32; 2];
+    let r = sys_socketpair(AF_UNIX, SOCK_STREAM, 0, &mut sv);
+    check("socketpair stream", r == 0, fmt_i32(r));
+    if r != 0 {
+        return;
+    }
+
+    let n = sys_write(sv[0], b"ping".as_ptr(), 4);
+    check("write to one end", n == 4, fmt_isize(n));
+    let mut buf = [0u8; 16];
+    let n = sys_read(sv[1], buf.as_mut_ptr(), buf.len());
+    check(
+        "read from other end",
+        n == 4 && &buf[..4] == b"ping",
+        fmt_isize(n),
+    );
+
+    let n = sys_write(sv[1], b"pong".as_ptr(), 4);
+    let n2 = sys_read(sv[0], buf.as_mut_ptr(), buf.len());
+    check(
+        "reply goes the other way",
+        n == 4 && n2 == 4 && &buf[..4] == b"pong",
+        fmt_isize(n2),
+    );
+
+    let n = sys_recvfrom(
+        sv[0],
+        buf.as_mut_ptr(),
+        buf.len(),
+        MSG_DONTWAIT,
+        core::ptr::null_mut(),
+        core::ptr::null_mut(),
+    );
+    check("EAGAIN when empty", n == -11, fmt_isize(n));
+
+    let mut st = unsafe { core::mem::zeroed::<StatBuf>() };
+    let r = sys_fstat(sv[0], &mut st);
+    check(
+        "fstat reports a socket",
+        r == 0 && st.st_mode & 0o170000 == 0o140000,
+        fmt_i32(r),
+    );
+
+    let r = sys_lseek(sv[0], 0, SEEK_SET);
+    check("ESPIPE on lseek", r == -29, fmt_i64(r));
+
+    sys_close(sv[0]);
+    let n = sys_read(sv[1], buf.as_mut_ptr(), buf.len());
+    check("eof after peer close", n == 0, fmt_isize(n));
+    let n = sys_write(sv[1], b"x".as_ptr(), 1);
+    check("EPIPE after peer close", n == -32, fmt_isize(n));
+    sys_close(sv[1]);
+}
+
+fn test_socket_dgram() {
+    println!("[socket dgram]");
+    let mut sv = [0i32; 2];
+    let r = sys_socketpair(AF_UNIX, SOCK_DGRAM, 0, &mut sv);
+    check("socketpair dgram", r == 0, fmt_i32(r));
+    if r != 0 {
+        return;
+    }
+
+    sys_write(sv[0], b"first".as_ptr(), 5);
+    sys_write(sv[0], b"second".as_ptr(), 6);
+
+    let mut buf = [0u8; 16];
+    let n = sys_read(sv[1], buf.as_mut_ptr(), buf.len());
+    check(
+        "message boundaries kept",
+        n == 5 && &buf[..5] == b"first",
+        fmt_isize(n),
+    );
+
+    let mut small = [0u8; 3];
+    let iov = IoVec {
+        iov_base: small.as_mut_ptr(),
+        iov_len: small.len(),
+    };
+    let mut msg = MsgHdr {
+        msg_name: core::ptr::null_mut(),
+        msg_namelen: 0,
+        msg_iov: &iov,
+        msg_iovlen: 1,
+        msg_control: core::ptr::null_mut(),
+        msg_controllen: 0,
+        msg_flags: 0,
+    };
+    let n = sys_recvmsg(sv[1], &mut msg, 0);
+    check(
+        "MSG_TRUNC on short buffer",
+        n == 3 && msg.msg_flags & MSG_TRUNC != 0 && &small == b"sec",
+        fmt_isize(n),
+    );
+
+    sys_close(sv[0]);
+    sys_close(sv[1]);
+}
+
+fn test_socket_bind_connect() {
+    println!("[socket bind/connect]");
+    let path = b"/tmp/test.sock";
+    let addr = SockAddrUn::new(path);
+
+    let server = sys_socket(AF_UNIX, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    check("socket", server >= 0, fmt_i32(server));
+    if server < 0 {
+        return;
+    }
+
+    let r = sys_bind(server, &addr, SOCK_ADDR_LEN);
+    check("bind", r == 0, fmt_i32(r));
+
+    let other = sys_socket(AF_UNIX, SOCK_STREAM, 0);
+    let r = sys_bind(other, &addr, SOCK_ADDR_LEN);
+    check("EADDRINUSE on taken path", r == -98, fmt_i32(r));
+
+    let mut st = unsafe { core::mem::zeroed::<StatBuf>() };
+    let r = sys_stat(c"/tmp/test.sock".as_ptr(), &mut st);
+    check(
+        "bound path is a socket node",
+        r == 0 && st.st_mode & 0o170000 == 0o140000,
+        fmt_i32(r),
+    );
+
+    let r = sys_connect(other, &addr, SOCK_ADDR_LEN);
+    check("ECONNREFUSED before listen", r == -111, fmt_i32(r));
+
+    let r = sys_listen(server, 4);
+    check("listen", r == 0, fmt_i32(r));
+
+    let r = sys_accept4(server, core::ptr::null_mut(), core::ptr::null_mut(), 0);
+    check("EAGAIN with empty backlog", r == -11, fmt_i32(r));
+
+    let r = sys_connect(other, &addr, SOCK_ADDR_LEN);
+    check("connect", r == 0, fmt_i32(r));
+
+    let mut peer = SockAddrUn::new(b"");
+    let mut peer_len = SOCK_ADDR_LEN;
+    let conn = sys_accept4(server, &mut peer, &mut peer_len, 0);
+    check("accept", conn >= 0, fmt_i32(conn));
+    check("unbound peer address", peer_len == 2, "");
+
+    let n = sys_write(other, b"hello server".as_ptr(), 12);
+    let mut buf = [0u8; 32];
+    let n2 = sys_read(conn, buf.as_mut_ptr(), buf.len());
+    check(
+        "client to server",
+        n == 12 && n2 == 12 && &buf[..12] == b"hello server",
+        fmt_isize(n2),
+    );
+
+    let r = sys_shutdown(other, SHUT_WR);
+    let n = sys_read(conn, buf.as_mut_ptr(), buf.len());
+    check("eof after shutdown", r == 0 && n == 0, fmt_isize(n));
+
+    let fd = sys_open(c"/tmp/test.sock".as_ptr(), O_RDWR, 0);
+    check("ENXIO opening a socket node", fd == -6, fmt_i32(fd));
+
+    let missing = SockAddrUn::new(b"/tmp/missing.sock");
+    let lone = sys_socket(AF_UNIX, SOCK_STREAM, 0);
+    let r = sys_connect(lone, &missing, SOCK_ADDR_LEN);
+    check("ENOENT on missing path", r == -2, fmt_i32(r));
+
+    let dir = sys_open(c"/tmp".as_ptr(), O_RDONLY, 0);
+    let r = sys_listen(dir, 1);
+    check("ENOTSOCK on non-socket", r == -88, fmt_i32(r));
+    sys_close(dir);
+
+    sys_close(lone);
+    sys_close(conn);
+    sys_close(other);
+    sys_close(server);
+
+    let r = sys_connect(sys_socket(AF_UNIX, SOCK_STREAM, 0), &addr, SOCK_ADDR_LEN);
+    check("ECONNREFUSED after close", r == -111, fmt_i32(r));
+    sys_unlink(c"/tmp/test.sock".as_ptr());
+}
+
+fn test_scm_rights() {
+    println!("[SCM_RIGHTS]");
+    let fd = sys_open(
+        c"/tmp/rights.txt".as_ptr(),
+        O_RDWR | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    sys_write(fd, b"passed along".as_ptr(), 12);
+    sys_lseek(fd, 0, SEEK_SET);
+
+    let mut sv = [0i32; 2];
+    sys_socketpair(AF_UNIX, SOCK_STREAM, 0, &mut sv);
+
+    #[repr(C)]
+    struct Control {
+        hdr: CmsgHdr,
+        fd: i32,
+        _pad: i32,
+    }
+
+    let mut control = Control {
+        hdr: CmsgHdr {
+            cmsg_len: size_of::<CmsgHdr>() + 4,
+            cmsg_level: SOL_SOCKET,
+            cmsg_type: SCM_RIGHTS,
+        },
+        fd,
+        _pad: 0,
+    };
+    let byte = *b"!";
+    let iov = IoVec {
+        iov_base: byte.as_ptr(),
+        iov_len: 1,
+    };
+    let msg = MsgHdr {
+        msg_name: core::ptr::null_mut(),
+        msg_namelen: 0,
+        msg_iov: &iov,
+        msg_iovlen: 1,
+        msg_control: &mut control as *mut Control as *mut u8,
+        msg_controllen: size_of::<Control>(),
+        msg_flags: 0,
+    };
+    let n = sys_sendmsg(sv[0], &msg, 0);
+    check("sendmsg with SCM_RIGHTS", n == 1, fmt_isize(n));
+    sys_close(fd);
+
+    let mut recv_control = Control {
+        hdr: CmsgHdr {
+            cmsg_len: 0,
+            cmsg_level: 0,
+            cmsg_type: 0,
+        },
+        fd: -1,
+        _pad: 0,
+    };
+    let mut byte = [0u8];
+    let iov = IoVec {
+        iov_base: byte.as_mut_ptr(),
+        iov_len: 1,
+    };
+    let mut msg = MsgHdr {
+        msg_name: core::ptr::null_mut(),
+        msg_namelen: 0,
+        msg_iov: &iov,
+        msg_iovlen: 1,
+        msg_control: &mut recv_control as *mut Control as *mut u8,
+        msg_controllen: size_of::<Control>(),
+        msg_flags: 0,
+    };
+    let n = sys_recvmsg(sv[1], &mut msg, 0);
+    check(
+        "recvmsg gets the fd",
+        n == 1
+            && recv_control.hdr.cmsg_level == SOL_SOCKET
+            && recv_control.hdr.cmsg_type == SCM_RIGHTS
+            && recv_control.fd >= 0,
+        fmt_isize(n),
+    );
+
+    let mut buf = [0u8; 16];
+    let n = sys_read(recv_control.fd, buf.as_mut_ptr(), buf.len());
+    check(
+        "received fd reads the file",
+        n == 12 && &buf[..12] == b"passed along",
+        fmt_isize(n),
+    );
+
+    control.fd = 999;
+    let msg = MsgHdr {
+        msg_name: core::ptr::null_mut(),
+        msg_namelen: 0,
+        msg_iov: &iov,
+        msg_iovlen: 1,
+        msg_control: &mut control as *mut Control as *mut u8,
+        msg_controllen: size_of::<Control>(),
+        msg_flags: 0,
+    };
+    let n = sys_sendmsg(sv[0], &msg, 0);
+    check("EBADF on bad passed fd", n == -9, fmt_isize(n));
+
+    sys_close(recv_control.fd);
+    sys_close(sv[0]);
+    sys_close(sv[1]);
+    sys_unlink(c"/tmp/rights.txt".as_ptr());
+}
+
+fn test_socket_fork() {
+    println!("[socket fork]");
+    let addr = SockAddrUn::new(b"/tmp/fork.sock");
+    let server = sys_socket(AF_UNIX, SOCK_STREAM, 0);
+    sys_bind(server, &addr, SOCK_ADDR_LEN);
+    sys_listen(server, 1);
+
+    let pid = sys_fork();
+    if pid == 0 {
+        let client = sys_socket(AF_UNIX, SOCK_STREAM, 0);
+        sys_connect(client, &addr, SOCK_ADDR_LEN);
+        sys_write(client, b"from child".as_ptr(), 10);
+        sys_exit(0);
+    }
+
+    // blocks until the child connects
+    let conn = sys_accept4(server, core::ptr::null_mut(), core::ptr::null_mut(), 0);
+    check("accept from child", conn >= 0, fmt_i32(conn));
+
+    let mut buf = [0u8; 16];
+    let n = sys_read(conn, buf.as_mut_ptr(), buf.len());
+    check(
+        "read child's message",
+        n == 10 && &buf[..10] == b"from child",
+        fmt_isize(n),
+    );
+    let n = sys_read(conn, buf.as_mut_ptr(), buf.len());
+    check("eof once child exits", n == 0, fmt_isize(n));
+
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+
+    sys_close(conn);
+    sys_close(server);
+    sys_unlink(c"/tmp/fork.sock".as_ptr());
+}
+
+fn read_u64(fd: i32) -> (isize, u64) {
+    let mut buf = [0u8; 8];
+    let n = sys_read(fd, buf.as_mut_ptr(), 8);
+    (n, u64::from_ne_bytes(buf))
+}
+
+fn write_u64(fd: i32, value: u64) -> isize {
+    sys_write(fd, value.to_ne_bytes().as_ptr(), 8)
+}
+
+fn itimerspec_ms(value_ms: i64, interval_ms: i64) -> Itimerspec {
+    Itimerspec {
+        it_interval: Timespec {
+            tv_sec: interval_ms / 1000,
+            tv_nsec: (interval_ms % 1000) * 1_000_000,
+        },
+        it_value: Timespec {
+            tv_sec: value_ms / 1000,
+            tv_nsec: (value_ms % 1000) * 1_000_000,
+        },
+    }
+}
+
+fn test_eventfd() {
+    println!("[eventfd]");
+    let efd = sys_eventfd2(0, EFD_NONBLOCK);
+    check("eventfd2", efd >= 0, fmt_i32(efd));
+
+    let (n, _) = read_u64(efd);
+    check("read of zero counter -> EAGAIN", n == -11, fmt_isize(n));
+
+    write_u64(efd, 3);
+    write_u64(efd, 4);
+    let (n, value) = read_u64(efd);
+    check("writes add up", n == 8 && value == 7, fmt_isize(n));
+    let (n, _) = read_u64(efd);
+    check("read drains counter", n == -11, fmt_isize(n));
+
+    let mut small = [0u8; 4];
+    let n = sys_read(efd, small.as_mut_ptr(), small.len());
+    check("short read -> EINVAL", n == -22, fmt_isize(n));
+    let n = write_u64(efd, u64::MAX);
+    check("write of u64::MAX -> EINVAL", n == -22, fmt_isize(n));
+    sys_close(efd);
+
+    let sem = sys_eventfd2(2, EFD_SEMAPHORE | EFD_NONBLOCK);
+    let (n1, v1) = read_u64(sem);
+    let (n2, v2) = read_u64(sem);
+    let (n3, _) = read_u64(sem);
+    check(
+        "semaphore reads one at a time",
+        n1 == 8 && v1 == 1 && n2 == 8 && v2 == 1 && n3 == -11,
+        fmt_isize(n3),
+    );
+    sys_close(sem);
+
+    let r = sys_eventfd2(0, 0x1234);
+    check("bad flags -> EINVAL", r == -22, fmt_i32(r));
+}
+
+fn test_timerfd() {
+    println!("[timerfd]");
+    let tfd = sys_timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK);
+    check("timerfd_create", tfd >= 0, fmt_i32(tfd));
+
+    let (n, _) = read_u64(tfd);
+    check("disarmed read -> EAGAIN", n == -11, fmt_isize(n));
+
+    let spec = itimerspec_ms(20, 0);
+    let r = sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    check("timerfd_settime", r == 0, fmt_i32(r));
+
+    let mut cur = itimerspec_ms(0, 0);
+    sys_timerfd_gettime(tfd, &mut cur);
+    check(
+        "gettime reports remaining time",
+        cur.it_value.tv_sec == 0 && cur.it_value.tv_nsec > 0 && cur.it_value.tv_nsec <= 20_000_000,
+        "",
+    );
+
+    let mut pfd = PollFd {
+        fd: tfd,
+        events: POLLIN,
+        revents: 0,
+    };
+    let r = sys_poll(&mut pfd, 1, 1000);
+    check(
+        "poll wakes on expiry",
+        r == 1 && pfd.revents == POLLIN,
+        fmt_i32(r),
+    );
+
+    let (n, count) = read_u64(tfd);
+    check("one expiration", n == 8 && count == 1, fmt_isize(n));
+
+    sys_timerfd_gettime(tfd, &mut cur);
+    check(
+        "one-shot disarms itself",
+        cur.it_value.tv_sec == 0 && cur.it_value.tv_nsec == 0,
+        "",
+    );
+
+    let spec = itimerspec_ms(5, 5);
+    sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    let req = Timespec {
+        tv_sec: 0,
+        tv_nsec: 30_000_000,
+    };
+    sys_nanosleep(&req);
+    let (n, count) = read_u64(tfd);
+    check(
+        "interval timer counts overruns",
+        n == 8 && count >= 2,
+        fmt_isize(n),
+    );
+
+    let mut old = itimerspec_ms(0, 0);
+    let spec = itimerspec_ms(0, 0);
+    sys_timerfd_settime(tfd, 0, &spec, &mut old);
+    check(
+        "settime returns old interval",
+        old.it_interval.tv_nsec == 5_000_000,
+        "",
+    );
+
+    // as far out as a timespec goes, it saturates instead of wrapping around
+    let mut spec = itimerspec_ms(0, 0);
+    spec.it_value.tv_sec = i64::MAX;
+    spec.it_interval.tv_sec = i64::MAX;
+    let r = sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    sys_timerfd_gettime(tfd, &mut cur);
+    let (n, _) = read_u64(tfd);
+    check(
+        "a timer at the end of time",
+        r == 0 && cur.it_value.tv_sec > 1 << 32 && n == -11,
+        fmt_i32(r),
+    );
+    let mut spec = itimerspec_ms(0, 0);
+    spec.it_value.tv_nsec = 1_000_000_000;
+    let r = sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    check("tv_nsec past a second -> EINVAL", r == -22, fmt_i32(r));
+    sys_close(tfd);
+
+    // no TFD_NONBLOCK, read itself sleeps until the timer fires
+    let tfd = sys_timerfd_create(CLOCK_MONOTONIC, 0);
+    let spec = itimerspec_ms(10, 0);
+    sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    let (n, count) = read_u64(tfd);
+    check(
+        "blocking read waits for expiry",
+        n == 8 && count == 1,
+        fmt_isize(n),
+    );
+    sys_close(tfd);
+
+    let r = sys_timerfd_create(42, 0);
+    check("bad clock -> EINVAL", r == -22, fmt_i32(r));
+}
+
+fn test_poll() {
+    println!("[poll]");
+    let efd = sys_eventfd2(0, EFD_NONBLOCK);
+    let mut fds = [
+        PollFd {
+            fd: 1,
+            events: POLLOUT,
+            revents: 0,
+        },
+        PollFd {
+            fd: 999,
+            events: POLLIN,
+            revents: 0,
+        },
+        PollFd {
+            fd: efd,
+            events: POLLIN,
+            revents: 0,
+        },
+    ];
+    let r = sys_poll(fds.as_mut_ptr(), fds.len(), 0);
+    check("poll counts ready fds", r == 2, fmt_i32(r));
+    check("stdout is writable", fds[0].revents == POLLOUT, "");
+    check("bad fd -> POLLNVAL", fds[1].revents == POLLNVAL, "");
+    check("empty eventfd not readable", fds[2].revents == 0, "");
+
+    let r = sys_poll(&mut fds[2], 1, 20);
+    check("poll times out", r == 0, fmt_i32(r));
+
+    write_u64(efd, 1);
+    let r = sys_poll(&mut fds[2], 1, -1);
+    check(
+        "eventfd readable after write",
+        r == 1 && fds[2].revents == POLLIN,
+        fmt_i32(r),
+    );
+    sys_close(efd);
+
+    let mut sv = [0i32; 2];
+    sys_socketpair(AF_UNIX, SOCK_STREAM, 0, &mut sv);
+    sys_close(sv[1]);
+    let mut pfd = PollFd {
+        fd: sv[0],
+        events: POLLIN,
+        revents: 0,
+    };
+    let r = sys_poll(&mut pfd, 1, 0);
+    check(
+        "closed peer -> POLLIN | POLLHUP",
+        r == 1 && pfd.revents == POLLIN | POLLHUP,
+        fmt_i32(r),
+    );
+    sys_close(sv[0]);
+}
+
+fn test_epoll() {
+    println!("[epoll]");
+    let ep = sys_epoll_create1(0);
+    check("epoll_create1", ep >= 0, fmt_i32(ep));
+
+    let efd = sys_eventfd2(0, EFD_NONBLOCK);
+    let tfd = sys_timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK);
+    let ev = EpollEvent {
+        events: EPOLLIN,
+        data: 1,
+    };
+    let r = sys_epoll_ctl(ep, EPOLL_CTL_ADD, efd, &ev);
+    check("add eventfd", r == 0, fmt_i32(r));
+    let r = sys_epoll_ctl(ep, EPOLL_CTL_ADD, efd, &ev);
+    check("add twice -> EEXIST", r == -17, fmt_i32(r));
+    let ev = EpollEvent {
+        events: EPOLLIN,
+        data: 2,
+    };
+    let r = sys_epoll_ctl(ep, EPOLL_CTL_ADD, tfd, &ev);
+    check("add timerfd", r == 0, fmt_i32(r));
+
+    let dir = sys_open(c"/tmp".as_ptr(), O_RDONLY, 0);
+    let r = sys_epoll_ctl(ep, EPOLL_CTL_ADD, dir, &ev);
+    check("plain file -> EPERM", r == -1, fmt_i32(r));
+    sys_close(dir);
+
+    let mut events = [const { EpollEvent { events: 0, data: 0 } }; 4];
+    let r = sys_epoll_wait(ep, events.as_mut_ptr(), 4, 0);
+    check("nothing ready yet", r == 0, fmt_i32(r));
+
+    write_u64(efd, 5);
+    let r = sys_epoll_wait(ep, events.as_mut_ptr(), 4, -1);
+    let data = events[0].data;
+    check("eventfd reported", r == 1 && data == 1, fmt_i32(r));
+    read_u64(efd);
+
+    let spec = itimerspec_ms(10, 0);
+    sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    let r = sys_epoll_wait(ep, events.as_mut_ptr(), 4, 1000);
+    let data = events[0].data;
+    check("timerfd wakes epoll_wait", r == 1 && data == 2, fmt_i32(r));
+    read_u64(tfd);
+
+    let r = sys_epoll_wait(ep, events.as_mut_ptr(), 4, 20);
+    check("epoll_wait times out", r == 0, fmt_i32(r));
+
+    let r = sys_epoll_ctl(ep, EPOLL_CTL_DEL, tfd, core::ptr::null());
+    check("del timerfd", r == 0, fmt_i32(r));
+    let r = sys_epoll_ctl(ep, EPOLL_CTL_DEL, tfd, core::ptr::null());
+    check("del twice -> ENOENT", r == -2, fmt_i32(r));
+
+    sys_close(tfd);
+    sys_close(efd);
+    sys_close(ep);
+}
+
+fn test_signalfd() {
+    println!("[signalfd]");
+    let mask: u64 = 1 << (SIGCHLD - 1);
+    let r = sys_signalfd4(-1, &mask, 4, 0);
+    check("bad sizemask -> EINVAL", r == -22, fmt_i32(r));
+
+    let sfd = sys_signalfd4(-1, &mask, 8, EFD_NONBLOCK);
+    check("signalfd4", sfd >= 0, fmt_i32(sfd));
+
+    // earlier tests leave a SIGCHLD around
+    let mut info = [0u8; SIGINFO_SIZE];
+    while sys_read(sfd, info.as_mut_ptr(), SIGINFO_SIZE) > 0 {}
+
+    // wait on the child's exit and a fallback timer from one loop
+    let ep = sys_epoll_create1(0);
+    let tfd = sys_timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK);
+    let spec = itimerspec_ms(5000, 0);
+    sys_timerfd_settime(tfd, 0, &spec, core::ptr::null_mut());
+    let ev = EpollEvent {
+        events: EPOLLIN,
+        data: sfd as u64,
+    };
+    sys_epoll_ctl(ep, EPOLL_CTL_ADD, sfd, &ev);
+    let ev = EpollEvent {
+        events: EPOLLIN,
+        data: tfd as u64,
+    };
+    sys_epoll_ctl(ep, EPOLL_CTL_ADD, tfd, &ev);
+
+    let pid = sys_fork();
+    if pid == 0 {
+        sys_exit(7);
+    }
+
+    let mut events = [const { EpollEvent { events: 0, data: 0 } }; 2];
+    let r = sys_epoll_wait(ep, events.as_mut_ptr(), 2, -1);
+    let data = events[0].data;
+    check(
+        "child exit wakes the loop",
+        r == 1 && data == sfd as u64,
+        fmt_i32(r),
+    );
+
+    let n = sys_read(sfd, info.as_mut_ptr(), SIGINFO_SIZE);
+    let signo = u32::from_ne_bytes(info[0..4].try_into().unwrap());
+    let ssi_pid = u32::from_ne_bytes(info[12..16].try_into().unwrap());
+    let status = i32::from_ne_bytes(info[40..44].try_into().unwrap());
+    check(
+        "siginfo describes the child",
+        n == SIGINFO_SIZE as isize
+            && signo == SIGCHLD as u32
+            && ssi_pid as i64 == pid
+            && status == 7,
+        fmt_isize(n),
+    );
+    let n = sys_read(sfd, info.as_mut_ptr(), SIGINFO_SIZE);
+    check("consumed -> EAGAIN", n == -11, fmt_isize(n));
+
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+
+    sys_close(ep);
+    sys_close(tfd);
+    sys_close(sfd);
+}
+
+fn test_mmap_anon() {
+    println!("[mmap anonymous]");
+    let len = 2 * 4096;
+    let addr = sys_mmap(
+        core::ptr::null_mut(),
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    check("mmap anonymous", addr > 0, fmt_i64(addr));
+    if addr <= 0 {
+        return;
+    }
+
+    let mem = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) };
+    check("starts zeroed", mem.iter().all(|&b| b == 0), "");
+    mem[0] = 0xaa;
+    mem[len - 1] = 0x55;
+    check("writable", mem[0] == 0xaa && mem[len - 1] == 0x55, "");
+
+    // a private mapping stays private across fork
+    let pid = sys_fork();
+    if pid == 0 {
+        mem[0] = 0x11;
+        sys_exit(0);
+    }
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+    check("child writes don't leak back", mem[0] == 0xaa, "");
+
+    let r = sys_munmap(addr as *mut u8, len);
+    check("munmap", r == 0, fmt_i32(r));
+
+    let r = sys_mmap(
+        core::ptr::null_mut(),
+        0,
+        PROT_READ,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    check("zero length -> EINVAL", r == -22, fmt_i64(r));
+    let r = sys_mmap(core::ptr::null_mut(), 4096, PROT_READ, MAP_ANONYMOUS, -1, 0);
+    check("neither shared nor private -> EINVAL", r == -22, fmt_i64(r));
+    let r = sys_mmap(
+        0x1001 as *mut u8,
+        4096,
+        PROT_READ,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        -1,
+        0,
+    );
+    check("misaligned MAP_FIXED -> EINVAL", r == -22, fmt_i64(r));
+    let r = sys_mmap(
+        core::ptr::null_mut(),
+        4096,
+        PROT_READ,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        -1,
+        0,
+    );
+    check("MAP_FIXED at 0 -> EINVAL", r == -22, fmt_i64(r));
+    // our own code wasn't mmapped, so it can't be mapped over
+    let text = test_mmap_anon as *const () as usize & !0xfff;
+    let r = sys_mmap(
+        text as *mut u8,
+        4096,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        -1,
+        0,
+    );
+    check(
+        "MAP_FIXED over program text -> ENOMEM",
+        r == -12,
+        fmt_i64(r),
+    );
+}
+
+fn test_mmap_shared_anon() {
+    println!("[mmap shared anonymous]");
+    let addr = sys_mmap(
+        core::ptr::null_mut(),
+        4096,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    check("mmap MAP_SHARED | MAP_ANONYMOUS", addr > 0, fmt_i64(addr));
+    if addr <= 0 {
+        return;
+    }
+    let mem = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+
+    let pid = sys_fork();
+    if pid == 0 {
+        mem[..5].copy_from_slice(b"child");
+        sys_exit(0);
+    }
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+    check("parent sees child's write", &mem[..5] == b"child", "");
+    sys_munmap(addr as *mut u8, 4096);
+}
+
+fn test_memfd() {
+    println!("[memfd]");
+    let fd = sys_memfd_create(c"frame".as_ptr(), 0);
+    check("memfd_create", fd >= 0, fmt_i32(fd));
+
+    let r = sys_ftruncate(fd, 8192);
+    check("ftruncate sets size", r == 0, fmt_i32(r));
+    let n = sys_write(fd, b"hello".as_ptr(), 5);
+    check("write", n == 5, fmt_isize(n));
+
+    let mut st = core::mem::MaybeUninit::<StatBuf>::uninit();
+    sys_fstat(fd, st.as_mut_ptr());
+    let st = unsafe { st.assume_init_ref() };
+    check(
+        "fstat: regular file of 8192 bytes",
+        st.st_mode & 0o170000 == 0o100000 && st.st_size == 8192,
+        "",
+    );
+
+    let addr = sys_mmap(
+        core::ptr::null_mut(),
+        8192,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd,
+        0,
+    );
+    check("mmap memfd", addr > 0, fmt_i64(addr));
+    if addr <= 0 {
+        sys_close(fd);
+        return;
+    }
+    let mem = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 8192) };
+    check("mapping shows written data", &mem[..5] == b"hello", "");
+
+    mem[4096..4101].copy_from_slice(b"world");
+    let mut buf = [0u8; 5];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), 5, 4096);
+    check(
+        "stores show up in the file",
+        n == 5 && &buf == b"world",
+        fmt_isize(n),
+    );
+
+    // a second mapping of the same fd lands on the same frames
+    let addr2 = sys_mmap(core::ptr::null_mut(), 4096, PROT_READ, MAP_SHARED, fd, 4096);
+    let mem2 = unsafe { core::slice::from_raw_parts(addr2 as *const u8, 5) };
+    check(
+        "mapping at an offset",
+        addr2 > 0 && mem2 == b"world",
+        fmt_i64(addr2),
+    );
+
+    let pid = sys_fork();
+    if pid == 0 {
+        mem[..5].copy_from_slice(b"HELLO");
+        sys_exit(0);
+    }
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+    let n = sys_pread64(fd, buf.as_mut_ptr(), 5, 0);
+    check(
+        "child's store reaches the parent",
+        n == 5 && &buf == b"HELLO",
+        fmt_isize(n),
+    );
+
+    sys_munmap(addr as *mut u8, 8192);
+    sys_munmap(addr2 as *mut u8, 4096);
+    sys_close(fd);
+
+    let r = sys_memfd_create(c"bad".as_ptr(), 0x100);
+    check("bad flags -> EINVAL", r == -22, fmt_i32(r));
+
+    let tmp = sys_open(c"/tmp/mmap_file".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    sys_write(tmp, b"plain".as_ptr(), 5);
+    let r = sys_mmap(core::ptr::null_mut(), 4096, PROT_READ, MAP_SHARED, tmp, 0);
+    check("MAP_SHARED of a plain file -> ENODEV", r == -19, fmt_i64(r));
+    let r = sys_mmap(core::ptr::null_mut(), 4096, PROT_READ, MAP_PRIVATE, tmp, 0);
+    let ok = r > 0 && unsafe { core::slice::from_raw_parts(r as *const u8, 5) } == b"plain";
+    check("MAP_PRIVATE of a plain file copies it", ok, fmt_i64(r));
+    if r > 0 {
+        sys_munmap(r as *mut u8, 4096);
+    }
+    // only the file gets copied, the pages past it are zeroes
+    let len = 64 * 4096;
+    let r = sys_mmap(core::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, tmp, 0);
+    let ok = r > 0 && {
+        let mem = unsafe { core::slice::from_raw_parts(r as *const u8, len) };
+        &mem[..5] == b"plain" && mem[5..].iter().all(|&b| b == 0)
+    };
+    check("MAP_PRIVATE past the end of the file", ok, fmt_i64(r));
+    if r > 0 {
+        sys_munmap(r as *mut u8, len);
+    }
+    sys_close(tmp);
+    sys_unlink(c"/tmp/mmap_file".as_ptr());
+}
+
+fn test_sysv_shm() {
+    println!("[sysv shm]");
+    let id = sys_shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0o600);
+    check("shmget", id >= 0, fmt_i32(id));
+
+    let addr = sys_shmat(id, core::ptr::null(), 0);
+    check("shmat", addr > 0, fmt_i64(addr));
+    if addr <= 0 {
+        return;
+    }
+    let mem = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, 4096) };
+
+    let mut ds = core::mem::MaybeUninit::<ShmidDs>::uninit();
+    let r = sys_shmctl(id, IPC_STAT, ds.as_mut_ptr());
+    let ds = unsafe { ds.assume_init_ref() };
+    check(
+        "IPC_STAT: size and one attachment",
+        r == 0 && ds.shm_segsz == 4096 && ds.shm_nattch == 1,
+        fmt_i32(r),
+    );
+
+    let pid = sys_fork();
+    if pid == 0 {
+        // attach a second time in the child and write through that
+        let child = sys_shmat(id, core::ptr::null(), 0);
+        unsafe { core::slice::from_raw_parts_mut(child as *mut u8, 3) }.copy_from_slice(b"shm");
+        sys_shmdt(child as *const u8);
+        sys_exit(0);
+    }
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+    check("segment shared with child", &mem[..3] == b"shm", "");
+
+    let r = sys_shmctl(id, IPC_RMID, core::ptr::null_mut());
+    check("IPC_RMID while attached", r == 0, fmt_i32(r));
+    check("memory still usable", &mem[..3] == b"shm", "");
+
+    let r = sys_shmdt(addr as *const u8);
+    check("shmdt", r == 0, fmt_i32(r));
+    let r = sys_shmdt(addr as *const u8);
+    check("shmdt twice -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_shmat(id, core::ptr::null(), 0);
+    check("removed segment is gone", r == -22, fmt_i64(r));
+
+    let key = 0x5eed;
+    let id = sys_shmget(key, 8192, IPC_CREAT | IPC_EXCL | 0o600);
+    check("shmget with key", id >= 0, fmt_i32(id));
+    let r = sys_shmget(key, 8192, IPC_CREAT | IPC_EXCL | 0o600);
+    check("IPC_EXCL on existing key -> EEXIST", r == -17, fmt_i32(r));
+    let r = sys_shmget(key, 4096, 0);
+    check("lookup by key", r == id, fmt_i32(r));
+    let r = sys_shmget(key, 16384, 0);
+    check("bigger than the segment -> EINVAL", r == -22, fmt_i32(r));
+    sys_shmctl(id, IPC_RMID, core::ptr::null_mut());
+    let r = sys_shmget(key, 4096, 0);
+    check("key gone after IPC_RMID", r == -2, fmt_i32(r));
+}
+
+fn test_dev_shm() {
+    println!("[/dev/shm]");
+    let fd = sys_open(
+        c"/dev/shm/compositor".as_ptr(),
+        O_RDWR | O_CREAT | O_EXCL,
+        0o600,
+    );
+    check("shm_open style create", fd >= 0, fmt_i32(fd));
+    sys_ftruncate(fd, 4096);
+
+    let a = sys_mmap(
+        core::ptr::null_mut(),
+        4096,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd,
+        0,
+    );
+    check("mmap /dev/shm file", a > 0, fmt_i64(a));
+
+    let fd2 = sys_open(c"/dev/shm/compositor".as_ptr(), O_RDWR, 0);
+    let b = sys_mmap(core::ptr::null_mut(), 4096, PROT_READ, MAP_SHARED, fd2, 0);
+    check("second open maps too", b > 0 && b != a, fmt_i64(b));
+    if a > 0 && b > 0 {
+        unsafe { core::slice::from_raw_parts_mut(a as *mut u8, 5) }.copy_from_slice(b"pixel");
+        let seen = unsafe { core::slice::from_raw_parts(b as *const u8, 5) };
+        check("both mappings share frames", seen == b"pixel", "");
+
+        let mut buf = [0u8; 5];
+        let n = sys_read(fd2, buf.as_mut_ptr(), 5);
+        check(
+            "read() sees mapped stores",
+            n == 5 && &buf == b"pixel",
+            fmt_isize(n),
+        );
+
+        sys_munmap(a as *mut u8, 4096);
+        sys_munmap(b as *mut u8, 4096);
+    }
+
+    let ro = sys_open(c"/dev/shm/compositor".as_ptr(), O_RDONLY, 0);
+    let r = sys_mmap(
+        core::ptr::null_mut(),
+        4096,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        ro,
+        0,
+    );
+    check(
+        "writable shared map of O_RDONLY fd -> EACCES",
+        r == -13,
+        fmt_i64(r),
+    );
+
+    // the object's pages come out of devfs' tmpfs space and go back when it shrinks
+    let before = statfs_of(c"/dev/shm").map_or(0, |st| st.f_bfree);
+    let r = sys_ftruncate(fd, 16 * 4096);
+    let grown = statfs_of(c"/dev/shm").map_or(0, |st| st.f_bfree);
+    check(
+        "growing charges /dev/shm",
+        r == 0 && before - grown == 15,
+        fmt_i32(r),
+    );
+    for _ in 0..8 {
+        sys_ftruncate(fd, 0);
+        sys_ftruncate(fd, 16 * 4096);
+    }
+    sys_ftruncate(fd, 4096);
+    let after = statfs_of(c"/dev/shm").map_or(0, |st| st.f_bfree);
+    check("shrink and regrow doesn't leak", after == before, "");
+    let mut buf = [0xffu8; 5];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), 5, 0);
+    check(
+        "regrown object reads zeroes",
+        n == 5 && buf == [0; 5],
+        fmt_isize(n),
+    );
+
+    sys_close(ro);
+    sys_close(fd);
+    sys_close(fd2);
+    sys_unlink(c"/dev/shm/compositor".as_ptr());
+    let freed = statfs_of(c"/dev/shm").map_or(0, |st| st.f_bfree);
+    check("unlink gives it all back", freed == before + 1, "");
+}
+
+fn test_mqueue() {
+    println!("[posix message queues]");
+    sys_mq_unlink(c"/chronos_mq".as_ptr());
+    let attr = MqAttr {
+        mq_flags: 0,
+        mq_maxmsg: 4,
+        mq_msgsize: 64,
+        mq_curmsgs: 0,
+        _reserved: [0; 4],
+    };
+    let mq = sys_mq_open(
+        c"/chronos_mq".as_ptr(),
+        O_RDWR | O_CREAT | O_EXCL,
+        0o600,
+        &attr,
+    );
+    check("mq_open create", mq >= 0, fmt_i32(mq));
+    let r = sys_mq_open(
+        c"/chronos_mq".as_ptr(),
+        O_RDWR | O_CREAT | O_EXCL,
+        0o600,
+        &attr,
+    );
+    check("O_EXCL on existing -> EEXIST", r == -17, fmt_i32(r));
+    // 64k messages of 1m each is more than all queues together get
+    let huge = MqAttr {
+        mq_maxmsg: 65536,
+        mq_msgsize: 1 << 20,
+        ..attr
+    };
+    let r = sys_mq_open(
+        c"/chronos_mq_huge".as_ptr(),
+        O_RDWR | O_CREAT | O_EXCL,
+        0o600,
+        &huge,
+    );
+    check(
+        "a queue over the byte limit -> EMFILE",
+        r == -24,
+        fmt_i32(r),
+    );
+
+    for (msg, prio) in [(&b"low"[..], 1), (b"high", 5), (b"mid", 3), (b"high2", 5)] {
+        sys_mq_timedsend(mq, msg.as_ptr(), msg.len(), prio, core::ptr::null());
+    }
+    let mut got = MqAttr {
+        mq_flags: 0,
+        mq_maxmsg: 0,
+        mq_msgsize: 0,
+        mq_curmsgs: 0,
+        _reserved: [0; 4],
+    };
+    let r = sys_mq_getsetattr(mq, core::ptr::null(), &mut got);
+    check(
+        "getattr reports 4 of 4 queued",
+        r == 0 && got.mq_curmsgs == 4 && got.mq_maxmsg == 4 && got.mq_msgsize == 64,
+        fmt_i32(r),
+    );
+
+    let mut buf = [0u8; 64];
+    let mut prio = 0u32;
+    let mut order = [0u32; 4];
+    let mut ok = true;
+    for slot in order.iter_mut() {
+        let n = sys_mq_timedreceive(mq, buf.as_mut_ptr(), 64, &mut prio, core::ptr::null());
+        ok &= n > 0;
+        *slot = prio;
+    }
+    check("highest priority first", ok && order == [5, 5, 3, 1], "");
+
+    let n = sys_mq_timedreceive(mq, buf.as_mut_ptr(), 8, &mut prio, core::ptr::null());
+    check("buffer below msgsize -> EMSGSIZE", n == -90, fmt_isize(n));
+    let r = sys_mq_timedsend(mq, buf.as_ptr(), 65, 0, core::ptr::null());
+    check("message above msgsize -> EMSGSIZE", r == -90, fmt_i32(r));
+
+    // the epoch has long passed, so this times out right away
+    let past = Timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let n = sys_mq_timedreceive(mq, buf.as_mut_ptr(), 64, &mut prio, &past);
+    check("expired timeout -> ETIMEDOUT", n == -110, fmt_isize(n));
+
+    let nonblock = MqAttr {
+        mq_flags: O_NONBLOCK as i64,
+        ..got
+    };
+    sys_mq_getsetattr(mq, &nonblock, core::ptr::null_mut());
+    let n = sys_mq_timedreceive(mq, buf.as_mut_ptr(), 64, &mut prio, core::ptr::null());
+    check("empty nonblocking queue -> EAGAIN", n == -11, fmt_isize(n));
+    sys_mq_getsetattr(mq, &got, core::ptr::null_mut());
+
+    // a receiver blocked on an empty queue gets the child's message
+    let pid = sys_fork();
+    if pid == 0 {
+        let child = sys_mq_open(c"/chronos_mq".as_ptr(), O_WRONLY, 0, core::ptr::null());
+        sys_mq_timedsend(child, b"ping".as_ptr(), 4, 2, core::ptr::null());
+        sys_exit(0);
+    }
+    let n = sys_mq_timedreceive(mq, buf.as_mut_ptr(), 64, &mut prio, core::ptr::null());
+    check(
+        "blocking receive",
+        n == 4 && &buf[..4] == b"ping" && prio == 2,
+        fmt_isize(n),
+    );
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+
+    // notification through a signal, read back with signalfd
+    let mask: u64 = 1 << (SIGUSR1 - 1);
+    let sfd = sys_signalfd4(-1, &mask, 8, EFD_NONBLOCK);
+    let sev = SigEvent {
+        sigev_value: 0x1234,
+        sigev_signo: SIGUSR1,
+        sigev_notify: SIGEV_SIGNAL,
+        _pad: [0; 12],
+    };
+    let r = sys_mq_notify(mq, &sev);
+    check("mq_notify", r == 0, fmt_i32(r));
+    let r = sys_mq_notify(mq, &sev);
+    check("second registration -> EBUSY", r == -16, fmt_i32(r));
+    sys_mq_timedsend(mq, b"wake".as_ptr(), 4, 0, core::ptr::null());
+
+    let mut info = [0u8; SIGINFO_SIZE];
+    let n = sys_read(sfd, info.as_mut_ptr(), SIGINFO_SIZE);
+    let signo = u32::from_ne_bytes(info[0..4].try_into().unwrap());
+    let code = i32::from_ne_bytes(info[8..12].try_into().unwrap());
+    let value = i32::from_ne_bytes(info[44..48].try_into().unwrap());
+    check(
+        "notified with SI_MESGQ",
+        n == SIGINFO_SIZE as isize && signo == SIGUSR1 as u32 && code == -3 && value == 0x1234,
+        fmt_isize(n),
+    );
+    let r = sys_mq_notify(mq, &sev);
+    check("registration is one-shot", r == 0, fmt_i32(r));
+    sys_mq_notify(mq, core::ptr::null());
+    sys_close(sfd);
+
+    let r = sys_mq_unlink(c"/chronos_mq".as_ptr());
+    check("mq_unlink", r == 0, fmt_i32(r));
+    let n = sys_mq_timedreceive(mq, buf.as_mut_ptr(), 64, &mut prio, core::ptr::null());
+    check("open queue outlives its name", n == 4, fmt_isize(n));
+    let r = sys_mq_open(c"/chronos_mq".as_ptr(), O_RDWR, 0, core::ptr::null());
+    check("unlinked name -> ENOENT", r == -2, fmt_i32(r));
+    let r = sys_mq_open(c"/a/b".as_ptr(), O_RDWR | O_CREAT, 0o600, core::ptr::null());
+    check("slash in name -> EACCES", r == -13, fmt_i32(r));
+    sys_close(mq);
+}
+
+fn test_sysv_sem() {
+    println!("[sysv semaphores]");
+    let id = sys_semget(IPC_PRIVATE, 2, IPC_CREAT | 0o600);
+    check("semget", id >= 0, fmt_i32(id));
+
+    let r = sys_semctl(id, 0, SETVAL, 1);
+    check("SETVAL", r == 0, fmt_i32(r));
+    let r = sys_semctl(id, 0, GETVAL, 0);
+    check("GETVAL", r == 1, fmt_i32(r));
+
+    let down = SemBuf {
+        sem_num: 0,
+        sem_op: -1,
+        sem_flg: IPC_NOWAIT,
+    };
+    let r = sys_semop(id, &down, 1);
+    check("semop down", r == 0, fmt_i32(r));
+    let r = sys_semop(id, &down, 1);
+    check(
+        "down at zero with IPC_NOWAIT -> EAGAIN",
+        r == -11,
+        fmt_i32(r),
+    );
+    let timeout = Timespec {
+        tv_sec: 0,
+        tv_nsec: 1_000_000,
+    };
+    let blocking = SemBuf { sem_flg: 0, ..down };
+    let r = sys_semtimedop(id, &blocking, 1, &timeout);
+    check("semtimedop times out -> EAGAIN", r == -11, fmt_i32(r));
+
+    // all or nothing: the second op can't go through so neither does the first
+    let ops = [
+        SemBuf {
+            sem_num: 1,
+            sem_op: 1,
+            sem_flg: 0,
+        },
+        down,
+    ];
+    let r = sys_semop(id, ops.as_ptr(), 2);
+    let v = sys_semctl(id, 1, GETVAL, 0);
+    check("partial ops aren't applied", r == -11 && v == 0, fmt_i32(r));
+
+    let bad = SemBuf { sem_num: 2, ..down };
+    let r = sys_semop(id, &bad, 1);
+    check("sem_num out of range -> EFBIG", r == -27, fmt_i32(r));
+    let r = sys_semctl(id, 0, SETVAL, 40000);
+    check("SETVAL above SEMVMX -> ERANGE", r == -34, fmt_i32(r));
+
+    // parent sleeps on the semaphore until the child ups it
+    let pid = sys_fork();
+    if pid == 0 {
+        sys_yield();
+        let up = SemBuf {
+            sem_num: 0,
+            sem_op: 1,
+            sem_flg: 0,
+        };
+        sys_semop(id, &up, 1);
+        sys_exit(0);
+    }
+    let r = sys_semop(id, &blocking, 1);
+    check("blocking down woken by child", r == 0, fmt_i32(r));
+    let r = sys_semctl(id, 0, GETPID, 0);
+    check(
+        "GETPID is the last caller",
+        r as u64 == sys_getpid(),
+        fmt_i32(r),
+    );
+    let mut status = 0;
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+
+    // SEM_UNDO is rolled back when the child exits
+    let pid = sys_fork();
+    if pid == 0 {
+        let up = SemBuf {
+            sem_num: 1,
+            sem_op: 3,
+            sem_flg: SEM_UNDO,
+        };
+        sys_semop(id, &up, 1);
+        sys_exit(0);
+    }
+    while sys_waitpid(pid, &mut status, 0) != pid {
+        sys_yield();
+    }
+    let v = sys_semctl(id, 1, GETVAL, 0);
+    check("SEM_UNDO reverted on exit", v == 0, fmt_i32(v));
+
+    let values = [3u16, 4];
+    let r = sys_semctl(id, 0, SETALL, values.as_ptr() as u64);
+    let mut out = [0u16; 2];
+    sys_semctl(id, 0, GETALL, out.as_mut_ptr() as u64);
+    check("SETALL/GETALL", r == 0 && out == values, fmt_i32(r));
+
+    let r = sys_semctl(id, 0, IPC_RMID, 0);
+    check("IPC_RMID", r == 0, fmt_i32(r));
+    let r = sys_semop(id, &down, 1);
+    check("removed set -> EINVAL", r == -22, fmt_i32(r));
+
+    let key = 0x5e3a;
+    let id = sys_semget(key, 1, IPC_CREAT | IPC_EXCL | 0o600);
+    let r = sys_semget(key, 1, IPC_CREAT | IPC_EXCL | 0o600);
+    check(
+        "IPC_EXCL on existing key -> EEXIST",
+        id >= 0 && r == -17,
+        fmt_i32(r),
+    );
+    let r = sys_semget(key, 2, 0);
+    check("more sems than the set -> EINVAL", r == -22, fmt_i32(r));
+    sys_semctl(id, 0, IPC_RMID, 0);
+}
+
+fn stat_dev(path: &core::ffi::CStr) -> u64 {
+    let mut st = core::mem::MaybeUninit::<StatBuf>::uninit();
+    if sys_stat(path.as_ptr(), st.as_mut_ptr()) != 0 {
+        return 0;
+    }
+    unsafe { st.assume_init_ref() }.st_dev
+}
+
+fn test_mount() {
+    println!("[mount]");
+    let null = core::ptr::null();
+    sys_mkdir(c"/tmp/mnt".as_ptr(), 0o755);
+
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/mnt".as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        null,
+    );
+    check("mount tmpfs", r == 0, fmt_i32(r));
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/mnt".as_ptr(),
+        c"nosuchfs".as_ptr(),
+        0,
+        null,
+    );
+    check("unknown type -> ENODEV", r == -19, fmt_i32(r));
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/nothere".as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        null,
+    );
+    check("missing target -> ENOENT", r == -2, fmt_i32(r));
+
+    let fd = sys_open(c"/tmp/mnt/file".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    check("create inside the mount", fd >= 0, fmt_i32(fd));
+    sys_write(fd, b"mounted".as_ptr(), 7);
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/mnt/file".as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        null,
+    );
+    check("mount on a file -> ENOTDIR", r == -20, fmt_i32(r));
+
+    let (tmp_dev, mnt_dev) = (stat_dev(c"/tmp"), stat_dev(c"/tmp/mnt/file"));
+    check(
+        "st_dev differs across mounts",
+        tmp_dev != 0 && mnt_dev != 0 && tmp_dev != mnt_dev,
+        "",
+    );
+
+    let r = sys_rename(c"/tmp/mnt/file".as_ptr(), c"/tmp/moved".as_ptr());
+    check("rename across mounts -> EXDEV", r == -18, fmt_i32(r));
+    let r = sys_rmdir(c"/tmp/mnt".as_ptr());
+    check("rmdir a mountpoint -> EBUSY", r == -16, fmt_i32(r));
+
+    let r = sys_umount2(c"/tmp/mnt".as_ptr(), 0);
+    check("umount with an open file -> EBUSY", r == -16, fmt_i32(r));
+    sys_close(fd);
+
+    // mounts stack, the newest one shadows the others
+    sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/mnt".as_ptr(),
+        c"ramfs".as_ptr(),
+        0,
+        null,
+    );
+    let r = sys_access(c"/tmp/mnt/file".as_ptr(), 0);
+    check("stacked mount hides the one below", r == -2, fmt_i32(r));
+    sys_umount2(c"/tmp/mnt".as_ptr(), 0);
+    let r = sys_access(c"/tmp/mnt/file".as_ptr(), 0);
+    check("and shows it again once gone", r == 0, fmt_i32(r));
+
+    let r = sys_mount(
+        null,
+        c"/tmp/mnt".as_ptr(),
+        null,
+        MS_REMOUNT | MS_RDONLY,
+        null,
+    );
+    check("remount read-only", r == 0, fmt_i32(r));
+    let r = sys_open(c"/tmp/mnt/new".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    check("create on read-only -> EROFS", r == -30, fmt_i32(r));
+    let r = sys_open(c"/tmp/mnt/file".as_ptr(), O_WRONLY, 0);
+    check("open for write on read-only -> EROFS", r == -30, fmt_i32(r));
+    let fd = sys_open(c"/tmp/mnt/file".as_ptr(), O_RDONLY, 0);
+    let mut buf = [0u8; 7];
+    let n = sys_read(fd, buf.as_mut_ptr(), 7);
+    check(
+        "reads still work",
+        n == 7 && &buf == b"mounted",
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let r = sys_umount2(c"/tmp/mnt".as_ptr(), 0);
+    check("umount", r == 0, fmt_i32(r));
+    let r = sys_access(c"/tmp/mnt/file".as_ptr(), 0);
+    check("contents gone with it", r == -2, fmt_i32(r));
+    let r = sys_umount2(c"/tmp/mnt".as_ptr(), 0);
+    check("umount a plain dir -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_umount2(c"/".as_ptr(), 0);
+    check("umount the root -> EBUSY", r == -16, fmt_i32(r));
+    sys_rmdir(c"/tmp/mnt".as_ptr());
+}
+
+fn stat_of(path: &core::ffi::CStr) -> Option<StatBuf> {
+    let mut st = core::mem::MaybeUninit::<StatBuf>::uninit();
+    if sys_stat(path.as_ptr(), st.as_mut_ptr()) != 0 {
+        return None;
+    }
+    Some(unsafe { st.assume_init() })
+}
+
+fn test_link() {
+    println!("[link]");
+    let fd = sys_open(c"/tmp/link_a".as_ptr(), O_RDWR | O_CREAT | O_TRUNC, 0o644);
+    sys_write(fd, b"linked".as_ptr(), 6);
+
+    let r = sys_link(c"/tmp/link_a".as_ptr(), c"/tmp/link_b".as_ptr());
+    check("link", r == 0, fmt_i32(r));
+    match (stat_of(c"/tmp/link_a"), stat_of(c"/tmp/link_b")) {
+        (Some(a), Some(b)) => {
+            check("same st_ino", a.st_ino != 0 && a.st_ino == b.st_ino, "");
+            check("st_nlink == 2", a.st_nlink == 2, fmt_i64(a.st_nlink as i64));
+        }
+        _ => check("stat both names", false, ""),
+    }
+
+    // writes through one name show up through the other
+    sys_write(fd, b"!".as_ptr(), 1);
+    let fd2 = sys_open(c"/tmp/link_b".as_ptr(), O_RDONLY, 0);
+    let mut buf = [0u8; 16];
+    let n = sys_read(fd2, buf.as_mut_ptr(), buf.len());
+    check(
+        "data shared",
+        n == 7 && &buf[..7] == b"linked!",
+        fmt_isize(n),
+    );
+    sys_close(fd2);
+
+    let r = sys_link(c"/tmp/link_a".as_ptr(), c"/tmp/link_b".as_ptr());
+    check("existing name -> EEXIST", r == -17, fmt_i32(r));
+    let r = sys_link(c"/tmp".as_ptr(), c"/tmp/link_dir".as_ptr());
+    check("directory -> EPERM", r == -1, fmt_i32(r));
+    let r = sys_link(c"/tmp/nothere".as_ptr(), c"/tmp/link_c".as_ptr());
+    check("missing source -> ENOENT", r == -2, fmt_i32(r));
+
+    let r = sys_linkat(
+        AT_FDCWD,
+        c"/tmp/link_a".as_ptr(),
+        AT_FDCWD,
+        c"/tmp/link_c".as_ptr(),
+        0,
+    );
+    check("linkat", r == 0, fmt_i32(r));
+    let dir = sys_open(c"/tmp".as_ptr(), O_RDONLY | O_DIRECTORY, 0);
+    let r = sys_linkat(dir, c"link_c".as_ptr(), dir, c"link_d".as_ptr(), 0);
+    check("linkat relative to a dirfd", r == 0, fmt_i32(r));
+    let r = sys_linkat(
+        fd,
+        c"".as_ptr(),
+        AT_FDCWD,
+        c"/tmp/link_e".as_ptr(),
+        AT_EMPTY_PATH,
+    );
+    check("linkat AT_EMPTY_PATH", r == 0, fmt_i32(r));
+    let nlink = stat_of(c"/tmp/link_a").map_or(0, |s| s.st_nlink);
+    check("st_nlink == 5", nlink == 5, fmt_i64(nlink as i64));
+    sys_close(dir);
+
+    for name in [
+        c"/tmp/link_b",
+        c"/tmp/link_c",
+        c"/tmp/link_d",
+        c"/tmp/link_e",
+    ] {
+        sys_unlink(name.as_ptr());
+    }
+    let ino = stat_of(c"/tmp/link_a").map_or(0, |s| s.st_ino);
+    let r = sys_rename(c"/tmp/link_a".as_ptr(), c"/tmp/link_moved".as_ptr());
+    let moved = stat_of(c"/tmp/link_moved");
+    check(
+        "st_ino survives rename",
+        r == 0
+            && moved
+                .as_ref()
+                .is_some_and(|s| s.st_ino == ino && s.st_nlink == 1),
+        fmt_i32(r),
+    );
+
+    // the open descriptor keeps the file around after its last name is gone
+    sys_unlink(c"/tmp/link_moved".as_ptr());
+    let r = sys_access(c"/tmp/link_moved".as_ptr(), 0);
+    check("name gone after unlink", r == -2, fmt_i32(r));
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), 0);
+    check(
+        "still readable while open",
+        n == 7 && &buf[..7] == b"linked!",
+        fmt_isize(n),
+    );
+    let mut st = core::mem::MaybeUninit::<StatBuf>::uninit();
+    let r = sys_fstat(fd, st.as_mut_ptr());
+    let st = unsafe { st.assume_init() };
+    check(
+        "fstat: st_nlink == 0",
+        r == 0 && st.st_nlink == 0,
+        fmt_i64(st.st_nlink as i64),
+    );
+    check("fstat: same st_ino", st.st_ino == ino, "");
+    let r = sys_linkat(
+        fd,
+        c"".as_ptr(),
+        AT_FDCWD,
+        c"/tmp/link_back".as_ptr(),
+        AT_EMPTY_PATH,
+    );
+    check("unlinked file can't be relinked", r == -2, fmt_i32(r));
+    sys_close(fd);
+
+    // directories count their subdirectories' ".."
+    sys_mkdir(c"/tmp/link_dir".as_ptr(), 0o755);
+    sys_mkdir(c"/tmp/link_dir/sub".as_ptr(), 0o755);
+    let nlink = stat_of(c"/tmp/link_dir").map_or(0, |s| s.st_nlink);
+    check("dir st_nlink == 3", nlink == 3, fmt_i64(nlink as i64));
+    let r = sys_rename(c"/tmp/link_dir".as_ptr(), c"/tmp/link_dir/sub/x".as_ptr());
+    check("rename into itself -> EINVAL", r == -22, fmt_i32(r));
+    sys_rmdir(c"/tmp/link_dir/sub".as_ptr());
+    sys_rmdir(c"/tmp/link_dir".as_ptr());
+}
+
+fn lstat_of(path: &core::ffi::CStr) -> Option<StatBuf> {
+    let mut st = core::mem::MaybeUninit::<StatBuf>::uninit();
+    if sys_lstat(path.as_ptr(), st.as_mut_ptr()) != 0 {
+        return None;
+    }
+    Some(unsafe { st.assume_init() })
+}
+
+fn test_symlink() {
+    println!("[symlink]");
+    sys_mkdir(c"/tmp/sl".as_ptr(), 0o755);
+    let fd = sys_open(
+        c"/tmp/sl/target".as_ptr(),
+        O_RDWR | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    sys_write(fd, b"pointed at".as_ptr(), 10);
+    sys_close(fd);
+
+    let r = sys_symlink(c"target".as_ptr(), c"/tmp/sl/rel".as_ptr());
+    check("symlink", r == 0, fmt_i32(r));
+    let r = sys_symlink(c"/tmp/sl".as_ptr(), c"/tmp/sl_abs".as_ptr());
+    check("symlink to a directory", r == 0, fmt_i32(r));
+    let r = sys_symlink(c"whatever".as_ptr(), c"/tmp/sl/rel".as_ptr());
+    check("existing name -> EEXIST", r == -17, fmt_i32(r));
+
+    let mut buf = [0u8; 32];
+    let n = sys_readlink(c"/tmp/sl/rel".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check("readlink", n == 6 && &buf[..6] == b"target", fmt_isize(n));
+    let n = sys_readlink(c"/tmp/sl/rel".as_ptr(), buf.as_mut_ptr(), 3);
+    check("readlink truncates", n == 3, fmt_isize(n));
+    let n = sys_readlink(c"/tmp/sl/target".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check("readlink on a file -> EINVAL", n == -22, fmt_isize(n));
+    let dir = sys_open(c"/tmp/sl".as_ptr(), O_RDONLY | O_DIRECTORY, 0);
+    let n = sys_readlinkat(dir, c"rel".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check("readlinkat relative to a dirfd", n == 6, fmt_isize(n));
+    let r = sys_symlinkat(c"target".as_ptr(), dir, c"rel2".as_ptr());
+    check("symlinkat", r == 0, fmt_i32(r));
+    sys_close(dir);
+
+    // relative targets resolve from the link's directory, not the cwd
+    let fd = sys_open(c"/tmp/sl/rel".as_ptr(), O_RDONLY, 0);
+    let n = sys_read(fd, buf.as_mut_ptr(), buf.len());
+    check(
+        "open follows the link",
+        n == 10 && &buf[..10] == b"pointed at",
+        fmt_isize(n),
+    );
+    sys_close(fd);
+    let r = sys_access(c"/tmp/sl_abs/rel2".as_ptr(), 0);
+    check("links in the middle of a path", r == 0, fmt_i32(r));
+    let r = sys_access(c"/tmp/sl_abs/../sl/target".as_ptr(), 0);
+    check("\"..\" after a link", r == 0, fmt_i32(r));
+
+    match (stat_of(c"/tmp/sl/rel"), lstat_of(c"/tmp/sl/rel")) {
+        (Some(st), Some(lst)) => {
+            check("stat sees the file", st.st_mode & 0o170000 == 0o100000, "");
+            check(
+                "lstat sees the link",
+                lst.st_mode & 0o170000 == 0o120000,
+                "",
+            );
+            check(
+                "lstat size is the target length",
+                lst.st_size == 6,
+                fmt_i64(lst.st_size),
+            );
+            check("different inodes", st.st_ino != lst.st_ino, "");
+        }
+        _ => check("stat and lstat", false, ""),
+    }
+
+    let r = sys_open(c"/tmp/sl/rel".as_ptr(), O_RDONLY | O_NOFOLLOW, 0);
+    check("O_NOFOLLOW -> ELOOP", r == -40, fmt_i32(r));
+    let r = sys_open(c"/tmp/sl/rel".as_ptr(), O_RDWR | O_CREAT | O_EXCL, 0o644);
+    check("O_EXCL doesn't follow", r == -17, fmt_i32(r));
+
+    sys_symlink(c"loop_b".as_ptr(), c"/tmp/sl/loop_a".as_ptr());
+    sys_symlink(c"loop_a".as_ptr(), c"/tmp/sl/loop_b".as_ptr());
+    let r = sys_open(c"/tmp/sl/loop_a".as_ptr(), O_RDONLY, 0);
+    check("symlink loop -> ELOOP", r == -40, fmt_i32(r));
+    sys_symlink(c"nothing".as_ptr(), c"/tmp/sl/dangling".as_ptr());
+    let r = sys_access(c"/tmp/sl/dangling".as_ptr(), 0);
+    check("dangling link -> ENOENT", r == -2, fmt_i32(r));
+    check(
+        "but lstat still works",
+        lstat_of(c"/tmp/sl/dangling").is_some(),
+        "",
+    );
+
+    // unlink and rename act on the link, not the target
+    let r = sys_rename(c"/tmp/sl/rel2".as_ptr(), c"/tmp/sl/rel3".as_ptr());
+    let n = sys_readlink(c"/tmp/sl/rel3".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check("rename moves the link", r == 0 && n == 6, fmt_i32(r));
+    let r = sys_unlink(c"/tmp/sl/rel".as_ptr());
+    check("unlink the link", r == 0, fmt_i32(r));
+    let r = sys_access(c"/tmp/sl/target".as_ptr(), 0);
+    check("target survives", r == 0, fmt_i32(r));
+    let r = sys_rmdir(c"/tmp/sl_abs".as_ptr());
+    check("rmdir on a link -> ENOTDIR", r == -20, fmt_i32(r));
+
+    // the build links /sbin -> bin in the initramfs tar
+    let n = sys_readlink(c"/sbin".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "tar symlink preserved",
+        n == 3 && &buf[..3] == b"bin",
+        fmt_isize(n),
+    );
+    let r = sys_access(c"/sbin/initramfs.elf".as_ptr(), 0);
+    check("and followed", r == 0, fmt_i32(r));
+
+    for name in [
+        c"/tmp/sl/rel3",
+        c"/tmp/sl/loop_a",
+        c"/tmp/sl/loop_b",
+        c"/tmp/sl/dangling",
+        c"/tmp/sl/target",
+        c"/tmp/sl_abs",
+    ] {
+        sys_unlink(name.as_ptr());
+    }
+    sys_rmdir(c"/tmp/sl".as_ptr());
+}
+
+fn test_devfs() {
+    println!("[devfs]");
+    let mut buf = [0xaau8; 16];
+
+    let fd = sys_open(c"/dev/null".as_ptr(), O_RDWR, 0);
+    check("open /dev/null", fd >= 0, fmt_i32(fd));
+    let n = sys_write(fd, b"gone".as_ptr(), 4);
+    check("/dev/null takes writes", n == 4, fmt_isize(n));
+    let n = sys_read(fd, buf.as_mut_ptr(), buf.len());
+    check("/dev/null reads eof", n == 0, fmt_isize(n));
+    sys_close(fd);
+
+    let fd = sys_open(c"/dev/zero".as_ptr(), O_RDONLY, 0);
+    let n = sys_read(fd, buf.as_mut_ptr(), buf.len());
+    check(
+        "/dev/zero reads zeroes",
+        n == 16 && buf.iter().all(|&b| b == 0),
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let fd = sys_open(c"/dev/full".as_ptr(), O_WRONLY, 0);
+    let n = sys_write(fd, b"x".as_ptr(), 1);
+    check("/dev/full -> ENOSPC", n == -28, fmt_isize(n));
+    sys_close(fd);
+
+    let fd = sys_open(c"/dev/urandom".as_ptr(), O_RDONLY, 0);
+    let n = sys_read(fd, buf.as_mut_ptr(), buf.len());
+    check(
+        "/dev/urandom reads something",
+        n == 16 && buf.iter().any(|&b| b != 0),
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let fd = sys_open(c"/dev/console".as_ptr(), O_WRONLY, 0);
+    check("open /dev/console", fd >= 0, fmt_i32(fd));
+    sys_close(fd);
+    let fd = sys_open(c"/dev/ttyS0".as_ptr(), O_WRONLY, 0);
+    check("open /dev/ttyS0", fd >= 0, fmt_i32(fd));
+    sys_close(fd);
+
+    // makedev(1, 3)
+    match stat_of(c"/dev/null") {
+        Some(st) => check(
+            "/dev/null is char device 1:3",
+            st.st_mode & 0o170000 == 0o020000 && st.st_rdev == 0x103,
+            "",
+        ),
+        None => check("stat /dev/null", false, ""),
+    }
+
+    let r = sys_mknod(c"/tmp/mynull".as_ptr(), 0o020666, 0x103);
+    check("mknod a char device", r == 0, fmt_i32(r));
+    let fd = sys_open(c"/tmp/mynull".as_ptr(), O_RDWR, 0);
+    let n = sys_write(fd, b"gone".as_ptr(), 4);
+    check("it behaves like /dev/null", n == 4, fmt_isize(n));
+    sys_close(fd);
+    let r = sys_mknod(c"/tmp/mynull".as_ptr(), 0o020666, 0x103);
+    check(
+        "mknod over an existing name -> EEXIST",
+        r == -17,
+        fmt_i32(r),
+    );
+    sys_unlink(c"/tmp/mynull".as_ptr());
+
+    // makedev(1, 250), nothing registered
+    sys_mknod(c"/tmp/nodev".as_ptr(), 0o020666, 0xfa | (1 << 8));
+    let fd = sys_open(c"/tmp/nodev".as_ptr(), O_RDWR, 0);
+    check("no driver for the numbers -> ENXIO", fd == -6, fmt_i32(fd));
+    sys_unlink(c"/tmp/nodev".as_ptr());
+
+    let r = sys_mknodat(AT_FDCWD, c"/tmp/plain".as_ptr(), 0o100644, 0);
+    check("mknodat a regular file", r == 0, fmt_i32(r));
+    match stat_of(c"/tmp/plain") {
+        Some(st) => check("it's a file", st.st_mode & 0o170000 == 0o100000, ""),
+        None => check("stat the file", false, ""),
+    }
+    sys_unlink(c"/tmp/plain".as_ptr());
+    let r = sys_mknod(c"/tmp/fifo".as_ptr(), 0o010644, 0);
+    check("no fifos -> EPERM", r == -1, fmt_i32(r));
+}
+
+// formats into a stack buffer, for building paths like /proc/<pid>/status
+struct BufWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+        if end >= self.buf.len() {
+            return Err(core::fmt::Error);
+        }
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+// nul terminated, for passing straight to a syscall
+fn cpath<'a>(buf: &'a mut [u8], args: core::fmt::Arguments) -> &'a core::ffi::CStr {
+    let mut w = BufWriter { buf, len: 0 };
+    w.write_fmt(args).ok();
+    let len = w.len;
+    buf[len] = 0;
+    core::ffi::CStr::from_bytes_with_nul(&buf[..=len]).unwrap()
+}
+
+// the whole file, proc files report size 0 so read until eof
+fn read_whole(path: &core::ffi::CStr, buf: &mut [u8]) -> isize {
+    let fd = sys_open(path.as_ptr(), O_RDONLY, 0);
+    if fd < 0 {
+        return fd as isize;
+    }
+    let mut total = 0;
+    while total < buf.len() {
+        let n = sys_read(fd, buf[total..].as_mut_ptr(), buf.len() - total);
+        if n <= 0 {
+            break;
+        }
+        total += n as usize;
+    }
+    sys_close(fd);
+    total as isize
+}
+
+fn text(buf: &[u8], n: isize) -> &str {
+    core::str::from_utf8(&buf[..n.max(0) as usize]).unwrap_or("")
+}
+
+fn test_procfs() {
+    println!("[procfs]");
+    let mut buf = [0u8; 2048];
+    let mut path = [0u8; 64];
+    let pid = sys_getpid();
+
+    let n = sys_readlink(c"/proc/self".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "/proc/self links to our pid",
+        text(&buf, n).parse::<u64>() == Ok(pid),
+        fmt_isize(n),
+    );
+
+    let n = read_whole(c"/proc/self/status", &mut buf);
+    check(
+        "/proc/self/status has our pid",
+        text(&buf, n)
+            .lines()
+            .any(|l| l.strip_prefix("Pid:\t").and_then(|p| p.parse().ok()) == Some(pid)),
+        fmt_isize(n),
+    );
+
+    let n = read_whole(cpath(&mut path, format_args!("/proc/{pid}/stat")), &mut buf);
+    let stat = text(&buf, n);
+    check(
+        "/proc/<pid>/stat starts with the pid and comm",
+        stat.split(' ').next().and_then(|p| p.parse().ok()) == Some(pid)
+            && stat.contains(" (")
+            && stat.split(' ').count() >= 24,
+        fmt_isize(n),
+    );
+
+    let n = read_whole(c"/proc/self/cmdline", &mut buf);
+    check(
+        "/proc/self/cmdline is nul terminated",
+        n > 0 && buf[n as usize - 1] == 0,
+        fmt_isize(n),
+    );
+
+    let n = read_whole(c"/proc/meminfo", &mut buf);
+    let total = text(&buf, n)
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))
+        .and_then(|v| v.trim().trim_end_matches(" kB").parse::<u64>().ok());
+    check(
+        "/proc/meminfo has MemTotal",
+        total.is_some_and(|t| t > 0),
+        fmt_isize(n),
+    );
+    check(
+        "/proc/meminfo has MemFree",
+        text(&buf, n).contains("MemFree:"),
+        "",
+    );
+
+    let n = read_whole(c"/proc/cpuinfo", &mut buf);
+    check(
+        "/proc/cpuinfo has a model name",
+        text(&buf, n).contains("model name"),
+        fmt_isize(n),
+    );
+
+    let n = read_whole(c"/proc/uptime", &mut buf);
+    check(
+        "/proc/uptime is two numbers",
+        text(&buf, n).trim_end().split(' ').count() == 2
+            && text(&buf, n)
+                .as_bytes()
+                .first()
+                .is_some_and(u8::is_ascii_digit),
+        fmt_isize(n),
+    );
+
+    let n = read_whole(c"/proc/mounts", &mut buf);
+    let mounts = text(&buf, n);
+    check(
+        "/proc/mounts lists proc and devfs",
+        mounts.contains(" /proc proc ") && mounts.contains(" /dev devfs "),
+        fmt_isize(n),
+    );
+
+    let n = sys_readlink(c"/proc/self/cwd".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "/proc/self/cwd is absolute",
+        n > 0 && buf[0] == b'/',
+        fmt_isize(n),
+    );
+
+    sys_mkdir(c"/tmp/x".as_ptr(), 0o755);
+    let fd = sys_open(c"/tmp/x/procfd".as_ptr(), O_CREAT | O_RDWR, 0o644);
+    let n = sys_readlink(
+        cpath(&mut path, format_args!("/proc/self/fd/{fd}")).as_ptr(),
+        buf.as_mut_ptr(),
+        buf.len(),
+    );
+    check(
+        "/proc/self/fd/<fd> points at the file",
+        text(&buf, n) == "/tmp/x/procfd",
+        fmt_isize(n),
+    );
+
+    let dir = sys_open(c"/proc/self/fd".as_ptr(), O_RDONLY | O_DIRECTORY, 0);
+    let n = sys_getdents64(dir, buf.as_mut_ptr(), buf.len());
+    let mut count = 0;
+    let mut offset = 0usize;
+    while offset < n.max(0) as usize {
+        let entry = unsafe { &*(buf.as_ptr().add(offset) as *const LinuxDirent64) };
+        count += 1;
+        offset += entry.d_reclen as usize;
+    }
+    check("/proc/self/fd lists open fds", count >= 2, fmt_isize(n));
+    sys_close(dir);
+    sys_close(fd);
+    sys_unlink(c"/tmp/x/procfd".as_ptr());
+
+    let r = sys_open(c"/proc/self/nope".as_ptr(), O_RDONLY, 0);
+    check("missing proc entry -> ENOENT", r == -2, fmt_i32(r));
+    let r = sys_open(c"/proc/999999/status".as_ptr(), O_RDONLY, 0);
+    check("missing pid -> ENOENT", r == -2, fmt_i32(r));
+    let r = sys_mkdir(c"/proc/self/new".as_ptr(), 0o755);
+    check("can't mkdir in /proc", r < 0, fmt_i32(r));
+}
+
+fn statfs_of(path: &core::ffi::CStr) -> Option<StatFs> {
+    let mut st = core::mem::MaybeUninit::<StatFs>::uninit();
+    if sys_statfs(path.as_ptr(), st.as_mut_ptr()) != 0 {
+        return None;
+    }
+    Some(unsafe { st.assume_init() })
+}
+
+fn test_tmpfs() {
+    println!("[tmpfs]");
+    sys_mkdir(c"/tmp/small".as_ptr(), 0o755);
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/small".as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        c"size=lots".as_ptr(),
+    );
+    check("bad size -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/small".as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        c"size=16k".as_ptr(),
+    );
+    check("mount with size=16k", r == 0, fmt_i32(r));
+
+    match statfs_of(c"/tmp/small") {
+        Some(st) => {
+            check("statfs f_type is tmpfs", st.f_type == 0x0102_1994, "");
+            check(
+                "statfs 4 blocks of 4k",
+                st.f_blocks == 4 && st.f_bsize == 4096,
+                "",
+            );
+            check("statfs all free", st.f_bfree == 4 && st.f_bavail == 4, "");
+        }
+        None => check("statfs", false, "failed"),
+    }
+
+    // 16k fits, the next byte doesn't
+    let fd = sys_open(c"/tmp/small/big".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    let page = [0x5au8; 4096];
+    let mut written = 0;
+    for _ in 0..4 {
+        written += sys_write(fd, page.as_ptr(), page.len());
+    }
+    check("fill the mount", written == 16384, fmt_isize(written));
+    let n = sys_write(fd, page.as_ptr(), 1);
+    check("write past the limit -> ENOSPC", n == -28, fmt_isize(n));
+    let mut st = core::mem::MaybeUninit::<StatFs>::uninit();
+    let r = sys_fstatfs(fd, st.as_mut_ptr());
+    let st = unsafe { st.assume_init() };
+    check(
+        "fstatfs sees it full",
+        r == 0 && st.f_bfree == 0,
+        fmt_i32(r),
+    );
+    let r = sys_ftruncate(fd, 4096);
+    check("truncate", r == 0, fmt_i32(r));
+    let free = statfs_of(c"/tmp/small").map_or(0, |st| st.f_bfree);
+    check("truncate gives pages back", free == 3, "");
+    sys_close(fd);
+    sys_unlink(c"/tmp/small/big".as_ptr());
+    let free = statfs_of(c"/tmp/small").map_or(0, |st| st.f_bfree);
+    check("unlink gives the rest back", free == 4, "");
+
+    // a byte at 1m is one page, the rest is a hole
+    let fd = sys_open(c"/tmp/small/sparse".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    let n = sys_pwrite64(fd, b"x".as_ptr(), 1, 1 << 20);
+    check("write far past the end", n == 1, fmt_isize(n));
+    match stat_of(c"/tmp/small/sparse") {
+        Some(st) => {
+            check("sparse size", st.st_size == (1 << 20) + 1, "");
+            check("sparse blocks", st.st_blocks == 8, "");
+        }
+        None => check("stat sparse", false, "failed"),
+    }
+    let mut buf = [0xffu8; 16];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), 4096);
+    check(
+        "hole reads as zeroes",
+        n == 16 && buf.iter().all(|&b| b == 0),
+        "",
+    );
+    sys_close(fd);
+
+    // the root is a tmpfs too, a huge file there is one big hole
+    check(
+        "/tmp is on a tmpfs",
+        statfs_of(c"/tmp").is_some_and(|st| st.f_type == 0x0102_1994 && st.f_blocks > 0),
+        "",
+    );
+    let fd = sys_open(c"/tmp/huge".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    let r = sys_ftruncate(fd, 1 << 40);
+    let n = sys_pwrite64(fd, b"x".as_ptr(), 1, 1 << 41);
+    check(
+        "a terabyte file in /tmp",
+        r == 0 && n == 1 && stat_of(c"/tmp/huge").is_some_and(|st| st.st_blocks == 8),
+        fmt_i32(r),
+    );
+    sys_close(fd);
+    sys_unlink(c"/tmp/huge".as_ptr());
+
+    // timestamps
+    let times = [
+        Timespec {
+            tv_sec: 1000,
+            tv_nsec: 0,
+        },
+        Timespec {
+            tv_sec: 2000,
+            tv_nsec: 0,
+        },
+    ];
+    let r = sys_utimensat(AT_FDCWD, c"/tmp/small/sparse".as_ptr(), times.as_ptr(), 0);
+    check("utimensat", r == 0, fmt_i32(r));
+    match stat_of(c"/tmp/small/sparse") {
+        Some(st) => {
+            check("atime set", st.st_atime == 1000, "");
+            check("mtime set", st.st_mtime == 2000, "");
+            check("ctime moved to now", st.st_ctime > 2000, "");
+        }
+        None => check("stat after utimensat", false, "failed"),
+    }
+    let omit = [
+        Timespec {
+            tv_sec: 0,
+            tv_nsec: (1 << 30) - 2,
+        },
+        Timespec {
+            tv_sec: 3000,
+            tv_nsec: 0,
+        },
+    ];
+    let fd = sys_open(c"/tmp/small/sparse".as_ptr(), O_RDONLY, 0);
+    let r = sys_utimensat(fd, core::ptr::null(), omit.as_ptr(), 0);
+    check("futimens with UTIME_OMIT", r == 0, fmt_i32(r));
+    sys_close(fd);
+    match stat_of(c"/tmp/small/sparse") {
+        Some(st) => check(
+            "omit keeps atime",
+            st.st_atime == 1000 && st.st_mtime == 3000,
+            "",
+        ),
+        None => check("stat after futimens", false, "failed"),
+    }
+    let bad = [
+        Timespec {
+            tv_sec: 0,
+            tv_nsec: 1_000_000_000,
+        },
+        Timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        },
+    ];
+    let r = sys_utimensat(AT_FDCWD, c"/tmp/small/sparse".as_ptr(), bad.as_ptr(), 0);
+    check("bad tv_nsec -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_utimensat(
+        AT_FDCWD,
+        c"/tmp/small/sparse".as_ptr(),
+        core::ptr::null(),
+        0,
+    );
+    let now = stat_of(c"/tmp/small/sparse").map_or(0, |st| st.st_mtime);
+    check("null times is now", r == 0 && now > 3000, fmt_i32(r));
+
+    sys_unlink(c"/tmp/small/sparse".as_ptr());
+    sys_umount2(c"/tmp/small".as_ptr(), 0);
+
+    for options in [
+        c"size=99999999999g",
+        c"size=999999999999999999%",
+        c"nr_blocks=18446744073709551615",
+    ] {
+        let r = sys_mount(
+            c"none".as_ptr(),
+            c"/tmp/small".as_ptr(),
+            c"tmpfs".as_ptr(),
+            0,
+            options.as_ptr(),
+        );
+        check("overflowing size -> EINVAL", r == -22, fmt_i32(r));
+    }
+
+    // entries and xattrs come out of nr_inodes, not just file pages
+    let r = sys_mount(
+        c"none".as_ptr(),
+        c"/tmp/small".as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        c"size=16k,nr_inodes=2".as_ptr(),
+    );
+    check("mount with nr_inodes=2", r == 0, fmt_i32(r));
+    let fd = sys_open(c"/tmp/small/a".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    check("first file fits", fd >= 0, fmt_i32(fd));
+    sys_close(fd);
+    let r = sys_open(c"/tmp/small/b".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    check("second file -> ENOSPC", r == -28, fmt_i32(r));
+    let r = sys_mkdir(c"/tmp/small/d".as_ptr(), 0o755);
+    check("mkdir -> ENOSPC", r == -28, fmt_i32(r));
+    let value = [0x61u8; 1500];
+    let r = sys_setxattr(
+        c"/tmp/small/a".as_ptr(),
+        c"user.big".as_ptr(),
+        value.as_ptr(),
+        value.len(),
+        0,
+    );
+    check("big xattr -> ENOSPC", r == -28, fmt_i32(r));
+    sys_unlink(c"/tmp/small/a".as_ptr());
+    let fd = sys_open(c"/tmp/small/b".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    check("unlink gives the entry back", fd >= 0, fmt_i32(fd));
+    sys_close(fd);
+    sys_unlink(c"/tmp/small/b".as_ptr());
+    sys_umount2(c"/tmp/small".as_ptr(), 0);
+    sys_rmdir(c"/tmp/small".as_ptr());
+}
+
+// user::rw-, group::r-- and other::r--, the way setfacl hands it to the kernel
+const ACL: [u8; 28] = [
+    2, 0, 0, 0, 1, 0, 6, 0, 0xff, 0xff, 0xff, 0xff, 4, 0, 4, 0, 0xff, 0xff, 0xff, 0xff, 0x20, 0, 4,
+    0, 0xff, 0xff, 0xff, 0xff,
+];
+
+fn xattr_of(path: &core::ffi::CStr, name: &core::ffi::CStr, buf: &mut [u8]) -> isize {
+    sys_getxattr(path.as_ptr(), name.as_ptr(), buf.as_mut_ptr(), buf.len())
+}
+
+fn test_xattr() {
+    println!("[xattr]");
+    let file = c"/tmp/xattr";
+    let fd = sys_open(file.as_ptr(), O_RDWR | O_CREAT | O_TRUNC, 0o644);
+    let mut buf = [0u8; 256];
+
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"user.mime_type".as_ptr(),
+        b"text/plain".as_ptr(),
+        10,
+        0,
+    );
+    check("setxattr", r == 0, fmt_i32(r));
+    let n = sys_getxattr(
+        file.as_ptr(),
+        c"user.mime_type".as_ptr(),
+        buf.as_mut_ptr(),
+        0,
+    );
+    check("a size of 0 asks for the length", n == 10, fmt_isize(n));
+    let n = xattr_of(file, c"user.mime_type", &mut buf);
+    check(
+        "getxattr",
+        n == 10 && &buf[..10] == b"text/plain",
+        fmt_isize(n),
+    );
+    let n = xattr_of(file, c"user.mime_type", &mut buf[..4]);
+    check("a short buffer -> ERANGE", n == -34, fmt_isize(n));
+    let n = xattr_of(file, c"user.missing", &mut buf);
+    check("a missing one -> ENODATA", n == -61, fmt_isize(n));
+
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"user.mime_type".as_ptr(),
+        b"x".as_ptr(),
+        1,
+        XATTR_CREATE,
+    );
+    check("XATTR_CREATE on one there -> EEXIST", r == -17, fmt_i32(r));
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"user.missing".as_ptr(),
+        b"x".as_ptr(),
+        1,
+        XATTR_REPLACE,
+    );
+    check("XATTR_REPLACE on none -> ENODATA", r == -61, fmt_i32(r));
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"user.mime_type".as_ptr(),
+        b"text/x-rust".as_ptr(),
+        11,
+        XATTR_REPLACE,
+    );
+    let n = xattr_of(file, c"user.mime_type", &mut buf);
+    check(
+        "XATTR_REPLACE replaces it",
+        r == 0 && text(&buf, n) == "text/x-rust",
+        fmt_i32(r),
+    );
+
+    // file capabilities, what setcap writes for cap_net_raw+ep
+    let cap = [
+        0, 0, 0, 2, 0, 0x20, 0, 0, 0, 0x20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let r = sys_fsetxattr(
+        fd,
+        c"security.capability".as_ptr(),
+        cap.as_ptr(),
+        cap.len(),
+        0,
+    );
+    check("fsetxattr", r == 0, fmt_i32(r));
+    let n = sys_fgetxattr(
+        fd,
+        c"security.capability".as_ptr(),
+        buf.as_mut_ptr(),
+        buf.len(),
+    );
+    check(
+        "fgetxattr",
+        n == cap.len() as isize && buf[..cap.len()] == cap,
+        fmt_isize(n),
+    );
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"trusted.empty".as_ptr(),
+        core::ptr::null(),
+        0,
+        0,
+    );
+    let n = xattr_of(file, c"trusted.empty", &mut buf);
+    check("an empty value", r == 0 && n == 0, fmt_isize(n));
+
+    let n = sys_listxattr(file.as_ptr(), buf.as_mut_ptr(), 0);
+    let names = b"security.capability\0trusted.empty\0user.mime_type\0";
+    check(
+        "listxattr with a size of 0",
+        n == names.len() as isize,
+        fmt_isize(n),
+    );
+    let n = sys_flistxattr(fd, buf.as_mut_ptr(), buf.len());
+    check(
+        "flistxattr",
+        n == names.len() as isize && &buf[..names.len()] == names,
+        fmt_isize(n),
+    );
+    let n = sys_listxattr(file.as_ptr(), buf.as_mut_ptr(), 8);
+    check("into a short buffer -> ERANGE", n == -34, fmt_isize(n));
+
+    let r = sys_setxattr(file.as_ptr(), c"foo.bar".as_ptr(), b"x".as_ptr(), 1, 0);
+    check("an unknown namespace -> EOPNOTSUPP", r == -95, fmt_i32(r));
+    let r = sys_setxattr(file.as_ptr(), c"user.".as_ptr(), b"x".as_ptr(), 1, 0);
+    check("just the prefix -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_setxattr(file.as_ptr(), c"user.big".as_ptr(), buf.as_ptr(), 65537, 0);
+    check("more than 64K -> E2BIG", r == -7, fmt_i32(r));
+    let r = sys_setxattr(file.as_ptr(), c"user.x".as_ptr(), b"x".as_ptr(), 1, 4);
+    check("unknown flags -> EINVAL", r == -22, fmt_i32(r));
+
+    // acls are checked and kept, nothing enforces them
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"system.posix_acl_access".as_ptr(),
+        ACL.as_ptr(),
+        ACL.len(),
+        0,
+    );
+    let n = xattr_of(file, c"system.posix_acl_access", &mut buf);
+    check(
+        "an access acl",
+        r == 0 && n == ACL.len() as isize && buf[..ACL.len()] == ACL,
+        fmt_i32(r),
+    );
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"system.posix_acl_access".as_ptr(),
+        ACL[4..].as_ptr(),
+        ACL.len() - 4,
+        0,
+    );
+    check("a broken acl -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_setxattr(
+        file.as_ptr(),
+        c"system.posix_acl_default".as_ptr(),
+        ACL.as_ptr(),
+        ACL.len(),
+        0,
+    );
+    check("a default acl on a file -> EACCES", r == -13, fmt_i32(r));
+    let r = sys_setxattr(
+        c"/tmp".as_ptr(),
+        c"system.posix_acl_default".as_ptr(),
+        ACL.as_ptr(),
+        ACL.len(),
+        0,
+    );
+    let r2 = sys_removexattr(c"/tmp".as_ptr(), c"system.posix_acl_default".as_ptr());
+    check("and on a directory", r == 0 && r2 == 0, fmt_i32(r));
+    let r = sys_setxattr(
+        c"/tmp".as_ptr(),
+        c"system.other".as_ptr(),
+        b"x".as_ptr(),
+        1,
+        0,
+    );
+    check("other system. names -> EOPNOTSUPP", r == -95, fmt_i32(r));
+
+    // user. is only for files and directories, security. goes on anything
+    let link = c"/tmp/xattr-link";
+    sys_symlink(file.as_ptr(), link.as_ptr());
+    let r = sys_lsetxattr(link.as_ptr(), c"user.x".as_ptr(), b"x".as_ptr(), 1, 0);
+    check("user. on a symlink -> EPERM", r == -1, fmt_i32(r));
+    let r = sys_lsetxattr(
+        link.as_ptr(),
+        c"security.selinux".as_ptr(),
+        b"label".as_ptr(),
+        5,
+        0,
+    );
+    let n = sys_lgetxattr(
+        link.as_ptr(),
+        c"security.selinux".as_ptr(),
+        buf.as_mut_ptr(),
+        buf.len(),
+    );
+    check(
+        "security. on a symlink",
+        r == 0 && text(&buf, n) == "label",
+        fmt_i32(r),
+    );
+    let n = xattr_of(link, c"security.selinux", &mut buf);
+    check("getxattr follows it to the file", n == -61, fmt_isize(n));
+    let n = sys_llistxattr(link.as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "llistxattr",
+        text(&buf, n) == "security.selinux\0",
+        fmt_isize(n),
+    );
+    let r = sys_lremovexattr(link.as_ptr(), c"security.selinux".as_ptr());
+    check("lremovexattr", r == 0, fmt_i32(r));
+
+    let r = sys_removexattr(file.as_ptr(), c"user.mime_type".as_ptr());
+    check("removexattr", r == 0, fmt_i32(r));
+    let r = sys_removexattr(file.as_ptr(), c"user.mime_type".as_ptr());
+    check("again -> ENODATA", r == -61, fmt_i32(r));
+    let r = sys_fremovexattr(fd, c"security.capability".as_ptr());
+    check("fremovexattr", r == 0, fmt_i32(r));
+    let n = sys_listxattr(file.as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "the rest is still there",
+        text(&buf, n) == "system.posix_acl_access\0trusted.empty\0",
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let r = sys_setxattr(
+        c"/proc/self/status".as_ptr(),
+        c"user.x".as_ptr(),
+        b"x".as_ptr(),
+        1,
+        0,
+    );
+    check("procfs -> EOPNOTSUPP", r == -95, fmt_i32(r));
+    sys_unlink(link.as_ptr());
+    sys_unlink(file.as_ptr());
+}
+
+fn test_tarfs() {
+    println!("[tarfs]");
+    sys_mkdir(c"/tmp/tar".as_ptr(), 0o755);
+    let r = sys_mount(
+        c"nosuchmodule".as_ptr(),
+        c"/tmp/tar".as_ptr(),
+        c"tarfs".as_ptr(),
+        0,
+        core::ptr::null(),
+    );
+    check("unknown module -> ENOENT", r == -2, fmt_i32(r));
+    let r = sys_mount(
+        c"initramfs".as_ptr(),
+        c"/tmp/tar".as_ptr(),
+        c"tarfs".as_ptr(),
+        0,
+        core::ptr::null(),
+    );
+    check("mount the initramfs module", r == 0, fmt_i32(r));
+    let same = match (stat_of(c"/Cargo.toml"), stat_of(c"/tmp/tar/Cargo.toml")) {
+        (Some(a), Some(b)) => a.st_size == b.st_size,
+        _ => false,
+    };
+    check("same file in both", same, "");
+    match statfs_of(c"/tmp/tar") {
+        Some(st) => check("forced read-only", st.f_flags & 1 != 0, ""),
+        None => check("statfs tarfs", false, "failed"),
+    }
+    let fd = sys_open(c"/tmp/tar/Cargo.toml".as_ptr(), O_RDWR, 0);
+    check("open for writing -> EROFS", fd == -30, fmt_i32(fd));
+    let r = sys_mount(
+        c"initramfs".as_ptr(),
+        c"/tmp/tar".as_ptr(),
+        c"tarfs".as_ptr(),
+        MS_REMOUNT,
+        core::ptr::null(),
+    );
+    check("remount read-write -> EROFS", r == -30, fmt_i32(r));
+
+    // the root copy is writable, writing copies it out of the module first
+    let mut orig = [0u8; 8];
+    let mut buf = [0u8; 8];
+    let fd = sys_open(c"/Cargo.toml".as_ptr(), O_RDWR, 0);
+    sys_pread64(fd, orig.as_mut_ptr(), orig.len(), 0);
+    let free = statfs_of(c"/").map_or(0, |st| st.f_bfree);
+    let n = sys_pwrite64(fd, b"chronos!".as_ptr(), 8, 0);
+    check("write to an initramfs file", n == 8, fmt_isize(n));
+    sys_pread64(fd, buf.as_mut_ptr(), buf.len(), 0);
+    check("reads the new data", &buf == b"chronos!", "");
+    check(
+        "the copy counts against the root",
+        statfs_of(c"/").is_some_and(|st| st.f_bfree < free),
+        "",
+    );
+    let fd2 = sys_open(c"/tmp/tar/Cargo.toml".as_ptr(), O_RDONLY, 0);
+    sys_pread64(fd2, buf.as_mut_ptr(), buf.len(), 0);
+    check("module left alone", buf == orig, "");
+    sys_close(fd2);
+    sys_pwrite64(fd, orig.as_ptr(), orig.len(), 0);
+    sys_close(fd);
+
+    sys_umount2(c"/tmp/tar".as_ptr(), 0);
+    sys_rmdir(c"/tmp/tar".as_ptr());
+}
+
+fn test_initramfs_metadata() {
+    println!("[initramfs metadata]");
+    let fd = sys_open(c"/tmp/fresh".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    sys_close(fd);
+    match (stat_of(c"/src/main.rs"), stat_of(c"/tmp/fresh")) {
+        (Some(old), Some(fresh)) => {
+            check("mode from the archive", old.st_mode & 0o777 == 0o644, "");
+            check("mtime from the archive", old.st_mtime < fresh.st_mtime, "");
+        }
+        _ => check("stat", false, "failed"),
+    }
+    sys_unlink(c"/tmp/fresh".as_ptr());
+    let r = sys_access(c"/sbin/".as_ptr(), 0);
+    check("symlink from the archive", r == 0, fmt_i32(r));
+}
+
+fn test_initramfs_layers() {
+    println!("[initramfs layers]");
+    let mut buf = [0u8; 64];
+    let n = read_whole(c"/etc/hostname", &mut buf);
+    check(
+        "file from the gzipped cpio module",
+        text(&buf, n) == "chronos\n",
+        fmt_isize(n),
+    );
+    let r = sys_access(c"/src/main.rs".as_ptr(), 0);
+    check("first module still there", r == 0, fmt_i32(r));
+
+    sys_mkdir(c"/tmp/overlay".as_ptr(), 0o755);
+    let r = sys_mount(
+        c"overlay".as_ptr(),
+        c"/tmp/overlay".as_ptr(),
+        c"tarfs".as_ptr(),
+        0,
+        core::ptr::null(),
+    );
+    check("mount the cpio module", r == 0, fmt_i32(r));
+    let n = read_whole(c"/tmp/overlay/etc/hostname", &mut buf);
+    check(
+        "read through it",
+        text(&buf, n) == "chronos\n",
+        fmt_isize(n),
+    );
+    let r = sys_access(c"/tmp/overlay/src".as_ptr(), 0);
+    check("only its own files", r == -2, fmt_i32(r));
+    sys_umount2(c"/tmp/overlay".as_ptr(), 0);
+    sys_rmdir(c"/tmp/overlay".as_ptr());
+}
+
+fn test_sync() {
+    println!("[sync]");
+    let r = sys_sync();
+    check("sync", r == 0, fmt_i32(r));
+
+    let fd = sys_open(c"/tmp/synced".as_ptr(), O_RDWR | O_CREAT, 0o644);
+    sys_write(fd, b"on disk, eventually".as_ptr(), 19);
+    let r = sys_fsync(fd);
+    check("fsync a file", r == 0, fmt_i32(r));
+    let r = sys_fdatasync(fd);
+    check("fdatasync a file", r == 0, fmt_i32(r));
+    let r = sys_syncfs(fd);
+    check("syncfs", r == 0, fmt_i32(r));
+    sys_close(fd);
+    sys_unlink(c"/tmp/synced".as_ptr());
+
+    let fd = sys_open(c"/tmp".as_ptr(), O_RDONLY, 0);
+    let r = sys_fsync(fd);
+    check("fsync a directory", r == 0, fmt_i32(r));
+    sys_close(fd);
+
+    let fd = sys_memfd_create(c"synced".as_ptr(), 0);
+    let r = sys_fsync(fd);
+    check("fsync a memfd", r == 0, fmt_i32(r));
+    sys_close(fd);
+
+    // nowhere to sync to
+    let fd = sys_eventfd2(0, 0);
+    let r = sys_fsync(fd);
+    check("fsync an eventfd -> EINVAL", r == -22, fmt_i32(r));
+    sys_close(fd);
+    let fd = sys_open(c"/dev/null".as_ptr(), O_RDWR, 0);
+    let r = sys_fdatasync(fd);
+    check("fdatasync /dev/null -> EINVAL", r == -22, fmt_i32(r));
+    sys_close(fd);
+
+    let r = sys_fsync(-1);
+    check("fsync bad fd -> EBADF", r == -9, fmt_i32(r));
+    let r = sys_syncfs(-1);
+    check("syncfs bad fd -> EBADF", r == -9, fmt_i32(r));
+
+    let mut buf = [0u8; 1024];
+    let n = read_whole(c"/proc/meminfo", &mut buf);
+    check(
+        "/proc/meminfo has Buffers and Dirty",
+        text(&buf, n).contains("Buffers:") && text(&buf, n).contains("Dirty:"),
+        fmt_isize(n),
+    );
+}
+
+fn test_nvme() {
+    println!("[nvme]");
+    // only there when qemu attaches the disk, like `make run` does
+    let Some(st) = stat_of(c"/dev/nvme0n1") else {
+        println!("  no /dev/nvme0n1, skipped");
+        return;
+    };
+    check(
+        "/dev/nvme0n1 is a block device",
+        st.st_mode & 0o170000 == 0o060000,
+        "",
+    );
+    check(
+        "in the extended major",
+        (st.st_rdev >> 8) & 0xfff == 259,
+        "",
+    );
+
+    let fd = sys_open(c"/dev/nvme0n1".as_ptr(), O_RDWR, 0);
+    check("open", fd >= 0, fmt_i32(fd));
+    let size = sys_lseek(fd, 0, SEEK_END);
+    check(
+        "seeking to the end gives the capacity",
+        size > 0 && size % 512 == 0,
+        fmt_isize(size as isize),
+    );
+
+    // whatever is on the disk goes back unchanged
+    let mut first = [0u8; 4096];
+    let n = sys_pread64(fd, first.as_mut_ptr(), first.len(), 0);
+    check("read the first page", n == 4096, fmt_isize(n));
+    let n = sys_pwrite64(fd, first.as_ptr(), first.len(), 0);
+    check("write it back", n == 4096, fmt_isize(n));
+    let r = sys_fsync(fd);
+    check("fsync reaches the disk", r == 0, fmt_i32(r));
+    let mut again = [0u8; 4096];
+    let n = sys_pread64(fd, again.as_mut_ptr(), again.len(), 0);
+    check(
+        "reads back the same",
+        n == 4096 && again == first,
+        fmt_isize(n),
+    );
+
+    let mut buf = [0u8; 4096];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), size - 512);
+    check("short read at the end", n == 512, fmt_isize(n));
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), size);
+    check("nothing past the end", n == 0, fmt_isize(n));
+    let n = sys_pwrite64(fd, buf.as_ptr(), buf.len(), size);
+    check("write past the end -> ENOSPC", n == -28, fmt_isize(n));
+    sys_close(fd);
+}
+
+fn test_ahci() {
+    println!("[ahci]");
+    // the sata disk `make run` puts on the q35 controller
+    let Some(st) = stat_of(c"/dev/sda") else {
+        println!("  no /dev/sda, skipped");
+        return;
+    };
+    check(
+        "/dev/sda is a block device",
+        st.st_mode & 0o170000 == 0o060000,
+        "",
+    );
+    check(
+        "major 8, minor 0",
+        st.st_rdev >> 8 & 0xfff == 8 && st.st_rdev & 0xff == 0,
+        "",
+    );
+
+    let fd = sys_open(c"/dev/sda".as_ptr(), O_RDWR, 0);
+    check("open", fd >= 0, fmt_i32(fd));
+    let size = sys_lseek(fd, 0, SEEK_END);
+    check(
+        "seeking to the end gives the capacity",
+        size > 0 && size % 512 == 0,
+        fmt_isize(size as isize),
+    );
+
+    // whatever is on the disk goes back unchanged
+    let mut first = [0u8; 4096];
+    let n = sys_pread64(fd, first.as_mut_ptr(), first.len(), 0);
+    check("read the first page", n == 4096, fmt_isize(n));
+    let n = sys_pwrite64(fd, first.as_ptr(), first.len(), 0);
+    check("write it back", n == 4096, fmt_isize(n));
+    let r = sys_fsync(fd);
+    check("fsync flushes the disk cache", r == 0, fmt_i32(r));
+    let mut again = [0u8; 16384];
+    let n = sys_pread64(fd, again.as_mut_ptr(), again.len(), 0);
+    check(
+        "reads back the same",
+        n == 16384 && again[..4096] == first,
+        fmt_isize(n),
+    );
+
+    let mut buf = [0u8; 4096];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), size - 512);
+    check("short read at the end", n == 512, fmt_isize(n));
+    let n = sys_pwrite64(fd, buf.as_ptr(), buf.len(), size);
+    check("write past the end -> ENOSPC", n == -28, fmt_isize(n));
+    sys_close(fd);
+}
+
+fn test_virtio() {
+    println!("[virtio]");
+    // all of these come from `make run`, any of them can be missing
+    match stat_of(c"/dev/vda") {
+        Some(st) => {
+            check(
+                "/dev/vda is a block device in major 254",
+                st.st_mode & 0o170000 == 0o060000 && st.st_rdev >> 8 & 0xfff == 254,
+                "",
+            );
+            let fd = sys_open(c"/dev/vda".as_ptr(), O_RDWR, 0);
+            check("open /dev/vda", fd >= 0, fmt_i32(fd));
+            let size = sys_lseek(fd, 0, SEEK_END);
+            check("it has a capacity", size > 0, fmt_isize(size as isize));
+            let mut first = [0u8; 8192];
+            let n = sys_pread64(fd, first.as_mut_ptr(), first.len(), 0);
+            check("read the first pages", n == 8192, fmt_isize(n));
+            let n = sys_pwrite64(fd, first.as_ptr(), first.len(), 0);
+            check("write them back", n == 8192, fmt_isize(n));
+            let r = sys_fsync(fd);
+            check("fsync flushes", r == 0, fmt_i32(r));
+            sys_close(fd);
+        }
+        None => println!("  no /dev/vda, skipped"),
+    }
+
+    match stat_of(c"/dev/hwrng") {
+        Some(st) => {
+            check(
+                "/dev/hwrng is misc 183",
+                st.st_mode & 0o170000 == 0o020000 && st.st_rdev == (10 << 8 | 183),
+                "",
+            );
+            let fd = sys_open(c"/dev/hwrng".as_ptr(), O_RDONLY, 0);
+            let mut a = [0u8; 32];
+            let mut b = [0u8; 32];
+            let n = sys_read(fd, a.as_mut_ptr(), a.len());
+            check("read some entropy", n > 0, fmt_isize(n));
+            sys_read(fd, b.as_mut_ptr(), b.len());
+            check("and it changes", a != b, "");
+            sys_close(fd);
+        }
+        None => println!("  no /dev/hwrng, skipped"),
+    }
+
+    match stat_of(c"/dev/hvc0") {
+        Some(st) => {
+            check(
+                "/dev/hvc0 is a tty in major 229",
+                st.st_mode & 0o170000 == 0o020000 && st.st_rdev >> 8 & 0xfff == 229,
+                "",
+            );
+            let fd = sys_open(c"/dev/hvc0".as_ptr(), O_RDWR | O_NONBLOCK, 0);
+            let msg = b"hello from chronos\n";
+            let n = sys_write(fd, msg.as_ptr(), msg.len());
+            check("write to it", n == msg.len() as isize, fmt_isize(n));
+            sys_close(fd);
+        }
+        None => println!("  no /dev/hvc0, skipped"),
+    }
+}
+
+fn test_partitions() {
+    println!("[partitions]");
+    let mut buf = [0u8; 4096];
+    let n = read_whole(c"/proc/partitions", &mut buf);
+    let listing = text(&buf, n);
+    check(
+        "/proc/partitions has the header",
+        listing.starts_with("major minor  #blocks  name\n\n"),
+        fmt_isize(n),
+    );
+
+    // every disk and partition listed has its node, with the same numbers
+    let mut all_there = true;
+    for line in listing.lines().skip(2) {
+        let mut fields = line.split_whitespace();
+        let (Some(major), Some(minor), Some(_), Some(name)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            all_there = false;
+            continue;
+        };
+        let mut path = [0u8; 64];
+        let full = b"/dev/".iter().chain(name.as_bytes()).take(63);
+        for (dst, &src) in path.iter_mut().zip(full) {
+            *dst = src;
+        }
+        let path = core::ffi::CStr::from_bytes_until_nul(&path).unwrap();
+        let rdev = major.parse::<u64>().unwrap_or(0) << 8 | minor.parse::<u64>().unwrap_or(0);
+        match stat_of(path) {
+            Some(st) if st.st_mode & 0o170000 == 0o060000 && st.st_rdev == rdev => {}
+            _ => {
+                println!("  {} doesn't match its node", name);
+                all_there = false;
+            }
+        }
+    }
+    check("every entry has a matching /dev node", all_there, "");
+}
+
+fn test_vfat() {
+    println!("[vfat]");
+    let null = core::ptr::null();
+    sys_mkdir(c"/tmp/fat".as_ptr(), 0o755);
+    // the fat32 disk `make run` puts on the second sata port
+    let r = sys_mount(
+        c"/dev/sdb".as_ptr(),
+        c"/tmp/fat".as_ptr(),
+        c"vfat".as_ptr(),
+        0,
+        null,
+    );
+    if r != 0 {
+        println!("  no fat filesystem on /dev/sdb, skipped");
+        sys_rmdir(c"/tmp/fat".as_ptr());
+        return;
+    }
+    let fs = statfs_of(c"/tmp/fat");
+    check(
+        "statfs says msdos",
+        fs.is_some_and(|fs| fs.f_type == 0x4d44 && fs.f_blocks > 0),
+        "",
+    );
+
+    let mut buf = [0u8; 4096];
+    let n = read_whole(c"/tmp/fat/limine.conf", &mut buf);
+    check(
+        "read limine.conf off it",
+        text(&buf, n).contains("ChronOS"),
+        fmt_isize(n),
+    );
+    let n = read_whole(c"/tmp/fat/LIMINE.CONF", &mut buf);
+    check("names don't care about case", n > 0, fmt_isize(n));
+
+    // a long name, and more than one 512 byte cluster of data
+    let mut data = [0u8; 3000];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    let fd = sys_open(
+        c"/tmp/fat/A Long File Name.data".as_ptr(),
+        O_RDWR | O_CREAT,
+        0o644,
+    );
+    check("create a long name", fd >= 0, fmt_i32(fd));
+    let n = sys_write(fd, data.as_ptr(), data.len());
+    check("write across clusters", n == 3000, fmt_isize(n));
+    sys_close(fd);
+    let mut back = [0u8; 3000];
+    let n = read_whole(c"/tmp/fat/a long file name.data", &mut back);
+    check(
+        "reads back the same",
+        n == 3000 && back == data,
+        fmt_isize(n),
+    );
+    let r = sys_mkdir(c"/tmp/fat/A LONG FILE NAME.DATA".as_ptr(), 0o755);
+    check("the same name again -> EEXIST", r == -17, fmt_i32(r));
+
+    let r = sys_mkdir(c"/tmp/fat/Sub Directory".as_ptr(), 0o755);
+    check("mkdir", r == 0, fmt_i32(r));
+    let r = sys_rename(
+        c"/tmp/fat/A Long File Name.data".as_ptr(),
+        c"/tmp/fat/Sub Directory/moved.data".as_ptr(),
+    );
+    check("rename into it", r == 0, fmt_i32(r));
+    let r = sys_access(c"/tmp/fat/A Long File Name.data".as_ptr(), 0);
+    check("the old name is gone", r == -2, fmt_i32(r));
+
+    let fd = sys_open(c"/tmp/fat/Sub Directory/moved.data".as_ptr(), O_RDWR, 0);
+    let r = sys_ftruncate(fd, 100);
+    check("ftruncate shrinks", r == 0, fmt_i32(r));
+    let r = sys_ftruncate(fd, 1000);
+    check("and grows", r == 0, fmt_i32(r));
+    let n = sys_pread64(fd, back.as_mut_ptr(), back.len(), 0);
+    check(
+        "the grown part reads as zeroes",
+        n == 1000 && back[..100] == data[..100] && back[100..1000].iter().all(|&b| b == 0),
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let r = sys_mount(
+        c"/dev/sdb".as_ptr(),
+        c"/tmp/fat".as_ptr(),
+        c"vfat".as_ptr(),
+        0,
+        null,
+    );
+    check("mount it twice -> EBUSY", r == -16, fmt_i32(r));
+    let r = sys_mount(
+        c"/dev/null".as_ptr(),
+        c"/tmp/fat".as_ptr(),
+        c"vfat".as_ptr(),
+        0,
+        null,
+    );
+    check("not a block device -> ENOTBLK", r == -15, fmt_i32(r));
+
+    // everything has to be on the disk for the next mount
+    let r = sys_umount2(c"/tmp/fat".as_ptr(), 0);
+    check("umount", r == 0, fmt_i32(r));
+    sys_mount(
+        c"/dev/sdb".as_ptr(),
+        c"/tmp/fat".as_ptr(),
+        c"vfat".as_ptr(),
+        0,
+        null,
+    );
+    let n = read_whole(c"/tmp/fat/sub directory/MOVED.DATA", &mut back);
+    check("still there after a remount", n == 1000, fmt_isize(n));
+    let mut entries = [0u8; 1024];
+    let fd = sys_open(c"/tmp/fat/Sub Directory".as_ptr(), O_RDONLY, 0);
+    let n = sys_getdents64(fd, entries.as_mut_ptr(), entries.len());
+    check(
+        "with its long name",
+        n > 0
+            && entries[..n as usize]
+                .windows(10)
+                .any(|name| name == b"moved.data"),
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let r = sys_rmdir(c"/tmp/fat/Sub Directory".as_ptr());
+    check("rmdir a full directory -> ENOTEMPTY", r == -39, fmt_i32(r));
+    sys_unlink(c"/tmp/fat/Sub Directory/moved.data".as_ptr());
+    let r = sys_rmdir(c"/tmp/fat/Sub Directory".as_ptr());
+    check("and once it's empty", r == 0, fmt_i32(r));
+    sys_umount2(c"/tmp/fat".as_ptr(), 0);
+    sys_rmdir(c"/tmp/fat".as_ptr());
+}
+
+fn test_ext2() {
+    println!("[ext2]");
+    let null = core::ptr::null();
+    sys_mkdir(c"/tmp/ext2".as_ptr(), 0o755);
+    // the ext2 disk `make run` puts on the second virtio port, kept across boots
+    let r = sys_mount(
+        c"/dev/vdb".as_ptr(),
+        c"/tmp/ext2".as_ptr(),
+        c"ext2".as_ptr(),
+        0,
+        null,
+    );
+    if r != 0 {
+        println!("  no ext2 filesystem on /dev/vdb, skipped");
+        sys_rmdir(c"/tmp/ext2".as_ptr());
+        return;
+    }
+    let fs = statfs_of(c"/tmp/ext2");
+    check(
+        "statfs says ext2",
+        fs.as_ref()
+            .is_some_and(|fs| fs.f_type == 0xef53 && fs.f_blocks > 0),
+        "",
+    );
+
+    // one more every time this runs, it has to be there after a reboot
+    let mut buf = [0u8; 64];
+    let n = read_whole(c"/tmp/ext2/boots", &mut buf);
+    let boots = text(&buf, n).trim().parse::<u32>().unwrap_or(0);
+    println!("  ran {} times before", boots);
+    let mut count = [0u8; 16];
+    let count = cpath(&mut count, format_args!("{}\n", boots + 1)).to_bytes();
+    let fd = sys_open(
+        c"/tmp/ext2/boots".as_ptr(),
+        O_WRONLY | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    let n = sys_write(fd, count.as_ptr(), count.len());
+    check("count this run", n == count.len() as isize, fmt_isize(n));
+    let r = sys_fchmod(fd, 0o640);
+    check("fchmod", r == 0, fmt_i32(r));
+    sys_fsync(fd);
+    sys_close(fd);
+    let free = statfs_of(c"/tmp/ext2").map_or(0, |fs| fs.f_bfree);
+
+    // 320K in 1K blocks goes past the 12 direct and 256 indirect ones
+    let fd = sys_open(c"/tmp/ext2/big".as_ptr(), O_RDWR | O_CREAT | O_TRUNC, 0o644);
+    check("create a file", fd >= 0, fmt_i32(fd));
+    let mut block = [0u8; 4096];
+    let mut total = 0;
+    for i in 0..80 {
+        block.fill(i as u8);
+        total += sys_write(fd, block.as_ptr(), block.len());
+    }
+    check("write 320K", total == 80 * 4096, fmt_isize(total));
+    let n = sys_pread64(fd, block.as_mut_ptr(), 16, 300 * 1024);
+    check(
+        "read back from the double indirect blocks",
+        n == 16 && block[..16].iter().all(|&b| b == 75),
+        fmt_isize(n),
+    );
+    let mut st: StatBuf = unsafe { core::mem::zeroed() };
+    sys_fstat(fd, &mut st);
+    check(
+        "st_blocks counts the data and the indirect blocks",
+        st.st_size == 80 * 4096 && st.st_blocks > 640,
+        "",
+    );
+
+    let r = sys_ftruncate(fd, 8 << 20);
+    check("ftruncate grows it", r == 0, fmt_i32(r));
+    let blocks = st.st_blocks;
+    sys_fstat(fd, &mut st);
+    check(
+        "as a hole",
+        st.st_size == 8 << 20 && st.st_blocks == blocks,
+        "",
+    );
+    let n = sys_pread64(fd, block.as_mut_ptr(), block.len(), 4 << 20);
+    check(
+        "which reads as zeroes",
+        n == 4096 && block.iter().all(|&b| b == 0),
+        fmt_isize(n),
+    );
+    let r = sys_ftruncate(fd, 5000);
+    check("ftruncate shrinks it", r == 0, fmt_i32(r));
+    sys_fstat(fd, &mut st);
+    check("and frees the blocks", st.st_blocks == 10, "");
+    let n = sys_pread64(fd, block.as_mut_ptr(), block.len(), 4096);
+    check(
+        "what's left is still there",
+        n == 904 && block[..904].iter().all(|&b| b == 1),
+        fmt_isize(n),
+    );
+    sys_close(fd);
+
+    let r = sys_mkdir(c"/tmp/ext2/dir".as_ptr(), 0o755);
+    check("mkdir", r == 0, fmt_i32(r));
+    let r = sys_symlink(c"../big".as_ptr(), c"/tmp/ext2/dir/link".as_ptr());
+    check("a symlink", r == 0, fmt_i32(r));
+    let long = c"/a/long/target/that/does/not/fit/in/the/sixty/bytes/of/the/inode/itself";
+    let r = sys_symlink(long.as_ptr(), c"/tmp/ext2/dir/long".as_ptr());
+    check("one that needs a block", r == 0, fmt_i32(r));
+    let r = sys_link(c"/tmp/ext2/big".as_ptr(), c"/tmp/ext2/dir/hard".as_ptr());
+    check("a hard link", r == 0, fmt_i32(r));
+    let r = sys_mknod(c"/tmp/ext2/dir/null".as_ptr(), 0o020640, 1 << 8 | 3);
+    check("a device node", r == 0, fmt_i32(r));
+    let r = sys_chmod(c"/tmp/ext2/big".as_ptr(), 0o600);
+    check("chmod", r == 0, fmt_i32(r));
+    let r = sys_chown(c"/tmp/ext2/big".as_ptr(), 1000, 100);
+    check("chown", r == 0, fmt_i32(r));
+    let r = sys_chown(c"/tmp/ext2/big".as_ptr(), u32::MAX, 50);
+    check("chown only the group", r == 0, fmt_i32(r));
+    let r = sys_rename(
+        c"/tmp/ext2/dir/hard".as_ptr(),
+        c"/tmp/ext2/dir/renamed".as_ptr(),
+    );
+    check("rename", r == 0, fmt_i32(r));
+
+    // everything has to come back off the disk
+    let r = sys_umount2(c"/tmp/ext2".as_ptr(), 0);
+    check("umount", r == 0, fmt_i32(r));
+    sys_mount(
+        c"/dev/vdb".as_ptr(),
+        c"/tmp/ext2".as_ptr(),
+        c"ext2".as_ptr(),
+        0,
+        null,
+    );
+    let big = stat_of(c"/tmp/ext2/big");
+    let renamed = stat_of(c"/tmp/ext2/dir/renamed");
+    check(
+        "the hard link is the same inode",
+        big.as_ref()
+            .zip(renamed.as_ref())
+            .is_some_and(|(a, b)| a.st_ino == b.st_ino && a.st_nlink == 2),
+        "",
+    );
+    check(
+        "mode and owner stuck",
+        big.as_ref().is_some_and(|st| {
+            st.st_mode == 0o100600 && st.st_uid == 1000 && st.st_gid == 50 && st.st_size == 5000
+        }),
+        "",
+    );
+    let mut target = [0u8; 128];
+    let n = sys_readlink(
+        c"/tmp/ext2/dir/link".as_ptr(),
+        target.as_mut_ptr(),
+        target.len(),
+    );
+    check("readlink", text(&target, n) == "../big", fmt_isize(n));
+    let n = sys_readlink(
+        c"/tmp/ext2/dir/long".as_ptr(),
+        target.as_mut_ptr(),
+        target.len(),
+    );
+    check(
+        "the long one too",
+        text(&target, n).as_bytes() == long.to_bytes(),
+        fmt_isize(n),
+    );
+    let n = read_whole(c"/tmp/ext2/dir/link", &mut block);
+    check("reading through the link", n == 4096, fmt_isize(n));
+    check(
+        "the device node",
+        stat_of(c"/tmp/ext2/dir/null")
+            .is_some_and(|st| st.st_mode == 0o020640 && st.st_rdev == 1 << 8 | 3),
+        "",
+    );
+    let fd = sys_open(c"/tmp/ext2/dir/null".as_ptr(), O_WRONLY, 0);
+    let n = sys_write(fd, b"gone".as_ptr(), 4);
+    check("opens the driver", n == 4, fmt_isize(n));
+    sys_close(fd);
+    let dir = stat_of(c"/tmp/ext2/dir");
+    check(
+        "directories count their own \".\"",
+        dir.is_some_and(|st| st.st_nlink == 2),
+        "",
+    );
+
+    let r = sys_rmdir(c"/tmp/ext2/dir".as_ptr());
+    check("rmdir a full directory -> ENOTEMPTY", r == -39, fmt_i32(r));
+    for name in [
+        c"/tmp/ext2/dir/link",
+        c"/tmp/ext2/dir/long",
+        c"/tmp/ext2/dir/null",
+        c"/tmp/ext2/dir/renamed",
+        c"/tmp/ext2/big",
+    ] {
+        sys_unlink(name.as_ptr());
+    }
+    let r = sys_rmdir(c"/tmp/ext2/dir".as_ptr());
+    check("and once it's empty", r == 0, fmt_i32(r));
+    let after = statfs_of(c"/tmp/ext2").map_or(0, |fs| fs.f_bfree);
+    check("every block is free again", after == free, "");
+
+    // into the disk and back out, the old root and everything on it lands on /old
+    sys_mkdir(c"/tmp/ext2/old".as_ptr(), 0o755);
+    let r = sys_pivot_root(c"/tmp/ext2".as_ptr(), c"/dev".as_ptr());
+    check("put_old outside new_root -> EINVAL", r == -22, fmt_i32(r));
+    let r = sys_pivot_root(c"/tmp/ext2".as_ptr(), c"/tmp/ext2/old".as_ptr());
+    check("pivot_root onto it", r == 0, fmt_i32(r));
+    check(
+        "the disk is /",
+        stat_of(c"/boots").is_some() && stat_of(c"/tmp/ext2").is_none(),
+        "",
+    );
+    check(
+        "and the old root is on /old",
+        stat_of(c"/old/dev/null").is_some(),
+        "",
+    );
+    let r = sys_pivot_root(c"/old".as_ptr(), c"/old/tmp/ext2".as_ptr());
+    check("and back", r == 0, fmt_i32(r));
+    check(
+        "where it was",
+        stat_of(c"/tmp/ext2/boots").is_some() && stat_of(c"/dev/null").is_some(),
+        "",
+    );
+    sys_rmdir(c"/tmp/ext2/old".as_ptr());
+
+    sys_umount2(c"/tmp/ext2".as_ptr(), 0);
+    sys_rmdir(c"/tmp/ext2".as_ptr());
+}
+
+// mounts the cd again with `data` and checks a name it should have
+fn remount_cdrom(data: &core::ffi::CStr, path: &core::ffi::CStr) -> bool {
+    sys_umount2(c"/cdrom".as_ptr(), 0);
+    let r = sys_mount(
+        c"/dev/sr0".as_ptr(),
+        c"/cdrom".as_ptr(),
+        c"iso9660".as_ptr(),
+        MS_RDONLY,
+        data.as_ptr(),
+    );
+    r == 0 && stat_of(path).is_some()
+}
+
+fn test_iso9660() {
+    println!("[iso9660]");
+    // the boot cd, the kernel mounts it there
+    let fs = statfs_of(c"/cdrom");
+    if !fs.as_ref().is_some_and(|fs| fs.f_type == 0x9660) {
+        println!("  no cd on /cdrom, skipped");
+        return;
+    }
+    check(
+        "statfs says iso9660",
+        fs.is_some_and(|fs| fs.f_blocks > 0 && fs.f_bfree == 0),
+        "",
+    );
+    check(
+        "/dev/sr0 is a block device with major 11",
+        stat_of(c"/dev/sr0")
+            .is_some_and(|st| st.st_mode & 0o170000 == 0o060000 && st.st_rdev >> 8 & 0xfff == 11),
+        "",
+    );
+
+    let mut buf = [0u8; 4096];
+    let n = read_whole(c"/cdrom/extra/hostname", &mut buf);
+    check(
+        "read a file off it",
+        text(&buf, n) == "chronos\n",
+        fmt_isize(n),
+    );
+    check(
+        "the kernel is there too",
+        stat_of(c"/cdrom/boot/chronos")
+            .is_some_and(|st| st.st_mode & 0o170000 == 0o100000 && st.st_size > 0),
+        "",
+    );
+    check(
+        "a long mixed case name",
+        stat_of(c"/cdrom/extra/Read Me First.md").is_some_and(|st| st.st_size > 0),
+        "",
+    );
+    check(
+        "and a subdirectory",
+        stat_of(c"/cdrom/extra/deep/er").is_some_and(|st| st.st_mode & 0o170000 == 0o040000),
+        "",
+    );
+
+    // rock ridge
+    let n = sys_readlink(
+        c"/cdrom/extra/limine.conf".as_ptr(),
+        buf.as_mut_ptr(),
+        buf.len(),
+    );
+    check(
+        "readlink a symlink",
+        text(&buf, n) == "../boot/limine/limine.conf",
+        fmt_isize(n),
+    );
+    let n = read_whole(c"/cdrom/extra/limine.conf", &mut buf);
+    check(
+        "and read through it",
+        text(&buf, n).contains("ChronOS"),
+        fmt_isize(n),
+    );
+    check(
+        "modes come from rock ridge",
+        stat_of(c"/cdrom/extra/hostname").is_some_and(|st| st.st_mode & 0o111 == 0),
+        "",
+    );
+
+    let fd = sys_open(c"/cdrom/extra/hostname".as_ptr(), O_WRONLY, 0);
+    check("open for writing -> EROFS", fd == -30, fmt_i32(fd));
+    let r = sys_mkdir(c"/cdrom/new".as_ptr(), 0o755);
+    check("mkdir -> EROFS", r == -30, fmt_i32(r));
+    sys_umount2(c"/cdrom".as_ptr(), 0);
+    let r = sys_mount(
+        c"/dev/sr0".as_ptr(),
+        c"/cdrom".as_ptr(),
+        c"iso9660".as_ptr(),
+        MS_RDONLY,
+        c"bogus".as_ptr(),
+    );
+    check("an unknown option -> EINVAL", r == -22, fmt_i32(r));
+
+    // without rock ridge the names come from joliet, and then from the plain tree
+    check(
+        "joliet keeps long names",
+        remount_cdrom(c"norock", c"/cdrom/extra/Read Me First.md"),
+        "",
+    );
+    check(
+        "plain names are lowercased",
+        remount_cdrom(c"norock,nojoliet", c"/cdrom/extra/hostname"),
+        "",
+    );
+    check(
+        "and looked up without caring about case",
+        stat_of(c"/cdrom/EXTRA/HOSTNAME").is_some(),
+        "",
+    );
+    check(
+        "back to rock ridge",
+        remount_cdrom(c"", c"/cdrom/extra/limine.conf"),
+        "",
+    );
+}
+
+fn test_ramdisk() {
+    println!("[ramdisk]");
+    let Some(st) = stat_of(c"/dev/ram0") else {
+        println!("  no /dev/ram0, skipped");
+        return;
+    };
+    check(
+        "/dev/ram0 is a block device with major 1",
+        st.st_mode & 0o170000 == 0o060000 && st.st_rdev >> 8 & 0xfff == 1,
+        "",
+    );
+    let fd = sys_open(c"/dev/ram0".as_ptr(), O_RDWR, 0);
+    check("open it", fd >= 0, fmt_i32(fd));
+    let size = sys_lseek(fd, 0, SEEK_END);
+    check(
+        "it has a size in whole sectors",
+        size > 0 && size % 512 == 0,
+        fmt_i64(size),
+    );
+
+    let mut buf = [0xffu8; 4096];
+    let n = sys_pread64(fd, buf.as_mut_ptr(), buf.len(), 1 << 20);
+    check(
+        "unwritten parts read as zeroes",
+        n == 4096 && buf.iter().all(|&b| b == 0),
+        fmt_isize(n),
+    );
+    // across a page boundary, the pages come and go separately
+    let n = sys_pwrite64(fd, b"chronos!".as_ptr(), 8, 4092);
+    check("write to it", n == 8, fmt_isize(n));
+    let r = sys_fsync(fd);
+    check("fsync", r == 0, fmt_i32(r));
+    let n = sys_pread64(fd, buf.as_mut_ptr(), 16, 4088);
+    check(
+        "read it back",
+        n == 16 && &buf[4..12] == b"chronos!" && buf[..4] == [0; 4] && buf[12..16] == [0; 4],
+        fmt_isize(n),
+    );
+    let n = sys_pwrite64(fd, b"x".as_ptr(), 1, size);
+    check("write past the end -> ENOSPC", n == -28, fmt_isize(n));
+    let r = sys_ioctl(fd, LOOP_CLR_FD, 0);
+    check("loop ioctls -> ENOTTY", r == -25, fmt_i32(r));
+    sys_close(fd);
+}
+
+// a free loop device from /dev/loop-control with `image` behind it, the loop fd and its
+// number or the error
+fn attach_loop(image: &core::ffi::CStr, flags: i32) -> Result<(i32, i32), i32> {
+    let control = sys_open(c"/dev/loop-control".as_ptr(), O_RDWR, 0);
+    if control < 0 {
+        return Err(control);
+    }
+    let n = sys_ioctl(control, LOOP_CTL_GET_FREE, 0);
+    sys_close(control);
+    if n < 0 {
+        return Err(n);
+    }
+    let mut path = [0u8; 32];
+    let path = cpath(&mut path, format_args!("/dev/loop{}", n));
+    let fd = sys_open(path.as_ptr(), O_RDWR, 0);
+    let file = sys_open(image.as_ptr(), flags, 0);
+    if fd < 0 || file < 0 {
+        return Err(fd.min(file));
+    }
+    let r = sys_ioctl(fd, LOOP_SET_FD, file as u64);
+    sys_close(file);
+    if r < 0 {
+        sys_close(fd);
+        return Err(r);
+    }
+    Ok((fd, n))
+}
+
+fn mount_loop(n: i32, fstype: &core::ffi::CStr) -> i32 {
+    let mut path = [0u8; 32];
+    let path = cpath(&mut path, format_args!("/dev/loop{}", n));
+    sys_mount(
+        path.as_ptr(),
+        c"/tmp/loop".as_ptr(),
+        fstype.as_ptr(),
+        0,
+        core::ptr::null(),
+    )
+}
+
+fn test_loop() {
+    println!("[loop]");
+    // made by mkfs at build time and shipped in the initramfs
+    if stat_of(c"/images/fat.img").is_none() || stat_of(c"/images/ext2.img").is_none() {
+        println!("  no images in the initramfs, skipped");
+        return;
+    }
+    check(
+        "/dev/loop0 is a block device with major 7",
+        stat_of(c"/dev/loop0")
+            .is_some_and(|st| st.st_mode & 0o170000 == 0o060000 && st.st_rdev >> 8 & 0xfff == 7),
+        "",
+    );
+    sys_mkdir(c"/tmp/loop".as_ptr(), 0o755);
+
+    // a read-only fd makes a read-only device
+    let (fd, n) = match attach_loop(c"/images/fat.img", O_RDONLY) {
+        Ok(attached) => attached,
+        Err(e) => {
+            check("attach the fat image", false, fmt_i32(e));
+            return;
+        }
+    };
+    check("attach the fat image", true, "");
+    let size = sys_lseek(fd, 0, SEEK_END);
+    let image = stat_of(c"/images/fat.img").map_or(0, |st| st.st_size);
+    check(
+        "the device is as big as the file",
+        size == image,
+        fmt_i64(size),
+    );
+    let r = mount_loop(n, c"vfat");
+    check("mount it", r == 0, fmt_i32(r));
+    let mut buf = [0u8; 4096];
+    let len = read_whole(c"/tmp/loop/limine.conf", &mut buf);
+    check(
+        "read a file off it",
+        text(&buf, len).contains("ChronOS"),
+        fmt_isize(len),
+    );
+    let file = sys_open(c"/tmp/loop/new".as_ptr(), O_WRONLY | O_CREAT, 0o644);
+    check("create -> EROFS", file == -30, fmt_i32(file));
+    let file = sys_open(c"/images/ext2.img".as_ptr(), O_RDONLY, 0);
+    let r = sys_ioctl(fd, LOOP_SET_FD, file as u64);
+    check("attach another file -> EBUSY", r == -16, fmt_i32(r));
+    sys_close(file);
+    let r = sys_ioctl(fd, LOOP_CLR_FD, 0);
+    check("detach while mounted -> EBUSY", r == -16, fmt_i32(r));
+    sys_umount2(c"/tmp/loop".as_ptr(), 0);
+    let r = sys_ioctl(fd, LOOP_CLR_FD, 0);
+    check("detach after umount", r == 0, fmt_i32(r));
+    let r = sys_ioctl(fd, LOOP_CLR_FD, 0);
+    check("detach again -> ENXIO", r == -6, fmt_i32(r));
+    sys_close(fd);
+
+    let r = attach_loop(c"/tmp", O_RDONLY).map_or_else(|e| e, |_| 0);
+    check("attach a directory -> EINVAL", r == -22, fmt_i32(r));
+    let file = sys_open(c"/images/fat.img".as_ptr(), O_RDONLY, 0);
+    let r = sys_ioctl(file, LOOP_SET_FD, file as u64);
+    check("loop ioctls on a file -> ENOTTY", r == -25, fmt_i32(r));
+    sys_close(file);
+
+    // a writable copy, what's written through the device has to land in the file
+    let src = sys_open(c"/images/ext2.img".as_ptr(), O_RDONLY, 0);
+    let dst = sys_open(
+        c"/tmp/ext2.img".as_ptr(),
+        O_WRONLY | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    loop {
+        let len = sys_read(src, buf.as_mut_ptr(), buf.len());
+        if len <= 0 {
+            break;
+        }
+        sys_write(dst, buf.as_ptr(), len as usize);
+    }
+    sys_close(src);
+    sys_close(dst);
+    let Ok((fd, n)) = attach_loop(c"/tmp/ext2.img", O_RDWR) else {
+        check("attach a copy of the ext2 image", false, "");
+        return;
+    };
+    let r = mount_loop(n, c"ext2");
+    check("mount it read-write", r == 0, fmt_i32(r));
+    let file = sys_open(
+        c"/tmp/loop/hello".as_ptr(),
+        O_WRONLY | O_CREAT | O_TRUNC,
+        0o644,
+    );
+    let len = sys_write(file, b"chronos\n".as_ptr(), 8);
+    check("write a file on it", len == 8, fmt_isize(len));
+    sys_close(file);
+    let r = sys_setxattr(
+        c"/tmp/loop/hello".as_ptr(),
+        c"user.origin".as_ptr(),
+        b"loop".as_ptr(),
+        4,
+        0,
+    );
+    let r2 = sys_setxattr(
+        c"/tmp/loop/hello".as_ptr(),
+        c"system.posix_acl_access".as_ptr(),
+        ACL.as_ptr(),
+        ACL.len(),
+        0,
+    );
+    check("xattrs and an acl on it", r == 0 && r2 == 0, fmt_i32(r));
+    sys_umount2(c"/tmp/loop".as_ptr(), 0);
+    let r = sys_ioctl(fd, LOOP_CLR_FD, 0);
+    check("detach", r == 0, fmt_i32(r));
+    sys_close(fd);
+
+    let Ok((fd, n)) = attach_loop(c"/tmp/ext2.img", O_RDWR) else {
+        check("attach it again", false, "");
+        return;
+    };
+    mount_loop(n, c"ext2");
+    let len = read_whole(c"/tmp/loop/hello", &mut buf);
+    check(
+        "the file is still there",
+        text(&buf, len) == "chronos\n",
+        fmt_isize(len),
+    );
+    // the disk turns the new name down after the old one is gone, it has to come back
+    let mut long = [b'a'; 311];
+    long[..10].copy_from_slice(b"/tmp/loop/");
+    long[310] = 0;
+    let r = sys_rename(
+        c"/tmp/loop/hello".as_ptr(),
+        long.as_ptr() as *const core::ffi::c_char,
+    );
+    check("rename to a name too long for it", r == -36, fmt_i32(r));
+    check(
+        "keeps the old name",
+        stat_of(c"/tmp/loop/hello").is_some(),
+        "",
+    );
+    let len = xattr_of(c"/tmp/loop/hello", c"user.origin", &mut buf);
+    check(
+        "the xattr is in its block",
+        text(&buf, len) == "loop",
+        fmt_isize(len),
+    );
+    let len = xattr_of(c"/tmp/loop/hello", c"system.posix_acl_access", &mut buf);
+    check(
+        "and the acl comes back the same",
+        len == ACL.len() as isize && buf[..ACL.len()] == ACL,
+        fmt_isize(len),
+    );
+    let len = sys_listxattr(c"/tmp/loop/hello".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "listxattr off the disk",
+        text(&buf, len) == "user.origin\0system.posix_acl_access\0",
+        fmt_isize(len),
+    );
+    sys_removexattr(c"/tmp/loop/hello".as_ptr(), c"user.origin".as_ptr());
+    sys_removexattr(
+        c"/tmp/loop/hello".as_ptr(),
+        c"system.posix_acl_access".as_ptr(),
+    );
+    let len = sys_listxattr(c"/tmp/loop/hello".as_ptr(), buf.as_mut_ptr(), buf.len());
+    check(
+        "removing the last one frees the block",
+        len == 0,
+        fmt_isize(len),
+    );
+    sys_umount2(c"/tmp/loop".as_ptr(), 0);
+    sys_ioctl(fd, LOOP_CLR_FD, 0);
+    sys_close(fd);
+    sys_unlink(c"/tmp/ext2.img".as_ptr());
+    sys_rmdir(c"/tmp/loop".as_ptr());
+}
+
 fn test_fork() {
     println!("[fork]");
     let pid = sys_fork();
@@ -920,17 +4641,44 @@ fn test_execve() {
 
 fn fmt_i32(v: i32) -> &'static str {
     match v {
+        -1 => "EPERM (-1)",
         -2 => "ENOENT (-2)",
         -5 => "EIO (-5)",
+        -6 => "ENXIO (-6)",
+        -7 => "E2BIG (-7)",
         -9 => "EBADF (-9)",
+        -11 => "EAGAIN (-11)",
+        -12 => "ENOMEM (-12)",
+        -13 => "EACCES (-13)",
         -14 => "EFAULT (-14)",
+        -16 => "EBUSY (-16)",
         -17 => "EEXIST (-17)",
+        -18 => "EXDEV (-18)",
+        -19 => "ENODEV (-19)",
         -20 => "ENOTDIR (-20)",
         -21 => "EISDIR (-21)",
         -22 => "EINVAL (-22)",
+        -24 => "EMFILE (-24)",
+        -25 => "ENOTTY (-25)",
+        -27 => "EFBIG (-27)",
+        -28 => "ENOSPC (-28)",
+        -29 => "ESPIPE (-29)",
+        -30 => "EROFS (-30)",
+        -32 => "EPIPE (-32)",
         -34 => "ERANGE (-34)",
+        -36 => "ENAMETOOLONG (-36)",
         -38 => "ENOSYS (-38)",
         -39 => "ENOTEMPTY (-39)",
+        -40 => "ELOOP (-40)",
+        -43 => "EIDRM (-43)",
+        -61 => "ENODATA (-61)",
+        -88 => "ENOTSOCK (-88)",
+        -90 => "EMSGSIZE (-90)",
+        -95 => "EOPNOTSUPP (-95)",
+        -98 => "EADDRINUSE (-98)",
+        -107 => "ENOTCONN (-107)",
+        -110 => "ETIMEDOUT (-110)",
+        -111 => "ECONNREFUSED (-111)",
         _ => "unexpected value",
     }
 }
@@ -264,6 +264,11 @@ pub fn sys_ftruncate(fd: i32, length: i64) -> i32 {
     syscall!(SyscallId::Ftruncate, fd, length) as i32
 }
 
+#[inline(always)]
+pub fn sys_ioctl(fd: i32, request: u64, arg: u64) -> i32 {
+    syscall!(SyscallId::Ioctl, fd, request, arg) as i32
+}
+
 #[repr(C)]
 pub struct LinuxDirent64 {
     pub d_ino: u64,
@@ -322,6 +327,628 @@ pub fn sys_waitpid(pid: i64, wstatus: *mut i32, options: u64) -> i64 {
     sys_wait4(pid, wstatus, options, 0)
 }
 
+#[repr(C)]
+pub struct IoVec {
+    pub iov_base: *const u8,
+    pub iov_len: usize,
+}
+
+#[inline(always)]
+pub fn sys_readv(fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+    syscall!(SyscallId::Readv, fd, iov, iovcnt) as isize
+}
+
+#[inline(always)]
+pub fn sys_writev(fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+    syscall!(SyscallId::Writev, fd, iov, iovcnt) as isize
+}
+
+#[inline(always)]
+pub fn sys_pread64(fd: i32, buf: *mut u8, count: usize, offset: i64) -> isize {
+    syscall!(SyscallId::Pread64, fd, buf, count, offset) as isize
+}
+
+#[inline(always)]
+pub fn sys_pwrite64(fd: i32, buf: *const u8, count: usize, offset: i64) -> isize {
+    syscall!(SyscallId::Pwrite64, fd, buf, count, offset) as isize
+}
+
+#[inline(always)]
+pub fn sys_preadv(fd: i32, iov: *const IoVec, iovcnt: i32, offset: i64) -> isize {
+    syscall!(SyscallId::Preadv, fd, iov, iovcnt, offset, 0) as isize
+}
+
+#[inline(always)]
+pub fn sys_pwritev(fd: i32, iov: *const IoVec, iovcnt: i32, offset: i64) -> isize {
+    syscall!(SyscallId::Pwritev, fd, iov, iovcnt, offset, 0) as isize
+}
+
+#[repr(C)]
+pub struct SockAddrUn {
+    pub sun_family: u16,
+    pub sun_path: [u8; 108],
+}
+
+impl SockAddrUn {
+    pub fn new(path: &[u8]) -> Self {
+        let mut addr = SockAddrUn {
+            sun_family: 1,
+            sun_path: [0; 108],
+        };
+        addr.sun_path[..path.len()].copy_from_slice(path);
+        addr
+    }
+}
+
+#[repr(C)]
+pub struct MsgHdr {
+    pub msg_name: *mut u8,
+    pub msg_namelen: u32,
+    pub msg_iov: *const IoVec,
+    pub msg_iovlen: usize,
+    pub msg_control: *mut u8,
+    pub msg_controllen: usize,
+    pub msg_flags: i32,
+}
+
+#[repr(C)]
+pub struct CmsgHdr {
+    pub cmsg_len: usize,
+    pub cmsg_level: i32,
+    pub cmsg_type: i32,
+}
+
+#[inline(always)]
+pub fn sys_socket(domain: i32, type_: i32, protocol: i32) -> i32 {
+    syscall!(SyscallId::Socket, domain, type_, protocol) as i32
+}
+
+#[inline(always)]
+pub fn sys_socketpair(domain: i32, type_: i32, protocol: i32, sv: *mut [i32; 2]) -> i32 {
+    syscall!(SyscallId::Socketpair, domain, type_, protocol, sv) as i32
+}
+
+#[inline(always)]
+pub fn sys_bind(fd: i32, addr: *const SockAddrUn, addrlen: u32) -> i32 {
+    syscall!(SyscallId::Bind, fd, addr, addrlen) as i32
+}
+
+#[inline(always)]
+pub fn sys_listen(fd: i32, backlog: i32) -> i32 {
+    syscall!(SyscallId::Listen, fd, backlog) as i32
+}
+
+#[inline(always)]
+pub fn sys_accept4(fd: i32, addr: *mut SockAddrUn, addrlen: *mut u32, flags: i32) -> i32 {
+    syscall!(SyscallId::Accept4, fd, addr, addrlen, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_connect(fd: i32, addr: *const SockAddrUn, addrlen: u32) -> i32 {
+    syscall!(SyscallId::Connect, fd, addr, addrlen) as i32
+}
+
+#[inline(always)]
+pub fn sys_sendto(
+    fd: i32,
+    buf: *const u8,
+    len: usize,
+    flags: i32,
+    addr: *const SockAddrUn,
+    addrlen: u32,
+) -> isize {
+    syscall!(SyscallId::Sendto, fd, buf, len, flags, addr, addrlen) as isize
+}
+
+#[inline(always)]
+pub fn sys_recvfrom(
+    fd: i32,
+    buf: *mut u8,
+    len: usize,
+    flags: i32,
+    addr: *mut SockAddrUn,
+    addrlen: *mut u32,
+) -> isize {
+    syscall!(SyscallId::Recvfrom, fd, buf, len, flags, addr, addrlen) as isize
+}
+
+#[inline(always)]
+pub fn sys_sendmsg(fd: i32, msg: *const MsgHdr, flags: i32) -> isize {
+    syscall!(SyscallId::Sendmsg, fd, msg, flags) as isize
+}
+
+#[inline(always)]
+pub fn sys_recvmsg(fd: i32, msg: *mut MsgHdr, flags: i32) -> isize {
+    syscall!(SyscallId::Recvmsg, fd, msg, flags) as isize
+}
+
+#[inline(always)]
+pub fn sys_shutdown(fd: i32, how: i32) -> i32 {
+    syscall!(SyscallId::Shutdown, fd, how) as i32
+}
+
+#[repr(C)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+#[repr(C, packed)]
+pub struct EpollEvent {
+    pub events: u32,
+    pub data: u64,
+}
+
+#[repr(C)]
+pub struct Itimerspec {
+    pub it_interval: Timespec,
+    pub it_value: Timespec,
+}
+
+#[inline(always)]
+pub fn sys_poll(fds: *mut PollFd, nfds: usize, timeout: i32) -> i32 {
+    syscall!(SyscallId::Poll, fds, nfds, timeout) as i32
+}
+
+#[inline(always)]
+pub fn sys_epoll_create1(flags: i32) -> i32 {
+    syscall!(SyscallId::EpollCreate1, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_epoll_ctl(epfd: i32, op: i32, fd: i32, event: *const EpollEvent) -> i32 {
+    syscall!(SyscallId::EpollCtl, epfd, op, fd, event) as i32
+}
+
+#[inline(always)]
+pub fn sys_epoll_wait(epfd: i32, events: *mut EpollEvent, maxevents: i32, timeout: i32) -> i32 {
+    syscall!(SyscallId::EpollWait, epfd, events, maxevents, timeout) as i32
+}
+
+#[inline(always)]
+pub fn sys_eventfd2(initval: u32, flags: i32) -> i32 {
+    syscall!(SyscallId::Eventfd2, initval, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_timerfd_create(clockid: i32, flags: i32) -> i32 {
+    syscall!(SyscallId::TimerfdCreate, clockid, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_timerfd_settime(
+    fd: i32,
+    flags: i32,
+    new_value: *const Itimerspec,
+    old_value: *mut Itimerspec,
+) -> i32 {
+    syscall!(SyscallId::TimerfdSettime, fd, flags, new_value, old_value) as i32
+}
+
+#[inline(always)]
+pub fn sys_timerfd_gettime(fd: i32, curr_value: *mut Itimerspec) -> i32 {
+    syscall!(SyscallId::TimerfdGettime, fd, curr_value) as i32
+}
+
+#[inline(always)]
+pub fn sys_signalfd4(fd: i32, mask: *const u64, sizemask: usize, flags: i32) -> i32 {
+    syscall!(SyscallId::Signalfd4, fd, mask, sizemask, flags) as i32
+}
+
+#[repr(C)]
+pub struct IpcPerm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    pub mode: u32,
+    pub seq: u16,
+    pub _pad: u16,
+    pub _unused: [u64; 2],
+}
+
+#[repr(C)]
+pub struct ShmidDs {
+    pub shm_perm: IpcPerm,
+    pub shm_segsz: u64,
+    pub shm_atime: i64,
+    pub shm_dtime: i64,
+    pub shm_ctime: i64,
+    pub shm_cpid: i32,
+    pub shm_lpid: i32,
+    pub shm_nattch: u64,
+    pub _unused: [u64; 2],
+}
+
+#[inline(always)]
+pub fn sys_mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> i64 {
+    syscall!(SyscallId::Mmap, addr, len, prot, flags, fd, offset) as i64
+}
+
+#[inline(always)]
+pub fn sys_munmap(addr: *mut u8, len: usize) -> i32 {
+    syscall!(SyscallId::Munmap, addr, len) as i32
+}
+
+#[inline(always)]
+pub fn sys_memfd_create(name: *const core::ffi::c_char, flags: u32) -> i32 {
+    syscall!(SyscallId::MemfdCreate, name, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_shmget(key: i32, size: usize, flags: i32) -> i32 {
+    syscall!(SyscallId::Shmget, key, size, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_shmat(shmid: i32, addr: *const u8, flags: i32) -> i64 {
+    syscall!(SyscallId::Shmat, shmid, addr, flags) as i64
+}
+
+#[inline(always)]
+pub fn sys_shmdt(addr: *const u8) -> i32 {
+    syscall!(SyscallId::Shmdt, addr) as i32
+}
+
+#[inline(always)]
+pub fn sys_shmctl(shmid: i32, cmd: i32, buf: *mut ShmidDs) -> i32 {
+    syscall!(SyscallId::Shmctl, shmid, cmd, buf) as i32
+}
+
+#[repr(C)]
+pub struct MqAttr {
+    pub mq_flags: i64,
+    pub mq_maxmsg: i64,
+    pub mq_msgsize: i64,
+    pub mq_curmsgs: i64,
+    pub _reserved: [i64; 4],
+}
+
+#[repr(C)]
+pub struct SigEvent {
+    pub sigev_value: u64,
+    pub sigev_signo: i32,
+    pub sigev_notify: i32,
+    pub _pad: [i32; 12],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SemBuf {
+    pub sem_num: u16,
+    pub sem_op: i16,
+    pub sem_flg: i16,
+}
+
+#[inline(always)]
+pub fn sys_mq_open(
+    name: *const core::ffi::c_char,
+    oflag: i32,
+    mode: u32,
+    attr: *const MqAttr,
+) -> i32 {
+    syscall!(SyscallId::MqOpen, name, oflag, mode, attr) as i32
+}
+
+#[inline(always)]
+pub fn sys_mq_unlink(name: *const core::ffi::c_char) -> i32 {
+    syscall!(SyscallId::MqUnlink, name) as i32
+}
+
+#[inline(always)]
+pub fn sys_mq_timedsend(
+    mqd: i32,
+    msg: *const u8,
+    len: usize,
+    prio: u32,
+    timeout: *const Timespec,
+) -> i32 {
+    syscall!(SyscallId::MqTimedsend, mqd, msg, len, prio, timeout) as i32
+}
+
+#[inline(always)]
+pub fn sys_mq_timedreceive(
+    mqd: i32,
+    msg: *mut u8,
+    len: usize,
+    prio: *mut u32,
+    timeout: *const Timespec,
+) -> isize {
+    syscall!(SyscallId::MqTimedreceive, mqd, msg, len, prio, timeout) as isize
+}
+
+#[inline(always)]
+pub fn sys_mq_notify(mqd: i32, sev: *const SigEvent) -> i32 {
+    syscall!(SyscallId::MqNotify, mqd, sev) as i32
+}
+
+#[inline(always)]
+pub fn sys_mq_getsetattr(mqd: i32, new: *const MqAttr, old: *mut MqAttr) -> i32 {
+    syscall!(SyscallId::MqGetsetattr, mqd, new, old) as i32
+}
+
+#[inline(always)]
+pub fn sys_semget(key: i32, nsems: i32, flags: i32) -> i32 {
+    syscall!(SyscallId::Semget, key, nsems, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_semop(semid: i32, sops: *const SemBuf, nsops: usize) -> i32 {
+    syscall!(SyscallId::Semop, semid, sops, nsops) as i32
+}
+
+#[inline(always)]
+pub fn sys_semtimedop(
+    semid: i32,
+    sops: *const SemBuf,
+    nsops: usize,
+    timeout: *const Timespec,
+) -> i32 {
+    syscall!(SyscallId::Semtimedop, semid, sops, nsops, timeout) as i32
+}
+
+#[inline(always)]
+pub fn sys_semctl(semid: i32, semnum: i32, cmd: i32, arg: u64) -> i32 {
+    syscall!(SyscallId::Semctl, semid, semnum, cmd, arg) as i32
+}
+
+#[inline(always)]
+pub fn sys_mount(
+    source: *const core::ffi::c_char,
+    target: *const core::ffi::c_char,
+    fstype: *const core::ffi::c_char,
+    flags: u64,
+    data: *const core::ffi::c_char,
+) -> i32 {
+    syscall!(SyscallId::Mount, source, target, fstype, flags, data) as i32
+}
+
+#[inline(always)]
+pub fn sys_umount2(target: *const core::ffi::c_char, flags: i32) -> i32 {
+    syscall!(SyscallId::Umount2, target, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_pivot_root(
+    new_root: *const core::ffi::c_char,
+    put_old: *const core::ffi::c_char,
+) -> i32 {
+    syscall!(SyscallId::PivotRoot, new_root, put_old) as i32
+}
+
+#[inline(always)]
+pub fn sys_link(oldpath: *const core::ffi::c_char, newpath: *const core::ffi::c_char) -> i32 {
+    syscall!(SyscallId::Link, oldpath, newpath) as i32
+}
+
+#[inline(always)]
+pub fn sys_linkat(
+    olddirfd: i32,
+    oldpath: *const core::ffi::c_char,
+    newdirfd: i32,
+    newpath: *const core::ffi::c_char,
+    flags: i32,
+) -> i32 {
+    syscall!(
+        SyscallId::Linkat,
+        olddirfd,
+        oldpath,
+        newdirfd,
+        newpath,
+        flags
+    ) as i32
+}
+
+#[inline(always)]
+pub fn sys_lstat(path: *const core::ffi::c_char, buf: *mut StatBuf) -> i32 {
+    syscall!(SyscallId::Lstat, path, buf) as i32
+}
+
+#[inline(always)]
+pub fn sys_symlink(target: *const core::ffi::c_char, linkpath: *const core::ffi::c_char) -> i32 {
+    syscall!(SyscallId::Symlink, target, linkpath) as i32
+}
+
+#[inline(always)]
+pub fn sys_symlinkat(
+    target: *const core::ffi::c_char,
+    newdirfd: i32,
+    linkpath: *const core::ffi::c_char,
+) -> i32 {
+    syscall!(SyscallId::Symlinkat, target, newdirfd, linkpath) as i32
+}
+
+#[inline(always)]
+pub fn sys_readlink(path: *const core::ffi::c_char, buf: *mut u8, size: usize) -> isize {
+    syscall!(SyscallId::Readlink, path, buf, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_readlinkat(
+    dirfd: i32,
+    path: *const core::ffi::c_char,
+    buf: *mut u8,
+    size: usize,
+) -> isize {
+    syscall!(SyscallId::Readlinkat, dirfd, path, buf, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_mknod(path: *const core::ffi::c_char, mode: u32, dev: u64) -> i32 {
+    syscall!(SyscallId::Mknod, path, mode, dev) as i32
+}
+
+#[inline(always)]
+pub fn sys_mknodat(dirfd: i32, path: *const core::ffi::c_char, mode: u32, dev: u64) -> i32 {
+    syscall!(SyscallId::Mknodat, dirfd, path, mode, dev) as i32
+}
+
+#[repr(C)]
+pub struct StatFs {
+    pub f_type: i64,
+    pub f_bsize: i64,
+    pub f_blocks: u64,
+    pub f_bfree: u64,
+    pub f_bavail: u64,
+    pub f_files: u64,
+    pub f_ffree: u64,
+    pub f_fsid: [i32; 2],
+    pub f_namelen: i64,
+    pub f_frsize: i64,
+    pub f_flags: i64,
+    pub f_spare: [i64; 4],
+}
+
+#[inline(always)]
+pub fn sys_statfs(path: *const core::ffi::c_char, buf: *mut StatFs) -> i32 {
+    syscall!(SyscallId::Statfs, path, buf) as i32
+}
+
+#[inline(always)]
+pub fn sys_fstatfs(fd: i32, buf: *mut StatFs) -> i32 {
+    syscall!(SyscallId::Fstatfs, fd, buf) as i32
+}
+
+#[inline(always)]
+pub fn sys_utimensat(
+    dirfd: i32,
+    path: *const core::ffi::c_char,
+    times: *const Timespec,
+    flags: i32,
+) -> i32 {
+    syscall!(SyscallId::Utimensat, dirfd, path, times, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_chmod(path: *const core::ffi::c_char, mode: u32) -> i32 {
+    syscall!(SyscallId::Chmod, path, mode) as i32
+}
+
+#[inline(always)]
+pub fn sys_fchmod(fd: i32, mode: u32) -> i32 {
+    syscall!(SyscallId::Fchmod, fd, mode) as i32
+}
+
+#[inline(always)]
+pub fn sys_chown(path: *const core::ffi::c_char, uid: u32, gid: u32) -> i32 {
+    syscall!(SyscallId::Chown, path, uid, gid) as i32
+}
+
+#[inline(always)]
+pub fn sys_sync() -> i32 {
+    syscall!(SyscallId::Sync) as i32
+}
+
+#[inline(always)]
+pub fn sys_syncfs(fd: i32) -> i32 {
+    syscall!(SyscallId::SyncFs, fd) as i32
+}
+
+#[inline(always)]
+pub fn sys_fsync(fd: i32) -> i32 {
+    syscall!(SyscallId::Fsync, fd) as i32
+}
+
+#[inline(always)]
+pub fn sys_fdatasync(fd: i32) -> i32 {
+    syscall!(SyscallId::Fdatasync, fd) as i32
+}
+
+#[inline(always)]
+pub fn sys_setxattr(
+    path: *const core::ffi::c_char,
+    name: *const core::ffi::c_char,
+    value: *const u8,
+    size: usize,
+    flags: i32,
+) -> i32 {
+    syscall!(SyscallId::Setxattr, path, name, value, size, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_lsetxattr(
+    path: *const core::ffi::c_char,
+    name: *const core::ffi::c_char,
+    value: *const u8,
+    size: usize,
+    flags: i32,
+) -> i32 {
+    syscall!(SyscallId::Lsetxattr, path, name, value, size, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_fsetxattr(
+    fd: i32,
+    name: *const core::ffi::c_char,
+    value: *const u8,
+    size: usize,
+    flags: i32,
+) -> i32 {
+    syscall!(SyscallId::Fsetxattr, fd, name, value, size, flags) as i32
+}
+
+#[inline(always)]
+pub fn sys_getxattr(
+    path: *const core::ffi::c_char,
+    name: *const core::ffi::c_char,
+    value: *mut u8,
+    size: usize,
+) -> isize {
+    syscall!(SyscallId::Getxattr, path, name, value, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_lgetxattr(
+    path: *const core::ffi::c_char,
+    name: *const core::ffi::c_char,
+    value: *mut u8,
+    size: usize,
+) -> isize {
+    syscall!(SyscallId::Lgetxattr, path, name, value, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_fgetxattr(
+    fd: i32,
+    name: *const core::ffi::c_char,
+    value: *mut u8,
+    size: usize,
+) -> isize {
+    syscall!(SyscallId::Fgetxattr, fd, name, value, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_listxattr(path: *const core::ffi::c_char, list: *mut u8, size: usize) -> isize {
+    syscall!(SyscallId::Listxattr, path, list, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_llistxattr(path: *const core::ffi::c_char, list: *mut u8, size: usize) -> isize {
+    syscall!(SyscallId::Llistxattr, path, list, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_flistxattr(fd: i32, list: *mut u8, size: usize) -> isize {
+    syscall!(SyscallId::Flistxattr, fd, list, size) as isize
+}
+
+#[inline(always)]
+pub fn sys_removexattr(path: *const core::ffi::c_char, name: *const core::ffi::c_char) -> i32 {
+    syscall!(SyscallId::Removexattr, path, name) as i32
+}
+
+#[inline(always)]
+pub fn sys_lremovexattr(path: *const core::ffi::c_char, name: *const core::ffi::c_char) -> i32 {
+    syscall!(SyscallId::Lremovexattr, path, name) as i32
+}
+
+#[inline(always)]
+pub fn sys_fremovexattr(fd: i32, name: *const core::ffi::c_char) -> i32 {
+    syscall!(SyscallId::Fremovexattr, fd, name) as i32
+}
+
 #[repr(u64)]
 pub enum SyscallId {
     Read,
@@ -612,7 +1239,7 @@ pub enum SyscallId {
     Fallocate,
     TimerfdSettime,
     TimerfdGettime,
-    Accept6,
+    Accept4,
     Signalfd4,
     Eventfd2,
     EpollCreate1,
@@ -83,6 +83,13 @@ pub fn _start() -> ! {
         }
     }
     crate::device::nvme::init();
+    crate::device::ahci::init();
+    crate::device::virtio::init();
+    crate::device::block::ramdisk::init();
+    crate::device::block::loopdev::init();
+    crate::device::block::partition::select_root();
+    crate::drivers::fs::mount_root();
+    crate::drivers::fs::mount_cdrom();
 
     #[cfg(feature = "tests")]
     crate::tests::init();
@@ -94,6 +101,12 @@ pub fn _start() -> ! {
         "main",
         false,
     );
+    scheduler::thread::spawn(
+        &scheduler::get_proc_by_pid(0).unwrap(),
+        crate::device::block::flush_thread as _,
+        "flush",
+        false,
+    );
     scheduler::start();
 }
 
@@ -160,8 +173,9 @@ pub fn main_thread() -> ! {
     // scheduler::thread::spawn(pid0, serial_thread as _, "serial", false);
 
     {
-        let vfs = get_vfs();
-        if let Some(bin) = vfs.resolve_path(Path::new("/bin/initramfs.elf"))
+        let bin = get_vfs().resolve(Path::new("/bin/initramfs.elf"));
+        let bin = bin.as_ref().map(|bin| bin.node());
+        if let Some(bin) = &bin
             && let Some(elf_data) = bin.read()
         {
             let user_pagemap = Arc::new(Spin::new(crate::memory::vmm::Pagemap::new_user()));
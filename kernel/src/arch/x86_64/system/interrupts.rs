@@ -168,6 +168,20 @@ pub fn install_interrupt(vector: u8, func: fn(&mut Registers)) {
     HANDLERS[vector as usize].store(func as _, Ordering::Release);
 }
 
+// the first free vector above the legacy irqs, for msi and the like
+pub fn allocate_interrupt(func: fn(&mut Registers)) -> Option<u8> {
+    (0x30..0xF0).find(|&vector| {
+        HANDLERS[vector as usize]
+            .compare_exchange(
+                core::ptr::null_mut(),
+                func as _,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    })
+}
+
 pub fn clear_interrupt(vector: u8) {
     HANDLERS[vector as usize].store(0 as _, Ordering::Release);
 }
@@ -17,6 +17,7 @@ use core::sync::atomic::{AtomicU64, Ordering, fence};
 #[allow(dead_code)]
 mod reg {
     pub const APIC_BASE: u32 = 0x1B;
+    pub const ID: u32 = 0x20;
     pub const TPR: u32 = 0x80;
     pub const SIV: u32 = 0xF0;
     pub const ICRL: u32 = 0x300;
@@ -77,6 +78,15 @@ fn lapic_spurious_handler(_stack_frame: &mut Registers) {
     // no eoi
 }
 
+// for interrupts that come through the lapic (msi), the legacy ones go to the pic
+pub fn eoi() {
+    mmio_write(reg::EOI, 0);
+}
+
+pub fn id() -> u32 {
+    mmio_read(reg::ID) >> 24
+}
+
 pub fn arm(ns: usize, vector: u8) {
     let freq = LAPIC_FREQUENCY.load(Ordering::SeqCst);
 
@@ -0,0 +1,224 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use crate::drivers::fs::{InodeRef, NodeMode, get_vfs, now};
+
+use super::{
+    link::{AT_EMPTY_PATH, AT_FDCWD, at_path},
+    *,
+};
+
+const AT_SYMLINK_NOFOLLOW: u64 = 0x100;
+const UTIME_NOW: i64 = (1 << 30) - 1;
+const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+// what to set one timestamp to, None leaves it as it is. whole seconds only
+fn timestamp(ts: &Timespec) -> Result<Option<u64>, i64> {
+    match ts.tv_nsec {
+        UTIME_OMIT => Ok(None),
+        UTIME_NOW => Ok(Some(now())),
+        0..1_000_000_000 => Ok(Some(ts.tv_sec.max(0) as u64)),
+        _ => Err(EINVAL),
+    }
+}
+
+pub(super) fn sys_utimensat(regs: &mut Registers) {
+    let ret = do_utimensat(regs);
+    set_result(regs, ret);
+}
+
+fn do_utimensat(regs: &Registers) -> Result<u64, i64> {
+    let dirfd = regs.rdi as i32;
+    let times = regs.rdx;
+    let flags = regs.r10;
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(EINVAL);
+    }
+
+    let (atime, mtime) = if times == 0 {
+        (Some(now()), Some(now()))
+    } else {
+        if !validate_user_buf(times, 2 * size_of::<Timespec>() as u64) {
+            return Err(EFAULT);
+        }
+        let times = unsafe { &*(times as *const [Timespec; 2]) };
+        (timestamp(&times[0])?, timestamp(&times[1])?)
+    };
+
+    let path = match regs.rsi {
+        0 => None,
+        ptr => Some(validate_user_cstr(ptr).ok_or(EFAULT)?),
+    };
+    let inode = match path {
+        Some(path) if !path.is_empty() || flags & AT_EMPTY_PATH == 0 => {
+            let path = at_path(dirfd, path)?;
+            let vfs = get_vfs();
+            let inode = vfs.walk(&path, flags & AT_SYMLINK_NOFOLLOW == 0)?;
+            if vfs.is_readonly(&path) {
+                return Err(EROFS);
+            }
+            inode
+        }
+        // a null path is futimens, the times go on `dirfd` itself
+        _ => {
+            if dirfd == AT_FDCWD {
+                return Err(EFAULT);
+            }
+            let current = current_process().unwrap();
+            let proc = current.lock();
+            let file = proc.fdt.get(&dirfd).ok_or(EBADF)?;
+            // sockets and the like keep no times
+            let Some(inode) = file.inode().cloned() else {
+                return Ok(0);
+            };
+            drop(proc);
+            if get_vfs()
+                .mount_of_inode(&inode)
+                .is_some_and(|m| m.is_readonly())
+            {
+                return Err(EROFS);
+            }
+            inode
+        }
+    };
+
+    if atime.is_none() && mtime.is_none() {
+        return Ok(0);
+    }
+    let mut node = inode.node_mut();
+    let meta = node.get_metadata_mut();
+    if let Some(atime) = atime {
+        meta.set_accessed_at(atime);
+    }
+    if let Some(mtime) = mtime {
+        meta.modified_at = mtime;
+    }
+    meta.touch_changed();
+    Ok(0)
+}
+
+// the node a path names, for changing it
+fn path_inode(dirfd: i32, path: &str, follow: bool) -> Result<InodeRef, i64> {
+    let path = at_path(dirfd, path)?;
+    let vfs = get_vfs();
+    let inode = vfs.walk(&path, follow)?;
+    if vfs.is_readonly(&path) {
+        return Err(EROFS);
+    }
+    Ok(inode)
+}
+
+// the node behind a descriptor, None for sockets and the like that have nothing to change
+fn fd_inode(fd: i32) -> Result<Option<InodeRef>, i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&fd).ok_or(EBADF)?;
+    let inode = file.inode().cloned();
+    drop(proc);
+    if let Some(inode) = &inode
+        && get_vfs()
+            .mount_of_inode(inode)
+            .is_some_and(|m| m.is_readonly())
+    {
+        return Err(EROFS);
+    }
+    Ok(inode)
+}
+
+// only the permission bits, there's nothing for setuid and friends to do
+fn chmod(inode: Option<InodeRef>, mode: u64) -> Result<u64, i64> {
+    if let Some(inode) = inode {
+        let mut node = inode.node_mut();
+        let meta = node.get_metadata_mut();
+        meta.permissions = NodeMode::from_bits_truncate((mode & 0o777) as i32);
+        meta.touch_changed();
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_chmod(regs: &mut Registers) {
+    let ret = do_chmod(regs);
+    set_result(regs, ret);
+}
+
+fn do_chmod(regs: &Registers) -> Result<u64, i64> {
+    let path = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    chmod(Some(path_inode(AT_FDCWD, path, true)?), regs.rsi)
+}
+
+pub(super) fn sys_fchmod(regs: &mut Registers) {
+    let ret = fd_inode(regs.rdi as i32).and_then(|inode| chmod(inode, regs.rsi));
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_fchmodat(regs: &mut Registers) {
+    let ret = do_fchmodat(regs);
+    set_result(regs, ret);
+}
+
+fn do_fchmodat(regs: &Registers) -> Result<u64, i64> {
+    let path = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    chmod(Some(path_inode(regs.rdi as i32, path, true)?), regs.rdx)
+}
+
+// -1 leaves that one as it is
+fn chown(inode: Option<InodeRef>, uid: u64, gid: u64) -> Result<u64, i64> {
+    let (uid, gid) = (uid as u32, gid as u32);
+    if let Some(inode) = inode {
+        let mut node = inode.node_mut();
+        let meta = node.get_metadata_mut();
+        if uid != u32::MAX {
+            meta.uid = uid;
+        }
+        if gid != u32::MAX {
+            meta.gid = gid;
+        }
+        meta.touch_changed();
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_chown(regs: &mut Registers) {
+    let ret = do_chown(regs, true);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_lchown(regs: &mut Registers) {
+    let ret = do_chown(regs, false);
+    set_result(regs, ret);
+}
+
+fn do_chown(regs: &Registers, follow: bool) -> Result<u64, i64> {
+    let path = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    chown(
+        Some(path_inode(AT_FDCWD, path, follow)?),
+        regs.rsi,
+        regs.rdx,
+    )
+}
+
+pub(super) fn sys_fchown(regs: &mut Registers) {
+    let ret = fd_inode(regs.rdi as i32).and_then(|inode| chown(inode, regs.rsi, regs.rdx));
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_fchownat(regs: &mut Registers) {
+    let ret = do_fchownat(regs);
+    set_result(regs, ret);
+}
+
+fn do_fchownat(regs: &Registers) -> Result<u64, i64> {
+    let dirfd = regs.rdi as i32;
+    let flags = regs.r8;
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(EINVAL);
+    }
+    let path = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    let inode = match path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        true => fd_inode(dirfd)?,
+        false => Some(path_inode(dirfd, path, flags & AT_SYMLINK_NOFOLLOW == 0)?),
+    };
+    chown(inode, regs.rdx, regs.r10)
+}
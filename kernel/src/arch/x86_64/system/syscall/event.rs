@@ -0,0 +1,183 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::sync::Arc;
+
+use crate::ipc::{
+    eventfd::EventFd,
+    signalfd::SignalFd,
+    timerfd::{Clock, TimerFd},
+};
+
+use super::*;
+
+// shared by the EFD_, TFD_ and SFD_ flags
+const O_NONBLOCK: u64 = 0o4000;
+const O_CLOEXEC: u64 = 0o2000000;
+
+const EFD_SEMAPHORE: u64 = 1;
+
+const TFD_TIMER_ABSTIME: u64 = 1;
+const TFD_TIMER_CANCEL_ON_SET: u64 = 2;
+
+#[repr(C)]
+struct Itimerspec {
+    it_interval: Timespec,
+    it_value: Timespec,
+}
+
+// saturates, a timer that far out never goes off either way
+fn timespec_to_ns(ts: &Timespec) -> Result<u64, i64> {
+    if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec >= 1_000_000_000 {
+        return Err(EINVAL);
+    }
+    Ok((ts.tv_sec as u64)
+        .saturating_mul(1_000_000_000)
+        .saturating_add(ts.tv_nsec as u64))
+}
+
+fn ns_to_timespec(ns: u64) -> Timespec {
+    Timespec {
+        tv_sec: (ns / 1_000_000_000) as i64,
+        tv_nsec: (ns % 1_000_000_000) as i64,
+    }
+}
+
+// looks up an fd that has to be a specific kind of object, EINVAL otherwise
+fn get_object<T: FileObject>(fd: u64) -> Result<Arc<T>, i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&(fd as i32)).ok_or(EBADF)?;
+    file.object_as::<T>().ok_or(EINVAL)
+}
+
+pub(super) fn sys_eventfd(regs: &mut Registers) {
+    let ret = do_eventfd(regs.rdi, 0);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_eventfd2(regs: &mut Registers) {
+    let ret = do_eventfd(regs.rdi, regs.rsi);
+    set_result(regs, ret);
+}
+
+fn do_eventfd(initval: u64, flags: u64) -> Result<u64, i64> {
+    if flags & !(EFD_SEMAPHORE | O_NONBLOCK | O_CLOEXEC) != 0 {
+        return Err(EINVAL);
+    }
+    let eventfd = EventFd::new(initval as u32 as u64, flags & EFD_SEMAPHORE != 0);
+    let file = FileDescriptor::from_object(eventfd, Permissions::RW)
+        .with_nonblock(flags & O_NONBLOCK != 0);
+    Ok(install_fd(file) as _)
+}
+
+pub(super) fn sys_timerfd_create(regs: &mut Registers) {
+    let ret = do_timerfd_create(regs);
+    set_result(regs, ret);
+}
+
+fn do_timerfd_create(regs: &Registers) -> Result<u64, i64> {
+    let clock = match regs.rdi {
+        CLOCK_REALTIME => Clock::Realtime,
+        CLOCK_MONOTONIC | CLOCK_BOOTTIME => Clock::Monotonic,
+        _ => return Err(EINVAL),
+    };
+    let flags = regs.rsi;
+    if flags & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+        return Err(EINVAL);
+    }
+    let file = FileDescriptor::from_object(TimerFd::new(clock), Permissions::READ)
+        .with_nonblock(flags & O_NONBLOCK != 0);
+    Ok(install_fd(file) as _)
+}
+
+pub(super) fn sys_timerfd_settime(regs: &mut Registers) {
+    let ret = do_timerfd_settime(regs);
+    set_result(regs, ret);
+}
+
+fn do_timerfd_settime(regs: &Registers) -> Result<u64, i64> {
+    let flags = regs.rsi;
+    let new_value = regs.rdx;
+    let old_value = regs.r10;
+
+    if flags & !(TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET) != 0 {
+        return Err(EINVAL);
+    }
+    let size = size_of::<Itimerspec>() as u64;
+    if new_value == 0 || !validate_user_buf(new_value, size) {
+        return Err(EFAULT);
+    }
+    if old_value != 0 && !validate_user_buf(old_value, size) {
+        return Err(EFAULT);
+    }
+
+    let timer = get_object::<TimerFd>(regs.rdi)?;
+    let new = unsafe { &*(new_value as *const Itimerspec) };
+    let value = timespec_to_ns(&new.it_value)?;
+    let interval = timespec_to_ns(&new.it_interval)?;
+
+    let (old_remaining, old_interval) = timer.set(value, interval, flags & TFD_TIMER_ABSTIME != 0);
+    if old_value != 0 {
+        unsafe {
+            *(old_value as *mut Itimerspec) = Itimerspec {
+                it_interval: ns_to_timespec(old_interval),
+                it_value: ns_to_timespec(old_remaining),
+            };
+        }
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_timerfd_gettime(regs: &mut Registers) {
+    let ret = do_timerfd_gettime(regs);
+    set_result(regs, ret);
+}
+
+fn do_timerfd_gettime(regs: &Registers) -> Result<u64, i64> {
+    let curr_value = regs.rsi;
+    if !validate_user_buf(curr_value, size_of::<Itimerspec>() as _) {
+        return Err(EFAULT);
+    }
+
+    let timer = get_object::<TimerFd>(regs.rdi)?;
+    let (remaining, interval) = timer.get();
+    unsafe {
+        *(curr_value as *mut Itimerspec) = Itimerspec {
+            it_interval: ns_to_timespec(interval),
+            it_value: ns_to_timespec(remaining),
+        };
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_signalfd(regs: &mut Registers) {
+    let ret = do_signalfd(regs.rdi, regs.rsi, regs.rdx, 0);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_signalfd4(regs: &mut Registers) {
+    let ret = do_signalfd(regs.rdi, regs.rsi, regs.rdx, regs.r10);
+    set_result(regs, ret);
+}
+
+fn do_signalfd(fd: u64, mask: u64, sizemask: u64, flags: u64) -> Result<u64, i64> {
+    if sizemask != 8 || flags & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+        return Err(EINVAL);
+    }
+    if mask == 0 || !validate_user_buf(mask, 8) {
+        return Err(EFAULT);
+    }
+    let mask = unsafe { *(mask as *const u64) };
+
+    // -1 makes a new one, anything else updates the mask of an existing signalfd
+    if fd as i32 == -1 {
+        let file = FileDescriptor::from_object(SignalFd::new(mask), Permissions::READ)
+            .with_nonblock(flags & O_NONBLOCK != 0);
+        return Ok(install_fd(file) as _);
+    }
+    get_object::<SignalFd>(fd)?.set_mask(mask);
+    Ok(fd)
+}
@@ -288,7 +288,7 @@ pub enum SyscallId {
     Fallocate,
     TimerfdSettime,
     TimerfdGettime,
-    Accept6,
+    Accept4,
     Signalfd4,
     Eventfd2,
     EpollCreate1,
@@ -0,0 +1,416 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::sync::Arc;
+
+use crate::{
+    arch::drivers::time::{preferred_timer_ns, rtc::read_rtc},
+    ipc::{
+        mqueue::{self, MessageQueue, Notify},
+        sem::{SEMAPHORES, SEMOPM, SEMVMX, SemOp, semop},
+        shm::now,
+    },
+    scheduler::signal::NSIG,
+};
+
+use super::{
+    poll::{deadline_after, read_timeout},
+    *,
+};
+
+pub(super) const IPC_CREAT: u64 = 0o1000;
+pub(super) const IPC_EXCL: u64 = 0o2000;
+pub(super) const IPC_RMID: u64 = 0;
+pub(super) const IPC_SET: u64 = 1;
+pub(super) const IPC_STAT: u64 = 2;
+// glibc ors this into every ctl command
+pub(super) const IPC_64: u64 = 0x100;
+
+const GETPID: u64 = 11;
+const GETVAL: u64 = 12;
+const GETALL: u64 = 13;
+const GETNCNT: u64 = 14;
+const GETZCNT: u64 = 15;
+const SETVAL: u64 = 16;
+const SETALL: u64 = 17;
+
+const O_ACCMODE: u64 = 0o3;
+const O_CREAT: u64 = 0o100;
+const O_EXCL: u64 = 0o200;
+const O_NONBLOCK: u64 = 0o4000;
+
+const SIGEV_SIGNAL: i32 = 0;
+const SIGEV_NONE: i32 = 1;
+
+// struct ipc64_perm
+#[repr(C)]
+pub(super) struct IpcPerm {
+    key: i32,
+    uid: u32,
+    gid: u32,
+    cuid: u32,
+    cgid: u32,
+    pub(super) mode: u32,
+    seq: u16,
+    _pad: u16,
+    _unused: [u64; 2],
+}
+
+impl IpcPerm {
+    // everything is owned by root for now
+    pub(super) fn new(key: i64, mode: u32) -> Self {
+        Self {
+            key: key as i32,
+            uid: 0,
+            gid: 0,
+            cuid: 0,
+            cgid: 0,
+            mode,
+            seq: 0,
+            _pad: 0,
+            _unused: [0; 2],
+        }
+    }
+}
+
+#[repr(C)]
+struct SemidDs {
+    sem_perm: IpcPerm,
+    sem_otime: i64,
+    _unused1: u64,
+    sem_ctime: i64,
+    _unused2: u64,
+    sem_nsems: u64,
+    _unused3: u64,
+    _unused4: u64,
+}
+
+#[repr(C)]
+struct MqAttr {
+    mq_flags: i64,
+    mq_maxmsg: i64,
+    mq_msgsize: i64,
+    mq_curmsgs: i64,
+    _reserved: [i64; 4],
+}
+
+#[repr(C)]
+struct SigEvent {
+    sigev_value: u64,
+    sigev_signo: i32,
+    sigev_notify: i32,
+    _pad: [i32; 12],
+}
+
+fn current_pid() -> u64 {
+    current_process().unwrap().lock().get_pid()
+}
+
+// mq timeouts are absolute CLOCK_REALTIME, the rtc only has whole seconds
+fn realtime_deadline(ts: u64) -> Result<Option<u64>, i64> {
+    let realtime = read_rtc().to_epoch().unwrap_or(0) * 1_000_000_000;
+    Ok(read_timeout(ts)?
+        .map(|abs| preferred_timer_ns().saturating_add(abs.saturating_sub(realtime))))
+}
+
+// the queue behind an mq fd and whether that fd is nonblocking
+fn get_queue(fd: u64, perms: Permissions) -> Result<(Arc<MessageQueue>, bool), i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&(fd as i32)).ok_or(EBADF)?;
+    let queue = file.object_as::<MessageQueue>().ok_or(EBADF)?;
+    if !file.permissions.contains(perms) {
+        return Err(EBADF);
+    }
+    Ok((queue, file.nonblock))
+}
+
+pub(super) fn sys_mq_open(regs: &mut Registers) {
+    let ret = do_mq_open(regs);
+    set_result(regs, ret);
+}
+
+fn do_mq_open(regs: &Registers) -> Result<u64, i64> {
+    let name = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let oflag = regs.rsi;
+    let attr = regs.r10;
+
+    let perms = match oflag & O_ACCMODE {
+        0 => Permissions::READ,
+        1 => Permissions::WRITE,
+        2 => Permissions::RW,
+        _ => return Err(EINVAL),
+    };
+    let attr = if oflag & O_CREAT != 0 && attr != 0 {
+        if !validate_user_buf(attr, size_of::<MqAttr>() as _) {
+            return Err(EFAULT);
+        }
+        let attr = unsafe { &*(attr as *const MqAttr) };
+        if attr.mq_maxmsg <= 0 || attr.mq_msgsize <= 0 {
+            return Err(EINVAL);
+        }
+        Some((attr.mq_maxmsg as usize, attr.mq_msgsize as usize))
+    } else {
+        None
+    };
+
+    let queue = mqueue::open(name, oflag & O_CREAT != 0, oflag & O_EXCL != 0, attr)?;
+    let mut file = FileDescriptor::from_object(queue, perms);
+    file.nonblock = oflag & O_NONBLOCK != 0;
+    Ok(install_fd(file) as _)
+}
+
+pub(super) fn sys_mq_unlink(regs: &mut Registers) {
+    let ret = validate_user_cstr(regs.rdi)
+        .ok_or(EFAULT)
+        .and_then(mqueue::unlink)
+        .map(|_| 0);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_mq_timedsend(regs: &mut Registers) {
+    let ret = do_mq_timedsend(regs);
+    set_result(regs, ret);
+}
+
+fn do_mq_timedsend(regs: &Registers) -> Result<u64, i64> {
+    let (msg, len, prio) = (regs.rsi, regs.rdx, regs.r10 as u32);
+    let (queue, nonblock) = get_queue(regs.rdi, Permissions::WRITE)?;
+    if !validate_user_buf(msg, len) {
+        return Err(EFAULT);
+    }
+    let deadline = realtime_deadline(regs.r8)?;
+    let msg = unsafe { core::slice::from_raw_parts(msg as *const u8, len as usize) };
+    queue.send(msg, prio, nonblock, deadline)?;
+    Ok(0)
+}
+
+pub(super) fn sys_mq_timedreceive(regs: &mut Registers) {
+    let ret = do_mq_timedreceive(regs);
+    set_result(regs, ret);
+}
+
+fn do_mq_timedreceive(regs: &Registers) -> Result<u64, i64> {
+    let (buf, len, prio_ptr) = (regs.rsi, regs.rdx, regs.r10);
+    let (queue, nonblock) = get_queue(regs.rdi, Permissions::READ)?;
+    if !validate_user_buf(buf, len) || !validate_user_buf(prio_ptr, size_of::<u32>() as _) {
+        return Err(EFAULT);
+    }
+    let deadline = realtime_deadline(regs.r8)?;
+    let (msg, prio) = queue.receive(len as usize, nonblock, deadline)?;
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(msg.as_ptr(), buf as *mut u8, msg.len());
+        if prio_ptr != 0 {
+            *(prio_ptr as *mut u32) = prio;
+        }
+    }
+    Ok(msg.len() as _)
+}
+
+pub(super) fn sys_mq_notify(regs: &mut Registers) {
+    let ret = do_mq_notify(regs);
+    set_result(regs, ret);
+}
+
+fn do_mq_notify(regs: &Registers) -> Result<u64, i64> {
+    let (queue, _) = get_queue(regs.rdi, Permissions::empty())?;
+    let sev = regs.rsi;
+    let pid = current_pid();
+    if sev == 0 {
+        queue.notify(pid, None)?;
+        return Ok(0);
+    }
+
+    if !validate_user_buf(sev, size_of::<SigEvent>() as _) {
+        return Err(EFAULT);
+    }
+    let sev = unsafe { &*(sev as *const SigEvent) };
+    let signo = match sev.sigev_notify {
+        SIGEV_NONE => 0,
+        SIGEV_SIGNAL if (1..=NSIG as i32).contains(&sev.sigev_signo) => sev.sigev_signo as u32,
+        // SIGEV_THREAD needs a netlink socket to hand the notification to libc
+        _ => return Err(EINVAL),
+    };
+    queue.notify(
+        pid,
+        Some(Notify {
+            pid,
+            signo,
+            value: sev.sigev_value,
+        }),
+    )?;
+    Ok(0)
+}
+
+pub(super) fn sys_mq_getsetattr(regs: &mut Registers) {
+    let ret = do_mq_getsetattr(regs);
+    set_result(regs, ret);
+}
+
+fn do_mq_getsetattr(regs: &Registers) -> Result<u64, i64> {
+    let (new, old) = (regs.rsi, regs.rdx);
+    if !validate_user_buf(new, size_of::<MqAttr>() as _)
+        || !validate_user_buf(old, size_of::<MqAttr>() as _)
+    {
+        return Err(EFAULT);
+    }
+
+    let current = current_process().unwrap();
+    let mut proc = current.lock();
+    let file = proc.fdt.get_mut(&(regs.rdi as i32)).ok_or(EBADF)?;
+    let queue = file.object_as::<MessageQueue>().ok_or(EBADF)?;
+    if old != 0 {
+        let attr = MqAttr {
+            mq_flags: if file.nonblock { O_NONBLOCK as _ } else { 0 },
+            mq_maxmsg: queue.maxmsg as _,
+            mq_msgsize: queue.msgsize as _,
+            mq_curmsgs: queue.len() as _,
+            _reserved: [0; 4],
+        };
+        unsafe { *(old as *mut MqAttr) = attr };
+    }
+    // only O_NONBLOCK can be changed, the rest is fixed at creation
+    if new != 0 {
+        let attr = unsafe { &*(new as *const MqAttr) };
+        if attr.mq_flags as u64 & !O_NONBLOCK != 0 {
+            return Err(EINVAL);
+        }
+        file.nonblock = attr.mq_flags as u64 & O_NONBLOCK != 0;
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_semget(regs: &mut Registers) {
+    let (key, nsems, flags) = (regs.rdi as i32 as i64, regs.rsi as i32, regs.rdx);
+    let ret = match nsems {
+        ..0 => Err(EINVAL),
+        _ => SEMAPHORES.lock().get(
+            key,
+            nsems as usize,
+            flags as u32,
+            flags & IPC_CREAT != 0,
+            flags & IPC_EXCL != 0,
+        ),
+    };
+    set_result(regs, ret.map(|id| id as u64));
+}
+
+pub(super) fn sys_semop(regs: &mut Registers) {
+    let ret = do_semtimedop(regs.rdi, regs.rsi, regs.rdx, None);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_semtimedop(regs: &mut Registers) {
+    let ret = read_timeout(regs.r10)
+        .and_then(|timeout| do_semtimedop(regs.rdi, regs.rsi, regs.rdx, timeout));
+    set_result(regs, ret);
+}
+
+fn do_semtimedop(id: u64, sops: u64, nsops: u64, timeout: Option<u64>) -> Result<u64, i64> {
+    if nsops as usize > SEMOPM {
+        return Err(E2BIG);
+    }
+    if !validate_user_buf(sops, nsops * size_of::<SemOp>() as u64) {
+        return Err(EFAULT);
+    }
+    let ops = match nsops {
+        0 => &[],
+        _ => unsafe { core::slice::from_raw_parts(sops as *const SemOp, nsops as usize) },
+    };
+    semop(id as i32, ops, current_pid(), deadline_after(timeout))?;
+    Ok(0)
+}
+
+pub(super) fn sys_semctl(regs: &mut Registers) {
+    let ret = do_semctl(regs);
+    set_result(regs, ret);
+}
+
+fn do_semctl(regs: &Registers) -> Result<u64, i64> {
+    let id = regs.rdi as i32;
+    let num = regs.rsi as usize;
+    let cmd = regs.rdx & !IPC_64;
+    // union semun is passed by value, either an int or a pointer
+    let arg = regs.r10;
+
+    let mut sets = SEMAPHORES.lock();
+    if cmd == IPC_RMID {
+        sets.remove(id)?;
+        return Ok(0);
+    }
+    let set = sets.set(id)?;
+    let nsems = set.sems.len();
+
+    let ret = match cmd {
+        IPC_STAT => {
+            if !validate_user_buf(arg, size_of::<SemidDs>() as _) {
+                return Err(EFAULT);
+            }
+            let ds = SemidDs {
+                sem_perm: IpcPerm::new(set.key, set.mode),
+                sem_otime: set.op_time as i64,
+                _unused1: 0,
+                sem_ctime: set.change_time as i64,
+                _unused2: 0,
+                sem_nsems: nsems as u64,
+                _unused3: 0,
+                _unused4: 0,
+            };
+            unsafe { *(arg as *mut SemidDs) = ds };
+            0
+        }
+        IPC_SET => {
+            if !validate_user_buf(arg, size_of::<SemidDs>() as _) {
+                return Err(EFAULT);
+            }
+            let ds = unsafe { &*(arg as *const SemidDs) };
+            set.mode = ds.sem_perm.mode & 0o777;
+            set.change_time = now();
+            0
+        }
+        GETALL => {
+            if !validate_user_buf(arg, (nsems * size_of::<u16>()) as _) {
+                return Err(EFAULT);
+            }
+            let values = unsafe { core::slice::from_raw_parts_mut(arg as *mut u16, nsems) };
+            for (value, sem) in values.iter_mut().zip(&set.sems) {
+                *value = sem.value as u16;
+            }
+            0
+        }
+        SETALL => {
+            if !validate_user_buf(arg, (nsems * size_of::<u16>()) as _) {
+                return Err(EFAULT);
+            }
+            let values = unsafe { core::slice::from_raw_parts(arg as *const u16, nsems) };
+            if values.iter().any(|&v| v as i32 > SEMVMX) {
+                return Err(ERANGE);
+            }
+            let pid = current_pid();
+            for (i, &value) in values.iter().enumerate() {
+                set.set_value(i, value as i32, pid)?;
+            }
+            set.wq.wake_all();
+            0
+        }
+        GETVAL | GETPID | GETNCNT | GETZCNT | SETVAL => {
+            let sem = set.sems.get(num).ok_or(EINVAL)?;
+            match cmd {
+                GETVAL => sem.value as u64,
+                GETPID => sem.pid,
+                GETNCNT => sem.ncnt as u64,
+                GETZCNT => sem.zcnt as u64,
+                _ => {
+                    set.set_value(num, arg as i32, current_pid())?;
+                    set.wq.wake_all();
+                    0
+                }
+            }
+        }
+        _ => return Err(EINVAL),
+    };
+    Ok(ret)
+}
@@ -0,0 +1,257 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use crate::drivers::fs::{
+    DeviceNode, Inode, InodeRef, NodeMode, Symlink, VfsNodeMetadataExt, VfsNodeType, get_vfs,
+};
+
+use super::*;
+
+pub(super) const AT_FDCWD: i32 = -100;
+const AT_SYMLINK_FOLLOW: u64 = 0x400;
+pub(super) const AT_EMPTY_PATH: u64 = 0x1000;
+
+const S_IFMT: u64 = 0o170000;
+const S_IFSOCK: u64 = 0o140000;
+const S_IFREG: u64 = 0o100000;
+const S_IFBLK: u64 = 0o060000;
+const S_IFCHR: u64 = 0o020000;
+const S_IFIFO: u64 = 0o010000;
+
+// a path for the *at syscalls, relative ones start at `dirfd` instead of the cwd
+pub(super) fn at_path(dirfd: i32, path: &str) -> Result<Path, i64> {
+    if path.is_empty() {
+        return Err(ENOENT);
+    }
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    if path.starts_with('/') || dirfd == AT_FDCWD {
+        return Ok(resolve_path(path, proc.get_cwd()));
+    }
+    let file = proc.fdt.get(&dirfd).ok_or(EBADF)?;
+    match (file.node(), &file.path) {
+        (Some(node), Some(dir)) if node.is_dir() => Ok(resolve_path(path, dir)),
+        _ => Err(ENOTDIR),
+    }
+}
+
+// gives `inode` another name at `new`
+fn link(inode: InodeRef, new: &Path) -> Result<u64, i64> {
+    if inode.node().is_dir() {
+        return Err(EPERM);
+    }
+    // unlinked files can't come back
+    if inode.nlink() == 0 {
+        return Err(ENOENT);
+    }
+
+    let vfs = get_vfs();
+    if vfs.resolve(new.clone()).is_some() {
+        return Err(EEXIST);
+    }
+    if vfs.is_readonly(new) {
+        return Err(EROFS);
+    }
+    if vfs.device_of_inode(&inode) != vfs.device_of(new) {
+        return Err(EXDEV);
+    }
+    let parent = vfs.resolve(new.get_parent()).ok_or(ENOENT)?;
+    drop(vfs);
+    let mut parent = parent.node_mut();
+    if !parent.is_dir() {
+        return Err(ENOTDIR);
+    }
+    parent.link(new.get_name(), inode)?;
+    Ok(0)
+}
+
+pub(super) fn sys_link(regs: &mut Registers) {
+    let ret = do_link(regs);
+    set_result(regs, ret);
+}
+
+fn do_link(regs: &Registers) -> Result<u64, i64> {
+    let old = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let new = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    let old = at_path(AT_FDCWD, old)?;
+    let new = at_path(AT_FDCWD, new)?;
+
+    // the link itself gets the new name, not what it points to
+    let inode = get_vfs().walk(&old, false)?;
+    link(inode, &new)
+}
+
+pub(super) fn sys_linkat(regs: &mut Registers) {
+    let ret = do_linkat(regs);
+    set_result(regs, ret);
+}
+
+fn do_linkat(regs: &Registers) -> Result<u64, i64> {
+    let old_dirfd = regs.rdi as i32;
+    let old = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    let new_dirfd = regs.rdx as i32;
+    let new = validate_user_cstr(regs.r10).ok_or(EFAULT)?;
+    let flags = regs.r8;
+
+    if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(EINVAL);
+    }
+    let new = at_path(new_dirfd, new)?;
+
+    // AT_EMPTY_PATH links whatever `old_dirfd` has open
+    let inode = if old.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        let current = current_process().unwrap();
+        let proc = current.lock();
+        let file = proc.fdt.get(&old_dirfd).ok_or(EBADF)?;
+        file.inode().cloned().ok_or(ENOENT)?
+    } else {
+        let old = at_path(old_dirfd, old)?;
+        let follow = flags & AT_SYMLINK_FOLLOW != 0;
+        get_vfs().walk(&old, follow)?
+    };
+    link(inode, &new)
+}
+
+fn symlink(target: &str, path: &Path) -> Result<u64, i64> {
+    if target.is_empty() {
+        return Err(ENOENT);
+    }
+    let vfs = get_vfs();
+    if vfs.resolve_nofollow(path.clone()).is_some() {
+        return Err(EEXIST);
+    }
+    if vfs.is_readonly(path) {
+        return Err(EROFS);
+    }
+    let parent = vfs.resolve(path.get_parent()).ok_or(ENOENT)?;
+    drop(vfs);
+    let mut parent = parent.node_mut();
+    if !parent.is_dir() {
+        return Err(ENOTDIR);
+    }
+    parent.link(path.get_name(), Inode::new(Symlink::new(target)))?;
+    Ok(0)
+}
+
+pub(super) fn sys_symlink(regs: &mut Registers) {
+    let ret = do_symlink(regs);
+    set_result(regs, ret);
+}
+
+fn do_symlink(regs: &Registers) -> Result<u64, i64> {
+    let target = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let path = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    symlink(target, &at_path(AT_FDCWD, path)?)
+}
+
+pub(super) fn sys_symlinkat(regs: &mut Registers) {
+    let ret = do_symlinkat(regs);
+    set_result(regs, ret);
+}
+
+fn do_symlinkat(regs: &Registers) -> Result<u64, i64> {
+    let target = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let dirfd = regs.rsi as i32;
+    let path = validate_user_cstr(regs.rdx).ok_or(EFAULT)?;
+    symlink(target, &at_path(dirfd, path)?)
+}
+
+// regular files and device nodes. there are no fifos, and sockets only come from bind
+fn mknod(path: &Path, mode: u64, dev: u64) -> Result<u64, i64> {
+    let device = match mode & S_IFMT {
+        0 | S_IFREG => None,
+        S_IFCHR => Some(VfsNodeType::CharDevice),
+        S_IFBLK => Some(VfsNodeType::BlockDevice),
+        S_IFIFO | S_IFSOCK => return Err(EPERM),
+        _ => return Err(EINVAL),
+    };
+    let permissions = NodeMode::from_bits_truncate((mode & 0o777) as i32);
+
+    let vfs = get_vfs();
+    if vfs.resolve_nofollow(path.clone()).is_some() {
+        return Err(EEXIST);
+    }
+    if vfs.is_readonly(path) {
+        return Err(EROFS);
+    }
+    let parent = vfs.resolve(path.get_parent()).ok_or(ENOENT)?;
+    drop(vfs);
+    let mut parent = parent.node_mut();
+    if !parent.is_dir() {
+        return Err(ENOTDIR);
+    }
+    let created = match device {
+        // /dev/shm makes shm files, so let the directory pick
+        None => parent.create_file(path.get_name())?,
+        Some(type_) => {
+            let inode = Inode::new(DeviceNode::new(type_, dev));
+            // disk filesystems link a node of their own, so the mode goes on what's there
+            let name = path.get_name();
+            parent.link(name, inode)?;
+            parent.get_child(name).ok_or(EEXIST)?
+        }
+    };
+    created.with_permissions(permissions);
+    Ok(0)
+}
+
+pub(super) fn sys_mknod(regs: &mut Registers) {
+    let ret = do_mknod(regs);
+    set_result(regs, ret);
+}
+
+fn do_mknod(regs: &Registers) -> Result<u64, i64> {
+    let path = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    mknod(&at_path(AT_FDCWD, path)?, regs.rsi, regs.rdx)
+}
+
+pub(super) fn sys_mknodat(regs: &mut Registers) {
+    let ret = do_mknodat(regs);
+    set_result(regs, ret);
+}
+
+fn do_mknodat(regs: &Registers) -> Result<u64, i64> {
+    let dirfd = regs.rdi as i32;
+    let path = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    mknod(&at_path(dirfd, path)?, regs.rdx, regs.r10)
+}
+
+// no terminating nul, truncated to `size` like on linux
+fn readlink(path: &Path, buf: u64, size: u64) -> Result<u64, i64> {
+    if size as i64 <= 0 {
+        return Err(EINVAL);
+    }
+    if !validate_user_buf(buf, size) {
+        return Err(EFAULT);
+    }
+    let inode = get_vfs().walk(path, false)?;
+    let node = inode.node();
+    let target = node.readlink().ok_or(EINVAL)?.as_bytes();
+    let n = target.len().min(size as usize);
+    let dst = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, n) };
+    dst.copy_from_slice(&target[..n]);
+    Ok(n as u64)
+}
+
+pub(super) fn sys_readlink(regs: &mut Registers) {
+    let ret = do_readlink(regs);
+    set_result(regs, ret);
+}
+
+fn do_readlink(regs: &Registers) -> Result<u64, i64> {
+    let path = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    readlink(&at_path(AT_FDCWD, path)?, regs.rsi, regs.rdx)
+}
+
+pub(super) fn sys_readlinkat(regs: &mut Registers) {
+    let ret = do_readlinkat(regs);
+    set_result(regs, ret);
+}
+
+fn do_readlinkat(regs: &Registers) -> Result<u64, i64> {
+    let dirfd = regs.rdi as i32;
+    let path = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    readlink(&at_path(dirfd, path)?, regs.rdx, regs.r10)
+}
@@ -0,0 +1,378 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    ipc::shm::{MemFd, SEGMENTS, now},
+    memory::{
+        mmap::{Backing, MMAP_END, Mapping},
+        shared::{MappedMemory, SharedMemory},
+        vmm::flag,
+    },
+};
+
+use super::{
+    ipc::{IPC_64, IPC_CREAT, IPC_EXCL, IPC_RMID, IPC_SET, IPC_STAT, IpcPerm},
+    *,
+};
+
+const PAGE: u64 = page_size::SMALL;
+
+const PROT_READ: u64 = 0x1;
+const PROT_WRITE: u64 = 0x2;
+const PROT_EXEC: u64 = 0x4;
+
+const MAP_SHARED: u64 = 0x01;
+const MAP_PRIVATE: u64 = 0x02;
+const MAP_SHARED_VALIDATE: u64 = 0x03;
+const MAP_TYPE: u64 = 0x0f;
+const MAP_FIXED: u64 = 0x10;
+const MAP_ANONYMOUS: u64 = 0x20;
+
+const MFD_CLOEXEC: u64 = 0x1;
+const MFD_ALLOW_SEALING: u64 = 0x2;
+const MFD_NAME_MAX: usize = 249;
+
+const SHM_RDONLY: u64 = 0o10000;
+const SHM_RND: u64 = 0o20000;
+const SHM_EXEC: u64 = 0o100000;
+
+#[repr(C)]
+struct ShmidDs {
+    shm_perm: IpcPerm,
+    shm_segsz: u64,
+    shm_atime: i64,
+    shm_dtime: i64,
+    shm_ctime: i64,
+    shm_cpid: i32,
+    shm_lpid: i32,
+    shm_nattch: u64,
+    _unused: [u64; 2],
+}
+
+fn page_flags(prot: u64) -> u64 {
+    if prot & (PROT_READ | PROT_WRITE | PROT_EXEC) == 0 {
+        // PROT_NONE reserves the range without mapping anything
+        return 0;
+    }
+    let mut flags = flag::PRESENT | flag::USER;
+    if prot & PROT_WRITE != 0 {
+        flags |= flag::WRITE;
+    }
+    if prot & PROT_EXEC == 0 {
+        flags |= flag::NO_EXEC;
+    }
+    flags
+}
+
+// page aligned, below MMAP_END and never page 0 so null stays a fault
+fn valid_range(addr: u64, len: u64) -> bool {
+    addr != 0
+        && addr.is_multiple_of(PAGE)
+        && addr.checked_add(len).is_some_and(|end| end <= MMAP_END)
+}
+
+// maps into the current process at `addr`, or wherever there's room when it's None
+fn map_current(addr: Option<u64>, mapping: Mapping, data: &[u8]) -> Result<u64, i64> {
+    let current = current_process().unwrap();
+    let mut proc = current.lock();
+    let pagemap = proc.get_pagemap().clone();
+    let mappings = proc.get_mappings_mut();
+    let start = match addr {
+        Some(addr) => addr,
+        None => mappings.find_free(mapping.len).ok_or(ENOMEM)?,
+    };
+    mappings.map(&mut pagemap.lock(), Mapping { start, ..mapping }, data)?;
+    Ok(start)
+}
+
+pub(super) fn sys_mmap(regs: &mut Registers) {
+    let ret = do_mmap(regs);
+    set_result(regs, ret);
+}
+
+fn do_mmap(regs: &Registers) -> Result<u64, i64> {
+    let addr = regs.rdi;
+    let prot = regs.rdx;
+    let flags = regs.r10;
+    let fd = regs.r8 as i32;
+    let offset = regs.r9;
+
+    if regs.rsi == 0 || !offset.is_multiple_of(PAGE) {
+        return Err(EINVAL);
+    }
+    let len = regs.rsi.checked_next_multiple_of(PAGE).ok_or(ENOMEM)?;
+    let shared = match flags & MAP_TYPE {
+        MAP_SHARED | MAP_SHARED_VALIDATE => true,
+        MAP_PRIVATE => false,
+        _ => return Err(EINVAL),
+    };
+    let addr = if flags & MAP_FIXED != 0 {
+        if !valid_range(addr, len) {
+            return Err(EINVAL);
+        }
+        Some(addr)
+    } else {
+        None
+    };
+
+    let mut data = Vec::new();
+    let backing = if flags & MAP_ANONYMOUS != 0 {
+        if shared {
+            Backing::Shared {
+                memory: MappedMemory::new(SharedMemory::new(len as usize)?),
+                offset: 0,
+            }
+        } else {
+            Backing::Private
+        }
+    } else {
+        // reading the file can sleep on a disk, so not under the process lock
+        let file = {
+            let current = current_process().unwrap();
+            let proc = current.lock();
+            proc.fdt.get(&fd).ok_or(EBADF)?.dup()
+        };
+        if !file.permissions.contains(Permissions::READ) {
+            return Err(EACCES);
+        }
+        if shared && prot & PROT_WRITE != 0 && !file.permissions.contains(Permissions::WRITE) {
+            return Err(EACCES);
+        }
+
+        let memory = match (file.node(), file.object()) {
+            (Some(node), _) => node.shared_memory(),
+            (None, Some(object)) => object.shared_memory(),
+            (None, None) => None,
+        };
+        match (shared, memory) {
+            (true, Some(memory)) => Backing::Shared {
+                memory: MappedMemory::new(memory),
+                offset,
+            },
+            (true, None) => return Err(ENODEV),
+            // private file mappings get a copy of the file as it is now
+            (false, memory) => {
+                data = snapshot(&file, memory, offset, len)?;
+                Backing::Private
+            }
+        }
+    };
+
+    let mapping = Mapping {
+        start: 0,
+        len,
+        flags: page_flags(prot),
+        backing,
+        shmid: None,
+    };
+    map_current(addr, mapping, &data)
+}
+
+// the part of the file a private mapping covers, fill() zeroes the pages past its end
+fn snapshot(
+    file: &FileDescriptor,
+    memory: Option<Arc<SharedMemory>>,
+    offset: u64,
+    len: u64,
+) -> Result<Vec<u8>, i64> {
+    let size = match (&memory, file.object(), file.node()) {
+        (Some(memory), ..) => memory.size() as u64,
+        (None, Some(object), _) => match object.size() {
+            Some(size) => size,
+            // devices that can be read in place without a size are endless zeroes
+            // (/dev/zero), which the fresh pages already are
+            None => {
+                object.read_at(offset, &mut []).map_err(|_| ENODEV)?;
+                return Ok(Vec::new());
+            }
+        },
+        (None, None, Some(node)) => node.size(),
+        (None, None, None) => return Err(ENODEV),
+    };
+
+    let want = len.min(size.saturating_sub(offset)) as usize;
+    let mut data = Vec::new();
+    data.try_reserve_exact(want).map_err(|_| ENOMEM)?;
+    data.resize(want, 0);
+    let n = match (memory, file.object(), file.node()) {
+        (Some(memory), ..) => memory.read_at(offset as usize, &mut data),
+        (None, Some(object), _) => object.read_at(offset, &mut data).map_err(|_| ENODEV)?,
+        (None, None, Some(node)) => node.read_at(offset, &mut data).ok_or(EIO)?,
+        (None, None, None) => return Err(ENODEV),
+    };
+    data.truncate(n);
+    Ok(data)
+}
+
+pub(super) fn sys_munmap(regs: &mut Registers) {
+    let addr = regs.rdi;
+    let Some(len) = regs.rsi.checked_next_multiple_of(PAGE) else {
+        regs.rax = -EINVAL as _;
+        return;
+    };
+    if len == 0 || !valid_range(addr, len) {
+        regs.rax = -EINVAL as _;
+        return;
+    }
+
+    let current = current_process().unwrap();
+    let mut proc = current.lock();
+    let pagemap = proc.get_pagemap().clone();
+    proc.get_mappings_mut()
+        .unmap(&mut pagemap.lock(), addr, len);
+    regs.rax = 0;
+}
+
+pub(super) fn sys_memfd_create(regs: &mut Registers) {
+    let ret = do_memfd_create(regs);
+    set_result(regs, ret);
+}
+
+fn do_memfd_create(regs: &Registers) -> Result<u64, i64> {
+    let name = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    if name.len() > MFD_NAME_MAX || regs.rsi & !(MFD_CLOEXEC | MFD_ALLOW_SEALING) != 0 {
+        return Err(EINVAL);
+    }
+    let file = FileDescriptor::from_object(MemFd::new()?, Permissions::RW);
+    Ok(install_fd(file) as _)
+}
+
+pub(super) fn sys_shmget(regs: &mut Registers) {
+    let key = regs.rdi as i32 as i64;
+    let size = regs.rsi as usize;
+    let flags = regs.rdx;
+    let pid = current_process().unwrap().lock().get_pid();
+
+    let ret = SEGMENTS.lock().get(
+        key,
+        size,
+        flags as u32,
+        flags & IPC_CREAT != 0,
+        flags & IPC_EXCL != 0,
+        pid,
+    );
+    set_result(regs, ret.map(|id| id as u64));
+}
+
+pub(super) fn sys_shmat(regs: &mut Registers) {
+    let ret = do_shmat(regs);
+    set_result(regs, ret);
+}
+
+fn do_shmat(regs: &Registers) -> Result<u64, i64> {
+    let id = regs.rdi as i32;
+    let flags = regs.rdx;
+    let mut addr = regs.rsi;
+    if flags & SHM_RND != 0 {
+        addr = align_down(addr, PAGE);
+    }
+
+    let (memory, size) = {
+        let mut segments = SEGMENTS.lock();
+        let segment = segments.segment(id)?;
+        (segment.memory.clone(), segment.size as u64)
+    };
+    let len = size.next_multiple_of(PAGE);
+    let addr = match addr {
+        0 => None,
+        addr if valid_range(addr, len) => Some(addr),
+        _ => return Err(EINVAL),
+    };
+
+    let mut prot = PROT_READ;
+    if flags & SHM_RDONLY == 0 {
+        prot |= PROT_WRITE;
+    }
+    if flags & SHM_EXEC != 0 {
+        prot |= PROT_EXEC;
+    }
+    let mapping = Mapping {
+        start: 0,
+        len,
+        flags: page_flags(prot),
+        backing: Backing::Shared {
+            memory: MappedMemory::new(memory),
+            offset: 0,
+        },
+        shmid: Some(id),
+    };
+    let start = map_current(addr, mapping, &[])?;
+
+    let pid = current_process().unwrap().lock().get_pid();
+    if let Ok(segment) = SEGMENTS.lock().segment(id) {
+        segment.last_pid = pid;
+        segment.attach_time = now();
+    }
+    Ok(start)
+}
+
+pub(super) fn sys_shmdt(regs: &mut Registers) {
+    let addr = regs.rdi;
+    let current = current_process().unwrap();
+    let mut proc = current.lock();
+    let pid = proc.get_pid();
+    let pagemap = proc.get_pagemap().clone();
+    let Some(mapping) = proc.get_mappings_mut().detach(&mut pagemap.lock(), addr) else {
+        regs.rax = -EINVAL as _;
+        return;
+    };
+    drop(proc);
+
+    let id = mapping.shmid.unwrap();
+    // drop our reference first so a removed segment can go right away
+    drop(mapping);
+    if let Ok(segment) = SEGMENTS.lock().segment(id) {
+        segment.last_pid = pid;
+        segment.detach_time = now();
+    }
+    regs.rax = 0;
+}
+
+pub(super) fn sys_shmctl(regs: &mut Registers) {
+    let ret = do_shmctl(regs);
+    set_result(regs, ret);
+}
+
+fn do_shmctl(regs: &Registers) -> Result<u64, i64> {
+    let id = regs.rdi as i32;
+    let cmd = regs.rsi & !IPC_64;
+    let buf = regs.rdx;
+
+    let mut segments = SEGMENTS.lock();
+    match cmd {
+        IPC_RMID => segments.remove(id)?,
+        IPC_STAT => {
+            if !validate_user_buf(buf, size_of::<ShmidDs>() as _) {
+                return Err(EFAULT);
+            }
+            let segment = segments.segment(id)?;
+            let ds = ShmidDs {
+                shm_perm: IpcPerm::new(segment.key, segment.mode),
+                shm_segsz: segment.size as u64,
+                shm_atime: segment.attach_time as i64,
+                shm_dtime: segment.detach_time as i64,
+                shm_ctime: segment.change_time as i64,
+                shm_cpid: segment.creator as i32,
+                shm_lpid: segment.last_pid as i32,
+                shm_nattch: segment.attached() as u64,
+                _unused: [0; 2],
+            };
+            unsafe { *(buf as *mut ShmidDs) = ds };
+        }
+        IPC_SET => {
+            if !validate_user_buf(buf, size_of::<ShmidDs>() as _) {
+                return Err(EFAULT);
+            }
+            let ds = unsafe { &*(buf as *const ShmidDs) };
+            let segment = segments.segment(id)?;
+            segment.mode = ds.shm_perm.mode & 0o777;
+            segment.change_time = now();
+        }
+        _ => return Err(EINVAL),
+    }
+    Ok(0)
+}
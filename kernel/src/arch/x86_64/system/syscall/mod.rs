@@ -6,37 +6,37 @@ use core::{
     sync::atomic::{AtomicPtr, Ordering},
 };
 
-use alloc::{boxed::Box, string::ToString};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 
 use crate::{
     arch::{
         drivers::time::rtc::read_rtc,
         system::{cpu::Registers, syscall::id::SyscallId},
     },
-    drivers::fs::{FileDescriptor, NodeMode, Path, Permissions, VfsNode, VfsNodeMetadataExt},
+    drivers::fs::{
+        FileDescriptor, FileObject, Inode, NodeMode, Path, Permissions, RENAME_LOCK,
+        VfsNodeMetadataExt, VfsNodeType,
+    },
     info,
     memory::{KERNEL_STACK_SIZE, vmm::page_size},
-    print,
     scheduler::current_process,
     utils::{
         align_down,
         asm::regs::{rdmsr, wrmsr},
+        errno::*,
     },
 };
 
+mod attr;
+mod event;
 pub mod id;
-
-const ENOENT: i64 = 2;
-const EIO: i64 = 5;
-const EBADF: i64 = 9;
-const EFAULT: i64 = 14;
-const EEXIST: i64 = 17;
-const EISDIR: i64 = 21;
-const ENOTDIR: i64 = 20;
-const EINVAL: i64 = 22;
-const ERANGE: i64 = 34;
-const ENOSYS: i64 = 38;
-const ENOTEMPTY: i64 = 39;
+mod ipc;
+mod link;
+mod memory;
+mod mount;
+mod poll;
+mod socket;
+mod xattr;
 
 const USER_ADDR_MAX: u64 = 0x0000_7FFF_FFFF_FFFF;
 
@@ -70,6 +70,22 @@ fn resolve_path(path_str: &str, cwd: &Path) -> Path {
     }
 }
 
+// for the submodules, whose handlers return Result<_, errno>
+fn set_result(regs: &mut Registers, ret: Result<u64, i64>) {
+    regs.rax = match ret {
+        Ok(v) => v,
+        Err(e) => -e as _,
+    };
+}
+
+fn install_fd(file: FileDescriptor) -> i32 {
+    let current = current_process().unwrap();
+    let mut proc = current.lock();
+    let fd = proc.next_fd.fetch_add(1, Ordering::SeqCst);
+    proc.fdt.insert(fd, file);
+    fd
+}
+
 #[repr(C)]
 struct SyscallCpuData {
     _reserved: u64,
@@ -97,6 +113,8 @@ pub extern "C" fn syscall_handler(regs: &mut Registers) {
         let handler: fn(&mut Registers) = unsafe { core::mem::transmute(handler_ptr) };
         handler(regs);
     }
+    // whatever the syscall let go of last, written now that it holds no locks
+    crate::drivers::fs::flush_deferred();
 }
 
 unsafe extern "C" {
@@ -185,6 +203,7 @@ fn sys_execve(regs: &mut Registers) {
 
     let mut argv = regs.rsi as *const *const c_char;
     let mut argc = 0;
+    let mut cmdline = Vec::new();
     let envp = regs.rdx;
     unsafe {
         if !argv.is_null()
@@ -196,22 +215,26 @@ fn sys_execve(regs: &mut Registers) {
             return;
         }
         while !argv.is_null() && !(*argv).is_null() {
+            if let Some(arg) = validate_user_cstr(*argv as u64) {
+                cmdline.push(String::from(arg));
+            }
             argc += 1;
             argv = argv.add(1);
         }
     }
 
-    let vfs = crate::drivers::fs::get_vfs();
-    let Some(node) = vfs.resolve_path(path) else {
+    if cmdline.is_empty() {
+        cmdline.push(String::from(path.get_name()));
+    }
+
+    let Some(inode) = crate::drivers::fs::get_vfs().resolve(path) else {
         regs.rax = -ENOENT as _;
         return;
     };
-    let Some(elf_data) = node.read() else {
+    let Some(elf_data) = inode.node().read_all() else {
         regs.rax = -EIO as _;
         return;
     };
-    let elf_data = elf_data.to_vec();
-    drop(vfs);
 
     {
         let mut pm = pagemap.lock();
@@ -261,6 +284,7 @@ fn sys_execve(regs: &mut Registers) {
 
                 let mut proc_lock = current.lock();
                 proc_lock.set_next_stack_addr(stack_vaddr - crate::memory::USER_STACK_SIZE as u64);
+                proc_lock.set_cmdline(cmdline);
             }
             Err(_e) => {
                 regs.rax = -ENOENT as _;
@@ -354,7 +378,9 @@ fn sys_nanosleep(regs: &mut Registers) {
         return;
     }
 
-    let ns = req.tv_sec as u64 * 1_000_000_000 + req.tv_nsec as u64;
+    let ns = (req.tv_sec as u64)
+        .saturating_mul(1_000_000_000)
+        .saturating_add(req.tv_nsec as u64);
     crate::scheduler::thread::sleep(ns);
     regs.rax = 0;
 }
@@ -364,82 +390,405 @@ fn sys_yield(_regs: &mut Registers) {
 }
 
 fn sys_read(regs: &mut Registers) {
-    let fd = regs.rdi;
     let buf = regs.rsi;
-    let count = regs.rdx;
+    let count = regs.rdx.min(MAX_RW_COUNT as u64);
 
     if !validate_user_buf(buf, count) {
         regs.rax = -EFAULT as _;
         return;
     }
 
-    if fd == 0 {
-        regs.rax = 0;
+    let slice = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count as usize) };
+    regs.rax = do_read(regs.rdi, slice, None) as _;
+}
+
+fn sys_write(regs: &mut Registers) {
+    let buf = regs.rsi;
+    let count = regs.rdx.min(MAX_RW_COUNT as u64);
+
+    if !validate_user_buf(buf, count) {
+        regs.rax = -EFAULT as _;
         return;
     }
+
+    let slice = unsafe { core::slice::from_raw_parts(buf as *const u8, count as usize) };
+    regs.rax = do_write(regs.rdi, slice, None) as _;
+}
+
+const IOV_MAX: u64 = 1024;
+// the most one call reads or writes, INT_MAX rounded down to a page like linux
+const MAX_RW_COUNT: usize = i32::MAX as usize & !0xfff;
+// iovecs go through the kernel this much at a time. bigger than any socket buffer, so a
+// datagram always fits in one piece
+const IOV_CHUNK: usize = 256 * 1024;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct IoVec {
+    iov_base: u64,
+    iov_len: u64,
+}
+
+// validates the iovec array and every buffer it points to, returns the total length
+// capped at MAX_RW_COUNT
+fn validate_user_iovecs(iov: u64, iovcnt: u64) -> Result<(&'static [IoVec], usize), i64> {
+    if iovcnt > IOV_MAX {
+        return Err(EINVAL);
+    }
+    if iovcnt == 0 {
+        return Ok((&[], 0));
+    }
+    if iov == 0 || !validate_user_buf(iov, iovcnt * size_of::<IoVec>() as u64) {
+        return Err(EFAULT);
+    }
+
+    let iovecs = unsafe { core::slice::from_raw_parts(iov as *const IoVec, iovcnt as usize) };
+    let mut total: u64 = 0;
+    for v in iovecs {
+        if !validate_user_buf(v.iov_base, v.iov_len) {
+            return Err(EFAULT);
+        }
+        total = match total.checked_add(v.iov_len) {
+            Some(t) if t <= isize::MAX as u64 => t,
+            _ => return Err(EINVAL),
+        };
+    }
+
+    Ok((iovecs, (total as usize).min(MAX_RW_COUNT)))
+}
+
+// the parts of the iovecs covering `len` bytes from `skip` on, as (address, length)
+fn iovec_parts(iovecs: &[IoVec], skip: usize, len: usize) -> impl Iterator<Item = (u64, usize)> {
+    let mut pos = 0;
+    iovecs.iter().filter_map(move |v| {
+        let (start, end) = (pos, pos + v.iov_len as usize);
+        pos = end;
+        let (from, to) = (start.max(skip), end.min(skip + len));
+        (from < to).then(|| (v.iov_base + (from - start) as u64, to - from))
+    })
+}
+
+fn gather_iovecs(iovecs: &[IoVec], skip: usize, len: usize) -> alloc::vec::Vec<u8> {
+    let mut data = alloc::vec::Vec::with_capacity(len);
+    for (base, n) in iovec_parts(iovecs, skip, len) {
+        data.extend_from_slice(unsafe { core::slice::from_raw_parts(base as *const u8, n) });
+    }
+    data
+}
+
+fn scatter_iovecs(iovecs: &[IoVec], skip: usize, data: &[u8]) {
+    let mut copied = 0;
+    for (base, n) in iovec_parts(iovecs, skip, data.len()) {
+        unsafe { core::ptr::copy_nonoverlapping(data[copied..].as_ptr(), base as *mut u8, n) };
+        copied += n;
+    }
+}
+
+// writes `total` bytes of the iovecs an IOV_CHUNK at a time, up to the first short
+// write. an error only comes back if nothing was written
+fn write_iovecs(
+    iovecs: &[IoVec],
+    total: usize,
+    mut write: impl FnMut(usize, &[u8]) -> Result<usize, i64>,
+) -> Result<usize, i64> {
+    let mut done = 0;
+    loop {
+        let len = (total - done).min(IOV_CHUNK);
+        match write(done, &gather_iovecs(iovecs, done, len)) {
+            Ok(n) => {
+                done += n;
+                if n < len || done == total {
+                    return Ok(done);
+                }
+            }
+            Err(_) if done > 0 => return Ok(done),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// reads into the iovecs an IOV_CHUNK at a time. only files and seekable objects carry
+// on after a full chunk, a socket or pipe could block on the next one
+fn read_iovecs(
+    iovecs: &[IoVec],
+    total: usize,
+    seekable: bool,
+    mut read: impl FnMut(usize, &mut [u8]) -> Result<usize, i64>,
+) -> Result<usize, i64> {
+    let mut done = 0;
+    let mut chunk = alloc::vec![0u8; total.min(IOV_CHUNK)];
+    loop {
+        let len = (total - done).min(IOV_CHUNK);
+        match read(done, &mut chunk[..len]) {
+            Ok(n) => {
+                scatter_iovecs(iovecs, done, &chunk[..n]);
+                done += n;
+                if n < len || done == total || !seekable {
+                    return Ok(done);
+                }
+            }
+            Err(_) if done > 0 => return Ok(done),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// whether io on `fd` has a position, files and disks do and pipes and sockets don't
+fn seekable(fd: u64) -> bool {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    proc.fdt
+        .get(&(fd as i32))
+        .is_some_and(|f| f.object().is_none_or(|o| o.size().is_some()))
+}
+
+// moves the fd offset after io on a file or seekable object, done without the process
+// lock held since disks make the caller wait
+fn advance(fd: u64, offset: u64) {
+    let current = current_process().unwrap();
+    if let Some(file) = current.lock().fdt.get_mut(&(fd as i32)) {
+        file.offset = offset;
+    }
+}
+
+// shared by read/readv/pread64/preadv, `offset` of None means use (and advance) the fd offset
+fn do_read(fd: u64, buf: &mut [u8], offset: Option<u64>) -> i64 {
+    if fd == 0 {
+        return if offset.is_some() { -ESPIPE } else { 0 };
+    }
     if fd == 1 || fd == 2 {
-        regs.rax = -EBADF as _;
-        return;
+        return -EBADF;
     }
 
     let current = current_process().unwrap();
     let mut lock = current.lock();
     let Some(file) = lock.fdt.get_mut(&(fd as i32)) else {
-        regs.rax = -EBADF as _;
-        return;
+        return -EBADF;
     };
 
     if !file.permissions.contains(Permissions::READ) {
-        regs.rax = -EBADF as _;
-        return;
+        return -EBADF;
     }
 
-    let slice = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count as usize) };
-    regs.rax = match file.read(slice) {
-        Some(n) => n as _,
-        None => -EIO as _,
+    // objects may block, so the process lock can't be held across the call
+    if let Some(object) = file.object().cloned() {
+        if object.size().is_some() {
+            let pos = offset.unwrap_or(file.offset);
+            drop(lock);
+            return match object.read_at(pos, buf) {
+                Ok(n) => {
+                    if offset.is_none() {
+                        advance(fd, pos + n as u64);
+                    }
+                    n as _
+                }
+                Err(e) => -e,
+            };
+        }
+        if offset.is_some() {
+            return -ESPIPE;
+        }
+        let nonblock = file.nonblock;
+        drop(lock);
+        return object.read(buf, nonblock).map_or_else(|e| -e, |n| n as _);
+    }
+
+    // disk filesystems sleep on the block layer, only the node stays locked for that
+    let Some(inode) = file.inode().cloned() else {
+        return -EBADF;
     };
+    let pos = offset.unwrap_or(file.offset);
+    drop(lock);
+    let node = inode.node();
+    if node.is_dir() {
+        return -EISDIR;
+    }
+    match node.read_at(pos, buf) {
+        Some(n) => {
+            drop(node);
+            if offset.is_none() {
+                advance(fd, pos + n as u64);
+            }
+            n as _
+        }
+        None => -EIO,
+    }
 }
 
-fn sys_write(regs: &mut Registers) {
+// shared by write/writev/pwrite64/pwritev, console output is written in one go
+fn do_write(fd: u64, data: &[u8], offset: Option<u64>) -> i64 {
+    if fd == 1 || fd == 2 {
+        if offset.is_some() {
+            return -ESPIPE;
+        }
+        crate::device::tty::console_write(data);
+        return data.len() as _;
+    }
+    if fd == 0 {
+        return -EBADF;
+    }
+
+    let current = current_process().unwrap();
+    let mut lock = current.lock();
+    let Some(file) = lock.fdt.get_mut(&(fd as i32)) else {
+        return -EBADF;
+    };
+
+    if !file.permissions.contains(Permissions::WRITE) {
+        return -EBADF;
+    }
+
+    if let Some(object) = file.object().cloned() {
+        if let Some(size) = object.size() {
+            let pos = match offset {
+                Some(offset) => offset,
+                None if file.append => size,
+                None => file.offset,
+            };
+            drop(lock);
+            return match object.write_at(pos, data) {
+                Ok(n) => {
+                    if offset.is_none() {
+                        advance(fd, pos + n as u64);
+                    }
+                    n as _
+                }
+                Err(e) => -e,
+            };
+        }
+        if offset.is_some() {
+            return -ESPIPE;
+        }
+        let nonblock = file.nonblock;
+        drop(lock);
+        return object.write(data, nonblock).map_or_else(|e| -e, |n| n as _);
+    }
+
+    let Some(inode) = file.inode().cloned() else {
+        return -EBADF;
+    };
+    let (append, file_offset) = (file.append, file.offset);
+    drop(lock);
+    // one node lock for the size and the write so appends from two descriptors don't
+    // interleave
+    let mut node = inode.node_mut();
+    let pos = match offset {
+        Some(offset) => offset,
+        None if append => node.size(),
+        None => file_offset,
+    };
+    let ret = node.write_at(pos, data);
+    drop(node);
+    match ret {
+        Ok(n) => {
+            if offset.is_none() {
+                advance(fd, pos + n as u64);
+            }
+            n as _
+        }
+        Err(e) => -e,
+    }
+}
+
+fn do_readv(regs: &mut Registers, offset: Option<u64>) {
+    let (iovecs, total) = match validate_user_iovecs(regs.rsi, regs.rdx) {
+        Ok(x) => x,
+        Err(e) => {
+            regs.rax = -e as _;
+            return;
+        }
+    };
+
     let fd = regs.rdi;
+    let ret = read_iovecs(iovecs, total, seekable(fd), |done, chunk| {
+        match do_read(fd, chunk, offset.map(|o| o + done as u64)) {
+            n if n < 0 => Err(-n),
+            n => Ok(n as usize),
+        }
+    });
+    set_result(regs, ret.map(|n| n as u64));
+}
+
+fn do_writev(regs: &mut Registers, offset: Option<u64>) {
+    let (iovecs, total) = match validate_user_iovecs(regs.rsi, regs.rdx) {
+        Ok(x) => x,
+        Err(e) => {
+            regs.rax = -e as _;
+            return;
+        }
+    };
+
+    let fd = regs.rdi;
+    let ret = write_iovecs(iovecs, total, |done, chunk| {
+        match do_write(fd, chunk, offset.map(|o| o + done as u64)) {
+            n if n < 0 => Err(-n),
+            n => Ok(n as usize),
+        }
+    });
+    set_result(regs, ret.map(|n| n as u64));
+}
+
+fn sys_readv(regs: &mut Registers) {
+    do_readv(regs, None);
+}
+
+fn sys_writev(regs: &mut Registers) {
+    do_writev(regs, None);
+}
+
+fn sys_pread64(regs: &mut Registers) {
     let buf = regs.rsi;
-    let count = regs.rdx;
+    let count = regs.rdx.min(MAX_RW_COUNT as u64);
+    let offset = regs.r10 as i64;
 
     if !validate_user_buf(buf, count) {
         regs.rax = -EFAULT as _;
         return;
     }
+    if offset < 0 {
+        regs.rax = -EINVAL as _;
+        return;
+    }
 
-    let slice = unsafe { core::slice::from_raw_parts(buf as *const u8, count as usize) };
+    let slice = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count as usize) };
+    regs.rax = do_read(regs.rdi, slice, Some(offset as u64)) as _;
+}
 
-    if fd == 1 || fd == 2 {
-        if let Ok(s) = core::str::from_utf8(slice) {
-            print!("{}", s);
-        } else {
-            print!("{:?}", slice);
-        }
-        regs.rax = count;
-    } else if fd == 0 {
-        regs.rax = -EBADF as _;
-    } else {
-        let current = current_process().unwrap();
-        let mut lock = current.lock();
-        let Some(file) = lock.fdt.get_mut(&(fd as i32)) else {
-            regs.rax = -EBADF as _;
-            return;
-        };
+fn sys_pwrite64(regs: &mut Registers) {
+    let buf = regs.rsi;
+    let count = regs.rdx.min(MAX_RW_COUNT as u64);
+    let offset = regs.r10 as i64;
 
-        if !file.permissions.contains(Permissions::WRITE) {
-            regs.rax = -EBADF as _;
-            return;
-        }
+    if !validate_user_buf(buf, count) {
+        regs.rax = -EFAULT as _;
+        return;
+    }
+    if offset < 0 {
+        regs.rax = -EINVAL as _;
+        return;
+    }
 
-        regs.rax = match file.write(slice) {
-            Some(n) => n as _,
-            None => -EIO as _,
-        };
+    let slice = unsafe { core::slice::from_raw_parts(buf as *const u8, count as usize) };
+    regs.rax = do_write(regs.rdi, slice, Some(offset as u64)) as _;
+}
+
+// on x86_64 the whole offset fits in pos_l (r10), pos_h (r8) is ignored
+fn sys_preadv(regs: &mut Registers) {
+    let offset = regs.r10 as i64;
+    if offset < 0 {
+        regs.rax = -EINVAL as _;
+        return;
+    }
+    do_readv(regs, Some(offset as u64));
+}
+
+fn sys_pwritev(regs: &mut Registers) {
+    let offset = regs.r10 as i64;
+    if offset < 0 {
+        regs.rax = -EINVAL as _;
+        return;
     }
+    do_writev(regs, Some(offset as u64));
 }
 
 fn sys_open(regs: &mut Registers) {
@@ -487,9 +836,9 @@ fn sys_open(regs: &mut Registers) {
         return;
     };
 
+    // not held across the lookup, procfs locks processes to list them
     let current = current_process().unwrap();
-    let mut proc = current.lock();
-    let path = resolve_path(path_str, proc.get_cwd());
+    let path = resolve_path(path_str, current.lock().get_cwd());
 
     let perms = match flags & Flags::PERMS_MASK {
         Flags::O_RDONLY => Permissions::READ,
@@ -498,56 +847,117 @@ fn sys_open(regs: &mut Registers) {
         _ => Permissions::READ,
     };
 
-    let mut vfs = crate::drivers::fs::get_vfs();
+    let vfs = crate::drivers::fs::get_vfs();
+
+    let readonly = vfs.is_readonly(&path);
+
+    // O_EXCL doesn't follow either, a dangling symlink still counts as existing
+    let exclusive = flags.contains(Flags::O_CREAT) && flags.contains(Flags::O_EXCL);
+    let follow = !flags.contains(Flags::O_NOFOLLOW) && !exclusive;
+    let existing = match vfs.walk(&path, follow) {
+        Ok(inode) => Some(inode),
+        Err(ENOENT) => None,
+        Err(e) => {
+            regs.rax = -e as _;
+            return;
+        }
+    };
+    let parent = if existing.is_none() && flags.contains(Flags::O_CREAT) && !readonly {
+        vfs.resolve(path.get_parent())
+    } else {
+        None
+    };
+    // opening, truncating and creating can go to disk, the table isn't needed for it
+    drop(vfs);
 
-    let file = if vfs.resolve_path(path.clone()).is_some() {
-        let file = vfs.resolve_path_mut(path.clone()).unwrap();
+    let file = if let Some(inode) = existing {
+        let mut file = inode.node_mut();
 
-        if flags.contains(Flags::O_CREAT) && flags.contains(Flags::O_EXCL) {
+        if exclusive {
             regs.rax = -EEXIST as _;
             return;
         }
 
+        if file.get_type() == &VfsNodeType::Symlink {
+            regs.rax = -ELOOP as _;
+            return;
+        }
+
+        if readonly && (perms.contains(Permissions::WRITE) || flags.contains(Flags::O_TRUNC)) {
+            regs.rax = -EROFS as _;
+            return;
+        }
+
         if flags.contains(Flags::O_DIRECTORY) && !file.is_dir() {
             regs.rax = -ENOTDIR as _;
             return;
         }
 
-        if flags.contains(Flags::O_TRUNC)
+        if file.get_type() == &VfsNodeType::Socket {
+            regs.rax = -ENXIO as _;
+            return;
+        }
+
+        // device nodes hand the io to their driver, proc files to a snapshot
+        let device = match file.open() {
+            Ok(device) => device,
+            Err(e) => {
+                regs.rax = -e as _;
+                return;
+            }
+        };
+
+        if device.is_none()
+            && flags.contains(Flags::O_TRUNC)
             && (perms == Permissions::WRITE || perms == Permissions::RW)
+            && let Err(e) = file.truncate(0)
         {
-            file.truncate(0);
+            regs.rax = -e as _;
+            return;
         }
+        drop(file);
 
-        FileDescriptor::new(file, perms)
+        match device {
+            Some(device) => FileDescriptor::device(inode, device, perms).with_path(path),
+            None => FileDescriptor::new(inode, perms).with_path(path),
+        }
     } else {
         if !flags.contains(Flags::O_CREAT) {
             regs.rax = -ENOENT as _;
             return;
         }
 
-        let Some(parent) = vfs.resolve_path_mut(path.get_parent()) else {
+        if readonly {
+            regs.rax = -EROFS as _;
+            return;
+        }
+
+        let Some(parent) = parent else {
             regs.rax = -ENOENT as _;
             return;
         };
 
-        let Some(created) = parent.create_file(path.get_name()) else {
-            regs.rax = -EEXIST as _;
-            return;
+        let created = match parent.node_mut().create_file(path.get_name()) {
+            Ok(created) => created,
+            Err(e) => {
+                regs.rax = -e as _;
+                return;
+            }
         };
+        // it wasn't looked up, it's on the same mount as where it was made
+        created.set_dev(parent.dev());
 
         let epoch = read_rtc().to_epoch().unwrap_or_default();
 
-        created
+        let created = created
             .with_permissions(mode)
             .with_created_at(epoch)
             .with_modified_at(epoch);
 
-        FileDescriptor::new(created.as_mut(), perms)
+        FileDescriptor::new(created, perms).with_path(path)
     };
 
-    drop(vfs);
-
+    let mut proc = current.lock();
     let fd = proc.next_fd.fetch_add(1, Ordering::SeqCst);
 
     proc.fdt
@@ -582,10 +992,24 @@ fn sys_lseek(regs: &mut Registers) {
         return;
     };
 
+    let size = match (file.node(), file.object()) {
+        // block devices know how big they are, their node doesn't
+        (Some(node), Some(object)) => object.size().unwrap_or_else(|| node.size()),
+        (Some(node), None) => node.size(),
+        (None, Some(object)) => match object.size() {
+            Some(size) => size,
+            None => {
+                regs.rax = -ESPIPE as _;
+                return;
+            }
+        },
+        (None, None) => unreachable!(),
+    };
+
     let new_pos = match whence {
         SEEK_SET => offset,
         SEEK_CUR => file.offset as i64 + offset,
-        SEEK_END => file.node().size() as i64 + offset,
+        SEEK_END => size as i64 + offset,
         _ => {
             regs.rax = -EINVAL as _;
             return;
@@ -636,17 +1060,23 @@ fn sys_mkdir(regs: &mut Registers) {
     let current = current_process().unwrap();
     let path = resolve_path(path_str, current.lock().get_cwd());
 
-    let mut vfs = crate::drivers::fs::get_vfs();
-    let Some(parent) = vfs.resolve_path_mut(path.get_parent()) else {
+    let vfs = crate::drivers::fs::get_vfs();
+    if vfs.is_readonly(&path) {
+        regs.rax = -EROFS as _;
+        return;
+    }
+    let Some(parent) = vfs.resolve(path.get_parent()) else {
         regs.rax = -ENOENT as _;
         return;
     };
+    drop(vfs);
 
-    if let Some(dir) = parent.create_dir(path.get_name()) {
-        dir.get_metadata_mut().permissions = mode;
-        regs.rax = 0;
-    } else {
-        regs.rax = -EEXIST as _;
+    match parent.node_mut().create_dir(path.get_name()) {
+        Ok(dir) => {
+            dir.node_mut().get_metadata_mut().permissions = mode;
+            regs.rax = 0;
+        }
+        Err(e) => regs.rax = -e as _,
     }
 }
 
@@ -708,22 +1138,23 @@ fn sys_chdir(regs: &mut Registers) {
     };
 
     let current = current_process().unwrap();
-    let mut proc = current.lock();
-    let path = resolve_path(path_str, proc.get_cwd());
+    let path = resolve_path(path_str, current.lock().get_cwd());
 
     let vfs = crate::drivers::fs::get_vfs();
-    let Some(node) = vfs.resolve_path(path.clone()) else {
+    let Some(inode) = vfs.resolve(path.clone()) else {
         regs.rax = -ENOENT as _;
         return;
     };
 
-    if !node.is_dir() {
+    if !inode.node().is_dir() {
         regs.rax = -ENOTDIR as _;
         return;
     }
 
+    // the cwd is kept free of symlinks and "..", like getcwd reports it
+    let path = vfs.realpath(&path, true).unwrap_or(path);
     drop(vfs);
-    proc.set_cwd(path);
+    current.lock().set_cwd(path);
     regs.rax = 0;
 }
 
@@ -749,34 +1180,75 @@ struct StatBuf {
     __unused: [i64; 3],
 }
 
-fn fill_stat(stat: &mut StatBuf, node: &dyn VfsNode) {
+fn fill_stat(stat: &mut StatBuf, inode: &Inode, dev: u64) {
+    let node = inode.node();
     let meta = node.get_metadata();
     let mode_bits = meta.permissions.bits() as u32;
-    let type_bits: u32 = if node.is_dir() { 0o040000 } else { 0o100000 };
+    let type_bits: u32 = match node.get_type() {
+        VfsNodeType::Directory => 0o040000,
+        VfsNodeType::File => 0o100000,
+        VfsNodeType::Socket => 0o140000,
+        VfsNodeType::Symlink => 0o120000,
+        VfsNodeType::CharDevice => 0o020000,
+        VfsNodeType::BlockDevice => 0o060000,
+    };
+
+    *stat = StatBuf {
+        st_dev: dev,
+        st_ino: inode.ino,
+        st_nlink: meta.nlink,
+        st_mode: type_bits | mode_bits,
+        st_uid: meta.uid,
+        st_gid: meta.gid,
+        __pad0: 0,
+        st_rdev: node.rdev(),
+        st_size: node.size() as i64,
+        st_blksize: 4096,
+        st_blocks: node.blocks() as i64,
+        st_atime: meta.accessed_at() as i64,
+        st_atime_nsec: 0,
+        st_mtime: meta.modified_at as i64,
+        st_mtime_nsec: 0,
+        st_ctime: meta.changed_at as i64,
+        st_ctime_nsec: 0,
+        __unused: [0; 3],
+    };
+}
 
+fn fill_stat_object(stat: &mut StatBuf, object: &dyn FileObject) {
+    let size = object.size().unwrap_or(0);
     *stat = StatBuf {
         st_dev: 0,
         st_ino: 0,
         st_nlink: 1,
-        st_mode: type_bits | mode_bits,
+        st_mode: object.mode() | 0o600,
         st_uid: 0,
         st_gid: 0,
         __pad0: 0,
         st_rdev: 0,
-        st_size: node.size() as i64,
+        st_size: size as i64,
         st_blksize: 4096,
-        st_blocks: node.size().div_ceil(512) as i64,
-        st_atime: meta.modified_at as i64,
+        st_blocks: size.div_ceil(512) as i64,
+        st_atime: 0,
         st_atime_nsec: 0,
-        st_mtime: meta.modified_at as i64,
+        st_mtime: 0,
         st_mtime_nsec: 0,
-        st_ctime: meta.created_at as i64,
+        st_ctime: 0,
         st_ctime_nsec: 0,
         __unused: [0; 3],
     };
 }
 
 fn sys_stat(regs: &mut Registers) {
+    do_stat(regs, true);
+}
+
+// the link itself rather than what it points to
+fn sys_lstat(regs: &mut Registers) {
+    do_stat(regs, false);
+}
+
+fn do_stat(regs: &mut Registers, follow: bool) {
     let Some(path_str) = validate_user_cstr(regs.rdi) else {
         regs.rax = -EFAULT as _;
         return;
@@ -794,13 +1266,17 @@ fn sys_stat(regs: &mut Registers) {
     drop(proc);
 
     let vfs = crate::drivers::fs::get_vfs();
-    let Some(node) = vfs.resolve_path(path) else {
-        regs.rax = -ENOENT as _;
-        return;
+    let inode = match vfs.walk(&path, follow) {
+        Ok(inode) => inode,
+        Err(e) => {
+            regs.rax = -e as _;
+            return;
+        }
     };
 
+    let dev = vfs.device_of_inode(&inode);
     let stat = unsafe { &mut *(stat_buf as *mut StatBuf) };
-    fill_stat(stat, node);
+    fill_stat(stat, &inode, dev);
     regs.rax = 0;
 }
 
@@ -819,9 +1295,18 @@ fn sys_fstat(regs: &mut Registers) {
         regs.rax = -EBADF as _;
         return;
     };
+    let (inode, object) = (file.inode().cloned(), file.object().cloned());
+    drop(lock);
 
     let stat = unsafe { &mut *(stat_buf as *mut StatBuf) };
-    fill_stat(stat, file.node());
+    match (inode, object) {
+        (Some(inode), _) => {
+            let dev = crate::drivers::fs::get_vfs().device_of_inode(&inode);
+            fill_stat(stat, &inode, dev)
+        }
+        (None, Some(object)) => fill_stat_object(stat, object.as_ref()),
+        (None, None) => unreachable!(),
+    }
     regs.rax = 0;
 }
 
@@ -834,29 +1319,34 @@ fn sys_unlink(regs: &mut Registers) {
     let current = current_process().unwrap();
     let path = resolve_path(path_str, current.lock().get_cwd());
 
-    let mut vfs = crate::drivers::fs::get_vfs();
+    let vfs = crate::drivers::fs::get_vfs();
+    // these walk the path, so before anything is locked
+    let readonly = vfs.is_readonly(&path);
 
-    let Some(node) = vfs.resolve_path(path.clone()) else {
+    let Some(parent) = vfs.resolve(path.get_parent()) else {
+        regs.rax = -ENOENT as _;
+        return;
+    };
+    drop(vfs);
+    // locked from the checks to the unlink so nothing swaps the entry in between
+    let mut parent = parent.node_mut();
+    let Some(inode) = parent.get_child(path.get_name()) else {
         regs.rax = -ENOENT as _;
         return;
     };
 
-    if node.is_dir() {
+    if inode.node().is_dir() {
         regs.rax = -EISDIR as _;
         return;
     }
 
-    let name = path.get_name().to_string();
-    let Some(parent) = vfs.resolve_path_mut(path.get_parent()) else {
-        regs.rax = -ENOENT as _;
+    if readonly {
+        regs.rax = -EROFS as _;
         return;
-    };
-
-    if parent.remove_child(&name) {
-        regs.rax = 0;
-    } else {
-        regs.rax = -ENOENT as _;
     }
+
+    parent.unlink(path.get_name());
+    regs.rax = 0;
 }
 
 fn sys_rmdir(regs: &mut Registers) {
@@ -868,34 +1358,45 @@ fn sys_rmdir(regs: &mut Registers) {
     let current = current_process().unwrap();
     let path = resolve_path(path_str, current.lock().get_cwd());
 
-    let mut vfs = crate::drivers::fs::get_vfs();
+    let vfs = crate::drivers::fs::get_vfs();
+    let readonly = vfs.is_readonly(&path);
+    let mountpoint = vfs.is_mountpoint(&path);
 
-    let Some(node) = vfs.resolve_path(path.clone()) else {
+    let Some(parent) = vfs.resolve(path.get_parent()) else {
         regs.rax = -ENOENT as _;
         return;
     };
+    drop(vfs);
+    let mut parent = parent.node_mut();
+    let Some(inode) = parent.get_child(path.get_name()) else {
+        regs.rax = -ENOENT as _;
+        return;
+    };
+    let node = inode.node();
 
     if !node.is_dir() {
         regs.rax = -ENOTDIR as _;
         return;
     }
 
+    if mountpoint {
+        regs.rax = -EBUSY as _;
+        return;
+    }
+
     if !node.get_children().is_empty() {
         regs.rax = -ENOTEMPTY as _;
         return;
     }
 
-    let name = path.get_name().to_string();
-    let Some(parent) = vfs.resolve_path_mut(path.get_parent()) else {
-        regs.rax = -ENOENT as _;
+    if readonly {
+        regs.rax = -EROFS as _;
         return;
-    };
-
-    if parent.remove_child(&name) {
-        regs.rax = 0;
-    } else {
-        regs.rax = -ENOENT as _;
     }
+
+    drop(node);
+    parent.unlink(path.get_name());
+    regs.rax = 0;
 }
 
 fn sys_dup(regs: &mut Registers) {
@@ -960,16 +1461,44 @@ fn sys_ftruncate(regs: &mut Registers) {
         return;
     }
 
-    if file.node().is_dir() {
-        regs.rax = -EISDIR as _;
+    if let Some(object) = file.object() {
+        regs.rax = match object.truncate(length as u64) {
+            Ok(()) => 0,
+            Err(e) => -e as _,
+        };
         return;
     }
 
-    if file.node_mut().truncate(length as u64) {
-        regs.rax = 0;
-    } else {
-        regs.rax = -EIO as _;
+    let Some(mut node) = file.node_mut() else {
+        regs.rax = -EINVAL as _;
+        return;
+    };
+
+    if node.is_dir() {
+        regs.rax = -EISDIR as _;
+        return;
     }
+
+    regs.rax = match node.truncate(length as u64) {
+        Ok(()) => 0,
+        Err(e) => -e as _,
+    };
+}
+
+fn sys_ioctl(regs: &mut Registers) {
+    let ret = do_ioctl(regs);
+    set_result(regs, ret);
+}
+
+// only objects take ioctls, regular files and directories have nothing to say to them.
+// the request may look at other fds (LOOP_SET_FD), so the process lock goes first
+fn do_ioctl(regs: &Registers) -> Result<u64, i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&(regs.rdi as i32)).ok_or(EBADF)?;
+    let object = file.object().cloned().ok_or(ENOTTY)?;
+    drop(proc);
+    object.ioctl(regs.rsi & 0xffff_ffff, regs.rdx)
 }
 
 #[repr(C)]
@@ -991,24 +1520,28 @@ fn sys_getdents64(regs: &mut Registers) {
     }
 
     let current = current_process().unwrap();
-    let mut lock = current.lock();
-    let Some(file) = lock.fdt.get_mut(&fd) else {
+    let lock = current.lock();
+    let Some(file) = lock.fdt.get(&fd) else {
         regs.rax = -EBADF as _;
         return;
     };
+    let (inode, start) = (file.inode().cloned(), file.offset);
+    // listing procfs locks processes, this one included
+    drop(lock);
 
-    if !file.node().is_dir() {
+    let Some(node) = inode.as_ref().map(|i| i.node()).filter(|n| n.is_dir()) else {
         regs.rax = -ENOTDIR as _;
         return;
-    }
+    };
 
-    let children = file.node().get_children();
-    let mut offset = file.offset as usize;
+    let children = node.get_children();
+    drop(node);
+    let mut offset = start as usize;
     let mut written: usize = 0;
 
     while offset < children.len() {
-        let child = children[offset];
-        let name = child.get_name().as_bytes();
+        let child = &children[offset];
+        let name = child.name.as_bytes();
         let reclen = ((19 + name.len() + 1) + 7) & !7;
 
         if written + reclen > count as usize {
@@ -1016,11 +1549,18 @@ fn sys_getdents64(regs: &mut Registers) {
         }
 
         let entry_ptr = (buf + written as u64) as *mut u8;
-        let d_type: u8 = if child.is_dir() { 4 } else { 8 };
+        let d_type: u8 = match child.inode.node().get_type() {
+            VfsNodeType::Directory => 4,
+            VfsNodeType::File => 8,
+            VfsNodeType::Socket => 12,
+            VfsNodeType::Symlink => 10,
+            VfsNodeType::CharDevice => 2,
+            VfsNodeType::BlockDevice => 6,
+        };
 
         unsafe {
             let entry = entry_ptr as *mut LinuxDirent64;
-            (*entry).d_ino = (offset + 1) as u64;
+            (*entry).d_ino = child.inode.ino;
             (*entry).d_off = (offset + 1) as i64;
             (*entry).d_reclen = reclen as u16;
             (*entry).d_type = d_type;
@@ -1039,7 +1579,9 @@ fn sys_getdents64(regs: &mut Registers) {
         offset += 1;
     }
 
-    file.offset = offset as u64;
+    if let Some(file) = current.lock().fdt.get_mut(&fd) {
+        file.offset = offset as u64;
+    }
     regs.rax = written as u64;
 }
 
@@ -1063,7 +1605,7 @@ fn sys_access(regs: &mut Registers) {
     drop(proc);
 
     let vfs = crate::drivers::fs::get_vfs();
-    if vfs.resolve_path(path).is_some() {
+    if vfs.resolve(path).is_some() {
         regs.rax = 0;
     } else {
         regs.rax = -ENOENT as _;
@@ -1086,52 +1628,107 @@ fn sys_rename(regs: &mut Registers) {
     let new_path = resolve_path(new_str, proc.get_cwd());
     drop(proc);
 
-    let mut vfs = crate::drivers::fs::get_vfs();
+    let vfs = crate::drivers::fs::get_vfs();
 
-    if vfs.resolve_path(old_path.clone()).is_none() {
+    if vfs.resolve_nofollow(old_path.clone()).is_none() {
         regs.rax = -ENOENT as _;
         return;
     }
-
-    if vfs.resolve_path(new_path.get_parent()).is_none() {
+    let Some(old_parent) = vfs.resolve(old_path.get_parent()) else {
+        regs.rax = -ENOENT as _;
+        return;
+    };
+    let Some(new_parent) = vfs.resolve(new_path.get_parent()) else {
         regs.rax = -ENOENT as _;
         return;
+    };
+    if !new_parent.node().is_dir() {
+        regs.rax = -ENOTDIR as _;
+        return;
     }
 
-    if let Some(existing) = vfs.resolve_path(new_path.clone())
-        && existing.is_dir()
-        && !existing.get_children().is_empty()
-    {
-        regs.rax = -ENOTEMPTY as _;
+    if vfs.is_mountpoint(&old_path) || vfs.is_mountpoint(&new_path) {
+        regs.rax = -EBUSY as _;
         return;
     }
 
-    let old_name = old_path.get_name().to_string();
-    let old_parent = vfs.resolve_path_mut(old_path.get_parent());
-    let Some(parent) = old_parent else {
-        regs.rax = -ENOENT as _;
+    // nodes can't move between filesystems
+    if !vfs.same_mount(&old_path, &new_path) {
+        regs.rax = -EXDEV as _;
         return;
-    };
-    let Some(mut child) = parent.take_child(&old_name) else {
-        regs.rax = -ENOENT as _;
+    }
+
+    if vfs.is_readonly(&old_path) {
+        regs.rax = -EROFS as _;
         return;
-    };
+    }
 
-    child.set_path(new_path.clone());
+    // a directory can't be moved below itself
+    if let (Some(old), Some(new)) = (vfs.physical(&old_path), vfs.physical(&new_path))
+        && new.as_str().starts_with(old.as_str())
+        && new.as_str()[old.as_str().len()..].starts_with('/')
+    {
+        regs.rax = -EINVAL as _;
+        return;
+    }
+
+    drop(vfs);
+
+    // no more walking from here, both parents stay locked until the entries have moved.
+    // two different ones are taken in inode order, and only by one rename at a time
+    let same_dir = Arc::ptr_eq(&old_parent, &new_parent);
+    let _rename = (!same_dir).then(|| RENAME_LOCK.lock());
+    let (mut old_dir, mut new_dir) = if same_dir {
+        (old_parent.node_mut(), None)
+    } else if old_parent.ino < new_parent.ino {
+        let old_dir = old_parent.node_mut();
+        (old_dir, Some(new_parent.node_mut()))
+    } else {
+        let new_dir = new_parent.node_mut();
+        (old_parent.node_mut(), Some(new_dir))
+    };
 
-    let new_name = new_path.get_name().to_string();
-    let Some(new_parent) = vfs.resolve_path_mut(new_path.get_parent()) else {
+    let (old_name, new_name) = (old_path.get_name(), new_path.get_name());
+    let Some(inode) = old_dir.get_child(old_name) else {
         regs.rax = -ENOENT as _;
         return;
     };
 
-    new_parent.remove_child(&new_name);
+    if let Some(existing) = new_dir.as_mut().unwrap_or(&mut old_dir).get_child(new_name) {
+        // both names already point at the same inode
+        if Arc::ptr_eq(&existing, &inode) {
+            regs.rax = 0;
+            return;
+        }
+        let existing = existing.node();
+        let err = match (inode.node().is_dir(), existing.is_dir()) {
+            (false, true) => EISDIR,
+            (true, false) => ENOTDIR,
+            (true, true) if !existing.get_children().is_empty() => ENOTEMPTY,
+            _ => 0,
+        };
+        if err != 0 {
+            regs.rax = -err as _;
+            return;
+        }
+    }
+
+    // only the directory entries move, the inode and whatever has it open stay put
+    old_dir.unlink(old_name);
+    let new_dir = new_dir.as_mut().unwrap_or(&mut old_dir);
+    let replaced = new_dir.unlink(new_name);
 
-    if new_parent.add_child(child) {
+    let Err(e) = new_dir.link(new_name, inode.clone()) else {
         regs.rax = 0;
-    } else {
-        regs.rax = -EEXIST as _;
+        return;
+    };
+    // the filesystem turned the new name down (bad characters, a full directory), both
+    // entries go back the way they were
+    if let Some(replaced) = replaced {
+        let _ = new_dir.link(new_name, replaced);
     }
+    let _ = old_dir.link(old_name, inode);
+    regs.rax = -e as _;
 }
 
 const CLOCK_REALTIME: u64 = 0;
@@ -1172,15 +1769,23 @@ fn sys_clock_gettime(regs: &mut Registers) {
 pub fn init() {
     HANDLERS[SyscallId::Read as usize].store(sys_read as _, Ordering::Release);
     HANDLERS[SyscallId::Write as usize].store(sys_write as _, Ordering::Release);
+    HANDLERS[SyscallId::Readv as usize].store(sys_readv as _, Ordering::Release);
+    HANDLERS[SyscallId::Writev as usize].store(sys_writev as _, Ordering::Release);
+    HANDLERS[SyscallId::Pread64 as usize].store(sys_pread64 as _, Ordering::Release);
+    HANDLERS[SyscallId::Pwrite64 as usize].store(sys_pwrite64 as _, Ordering::Release);
+    HANDLERS[SyscallId::Preadv as usize].store(sys_preadv as _, Ordering::Release);
+    HANDLERS[SyscallId::Pwritev as usize].store(sys_pwritev as _, Ordering::Release);
     HANDLERS[SyscallId::Open as usize].store(sys_open as _, Ordering::Release);
     HANDLERS[SyscallId::Close as usize].store(sys_close as _, Ordering::Release);
     HANDLERS[SyscallId::Stat as usize].store(sys_stat as _, Ordering::Release);
+    HANDLERS[SyscallId::Lstat as usize].store(sys_lstat as _, Ordering::Release);
     HANDLERS[SyscallId::Fstat as usize].store(sys_fstat as _, Ordering::Release);
     HANDLERS[SyscallId::Lseek as usize].store(sys_lseek as _, Ordering::Release);
     HANDLERS[SyscallId::Dup as usize].store(sys_dup as _, Ordering::Release);
     HANDLERS[SyscallId::Dup2 as usize].store(sys_dup2 as _, Ordering::Release);
     HANDLERS[SyscallId::Getpid as usize].store(sys_getpid as _, Ordering::Release);
     HANDLERS[SyscallId::Ftruncate as usize].store(sys_ftruncate as _, Ordering::Release);
+    HANDLERS[SyscallId::Ioctl as usize].store(sys_ioctl as _, Ordering::Release);
     HANDLERS[SyscallId::Getdents64 as usize].store(sys_getdents64 as _, Ordering::Release);
     HANDLERS[SyscallId::Getcwd as usize].store(sys_get_cwd as _, Ordering::Release);
     HANDLERS[SyscallId::Chdir as usize].store(sys_chdir as _, Ordering::Release);
@@ -1192,6 +1797,81 @@ pub fn init() {
     HANDLERS[SyscallId::Access as usize].store(sys_access as _, Ordering::Release);
     HANDLERS[SyscallId::Rename as usize].store(sys_rename as _, Ordering::Release);
     HANDLERS[SyscallId::Gettid as usize].store(sys_gettid as _, Ordering::Release);
+    HANDLERS[SyscallId::Socket as usize].store(socket::sys_socket as _, Ordering::Release);
+    HANDLERS[SyscallId::Socketpair as usize].store(socket::sys_socketpair as _, Ordering::Release);
+    HANDLERS[SyscallId::Bind as usize].store(socket::sys_bind as _, Ordering::Release);
+    HANDLERS[SyscallId::Listen as usize].store(socket::sys_listen as _, Ordering::Release);
+    HANDLERS[SyscallId::Accept as usize].store(socket::sys_accept as _, Ordering::Release);
+    HANDLERS[SyscallId::Accept4 as usize].store(socket::sys_accept4 as _, Ordering::Release);
+    HANDLERS[SyscallId::Connect as usize].store(socket::sys_connect as _, Ordering::Release);
+    HANDLERS[SyscallId::Shutdown as usize].store(socket::sys_shutdown as _, Ordering::Release);
+    HANDLERS[SyscallId::Sendto as usize].store(socket::sys_sendto as _, Ordering::Release);
+    HANDLERS[SyscallId::Recvfrom as usize].store(socket::sys_recvfrom as _, Ordering::Release);
+    HANDLERS[SyscallId::Sendmsg as usize].store(socket::sys_sendmsg as _, Ordering::Release);
+    HANDLERS[SyscallId::Recvmsg as usize].store(socket::sys_recvmsg as _, Ordering::Release);
+    HANDLERS[SyscallId::Mmap as usize].store(memory::sys_mmap as _, Ordering::Release);
+    HANDLERS[SyscallId::Munmap as usize].store(memory::sys_munmap as _, Ordering::Release);
+    HANDLERS[SyscallId::MemfdCreate as usize]
+        .store(memory::sys_memfd_create as _, Ordering::Release);
+    HANDLERS[SyscallId::Shmget as usize].store(memory::sys_shmget as _, Ordering::Release);
+    HANDLERS[SyscallId::Shmat as usize].store(memory::sys_shmat as _, Ordering::Release);
+    HANDLERS[SyscallId::Shmdt as usize].store(memory::sys_shmdt as _, Ordering::Release);
+    HANDLERS[SyscallId::Shmctl as usize].store(memory::sys_shmctl as _, Ordering::Release);
+    HANDLERS[SyscallId::Mount as usize].store(mount::sys_mount as _, Ordering::Release);
+    HANDLERS[SyscallId::Umount2 as usize].store(mount::sys_umount2 as _, Ordering::Release);
+    HANDLERS[SyscallId::PivotRoot as usize].store(mount::sys_pivot_root as _, Ordering::Release);
+    HANDLERS[SyscallId::MqOpen as usize].store(ipc::sys_mq_open as _, Ordering::Release);
+    HANDLERS[SyscallId::MqUnlink as usize].store(ipc::sys_mq_unlink as _, Ordering::Release);
+    HANDLERS[SyscallId::MqTimedsend as usize].store(ipc::sys_mq_timedsend as _, Ordering::Release);
+    HANDLERS[SyscallId::MqTimedreceive as usize]
+        .store(ipc::sys_mq_timedreceive as _, Ordering::Release);
+    HANDLERS[SyscallId::MqNotify as usize].store(ipc::sys_mq_notify as _, Ordering::Release);
+    HANDLERS[SyscallId::MqGetsetattr as usize]
+        .store(ipc::sys_mq_getsetattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Semget as usize].store(ipc::sys_semget as _, Ordering::Release);
+    HANDLERS[SyscallId::Semop as usize].store(ipc::sys_semop as _, Ordering::Release);
+    HANDLERS[SyscallId::Semtimedop as usize].store(ipc::sys_semtimedop as _, Ordering::Release);
+    HANDLERS[SyscallId::Semctl as usize].store(ipc::sys_semctl as _, Ordering::Release);
+    HANDLERS[SyscallId::Link as usize].store(link::sys_link as _, Ordering::Release);
+    HANDLERS[SyscallId::Linkat as usize].store(link::sys_linkat as _, Ordering::Release);
+    HANDLERS[SyscallId::Symlink as usize].store(link::sys_symlink as _, Ordering::Release);
+    HANDLERS[SyscallId::Symlinkat as usize].store(link::sys_symlinkat as _, Ordering::Release);
+    HANDLERS[SyscallId::Readlink as usize].store(link::sys_readlink as _, Ordering::Release);
+    HANDLERS[SyscallId::Readlinkat as usize].store(link::sys_readlinkat as _, Ordering::Release);
+    HANDLERS[SyscallId::Mknod as usize].store(link::sys_mknod as _, Ordering::Release);
+    HANDLERS[SyscallId::Mknodat as usize].store(link::sys_mknodat as _, Ordering::Release);
+    HANDLERS[SyscallId::Statfs as usize].store(mount::sys_statfs as _, Ordering::Release);
+    HANDLERS[SyscallId::Fstatfs as usize].store(mount::sys_fstatfs as _, Ordering::Release);
+    HANDLERS[SyscallId::Utimensat as usize].store(attr::sys_utimensat as _, Ordering::Release);
+    HANDLERS[SyscallId::Chmod as usize].store(attr::sys_chmod as _, Ordering::Release);
+    HANDLERS[SyscallId::Fchmod as usize].store(attr::sys_fchmod as _, Ordering::Release);
+    HANDLERS[SyscallId::Fchmodat as usize].store(attr::sys_fchmodat as _, Ordering::Release);
+    HANDLERS[SyscallId::Chown as usize].store(attr::sys_chown as _, Ordering::Release);
+    HANDLERS[SyscallId::Fchown as usize].store(attr::sys_fchown as _, Ordering::Release);
+    HANDLERS[SyscallId::Lchown as usize].store(attr::sys_lchown as _, Ordering::Release);
+    HANDLERS[SyscallId::Fchownat as usize].store(attr::sys_fchownat as _, Ordering::Release);
+    HANDLERS[SyscallId::Sync as usize].store(mount::sys_sync as _, Ordering::Release);
+    HANDLERS[SyscallId::SyncFs as usize].store(mount::sys_syncfs as _, Ordering::Release);
+    HANDLERS[SyscallId::Fsync as usize].store(mount::sys_fsync as _, Ordering::Release);
+    HANDLERS[SyscallId::Fdatasync as usize].store(mount::sys_fdatasync as _, Ordering::Release);
+    HANDLERS[SyscallId::Poll as usize].store(poll::sys_poll as _, Ordering::Release);
+    HANDLERS[SyscallId::Ppoll as usize].store(poll::sys_ppoll as _, Ordering::Release);
+    HANDLERS[SyscallId::EpollCreate as usize].store(poll::sys_epoll_create as _, Ordering::Release);
+    HANDLERS[SyscallId::EpollCreate1 as usize]
+        .store(poll::sys_epoll_create1 as _, Ordering::Release);
+    HANDLERS[SyscallId::EpollCtl as usize].store(poll::sys_epoll_ctl as _, Ordering::Release);
+    HANDLERS[SyscallId::EpollWait as usize].store(poll::sys_epoll_wait as _, Ordering::Release);
+    HANDLERS[SyscallId::EpollPwait as usize].store(poll::sys_epoll_pwait as _, Ordering::Release);
+    HANDLERS[SyscallId::Eventfd as usize].store(event::sys_eventfd as _, Ordering::Release);
+    HANDLERS[SyscallId::Eventfd2 as usize].store(event::sys_eventfd2 as _, Ordering::Release);
+    HANDLERS[SyscallId::TimerfdCreate as usize]
+        .store(event::sys_timerfd_create as _, Ordering::Release);
+    HANDLERS[SyscallId::TimerfdSettime as usize]
+        .store(event::sys_timerfd_settime as _, Ordering::Release);
+    HANDLERS[SyscallId::TimerfdGettime as usize]
+        .store(event::sys_timerfd_gettime as _, Ordering::Release);
+    HANDLERS[SyscallId::Signalfd as usize].store(event::sys_signalfd as _, Ordering::Release);
+    HANDLERS[SyscallId::Signalfd4 as usize].store(event::sys_signalfd4 as _, Ordering::Release);
     HANDLERS[SyscallId::ClockGettime as usize].store(sys_clock_gettime as _, Ordering::Release);
     HANDLERS[SyscallId::Clone as usize].store(sys_clone as _, Ordering::Release);
     HANDLERS[SyscallId::Fork as usize].store(sys_fork as _, Ordering::Release);
@@ -1200,6 +1880,20 @@ pub fn init() {
     HANDLERS[SyscallId::SchedYield as usize].store(sys_yield as _, Ordering::Release);
     HANDLERS[SyscallId::Nanosleep as usize].store(sys_nanosleep as _, Ordering::Release);
     HANDLERS[SyscallId::Exit as usize].store(sys_exit as _, Ordering::Release);
+    HANDLERS[SyscallId::Setxattr as usize].store(xattr::sys_setxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Lsetxattr as usize].store(xattr::sys_lsetxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Fsetxattr as usize].store(xattr::sys_fsetxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Getxattr as usize].store(xattr::sys_getxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Lgetxattr as usize].store(xattr::sys_lgetxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Fgetxattr as usize].store(xattr::sys_fgetxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Listxattr as usize].store(xattr::sys_listxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Llistxattr as usize].store(xattr::sys_llistxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Flistxattr as usize].store(xattr::sys_flistxattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Removexattr as usize].store(xattr::sys_removexattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Lremovexattr as usize]
+        .store(xattr::sys_lremovexattr as _, Ordering::Release);
+    HANDLERS[SyscallId::Fremovexattr as usize]
+        .store(xattr::sys_fremovexattr as _, Ordering::Release);
 
     // IA32_EFER syscall
     wrmsr(0xC0000080, rdmsr(0xC0000080) | (1 << 0));
@@ -0,0 +1,272 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::vec::Vec;
+
+use crate::{
+    drivers::fs::{
+        FsStats, Mount, MountFlags, UmountFlags, canonicalize, flush_deferred, get_filesystem,
+        get_vfs, get_vfs_mut, mount, umount,
+    },
+    scheduler::get_scheduler,
+    utils::asm::without_ints,
+};
+
+use super::*;
+
+// old userspace puts this magic in the top half of the flags
+const MS_MGC_VAL: u64 = 0xc0ed_0000;
+const MS_MGC_MSK: u64 = 0xffff_0000;
+
+// optional string arguments can be null
+fn optional_cstr(ptr: u64) -> Result<&'static str, i64> {
+    match ptr {
+        0 => Ok(""),
+        _ => validate_user_cstr(ptr).ok_or(EFAULT),
+    }
+}
+
+fn current_path(path: &str) -> Path {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    resolve_path(path, proc.get_cwd())
+}
+
+pub(super) fn sys_mount(regs: &mut Registers) {
+    let ret = do_mount(regs);
+    set_result(regs, ret);
+}
+
+fn do_mount(regs: &Registers) -> Result<u64, i64> {
+    let source = optional_cstr(regs.rdi)?;
+    let target = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    let fs_type = optional_cstr(regs.rdx)?;
+    let mut flags = regs.r10;
+    let data = optional_cstr(regs.r8)?;
+
+    if flags & MS_MGC_MSK == MS_MGC_VAL {
+        flags &= !MS_MGC_MSK;
+    }
+    let flags = MountFlags::from_bits_truncate(flags);
+    let target = current_path(target);
+    // a disk is named by a path like the target, anything else takes the source as it is
+    let device = get_filesystem(fs_type)
+        .filter(|fs| !fs.nodev() && !source.is_empty())
+        .map(|_| current_path(source));
+    let source = device.as_ref().map_or(source, |path| path.as_str());
+
+    mount(source, &target, fs_type, flags, data)?;
+    Ok(0)
+}
+
+// the mounts of open files and the working directories of every process, these keep
+// a mount busy
+fn in_use() -> (Vec<u64>, Vec<Path>) {
+    without_ints(|| {
+        let mut devs = Vec::new();
+        let mut cwds = Vec::new();
+        for proc in get_scheduler().processes.iter() {
+            let proc = proc.lock();
+            devs.extend(
+                proc.fdt
+                    .values()
+                    .filter_map(|f| f.inode())
+                    .map(|inode| inode.dev()),
+            );
+            cwds.extend(canonicalize(proc.get_cwd()));
+        }
+        (devs, cwds)
+    })
+}
+
+pub(super) fn sys_umount2(regs: &mut Registers) {
+    let ret = do_umount2(regs);
+    set_result(regs, ret);
+}
+
+fn do_umount2(regs: &Registers) -> Result<u64, i64> {
+    let target = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let flags = UmountFlags::from_bits(regs.rsi).ok_or(EINVAL)?;
+    let target = current_path(target);
+
+    let (devs, cwds) = in_use();
+    umount(&target, flags, |mount| {
+        devs.contains(&mount.sb.dev) || cwds.iter().any(|c| mount.covers(c))
+    })?;
+    Ok(0)
+}
+
+pub(super) fn sys_pivot_root(regs: &mut Registers) {
+    let ret = do_pivot_root(regs);
+    set_result(regs, ret);
+}
+
+fn do_pivot_root(regs: &Registers) -> Result<u64, i64> {
+    let new_root = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let put_old = validate_user_cstr(regs.rsi).ok_or(EFAULT)?;
+    let (new_root, put_old) = (current_path(new_root), current_path(put_old));
+
+    let pivot = get_vfs_mut().pivot_root(&new_root, &put_old)?;
+    // paths are all that's kept, so they move with the mounts
+    without_ints(|| {
+        for proc in get_scheduler().processes.iter() {
+            let mut proc = proc.lock();
+            if let Some(cwd) = canonicalize(proc.get_cwd()) {
+                proc.set_cwd(pivot.moved(&cwd));
+            }
+            for file in proc.fdt.values_mut() {
+                if let Some(path) = file.path.as_ref().and_then(canonicalize) {
+                    file.path = Some(pivot.moved(&path));
+                }
+            }
+        }
+    });
+    Ok(0)
+}
+
+// struct statfs
+#[repr(C)]
+struct StatFs {
+    f_type: i64,
+    f_bsize: i64,
+    f_blocks: u64,
+    f_bfree: u64,
+    f_bavail: u64,
+    f_files: u64,
+    f_ffree: u64,
+    f_fsid: [i32; 2],
+    f_namelen: i64,
+    f_frsize: i64,
+    f_flags: i64,
+    f_spare: [i64; 4],
+}
+
+const ST_VALID: i64 = 0x20;
+
+// the ST_ flags are the MS_ ones except for relatime
+fn statfs_flags(flags: MountFlags) -> i64 {
+    let same = MountFlags::RDONLY
+        | MountFlags::NOSUID
+        | MountFlags::NODEV
+        | MountFlags::NOEXEC
+        | MountFlags::NOATIME
+        | MountFlags::NODIRATIME;
+    let mut bits = (flags & same).bits() as i64 | ST_VALID;
+    if flags.contains(MountFlags::RELATIME) {
+        bits |= 4096;
+    }
+    bits
+}
+
+// `mount` is None for descriptors that aren't on any filesystem, sockets and such
+fn fill_statfs(buf: u64, mount: Option<&Mount>, stats: FsStats) -> Result<u64, i64> {
+    if !validate_user_buf(buf, size_of::<StatFs>() as _) {
+        return Err(EFAULT);
+    }
+    let (magic, dev, flags) = mount.map_or((0, 0, ST_VALID), |m| {
+        (m.sb.magic, m.sb.dev, statfs_flags(m.sb.flags))
+    });
+    unsafe {
+        *(buf as *mut StatFs) = StatFs {
+            f_type: magic as i64,
+            f_bsize: 4096,
+            f_blocks: stats.blocks,
+            f_bfree: stats.bfree,
+            f_bavail: stats.bavail,
+            f_files: 0,
+            f_ffree: 0,
+            f_fsid: [dev as i32, (dev >> 32) as i32],
+            f_namelen: 255,
+            f_frsize: 4096,
+            f_flags: flags,
+            f_spare: [0; 4],
+        }
+    };
+    Ok(0)
+}
+
+pub(super) fn sys_statfs(regs: &mut Registers) {
+    let ret = do_statfs(regs);
+    set_result(regs, ret);
+}
+
+fn do_statfs(regs: &Registers) -> Result<u64, i64> {
+    let path = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+    let path = current_path(path);
+
+    let vfs = get_vfs();
+    let inode = vfs.walk(&path, true)?;
+    let stats = inode.node().statfs().unwrap_or_default();
+    fill_statfs(regs.rsi, vfs.mount_of(&path), stats)
+}
+
+pub(super) fn sys_fstatfs(regs: &mut Registers) {
+    let ret = do_fstatfs(regs);
+    set_result(regs, ret);
+}
+
+fn do_fstatfs(regs: &Registers) -> Result<u64, i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&(regs.rdi as i32)).ok_or(EBADF)?;
+    let inode = file.inode().cloned();
+    drop(proc);
+
+    let Some(inode) = inode else {
+        return fill_statfs(regs.rsi, None, FsStats::default());
+    };
+    let stats = inode.node().statfs().unwrap_or_default();
+    let vfs = get_vfs();
+    fill_statfs(regs.rsi, vfs.mount_of_inode(&inode), stats)
+}
+
+// the buffer cache is one for every device, so syncfs writes it all out like sync
+pub(super) fn sys_sync(regs: &mut Registers) {
+    flush_deferred();
+    get_vfs().sync();
+    set_result(regs, Ok(0));
+}
+
+pub(super) fn sys_syncfs(regs: &mut Registers) {
+    let ret = do_syncfs(regs);
+    set_result(regs, ret);
+}
+
+fn do_syncfs(regs: &Registers) -> Result<u64, i64> {
+    let current = current_process().unwrap();
+    if !current.lock().fdt.contains_key(&(regs.rdi as i32)) {
+        return Err(EBADF);
+    }
+    flush_deferred();
+    get_vfs().sync();
+    Ok(0)
+}
+
+// files of in-memory filesystems are always synced, devices sync themselves and pipes,
+// sockets and the like give EINVAL
+fn fsync(fd: i32, data_only: bool) -> Result<u64, i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&fd).ok_or(EBADF)?;
+    let (inode, object) = (file.inode().cloned(), file.object().cloned());
+    drop(proc);
+
+    match (inode, object) {
+        (_, Some(object)) => object.sync()?,
+        (Some(inode), None) => inode.node().sync(data_only)?,
+        (None, None) => return Err(EINVAL),
+    }
+    Ok(0)
+}
+
+pub(super) fn sys_fsync(regs: &mut Registers) {
+    let ret = fsync(regs.rdi as i32, false);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_fdatasync(regs: &mut Registers) {
+    let ret = fsync(regs.rdi as i32, true);
+    set_result(regs, ret);
+}
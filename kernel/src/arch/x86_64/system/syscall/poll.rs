@@ -0,0 +1,254 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    arch::drivers::time::preferred_timer_ns,
+    drivers::fs::PollEvents,
+    ipc::epoll::{EPOLL_CTL_DEL, EventPoll},
+    scheduler::wait::{WaitQueue, wait_any},
+};
+
+use super::*;
+
+const POLL_MAX_FDS: u64 = 4096;
+const EPOLL_MAX_EVENTS: u64 = 4096;
+
+#[repr(C)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+#[repr(C, packed)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+enum Target {
+    Ignored,
+    Fixed(PollEvents),
+    Object(Arc<dyn FileObject>),
+}
+
+impl Target {
+    fn poll(&self) -> PollEvents {
+        match self {
+            Target::Ignored => PollEvents::empty(),
+            Target::Fixed(events) => *events,
+            Target::Object(object) => object.poll(),
+        }
+    }
+}
+
+// the console never blocks, stdin just reads eof
+fn lookup_target(fd: i32) -> Target {
+    match fd {
+        ..0 => Target::Ignored,
+        0 => Target::Fixed(PollEvents::IN),
+        1 | 2 => Target::Fixed(PollEvents::OUT),
+        _ => {
+            let current = current_process().unwrap();
+            let proc = current.lock();
+            match proc.fdt.get(&fd) {
+                None => Target::Fixed(PollEvents::NVAL),
+                Some(file) => match file.object() {
+                    Some(object) => Target::Object(object.clone()),
+                    None => Target::Fixed(PollEvents::IN | PollEvents::OUT),
+                },
+            }
+        }
+    }
+}
+
+// a negative timeout means forever
+pub(super) fn deadline_after(ns: Option<u64>) -> Option<u64> {
+    ns.map(|ns| preferred_timer_ns().saturating_add(ns))
+}
+
+fn do_poll(fds: u64, nfds: u64, timeout: Option<u64>) -> Result<u64, i64> {
+    if nfds > POLL_MAX_FDS {
+        return Err(EINVAL);
+    }
+    if nfds != 0 && (fds == 0 || !validate_user_buf(fds, nfds * size_of::<PollFd>() as u64)) {
+        return Err(EFAULT);
+    }
+    let pollfds = unsafe { core::slice::from_raw_parts_mut(fds as *mut PollFd, nfds as usize) };
+
+    let targets: Vec<Target> = pollfds.iter().map(|p| lookup_target(p.fd)).collect();
+    let queues: Vec<Arc<WaitQueue>> = targets
+        .iter()
+        .filter_map(|t| match t {
+            Target::Object(object) => Some(object.wait_queues()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let queues: Vec<&WaitQueue> = queues.iter().map(|q| &**q).collect();
+    let timers = targets
+        .iter()
+        .filter_map(|t| match t {
+            Target::Object(object) => object.deadline(),
+            _ => None,
+        })
+        .min();
+    let deadline = deadline_after(timeout);
+
+    loop {
+        let wake_at = match (deadline, timers) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let ready = wait_any(&queues, wake_at, || {
+            let mut count = 0;
+            for (pollfd, target) in pollfds.iter_mut().zip(&targets) {
+                let wanted = pollfd.events as u16 as u32
+                    | (PollEvents::ERR | PollEvents::HUP | PollEvents::NVAL).bits();
+                pollfd.revents = (target.poll().bits() & wanted) as i16;
+                if pollfd.revents != 0 {
+                    count += 1;
+                }
+            }
+            (count > 0).then_some(count)
+        });
+        match ready {
+            Some(count) => return Ok(count),
+            None if deadline.is_some_and(|d| preferred_timer_ns() >= d) => return Ok(0),
+            // a timer expired but nobody asked for it, keep going
+            None => {}
+        }
+    }
+}
+
+pub(super) fn sys_poll(regs: &mut Registers) {
+    let timeout = regs.rdx as i32;
+    let timeout = (timeout >= 0).then(|| timeout as u64 * 1_000_000);
+    let ret = do_poll(regs.rdi, regs.rsi, timeout);
+    set_result(regs, ret);
+}
+
+// the signal mask is ignored, there's no signal delivery to race with
+pub(super) fn sys_ppoll(regs: &mut Registers) {
+    let ret = read_timeout(regs.rdx).and_then(|timeout| do_poll(regs.rdi, regs.rsi, timeout));
+    set_result(regs, ret);
+}
+
+pub(super) fn read_timeout(ts: u64) -> Result<Option<u64>, i64> {
+    if ts == 0 {
+        return Ok(None);
+    }
+    if !validate_user_buf(ts, size_of::<Timespec>() as _) {
+        return Err(EFAULT);
+    }
+    let ts = unsafe { &*(ts as *const Timespec) };
+    if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec >= 1_000_000_000 {
+        return Err(EINVAL);
+    }
+    Ok(Some(
+        (ts.tv_sec as u64)
+            .saturating_mul(1_000_000_000)
+            .saturating_add(ts.tv_nsec as u64),
+    ))
+}
+
+fn new_epoll() -> u64 {
+    install_fd(FileDescriptor::from_object(
+        EventPoll::new(),
+        Permissions::READ,
+    )) as _
+}
+
+pub(super) fn sys_epoll_create(regs: &mut Registers) {
+    regs.rax = if (regs.rdi as i32) <= 0 {
+        -EINVAL as _
+    } else {
+        new_epoll()
+    };
+}
+
+pub(super) fn sys_epoll_create1(regs: &mut Registers) {
+    const EPOLL_CLOEXEC: u64 = 0o2000000;
+    regs.rax = if regs.rdi & !EPOLL_CLOEXEC != 0 {
+        -EINVAL as _
+    } else {
+        new_epoll()
+    };
+}
+
+fn get_epoll(epfd: u64) -> Result<Arc<EventPoll>, i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&(epfd as i32)).ok_or(EBADF)?;
+    file.object_as::<EventPoll>().ok_or(EINVAL)
+}
+
+pub(super) fn sys_epoll_ctl(regs: &mut Registers) {
+    let ret = do_epoll_ctl(regs);
+    set_result(regs, ret);
+}
+
+fn do_epoll_ctl(regs: &Registers) -> Result<u64, i64> {
+    let op = regs.rsi;
+    let fd = regs.rdx as i32;
+    let event = regs.r10;
+
+    let (events, data) = if op == EPOLL_CTL_DEL {
+        (0, 0)
+    } else {
+        if event == 0 || !validate_user_buf(event, size_of::<EpollEvent>() as _) {
+            return Err(EFAULT);
+        }
+        let event = unsafe { core::ptr::read_unaligned(event as *const EpollEvent) };
+        (event.events, event.data)
+    };
+
+    let epoll = get_epoll(regs.rdi)?;
+    // only objects can wake us up, plain files are always ready so linux refuses them too
+    let object = match lookup_target(fd) {
+        Target::Object(object) => object,
+        Target::Fixed(PollEvents::NVAL) | Target::Ignored => return Err(EBADF),
+        Target::Fixed(_) => return Err(EPERM),
+    };
+    epoll.ctl(op, fd, object, events, data)?;
+    Ok(0)
+}
+
+pub(super) fn sys_epoll_wait(regs: &mut Registers) {
+    let ret = do_epoll_wait(regs);
+    set_result(regs, ret);
+}
+
+// same as epoll_wait, the signal mask is ignored like in ppoll
+pub(super) fn sys_epoll_pwait(regs: &mut Registers) {
+    let ret = do_epoll_wait(regs);
+    set_result(regs, ret);
+}
+
+fn do_epoll_wait(regs: &Registers) -> Result<u64, i64> {
+    let events = regs.rsi;
+    let maxevents = regs.rdx as i32;
+    let timeout = regs.r10 as i32;
+
+    if maxevents <= 0 || maxevents as u64 > EPOLL_MAX_EVENTS {
+        return Err(EINVAL);
+    }
+    let len = maxevents as u64 * size_of::<EpollEvent>() as u64;
+    if events == 0 || !validate_user_buf(events, len) {
+        return Err(EFAULT);
+    }
+
+    let epoll = get_epoll(regs.rdi)?;
+    let deadline = deadline_after((timeout >= 0).then(|| timeout as u64 * 1_000_000));
+    let ready = epoll.wait(maxevents as usize, deadline);
+
+    let out = events as *mut EpollEvent;
+    for (i, &(events, data)) in ready.iter().enumerate() {
+        unsafe { core::ptr::write_unaligned(out.add(i), EpollEvent { events, data }) };
+    }
+    Ok(ready.len() as _)
+}
@@ -0,0 +1,448 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::ipc::unix::{SocketType, UnixSocket};
+
+use super::*;
+
+const AF_UNIX: u64 = 1;
+
+const SOCK_STREAM: u64 = 1;
+const SOCK_DGRAM: u64 = 2;
+const SOCK_TYPE_MASK: u64 = 0xf;
+const SOCK_NONBLOCK: u64 = 0o4000;
+const SOCK_CLOEXEC: u64 = 0o2000000;
+
+const MSG_PEEK: u64 = 0x2;
+const MSG_CTRUNC: i32 = 0x8;
+const MSG_TRUNC: i32 = 0x20;
+const MSG_DONTWAIT: u64 = 0x40;
+
+const SOL_SOCKET: i32 = 1;
+const SCM_RIGHTS: i32 = 1;
+const SCM_MAX_FD: usize = 253;
+
+const SHUT_RD: u64 = 0;
+const SHUT_WR: u64 = 1;
+const SHUT_RDWR: u64 = 2;
+
+const SUN_PATH_MAX: usize = 108;
+
+#[repr(C)]
+struct SockAddrUn {
+    sun_family: u16,
+    sun_path: [u8; SUN_PATH_MAX],
+}
+
+#[repr(C)]
+struct MsgHdr {
+    msg_name: u64,
+    msg_namelen: u32,
+    __pad0: u32,
+    msg_iov: u64,
+    msg_iovlen: u64,
+    msg_control: u64,
+    msg_controllen: u64,
+    msg_flags: i32,
+}
+
+#[repr(C)]
+struct CmsgHdr {
+    cmsg_len: u64,
+    cmsg_level: i32,
+    cmsg_type: i32,
+}
+
+const CMSG_HDR_LEN: u64 = size_of::<CmsgHdr>() as u64;
+
+fn new_socket_fd(socket: Arc<UnixSocket>, nonblock: bool) -> i32 {
+    install_fd(FileDescriptor::from_object(socket, Permissions::RW).with_nonblock(nonblock))
+}
+
+// looks up a socket fd, the process lock is released before returning
+fn get_socket(fd: u64) -> Result<(Arc<UnixSocket>, bool), i64> {
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let file = proc.fdt.get(&(fd as i32)).ok_or(EBADF)?;
+    let socket = file.object_as::<UnixSocket>().ok_or(ENOTSOCK)?;
+    Ok((socket, file.nonblock))
+}
+
+fn socket_type(type_: u64) -> Result<SocketType, i64> {
+    if type_ & !(SOCK_TYPE_MASK | SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(EINVAL);
+    }
+    match type_ & SOCK_TYPE_MASK {
+        SOCK_STREAM => Ok(SocketType::Stream),
+        SOCK_DGRAM => Ok(SocketType::Datagram),
+        _ => Err(ESOCKTNOSUPPORT),
+    }
+}
+
+fn read_sockaddr(addr: u64, len: u64) -> Result<Path, i64> {
+    if len <= 2 || len > size_of::<SockAddrUn>() as u64 {
+        return Err(EINVAL);
+    }
+    if !validate_user_buf(addr, len) {
+        return Err(EFAULT);
+    }
+
+    let sa = unsafe { &*(addr as *const SockAddrUn) };
+    if sa.sun_family as u64 != AF_UNIX {
+        return Err(EAFNOSUPPORT);
+    }
+
+    let raw = &sa.sun_path[..len as usize - 2];
+    let raw = &raw[..raw.iter().position(|&b| b == 0).unwrap_or(raw.len())];
+    // abstract addresses (leading nul) aren't supported
+    if raw.is_empty() {
+        return Err(EINVAL);
+    }
+    let path = core::str::from_utf8(raw).map_err(|_| EINVAL)?;
+
+    let current = current_process().unwrap();
+    let cwd = current.lock().get_cwd().clone();
+    Ok(resolve_path(path, &cwd))
+}
+
+// writes a sockaddr_un back to userspace, unbound sockets only get the family
+fn write_sockaddr(addr: u64, addrlen: u64, path: Option<&str>) -> Result<(), i64> {
+    if addr == 0 {
+        return Ok(());
+    }
+    if !validate_user_buf(addrlen, 4) {
+        return Err(EFAULT);
+    }
+
+    let avail = unsafe { *(addrlen as *const u32) } as usize;
+    if !validate_user_buf(addr, avail as u64) {
+        return Err(EFAULT);
+    }
+
+    let mut sa = SockAddrUn {
+        sun_family: AF_UNIX as u16,
+        sun_path: [0; SUN_PATH_MAX],
+    };
+    let path = path.unwrap_or_default().as_bytes();
+    let path_len = path.len().min(SUN_PATH_MAX - 1);
+    sa.sun_path[..path_len].copy_from_slice(&path[..path_len]);
+    let full_len = if path_len == 0 { 2 } else { 2 + path_len + 1 };
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            &sa as *const SockAddrUn as *const u8,
+            addr as *mut u8,
+            avail.min(full_len),
+        );
+        *(addrlen as *mut u32) = full_len as u32;
+    }
+    Ok(())
+}
+
+pub(super) fn sys_socket(regs: &mut Registers) {
+    let ret = do_socket(regs);
+    set_result(regs, ret);
+}
+
+fn do_socket(regs: &Registers) -> Result<u64, i64> {
+    if regs.rdi != AF_UNIX {
+        return Err(EAFNOSUPPORT);
+    }
+    let type_ = socket_type(regs.rsi)?;
+    if regs.rdx != 0 {
+        return Err(EPROTONOSUPPORT);
+    }
+    let fd = new_socket_fd(UnixSocket::new(type_), regs.rsi & SOCK_NONBLOCK != 0);
+    Ok(fd as u64)
+}
+
+pub(super) fn sys_socketpair(regs: &mut Registers) {
+    let ret = do_socketpair(regs);
+    set_result(regs, ret);
+}
+
+fn do_socketpair(regs: &Registers) -> Result<u64, i64> {
+    if regs.rdi != AF_UNIX {
+        return Err(EAFNOSUPPORT);
+    }
+    let type_ = socket_type(regs.rsi)?;
+    if regs.rdx != 0 {
+        return Err(EPROTONOSUPPORT);
+    }
+    let sv = regs.r10;
+    if !validate_user_buf(sv, 8) {
+        return Err(EFAULT);
+    }
+
+    let nonblock = regs.rsi & SOCK_NONBLOCK != 0;
+    let (a, b) = UnixSocket::pair(type_);
+    let fds = [new_socket_fd(a, nonblock), new_socket_fd(b, nonblock)];
+    unsafe { *(sv as *mut [i32; 2]) = fds };
+    Ok(0)
+}
+
+pub(super) fn sys_bind(regs: &mut Registers) {
+    let ret = do_bind(regs);
+    set_result(regs, ret);
+}
+
+fn do_bind(regs: &Registers) -> Result<u64, i64> {
+    let (socket, _) = get_socket(regs.rdi)?;
+    let path = read_sockaddr(regs.rsi, regs.rdx)?;
+    socket.bind(path)?;
+    Ok(0)
+}
+
+pub(super) fn sys_listen(regs: &mut Registers) {
+    let ret = do_listen(regs);
+    set_result(regs, ret);
+}
+
+fn do_listen(regs: &Registers) -> Result<u64, i64> {
+    let (socket, _) = get_socket(regs.rdi)?;
+    socket.listen((regs.rsi as i32).max(0) as usize)?;
+    Ok(0)
+}
+
+fn do_accept(regs: &Registers, flags: u64) -> Result<u64, i64> {
+    if flags & !(SOCK_NONBLOCK | SOCK_CLOEXEC) != 0 {
+        return Err(EINVAL);
+    }
+    let (socket, nonblock) = get_socket(regs.rdi)?;
+    let conn = socket.accept(nonblock)?;
+    let peer = conn.peer_path().ok().flatten();
+    write_sockaddr(regs.rsi, regs.rdx, peer.as_deref())?;
+    let fd = new_socket_fd(conn, flags & SOCK_NONBLOCK != 0);
+    Ok(fd as u64)
+}
+
+pub(super) fn sys_accept(regs: &mut Registers) {
+    let ret = do_accept(regs, 0);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_accept4(regs: &mut Registers) {
+    let ret = do_accept(regs, regs.r10);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_connect(regs: &mut Registers) {
+    let ret = do_connect(regs);
+    set_result(regs, ret);
+}
+
+fn do_connect(regs: &Registers) -> Result<u64, i64> {
+    let (socket, nonblock) = get_socket(regs.rdi)?;
+    let path = read_sockaddr(regs.rsi, regs.rdx)?;
+    socket.connect(path, nonblock)?;
+    Ok(0)
+}
+
+pub(super) fn sys_shutdown(regs: &mut Registers) {
+    let ret = do_shutdown(regs);
+    set_result(regs, ret);
+}
+
+fn do_shutdown(regs: &Registers) -> Result<u64, i64> {
+    let (socket, _) = get_socket(regs.rdi)?;
+    let (read, write) = match regs.rsi {
+        SHUT_RD => (true, false),
+        SHUT_WR => (false, true),
+        SHUT_RDWR => (true, true),
+        _ => return Err(EINVAL),
+    };
+    socket.shutdown(read, write)?;
+    Ok(0)
+}
+
+pub(super) fn sys_sendto(regs: &mut Registers) {
+    let ret = do_sendto(regs);
+    set_result(regs, ret);
+}
+
+fn do_sendto(regs: &Registers) -> Result<u64, i64> {
+    let (socket, nonblock) = get_socket(regs.rdi)?;
+    let (buf, len, flags) = (regs.rsi, regs.rdx, regs.r10);
+    if !validate_user_buf(buf, len) {
+        return Err(EFAULT);
+    }
+    let dest = match regs.r8 {
+        0 => None,
+        addr => Some(read_sockaddr(addr, regs.r9)?),
+    };
+
+    let data = unsafe { core::slice::from_raw_parts(buf as *const u8, len as usize) };
+    let n = socket.send(
+        data,
+        Vec::new(),
+        dest,
+        nonblock || flags & MSG_DONTWAIT != 0,
+    )?;
+    Ok(n as u64)
+}
+
+pub(super) fn sys_recvfrom(regs: &mut Registers) {
+    let ret = do_recvfrom(regs);
+    set_result(regs, ret);
+}
+
+fn do_recvfrom(regs: &Registers) -> Result<u64, i64> {
+    let (socket, nonblock) = get_socket(regs.rdi)?;
+    let (buf, len, flags) = (regs.rsi, regs.rdx, regs.r10);
+    if !validate_user_buf(buf, len) {
+        return Err(EFAULT);
+    }
+
+    let slice = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len as usize) };
+    let received = socket.recv(
+        slice,
+        flags & MSG_PEEK != 0,
+        nonblock || flags & MSG_DONTWAIT != 0,
+    )?;
+    write_sockaddr(regs.r8, regs.r9, received.from.as_deref())?;
+    Ok(received.len as u64)
+}
+
+// collects the fds of every SCM_RIGHTS message in the control buffer
+fn read_rights(control: u64, controllen: u64) -> Result<Vec<FileDescriptor>, i64> {
+    let mut rights = Vec::new();
+    if control == 0 || controllen == 0 {
+        return Ok(rights);
+    }
+    if !validate_user_buf(control, controllen) {
+        return Err(EFAULT);
+    }
+
+    let current = current_process().unwrap();
+    let proc = current.lock();
+    let mut offset = 0;
+    while offset + CMSG_HDR_LEN <= controllen {
+        let hdr = unsafe { &*((control + offset) as *const CmsgHdr) };
+        if hdr.cmsg_len < CMSG_HDR_LEN || offset + hdr.cmsg_len > controllen {
+            return Err(EINVAL);
+        }
+
+        if hdr.cmsg_level == SOL_SOCKET && hdr.cmsg_type == SCM_RIGHTS {
+            let count = (hdr.cmsg_len - CMSG_HDR_LEN) as usize / 4;
+            let fds = unsafe {
+                core::slice::from_raw_parts((control + offset + CMSG_HDR_LEN) as *const i32, count)
+            };
+            for fd in fds {
+                let file = proc.fdt.get(fd).ok_or(EBADF)?;
+                rights.push(file.dup());
+            }
+            if rights.len() > SCM_MAX_FD {
+                return Err(EINVAL);
+            }
+        } else {
+            return Err(EINVAL);
+        }
+
+        offset += hdr.cmsg_len.next_multiple_of(8);
+    }
+    Ok(rights)
+}
+
+// installs received fds and writes the SCM_RIGHTS message, returns (controllen, ctrunc)
+fn write_rights(control: u64, controllen: u64, rights: Vec<FileDescriptor>) -> (u64, bool) {
+    if rights.is_empty() {
+        return (0, false);
+    }
+    if control == 0 || controllen < CMSG_HDR_LEN + 4 || !validate_user_buf(control, controllen) {
+        return (0, true);
+    }
+
+    // whatever doesn't fit gets closed
+    let fit = ((controllen - CMSG_HDR_LEN) / 4) as usize;
+    let truncated = rights.len() > fit;
+    let fds: Vec<i32> = rights.into_iter().take(fit).map(install_fd).collect();
+
+    let cmsg_len = CMSG_HDR_LEN + fds.len() as u64 * 4;
+    unsafe {
+        *(control as *mut CmsgHdr) = CmsgHdr {
+            cmsg_len,
+            cmsg_level: SOL_SOCKET,
+            cmsg_type: SCM_RIGHTS,
+        };
+        core::ptr::copy_nonoverlapping(
+            fds.as_ptr(),
+            (control + CMSG_HDR_LEN) as *mut i32,
+            fds.len(),
+        );
+    }
+    (cmsg_len.next_multiple_of(8).min(controllen), truncated)
+}
+
+pub(super) fn sys_sendmsg(regs: &mut Registers) {
+    let ret = do_sendmsg(regs);
+    set_result(regs, ret);
+}
+
+fn do_sendmsg(regs: &Registers) -> Result<u64, i64> {
+    let (socket, nonblock) = get_socket(regs.rdi)?;
+    let (msg, flags) = (regs.rsi, regs.rdx);
+    if !validate_user_buf(msg, size_of::<MsgHdr>() as _) {
+        return Err(EFAULT);
+    }
+    let msg = unsafe { &*(msg as *const MsgHdr) };
+
+    let dest = match msg.msg_name {
+        0 => None,
+        addr => Some(read_sockaddr(addr, msg.msg_namelen as u64)?),
+    };
+    let (iovecs, total) = validate_user_iovecs(msg.msg_iov, msg.msg_iovlen)?;
+    let rights = read_rights(msg.msg_control, msg.msg_controllen)?;
+
+    let nonblock = nonblock || flags & MSG_DONTWAIT != 0;
+    // the rights go with the first chunk
+    let mut rights = Some(rights);
+    let n = write_iovecs(iovecs, total, |_, chunk| {
+        let rights = rights.take().unwrap_or_default();
+        socket.send(chunk, rights, dest.clone(), nonblock)
+    })?;
+    Ok(n as u64)
+}
+
+pub(super) fn sys_recvmsg(regs: &mut Registers) {
+    let ret = do_recvmsg(regs);
+    set_result(regs, ret);
+}
+
+fn do_recvmsg(regs: &Registers) -> Result<u64, i64> {
+    let (socket, nonblock) = get_socket(regs.rdi)?;
+    let (msg, flags) = (regs.rsi, regs.rdx);
+    if !validate_user_buf(msg, size_of::<MsgHdr>() as _) {
+        return Err(EFAULT);
+    }
+    let msg = unsafe { &mut *(msg as *mut MsgHdr) };
+    let (iovecs, total) = validate_user_iovecs(msg.msg_iov, msg.msg_iovlen)?;
+
+    // one chunk holds anything a socket has buffered
+    let mut data = alloc::vec![0u8; total.min(IOV_CHUNK)];
+    let received = socket.recv(
+        &mut data,
+        flags & MSG_PEEK != 0,
+        nonblock || flags & MSG_DONTWAIT != 0,
+    )?;
+    scatter_iovecs(iovecs, 0, &data[..received.len]);
+
+    if msg.msg_name != 0 {
+        let namelen = &mut msg.msg_namelen as *mut u32 as u64;
+        write_sockaddr(msg.msg_name, namelen, received.from.as_deref())?;
+    }
+
+    let (controllen, ctrunc) = write_rights(msg.msg_control, msg.msg_controllen, received.rights);
+    msg.msg_controllen = controllen;
+    msg.msg_flags = 0;
+    if ctrunc {
+        msg.msg_flags |= MSG_CTRUNC;
+    }
+    if received.truncated {
+        msg.msg_flags |= MSG_TRUNC;
+    }
+    Ok(received.len as u64)
+}
@@ -0,0 +1,255 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// extended attributes. the node keeps them (in its metadata or on disk), this checks the
+// names and does the flags and buffer sizes. every caller is root, so trusted. is open
+// to everyone and nothing looks at the acls
+
+use alloc::vec::Vec;
+
+use crate::drivers::fs::{InodeRef, VfsNodeType, get_vfs};
+
+use super::{
+    link::{AT_FDCWD, at_path},
+    *,
+};
+
+const XATTR_CREATE: u64 = 0x1;
+const XATTR_REPLACE: u64 = 0x2;
+const XATTR_NAME_MAX: usize = 255;
+const XATTR_SIZE_MAX: usize = 65536;
+
+const ACL_ACCESS: &str = "system.posix_acl_access";
+const ACL_DEFAULT: &str = "system.posix_acl_default";
+const ACL_XATTR_VERSION: u32 = 2;
+// ACL_USER_OBJ, ACL_USER, ACL_GROUP_OBJ, ACL_GROUP, ACL_MASK and ACL_OTHER
+const ACL_TAGS: [u16; 6] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20];
+
+// what the call works on, a path that's followed or not, or the fd in rdi
+#[derive(Clone, Copy)]
+enum Target {
+    Path { follow: bool },
+    Fd,
+}
+
+// the node, and EROFS on a read-only mount if it's going to be changed
+fn target_inode(regs: &Registers, target: Target, write: bool) -> Result<InodeRef, i64> {
+    let inode = match target {
+        Target::Path { follow } => {
+            let path = validate_user_cstr(regs.rdi).ok_or(EFAULT)?;
+            get_vfs().walk(&at_path(AT_FDCWD, path)?, follow)?
+        }
+        Target::Fd => {
+            let current = current_process().unwrap();
+            let proc = current.lock();
+            let file = proc.fdt.get(&(regs.rdi as i32)).ok_or(EBADF)?;
+            // sockets and pipes have no node to keep them on
+            file.inode().cloned().ok_or(EOPNOTSUPP)?
+        }
+    };
+    if write
+        && get_vfs()
+            .mount_of_inode(&inode)
+            .is_some_and(|m| m.is_readonly())
+    {
+        return Err(EROFS);
+    }
+    Ok(inode)
+}
+
+// the namespaces there are, anything else isn't supported
+fn check_name(ptr: u64) -> Result<&'static str, i64> {
+    let name = validate_user_cstr(ptr).ok_or(EFAULT)?;
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return Err(ERANGE);
+    }
+    if name == ACL_ACCESS || name == ACL_DEFAULT {
+        return Ok(name);
+    }
+    match ["user.", "trusted.", "security."]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+    {
+        Some("") => Err(EINVAL),
+        Some(_) => Ok(name),
+        None => Err(EOPNOTSUPP),
+    }
+}
+
+// user. is for what the owner puts on files and directories, devices and symlinks
+// belong to someone else
+fn user_allowed(name: &str, type_: &VfsNodeType) -> bool {
+    !name.starts_with("user.") || matches!(type_, VfsNodeType::File | VfsNodeType::Directory)
+}
+
+// the version and 8 byte entries of posix_acl_xattr
+fn check_acl(value: &[u8]) -> Result<(), i64> {
+    let (header, entries) = value.split_at_checked(4).ok_or(EINVAL)?;
+    if u32::from_le_bytes(header.try_into().unwrap()) != ACL_XATTR_VERSION
+        || !entries.len().is_multiple_of(8)
+    {
+        return Err(EINVAL);
+    }
+    for entry in entries.as_chunks::<8>().0 {
+        let tag = u16::from_le_bytes([entry[0], entry[1]]);
+        let perm = u16::from_le_bytes([entry[2], entry[3]]);
+        if !ACL_TAGS.contains(&tag) || perm & !0o7 != 0 {
+            return Err(EINVAL);
+        }
+    }
+    Ok(())
+}
+
+fn setxattr(regs: &Registers, target: Target) -> Result<u64, i64> {
+    let name = check_name(regs.rsi)?;
+    let (value, size, flags) = (regs.rdx, regs.r10 as usize, regs.r8);
+    if flags & !(XATTR_CREATE | XATTR_REPLACE) != 0 {
+        return Err(EINVAL);
+    }
+    if size > XATTR_SIZE_MAX {
+        return Err(E2BIG);
+    }
+    if !validate_user_buf(value, size as u64) || (size > 0 && value == 0) {
+        return Err(EFAULT);
+    }
+    let value = match size {
+        0 => &[][..],
+        _ => unsafe { core::slice::from_raw_parts(value as *const u8, size) },
+    };
+    if name == ACL_ACCESS || name == ACL_DEFAULT {
+        check_acl(value)?;
+    }
+
+    let inode = target_inode(regs, target, true)?;
+    let mut node = inode.node_mut();
+    if !user_allowed(name, node.get_type()) {
+        return Err(EPERM);
+    }
+    if name == ACL_DEFAULT && !node.is_dir() {
+        return Err(EACCES);
+    }
+    // the node's lock is held across both so no one gets in between
+    match node.get_xattr(name) {
+        Ok(_) if flags & XATTR_CREATE != 0 => return Err(EEXIST),
+        Err(ENODATA) if flags & XATTR_REPLACE != 0 => return Err(ENODATA),
+        Err(e) if e != ENODATA => return Err(e),
+        _ => {}
+    }
+    node.set_xattr(name, value)?;
+    node.get_metadata_mut().touch_changed();
+    Ok(0)
+}
+
+fn getxattr(regs: &Registers, target: Target) -> Result<u64, i64> {
+    let name = check_name(regs.rsi)?;
+    let (buf, size) = (regs.rdx, regs.r10 as usize);
+    let inode = target_inode(regs, target, false)?;
+    let node = inode.node();
+    if !user_allowed(name, node.get_type()) {
+        return Err(ENODATA);
+    }
+    let value = node.get_xattr(name)?;
+    drop(node);
+    copy_out(buf, size, &value)
+}
+
+fn listxattr(regs: &Registers, target: Target) -> Result<u64, i64> {
+    let (buf, size) = (regs.rsi, regs.rdx as usize);
+    let inode = target_inode(regs, target, false)?;
+    let names = inode.node().list_xattrs()?;
+    let mut list = Vec::new();
+    for name in names {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+    }
+    copy_out(buf, size, &list)
+}
+
+fn removexattr(regs: &Registers, target: Target) -> Result<u64, i64> {
+    let name = check_name(regs.rsi)?;
+    let inode = target_inode(regs, target, true)?;
+    let mut node = inode.node_mut();
+    if !user_allowed(name, node.get_type()) {
+        return Err(EPERM);
+    }
+    node.remove_xattr(name)?;
+    node.get_metadata_mut().touch_changed();
+    Ok(0)
+}
+
+// a size of 0 asks how big a buffer it takes, ERANGE if the one given is too small
+fn copy_out(buf: u64, size: usize, data: &[u8]) -> Result<u64, i64> {
+    if size == 0 {
+        return Ok(data.len() as u64);
+    }
+    if data.len() > size {
+        return Err(ERANGE);
+    }
+    if !validate_user_buf(buf, data.len() as u64) || buf == 0 {
+        return Err(EFAULT);
+    }
+    unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), buf as *mut u8, data.len()) };
+    Ok(data.len() as u64)
+}
+
+pub(super) fn sys_setxattr(regs: &mut Registers) {
+    let ret = setxattr(regs, Target::Path { follow: true });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_lsetxattr(regs: &mut Registers) {
+    let ret = setxattr(regs, Target::Path { follow: false });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_fsetxattr(regs: &mut Registers) {
+    let ret = setxattr(regs, Target::Fd);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_getxattr(regs: &mut Registers) {
+    let ret = getxattr(regs, Target::Path { follow: true });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_lgetxattr(regs: &mut Registers) {
+    let ret = getxattr(regs, Target::Path { follow: false });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_fgetxattr(regs: &mut Registers) {
+    let ret = getxattr(regs, Target::Fd);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_listxattr(regs: &mut Registers) {
+    let ret = listxattr(regs, Target::Path { follow: true });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_llistxattr(regs: &mut Registers) {
+    let ret = listxattr(regs, Target::Path { follow: false });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_flistxattr(regs: &mut Registers) {
+    let ret = listxattr(regs, Target::Fd);
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_removexattr(regs: &mut Registers) {
+    let ret = removexattr(regs, Target::Path { follow: true });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_lremovexattr(regs: &mut Registers) {
+    let ret = removexattr(regs, Target::Path { follow: false });
+    set_result(regs, ret);
+}
+
+pub(super) fn sys_fremovexattr(regs: &mut Registers) {
+    let ret = removexattr(regs, Target::Fd);
+    set_result(regs, ret);
+}
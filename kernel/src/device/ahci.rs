@@ -0,0 +1,724 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// sata disks behind an ahci controller. every port with a disk on it gets a command list,
+// commands go out as dma with one prd each and complete through a shared msi vector, or
+// through poll() if the controller can't do msi. disks are sda, sdb... like on linux, cd
+// drives are sr0, sr1... and only ever read with scsi commands in a packet
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    arch::{
+        drivers::time::preferred_timer_ns,
+        system::{cpu::Registers, interrupts::allocate_interrupt, lapic},
+    },
+    device::{
+        block::{BlockDevice, BlockOp, BlockRequest, Transfer, add_disk, register_block_device},
+        dma::DmaBuffer,
+        pci::{PCI_DEVICES, PciAddress, pci_enable_device, pci_enable_msi, pci_map_bar},
+    },
+    info,
+    utils::{asm::mmio, asm::without_ints, errno::*, spinlock::Spin},
+    warn,
+};
+
+// scsi disks, 16 minors each for the partitions
+pub const SD_MAJOR: u32 = 8;
+static NEXT_DISK: AtomicU32 = AtomicU32::new(0);
+// scsi cd drives, discs aren't partitioned
+pub const SR_MAJOR: u32 = 11;
+static NEXT_CD: AtomicU32 = AtomicU32::new(0);
+
+const HBA_CAP: u64 = 0x00;
+const HBA_GHC: u64 = 0x04;
+const HBA_IS: u64 = 0x08;
+const HBA_PI: u64 = 0x0C;
+const HBA_VS: u64 = 0x10;
+const HBA_CAP2: u64 = 0x24;
+const HBA_BOHC: u64 = 0x28;
+const PORTS: u64 = 0x100;
+const PORT_SIZE: u64 = 0x80;
+
+const CAP_S64A: u64 = 1 << 31;
+const CAP_SSS: u64 = 1 << 27;
+const GHC_AE: u64 = 1 << 31;
+const GHC_IE: u64 = 1 << 1;
+const GHC_HR: u64 = 1;
+const CAP2_BOH: u64 = 1;
+const BOHC_BOS: u64 = 1;
+const BOHC_OOS: u64 = 1 << 1;
+const BOHC_BB: u64 = 1 << 4;
+
+const PX_CLB: u64 = 0x00;
+const PX_FB: u64 = 0x08;
+const PX_IS: u64 = 0x10;
+const PX_IE: u64 = 0x14;
+const PX_CMD: u64 = 0x18;
+const PX_TFD: u64 = 0x20;
+const PX_SIG: u64 = 0x24;
+const PX_SSTS: u64 = 0x28;
+const PX_SERR: u64 = 0x30;
+const PX_CI: u64 = 0x38;
+
+const CMD_ST: u64 = 1;
+const CMD_SUD: u64 = 1 << 1;
+const CMD_POD: u64 = 1 << 2;
+const CMD_FRE: u64 = 1 << 4;
+const CMD_FR: u64 = 1 << 14;
+const CMD_CR: u64 = 1 << 15;
+const TFD_BSY: u64 = 1 << 7;
+const TFD_DRQ: u64 = 1 << 3;
+
+// register, pio setup, dma setup and set device bits fises, and everything that's an error
+const IS_DONE: u64 = 0xF;
+const IS_ERRORS: u64 = 0x7800_0000;
+
+const SIG_ATA: u64 = 0x0000_0101;
+const SIG_ATAPI: u64 = 0xEB14_0101;
+const DET_PRESENT: u64 = 3;
+
+const FIS_H2D: u8 = 0x27;
+const ATA_READ_DMA_EXT: u8 = 0x25;
+const ATA_WRITE_DMA_EXT: u8 = 0x35;
+const ATA_FLUSH_CACHE_EXT: u8 = 0xEA;
+const ATA_IDENTIFY: u8 = 0xEC;
+const ATA_PACKET: u8 = 0xA0;
+const ATA_IDENTIFY_PACKET: u8 = 0xA1;
+
+const SCSI_READ_CAPACITY: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+
+// a command table is the fis, the atapi packet at 0x40, then the prdt at 0x80. one prd is
+// all we use
+const TABLE_SIZE: usize = 0x100;
+const ACMD: usize = 0x40;
+const PRDT: usize = 0x80;
+// what one prd can describe
+const MAX_TRANSFER: usize = 4 << 20;
+
+const TIMEOUT_NS: u64 = 1_000_000_000;
+
+#[derive(Default, Clone, Copy)]
+struct Command {
+    ata: u8,
+    lba: u64,
+    count: u16,
+    // physical, `bytes` of it. nothing for commands without data
+    buffer: u64,
+    bytes: usize,
+    write: bool,
+    // the scsi command of an ATA_PACKET, lba and count are in there instead
+    packet: Option<[u8; 12]>,
+}
+
+impl Command {
+    // a scsi command reading `bytes` into `buffer`
+    fn packet(packet: [u8; 12], buffer: u64, bytes: usize) -> Self {
+        Self {
+            ata: ATA_PACKET,
+            buffer,
+            bytes,
+            packet: Some(packet),
+            ..Default::default()
+        }
+    }
+}
+
+// called with whether it went through
+type Callback = Box<dyn FnOnce(bool) + Send>;
+
+struct Port {
+    regs: u64,
+    slots: usize,
+    list: DmaBuffer,
+    fis: DmaBuffer,
+    tables: DmaBuffer,
+    // by slot
+    pending: Vec<Option<Callback>>,
+    issued: u32,
+}
+
+impl Port {
+    fn read(&self, reg: u64) -> u64 {
+        mmio::read(self.regs + reg, 4)
+    }
+
+    fn write(&self, reg: u64, value: u64) {
+        mmio::write(self.regs + reg, value, 4);
+    }
+
+    fn wait(&self, reg: u64, mask: u64, set: bool) -> Result<(), &'static str> {
+        let deadline = preferred_timer_ns() + TIMEOUT_NS;
+        while (self.read(reg) & mask != 0) != set {
+            if preferred_timer_ns() > deadline {
+                return Err("port timed out");
+            }
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), &'static str> {
+        self.write(PX_CMD, self.read(PX_CMD) & !CMD_ST);
+        self.wait(PX_CMD, CMD_CR, false)?;
+        self.write(PX_CMD, self.read(PX_CMD) & !CMD_FRE);
+        self.wait(PX_CMD, CMD_FR, false)
+    }
+
+    // the device has to be idle before the port takes commands again
+    fn start(&self) -> Result<(), &'static str> {
+        self.write(PX_CMD, self.read(PX_CMD) | CMD_FRE);
+        self.write(PX_SERR, 0xFFFF_FFFF);
+        self.write(PX_IS, 0xFFFF_FFFF);
+        self.wait(PX_TFD, TFD_BSY | TFD_DRQ, false)?;
+        self.write(PX_CMD, self.read(PX_CMD) | CMD_ST);
+        Ok(())
+    }
+
+    fn is_full(&self) -> bool {
+        self.issued.count_ones() as usize == self.slots
+    }
+
+    fn issue(&mut self, command: Command, done: Callback) {
+        let slot = (0..self.slots)
+            .find(|&slot| self.issued & 1 << slot == 0)
+            .unwrap();
+        self.pending[slot] = Some(done);
+        self.issued |= 1 << slot;
+
+        let table = slot * TABLE_SIZE;
+        let lba = command.lba.to_le_bytes();
+        let count = command.count.to_le_bytes();
+        let mut fis = [
+            FIS_H2D,
+            // a command, not a control update
+            0x80,
+            command.ata,
+            0,
+            lba[0],
+            lba[1],
+            lba[2],
+            // lba addressing
+            1 << 6,
+            lba[3],
+            lba[4],
+            lba[5],
+            0,
+            count[0],
+            count[1],
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ];
+        if let Some(packet) = command.packet {
+            // the data goes by dma, and there's no lba in the fis
+            fis[3] = 1;
+            fis[7] = 0;
+            for (i, byte) in packet.into_iter().enumerate() {
+                self.tables.write(table + ACMD + i, byte);
+            }
+        }
+        for (i, byte) in fis.into_iter().enumerate() {
+            self.tables.write(table + i, byte);
+        }
+        let prds = (command.bytes > 0) as u32;
+        if prds > 0 {
+            self.tables.write(table + PRDT, command.buffer);
+            self.tables.write(table + PRDT + 8, 0u32);
+            self.tables
+                .write(table + PRDT + 12, (command.bytes as u32 - 1) | 1 << 31);
+        }
+
+        // five dwords of fis, whether there's a packet, the direction and the prdt length
+        let header = slot * 32;
+        let atapi = command.packet.is_some() as u32;
+        let flags = 5 | atapi << 5 | (command.write as u32) << 6 | prds << 16;
+        self.list.write(header, flags);
+        self.list.write(header + 4, 0u32);
+        self.list
+            .write(header + 8, self.tables.phys() + table as u64);
+        self.write(PX_CI, 1 << slot);
+    }
+
+    // runs the callbacks of everything that finished. on an error the port stops, whatever
+    // was still in flight fails and it gets restarted
+    fn service(&mut self) {
+        let status = self.read(PX_IS);
+        self.write(PX_IS, status);
+        let active = self.read(PX_CI) as u32;
+        for slot in 0..self.slots {
+            if self.issued & 1 << slot != 0 && active & 1 << slot == 0 {
+                self.finish(slot, true);
+            }
+        }
+        if status & IS_ERRORS == 0 {
+            return;
+        }
+        for slot in 0..self.slots {
+            if self.issued & 1 << slot != 0 {
+                self.finish(slot, false);
+            }
+        }
+        if let Err(e) = self.stop().and_then(|_| self.start()) {
+            warn!("ahci: couldn't restart port after an error: {e}");
+        }
+    }
+
+    fn finish(&mut self, slot: usize, ok: bool) {
+        self.issued &= !(1 << slot);
+        if let Some(done) = self.pending[slot].take() {
+            done(ok);
+        }
+    }
+
+    // only used while setting up, so it just spins
+    fn execute(&mut self, command: Command) -> Result<(), &'static str> {
+        let status = Arc::new(Spin::new(None));
+        let result = status.clone();
+        self.issue(command, Box::new(move |ok| *result.lock() = Some(ok)));
+        let deadline = preferred_timer_ns() + TIMEOUT_NS * 5;
+        loop {
+            self.service();
+            match *status.lock() {
+                Some(true) => return Ok(()),
+                Some(false) => return Err("command failed"),
+                None => {}
+            }
+            if preferred_timer_ns() > deadline {
+                return Err("command timed out");
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+struct Hba {
+    regs: u64,
+    // by port number
+    ports: Vec<(u32, Arc<Spin<Port>>)>,
+    interrupts: bool,
+}
+
+impl Hba {
+    fn read(&self, reg: u64) -> u64 {
+        mmio::read(self.regs + reg, 4)
+    }
+
+    fn write(&self, reg: u64, value: u64) {
+        mmio::write(self.regs + reg, value, 4);
+    }
+
+    // the ports have to be cleared before the hba's own status
+    fn service(&self) {
+        let pending = self.read(HBA_IS);
+        if pending == 0 {
+            return;
+        }
+        for (number, port) in &self.ports {
+            if pending & 1 << number != 0 {
+                port.lock().service();
+            }
+        }
+        self.write(HBA_IS, pending);
+    }
+}
+
+// every controller with interrupts on, they all share one vector
+static HBAS: Spin<Vec<Arc<Hba>>> = Spin::new(Vec::new());
+
+fn interrupt_handler(_regs: &mut Registers) {
+    for hba in HBAS.lock().iter() {
+        hba.service();
+    }
+    lapic::eoi();
+}
+
+pub struct Disk {
+    port: Arc<Spin<Port>>,
+    // a cd drive, read only and through packets
+    atapi: bool,
+    // 64 bit addressing, without it the bounce buffer has to be under 4GiB
+    wide: bool,
+    write_cache: bool,
+    name: String,
+    sectors: u64,
+    sector_size: usize,
+}
+
+impl Disk {
+    // splits the request into commands of at most one prd
+    fn submit_io(&self, request: Arc<BlockRequest>) -> Result<(), i64> {
+        let len = request.buffer.lock().len();
+        if len == 0 {
+            request.complete(Ok(()));
+            return Ok(());
+        }
+        let mut transfer = Transfer::new(request)?;
+        if !self.wide && transfer.data.phys() + len as u64 > u32::MAX as u64 {
+            return Err(EIO);
+        }
+        let (ata, write) = match transfer.request.op {
+            BlockOp::Write if self.atapi => return Err(EROFS),
+            BlockOp::Write => (ATA_WRITE_DMA_EXT, true),
+            _ => (ATA_READ_DMA_EXT, false),
+        };
+
+        let commands = (0..len)
+            .step_by(MAX_TRANSFER)
+            .map(|offset| {
+                let bytes = MAX_TRANSFER.min(len - offset);
+                let lba = transfer.request.sector + (offset / self.sector_size) as u64;
+                let count = (bytes / self.sector_size) as u16;
+                let buffer = transfer.data.phys() + offset as u64;
+                match self.atapi {
+                    true => Command::packet(read_10(lba as u32, count), buffer, bytes),
+                    false => Command {
+                        ata,
+                        lba,
+                        count,
+                        buffer,
+                        bytes,
+                        write,
+                        packet: None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        transfer.parts = commands.len();
+        let transfer = Arc::new(Spin::new(transfer));
+        self.queue(commands, move |ok| transfer.lock().part_done(ok));
+        Ok(())
+    }
+
+    // the interrupt handler takes the port too, so not with interrupts on
+    fn queue(&self, commands: Vec<Command>, done: impl Fn(bool) + Clone + Send + 'static) {
+        without_ints(|| {
+            let mut port = self.port.lock();
+            for command in commands {
+                while port.is_full() {
+                    port.service();
+                }
+                port.issue(command, Box::new(done.clone()));
+            }
+        });
+    }
+}
+
+impl BlockDevice for Disk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+    fn sectors(&self) -> u64 {
+        self.sectors
+    }
+    fn read_only(&self) -> bool {
+        self.atapi
+    }
+    fn minors(&self) -> u32 {
+        match self.atapi {
+            true => 1,
+            false => 16,
+        }
+    }
+    fn submit(&self, request: Arc<BlockRequest>) {
+        match request.op {
+            BlockOp::Flush if !self.write_cache => request.complete(Ok(())),
+            BlockOp::Flush => {
+                let command = Command {
+                    ata: ATA_FLUSH_CACHE_EXT,
+                    ..Default::default()
+                };
+                self.queue(vec![command], move |ok| {
+                    request.complete(if ok { Ok(()) } else { Err(EIO) })
+                });
+            }
+            _ => {
+                if let Err(e) = self.submit_io(request.clone()) {
+                    request.complete(Err(e));
+                }
+            }
+        }
+    }
+    // with interrupts on too, whoever waits with them masked (shutdown) still sees
+    // completions
+    fn poll(&self) {
+        without_ints(|| self.port.lock().service());
+    }
+}
+
+// the model string, ata swaps the bytes of every word
+fn model(identify: &[u8]) -> String {
+    let bytes = identify[54..94]
+        .chunks(2)
+        .flat_map(|word| [word[1], word[0]])
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).trim().into()
+}
+
+fn read_10(lba: u32, count: u16) -> [u8; 12] {
+    let (lba, count) = (lba.to_be_bytes(), count.to_be_bytes());
+    [
+        SCSI_READ_10,
+        0,
+        lba[0],
+        lba[1],
+        lba[2],
+        lba[3],
+        0,
+        count[0],
+        count[1],
+        0,
+        0,
+        0,
+    ]
+}
+
+// the same for a cd drive, the size is the disc's. a drive that was just reset (or got
+// a new disc) fails the first command with a unit attention, so it gets a few tries
+fn identify_packet(port: &mut Port) -> Result<(String, u64, usize, bool), &'static str> {
+    let data = DmaBuffer::new(512).ok_or("out of memory")?;
+    port.execute(Command {
+        ata: ATA_IDENTIFY_PACKET,
+        buffer: data.phys(),
+        bytes: 512,
+        ..Default::default()
+    })?;
+    let model = model(data.as_slice());
+    let mut packet = [0; 12];
+    packet[0] = SCSI_READ_CAPACITY;
+    let read = Command::packet(packet, data.phys(), 8);
+    (0..3)
+        .find_map(|_| port.execute(read).ok())
+        .ok_or("no disc")?;
+    // the last block and the block size, big endian
+    let last = u32::from_be(data.read(0));
+    let block_size = u32::from_be(data.read(4));
+    Ok((model, last as u64 + 1, block_size as usize, false))
+}
+
+// what IDENTIFY DEVICE says about the disk: model, sectors, sector size and write cache
+fn identify(port: &mut Port) -> Result<(String, u64, usize, bool), &'static str> {
+    let data = DmaBuffer::new(512).ok_or("out of memory")?;
+    port.execute(Command {
+        ata: ATA_IDENTIFY,
+        buffer: data.phys(),
+        bytes: 512,
+        ..Default::default()
+    })?;
+    let word = |i: usize| data.read::<u16>(i * 2);
+    if word(83) & (1 << 10) == 0 {
+        return Err("no 48-bit addressing");
+    }
+    let sectors = data.read::<u64>(200);
+    // word 106 is only valid with bit 14 set and 15 clear
+    let sector_size = if word(106) & 0xD000 == 0x5000 {
+        (word(117) as usize | (word(118) as usize) << 16) * 2
+    } else {
+        512
+    };
+    let write_cache = word(85) & (1 << 5) != 0;
+    Ok((model(data.as_slice()), sectors, sector_size, write_cache))
+}
+
+fn setup_port(regs: u64, number: u32, slots: usize, staggered: bool) -> Result<Port, &'static str> {
+    let port = Port {
+        regs: regs + PORTS + number as u64 * PORT_SIZE,
+        slots,
+        list: DmaBuffer::new(1024).ok_or("out of memory")?,
+        fis: DmaBuffer::new(256).ok_or("out of memory")?,
+        tables: DmaBuffer::new(slots * TABLE_SIZE).ok_or("out of memory")?,
+        pending: (0..slots).map(|_| None).collect(),
+        issued: 0,
+    };
+    port.stop()?;
+    if staggered {
+        port.write(PX_CMD, port.read(PX_CMD) | CMD_SUD | CMD_POD);
+    }
+    // the link can take a moment to come up after spinning up
+    let deadline = preferred_timer_ns() + 10_000_000;
+    while port.read(PX_SSTS) & 0xF != DET_PRESENT {
+        if preferred_timer_ns() > deadline {
+            return Err("nothing attached");
+        }
+        core::hint::spin_loop();
+    }
+    if !matches!(port.read(PX_SIG), SIG_ATA | SIG_ATAPI) {
+        return Err("not a disk");
+    }
+    mmio::write(port.regs + PX_CLB, port.list.phys(), 8);
+    mmio::write(port.regs + PX_FB, port.fis.phys(), 8);
+    port.start()?;
+    Ok(port)
+}
+
+fn setup(address: PciAddress, vector: Option<u8>) -> Result<(), &'static str> {
+    pci_enable_device(address, false);
+    let regs = pci_map_bar(address, 5, PORTS + 32 * PORT_SIZE).ok_or("no usable bar 5")?;
+    let mut hba = Hba {
+        regs,
+        ports: Vec::new(),
+        interrupts: false,
+    };
+
+    // take it from the firmware, then reset it
+    if hba.read(HBA_CAP2) & CAP2_BOH != 0 {
+        hba.write(HBA_BOHC, hba.read(HBA_BOHC) | BOHC_OOS);
+        let deadline = preferred_timer_ns() + 25_000_000;
+        while hba.read(HBA_BOHC) & BOHC_BOS != 0 && preferred_timer_ns() < deadline {
+            core::hint::spin_loop();
+        }
+        let deadline = preferred_timer_ns() + 2 * TIMEOUT_NS;
+        while hba.read(HBA_BOHC) & BOHC_BB != 0 && preferred_timer_ns() < deadline {
+            core::hint::spin_loop();
+        }
+    }
+    hba.write(HBA_GHC, GHC_AE);
+    hba.write(HBA_GHC, GHC_AE | GHC_HR);
+    let deadline = preferred_timer_ns() + TIMEOUT_NS;
+    while hba.read(HBA_GHC) & GHC_HR != 0 {
+        if preferred_timer_ns() > deadline {
+            return Err("reset timed out");
+        }
+        core::hint::spin_loop();
+    }
+    hba.write(HBA_GHC, GHC_AE);
+
+    let cap = hba.read(HBA_CAP);
+    let slots = ((cap >> 8) & 0x1F) as usize + 1;
+    let wide = cap & CAP_S64A != 0;
+    let version = hba.read(HBA_VS);
+    info!(
+        "ahci at {:02x}:{:02x}.{}: ahci {}.{}, {} ports, {} slots",
+        address.bus,
+        address.device,
+        address.function,
+        version >> 16,
+        (version >> 8) & 0xFF,
+        (cap & 0x1F) + 1,
+        slots,
+    );
+
+    let implemented = hba.read(HBA_PI) as u32;
+    let mut disks = Vec::new();
+    for number in (0..32).filter(|n| implemented & 1 << n != 0) {
+        let mut port = match setup_port(regs, number, slots, cap & CAP_SSS != 0) {
+            Ok(port) => port,
+            // empty ports and whatever isn't a disk or a cd drive aren't worth a warning
+            Err("nothing attached" | "not a disk") => continue,
+            Err(e) => {
+                warn!("ahci: port {number}: {e}");
+                continue;
+            }
+        };
+        let atapi = port.read(PX_SIG) == SIG_ATAPI;
+        let identified = match atapi {
+            true => identify_packet(&mut port),
+            false => identify(&mut port),
+        };
+        match identified {
+            Ok(disk) => {
+                let port = Arc::new(Spin::new(port));
+                hba.ports.push((number, port.clone()));
+                disks.push((port, atapi, disk));
+            }
+            // discs put in later aren't noticed, so the drive isn't kept
+            Err("no disc") => info!("ahci: port {number}: cd drive without a disc"),
+            Err(e) => warn!("ahci: port {number}: {e}"),
+        }
+    }
+
+    for (_, port) in &hba.ports {
+        port.lock().write(PX_IE, IS_DONE | IS_ERRORS);
+    }
+    if let Some(vector) = vector
+        && pci_enable_msi(address, vector)
+    {
+        hba.interrupts = true;
+        hba.write(HBA_GHC, GHC_AE | GHC_IE);
+    }
+    let hba = Arc::new(hba);
+    if hba.interrupts {
+        without_ints(|| HBAS.lock().push(hba.clone()));
+    }
+
+    for (port, atapi, (model, sectors, sector_size, write_cache)) in disks {
+        if !sector_size.is_power_of_two() || !(512..=4096).contains(&sector_size) {
+            warn!("ahci: {model}: {sector_size} byte sectors, skipping");
+            continue;
+        }
+        if atapi {
+            let index = NEXT_CD.fetch_add(1, Ordering::Relaxed);
+            let name = format!("sr{index}");
+            info!("{name}: {model}");
+            let cd = Disk {
+                port,
+                atapi,
+                wide,
+                write_cache,
+                name: name.clone(),
+                sectors,
+                sector_size,
+            };
+            if let Err(e) = register_block_device(SR_MAJOR, index, Arc::new(cd)) {
+                warn!("{name}: couldn't register, errno {e}");
+            }
+            continue;
+        }
+        let index = NEXT_DISK.fetch_add(1, Ordering::Relaxed);
+        if index >= 26 {
+            warn!("ahci: {model}: out of disk names");
+            continue;
+        }
+        let name = format!("sd{}", (b'a' + index as u8) as char);
+        info!("{name}: {model}");
+        let disk = Disk {
+            port,
+            atapi,
+            wide,
+            write_cache,
+            name: name.clone(),
+            sectors,
+            sector_size,
+        };
+        if let Err(e) = add_disk(SD_MAJOR, index * 16, Arc::new(disk)) {
+            warn!("{name}: couldn't register, errno {e}");
+        }
+    }
+    Ok(())
+}
+
+// every ahci controller pci_enumerate found
+pub fn init() {
+    let controllers = PCI_DEVICES
+        .lock()
+        .iter()
+        .filter(|d| (d.class_code, d.subclass, d.prog_if) == (0x01, 0x06, 0x01))
+        .map(|d| d.address)
+        .collect::<Vec<_>>();
+    if controllers.is_empty() {
+        return;
+    }
+    let vector = allocate_interrupt(interrupt_handler);
+    if vector.is_none() {
+        warn!("ahci: no free interrupt vector, polling");
+    }
+    for address in controllers {
+        if let Err(e) = setup(address, vector) {
+            warn!(
+                "ahci at {:02x}:{:02x}.{}: {e}",
+                address.bus, address.device, address.function
+            );
+        }
+    }
+}
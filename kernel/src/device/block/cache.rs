@@ -0,0 +1,327 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// the buffer cache, page sized pieces of every block device. writes only dirty the page,
+// it reaches the disk on sync, when it's evicted or when the flush thread comes around
+
+use alloc::{
+    boxed::Box,
+    collections::{btree_map::BTreeMap, btree_set::BTreeSet},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    utils::{asm::without_ints, errno::*, spinlock::Spin},
+    warn,
+};
+
+use super::*;
+
+pub const PAGE_SIZE: usize = 4096;
+// 16MiB, past that the least recently used pages go
+const MAX_PAGES: usize = 4096;
+
+// dev_t and page index
+type Key = (u64, u64);
+
+struct Page {
+    data: Box<[u8]>,
+    dirty: bool,
+    // bumped by every write, a writeback that raced one leaves the page dirty
+    version: u64,
+    stamp: u64,
+}
+
+struct Cache {
+    pages: BTreeMap<Key, Page>,
+    // last use, oldest first
+    lru: BTreeMap<u64, Key>,
+    clock: u64,
+    // devices a writeback failed on, the next fsync reports it
+    errors: BTreeSet<u64>,
+}
+
+impl Cache {
+    fn touch(&mut self, key: Key) -> Option<&mut Page> {
+        let page = self.pages.get_mut(&key)?;
+        self.lru.remove(&page.stamp);
+        self.clock += 1;
+        page.stamp = self.clock;
+        self.lru.insert(self.clock, key);
+        Some(page)
+    }
+
+    // someone else may have loaded it while we were reading, theirs wins
+    fn insert(&mut self, key: Key, data: Box<[u8]>) -> &mut Page {
+        self.pages.entry(key).or_insert(Page {
+            data,
+            dirty: false,
+            version: 0,
+            stamp: 0,
+        });
+        self.touch(key).unwrap()
+    }
+
+    fn remove(&mut self, key: Key) {
+        if let Some(page) = self.pages.remove(&key) {
+            self.lru.remove(&page.stamp);
+        }
+    }
+}
+
+static CACHE: Spin<Cache> = Spin::new(Cache {
+    pages: BTreeMap::new(),
+    lru: BTreeMap::new(),
+    clock: 0,
+    errors: BTreeSet::new(),
+});
+
+// the flush thread runs with interrupts on, it can't be switched away from holding this
+fn with_cache<T>(f: impl FnOnce(&mut Cache) -> T) -> T {
+    without_ints(|| f(&mut CACHE.lock()))
+}
+
+// how much of page `index` is on the device, the last one can be short
+fn page_len(device: &dyn BlockDevice, index: u64) -> usize {
+    let start = index * PAGE_SIZE as u64;
+    device
+        .capacity()
+        .saturating_sub(start)
+        .min(PAGE_SIZE as u64) as usize
+}
+
+fn first_sector(device: &dyn BlockDevice, index: u64) -> u64 {
+    index * (PAGE_SIZE / device.sector_size()) as u64
+}
+
+fn load(device: &dyn BlockDevice, index: u64) -> Result<Box<[u8]>, i64> {
+    let len = page_len(device, index);
+    let sector = first_sector(device, index);
+    let mut data = transfer(device, BlockOp::Read, sector, vec![0; len])?;
+    data.resize(PAGE_SIZE, 0);
+    Ok(data.into_boxed_slice())
+}
+
+fn store(device: &dyn BlockDevice, index: u64, data: &[u8]) -> Result<(), i64> {
+    let len = page_len(device, index);
+    let sector = first_sector(device, index);
+    transfer(device, BlockOp::Write, sector, data[..len].to_vec()).map(|_| ())
+}
+
+fn dirty_pages(rdev: Option<u64>) -> Vec<(Key, u64, Box<[u8]>)> {
+    with_cache(|cache| {
+        cache
+            .pages
+            .iter()
+            .filter(|(key, page)| page.dirty && rdev.is_none_or(|rdev| key.0 == rdev))
+            .map(|(&key, page)| (key, page.version, page.data.clone()))
+            .collect()
+    })
+}
+
+// a failed write isn't retried, the page counts as clean and the error goes to fsync
+fn write_back(pages: Vec<(Key, u64, Box<[u8]>)>) {
+    for (key, version, data) in pages {
+        let result = match block_device(key.0) {
+            Some(device) => store(device.as_ref(), key.1, &data),
+            None => Ok(()),
+        };
+        with_cache(|cache| {
+            if result.is_err() {
+                cache.errors.insert(key.0);
+            }
+            if let Some(page) = cache.pages.get_mut(&key)
+                && page.version == version
+            {
+                page.dirty = false;
+            }
+        });
+    }
+}
+
+// evicts clean pages oldest first, dirty ones in the way get written back and go next round
+fn shrink() {
+    loop {
+        let dirty = with_cache(|cache| {
+            let excess = cache.pages.len().saturating_sub(MAX_PAGES);
+            let mut clean = Vec::new();
+            let mut dirty = Vec::new();
+            for &key in cache.lru.values() {
+                if clean.len() + dirty.len() == excess {
+                    break;
+                }
+                let page = &cache.pages[&key];
+                if page.dirty {
+                    dirty.push((key, page.version, page.data.clone()));
+                } else {
+                    clean.push(key);
+                }
+            }
+            for key in clean {
+                cache.remove(key);
+            }
+            dirty
+        });
+        if dirty.is_empty() {
+            return;
+        }
+        write_back(dirty);
+    }
+}
+
+// copies `out.len()` bytes from `start` in the page, reading it in if it isn't cached
+fn read_page(device: &dyn BlockDevice, key: Key, start: usize, out: &mut [u8]) -> Result<(), i64> {
+    let mut copy = |page: &mut Page| out.copy_from_slice(&page.data[start..start + out.len()]);
+    if with_cache(|cache| cache.touch(key).map(&mut copy)).is_some() {
+        return Ok(());
+    }
+    let data = load(device, key.1)?;
+    with_cache(|cache| copy(cache.insert(key, data)));
+    shrink();
+    Ok(())
+}
+
+fn write_page(device: &dyn BlockDevice, key: Key, start: usize, data: &[u8]) -> Result<(), i64> {
+    let copy = |page: &mut Page| {
+        page.data[start..start + data.len()].copy_from_slice(data);
+        page.dirty = true;
+        page.version += 1;
+    };
+    if with_cache(|cache| cache.touch(key).map(copy)).is_some() {
+        return Ok(());
+    }
+    // a whole page doesn't need what was there before
+    let page = if data.len() == PAGE_SIZE {
+        vec![0; PAGE_SIZE].into_boxed_slice()
+    } else {
+        load(device, key.1)?
+    };
+    with_cache(|cache| copy(cache.insert(key, page)));
+    shrink();
+    Ok(())
+}
+
+// splits [offset, offset + len) into (page index, offset in it, length) pieces
+fn pieces(offset: u64, len: usize) -> impl Iterator<Item = (u64, usize, usize)> {
+    let end = offset + len as u64;
+    let mut pos = offset;
+    core::iter::from_fn(move || {
+        if pos >= end {
+            return None;
+        }
+        let index = pos / PAGE_SIZE as u64;
+        let start = (pos % PAGE_SIZE as u64) as usize;
+        let n = (PAGE_SIZE - start).min((end - pos) as usize);
+        pos += n as u64;
+        Some((index, start, n))
+    })
+}
+
+// short at the end of the device, an error only if nothing was read
+pub fn read(rdev: u64, offset: u64, buf: &mut [u8]) -> Result<usize, i64> {
+    let device = block_device(rdev).ok_or(ENXIO)?;
+    let capacity = device.capacity();
+    if offset >= capacity {
+        return Ok(0);
+    }
+    let len = buf.len().min((capacity - offset) as usize);
+    let mut done = 0;
+    for (index, start, n) in pieces(offset, len) {
+        if let Err(e) = read_page(
+            device.as_ref(),
+            (rdev, index),
+            start,
+            &mut buf[done..done + n],
+        ) {
+            return if done > 0 { Ok(done) } else { Err(e) };
+        }
+        done += n;
+    }
+    Ok(done)
+}
+
+// ENOSPC past the end of the device
+pub fn write(rdev: u64, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+    let device = block_device(rdev).ok_or(ENXIO)?;
+    if device.read_only() {
+        return Err(EROFS);
+    }
+    let capacity = device.capacity();
+    if offset >= capacity && !buf.is_empty() {
+        return Err(ENOSPC);
+    }
+    let len = buf.len().min(capacity.saturating_sub(offset) as usize);
+    let mut done = 0;
+    for (index, start, n) in pieces(offset, len) {
+        if let Err(e) = write_page(device.as_ref(), (rdev, index), start, &buf[done..done + n]) {
+            return if done > 0 { Ok(done) } else { Err(e) };
+        }
+        done += n;
+    }
+    Ok(done)
+}
+
+// fsync on the device, EIO if a writeback failed since the last one
+pub fn sync_device(rdev: u64) -> Result<(), i64> {
+    write_back(dirty_pages(Some(rdev)));
+    let flushed = match block_device(rdev) {
+        Some(device) => flush(device.as_ref()),
+        None => Ok(()),
+    };
+    if with_cache(|cache| cache.errors.remove(&rdev)) {
+        return Err(EIO);
+    }
+    flushed
+}
+
+// writes every dirty page without waiting on the disks' own caches
+pub fn writeback_all() {
+    write_back(dirty_pages(None));
+}
+
+// sync(2), errors go nowhere
+pub fn sync_all() {
+    writeback_all();
+    for (_, device) in block_devices() {
+        let _ = flush(device.as_ref());
+    }
+}
+
+// before powering off or rebooting. a panic can leave the cache locked, losing what's
+// dirty beats hanging there
+pub fn sync_for_shutdown() {
+    if CACHE.is_locked() {
+        warn!("buffer cache is locked, not syncing");
+        return;
+    }
+    sync_all();
+}
+
+// drops everything cached for the device, dirty or not
+pub fn invalidate(rdev: u64) {
+    with_cache(|cache| {
+        let keys = cache
+            .pages
+            .range((rdev, 0)..=(rdev, u64::MAX))
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        for key in keys {
+            cache.remove(key);
+        }
+        cache.errors.remove(&rdev);
+    });
+}
+
+// bytes cached and how many of those are dirty, for meminfo
+pub fn stats() -> (u64, u64) {
+    with_cache(|cache| {
+        let dirty = cache.pages.values().filter(|page| page.dirty).count();
+        (
+            (cache.pages.len() * PAGE_SIZE) as u64,
+            (dirty * PAGE_SIZE) as u64,
+        )
+    })
+}
@@ -0,0 +1,236 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// loop devices, /dev/loopN with a regular file behind it so filesystem images can be
+// mounted. LOOP_SET_FD attaches one and LOOP_CLR_FD lets it go, /dev/loop-control hands
+// out free ones and makes more when they're all taken
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+
+use crate::{
+    device::virtio::rng::MISC_MAJOR,
+    drivers::fs::{InodeRef, Permissions, get_vfs, register_chrdev},
+    scheduler::current_process,
+    warn,
+};
+
+use super::*;
+
+pub const LOOP_MAJOR: u32 = 7;
+const LOOP_CTRL_MINOR: u32 = 237;
+const SECTOR_SIZE: usize = 512;
+// made at boot, more come from LOOP_CTL_GET_FREE up to the limit
+const INITIAL_LOOPS: u32 = 8;
+const MAX_LOOPS: u32 = 256;
+
+const LOOP_SET_FD: u64 = 0x4C00;
+const LOOP_CLR_FD: u64 = 0x4C01;
+const LOOP_SET_CAPACITY: u64 = 0x4C07;
+const LOOP_CTL_GET_FREE: u64 = 0x4C82;
+
+const S_IFCHR: u32 = 0o020000;
+
+// the file a loop device reads and writes
+struct Backing {
+    inode: InodeRef,
+    // the fd wasn't open for writing or the file is on a read-only mount
+    read_only: bool,
+    // as of attaching or the last LOOP_SET_CAPACITY, a partial sector at the end is left out
+    sectors: u64,
+}
+
+pub struct Loop {
+    name: String,
+    rdev: u64,
+    backing: Spin<Option<Backing>>,
+}
+
+impl Loop {
+    fn attach(&self, fd: i32) -> Result<(), i64> {
+        let current = current_process().ok_or(EINVAL)?;
+        let proc = current.lock();
+        let file = proc.fdt.get(&fd).ok_or(EBADF)?;
+        // devices, pipes and sockets aren't files
+        if file.object().is_some() {
+            return Err(EINVAL);
+        }
+        let inode = file.inode().cloned().ok_or(EINVAL)?;
+        let writable = file.permissions.contains(Permissions::WRITE);
+        drop(proc);
+        if !inode.node().is_file() {
+            return Err(EINVAL);
+        }
+
+        let vfs = get_vfs();
+        let mount = vfs.mount_of_inode(&inode);
+        // a file on the loop device itself would wait on its own io
+        if mount.is_some_and(|m| m.sb.device == Some(self.rdev)) {
+            return Err(EINVAL);
+        }
+        let read_only = !writable || mount.is_some_and(|m| m.is_readonly());
+        drop(vfs);
+
+        let sectors = inode.node().size() / SECTOR_SIZE as u64;
+        let mut backing = self.backing.lock();
+        if backing.is_some() {
+            return Err(EBUSY);
+        }
+        *backing = Some(Backing {
+            inode,
+            read_only,
+            sectors,
+        });
+        Ok(())
+    }
+
+    // EBUSY while it's mounted, what's cached goes to the file before letting it go
+    fn detach(&self) -> Result<(), i64> {
+        if self.backing.lock().is_none() {
+            return Err(ENXIO);
+        }
+        if get_vfs()
+            .mounts
+            .iter()
+            .any(|m| m.sb.device == Some(self.rdev))
+        {
+            return Err(EBUSY);
+        }
+        let synced = cache::sync_device(self.rdev);
+        cache::invalidate(self.rdev);
+        *self.backing.lock() = None;
+        synced
+    }
+
+    // picks up a file that grew or shrank since it was attached
+    fn resize(&self) -> Result<(), i64> {
+        let inode = self.inode().ok_or(ENXIO)?;
+        let sectors = inode.node().size() / SECTOR_SIZE as u64;
+        if let Some(backing) = self.backing.lock().as_mut() {
+            backing.sectors = sectors;
+        }
+        Ok(())
+    }
+
+    fn inode(&self) -> Option<InodeRef> {
+        self.backing.lock().as_ref().map(|b| b.inode.clone())
+    }
+}
+
+impl BlockDevice for Loop {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+    // empty until something is attached
+    fn sectors(&self) -> u64 {
+        self.backing.lock().as_ref().map_or(0, |b| b.sectors)
+    }
+    fn read_only(&self) -> bool {
+        self.backing.lock().as_ref().is_some_and(|b| b.read_only)
+    }
+    // goes through the file's node and waits, there's nothing to queue. a file that
+    // shrank under the device reads as zeroes past its end
+    fn submit(&self, request: Arc<BlockRequest>) {
+        let Some(inode) = self.inode() else {
+            request.complete(Err(ENXIO));
+            return;
+        };
+        let offset = request.sector * SECTOR_SIZE as u64;
+        let mut buffer = core::mem::take(&mut *request.buffer.lock());
+        let result = match request.op {
+            BlockOp::Read => match inode.node().read_at(offset, &mut buffer) {
+                Some(n) => {
+                    buffer[n..].fill(0);
+                    Ok(())
+                }
+                None => Err(EIO),
+            },
+            BlockOp::Write => inode.node_mut().write_at(offset, &buffer).map(|_| ()),
+            BlockOp::Flush => inode.node().sync(false),
+        };
+        *request.buffer.lock() = buffer;
+        request.complete(result);
+    }
+    fn ioctl(&self, request: u64, arg: u64) -> Result<u64, i64> {
+        match request {
+            LOOP_SET_FD => self.attach(arg as i32),
+            LOOP_CLR_FD => self.detach(),
+            LOOP_SET_CAPACITY => self.resize(),
+            _ => return Err(ENOTTY),
+        }
+        .map(|_| 0)
+    }
+}
+
+// every loop device by minor
+static LOOPS: Spin<Vec<Arc<Loop>>> = Spin::new(Vec::new());
+
+fn add_loop(index: u32) -> Result<Arc<Loop>, i64> {
+    let device = Arc::new(Loop {
+        name: format!("loop{index}"),
+        rdev: makedev(LOOP_MAJOR, index),
+        backing: Spin::new(None),
+    });
+    register_block_device(LOOP_MAJOR, index, device.clone())?;
+    Ok(device)
+}
+
+// the first one with nothing attached, or a new one after the rest
+fn free_loop() -> Result<u32, i64> {
+    let mut loops = LOOPS.lock();
+    if let Some(index) = loops.iter().position(|l| l.backing.lock().is_none()) {
+        return Ok(index as u32);
+    }
+    let index = loops.len() as u32;
+    if index >= MAX_LOOPS {
+        return Err(ENOSPC);
+    }
+    loops.push(add_loop(index)?);
+    Ok(index)
+}
+
+// /dev/loop-control, only good for ioctls
+struct LoopControl;
+
+impl FileObject for LoopControl {
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+    fn ioctl(&self, request: u64, _arg: u64) -> Result<u64, i64> {
+        match request {
+            LOOP_CTL_GET_FREE => free_loop().map(|index| index as u64),
+            _ => Err(ENOTTY),
+        }
+    }
+}
+
+pub fn init() {
+    let mut loops = LOOPS.lock();
+    for index in 0..INITIAL_LOOPS {
+        match add_loop(index) {
+            Ok(device) => loops.push(device),
+            Err(e) => {
+                warn!("loop{index}: couldn't register, errno {e}");
+                break;
+            }
+        }
+    }
+    drop(loops);
+    let _ = register_chrdev(
+        "loop-control",
+        MISC_MAJOR,
+        LOOP_CTRL_MINOR,
+        NodeMode::from_bits_truncate(0o660),
+        Arc::new(LoopControl),
+    );
+}
@@ -0,0 +1,311 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// disks and anything that looks like one. drivers implement BlockDevice and register it,
+// everything above them (filesystems, /dev nodes) goes through the buffer cache
+
+pub mod cache;
+pub mod loopdev;
+pub mod partition;
+pub mod ramdisk;
+
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    arch::drivers::time::preferred_timer_ns,
+    device::dma::DmaBuffer,
+    drivers::fs::{FileObject, NodeMode, makedev, register_blkdev},
+    info,
+    scheduler::wait::WaitQueue,
+    utils::{asm::without_ints, errno::*, spinlock::Spin},
+};
+
+const S_IFBLK: u32 = 0o060000;
+
+// blkext on linux, for disks without a major of their own and partitions that don't fit
+// in the minors after their disk. minors are handed out in order
+pub const BLOCK_EXT_MAJOR: u32 = 259;
+static NEXT_EXT_MINOR: AtomicU32 = AtomicU32::new(0);
+
+pub fn next_ext_minor() -> u32 {
+    NEXT_EXT_MINOR.fetch_add(1, Ordering::Relaxed)
+}
+
+// how often someone waiting on a polled driver looks again
+const POLL_NS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOp {
+    Read,
+    Write,
+    // everything written so far is on stable storage once this completes
+    Flush,
+}
+
+// one transfer of whole sectors. the driver fills or drains `buffer` and calls complete(),
+// from an interrupt handler or from poll()
+pub struct BlockRequest {
+    pub op: BlockOp,
+    pub sector: u64,
+    pub buffer: Spin<Vec<u8>>,
+    result: Spin<Option<Result<(), i64>>>,
+    done: WaitQueue,
+}
+
+impl BlockRequest {
+    pub fn new(op: BlockOp, sector: u64, buffer: Vec<u8>) -> Arc<Self> {
+        Arc::new(Self {
+            op,
+            sector,
+            buffer: Spin::new(buffer),
+            result: Spin::new(None),
+            done: WaitQueue::new(),
+        })
+    }
+
+    pub fn complete(&self, result: Result<(), i64>) {
+        without_ints(|| *self.result.lock() = Some(result));
+        self.done.wake_all();
+    }
+
+    pub fn result(&self) -> Option<Result<(), i64>> {
+        without_ints(|| *self.result.lock())
+    }
+
+    // sleeps until the driver is done with it. before the scheduler is up (and while
+    // shutting down) there's nothing to sleep on, so it spins on poll() instead
+    pub fn wait(&self, device: &dyn BlockDevice) -> Result<(), i64> {
+        loop {
+            device.poll();
+            if let Some(result) = self.result() {
+                return result;
+            }
+            let deadline = preferred_timer_ns() + POLL_NS;
+            match self
+                .done
+                .wait_until_deadline(Some(deadline), || self.result())
+            {
+                Some(result) => return result,
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+// a request that went to the device as `parts` commands sharing one bounce buffer, it
+// completes when the last of them does
+pub struct Transfer {
+    pub request: Arc<BlockRequest>,
+    pub data: DmaBuffer,
+    // whatever else the device reads until it's done, prp lists and such
+    pub extra: Vec<DmaBuffer>,
+    pub parts: usize,
+    failed: bool,
+}
+
+impl Transfer {
+    // the bounce buffer starts out with what's being written
+    pub fn new(request: Arc<BlockRequest>) -> Result<Self, i64> {
+        let mut data = DmaBuffer::new(request.buffer.lock().len()).ok_or(ENOMEM)?;
+        if request.op == BlockOp::Write {
+            let buffer = request.buffer.lock();
+            data.as_mut_slice()[..buffer.len()].copy_from_slice(&buffer);
+        }
+        Ok(Self {
+            request,
+            data,
+            extra: Vec::new(),
+            parts: 1,
+            failed: false,
+        })
+    }
+
+    pub fn part_done(&mut self, ok: bool) {
+        self.parts -= 1;
+        self.failed |= !ok;
+        if self.parts > 0 {
+            return;
+        }
+        if self.failed {
+            self.request.complete(Err(EIO));
+            return;
+        }
+        if self.request.op == BlockOp::Read {
+            let mut buffer = self.request.buffer.lock();
+            let len = buffer.len();
+            buffer.copy_from_slice(&self.data.as_slice()[..len]);
+        }
+        self.request.complete(Ok(()));
+    }
+}
+
+pub trait BlockDevice: Send + Sync {
+    // the name under /dev, "nvme0n1", "ram0"...
+    fn name(&self) -> &str;
+    // the logical sector size, a power of two no bigger than a page
+    fn sector_size(&self) -> usize;
+    fn sectors(&self) -> u64;
+    fn capacity(&self) -> u64 {
+        self.sectors() * self.sector_size() as u64
+    }
+    fn read_only(&self) -> bool {
+        false
+    }
+    // how many minors from its own are the device's, partition n goes in the n-th if
+    // there's one
+    fn minors(&self) -> u32 {
+        1
+    }
+    // queues the request and returns, the driver completes it whenever it's done
+    fn submit(&self, request: Arc<BlockRequest>);
+    // drivers that don't take interrupts reap their completions here
+    fn poll(&self) {}
+    // ioctls on the /dev node, loop devices get attached through this
+    fn ioctl(&self, _request: u64, _arg: u64) -> Result<u64, i64> {
+        Err(ENOTTY)
+    }
+}
+
+// submits and waits, `buffer` is whole sectors
+pub fn transfer(
+    device: &dyn BlockDevice,
+    op: BlockOp,
+    sector: u64,
+    buffer: Vec<u8>,
+) -> Result<Vec<u8>, i64> {
+    let sectors = (buffer.len() / device.sector_size()) as u64;
+    if !buffer.len().is_multiple_of(device.sector_size())
+        || sector
+            .checked_add(sectors)
+            .is_none_or(|end| end > device.sectors())
+    {
+        return Err(EINVAL);
+    }
+    if op == BlockOp::Write && device.read_only() {
+        return Err(EROFS);
+    }
+    let request = BlockRequest::new(op, sector, buffer);
+    device.submit(request.clone());
+    request.wait(device)?;
+    Ok(core::mem::take(&mut *request.buffer.lock()))
+}
+
+pub fn flush(device: &dyn BlockDevice) -> Result<(), i64> {
+    if device.read_only() {
+        return Ok(());
+    }
+    let request = BlockRequest::new(BlockOp::Flush, 0, Vec::new());
+    device.submit(request.clone());
+    request.wait(device)
+}
+
+// every registered device by dev_t
+static BLOCK_DEVICES: Spin<BTreeMap<u64, Arc<dyn BlockDevice>>> = Spin::new(BTreeMap::new());
+
+// publishes the device as /dev/`name`. EINVAL for sector sizes the cache can't handle,
+// EBUSY if the numbers or the name are taken
+pub fn register_block_device(
+    major: u32,
+    minor: u32,
+    device: Arc<dyn BlockDevice>,
+) -> Result<(), i64> {
+    let size = device.sector_size();
+    if !size.is_power_of_two() || !(512..=cache::PAGE_SIZE).contains(&size) {
+        return Err(EINVAL);
+    }
+    let rdev = makedev(major, minor);
+    if BLOCK_DEVICES.lock().contains_key(&rdev) {
+        return Err(EBUSY);
+    }
+    register_blkdev(
+        device.name(),
+        major,
+        minor,
+        NodeMode::from_bits_truncate(0o660),
+    )?;
+    info!(
+        "block device {}: {} sectors of {} bytes",
+        device.name(),
+        device.sectors(),
+        size,
+    );
+    BLOCK_DEVICES.lock().insert(rdev, device);
+    Ok(())
+}
+
+// registers a whole disk and then whatever partitions are on it
+pub fn add_disk(major: u32, minor: u32, device: Arc<dyn BlockDevice>) -> Result<(), i64> {
+    register_block_device(major, minor, device.clone())?;
+    partition::add_partitions(makedev(major, minor), &device);
+    Ok(())
+}
+
+// writes back what's cached first. the node stays, opening it gives ENXIO from now on
+pub fn unregister_block_device(major: u32, minor: u32) -> bool {
+    let rdev = makedev(major, minor);
+    let _ = cache::sync_device(rdev);
+    cache::invalidate(rdev);
+    BLOCK_DEVICES.lock().remove(&rdev).is_some()
+}
+
+pub fn block_device(rdev: u64) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.lock().get(&rdev).cloned()
+}
+
+pub fn block_devices() -> Vec<(u64, Arc<dyn BlockDevice>)> {
+    BLOCK_DEVICES
+        .lock()
+        .iter()
+        .map(|(&rdev, device)| (rdev, device.clone()))
+        .collect()
+}
+
+// an open /dev node, byte addressed io through the cache
+pub struct BlockFile {
+    rdev: u64,
+    device: Arc<dyn BlockDevice>,
+}
+
+impl FileObject for BlockFile {
+    fn read(&self, buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        self.read_at(0, buf)
+    }
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        self.write_at(0, buf)
+    }
+    fn mode(&self) -> u32 {
+        S_IFBLK
+    }
+    fn size(&self) -> Option<u64> {
+        Some(self.device.capacity())
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, i64> {
+        cache::read(self.rdev, offset, buf)
+    }
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        cache::write(self.rdev, offset, buf)
+    }
+    fn sync(&self) -> Result<(), i64> {
+        cache::sync_device(self.rdev)
+    }
+    fn ioctl(&self, request: u64, arg: u64) -> Result<u64, i64> {
+        self.device.ioctl(request, arg)
+    }
+}
+
+// what opening a block device node gets
+pub fn open(rdev: u64) -> Option<Arc<dyn FileObject>> {
+    let device = block_device(rdev)?;
+    Some(Arc::new(BlockFile { rdev, device }))
+}
+
+// writes dirty buffers back every few seconds, so a crash loses at most that much
+pub fn flush_thread() -> ! {
+    loop {
+        crate::scheduler::thread::sleep_ms(5000);
+        cache::writeback_all();
+    }
+}
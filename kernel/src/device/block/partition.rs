@@ -0,0 +1,347 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// partition tables, gpt behind its protective mbr or a plain mbr with logical partitions
+// in an extended one. every partition becomes a block device of its own, nvme0n1p1, sda5...
+
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+
+use crate::{
+    drivers::fs::{major, minor, register_blkdev},
+    info,
+    utils::{config::get_config, crc32::crc32, spinlock::Spin},
+    warn,
+};
+
+use super::*;
+
+const MBR_SIGNATURE: u16 = 0xAA55;
+const MBR_ENTRIES: usize = 446;
+const MBR_DISK_SIGNATURE: usize = 440;
+const TYPE_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+const TYPE_GPT: u8 = 0xEE;
+
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+// more than any sane table, a corrupt count could ask for gigabytes
+const GPT_MAX_ENTRIES_BYTES: u64 = 1 << 20;
+// an extended partition pointing at itself would go on forever
+const MAX_LOGICAL: u32 = 128;
+
+pub struct Partition {
+    disk: Arc<dyn BlockDevice>,
+    name: String,
+    start: u64,
+    sectors: u64,
+}
+
+impl BlockDevice for Partition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn sector_size(&self) -> usize {
+        self.disk.sector_size()
+    }
+    fn sectors(&self) -> u64 {
+        self.sectors
+    }
+    fn read_only(&self) -> bool {
+        self.disk.read_only()
+    }
+    // passes it down and waits, there's nothing of its own to queue
+    fn submit(&self, request: Arc<BlockRequest>) {
+        let buffer = core::mem::take(&mut *request.buffer.lock());
+        let result = match request.op {
+            BlockOp::Flush => flush(self.disk.as_ref()).map(|_| buffer),
+            op => transfer(self.disk.as_ref(), op, self.start + request.sector, buffer),
+        };
+        request.complete(result.map(|buffer| *request.buffer.lock() = buffer));
+    }
+    fn poll(&self) {
+        self.disk.poll();
+    }
+}
+
+// what a table says about one partition, sectors are the disk's
+struct Entry {
+    number: u32,
+    start: u64,
+    sectors: u64,
+    // PARTUUID= and PARTLABEL=, gpt names are utf-16 and mbr ones don't exist
+    uuid: String,
+    label: String,
+}
+
+// every partition found so far by dev_t, for picking the root
+static PARTITIONS: Spin<Vec<(u64, String, String)>> = Spin::new(Vec::new());
+
+fn read_sectors(device: &dyn BlockDevice, sector: u64, count: u64) -> Result<Vec<u8>, i64> {
+    let len = count as usize * device.sector_size();
+    transfer(device, BlockOp::Read, sector, vec![0; len])
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(data[pos..pos + 8].try_into().unwrap())
+}
+
+// the first three fields are little endian, the rest goes as it is
+fn guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+        u32_at(bytes, 0),
+        u16_at(bytes, 4),
+        u16_at(bytes, 6),
+        bytes[8],
+        bytes[9],
+        bytes[10],
+        bytes[11],
+        bytes[12],
+        bytes[13],
+        bytes[14],
+        bytes[15],
+    )
+}
+
+// the header at `lba` if it checks out: its crc, where it says it is, and the crc of the
+// entry array it points at
+fn gpt_header(device: &dyn BlockDevice, lba: u64) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut header = read_sectors(device, lba, 1).ok()?;
+    let size = u32_at(&header, 12) as usize;
+    if &header[..8] != GPT_SIGNATURE || !(92..=header.len()).contains(&size) {
+        return None;
+    }
+    let expected = u32_at(&header, 16);
+    header[16..20].fill(0);
+    if crc32(&header[..size]) != expected || u64_at(&header, 24) != lba {
+        return None;
+    }
+
+    let entries_lba = u64_at(&header, 72);
+    let count = u32_at(&header, 80) as u64;
+    let entry_size = u32_at(&header, 84) as u64;
+    let bytes = count * entry_size;
+    if entry_size < 128 || !entry_size.is_multiple_of(8) || bytes > GPT_MAX_ENTRIES_BYTES {
+        return None;
+    }
+    let sector_size = device.sector_size() as u64;
+    let mut entries = read_sectors(device, entries_lba, bytes.div_ceil(sector_size)).ok()?;
+    entries.truncate(bytes as usize);
+    (crc32(&entries) == u32_at(&header, 88)).then_some((header, entries))
+}
+
+// the primary header, or the backup at the end of the disk if that one's damaged
+fn gpt(device: &dyn BlockDevice) -> Option<Vec<Entry>> {
+    let (header, entries) = gpt_header(device, 1).or_else(|| {
+        warn!(
+            "{}: primary gpt header is damaged, trying the backup",
+            device.name()
+        );
+        gpt_header(device, device.sectors() - 1)
+    })?;
+    let entry_size = u32_at(&header, 84) as usize;
+    let partitions = entries
+        .chunks_exact(entry_size)
+        .enumerate()
+        .filter(|(_, entry)| entry[..16].iter().any(|&b| b != 0))
+        .filter_map(|(i, entry)| {
+            let first = u64_at(entry, 32);
+            let last = u64_at(entry, 40);
+            let name = entry[56..128]
+                .as_chunks::<2>()
+                .0
+                .iter()
+                .map(|&c| u16::from_le_bytes(c))
+                .take_while(|&c| c != 0);
+            Some(Entry {
+                number: i as u32 + 1,
+                start: first,
+                sectors: last.checked_sub(first)? + 1,
+                uuid: guid(&entry[16..32]),
+                label: char::decode_utf16(name)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect(),
+            })
+        })
+        .collect();
+    Some(partitions)
+}
+
+// (type, start, sectors) of the four entries, None if it isn't a partition table at all.
+// a fat boot sector ends in 55aa too, but its "entries" have junk where the boot flag goes
+fn mbr_entries(sector: &[u8]) -> Option<[(u8, u64, u64); 4]> {
+    if u16_at(sector, 510) != MBR_SIGNATURE {
+        return None;
+    }
+    let mut entries = [(0, 0, 0); 4];
+    for (i, entry) in entries.iter_mut().enumerate() {
+        let raw = &sector[MBR_ENTRIES + i * 16..MBR_ENTRIES + (i + 1) * 16];
+        if raw[0] != 0 && raw[0] != 0x80 {
+            return None;
+        }
+        *entry = (raw[4], u32_at(raw, 8) as u64, u32_at(raw, 12) as u64);
+    }
+    Some(entries)
+}
+
+// the logical partitions, each ebr has one and points at the next relative to the start
+// of the extended partition
+fn logical(device: &dyn BlockDevice, extended: u64, signature: u32) -> Vec<Entry> {
+    let mut partitions = Vec::new();
+    let mut ebr = extended;
+    for number in 5..5 + MAX_LOGICAL {
+        let Some(entries) = read_sectors(device, ebr, 1)
+            .ok()
+            .and_then(|sector| mbr_entries(&sector))
+        else {
+            break;
+        };
+        let (kind, start, sectors) = entries[0];
+        if kind != 0 && sectors != 0 {
+            partitions.push(Entry {
+                number,
+                start: ebr + start,
+                sectors,
+                uuid: format!("{signature:08x}-{number:02x}"),
+                label: String::new(),
+            });
+        }
+        let (kind, next, _) = entries[1];
+        if !TYPE_EXTENDED.contains(&kind) || next == 0 {
+            break;
+        }
+        ebr = extended + next;
+    }
+    partitions
+}
+
+// None when there's no table, an empty list when there is one without partitions
+fn scan(device: &dyn BlockDevice) -> Option<Vec<Entry>> {
+    let sector = read_sectors(device, 0, 1).ok()?;
+    let entries = mbr_entries(&sector)?;
+    if entries.iter().any(|&(kind, _, _)| kind == TYPE_GPT) {
+        let partitions = gpt(device);
+        if partitions.is_none() {
+            warn!("{}: protective mbr but no valid gpt", device.name());
+        }
+        return partitions;
+    }
+
+    let signature = u32_at(&sector, MBR_DISK_SIGNATURE);
+    let mut partitions = Vec::new();
+    for (i, &(kind, start, sectors)) in entries.iter().enumerate() {
+        if kind == 0 || sectors == 0 {
+            continue;
+        }
+        if TYPE_EXTENDED.contains(&kind) {
+            partitions.extend(logical(device, start, signature));
+            continue;
+        }
+        let number = i as u32 + 1;
+        partitions.push(Entry {
+            number,
+            start,
+            sectors,
+            uuid: format!("{signature:08x}-{number:02x}"),
+            label: String::new(),
+        });
+    }
+    Some(partitions)
+}
+
+// nvme0n1 gets a p before the number so it doesn't run into the namespace's
+fn partition_name(disk: &str, number: u32) -> String {
+    if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{disk}p{number}")
+    } else {
+        format!("{disk}{number}")
+    }
+}
+
+// registers whatever partitions the disk at `rdev` has. the ones that fit go in the
+// minors after the disk's own, the rest in the extended major
+pub fn add_partitions(rdev: u64, disk: &Arc<dyn BlockDevice>) {
+    let Some(partitions) = scan(disk.as_ref()) else {
+        return;
+    };
+    for entry in partitions {
+        let name = partition_name(disk.name(), entry.number);
+        if entry.start == 0 || entry.start.saturating_add(entry.sectors) > disk.sectors() {
+            warn!("{name}: outside the disk, skipping");
+            continue;
+        }
+        let (major, minor) = if entry.number < disk.minors() {
+            (major(rdev), minor(rdev) + entry.number)
+        } else {
+            (BLOCK_EXT_MAJOR, next_ext_minor())
+        };
+        let partition = Partition {
+            disk: disk.clone(),
+            name: name.clone(),
+            start: entry.start,
+            sectors: entry.sectors,
+        };
+        match register_block_device(major, minor, Arc::new(partition)) {
+            Ok(()) => PARTITIONS
+                .lock()
+                .push((makedev(major, minor), entry.uuid, entry.label)),
+            Err(e) => warn!("{name}: couldn't register, errno {e}"),
+        }
+    }
+}
+
+// the device `root` in the kernel config names: PARTUUID=<guid>, PARTLABEL=<name> or a
+// device name with or without /dev/
+fn find_root(spec: &str) -> Option<u64> {
+    let partitions = PARTITIONS.lock();
+    if let Some(uuid) = spec.strip_prefix("PARTUUID=") {
+        return partitions
+            .iter()
+            .find(|(_, u, _)| u.eq_ignore_ascii_case(uuid))
+            .map(|&(rdev, _, _)| rdev);
+    }
+    if let Some(label) = spec.strip_prefix("PARTLABEL=") {
+        return partitions
+            .iter()
+            .find(|(_, _, l)| l == label)
+            .map(|&(rdev, _, _)| rdev);
+    }
+    let name = spec.strip_prefix("/dev/").unwrap_or(spec);
+    block_devices()
+        .into_iter()
+        .find(|(_, device)| device.name() == name)
+        .map(|(rdev, _)| rdev)
+}
+
+// publishes the configured root as /dev/root once every disk is in
+pub fn select_root() {
+    let config = get_config();
+    let spec = config.root.to_str();
+    if spec.is_empty() {
+        return;
+    }
+    let Some(rdev) = find_root(spec) else {
+        warn!("root device {spec} not found");
+        return;
+    };
+    let Some(device) = block_device(rdev) else {
+        return;
+    };
+    match register_blkdev(
+        "root",
+        major(rdev),
+        minor(rdev),
+        NodeMode::from_bits_truncate(0o660),
+    ) {
+        Ok(()) => info!("root device: {} ({spec})", device.name()),
+        Err(e) => warn!("couldn't publish /dev/root, errno {e}"),
+    }
+}
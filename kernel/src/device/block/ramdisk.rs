@@ -0,0 +1,107 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// ram disks, /dev/ram0 and on. how many and how big comes from the config, pages are only
+// allocated once something is written to them and the rest reads as zeroes
+
+use alloc::{boxed::Box, collections::btree_map::BTreeMap, format, string::String, sync::Arc, vec};
+
+use crate::{utils::config::get_config, warn};
+
+use super::*;
+
+pub const RAMDISK_MAJOR: u32 = 1;
+const SECTOR_SIZE: usize = 512;
+const PAGE_SIZE: usize = 4096;
+// more than anyone would want, the minors are there for them
+const MAX_RAMDISKS: i64 = 16;
+
+pub struct RamDisk {
+    name: String,
+    sectors: u64,
+    // by page number
+    pages: Spin<BTreeMap<u64, Box<[u8]>>>,
+}
+
+impl RamDisk {
+    fn read(&self, offset: u64, buf: &mut [u8]) {
+        let pages = self.pages.lock();
+        for (done, page, start, len) in page_chunks(offset, buf.len()) {
+            let dst = &mut buf[done..done + len];
+            match pages.get(&page) {
+                Some(data) => dst.copy_from_slice(&data[start..start + len]),
+                None => dst.fill(0),
+            }
+        }
+    }
+
+    fn write(&self, offset: u64, buf: &[u8]) {
+        let mut pages = self.pages.lock();
+        for (done, page, start, len) in page_chunks(offset, buf.len()) {
+            let data = pages
+                .entry(page)
+                .or_insert_with(|| vec![0; PAGE_SIZE].into_boxed_slice());
+            data[start..start + len].copy_from_slice(&buf[done..done + len]);
+        }
+    }
+}
+
+// splits `len` bytes at `offset` into (bytes before it, page, offset in it, length)
+fn page_chunks(offset: u64, len: usize) -> impl Iterator<Item = (usize, u64, usize, usize)> {
+    let mut done = 0;
+    core::iter::from_fn(move || {
+        if done == len {
+            return None;
+        }
+        let pos = offset + done as u64;
+        let start = pos as usize % PAGE_SIZE;
+        let n = (PAGE_SIZE - start).min(len - done);
+        let chunk = (done, pos / PAGE_SIZE as u64, start, n);
+        done += n;
+        Some(chunk)
+    })
+}
+
+impl BlockDevice for RamDisk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+    fn sectors(&self) -> u64 {
+        self.sectors
+    }
+    // nothing to wait for, it's done before submit returns. the flush thread runs with
+    // interrupts on, so the pages are only locked with them off
+    fn submit(&self, request: Arc<BlockRequest>) {
+        let offset = request.sector * SECTOR_SIZE as u64;
+        without_ints(|| {
+            let mut buffer = request.buffer.lock();
+            match request.op {
+                BlockOp::Read => self.read(offset, &mut buffer),
+                BlockOp::Write => self.write(offset, &buffer),
+                BlockOp::Flush => {}
+            }
+        });
+        request.complete(Ok(()));
+    }
+}
+
+pub fn init() {
+    let config = get_config();
+    let count = config.ramdisks.to_int().clamp(0, MAX_RAMDISKS) as u32;
+    let size = config.ramdisk_size.to_int().max(0) as u64 * 1024;
+    for index in 0..count {
+        let disk = RamDisk {
+            name: format!("ram{index}"),
+            sectors: size / SECTOR_SIZE as u64,
+            pages: Spin::new(BTreeMap::new()),
+        };
+        if let Err(e) = register_block_device(RAMDISK_MAJOR, index, Arc::new(disk)) {
+            warn!("ram{index}: couldn't register, errno {e}");
+        }
+    }
+}
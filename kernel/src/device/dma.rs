@@ -0,0 +1,63 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::alloc::{Layout, alloc_zeroed, dealloc};
+
+use crate::utils::limine::get_hhdm_offset;
+
+const PAGE_SIZE: usize = 0x1000;
+
+// zeroed, page aligned memory a device reads and writes on its own. it comes out of the
+// heap like frames do, so the physical address is the hhdm offset away
+pub struct DmaBuffer {
+    ptr: *mut u8,
+    len: usize,
+}
+
+unsafe impl Send for DmaBuffer {}
+unsafe impl Sync for DmaBuffer {}
+
+impl DmaBuffer {
+    // rounded up to whole pages, None when out of memory
+    pub fn new(len: usize) -> Option<Self> {
+        let len = len.max(1).next_multiple_of(PAGE_SIZE);
+        let ptr = unsafe { alloc_zeroed(Layout::from_size_align(len, PAGE_SIZE).unwrap()) };
+        (!ptr.is_null()).then_some(Self { ptr, len })
+    }
+
+    pub fn phys(&self) -> u64 {
+        self.ptr as u64 - get_hhdm_offset()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    // the device changes these behind our back, so volatile
+    pub fn read<T: Copy>(&self, offset: usize) -> T {
+        assert!(offset + size_of::<T>() <= self.len);
+        unsafe { self.ptr.add(offset).cast::<T>().read_volatile() }
+    }
+
+    pub fn write<T: Copy>(&self, offset: usize, value: T) {
+        assert!(offset + size_of::<T>() <= self.len);
+        unsafe { self.ptr.add(offset).cast::<T>().write_volatile(value) }
+    }
+}
+
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc(
+                self.ptr,
+                Layout::from_size_align(self.len, PAGE_SIZE).unwrap(),
+            )
+        }
+    }
+}
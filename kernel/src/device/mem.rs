@@ -0,0 +1,71 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::sync::Arc;
+
+use crate::{
+    drivers::fs::{FileObject, NodeMode, register_chrdev},
+    utils::errno::*,
+};
+
+pub const MEM_MAJOR: u32 = 1;
+
+const S_IFCHR: u32 = 0o020000;
+
+// /dev/null, eats everything and reads eof
+pub struct Null;
+
+impl FileObject for Null {
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Ok(0)
+    }
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Ok(buf.len())
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+}
+
+// /dev/zero, endless zeroes. private mmaps of it are plain anonymous memory
+pub struct Zero;
+
+impl FileObject for Zero {
+    fn read(&self, buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Ok(buf.len())
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> Result<usize, i64> {
+        self.read(buf, false)
+    }
+}
+
+// /dev/full, reads like /dev/zero but every write fails with ENOSPC
+pub struct Full;
+
+impl FileObject for Full {
+    fn read(&self, buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        buf.fill(0);
+        Ok(buf.len())
+    }
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(ENOSPC)
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+}
+
+pub fn init() {
+    let _ = register_chrdev("null", MEM_MAJOR, 3, NodeMode::RW, Arc::new(Null));
+    let _ = register_chrdev("zero", MEM_MAJOR, 5, NodeMode::RW, Arc::new(Zero));
+    let _ = register_chrdev("full", MEM_MAJOR, 7, NodeMode::RW, Arc::new(Full));
+}
@@ -3,6 +3,20 @@
     Released under EUPL 1.2 License
 */
 
+pub mod ahci;
+pub mod block;
+pub mod dma;
+pub mod mem;
 pub mod nvme;
 pub mod pci;
+pub mod random;
 pub mod serial;
+pub mod tty;
+pub mod virtio;
+
+// the character devices that are always there, called once devfs is up
+pub fn register_chrdevs() {
+    mem::init();
+    random::init();
+    tty::init();
+}
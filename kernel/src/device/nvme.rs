@@ -3,5 +3,489 @@
     Released under EUPL 1.2 License
 */
 
-// TODO
-pub fn init() {}
+// nvme over pci. one admin and one i/o queue pair per controller, completions are polled
+// instead of taken as interrupts. every namespace becomes a block device, nvme<c>n<nsid>
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    arch::drivers::time::preferred_timer_ns,
+    device::{
+        block::{
+            BLOCK_EXT_MAJOR, BlockDevice, BlockOp, BlockRequest, Transfer, add_disk, next_ext_minor,
+        },
+        dma::DmaBuffer,
+        pci::{PCI_DEVICES, PciAddress, pci_enable_device, pci_map_bar},
+    },
+    info,
+    utils::{asm::mmio, asm::without_ints, errno::*, spinlock::Spin},
+    warn,
+};
+
+// namespaces go in the extended block major like on linux, minors in the order found
+pub const NVME_MAJOR: u32 = BLOCK_EXT_MAJOR;
+static NEXT_CONTROLLER: AtomicU32 = AtomicU32::new(0);
+
+const REG_CAP: u64 = 0x00;
+const REG_VS: u64 = 0x08;
+const REG_CC: u64 = 0x14;
+const REG_CSTS: u64 = 0x1C;
+const REG_AQA: u64 = 0x24;
+const REG_ASQ: u64 = 0x28;
+const REG_ACQ: u64 = 0x30;
+const DOORBELLS: u64 = 0x1000;
+
+const CC_ENABLE: u64 = 1;
+// 64 byte submissions and 16 byte completions, as powers of two
+const CC_IOSQES: u64 = 6 << 16;
+const CC_IOCQES: u64 = 4 << 20;
+const CSTS_READY: u64 = 1;
+const CSTS_FATAL: u64 = 2;
+
+const ADMIN_CREATE_SQ: u8 = 0x01;
+const ADMIN_CREATE_CQ: u8 = 0x05;
+const ADMIN_IDENTIFY: u8 = 0x06;
+const NVM_FLUSH: u8 = 0x00;
+const NVM_WRITE: u8 = 0x01;
+const NVM_READ: u8 = 0x02;
+
+const CNS_NAMESPACE: u32 = 0x00;
+const CNS_CONTROLLER: u32 = 0x01;
+const CNS_ACTIVE_NAMESPACES: u32 = 0x02;
+
+const ADMIN_QUEUE_SIZE: u16 = 32;
+const IO_QUEUE_SIZE: u16 = 64;
+const PAGE_SIZE: usize = 0x1000;
+// as much as one page of prp entries can describe
+const MAX_TRANSFER_PAGES: usize = PAGE_SIZE / 8;
+
+#[derive(Default, Clone, Copy)]
+struct Command {
+    opcode: u8,
+    nsid: u32,
+    prp1: u64,
+    prp2: u64,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+}
+
+// called with the status field of the completion, 0 is success
+type Callback = Box<dyn FnOnce(u16) + Send>;
+
+// a submission queue and the completion queue it posts to, same id and size
+struct Queue {
+    size: u16,
+    sq: DmaBuffer,
+    cq: DmaBuffer,
+    sq_tail: u16,
+    cq_head: u16,
+    // what the phase bit of a new completion looks like, flips every lap
+    phase: bool,
+    sq_doorbell: u64,
+    cq_doorbell: u64,
+    // by command id
+    pending: Vec<Option<Callback>>,
+    in_flight: usize,
+}
+
+impl Queue {
+    fn new(regs: u64, stride: u64, id: u16, size: u16) -> Option<Self> {
+        Some(Self {
+            size,
+            sq: DmaBuffer::new(size as usize * 64)?,
+            cq: DmaBuffer::new(size as usize * 16)?,
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            sq_doorbell: regs + DOORBELLS + (2 * id as u64) * stride,
+            cq_doorbell: regs + DOORBELLS + (2 * id as u64 + 1) * stride,
+            pending: (0..size).map(|_| None).collect(),
+            in_flight: 0,
+        })
+    }
+
+    // one slot always stays empty, a full queue would look like an empty one
+    fn is_full(&self) -> bool {
+        self.in_flight >= self.size as usize - 1
+    }
+
+    fn submit(&mut self, command: Command, done: Callback) {
+        let cid = self.pending.iter().position(Option::is_none).unwrap();
+        self.pending[cid] = Some(done);
+        self.in_flight += 1;
+
+        let mut dwords = [0u32; 16];
+        dwords[0] = command.opcode as u32 | (cid as u32) << 16;
+        dwords[1] = command.nsid;
+        dwords[6] = command.prp1 as u32;
+        dwords[7] = (command.prp1 >> 32) as u32;
+        dwords[8] = command.prp2 as u32;
+        dwords[9] = (command.prp2 >> 32) as u32;
+        dwords[10] = command.cdw10;
+        dwords[11] = command.cdw11;
+        dwords[12] = command.cdw12;
+        let entry = self.sq_tail as usize * 64;
+        for (i, dword) in dwords.into_iter().enumerate() {
+            self.sq.write(entry + i * 4, dword);
+        }
+        self.sq_tail = (self.sq_tail + 1) % self.size;
+        mmio::write(self.sq_doorbell, self.sq_tail as u64, 4);
+    }
+
+    // runs the callbacks of everything that completed since last time
+    fn reap(&mut self) {
+        let mut reaped = false;
+        loop {
+            let status = self.cq.read::<u32>(self.cq_head as usize * 16 + 12);
+            if (status >> 16) & 1 != self.phase as u32 {
+                break;
+            }
+            self.cq_head += 1;
+            if self.cq_head == self.size {
+                self.cq_head = 0;
+                self.phase = !self.phase;
+            }
+            reaped = true;
+            let cid = status as u16 as usize;
+            if let Some(done) = self.pending.get_mut(cid).and_then(Option::take) {
+                self.in_flight -= 1;
+                done((status >> 17) as u16);
+            }
+        }
+        if reaped {
+            mmio::write(self.cq_doorbell, self.cq_head as u64, 4);
+        }
+    }
+}
+
+struct Controller {
+    regs: u64,
+    // CAP.TO, how long the controller may take to do anything
+    timeout: u64,
+    max_pages: usize,
+    // a volatile write cache, only then flushes mean anything
+    write_cache: bool,
+    admin: Spin<Queue>,
+    io: Spin<Option<Queue>>,
+}
+
+impl Controller {
+    fn read(&self, reg: u64, width: usize) -> u64 {
+        mmio::read(self.regs + reg, width)
+    }
+
+    fn write(&self, reg: u64, value: u64, width: usize) {
+        mmio::write(self.regs + reg, value, width);
+    }
+
+    fn wait_status(&self, ready: bool) -> Result<(), &'static str> {
+        let deadline = preferred_timer_ns() + self.timeout;
+        loop {
+            let status = self.read(REG_CSTS, 4);
+            if status & CSTS_FATAL != 0 {
+                return Err("controller fatal status");
+            }
+            if (status & CSTS_READY != 0) == ready {
+                return Ok(());
+            }
+            if preferred_timer_ns() > deadline {
+                return Err("controller timed out");
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    // only used while setting up, so it just spins
+    fn admin(&self, command: Command) -> Result<(), &'static str> {
+        let status = Arc::new(Spin::new(None));
+        let result = status.clone();
+        self.admin
+            .lock()
+            .submit(command, Box::new(move |s| *result.lock() = Some(s)));
+        let deadline = preferred_timer_ns() + self.timeout;
+        loop {
+            self.admin.lock().reap();
+            match *status.lock() {
+                Some(0) => return Ok(()),
+                Some(_) => return Err("admin command failed"),
+                None => {}
+            }
+            if preferred_timer_ns() > deadline {
+                return Err("admin command timed out");
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn identify(&self, cns: u32, nsid: u32) -> Result<DmaBuffer, &'static str> {
+        let data = DmaBuffer::new(PAGE_SIZE).ok_or("out of memory")?;
+        self.admin(Command {
+            opcode: ADMIN_IDENTIFY,
+            nsid,
+            prp1: data.phys(),
+            cdw10: cns,
+            ..Default::default()
+        })?;
+        Ok(data)
+    }
+}
+
+pub struct Namespace {
+    controller: Arc<Controller>,
+    nsid: u32,
+    name: String,
+    sectors: u64,
+    sector_size: usize,
+}
+
+impl Namespace {
+    // splits the request into commands of at most max_pages
+    fn submit_io(&self, request: Arc<BlockRequest>) -> Result<(), i64> {
+        let len = request.buffer.lock().len();
+        if len == 0 {
+            request.complete(Ok(()));
+            return Ok(());
+        }
+        let mut transfer = Transfer::new(request)?;
+        let opcode = match transfer.request.op {
+            BlockOp::Write => NVM_WRITE,
+            _ => NVM_READ,
+        };
+
+        let chunk = self.controller.max_pages * PAGE_SIZE;
+        let mut commands = Vec::new();
+        for offset in (0..len).step_by(chunk) {
+            let bytes = chunk.min(len - offset);
+            let pages = bytes.div_ceil(PAGE_SIZE);
+            let prp1 = transfer.data.phys() + offset as u64;
+            let prp2 = match pages {
+                1 => 0,
+                2 => prp1 + PAGE_SIZE as u64,
+                _ => {
+                    let list = DmaBuffer::new(PAGE_SIZE).ok_or(ENOMEM)?;
+                    for i in 1..pages {
+                        list.write((i - 1) * 8, prp1 + (i * PAGE_SIZE) as u64);
+                    }
+                    let phys = list.phys();
+                    transfer.extra.push(list);
+                    phys
+                }
+            };
+            let lba = transfer.request.sector + (offset / self.sector_size) as u64;
+            commands.push(Command {
+                opcode,
+                nsid: self.nsid,
+                prp1,
+                prp2,
+                cdw10: lba as u32,
+                cdw11: (lba >> 32) as u32,
+                cdw12: (bytes / self.sector_size - 1) as u32,
+            });
+        }
+
+        transfer.parts = commands.len();
+        let transfer = Arc::new(Spin::new(transfer));
+        self.queue(commands, move |status| {
+            transfer.lock().part_done(status == 0)
+        });
+        Ok(())
+    }
+
+    fn queue(&self, commands: Vec<Command>, done: impl Fn(u16) + Clone + Send + 'static) {
+        without_ints(|| {
+            let mut io = self.controller.io.lock();
+            let queue = io.as_mut().unwrap();
+            for command in commands {
+                while queue.is_full() {
+                    queue.reap();
+                }
+                queue.submit(command, Box::new(done.clone()));
+            }
+        });
+    }
+}
+
+impl BlockDevice for Namespace {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+    fn sectors(&self) -> u64 {
+        self.sectors
+    }
+    fn submit(&self, request: Arc<BlockRequest>) {
+        match request.op {
+            BlockOp::Flush if !self.controller.write_cache => request.complete(Ok(())),
+            BlockOp::Flush => {
+                let command = Command {
+                    opcode: NVM_FLUSH,
+                    nsid: self.nsid,
+                    ..Default::default()
+                };
+                self.queue(vec![command], move |status| {
+                    request.complete(if status == 0 { Ok(()) } else { Err(EIO) })
+                });
+            }
+            _ => {
+                if let Err(e) = self.submit_io(request.clone()) {
+                    request.complete(Err(e));
+                }
+            }
+        }
+    }
+    fn poll(&self) {
+        without_ints(|| {
+            if let Some(queue) = self.controller.io.lock().as_mut() {
+                queue.reap();
+            }
+        });
+    }
+}
+
+fn ascii(bytes: &[u8]) -> &str {
+    core::str::from_utf8(bytes).unwrap_or("").trim()
+}
+
+fn setup(address: PciAddress) -> Result<(), &'static str> {
+    pci_enable_device(address, false);
+    let regs = pci_map_bar(address, 0, 0x1000).ok_or("no usable bar 0")?;
+    let cap = mmio::read(regs + REG_CAP, 8);
+    let stride = 4 << ((cap >> 32) & 0xF);
+    if (cap >> 37) & 1 == 0 {
+        return Err("no nvm command set");
+    }
+    if (cap >> 48) & 0xF != 0 {
+        return Err("no 4k page support");
+    }
+    // the doorbells of the two queue pairs
+    pci_map_bar(address, 0, DOORBELLS + 4 * stride).ok_or("couldn't map doorbells")?;
+    let max_entries = ((cap & 0xFFFF) + 1).min(u16::MAX as u64) as u16;
+
+    let mut controller = Controller {
+        regs,
+        timeout: ((cap >> 24) & 0xFF).max(1) * 500_000_000,
+        max_pages: MAX_TRANSFER_PAGES,
+        write_cache: false,
+        admin: Spin::new(
+            Queue::new(regs, stride, 0, ADMIN_QUEUE_SIZE.min(max_entries))
+                .ok_or("out of memory")?,
+        ),
+        io: Spin::new(None),
+    };
+
+    // reset, then point it at the admin queues and turn it back on
+    if controller.read(REG_CC, 4) & CC_ENABLE != 0 {
+        controller.write(REG_CC, 0, 4);
+    }
+    controller.wait_status(false)?;
+    let (asq, acq, admin_size) = {
+        let admin = controller.admin.lock();
+        (admin.sq.phys(), admin.cq.phys(), admin.size as u64)
+    };
+    controller.write(REG_AQA, (admin_size - 1) << 16 | (admin_size - 1), 4);
+    controller.write(REG_ASQ, asq, 8);
+    controller.write(REG_ACQ, acq, 8);
+    controller.write(REG_CC, CC_ENABLE | CC_IOSQES | CC_IOCQES, 4);
+    controller.wait_status(true)?;
+
+    let identify = controller.identify(CNS_CONTROLLER, 0)?;
+    let id = identify.as_slice();
+    let version = controller.read(REG_VS, 4);
+    let index = NEXT_CONTROLLER.fetch_add(1, Ordering::Relaxed);
+    info!(
+        "nvme{index}: {} ({}), nvme {}.{}",
+        ascii(&id[24..64]),
+        ascii(&id[4..24]),
+        version >> 16,
+        (version >> 8) & 0xFF,
+    );
+    let mdts = id[77];
+    let namespaces = identify.read::<u32>(516);
+    if mdts != 0 {
+        controller.max_pages = 1usize
+            .checked_shl(mdts as u32)
+            .map_or(MAX_TRANSFER_PAGES, |pages| pages.min(MAX_TRANSFER_PAGES));
+    }
+    controller.write_cache = id[525] & 1 != 0;
+
+    // completion queue first, the submission queue names it
+    let io = Queue::new(regs, stride, 1, IO_QUEUE_SIZE.min(max_entries)).ok_or("out of memory")?;
+    let size = (io.size as u32 - 1) << 16;
+    controller.admin(Command {
+        opcode: ADMIN_CREATE_CQ,
+        prp1: io.cq.phys(),
+        cdw10: size | 1,
+        // physically contiguous, no interrupts
+        cdw11: 1,
+        ..Default::default()
+    })?;
+    controller.admin(Command {
+        opcode: ADMIN_CREATE_SQ,
+        prp1: io.sq.phys(),
+        cdw10: size | 1,
+        // completions go to queue 1
+        cdw11: 1 << 16 | 1,
+        ..Default::default()
+    })?;
+    *controller.io.lock() = Some(io);
+    let controller = Arc::new(controller);
+
+    // 1.0 controllers can't list the active ones, so just try them all
+    let nsids = match controller.identify(CNS_ACTIVE_NAMESPACES, 0) {
+        Ok(list) => (0..1024)
+            .map(|i| list.read::<u32>(i * 4))
+            .take_while(|&nsid| nsid != 0)
+            .collect(),
+        Err(_) => (1..=namespaces.min(1024)).collect::<Vec<_>>(),
+    };
+    for nsid in nsids {
+        let Ok(identify) = controller.identify(CNS_NAMESPACE, nsid) else {
+            continue;
+        };
+        let sectors = identify.read::<u64>(0);
+        if sectors == 0 {
+            continue;
+        }
+        let format = (identify.as_slice()[26] & 0xF) as usize;
+        // log2 of the sector size, the cache takes 512 to 4096
+        let shift = identify.as_slice()[128 + format * 4 + 2];
+        if !(9..=12).contains(&shift) {
+            warn!("nvme{index}n{nsid}: {}-bit sectors, skipping", shift);
+            continue;
+        }
+        let sector_size = 1 << shift;
+        let namespace = Namespace {
+            controller: controller.clone(),
+            nsid,
+            name: format!("nvme{index}n{nsid}"),
+            sectors,
+            sector_size,
+        };
+        if let Err(e) = add_disk(NVME_MAJOR, next_ext_minor(), Arc::new(namespace)) {
+            warn!("nvme{index}n{nsid}: couldn't register, errno {e}");
+        }
+    }
+    Ok(())
+}
+
+// every nvme controller pci_enumerate found
+pub fn init() {
+    let controllers = PCI_DEVICES
+        .lock()
+        .iter()
+        .filter(|d| (d.class_code, d.subclass, d.prog_if) == (0x01, 0x08, 0x02))
+        .map(|d| d.address)
+        .collect::<Vec<_>>();
+    for address in controllers {
+        if let Err(e) = setup(address) {
+            warn!(
+                "nvme at {:02x}:{:02x}.{}: {e}",
+                address.bus, address.device, address.function
+            );
+        }
+    }
+}
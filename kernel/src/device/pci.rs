@@ -6,9 +6,11 @@
 use alloc::vec::Vec;
 use core::sync::atomic::{AtomicU64, Ordering};
 
+#[cfg(target_arch = "x86_64")]
+use crate::memory::vmm::{PAGEMAP, flag, page_size};
 #[cfg(target_arch = "x86_64")]
 use crate::utils::asm::port::{inl, outl};
-use crate::utils::spinlock::Spin;
+use crate::utils::{limine::get_hhdm_offset, spinlock::Spin};
 
 pub static PCI_DEVICES: Spin<Vec<PciDevice>> = Spin::new(Vec::new());
 pub static MCFG_ADDRESS: AtomicU64 = AtomicU64::new(0);
@@ -113,6 +115,104 @@ pub fn pci_config_write_u32(addr: PciAddress, offset: u8, value: u32) {
     }
 }
 
+const COMMAND_MEMORY: u16 = 1 << 1;
+const COMMAND_BUS_MASTER: u16 = 1 << 2;
+const COMMAND_INTX_DISABLE: u16 = 1 << 10;
+
+// the physical address of memory bar `index`, 64 bit bars use the next slot too.
+// None for io bars and unset ones
+pub fn pci_bar(addr: PciAddress, index: u8) -> Option<u64> {
+    let low = pci_config_read_u32(addr, 0x10 + index * 4);
+    if low & 1 != 0 {
+        return None;
+    }
+    let mut base = (low & !0xF) as u64;
+    // type 2 is anywhere in 64 bits
+    if (low >> 1) & 3 == 2 {
+        base |= (pci_config_read_u32(addr, 0x14 + index * 4) as u64) << 32;
+    }
+    (base != 0).then_some(base)
+}
+
+// maps `len` bytes of memory bar `index` at its hhdm address and returns that
+pub fn pci_map_bar(addr: PciAddress, index: u8, len: u64) -> Option<u64> {
+    let phys = pci_bar(addr, index)?;
+    let virt = phys + get_hhdm_offset();
+    #[cfg(target_arch = "x86_64")]
+    for offset in (0..len).step_by(page_size::SMALL as usize) {
+        unsafe {
+            PAGEMAP
+                .get()
+                .unwrap()
+                .lock()
+                .map(virt + offset, phys + offset, flag::RW, page_size::SMALL)
+                .ok()?;
+        }
+    }
+    Some(virt)
+}
+
+// lets the device answer on its memory bars and do dma. drivers that poll turn legacy
+// interrupts off too
+pub fn pci_enable_device(addr: PciAddress, interrupts: bool) {
+    let mut command = pci_config_read_u16(addr, 0x04) | COMMAND_MEMORY | COMMAND_BUS_MASTER;
+    if interrupts {
+        command &= !COMMAND_INTX_DISABLE;
+    } else {
+        command |= COMMAND_INTX_DISABLE;
+    }
+    pci_config_write_u16(addr, 0x04, command);
+}
+
+const STATUS_CAPABILITIES: u16 = 1 << 4;
+const CAPABILITY_MSI: u8 = 0x05;
+
+// every capability in the list, (id, where it starts in config space)
+pub fn pci_capabilities(addr: PciAddress) -> Vec<(u8, u8)> {
+    let mut capabilities = Vec::new();
+    if pci_config_read_u16(addr, 0x06) & STATUS_CAPABILITIES == 0 {
+        return capabilities;
+    }
+    let mut offset = pci_config_read_u8(addr, 0x34) & !3;
+    // a broken list could loop, there's only room for 48 of them anyway
+    while offset != 0 && capabilities.len() < 48 {
+        capabilities.push((pci_config_read_u8(addr, offset), offset));
+        offset = pci_config_read_u8(addr, offset + 1) & !3;
+    }
+    capabilities
+}
+
+// where capability `id` starts, the first one if there are several
+pub fn pci_capability(addr: PciAddress, id: u8) -> Option<u8> {
+    pci_capabilities(addr)
+        .into_iter()
+        .find(|&(cap, _)| cap == id)
+        .map(|(_, offset)| offset)
+}
+
+// points the device's single msi message at `vector` on this cpu. false if it can't
+// do msi, the driver has to poll then
+#[cfg(target_arch = "x86_64")]
+pub fn pci_enable_msi(addr: PciAddress, vector: u8) -> bool {
+    let Some(cap) = pci_capability(addr, CAPABILITY_MSI) else {
+        return false;
+    };
+    let control = pci_config_read_u16(addr, cap + 2);
+    let address = 0xFEE0_0000 | crate::arch::system::lapic::id() << 12;
+    pci_config_write_u32(addr, cap + 4, address);
+    // 64 bit capable ones have the upper half of the address before the data
+    let data = if control & (1 << 7) != 0 {
+        pci_config_write_u32(addr, cap + 8, 0);
+        cap + 12
+    } else {
+        cap + 8
+    };
+    pci_config_write_u16(addr, data, vector as u16);
+    // enabled, one message
+    pci_config_write_u16(addr, cap + 2, (control & !(7 << 4)) | 1);
+    true
+}
+
 pub fn pci_enumerate() {
     PCI_DEVICES.lock().clear();
     enumerate_bus(0);
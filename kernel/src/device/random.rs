@@ -0,0 +1,69 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::sync::Arc;
+
+use crate::{
+    arch::drivers::time::rtc::read_rtc,
+    drivers::fs::{FileObject, NodeMode, register_chrdev},
+    utils::{asm::_rdtsc, spinlock::Spin},
+};
+
+use super::mem::MEM_MAJOR;
+
+// xorshift64*, stirred with the tsc on every read. fine for seeding hash tables and
+// picking temp names, not for keys
+static STATE: Spin<u64> = Spin::new(0);
+
+pub fn fill(buf: &mut [u8]) {
+    let mut state = STATE.lock();
+    if *state == 0 {
+        *state = read_rtc().to_epoch().unwrap_or_default() ^ 0x9e37_79b9_7f4a_7c15;
+    }
+    *state ^= _rdtsc().rotate_left(17);
+    for chunk in buf.chunks_mut(8) {
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        let bytes = state.wrapping_mul(0x2545_f491_4f6c_dd1d).to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+// folds outside randomness (virtio-rng, whatever gets written to /dev/random) into the state
+pub fn add_entropy(data: &[u8]) {
+    let mut state = STATE.lock();
+    for chunk in data.chunks(8) {
+        let mut bytes = [0; 8];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        *state = (*state ^ u64::from_le_bytes(bytes)).rotate_left(23);
+    }
+    // all zero would stop xorshift for good
+    if *state == 0 {
+        *state = 0x9e37_79b9_7f4a_7c15;
+    }
+}
+
+// /dev/random and /dev/urandom, neither ever blocks like on newer linux
+pub struct Random;
+
+impl FileObject for Random {
+    fn read(&self, buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        fill(buf);
+        Ok(buf.len())
+    }
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        add_entropy(buf);
+        Ok(buf.len())
+    }
+    fn mode(&self) -> u32 {
+        0o020000
+    }
+}
+
+pub fn init() {
+    let _ = register_chrdev("random", MEM_MAJOR, 8, NodeMode::RW, Arc::new(Random));
+    let _ = register_chrdev("urandom", MEM_MAJOR, 9, NodeMode::RW, Arc::new(Random));
+}
@@ -8,12 +8,12 @@ use core::fmt::Write;
 use crate::utils::asm::port::{inb, outb};
 
 const COM1_BASE: u16 = 0x3F8;
-const COM1_DATA: u16 = COM1_BASE;
+pub const COM1_DATA: u16 = COM1_BASE;
 const COM1_INTERRUPT_ENABLE: u16 = COM1_BASE + 1;
 const COM1_LINE_CONTROL: u16 = COM1_BASE + 3;
 const COM1_FIFO_CONTROL: u16 = COM1_BASE + 2;
 const COM1_MODEM_CONTROL: u16 = COM1_BASE + 4;
-const COM1_LINE_STATUS: u16 = COM1_BASE + 5;
+pub const COM1_LINE_STATUS: u16 = COM1_BASE + 5;
 
 pub fn init() {
     outb(COM1_INTERRUPT_ENABLE, 0x00);
@@ -0,0 +1,100 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::sync::Arc;
+
+use crate::{
+    drivers::fs::{FileObject, NodeMode, PollEvents, register_chrdev},
+    print,
+    utils::{asm::port::inb, errno::*},
+};
+
+use super::serial::{COM1_DATA, COM1_LINE_STATUS, serial_write};
+
+pub const TTY_MAJOR: u32 = 4;
+pub const TTYAUX_MAJOR: u32 = 5;
+
+const S_IFCHR: u32 = 0o020000;
+
+// the framebuffer terminal, also where stdout and stderr end up
+pub fn console_write(data: &[u8]) {
+    if let Ok(s) = core::str::from_utf8(data) {
+        print!("{}", s);
+    } else {
+        print!("{:?}", data);
+    }
+}
+
+// /dev/console and /dev/tty. the keyboard belongs to the shell, so reads are eof
+// just like stdin
+pub struct Console;
+
+impl FileObject for Console {
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Ok(0)
+    }
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        console_write(buf);
+        Ok(buf.len())
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+}
+
+// /dev/ttyS0, the COM1 uart
+pub struct Serial;
+
+fn serial_ready() -> bool {
+    inb(COM1_LINE_STATUS) & 1 != 0
+}
+
+impl FileObject for Serial {
+    // whatever the uart has buffered, waiting for the first byte unless nonblocking.
+    // there's no receive interrupt, so waiting is polling
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while !serial_ready() {
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            crate::scheduler::thread::sleep_ms(1);
+        }
+        let mut n = 0;
+        while n < buf.len() && serial_ready() {
+            buf[n] = inb(COM1_DATA);
+            n += 1;
+        }
+        Ok(n)
+    }
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        buf.iter().for_each(|&b| serial_write(b));
+        Ok(buf.len())
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+    fn poll(&self) -> PollEvents {
+        if serial_ready() {
+            PollEvents::IN | PollEvents::OUT
+        } else {
+            PollEvents::OUT
+        }
+    }
+}
+
+pub fn init() {
+    let _ = register_chrdev("tty", TTYAUX_MAJOR, 0, NodeMode::RW, Arc::new(Console));
+    let _ = register_chrdev(
+        "console",
+        TTYAUX_MAJOR,
+        1,
+        NodeMode::S_IRUSR | NodeMode::S_IWUSR,
+        Arc::new(Console),
+    );
+    let _ = register_chrdev("ttyS0", TTY_MAJOR, 64, NodeMode::RW, Arc::new(Serial));
+}
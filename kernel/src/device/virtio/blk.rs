@@ -0,0 +1,241 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// virtio-blk, vda, vdb... every request is a header, the data and a status byte the
+// device writes last
+
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    device::{
+        block::{BlockDevice, BlockOp, BlockRequest, Transfer, add_disk},
+        dma::DmaBuffer,
+    },
+    info,
+    utils::{asm::without_ints, errno::*, spinlock::Spin},
+    warn,
+};
+
+use super::{
+    Transport,
+    queue::{Buffer, Virtqueue},
+};
+
+// where linux's dynamic major usually lands, 16 minors each for the partitions
+pub const VIRTIO_BLK_MAJOR: u32 = 254;
+static NEXT_DISK: AtomicU32 = AtomicU32::new(0);
+
+const F_SIZE_MAX: u64 = 1 << 1;
+const F_SEG_MAX: u64 = 1 << 2;
+const F_RO: u64 = 1 << 5;
+const F_BLK_SIZE: u64 = 1 << 6;
+const F_FLUSH: u64 = 1 << 9;
+
+const CONFIG_CAPACITY: u64 = 0x00;
+const CONFIG_SIZE_MAX: u64 = 0x08;
+const CONFIG_BLK_SIZE: u64 = 0x14;
+
+const T_IN: u32 = 0;
+const T_OUT: u32 = 1;
+const T_FLUSH: u32 = 4;
+// where the device puts the status in a request's header buffer
+const STATUS: usize = 16;
+
+const QUEUE_SIZE: u16 = 128;
+const MAX_TRANSFER: usize = 1 << 20;
+
+pub struct Disk {
+    queue: Spin<Virtqueue>,
+    name: String,
+    sectors: u64,
+    sector_size: usize,
+    read_only: bool,
+    flush: bool,
+    max_transfer: usize,
+}
+
+// the header of a request, with room for the status after it
+fn header(kind: u32, sector: u64) -> Result<DmaBuffer, i64> {
+    let header = DmaBuffer::new(STATUS + 1).ok_or(ENOMEM)?;
+    header.write(0, kind);
+    header.write(8, sector);
+    header.write(STATUS, 0xFFu8);
+    Ok(header)
+}
+
+impl Disk {
+    // splits the request into max_transfer sized ones, sectors are always 512 bytes to
+    // the device whatever the disk's own are
+    fn submit_io(&self, request: Arc<BlockRequest>) -> Result<(), i64> {
+        let len = request.buffer.lock().len();
+        if len == 0 {
+            request.complete(Ok(()));
+            return Ok(());
+        }
+        let mut transfer = Transfer::new(request)?;
+        let (kind, writable) = match transfer.request.op {
+            BlockOp::Write => (T_OUT, false),
+            _ => (T_IN, true),
+        };
+
+        let mut chains = Vec::new();
+        for offset in (0..len).step_by(self.max_transfer) {
+            let bytes = self.max_transfer.min(len - offset);
+            let sector = (transfer.request.sector * self.sector_size as u64 + offset as u64) / 512;
+            let header = header(kind, sector)?;
+            chains.push([
+                Buffer {
+                    phys: header.phys(),
+                    len: 16,
+                    writable: false,
+                },
+                Buffer {
+                    phys: transfer.data.phys() + offset as u64,
+                    len: bytes as u32,
+                    writable,
+                },
+                Buffer {
+                    phys: header.phys() + STATUS as u64,
+                    len: 1,
+                    writable: true,
+                },
+            ]);
+            transfer.extra.push(header);
+        }
+
+        transfer.parts = chains.len();
+        let transfer = Arc::new(Spin::new(transfer));
+        without_ints(|| {
+            let mut queue = self.queue.lock();
+            for (i, chain) in chains.iter().enumerate() {
+                let transfer = transfer.clone();
+                let done = Box::new(move |_| {
+                    let mut transfer = transfer.lock();
+                    let ok = transfer.extra[i].read::<u8>(STATUS) == 0;
+                    transfer.part_done(ok);
+                });
+                while !queue.has_room(chain.len()) {
+                    queue.reap();
+                }
+                queue.push(chain, done);
+            }
+        });
+        Ok(())
+    }
+
+    fn submit_flush(&self, request: Arc<BlockRequest>) -> Result<(), i64> {
+        let header = header(T_FLUSH, 0)?;
+        let chain = [
+            Buffer {
+                phys: header.phys(),
+                len: 16,
+                writable: false,
+            },
+            Buffer {
+                phys: header.phys() + STATUS as u64,
+                len: 1,
+                writable: true,
+            },
+        ];
+        without_ints(|| {
+            let mut queue = self.queue.lock();
+            while !queue.has_room(chain.len()) {
+                queue.reap();
+            }
+            queue.push(
+                &chain,
+                Box::new(move |_| {
+                    let ok = header.read::<u8>(STATUS) == 0;
+                    request.complete(if ok { Ok(()) } else { Err(EIO) });
+                }),
+            );
+        });
+        Ok(())
+    }
+}
+
+impl BlockDevice for Disk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+    fn sectors(&self) -> u64 {
+        self.sectors
+    }
+    fn minors(&self) -> u32 {
+        16
+    }
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+    fn submit(&self, request: Arc<BlockRequest>) {
+        let result = match request.op {
+            BlockOp::Flush if !self.flush => {
+                request.complete(Ok(()));
+                return;
+            }
+            BlockOp::Flush => self.submit_flush(request.clone()),
+            _ => self.submit_io(request.clone()),
+        };
+        if let Err(e) = result {
+            request.complete(Err(e));
+        }
+    }
+    fn poll(&self) {
+        without_ints(|| self.queue.lock().reap());
+    }
+}
+
+pub(super) fn setup(transport: Transport) -> Result<(), &'static str> {
+    let features = transport.negotiate(F_SIZE_MAX | F_SEG_MAX | F_RO | F_BLK_SIZE | F_FLUSH)?;
+    if !transport.has_config() {
+        transport.fail();
+        return Err("no device config");
+    }
+    let queue = transport.queue(0, QUEUE_SIZE)?;
+    transport.ready();
+
+    // capacity is in 512 byte sectors no matter what blk_size says
+    let capacity = transport.config(CONFIG_CAPACITY, 8) * 512;
+    let sector_size = match features & F_BLK_SIZE {
+        0 => 512,
+        _ => transport.config(CONFIG_BLK_SIZE, 4) as usize,
+    };
+    let max_transfer = match features & F_SIZE_MAX {
+        0 => MAX_TRANSFER,
+        _ => MAX_TRANSFER.min(transport.config(CONFIG_SIZE_MAX, 4) as usize),
+    };
+    if !sector_size.is_power_of_two() || !(512..=4096).contains(&sector_size) {
+        return Err("unsupported sector size");
+    }
+    // a size_max smaller than a sector would be useless
+    let max_transfer = (max_transfer / sector_size * sector_size).max(sector_size);
+
+    let index = NEXT_DISK.fetch_add(1, Ordering::Relaxed);
+    if index >= 26 {
+        return Err("out of disk names");
+    }
+    let name = format!("vd{}", (b'a' + index as u8) as char);
+    let disk = Disk {
+        queue: Spin::new(queue),
+        name: name.clone(),
+        sectors: capacity / sector_size as u64,
+        sector_size,
+        read_only: features & F_RO != 0,
+        flush: features & F_FLUSH != 0,
+        max_transfer,
+    };
+    info!(
+        "{name}: virtio-blk{}",
+        if disk.read_only { ", read only" } else { "" }
+    );
+    if let Err(e) = add_disk(VIRTIO_BLK_MAJOR, index * 16, Arc::new(disk)) {
+        warn!("{name}: couldn't register, errno {e}");
+    }
+    Ok(())
+}
@@ -0,0 +1,177 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// virtio-console as /dev/hvc0, hvc1... just the first port of each, no multiport. the
+// receive queue always has buffers out, whatever comes back piles up until someone reads
+
+use alloc::{boxed::Box, collections::vec_deque::VecDeque, format, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    device::dma::DmaBuffer,
+    drivers::fs::{FileObject, NodeMode, PollEvents, register_chrdev},
+    info,
+    utils::{asm::without_ints, errno::*, spinlock::Spin},
+};
+
+use super::{
+    Transport,
+    queue::{Buffer, Virtqueue},
+};
+
+pub const HVC_MAJOR: u32 = 229;
+static NEXT_CONSOLE: AtomicU32 = AtomicU32::new(0);
+
+const S_IFCHR: u32 = 0o020000;
+
+const QUEUE_SIZE: u16 = 16;
+// one page split between the receive buffers
+const RX_BUFFER: usize = 256;
+// what's kept around unread before the oldest goes
+const MAX_INPUT: usize = 64 * 1024;
+
+struct Input {
+    data: VecDeque<u8>,
+    // receive buffers the device gave back, to go out again
+    returned: Vec<usize>,
+}
+
+pub struct Console {
+    rx: Spin<Virtqueue>,
+    tx: Spin<Virtqueue>,
+    buffers: Arc<DmaBuffer>,
+    input: Arc<Spin<Input>>,
+}
+
+impl Console {
+    fn post(&self, rx: &mut Virtqueue, slot: usize) {
+        let buffers = self.buffers.clone();
+        let input = self.input.clone();
+        let buffer = Buffer {
+            phys: self.buffers.phys() + (slot * RX_BUFFER) as u64,
+            len: RX_BUFFER as u32,
+            writable: true,
+        };
+        rx.push(
+            &[buffer],
+            Box::new(move |written| {
+                let mut input = input.lock();
+                let start = slot * RX_BUFFER;
+                let end = start + (written as usize).min(RX_BUFFER);
+                input.data.extend(&buffers.as_slice()[start..end]);
+                let excess = input.data.len().saturating_sub(MAX_INPUT);
+                input.data.drain(..excess);
+                input.returned.push(slot);
+            }),
+        );
+    }
+
+    // takes in what arrived and puts the buffers back out
+    fn receive(&self) {
+        without_ints(|| {
+            let mut rx = self.rx.lock();
+            rx.reap();
+            let returned = core::mem::take(&mut self.input.lock().returned);
+            for slot in returned {
+                self.post(&mut rx, slot);
+            }
+        });
+    }
+
+    fn pending(&self) -> bool {
+        self.receive();
+        without_ints(|| !self.input.lock().data.is_empty())
+    }
+}
+
+impl FileObject for Console {
+    // waits for the first byte unless nonblocking, polling like ttyS0 does
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while !self.pending() {
+            if nonblock {
+                return Err(EAGAIN);
+            }
+            crate::scheduler::thread::sleep_ms(1);
+        }
+        Ok(without_ints(|| {
+            let mut input = self.input.lock();
+            let n = buf.len().min(input.data.len());
+            for (byte, data) in buf.iter_mut().zip(input.data.drain(..n)) {
+                *byte = data;
+            }
+            n
+        }))
+    }
+    // a page at a time, each waited for before the next
+    fn write(&self, buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        let mut data = DmaBuffer::new(4096).ok_or(ENOMEM)?;
+        for chunk in buf.chunks(4096) {
+            data.as_mut_slice()[..chunk.len()].copy_from_slice(chunk);
+            let done = Arc::new(Spin::new(false));
+            let flag = done.clone();
+            let buffer = Buffer {
+                phys: data.phys(),
+                len: chunk.len() as u32,
+                writable: false,
+            };
+            without_ints(|| {
+                let mut tx = self.tx.lock();
+                while !tx.has_room(1) {
+                    tx.reap();
+                }
+                tx.push(&[buffer], Box::new(move |_| *flag.lock() = true));
+                while !*done.lock() {
+                    tx.reap();
+                    core::hint::spin_loop();
+                }
+            });
+        }
+        Ok(buf.len())
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+    fn poll(&self) -> PollEvents {
+        if self.pending() {
+            PollEvents::IN | PollEvents::OUT
+        } else {
+            PollEvents::OUT
+        }
+    }
+}
+
+pub(super) fn setup(transport: Transport) -> Result<(), &'static str> {
+    transport.negotiate(0)?;
+    let rx = transport.queue(0, QUEUE_SIZE)?;
+    let tx = transport.queue(1, QUEUE_SIZE)?;
+    transport.ready();
+
+    let slots = (4096 / RX_BUFFER).min(rx.size() as usize);
+    let console = Console {
+        rx: Spin::new(rx),
+        tx: Spin::new(tx),
+        buffers: Arc::new(DmaBuffer::new(4096).ok_or("out of memory")?),
+        input: Arc::new(Spin::new(Input {
+            data: VecDeque::new(),
+            returned: Vec::new(),
+        })),
+    };
+    {
+        let mut rx = console.rx.lock();
+        for slot in 0..slots {
+            console.post(&mut rx, slot);
+        }
+    }
+
+    let index = NEXT_CONSOLE.fetch_add(1, Ordering::Relaxed);
+    let name = format!("hvc{index}");
+    register_chrdev(&name, HVC_MAJOR, index, NodeMode::RW, Arc::new(console))
+        .map_err(|_| "couldn't register")?;
+    info!("{name}: virtio-console");
+    Ok(())
+}
@@ -0,0 +1,234 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// virtio over pci, the modern (1.0+) transport only. the device's vendor capabilities say
+// which bar holds the common config, the notify doorbells and its own config. everything
+// is polled, queues never get an msi-x vector
+
+pub mod blk;
+pub mod console;
+pub mod queue;
+pub mod rng;
+
+use alloc::vec::Vec;
+
+use crate::{
+    device::pci::{
+        PCI_DEVICES, PciAddress, pci_capabilities, pci_config_read_u8, pci_config_read_u16,
+        pci_config_read_u32, pci_enable_device, pci_map_bar,
+    },
+    utils::asm::mmio,
+    warn,
+};
+
+use queue::Virtqueue;
+
+pub const VIRTIO_VENDOR: u16 = 0x1AF4;
+
+const CAPABILITY_VENDOR: u8 = 0x09;
+const CFG_COMMON: u8 = 1;
+const CFG_NOTIFY: u8 = 2;
+const CFG_DEVICE: u8 = 4;
+
+const COMMON_DEVICE_FEATURE_SELECT: u64 = 0x00;
+const COMMON_DEVICE_FEATURE: u64 = 0x04;
+const COMMON_DRIVER_FEATURE_SELECT: u64 = 0x08;
+const COMMON_DRIVER_FEATURE: u64 = 0x0C;
+const COMMON_STATUS: u64 = 0x14;
+const COMMON_GENERATION: u64 = 0x15;
+const COMMON_QUEUE_SELECT: u64 = 0x16;
+const COMMON_QUEUE_SIZE: u64 = 0x18;
+const COMMON_QUEUE_MSIX_VECTOR: u64 = 0x1A;
+const COMMON_QUEUE_ENABLE: u64 = 0x1C;
+const COMMON_QUEUE_NOTIFY_OFF: u64 = 0x1E;
+const COMMON_QUEUE_DESC: u64 = 0x20;
+const COMMON_QUEUE_DRIVER: u64 = 0x28;
+const COMMON_QUEUE_DEVICE: u64 = 0x30;
+
+const STATUS_ACKNOWLEDGE: u64 = 1;
+const STATUS_DRIVER: u64 = 2;
+const STATUS_DRIVER_OK: u64 = 4;
+const STATUS_FEATURES_OK: u64 = 8;
+const STATUS_FAILED: u64 = 128;
+
+const NO_VECTOR: u64 = 0xFFFF;
+
+// the only one every driver wants, legacy devices don't offer it
+pub const F_VERSION_1: u64 = 1 << 32;
+
+const TYPE_BLOCK: u16 = 2;
+const TYPE_CONSOLE: u16 = 3;
+const TYPE_RNG: u16 = 4;
+
+pub struct Transport {
+    common: u64,
+    notify: u64,
+    notify_multiplier: u64,
+    device: u64,
+}
+
+impl Transport {
+    fn new(address: PciAddress) -> Result<Self, &'static str> {
+        let (mut common, mut notify, mut device) = (None, None, None);
+        let mut notify_multiplier = 0;
+        for (_, cap) in pci_capabilities(address)
+            .into_iter()
+            .filter(|&(id, _)| id == CAPABILITY_VENDOR)
+        {
+            let bar = pci_config_read_u8(address, cap + 4);
+            let offset = pci_config_read_u32(address, cap + 8) as u64;
+            let len = pci_config_read_u32(address, cap + 12) as u64;
+            let region = || Some(pci_map_bar(address, bar, offset + len)? + offset);
+            // the first of each kind is the one to use
+            match pci_config_read_u8(address, cap + 3) {
+                CFG_COMMON if common.is_none() => common = region(),
+                CFG_NOTIFY if notify.is_none() => {
+                    notify = region();
+                    notify_multiplier = pci_config_read_u32(address, cap + 16) as u64;
+                }
+                CFG_DEVICE if device.is_none() => device = region(),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            common: common.ok_or("no common config")?,
+            notify: notify.ok_or("no notify config")?,
+            notify_multiplier,
+            device: device.unwrap_or(0),
+        })
+    }
+
+    fn read(&self, reg: u64, width: usize) -> u64 {
+        mmio::read(self.common + reg, width)
+    }
+
+    fn write(&self, reg: u64, value: u64, width: usize) {
+        mmio::write(self.common + reg, value, width);
+    }
+
+    // resets the device and agrees on the features both sides know, which it returns
+    pub fn negotiate(&self, wanted: u64) -> Result<u64, &'static str> {
+        self.write(COMMON_STATUS, 0, 1);
+        while self.read(COMMON_STATUS, 1) != 0 {
+            core::hint::spin_loop();
+        }
+        self.write(COMMON_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER, 1);
+
+        let mut offered = 0;
+        for half in 0..2 {
+            self.write(COMMON_DEVICE_FEATURE_SELECT, half, 4);
+            offered |= self.read(COMMON_DEVICE_FEATURE, 4) << (half * 32);
+        }
+        let features = offered & (wanted | F_VERSION_1);
+        if features & F_VERSION_1 == 0 {
+            self.fail();
+            return Err("not a virtio 1.0 device");
+        }
+        for half in 0..2 {
+            self.write(COMMON_DRIVER_FEATURE_SELECT, half, 4);
+            self.write(
+                COMMON_DRIVER_FEATURE,
+                features >> (half * 32) & 0xFFFF_FFFF,
+                4,
+            );
+        }
+        let status = self.read(COMMON_STATUS, 1) | STATUS_FEATURES_OK;
+        self.write(COMMON_STATUS, status, 1);
+        if self.read(COMMON_STATUS, 1) & STATUS_FEATURES_OK == 0 {
+            self.fail();
+            return Err("features not accepted");
+        }
+        Ok(features)
+    }
+
+    // sets up queue `index` with at most `size` entries, after negotiate() and before ready()
+    pub fn queue(&self, index: u16, size: u16) -> Result<Virtqueue, &'static str> {
+        self.write(COMMON_QUEUE_SELECT, index as u64, 2);
+        let max = self.read(COMMON_QUEUE_SIZE, 2) as u16;
+        if max == 0 {
+            return Err("queue not available");
+        }
+        // split rings have to be a power of two long
+        let size = size.min(max);
+        let size = 1 << (15 - size.leading_zeros());
+        let notify = self.notify + self.read(COMMON_QUEUE_NOTIFY_OFF, 2) * self.notify_multiplier;
+        let queue = Virtqueue::new(index, size, notify).ok_or("out of memory")?;
+
+        let (desc, avail, used) = queue.addresses();
+        self.write(COMMON_QUEUE_SIZE, size as u64, 2);
+        self.write(COMMON_QUEUE_MSIX_VECTOR, NO_VECTOR, 2);
+        self.write(COMMON_QUEUE_DESC, desc, 8);
+        self.write(COMMON_QUEUE_DRIVER, avail, 8);
+        self.write(COMMON_QUEUE_DEVICE, used, 8);
+        self.write(COMMON_QUEUE_ENABLE, 1, 2);
+        Ok(queue)
+    }
+
+    pub fn ready(&self) {
+        let status = self.read(COMMON_STATUS, 1) | STATUS_DRIVER_OK;
+        self.write(COMMON_STATUS, status, 1);
+    }
+
+    pub fn fail(&self) {
+        let status = self.read(COMMON_STATUS, 1) | STATUS_FAILED;
+        self.write(COMMON_STATUS, status, 1);
+    }
+
+    // a field of the device's own config, read again if it changed halfway through
+    pub fn config(&self, offset: u64, width: usize) -> u64 {
+        loop {
+            let generation = self.read(COMMON_GENERATION, 1);
+            let value = if width == 8 {
+                mmio::read(self.device + offset, 4) | mmio::read(self.device + offset + 4, 4) << 32
+            } else {
+                mmio::read(self.device + offset, width)
+            };
+            if self.read(COMMON_GENERATION, 1) == generation {
+                return value;
+            }
+        }
+    }
+
+    pub fn has_config(&self) -> bool {
+        self.device != 0
+    }
+}
+
+// 0x1040 + type for modern devices, transitional ones say it in the subsystem id
+fn device_type(address: PciAddress, device_id: u16) -> Option<u16> {
+    match device_id {
+        0x1040..=0x107F => Some(device_id - 0x1040),
+        0x1000..=0x103F => Some(pci_config_read_u16(address, 0x2E)),
+        _ => None,
+    }
+}
+
+// every virtio device pci_enumerate found that there's a driver for
+pub fn init() {
+    let devices = PCI_DEVICES
+        .lock()
+        .iter()
+        .filter(|d| d.vendor_id == VIRTIO_VENDOR)
+        .map(|d| (d.address, d.device_id))
+        .collect::<Vec<_>>();
+    for (address, device_id) in devices {
+        let Some(kind) = device_type(address, device_id) else {
+            continue;
+        };
+        let setup = match kind {
+            TYPE_BLOCK => blk::setup,
+            TYPE_CONSOLE => console::setup,
+            TYPE_RNG => rng::setup,
+            _ => continue,
+        };
+        pci_enable_device(address, false);
+        if let Err(e) = Transport::new(address).and_then(setup) {
+            warn!(
+                "virtio at {:02x}:{:02x}.{}: {e}",
+                address.bus, address.device, address.function
+            );
+        }
+    }
+}
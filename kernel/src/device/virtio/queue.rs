@@ -0,0 +1,137 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// a split virtqueue. buffers go out as descriptor chains on the available ring, the device
+// hands them back on the used ring with how much it wrote. nothing here takes interrupts,
+// whoever owns the queue calls reap()
+
+use alloc::{boxed::Box, vec::Vec};
+use core::sync::atomic::{Ordering, fence};
+
+use crate::{device::dma::DmaBuffer, utils::asm::mmio};
+
+const DESC_NEXT: u16 = 1;
+const DESC_WRITE: u16 = 2;
+// in the available ring's flags, we poll anyway
+const AVAIL_NO_INTERRUPT: u16 = 1;
+
+// one piece of a chain
+#[derive(Clone, Copy)]
+pub struct Buffer {
+    pub phys: u64,
+    pub len: u32,
+    // the device writes it instead of reading it
+    pub writable: bool,
+}
+
+// called with how many bytes the device wrote into the chain
+pub type Callback = Box<dyn FnOnce(u32) + Send>;
+
+pub struct Virtqueue {
+    index: u16,
+    size: u16,
+    desc: DmaBuffer,
+    avail: DmaBuffer,
+    used: DmaBuffer,
+    free: Vec<u16>,
+    avail_idx: u16,
+    last_used: u16,
+    // by the chain's first descriptor
+    pending: Vec<Option<Callback>>,
+    notify: u64,
+}
+
+impl Virtqueue {
+    pub fn new(index: u16, size: u16, notify: u64) -> Option<Self> {
+        let queue = Self {
+            index,
+            size,
+            desc: DmaBuffer::new(size as usize * 16)?,
+            avail: DmaBuffer::new(6 + size as usize * 2)?,
+            used: DmaBuffer::new(6 + size as usize * 8)?,
+            free: (0..size).rev().collect(),
+            avail_idx: 0,
+            last_used: 0,
+            pending: (0..size).map(|_| None).collect(),
+            notify,
+        };
+        queue.avail.write(0, AVAIL_NO_INTERRUPT);
+        Some(queue)
+    }
+
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    // the descriptor table and both rings, for the transport to hand to the device
+    pub fn addresses(&self) -> (u64, u64, u64) {
+        (self.desc.phys(), self.avail.phys(), self.used.phys())
+    }
+
+    pub fn has_room(&self, buffers: usize) -> bool {
+        self.free.len() >= buffers
+    }
+
+    // false without enough free descriptors, reap and try again
+    pub fn push(&mut self, buffers: &[Buffer], done: Callback) -> bool {
+        if buffers.is_empty() || !self.has_room(buffers.len()) {
+            return false;
+        }
+        let ids = self.free.split_off(self.free.len() - buffers.len());
+        for (i, (buffer, &id)) in buffers.iter().zip(&ids).enumerate() {
+            let next = ids.get(i + 1).copied();
+            let mut flags = if buffer.writable { DESC_WRITE } else { 0 };
+            if next.is_some() {
+                flags |= DESC_NEXT;
+            }
+            let desc = id as usize * 16;
+            self.desc.write(desc, buffer.phys);
+            self.desc.write(desc + 8, buffer.len);
+            self.desc.write(desc + 12, flags);
+            self.desc.write(desc + 14, next.unwrap_or(0));
+        }
+        let head = ids[0];
+        self.pending[head as usize] = Some(done);
+
+        let slot = 4 + (self.avail_idx % self.size) as usize * 2;
+        self.avail.write(slot, head);
+        // the device mustn't see the new index before the entry
+        fence(Ordering::SeqCst);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        self.avail.write(2, self.avail_idx);
+        fence(Ordering::SeqCst);
+        mmio::write(self.notify, self.index as u64, 2);
+        true
+    }
+
+    // runs the callbacks of every chain the device gave back since last time
+    pub fn reap(&mut self) {
+        loop {
+            let used_idx = self.used.read::<u16>(2);
+            if used_idx == self.last_used {
+                return;
+            }
+            fence(Ordering::SeqCst);
+            let entry = 4 + (self.last_used % self.size) as usize * 8;
+            let head = self.used.read::<u32>(entry) as u16;
+            let written = self.used.read::<u32>(entry + 4);
+            self.last_used = self.last_used.wrapping_add(1);
+
+            // the chain's descriptors go back on the free list
+            let mut id = head;
+            loop {
+                self.free.push(id);
+                let desc = id as usize * 16;
+                if self.desc.read::<u16>(desc + 12) & DESC_NEXT == 0 {
+                    break;
+                }
+                id = self.desc.read::<u16>(desc + 14);
+            }
+            if let Some(done) = self.pending.get_mut(head as usize).and_then(Option::take) {
+                done(written);
+            }
+        }
+    }
+}
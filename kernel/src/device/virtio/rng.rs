@@ -0,0 +1,96 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// virtio-rng, the host's entropy. it's stirred into /dev/random's state when found and on
+// every read of /dev/hwrng, which hands out the device's bytes as they are
+
+use alloc::{boxed::Box, sync::Arc};
+
+use crate::{
+    device::{dma::DmaBuffer, random},
+    drivers::fs::{FileObject, NodeMode, register_chrdev},
+    info,
+    utils::{asm::without_ints, errno::*, spinlock::Spin},
+};
+
+use super::{
+    Transport,
+    queue::{Buffer, Virtqueue},
+};
+
+pub const MISC_MAJOR: u32 = 10;
+const HWRNG_MINOR: u32 = 183;
+
+const S_IFCHR: u32 = 0o020000;
+
+pub struct Rng {
+    queue: Spin<Virtqueue>,
+    data: DmaBuffer,
+}
+
+impl Rng {
+    // however much the device had, at least a byte. it's quick, so this just spins
+    fn fetch(&self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(4096);
+        let written = Arc::new(Spin::new(None));
+        let result = written.clone();
+        let buffer = Buffer {
+            phys: self.data.phys(),
+            len: len as u32,
+            writable: true,
+        };
+        let n = without_ints(|| {
+            let mut queue = self.queue.lock();
+            queue.push(&[buffer], Box::new(move |n| *result.lock() = Some(n)));
+            loop {
+                queue.reap();
+                if let Some(n) = *written.lock() {
+                    break (n as usize).min(len);
+                }
+                core::hint::spin_loop();
+            }
+        });
+        buf[..n].copy_from_slice(&self.data.as_slice()[..n]);
+        random::add_entropy(&buf[..n]);
+        n
+    }
+}
+
+// /dev/hwrng
+impl FileObject for Rng {
+    fn read(&self, buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        Ok(self.fetch(buf))
+    }
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn mode(&self) -> u32 {
+        S_IFCHR
+    }
+}
+
+pub(super) fn setup(transport: Transport) -> Result<(), &'static str> {
+    transport.negotiate(0)?;
+    let queue = transport.queue(0, 8)?;
+    transport.ready();
+    let rng = Rng {
+        queue: Spin::new(queue),
+        data: DmaBuffer::new(4096).ok_or("out of memory")?,
+    };
+    rng.fetch(&mut [0; 64]);
+    info!("hwrng: virtio-rng");
+    // only the first one gets the node
+    let _ = register_chrdev(
+        "hwrng",
+        MISC_MAJOR,
+        HWRNG_MINOR,
+        NodeMode::S_IRUSR,
+        Arc::new(rng),
+    );
+    Ok(())
+}
@@ -87,6 +87,7 @@ extern "C" fn uacpi_sleepbtn_handler(_: uacpi_handle) -> uacpi_interrupt_ret {
 }
 
 pub fn shutdown() -> uacpi_status {
+    crate::device::block::cache::sync_for_shutdown();
     unsafe {
         let ret = uacpi_prepare_for_sleep_state(UACPI_SLEEP_STATE_S5);
         if ret != UACPI_STATUS_OK {
@@ -97,6 +98,7 @@ pub fn shutdown() -> uacpi_status {
 }
 
 pub fn reboot() -> uacpi_status {
+    crate::device::block::cache::sync_for_shutdown();
     unsafe { uacpi_reboot() }
 }
 
@@ -0,0 +1,119 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::collections::btree_map::BTreeMap;
+
+use crate::utils::errno::*;
+
+use super::*;
+
+// dev_t packed like glibc does it, 12 bits of major and 20 of minor
+pub const fn makedev(major: u32, minor: u32) -> u64 {
+    let (major, minor) = (major as u64, minor as u64);
+    (minor & 0xff) | ((major & 0xfff) << 8) | ((minor & !0xff) << 12) | ((major & !0xfff) << 32)
+}
+
+pub const fn major(dev: u64) -> u32 {
+    (((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff)) as u32
+}
+
+pub const fn minor(dev: u64) -> u32 {
+    ((dev & 0xff) | ((dev >> 12) & !0xff)) as u32
+}
+
+// character devices by dev_t. every open of a node gets the same object, drivers
+// that want per-open state can hand out fresh objects from their own read/write
+static CHAR_DEVICES: Spin<BTreeMap<u64, Arc<dyn FileObject>>> = Spin::new(BTreeMap::new());
+
+// one tree shared by every devfs mount, like devtmpfs
+static ROOT: Spin<Option<InodeRef>> = Spin::new(None);
+
+fn root() -> InodeRef {
+    ROOT.lock()
+        .get_or_insert_with(|| {
+            let sb = TmpfsSb::new(TmpfsSb::default_size(), TmpfsSb::default_inodes());
+            let root = Inode::new(Directory::new().with_storage(Storage::Tmpfs(sb.clone())));
+            // posix shared memory (shm_open) lives in /dev/shm, out of the same space
+            let shm = Directory::new().with_storage(Storage::Shm(sb));
+            let _ = root.node_mut().link("shm", Inode::new(shm));
+            root
+        })
+        .clone()
+}
+
+// makes /dev/`name`, creating the directories in it ("input/event0") on the way
+fn publish(name: &str, type_: VfsNodeType, rdev: u64, mode: NodeMode) -> Result<(), i64> {
+    let path = Path::new(&format!("/{name}"));
+    let mut dir = root();
+    for part in path
+        .get_parent()
+        .as_str()
+        .split('/')
+        .filter(|p| !p.is_empty())
+    {
+        let next = {
+            let mut node = dir.node_mut();
+            match node.get_child(part) {
+                Some(child) => child,
+                None => node.create_dir(part)?,
+            }
+        };
+        dir = next;
+    }
+    let node = Inode::new(DeviceNode::new(type_, rdev)).with_permissions(mode);
+    dir.node_mut()
+        .link(path.get_name(), node)
+        .map_err(|_| EBUSY)
+}
+
+// publishes a character device as /dev/`name`, which may have directories in it.
+// EBUSY if the numbers or the name are taken
+pub fn register_chrdev(
+    name: &str,
+    major: u32,
+    minor: u32,
+    mode: NodeMode,
+    device: Arc<dyn FileObject>,
+) -> Result<(), i64> {
+    let rdev = makedev(major, minor);
+    let mut devices = CHAR_DEVICES.lock();
+    if devices.contains_key(&rdev) {
+        return Err(EBUSY);
+    }
+    publish(name, VfsNodeType::CharDevice, rdev, mode)?;
+    devices.insert(rdev, device);
+    Ok(())
+}
+
+// just the node, opening it goes to the block layer's own registry
+pub fn register_blkdev(name: &str, major: u32, minor: u32, mode: NodeMode) -> Result<(), i64> {
+    publish(name, VfsNodeType::BlockDevice, makedev(major, minor), mode)
+}
+
+// the node stays, opening it gives ENXIO from now on
+pub fn unregister_chrdev(major: u32, minor: u32) -> bool {
+    CHAR_DEVICES.lock().remove(&makedev(major, minor)).is_some()
+}
+
+pub fn chrdev(rdev: u64) -> Option<Arc<dyn FileObject>> {
+    CHAR_DEVICES.lock().get(&rdev).cloned()
+}
+
+pub struct DevFs;
+
+impl FileSystemType for DevFs {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+    // devtmpfs says tmpfs too
+    fn magic(&self) -> u64 {
+        TMPFS_MAGIC
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Ok(root())
+    }
+}
+
+pub static DEVFS: DevFs = DevFs;
@@ -0,0 +1,1901 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// ext2, revision 0 and 1 with 1K to 64K blocks, everything goes through the buffer cache.
+// inodes are kept while anyone holds them, so a file with several names is one node
+// however it's looked up. directories hold on to what they've handed out like on fat
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{collections::BTreeMap, sync::Weak};
+
+use crate::{
+    device::block::{block_device, cache},
+    utils::{errno::*, mutex::Mutex},
+};
+
+use super::*;
+
+pub const EXT2_SUPER_MAGIC: u64 = 0xEF53;
+
+// always at byte 1024, whatever the block size
+const SUPERBLOCK: u64 = 1024;
+const ROOT_INO: u32 = 2;
+
+const STATE_VALID: u16 = 0x1;
+const STATE_ERROR: u16 = 0x2;
+
+// the file type in directory entries, the only incompatible feature that's understood
+const INCOMPAT_FILETYPE: u32 = 0x2;
+// sparse superblocks, large files and btree directories, none of which need anything
+// special to write to
+const RO_COMPAT_KNOWN: u32 = 0x7;
+const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+
+// a hashed directory, the index goes stale as soon as anything is added the plain way
+const INDEX_FL: u32 = 0x1000;
+
+const S_IFMT: u16 = 0xF000;
+const S_IFCHR: u16 = 0x2000;
+const S_IFDIR: u16 = 0x4000;
+const S_IFBLK: u16 = 0x6000;
+const S_IFREG: u16 = 0x8000;
+const S_IFLNK: u16 = 0xA000;
+
+const FT_REG_FILE: u8 = 1;
+const FT_DIR: u8 = 2;
+const FT_CHRDEV: u8 = 3;
+const FT_BLKDEV: u8 = 4;
+const FT_SYMLINK: u8 = 7;
+
+// block pointers in the inode before the indirect ones
+const DIRECT: usize = 12;
+// i_block is 60 bytes, shorter targets are kept right in there
+const FAST_SYMLINK: usize = 60;
+const GROUP_DESC: u64 = 32;
+const MAX_NAME: usize = 255;
+
+// extended attributes, one block i_file_acl points at. inodes with the same ones may
+// share it, h_refcount says how many do
+const COMPAT_EXT_ATTR: u32 = 0x8;
+const XATTR_MAGIC: u32 = 0xEA02_0000;
+const XATTR_HEADER: usize = 32;
+const XATTR_ENTRY: usize = 16;
+// the prefix is stored as an index, acls are a whole name with nothing after it
+const XATTR_INDEXES: [(u8, &str); 5] = [
+    (1, "user."),
+    (ACL_ACCESS, "system.posix_acl_access"),
+    (ACL_DEFAULT, "system.posix_acl_default"),
+    (4, "trusted."),
+    (6, "security."),
+];
+const ACL_ACCESS: u8 = 2;
+const ACL_DEFAULT: u8 = 3;
+// acl entries on disk leave out the id unless it's a named user or group
+const ACL_VERSION: u32 = 1;
+const ACL_XATTR_VERSION: u32 = 2;
+const ACL_USER: u16 = 2;
+const ACL_GROUP: u16 = 8;
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn put_u16(data: &mut [u8], pos: usize, value: u16) {
+    data[pos..pos + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(data: &mut [u8], pos: usize, value: u32) {
+    data[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// what a directory entry with a name this long takes up
+fn rec_size(name_len: usize) -> usize {
+    (8 + name_len).next_multiple_of(4)
+}
+
+fn check_name(name: &str) -> Result<(), i64> {
+    if name.len() > MAX_NAME {
+        return Err(ENAMETOOLONG);
+    }
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        return Err(EINVAL);
+    }
+    Ok(())
+}
+
+// old style device numbers are 8 bits each in i_block[0], anything bigger goes in
+// i_block[1] the way linux packs it
+fn decode_rdev(block: &[u32; 15]) -> u64 {
+    match block[0] {
+        0 => {
+            let dev = block[1];
+            makedev((dev & 0xfff00) >> 8, (dev & 0xff) | (dev >> 12 & 0xfff00))
+        }
+        dev => makedev(dev >> 8 & 0xff, dev & 0xff),
+    }
+}
+
+fn encode_rdev(rdev: u64, block: &mut [u32; 15]) {
+    let (major, minor) = (major(rdev), minor(rdev));
+    if major < 256 && minor < 256 {
+        block[0] = major << 8 | minor;
+        block[1] = 0;
+    } else {
+        block[0] = 0;
+        block[1] = (minor & 0xff) | major << 8 | (minor & !0xff) << 12;
+    }
+}
+
+struct Xattr {
+    index: u8,
+    name: Vec<u8>,
+    value: Vec<u8>,
+}
+
+fn xattr_key(name: &str) -> Option<(u8, &[u8])> {
+    XATTR_INDEXES.iter().find_map(|&(index, prefix)| {
+        let rest = name.strip_prefix(prefix)?;
+        let acl = matches!(index, ACL_ACCESS | ACL_DEFAULT);
+        (!acl || rest.is_empty()).then_some((index, rest.as_bytes()))
+    })
+}
+
+// None for indexes that aren't understood, those are kept but not shown
+fn xattr_name(xattr: &Xattr) -> Option<String> {
+    let (_, prefix) = XATTR_INDEXES.iter().find(|(i, _)| *i == xattr.index)?;
+    Some(format!("{prefix}{}", String::from_utf8_lossy(&xattr.name)))
+}
+
+// the entry hash e2fsck checks, over the name and the value padded to 4 bytes. names
+// are signed chars the way linux hashes them on x86
+fn xattr_hash(name: &[u8], value: &[u8]) -> u32 {
+    let hash = name
+        .iter()
+        .fold(0u32, |h, &c| h.rotate_left(5) ^ c as i8 as u32);
+    value.as_chunks::<4>().0.iter().fold(hash, |h, word| {
+        h.rotate_left(16) ^ u32::from_le_bytes(*word)
+    })
+}
+
+fn acl_to_disk(value: &[u8]) -> Result<Vec<u8>, i64> {
+    let (header, entries) = value.split_at_checked(4).ok_or(EINVAL)?;
+    if u32_at(header, 0) != ACL_XATTR_VERSION || !entries.len().is_multiple_of(8) {
+        return Err(EINVAL);
+    }
+    let mut raw = ACL_VERSION.to_le_bytes().to_vec();
+    for entry in entries.as_chunks::<8>().0 {
+        match u16_at(entry, 0) {
+            ACL_USER | ACL_GROUP => raw.extend_from_slice(entry),
+            _ => raw.extend_from_slice(&entry[..4]),
+        }
+    }
+    Ok(raw)
+}
+
+fn acl_from_disk(raw: &[u8]) -> Result<Vec<u8>, i64> {
+    if raw.len() < 4 || u32_at(raw, 0) != ACL_VERSION {
+        return Err(EIO);
+    }
+    let mut value = ACL_XATTR_VERSION.to_le_bytes().to_vec();
+    let mut pos = 4;
+    while pos < raw.len() {
+        let entry = raw.get(pos..pos + 4).ok_or(EIO)?;
+        value.extend_from_slice(entry);
+        pos += 4;
+        match u16_at(entry, 0) {
+            ACL_USER | ACL_GROUP => {
+                value.extend_from_slice(raw.get(pos..pos + 4).ok_or(EIO)?);
+                pos += 4;
+            }
+            _ => value.extend_from_slice(&ACL_UNDEFINED_ID.to_le_bytes()),
+        }
+    }
+    Ok(value)
+}
+
+// where a group's bitmaps and inodes are, these never move
+struct Group {
+    block_bitmap: u32,
+    inode_bitmap: u32,
+    inode_table: u32,
+}
+
+#[derive(Clone, Copy)]
+struct GroupCounts {
+    free_blocks: u16,
+    free_inodes: u16,
+    dirs: u16,
+}
+
+struct Counts {
+    groups: Vec<GroupCounts>,
+    free_blocks: u32,
+    free_inodes: u32,
+    // the superblock's totals are out of date, the group descriptors never are
+    dirty: bool,
+}
+
+// one per mount, what the superblock and group descriptors say
+pub struct Ext2Sb {
+    rdev: u64,
+    block_size: u64,
+    blocks: u32,
+    inodes: u32,
+    reserved: u32,
+    first_data_block: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    inode_size: u64,
+    first_ino: u32,
+    filetype: bool,
+    large_file: bool,
+    // some inode has an xattr block, set along with the first one
+    ext_attr: AtomicBool,
+    // the disk can be written and there's no feature in the way
+    writable: bool,
+    // what the state was before the mount, put back on umount. None when mounted read-only
+    state: Option<u16>,
+    gdt: u64,
+    groups: Vec<Group>,
+    counts: Mutex<Counts>,
+    nodes: Spin<BTreeMap<u32, Weak<Inode>>>,
+    // dropped nodes that still have to be written or freed, see flush. taken after nodes
+    pending: Spin<BTreeMap<u32, Ext2Node>>,
+}
+
+impl Ext2Sb {
+    fn new(rdev: u64, rw: bool) -> Result<Self, i64> {
+        let device = block_device(rdev).ok_or(ENXIO)?;
+        let mut raw = [0u8; 1024];
+        if cache::read(rdev, SUPERBLOCK, &mut raw)? != raw.len() {
+            return Err(EINVAL);
+        }
+        if u16_at(&raw, 56) as u64 != EXT2_SUPER_MAGIC || u32_at(&raw, 24) > 6 {
+            return Err(EINVAL);
+        }
+
+        let block_size = 1024u64 << u32_at(&raw, 24);
+        let (first_ino, inode_size, compat, incompat, ro_compat) = match u32_at(&raw, 76) {
+            0 => (11, 128, 0, 0, 0),
+            _ => (
+                u32_at(&raw, 84),
+                u16_at(&raw, 88) as u64,
+                u32_at(&raw, 92),
+                u32_at(&raw, 96),
+                u32_at(&raw, 100),
+            ),
+        };
+        if incompat & !INCOMPAT_FILETYPE != 0 {
+            warn!(
+                "{}: ext2 has features {:#x} that aren't supported",
+                device.name(),
+                incompat & !INCOMPAT_FILETYPE
+            );
+            return Err(EINVAL);
+        }
+
+        let inodes = u32_at(&raw, 0);
+        let blocks = u32_at(&raw, 4);
+        let first_data_block = u32_at(&raw, 20);
+        let blocks_per_group = u32_at(&raw, 32);
+        let inodes_per_group = u32_at(&raw, 40);
+        let bits = block_size as u32 * 8;
+        if blocks_per_group == 0
+            || blocks_per_group > bits
+            || inodes_per_group == 0
+            || inodes_per_group > bits
+            || !inode_size.is_power_of_two()
+            || !(128..=block_size).contains(&inode_size)
+            || first_data_block >= blocks
+            || first_ino <= ROOT_INO
+        {
+            return Err(EINVAL);
+        }
+        if blocks as u64 * block_size > device.capacity() {
+            warn!(
+                "{}: ext2 filesystem is bigger than the device",
+                device.name()
+            );
+            return Err(EINVAL);
+        }
+
+        let count = (blocks - first_data_block).div_ceil(blocks_per_group) as usize;
+        if (count as u64 * inodes_per_group as u64) < inodes as u64 {
+            return Err(EINVAL);
+        }
+        let gdt = (first_data_block as u64 + 1) * block_size;
+        let mut table = vec![0u8; count * GROUP_DESC as usize];
+        if cache::read(rdev, gdt, &mut table)? != table.len() {
+            return Err(EINVAL);
+        }
+        let mut groups = Vec::with_capacity(count);
+        let mut counts = Counts {
+            groups: Vec::with_capacity(count),
+            free_blocks: 0,
+            free_inodes: 0,
+            dirty: false,
+        };
+        for desc in table.as_chunks::<{ GROUP_DESC as usize }>().0 {
+            let group = Group {
+                block_bitmap: u32_at(desc, 0),
+                inode_bitmap: u32_at(desc, 4),
+                inode_table: u32_at(desc, 8),
+            };
+            if [group.block_bitmap, group.inode_bitmap, group.inode_table]
+                .iter()
+                .any(|&b| !(first_data_block..blocks).contains(&b))
+            {
+                return Err(EINVAL);
+            }
+            let group_counts = GroupCounts {
+                free_blocks: u16_at(desc, 12),
+                free_inodes: u16_at(desc, 14),
+                dirs: u16_at(desc, 16),
+            };
+            counts.free_blocks += group_counts.free_blocks as u32;
+            counts.free_inodes += group_counts.free_inodes as u32;
+            groups.push(group);
+            counts.groups.push(group_counts);
+        }
+
+        // what isn't understood could be broken by writing, reading it is fine
+        let writable = ro_compat & !RO_COMPAT_KNOWN == 0 && !device.read_only();
+        if rw && ro_compat & !RO_COMPAT_KNOWN != 0 {
+            warn!(
+                "{}: ext2 has features {:#x} that can only be mounted read-only",
+                device.name(),
+                ro_compat & !RO_COMPAT_KNOWN
+            );
+            return Err(EROFS);
+        }
+
+        let mut sb = Self {
+            rdev,
+            block_size,
+            blocks,
+            inodes,
+            reserved: u32_at(&raw, 8),
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            first_ino,
+            filetype: incompat & INCOMPAT_FILETYPE != 0,
+            large_file: ro_compat & RO_COMPAT_LARGE_FILE != 0,
+            ext_attr: AtomicBool::new(compat & COMPAT_EXT_ATTR != 0),
+            writable,
+            state: None,
+            gdt,
+            groups,
+            counts: Mutex::new(counts),
+            nodes: Spin::new(BTreeMap::new()),
+            pending: Spin::new(BTreeMap::new()),
+        };
+
+        // not clean until it's unmounted again, so fsck knows to look if it never is
+        if rw && writable {
+            let state = u16_at(&raw, 58);
+            if state & STATE_VALID == 0 || state & STATE_ERROR != 0 {
+                warn!(
+                    "{}: ext2 wasn't unmounted cleanly, run e2fsck on it",
+                    device.name()
+                );
+            }
+            let mounts = u16_at(&raw, 52).wrapping_add(1);
+            sb.write(SUPERBLOCK + 44, &(now() as u32).to_le_bytes())?;
+            sb.write(SUPERBLOCK + 52, &mounts.to_le_bytes())?;
+            sb.write(SUPERBLOCK + 58, &(state & !STATE_VALID).to_le_bytes())?;
+            sb.state = Some(state);
+        }
+        Ok(sb)
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), i64> {
+        match cache::read(self.rdev, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(EIO),
+        }
+    }
+
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<(), i64> {
+        match cache::write(self.rdev, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(EIO),
+        }
+    }
+
+    fn set_ext_attr(&self) -> Result<(), i64> {
+        if self.ext_attr.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let mut raw = [0u8; 4];
+        self.read(SUPERBLOCK + 92, &mut raw)?;
+        let compat = u32::from_le_bytes(raw) | COMPAT_EXT_ATTR;
+        self.write(SUPERBLOCK + 92, &compat.to_le_bytes())
+    }
+
+    fn check_writable(&self) -> Result<(), i64> {
+        match self.writable {
+            true => Ok(()),
+            false => Err(EROFS),
+        }
+    }
+
+    fn offset(&self, block: u32) -> u64 {
+        block as u64 * self.block_size
+    }
+
+    fn per_block(&self) -> u64 {
+        self.block_size / 4
+    }
+
+    // the biggest file the block tree can hold, or i_size without large_file
+    fn max_size(&self) -> u64 {
+        let p = self.per_block();
+        let tree = (DIRECT as u64 + p + p * p + p * p * p) * self.block_size;
+        match self.large_file {
+            // i_blocks counts 512 byte sectors in 32 bits
+            true => tree.min(u32::MAX as u64 * 512),
+            false => tree.min(i32::MAX as u64),
+        }
+    }
+
+    // which i_block slot file block `n` starts at, then the index at every indirect level
+    fn path(&self, n: u64) -> Result<(usize, Vec<usize>), i64> {
+        if n < DIRECT as u64 {
+            return Ok((n as usize, Vec::new()));
+        }
+        let p = self.per_block();
+        let (mut n, mut span) = (n - DIRECT as u64, p);
+        for level in 0..3 {
+            if n < span {
+                let mut path = Vec::with_capacity(level + 1);
+                let mut below = span / p;
+                for _ in 0..=level {
+                    path.push((n / below) as usize);
+                    n %= below;
+                    below /= p;
+                }
+                return Ok((DIRECT + level, path));
+            }
+            n -= span;
+            span *= p;
+        }
+        Err(EFBIG)
+    }
+
+    fn ptrs(&self, block: u32) -> Result<Vec<u32>, i64> {
+        let mut raw = vec![0u8; self.block_size as usize];
+        self.read(self.offset(block), &mut raw)?;
+        Ok(raw
+            .as_chunks::<4>()
+            .0
+            .iter()
+            .map(|b| u32::from_le_bytes(*b))
+            .collect())
+    }
+
+    fn write_ptrs(&self, block: u32, ptrs: &[u32]) -> Result<(), i64> {
+        let raw = ptrs
+            .iter()
+            .flat_map(|p| p.to_le_bytes())
+            .collect::<Vec<u8>>();
+        self.write(self.offset(block), &raw)
+    }
+
+    // one pointer out of an indirect block, past the end of the disk is a broken tree
+    fn ptr(&self, block: u32, index: usize) -> Result<u32, i64> {
+        let mut raw = [0u8; 4];
+        self.read(self.offset(block) + index as u64 * 4, &mut raw)?;
+        match u32::from_le_bytes(raw) {
+            ptr if ptr < self.blocks => Ok(ptr),
+            _ => Err(EIO),
+        }
+    }
+
+    fn set_ptr(&self, block: u32, index: usize, value: u32) -> Result<(), i64> {
+        self.write(self.offset(block) + index as u64 * 4, &value.to_le_bytes())
+    }
+
+    fn inode_offset(&self, ino: u32) -> Result<u64, i64> {
+        if ino == 0 || ino > self.inodes {
+            return Err(EIO);
+        }
+        let (group, index) = (
+            (ino - 1) / self.inodes_per_group,
+            (ino - 1) % self.inodes_per_group,
+        );
+        let table = self.groups[group as usize].inode_table;
+        Ok(self.offset(table) + index as u64 * self.inode_size)
+    }
+
+    fn write_group(&self, group: usize, counts: &GroupCounts) -> Result<(), i64> {
+        let mut raw = [0u8; 6];
+        put_u16(&mut raw, 0, counts.free_blocks);
+        put_u16(&mut raw, 2, counts.free_inodes);
+        put_u16(&mut raw, 4, counts.dirs);
+        self.write(self.gdt + group as u64 * GROUP_DESC + 12, &raw)
+    }
+
+    // the first clear bit from `from` on in a bitmap of `len` bits, set
+    fn take_bit(&self, bitmap: u32, from: u32, len: u32) -> Result<Option<u32>, i64> {
+        let mut map = vec![0u8; len.div_ceil(8) as usize];
+        self.read(self.offset(bitmap), &mut map)?;
+        let Some(bit) = (from..len).find(|&b| map[b as usize / 8] & 1 << (b % 8) == 0) else {
+            return Ok(None);
+        };
+        let byte = map[bit as usize / 8] | 1 << (bit % 8);
+        self.write(self.offset(bitmap) + bit as u64 / 8, &[byte])?;
+        Ok(Some(bit))
+    }
+
+    // false if it was clear already
+    fn clear_bit(&self, bitmap: u32, bit: u32) -> Result<bool, i64> {
+        let pos = self.offset(bitmap) + bit as u64 / 8;
+        let mut byte = [0u8];
+        self.read(pos, &mut byte)?;
+        if byte[0] & 1 << (bit % 8) == 0 {
+            return Ok(false);
+        }
+        byte[0] &= !(1 << (bit % 8));
+        self.write(pos, &byte)?;
+        Ok(true)
+    }
+
+    // a free block as close after `goal` as there is, the goal's group first
+    fn alloc_block(&self, goal: u32) -> Result<u32, i64> {
+        let mut counts = self.counts.lock();
+        if counts.free_blocks == 0 {
+            return Err(ENOSPC);
+        }
+        let goal = goal.clamp(self.first_data_block, self.blocks - 1) - self.first_data_block;
+        let (start, count) = ((goal / self.blocks_per_group) as usize, self.groups.len());
+        // the goal's group is looked at again last, from its start
+        for i in 0..=count {
+            let group = (start + i) % count;
+            if counts.groups[group].free_blocks == 0 {
+                continue;
+            }
+            let first = self.blocks_per_group * group as u32;
+            let from = if i == 0 { goal - first } else { 0 };
+            let len = (self.blocks - self.first_data_block - first).min(self.blocks_per_group);
+            let Some(bit) = self.take_bit(self.groups[group].block_bitmap, from, len)? else {
+                continue;
+            };
+            counts.groups[group].free_blocks -= 1;
+            counts.free_blocks -= 1;
+            counts.dirty = true;
+            self.write_group(group, &counts.groups[group])?;
+            return Ok(self.first_data_block + first + bit);
+        }
+        Err(ENOSPC)
+    }
+
+    fn free_block(&self, block: u32) {
+        if !(self.first_data_block..self.blocks).contains(&block) {
+            warn!("ext2: freeing block {block} that isn't on the disk");
+            return;
+        }
+        let n = block - self.first_data_block;
+        let group = (n / self.blocks_per_group) as usize;
+        let mut counts = self.counts.lock();
+        match self.clear_bit(self.groups[group].block_bitmap, n % self.blocks_per_group) {
+            Ok(true) => {
+                counts.groups[group].free_blocks += 1;
+                counts.free_blocks += 1;
+                counts.dirty = true;
+                let _ = self.write_group(group, &counts.groups[group]);
+            }
+            Ok(false) => warn!("ext2: block {block} was already free"),
+            Err(_) => {}
+        }
+    }
+
+    // a free inode number, starting at `group`
+    fn alloc_inode(&self, group: usize, dir: bool) -> Result<u32, i64> {
+        let mut counts = self.counts.lock();
+        let count = self.groups.len();
+        for i in 0..count {
+            let group = (group + i) % count;
+            if counts.groups[group].free_inodes == 0 {
+                continue;
+            }
+            let first = self.inodes_per_group * group as u32;
+            // the reserved ones at the start are never handed out
+            let from = (self.first_ino - 1).saturating_sub(first);
+            let len = (self.inodes - first).min(self.inodes_per_group);
+            if from >= len {
+                continue;
+            }
+            let Some(bit) = self.take_bit(self.groups[group].inode_bitmap, from, len)? else {
+                continue;
+            };
+            let group_counts = &mut counts.groups[group];
+            group_counts.free_inodes -= 1;
+            group_counts.dirs += dir as u16;
+            let group_counts = *group_counts;
+            counts.free_inodes -= 1;
+            counts.dirty = true;
+            self.write_group(group, &group_counts)?;
+            return Ok(first + bit + 1);
+        }
+        Err(ENOSPC)
+    }
+
+    fn free_inode(&self, ino: u32, dir: bool) {
+        let group = ((ino - 1) / self.inodes_per_group) as usize;
+        let mut counts = self.counts.lock();
+        let bit = (ino - 1) % self.inodes_per_group;
+        match self.clear_bit(self.groups[group].inode_bitmap, bit) {
+            Ok(true) => {
+                let group_counts = &mut counts.groups[group];
+                group_counts.free_inodes += 1;
+                group_counts.dirs -= dir as u16;
+                let group_counts = *group_counts;
+                counts.free_inodes += 1;
+                counts.dirty = true;
+                let _ = self.write_group(group, &group_counts);
+            }
+            Ok(false) => warn!("ext2: inode {ino} was already free"),
+            Err(_) => {}
+        }
+    }
+
+    // the superblock's free counts
+    fn write_counts(&self) {
+        let mut counts = self.counts.lock();
+        if !counts.dirty || !self.writable {
+            return;
+        }
+        let _ = self.write(SUPERBLOCK + 12, &counts.free_blocks.to_le_bytes());
+        let _ = self.write(SUPERBLOCK + 16, &counts.free_inodes.to_le_bytes());
+        counts.dirty = false;
+    }
+
+    fn stats(&self) -> FsStats {
+        let counts = self.counts.lock();
+        let to_pages = |blocks: u32| blocks as u64 * self.block_size / 4096;
+        FsStats {
+            blocks: to_pages(self.blocks - self.first_data_block),
+            bfree: to_pages(counts.free_blocks),
+            bavail: to_pages(counts.free_blocks.saturating_sub(self.reserved)),
+        }
+    }
+
+    // the node for `ino`, the one that's already around if anyone has it
+    fn get_inode(self: &Arc<Self>, ino: u32) -> Result<InodeRef, i64> {
+        let cached = self.cached(&mut self.nodes.lock(), ino);
+        if let Some(inode) = cached {
+            return Ok(inode);
+        }
+        let inode = Inode::new(Ext2Node::load(self, ino)?);
+        let mut nodes = self.nodes.lock();
+        // someone read it in meanwhile, theirs wins and this one goes outside the lock
+        if let Some(other) = self.cached(&mut nodes, ino) {
+            drop(nodes);
+            return Ok(other);
+        }
+        nodes.insert(ino, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    // a node still in use, or one waiting to be written back. that one is newer than the disk
+    fn cached(&self, nodes: &mut BTreeMap<u32, Weak<Inode>>, ino: u32) -> Option<InodeRef> {
+        if let Some(inode) = nodes.get(&ino).and_then(Weak::upgrade) {
+            return Some(inode);
+        }
+        let inode = Inode::new(self.pending.lock().remove(&ino)?);
+        nodes.insert(ino, Arc::downgrade(&inode));
+        Some(inode)
+    }
+
+    // writes back what was dropped since the last time, with no locks held
+    fn flush(&self) {
+        let pending = core::mem::take(&mut *self.pending.lock());
+        for (_, mut node) in pending {
+            node.write_back();
+        }
+    }
+
+    // a node that was just made
+    fn insert(&self, node: Ext2Node) -> InodeRef {
+        let ino = node.ino;
+        let inode = Inode::new(node);
+        self.nodes.lock().insert(ino, Arc::downgrade(&inode));
+        inode
+    }
+}
+
+// the last node is gone, which is after the umount
+impl Drop for Ext2Sb {
+    fn drop(&mut self) {
+        let counts = self.counts.lock();
+        let free =
+            (counts.dirty && self.writable).then_some((counts.free_blocks, counts.free_inodes));
+        drop(counts);
+        let (rdev, state) = (self.rdev, self.state);
+        // the last node can go anywhere, the superblock is written from flush_deferred
+        defer(move || {
+            let write = |offset, buf: &[u8]| cache::write(rdev, offset, buf).map(|_| ());
+            if let Some((blocks, inodes)) = free {
+                let _ = write(SUPERBLOCK + 12, &blocks.to_le_bytes());
+                let _ = write(SUPERBLOCK + 16, &inodes.to_le_bytes());
+            }
+            if let Some(state) = state {
+                let _ = write(SUPERBLOCK + 48, &(now() as u32).to_le_bytes());
+                let _ = write(SUPERBLOCK + 58, &state.to_le_bytes());
+            }
+            let _ = cache::sync_device(rdev);
+        });
+    }
+}
+
+struct Child {
+    name: String,
+    inode: InodeRef,
+}
+
+// an inode, whatever's in it on disk is kept here and written back as it changes
+pub struct Ext2Node {
+    sb: Arc<Ext2Sb>,
+    ino: u32,
+    // setuid, setgid and sticky, NodeMode only has the permission bits
+    special: u16,
+    flags: u32,
+    // i_block as it is on disk, fast symlinks and device numbers live in there too
+    block: [u32; 15],
+    // i_blocks, 512 byte units with the indirect blocks counted in
+    sectors: u32,
+    file_acl: u32,
+    // the last block handed to this node, the next one goes right after it
+    goal: u32,
+    // directories only, there's an entry naming it somewhere
+    linked: bool,
+    target: String,
+    rdev: u64,
+    // directories only, read in the first time someone looks
+    children: Spin<Option<Vec<Child>>>,
+    // changed through get_metadata_mut, written back on sync or when it's dropped
+    dirty: AtomicBool,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for Ext2Node {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "ext2 inode {}, {:?}", self.ino, self.metadata)
+    }
+}
+
+impl Ext2Node {
+    fn new(sb: Arc<Ext2Sb>, ino: u32, type_: VfsNodeType) -> Self {
+        Self {
+            sb,
+            ino,
+            special: 0,
+            flags: 0,
+            block: [0; 15],
+            sectors: 0,
+            file_acl: 0,
+            goal: 0,
+            linked: false,
+            target: String::new(),
+            rdev: 0,
+            children: Spin::new(None),
+            dirty: AtomicBool::new(false),
+            metadata: VfsNodeMetadata::new(type_),
+        }
+    }
+
+    fn load(sb: &Arc<Ext2Sb>, ino: u32) -> Result<Self, i64> {
+        let mut raw = [0u8; 128];
+        sb.read(sb.inode_offset(ino)?, &mut raw)?;
+        let mode = u16_at(&raw, 0);
+        let type_ = match mode & S_IFMT {
+            S_IFREG => VfsNodeType::File,
+            S_IFDIR => VfsNodeType::Directory,
+            S_IFLNK => VfsNodeType::Symlink,
+            S_IFCHR => VfsNodeType::CharDevice,
+            S_IFBLK => VfsNodeType::BlockDevice,
+            // fifos and sockets have nothing behind them here
+            _ => return Err(EOPNOTSUPP),
+        };
+        // a directory entry pointing at a deleted inode
+        let links = u16_at(&raw, 26);
+        if links == 0 {
+            return Err(EIO);
+        }
+
+        let mut node = Self::new(sb.clone(), ino, type_);
+        node.special = mode & 0o7000;
+        node.sectors = u32_at(&raw, 28);
+        node.flags = u32_at(&raw, 32);
+        node.file_acl = u32_at(&raw, 104);
+        for (i, block) in node.block.iter_mut().enumerate() {
+            *block = u32_at(&raw, 40 + i * 4);
+        }
+        node.linked = true;
+
+        let metadata = &mut node.metadata;
+        metadata.permissions = NodeMode::from_bits_truncate((mode & 0o777) as i32);
+        metadata.uid = u16_at(&raw, 2) as u32 | (u16_at(&raw, 120) as u32) << 16;
+        metadata.gid = u16_at(&raw, 24) as u32 | (u16_at(&raw, 122) as u32) << 16;
+        metadata.size = u32_at(&raw, 4) as u64;
+        if metadata.type_ == VfsNodeType::File {
+            metadata.size |= (u32_at(&raw, 108) as u64) << 32;
+        }
+        metadata.set_accessed_at(u32_at(&raw, 8) as u64);
+        metadata.changed_at = u32_at(&raw, 12) as u64;
+        metadata.modified_at = u32_at(&raw, 16) as u64;
+        // there's no creation time in ext2
+        metadata.created_at = metadata.changed_at;
+        metadata.nlink = links as u64;
+
+        match node.metadata.type_ {
+            VfsNodeType::Symlink => node.target = node.load_target()?,
+            VfsNodeType::CharDevice | VfsNodeType::BlockDevice => {
+                node.rdev = decode_rdev(&node.block)
+            }
+            _ => {}
+        }
+        Ok(node)
+    }
+
+    fn group(&self) -> usize {
+        ((self.ino - 1) / self.sb.inodes_per_group) as usize
+    }
+
+    // only an extended attribute block, if anything
+    fn is_fast_symlink(&self) -> bool {
+        let acl = if self.file_acl != 0 {
+            self.sb.block_size / 512
+        } else {
+            0
+        };
+        self.metadata.type_ == VfsNodeType::Symlink && self.sectors as u64 == acl
+    }
+
+    // i_block is block pointers, not a target or a device number
+    fn has_blocks(&self) -> bool {
+        match self.metadata.type_ {
+            VfsNodeType::File | VfsNodeType::Directory => true,
+            VfsNodeType::Symlink => !self.is_fast_symlink(),
+            _ => false,
+        }
+    }
+
+    fn load_target(&self) -> Result<String, i64> {
+        let len = self.metadata.size as usize;
+        let mut raw = vec![0u8; len];
+        if self.is_fast_symlink() {
+            let bytes = self
+                .block
+                .iter()
+                .flat_map(|b| b.to_le_bytes())
+                .collect::<Vec<u8>>();
+            raw.copy_from_slice(bytes.get(..len).ok_or(EIO)?);
+        } else {
+            if len > self.sb.block_size as usize {
+                return Err(EIO);
+            }
+            match self.bmap(0)? {
+                0 => return Err(EIO),
+                block => self.sb.read(self.sb.offset(block), &mut raw)?,
+            }
+        }
+        Ok(String::from_utf8_lossy(&raw).into_owned())
+    }
+
+    fn set_target(&mut self, target: &str) -> Result<(), i64> {
+        let len = target.len();
+        if len >= FAST_SYMLINK {
+            if len >= self.sb.block_size as usize {
+                return Err(ENAMETOOLONG);
+            }
+            let block = self.bmap_alloc(0, true)?;
+            self.sb.write(self.sb.offset(block), target.as_bytes())?;
+        } else {
+            let mut raw = [0u8; FAST_SYMLINK];
+            raw[..len].copy_from_slice(target.as_bytes());
+            for (i, block) in self.block.iter_mut().enumerate() {
+                *block = u32_at(&raw, i * 4);
+            }
+        }
+        self.metadata.size = len as u64;
+        self.target = String::from(target);
+        Ok(())
+    }
+
+    fn mode(&self) -> u16 {
+        let type_ = match self.metadata.type_ {
+            VfsNodeType::Directory => S_IFDIR,
+            VfsNodeType::Symlink => S_IFLNK,
+            VfsNodeType::CharDevice => S_IFCHR,
+            VfsNodeType::BlockDevice => S_IFBLK,
+            _ => S_IFREG,
+        };
+        type_ | self.special | (self.metadata.permissions.bits() & 0o777) as u16
+    }
+
+    fn file_type(&self) -> u8 {
+        match self.metadata.type_ {
+            VfsNodeType::Directory => FT_DIR,
+            VfsNodeType::Symlink => FT_SYMLINK,
+            VfsNodeType::CharDevice => FT_CHRDEV,
+            VfsNodeType::BlockDevice => FT_BLKDEV,
+            _ => FT_REG_FILE,
+        }
+    }
+
+    fn fill_inode(&self, raw: &mut [u8]) {
+        let metadata = &self.metadata;
+        put_u16(raw, 0, self.mode());
+        put_u16(raw, 2, metadata.uid as u16);
+        put_u16(raw, 120, (metadata.uid >> 16) as u16);
+        put_u16(raw, 24, metadata.gid as u16);
+        put_u16(raw, 122, (metadata.gid >> 16) as u16);
+        put_u32(raw, 4, metadata.size as u32);
+        if metadata.type_ == VfsNodeType::File {
+            put_u32(raw, 108, (metadata.size >> 32) as u32);
+        }
+        put_u32(raw, 8, metadata.accessed_at() as u32);
+        put_u32(raw, 12, metadata.changed_at as u32);
+        put_u32(raw, 16, metadata.modified_at as u32);
+        put_u16(raw, 26, metadata.nlink.min(u16::MAX as u64) as u16);
+        put_u32(raw, 28, self.sectors);
+        put_u32(raw, 32, self.flags);
+        for (i, &block) in self.block.iter().enumerate() {
+            put_u32(raw, 40 + i * 4, block);
+        }
+        put_u32(raw, 104, self.file_acl);
+    }
+
+    fn write_inode(&self) -> Result<(), i64> {
+        let pos = self.sb.inode_offset(self.ino)?;
+        let mut raw = [0u8; 128];
+        self.sb.read(pos, &mut raw)?;
+        self.fill_inode(&mut raw);
+        self.sb.write(pos, &raw)?;
+        self.dirty.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // a block for this node, zeroed if it won't be written over whole
+    fn alloc(&mut self, zero: bool) -> Result<u32, i64> {
+        let goal = match self.goal {
+            0 => self.sb.first_data_block + self.group() as u32 * self.sb.blocks_per_group,
+            goal => goal + 1,
+        };
+        let block = self.sb.alloc_block(goal)?;
+        if zero
+            && let Err(e) = self.sb.write(
+                self.sb.offset(block),
+                &vec![0u8; self.sb.block_size as usize],
+            )
+        {
+            self.sb.free_block(block);
+            return Err(e);
+        }
+        self.goal = block;
+        self.sectors += (self.sb.block_size / 512) as u32;
+        Ok(block)
+    }
+
+    fn release(&mut self, block: u32) {
+        self.sb.free_block(block);
+        self.sectors = self
+            .sectors
+            .saturating_sub((self.sb.block_size / 512) as u32);
+    }
+
+    // where file block `n` is on the disk, 0 for a hole
+    fn bmap(&self, n: u64) -> Result<u32, i64> {
+        let (slot, path) = self.sb.path(n)?;
+        let mut block = self.block[slot];
+        for index in path {
+            if block == 0 {
+                break;
+            }
+            block = self.sb.ptr(block, index)?;
+        }
+        Ok(block)
+    }
+
+    // the same, filling in the hole and any indirect blocks on the way
+    fn bmap_alloc(&mut self, n: u64, zero: bool) -> Result<u32, i64> {
+        let (slot, path) = self.sb.path(n)?;
+        let mut block = self.block[slot];
+        if block == 0 {
+            block = self.alloc(zero || !path.is_empty())?;
+            self.block[slot] = block;
+        }
+        for (level, &index) in path.iter().enumerate() {
+            let mut next = self.sb.ptr(block, index)?;
+            if next == 0 {
+                next = self.alloc(zero || level + 1 < path.len())?;
+                self.sb.set_ptr(block, index, next)?;
+            }
+            block = next;
+        }
+        Ok(block)
+    }
+
+    // frees everything past the first `keep` blocks
+    fn shrink(&mut self, keep: u64) -> Result<(), i64> {
+        for slot in keep.min(DIRECT as u64) as usize..DIRECT {
+            let block = core::mem::take(&mut self.block[slot]);
+            if block != 0 {
+                self.release(block);
+            }
+        }
+        let p = self.sb.per_block();
+        let (mut start, mut span) = (DIRECT as u64, p);
+        for level in 0..3 {
+            let block = self.block[DIRECT + level];
+            let keep = keep.saturating_sub(start);
+            if block != 0 && keep == 0 {
+                self.free_tree(block, level)?;
+                self.block[DIRECT + level] = 0;
+            } else if block != 0 && keep < span {
+                self.trim(block, level, keep)?;
+            }
+            start += span;
+            span *= p;
+        }
+        Ok(())
+    }
+
+    // an indirect block `level` levels above the data and everything under it
+    fn free_tree(&mut self, block: u32, level: usize) -> Result<(), i64> {
+        for ptr in self.sb.ptrs(block)? {
+            match ptr {
+                0 => {}
+                ptr if level == 0 => self.release(ptr),
+                ptr => self.free_tree(ptr, level - 1)?,
+            }
+        }
+        self.release(block);
+        Ok(())
+    }
+
+    // keeps the first `keep` data blocks under an indirect block, frees the rest
+    fn trim(&mut self, block: u32, level: usize, keep: u64) -> Result<(), i64> {
+        let below = self.sb.per_block().pow(level as u32);
+        let mut ptrs = self.sb.ptrs(block)?;
+        for (i, ptr) in ptrs.iter_mut().enumerate() {
+            let first = i as u64 * below;
+            if *ptr == 0 || first + below <= keep {
+                continue;
+            }
+            if first < keep {
+                self.trim(*ptr, level - 1, keep - first)?;
+                continue;
+            }
+            match level {
+                0 => self.release(*ptr),
+                _ => self.free_tree(*ptr, level - 1)?,
+            }
+            *ptr = 0;
+        }
+        self.sb.write_ptrs(block, &ptrs)
+    }
+
+    fn xattr_block(&self) -> Result<Vec<u8>, i64> {
+        if self.file_acl >= self.sb.blocks {
+            return Err(EIO);
+        }
+        let mut raw = vec![0u8; self.sb.block_size as usize];
+        self.sb.read(self.sb.offset(self.file_acl), &mut raw)?;
+        if u32_at(&raw, 0) != XATTR_MAGIC || u32_at(&raw, 8) != 1 {
+            return Err(EIO);
+        }
+        Ok(raw)
+    }
+
+    fn load_xattrs(&self) -> Result<Vec<Xattr>, i64> {
+        if self.file_acl == 0 {
+            return Ok(Vec::new());
+        }
+        let raw = self.xattr_block()?;
+        let mut xattrs = Vec::new();
+        let mut pos = XATTR_HEADER;
+        // four zero bytes after the last entry
+        while raw.get(pos..pos + 4).ok_or(EIO)? != [0; 4] {
+            let entry = raw.get(pos..pos + XATTR_ENTRY).ok_or(EIO)?;
+            let name_len = entry[0] as usize;
+            let (offset, size) = (u16_at(entry, 2) as usize, u32_at(entry, 8) as usize);
+            // values in an inode of their own are ext4's
+            if u32_at(entry, 4) != 0 {
+                return Err(EIO);
+            }
+            let name = raw.get(pos + XATTR_ENTRY..pos + XATTR_ENTRY + name_len);
+            let value = raw.get(offset..offset + size);
+            xattrs.push(Xattr {
+                index: entry[1],
+                name: name.ok_or(EIO)?.to_vec(),
+                value: value.ok_or(EIO)?.to_vec(),
+            });
+            pos += (XATTR_ENTRY + name_len).next_multiple_of(4);
+        }
+        Ok(xattrs)
+    }
+
+    // entries sorted from the front and values packed from the back like linux does it.
+    // a block shared with other inodes is left to them and this one gets its own
+    fn store_xattrs(&mut self, mut xattrs: Vec<Xattr>) -> Result<(), i64> {
+        if xattrs.is_empty() {
+            self.drop_xattrs()?;
+            return self.write_inode();
+        }
+        xattrs.sort_by(|a, b| {
+            (a.index, a.name.len(), &a.name).cmp(&(b.index, b.name.len(), &b.name))
+        });
+        let bs = self.sb.block_size as usize;
+        let mut raw = vec![0u8; bs];
+        put_u32(&mut raw, 0, XATTR_MAGIC);
+        put_u32(&mut raw, 4, 1);
+        put_u32(&mut raw, 8, 1);
+        let (mut pos, mut end) = (XATTR_HEADER, bs);
+        // zero if any entry's is
+        let mut block_hash = Some(0u32);
+        for xattr in &xattrs {
+            let len = (XATTR_ENTRY + xattr.name.len()).next_multiple_of(4);
+            let padded = xattr.value.len().next_multiple_of(4);
+            if pos + len + 4 + padded > end {
+                return Err(ENOSPC);
+            }
+            end -= padded;
+            raw[end..end + xattr.value.len()].copy_from_slice(&xattr.value);
+            let hash = xattr_hash(&xattr.name, &raw[end..end + padded]);
+            raw[pos] = xattr.name.len() as u8;
+            raw[pos + 1] = xattr.index;
+            put_u16(&mut raw, pos + 2, if padded == 0 { 0 } else { end as u16 });
+            put_u32(&mut raw, pos + 8, xattr.value.len() as u32);
+            put_u32(&mut raw, pos + 12, hash);
+            raw[pos + XATTR_ENTRY..pos + XATTR_ENTRY + xattr.name.len()]
+                .copy_from_slice(&xattr.name);
+            block_hash = block_hash
+                .filter(|_| hash != 0)
+                .map(|h| h.rotate_left(16) ^ hash);
+            pos += len;
+        }
+        put_u32(&mut raw, 12, block_hash.unwrap_or(0));
+
+        let shared = self.file_acl != 0 && u32_at(&self.xattr_block()?, 4) > 1;
+        let block = match self.file_acl {
+            0 => self.alloc(false)?,
+            _ if shared => self.alloc(false)?,
+            block => block,
+        };
+        if let Err(e) = self.sb.write(self.sb.offset(block), &raw) {
+            if block != self.file_acl {
+                self.release(block);
+            }
+            return Err(e);
+        }
+        if block != self.file_acl {
+            self.drop_xattrs()?;
+            self.file_acl = block;
+            self.write_inode()?;
+        }
+        self.sb.set_ext_attr()
+    }
+
+    // lets go of the xattr block, it's only freed if no other inode has it
+    fn drop_xattrs(&mut self) -> Result<(), i64> {
+        let block = core::mem::take(&mut self.file_acl);
+        if block == 0 {
+            return Ok(());
+        }
+        let pos = self.sb.offset(block) + 4;
+        let mut refcount = [0u8; 4];
+        self.sb.read(pos, &mut refcount)?;
+        match u32::from_le_bytes(refcount) {
+            0 | 1 => self.release(block),
+            n => {
+                self.sb.write(pos, &(n - 1).to_le_bytes())?;
+                self.sectors = self
+                    .sectors
+                    .saturating_sub((self.sb.block_size / 512) as u32);
+            }
+        }
+        Ok(())
+    }
+
+    // the last name and the last user are gone
+    fn delete(&mut self) -> Result<(), i64> {
+        if self.has_blocks() {
+            self.shrink(0)?;
+        }
+        self.drop_xattrs()?;
+        self.metadata.nlink = 0;
+        self.write_inode()?;
+        let pos = self.sb.inode_offset(self.ino)?;
+        self.sb.write(pos + 20, &(now() as u32).to_le_bytes())?;
+        self.sb.free_inode(self.ino, self.is_dir());
+        Ok(())
+    }
+
+    fn orphaned(&self) -> bool {
+        let unlinked = match self.is_dir() {
+            true => !self.linked,
+            false => self.metadata.nlink == 0,
+        };
+        unlinked && self.ino != ROOT_INO && self.ino != 0
+    }
+
+    // everything that goes to the disk moves to a new node, for the pending list
+    fn take(&mut self) -> Ext2Node {
+        Ext2Node {
+            sb: self.sb.clone(),
+            ino: self.ino,
+            special: self.special,
+            flags: self.flags,
+            block: self.block,
+            sectors: self.sectors,
+            file_acl: self.file_acl,
+            goal: self.goal,
+            linked: self.linked,
+            target: core::mem::take(&mut self.target),
+            rdev: self.rdev,
+            children: Spin::new(self.children.lock().take()),
+            dirty: AtomicBool::new(*self.dirty.get_mut()),
+            // what's left of this one is only dropped
+            metadata: core::mem::replace(
+                &mut self.metadata,
+                VfsNodeMetadata::new(VfsNodeType::File),
+            ),
+        }
+    }
+
+    // what the last drop left to do. it's clean after, even if the disk said no
+    fn write_back(&mut self) {
+        if self.orphaned() {
+            if let Err(e) = self.delete() {
+                warn!("ext2: couldn't free inode {}, errno {e}", self.ino);
+            }
+            // the inode is free, nothing is written for it again
+            self.ino = 0;
+        } else if *self.dirty.get_mut() {
+            let _ = self.write_inode();
+        }
+        *self.dirty.get_mut() = false;
+    }
+
+    // a fresh inode near this directory, not linked anywhere yet
+    fn create(&self, type_: VfsNodeType) -> Result<Ext2Node, i64> {
+        self.sb.check_writable()?;
+        let dir = type_ == VfsNodeType::Directory;
+        let ino = self.sb.alloc_inode(self.group(), dir)?;
+        let mut node = Ext2Node::new(self.sb.clone(), ino, type_);
+        node.metadata.permissions = NodeMode::from_bits_truncate(match dir {
+            true => 0o755,
+            false => 0o644,
+        });
+        // whatever the last owner of the number left behind
+        let pos = self.sb.inode_offset(ino)?;
+        self.sb
+            .write(pos, &vec![0u8; self.sb.inode_size as usize])?;
+        node.write_inode()?;
+        Ok(node)
+    }
+
+    // "." and, until it's linked somewhere, a ".." pointing nowhere
+    fn init_dir(&mut self) -> Result<(), i64> {
+        let bs = self.sb.block_size as usize;
+        let mut raw = vec![0u8; bs];
+        self.put_entry(&mut raw, 0, self.ino, 12, b".", FT_DIR);
+        self.put_entry(&mut raw, 12, 0, bs - 12, b"..", FT_DIR);
+        let block = self.bmap_alloc(0, false)?;
+        self.sb.write(self.sb.offset(block), &raw)?;
+        self.metadata.size = bs as u64;
+        self.metadata.nlink = 1;
+        self.children = Spin::new(Some(Vec::new()));
+        self.write_inode()
+    }
+
+    // records are 64K long at most, one covering a whole 64K block is stored as 0xffff
+    fn rec_len(&self, raw: &[u8], pos: usize) -> usize {
+        match u16_at(raw, pos + 4) {
+            0 | 0xFFFF if self.sb.block_size == 65536 => 65536,
+            len => len as usize,
+        }
+    }
+
+    fn put_rec_len(&self, raw: &mut [u8], pos: usize, len: usize) {
+        put_u16(raw, pos + 4, len.min(0xFFFF) as u16);
+    }
+
+    fn put_entry(&self, raw: &mut [u8], pos: usize, ino: u32, len: usize, name: &[u8], kind: u8) {
+        put_u32(raw, pos, ino);
+        self.put_rec_len(raw, pos, len);
+        raw[pos + 6] = name.len() as u8;
+        raw[pos + 7] = if self.sb.filetype { kind } else { 0 };
+        raw[pos + 8..pos + 8 + name.len()].copy_from_slice(name);
+    }
+
+    // (inode, record length, name length) of the entry at `pos`, EIO if it's broken
+    fn entry_at(&self, raw: &[u8], pos: usize) -> Result<(u32, usize, usize), i64> {
+        let len = self.rec_len(raw, pos);
+        let name_len = raw[pos + 6] as usize;
+        if len < 8 || len % 4 != 0 || pos + len > raw.len() || 8 + name_len > len {
+            return Err(EIO);
+        }
+        Ok((u32_at(raw, pos), len, name_len))
+    }
+
+    // block `n` of a directory, holes don't belong in one
+    fn dir_block(&self, n: u64) -> Result<(u32, Vec<u8>), i64> {
+        let block = match self.bmap(n)? {
+            0 => return Err(EIO),
+            block => block,
+        };
+        let mut raw = vec![0u8; self.sb.block_size as usize];
+        self.sb.read(self.sb.offset(block), &mut raw)?;
+        Ok((block, raw))
+    }
+
+    fn dir_blocks(&self) -> u64 {
+        self.metadata.size / self.sb.block_size
+    }
+
+    fn scan(&self) -> Result<Vec<Child>, i64> {
+        let mut children = Vec::new();
+        for n in 0..self.dir_blocks() {
+            let (_, raw) = self.dir_block(n)?;
+            let mut pos = 0;
+            while pos + 8 <= raw.len() {
+                let (ino, len, name_len) = self.entry_at(&raw, pos)?;
+                let name = &raw[pos + 8..pos + 8 + name_len];
+                pos += len;
+                if ino == 0 || ino == self.ino || name == b"." || name == b".." {
+                    continue;
+                }
+                let name = String::from_utf8_lossy(name).into_owned();
+                match self.sb.get_inode(ino) {
+                    Ok(inode) => children.push(Child { name, inode }),
+                    Err(EOPNOTSUPP) => {}
+                    Err(_) => warn!("ext2: {name} has a broken inode {ino}, skipping it"),
+                }
+            }
+        }
+        Ok(children)
+    }
+
+    // the entries are read in outside the lock, someone who got there first wins
+    fn with_children<T>(&self, f: impl FnOnce(&mut Vec<Child>) -> T) -> T {
+        if self.children.lock().is_none() {
+            match self.scan() {
+                Ok(scanned) => {
+                    let mut children = self.children.lock();
+                    if children.is_none() {
+                        *children = Some(scanned);
+                    }
+                }
+                Err(e) => {
+                    warn!("ext2: couldn't read directory {}, errno {e}", self.ino);
+                    return f(&mut Vec::new());
+                }
+            }
+        }
+        f(self.children.lock().as_mut().unwrap())
+    }
+
+    // whether `name` can be added here
+    fn check_new(&self, name: &str) -> Result<(), i64> {
+        if !self.is_dir() {
+            return Err(ENOTDIR);
+        }
+        self.sb.check_writable()?;
+        check_name(name)?;
+        match self.with_children(|c| c.iter().any(|c| c.name == name)) {
+            true => Err(EEXIST),
+            false => Ok(()),
+        }
+    }
+
+    // into the first gap that's big enough, or a new block at the end
+    fn add_entry(&mut self, name: &str, ino: u32, kind: u8) -> Result<(), i64> {
+        let need = rec_size(name.len());
+        self.flags &= !INDEX_FL;
+        for n in 0..self.dir_blocks() {
+            let (block, mut raw) = self.dir_block(n)?;
+            let mut pos = 0;
+            while pos + 8 <= raw.len() {
+                let (used_by, len, name_len) = self.entry_at(&raw, pos)?;
+                let used = if used_by == 0 { 0 } else { rec_size(name_len) };
+                if len >= used + need {
+                    if used != 0 {
+                        self.put_rec_len(&mut raw, pos, used);
+                    }
+                    self.put_entry(&mut raw, pos + used, ino, len - used, name.as_bytes(), kind);
+                    return self
+                        .sb
+                        .write(self.sb.offset(block) + pos as u64, &raw[pos..pos + len]);
+                }
+                pos += len;
+            }
+        }
+
+        let bs = self.sb.block_size as usize;
+        let block = self.bmap_alloc(self.dir_blocks(), false)?;
+        let mut raw = vec![0u8; bs];
+        self.put_entry(&mut raw, 0, ino, bs, name.as_bytes(), kind);
+        self.sb.write(self.sb.offset(block), &raw)?;
+        self.metadata.size += bs as u64;
+        Ok(())
+    }
+
+    // the one before takes over its space, the first in a block just gets emptied
+    fn remove_entry(&mut self, name: &str) -> Result<(), i64> {
+        self.flags &= !INDEX_FL;
+        for n in 0..self.dir_blocks() {
+            let (block, mut raw) = self.dir_block(n)?;
+            let (mut pos, mut prev) = (0, None);
+            while pos + 8 <= raw.len() {
+                let (ino, len, name_len) = self.entry_at(&raw, pos)?;
+                if ino == 0 || &raw[pos + 8..pos + 8 + name_len] != name.as_bytes() {
+                    prev = Some(pos);
+                    pos += len;
+                    continue;
+                }
+                let start = match prev {
+                    Some(prev) => {
+                        let merged = self.rec_len(&raw, prev) + len;
+                        self.put_rec_len(&mut raw, prev, merged);
+                        prev
+                    }
+                    None => {
+                        put_u32(&mut raw, pos, 0);
+                        pos
+                    }
+                };
+                return self
+                    .sb
+                    .write(self.sb.offset(block) + start as u64, &raw[start..pos + len]);
+            }
+        }
+        Err(ENOENT)
+    }
+
+    // a moved directory's ".." follows it
+    fn set_dotdot(&self, parent: u32) -> Result<(), i64> {
+        let (block, raw) = self.dir_block(0)?;
+        let (_, len, _) = self.entry_at(&raw, 0)?;
+        let (_, _, name_len) = self.entry_at(&raw, len)?;
+        if &raw[len + 8..len + 8 + name_len] != b".." {
+            return Err(EIO);
+        }
+        self.sb
+            .write(self.sb.offset(block) + len as u64, &parent.to_le_bytes())
+    }
+
+    // an entry naming `inode`, which has to be one of ours. directories can only have one
+    fn add_link(&mut self, name: &str, inode: &InodeRef) -> Result<(), i64> {
+        self.check_new(name)?;
+        let mut guard = inode.node_mut();
+        let node = as_ext2(&mut guard).ok_or(EXDEV)?;
+        if !Arc::ptr_eq(&node.sb, &self.sb) {
+            return Err(EXDEV);
+        }
+        if node.is_dir() && node.linked {
+            return Err(EPERM);
+        }
+        self.add_entry(name, node.ino, node.file_type())?;
+        node.metadata.nlink += 1;
+        node.metadata.touch_changed();
+        if node.is_dir() {
+            node.set_dotdot(self.ino)?;
+            node.linked = true;
+            self.metadata.nlink += 1;
+        }
+        node.write_inode()?;
+        drop(guard);
+
+        self.with_children(|children| {
+            children.push(Child {
+                name: name.to_string(),
+                inode: inode.clone(),
+            })
+        });
+        self.metadata.touch_modified();
+        self.write_inode()
+    }
+
+    // the vfs makes symlinks and device nodes itself and links them in, those are
+    // copied into inodes of ours
+    fn adopt(&self, inode: InodeRef) -> Result<InodeRef, i64> {
+        let node = inode.node();
+        let any: &dyn core::any::Any = node.as_ref();
+        if any.is::<Ext2Node>() {
+            drop(node);
+            return Ok(inode);
+        }
+        let mut new = match node.get_type() {
+            VfsNodeType::Symlink => {
+                let mut new = self.create(VfsNodeType::Symlink)?;
+                new.set_target(node.readlink().unwrap_or_default())?;
+                new
+            }
+            VfsNodeType::CharDevice | VfsNodeType::BlockDevice => {
+                let mut new = self.create(match node.get_type() {
+                    VfsNodeType::CharDevice => VfsNodeType::CharDevice,
+                    _ => VfsNodeType::BlockDevice,
+                })?;
+                new.rdev = node.rdev();
+                encode_rdev(new.rdev, &mut new.block);
+                new
+            }
+            _ => return Err(EPERM),
+        };
+        let metadata = node.get_metadata();
+        new.metadata.permissions = NodeMode::from_bits_truncate(metadata.permissions.bits());
+        new.metadata.uid = metadata.uid;
+        new.metadata.gid = metadata.gid;
+        drop(node);
+        new.write_inode()?;
+        Ok(self.sb.insert(new))
+    }
+}
+
+fn as_ext2(node: &mut Box<dyn VfsNode>) -> Option<&mut Ext2Node> {
+    let node: &mut dyn core::any::Any = node.as_mut();
+    node.downcast_mut()
+}
+
+impl Drop for Ext2Node {
+    fn drop(&mut self) {
+        let sb = self.sb.clone();
+        let mut nodes = sb.nodes.lock();
+        if nodes.get(&self.ino).is_some_and(|n| n.strong_count() == 0) {
+            nodes.remove(&self.ino);
+        }
+        if !sb.writable || !(self.orphaned() || *self.dirty.get_mut()) {
+            return;
+        }
+        // whoever let go may be holding locks, the disk is written from flush_deferred. it's
+        // queued under the nodes lock so nobody reads the old inode in before that
+        let mut pending = sb.pending.lock();
+        let first = pending.is_empty();
+        pending.insert(self.ino, self.take());
+        drop(pending);
+        drop(nodes);
+        if first {
+            defer(move || sb.flush());
+        }
+    }
+}
+
+impl VfsNode for Ext2Node {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        *self.dirty.get_mut() = true;
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn get_child(&self, name: &str) -> Option<InodeRef> {
+        if !self.is_dir() {
+            return None;
+        }
+        self.with_children(|children| {
+            children
+                .iter()
+                .find(|c| c.name == name)
+                .map(|c| c.inode.clone())
+        })
+    }
+    fn get_children(&self) -> Vec<DirEntry> {
+        if !self.is_dir() {
+            return Vec::new();
+        }
+        self.with_children(|children| {
+            children
+                .iter()
+                .map(|c| DirEntry {
+                    name: c.name.clone(),
+                    inode: c.inode.clone(),
+                })
+                .collect()
+        })
+    }
+    fn create_dir(&mut self, name: &str) -> Result<InodeRef, i64> {
+        self.check_new(name)?;
+        let mut dir = self.create(VfsNodeType::Directory)?;
+        dir.init_dir()?;
+        let inode = self.sb.insert(dir);
+        self.add_link(name, &inode)?;
+        Ok(inode)
+    }
+    fn create_file(&mut self, name: &str) -> Result<InodeRef, i64> {
+        self.check_new(name)?;
+        let inode = self.sb.insert(self.create(VfsNodeType::File)?);
+        self.add_link(name, &inode)?;
+        Ok(inode)
+    }
+    fn link(&mut self, name: &str, inode: InodeRef) -> Result<(), i64> {
+        self.check_new(name)?;
+        let inode = self.adopt(inode)?;
+        self.add_link(name, &inode)
+    }
+    fn unlink(&mut self, name: &str) -> Option<InodeRef> {
+        if !self.sb.writable {
+            return None;
+        }
+        let inode = self.get_child(name)?;
+        self.remove_entry(name).ok()?;
+        self.with_children(|children| children.retain(|c| c.name != name));
+
+        let mut guard = inode.node_mut();
+        let node = as_ext2(&mut guard)?;
+        node.metadata.nlink -= 1;
+        node.metadata.touch_changed();
+        if node.is_dir() {
+            node.linked = false;
+            self.metadata.nlink -= 1;
+        }
+        let _ = node.write_inode();
+        drop(guard);
+        self.metadata.touch_modified();
+        let _ = self.write_inode();
+        Some(inode)
+    }
+    fn size(&self) -> u64 {
+        self.metadata.size
+    }
+    // blocks aren't contiguous in memory
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        if !self.is_file() {
+            return None;
+        }
+        self.metadata.touch_accessed();
+        let n = self
+            .metadata
+            .size
+            .saturating_sub(offset)
+            .min(buf.len() as u64) as usize;
+        let bs = self.sb.block_size;
+        let mut done = 0;
+        while done < n {
+            let pos = offset + done as u64;
+            let within = pos % bs;
+            let len = ((bs - within) as usize).min(n - done);
+            let chunk = &mut buf[done..done + len];
+            match self.bmap(pos / bs).ok()? {
+                0 => chunk.fill(0),
+                block => self.sb.read(self.sb.offset(block) + within, chunk).ok()?,
+            }
+            done += len;
+        }
+        Some(n)
+    }
+    // short if the disk fills up halfway, ENOSPC if nothing fit
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        match self.metadata.type_ {
+            VfsNodeType::File => {}
+            VfsNodeType::Directory => return Err(EISDIR),
+            _ => return Err(EINVAL),
+        }
+        self.sb.check_writable()?;
+        offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= self.sb.max_size())
+            .ok_or(EFBIG)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let bs = self.sb.block_size;
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let within = pos % bs;
+            let len = ((bs - within) as usize).min(buf.len() - done);
+            let block = match self.bmap_alloc(pos / bs, len < bs as usize) {
+                Ok(block) => block,
+                Err(ENOSPC) if done > 0 => break,
+                Err(e) => {
+                    // indirect blocks it got on the way are in the inode
+                    let _ = self.write_inode();
+                    return Err(e);
+                }
+            };
+            self.sb
+                .write(self.sb.offset(block) + within, &buf[done..done + len])?;
+            done += len;
+        }
+        self.metadata.size = self.metadata.size.max(offset + done as u64);
+        self.metadata.touch_modified();
+        self.write_inode()?;
+        Ok(done)
+    }
+    // growing leaves a hole, which reads as zeroes
+    fn truncate(&mut self, len: u64) -> Result<(), i64> {
+        match self.metadata.type_ {
+            VfsNodeType::File => {}
+            VfsNodeType::Directory => return Err(EISDIR),
+            _ => return Err(EINVAL),
+        }
+        self.sb.check_writable()?;
+        if len > self.sb.max_size() {
+            return Err(EFBIG);
+        }
+        let bs = self.sb.block_size;
+        if len < self.metadata.size {
+            // the rest of the last block has to be zeroes if it grows again
+            if !len.is_multiple_of(bs)
+                && let block @ 1.. = self.bmap(len / bs)?
+            {
+                let zeroes = vec![0u8; (bs - len % bs) as usize];
+                self.sb.write(self.sb.offset(block) + len % bs, &zeroes)?;
+            }
+            self.shrink(len.div_ceil(bs))?;
+        }
+        self.metadata.size = len;
+        self.metadata.touch_modified();
+        self.write_inode()
+    }
+    fn readlink(&self) -> Option<&str> {
+        match self.metadata.type_ {
+            VfsNodeType::Symlink => Some(&self.target),
+            _ => None,
+        }
+    }
+    fn rdev(&self) -> u64 {
+        self.rdev
+    }
+    fn blocks(&self) -> u64 {
+        self.sectors as u64
+    }
+    fn statfs(&self) -> Option<FsStats> {
+        Some(self.sb.stats())
+    }
+    fn open(&self) -> Result<Option<Arc<dyn FileObject>>, i64> {
+        match self.metadata.type_ {
+            VfsNodeType::CharDevice | VfsNodeType::BlockDevice => {
+                open_device(&self.metadata.type_, self.rdev).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+    fn sync(&self, _data_only: bool) -> Result<(), i64> {
+        if self.sb.writable {
+            self.write_inode()?;
+            self.sb.write_counts();
+        }
+        cache::sync_device(self.sb.rdev)
+    }
+    fn get_xattr(&self, name: &str) -> Result<Vec<u8>, i64> {
+        let (index, key) = xattr_key(name).ok_or(ENODATA)?;
+        let xattr = self
+            .load_xattrs()?
+            .into_iter()
+            .find(|x| x.index == index && x.name == key)
+            .ok_or(ENODATA)?;
+        match index {
+            ACL_ACCESS | ACL_DEFAULT => acl_from_disk(&xattr.value),
+            _ => Ok(xattr.value),
+        }
+    }
+    fn set_xattr(&mut self, name: &str, value: &[u8]) -> Result<(), i64> {
+        self.sb.check_writable()?;
+        let (index, key) = xattr_key(name).ok_or(EOPNOTSUPP)?;
+        let value = match index {
+            ACL_ACCESS | ACL_DEFAULT => acl_to_disk(value)?,
+            _ => value.to_vec(),
+        };
+        let mut xattrs = self.load_xattrs()?;
+        xattrs.retain(|x| x.index != index || x.name != key);
+        xattrs.push(Xattr {
+            index,
+            name: key.to_vec(),
+            value,
+        });
+        self.store_xattrs(xattrs)
+    }
+    fn remove_xattr(&mut self, name: &str) -> Result<(), i64> {
+        self.sb.check_writable()?;
+        let (index, key) = xattr_key(name).ok_or(ENODATA)?;
+        let mut xattrs = self.load_xattrs()?;
+        let count = xattrs.len();
+        xattrs.retain(|x| x.index != index || x.name != key);
+        if xattrs.len() == count {
+            return Err(ENODATA);
+        }
+        self.store_xattrs(xattrs)
+    }
+    fn list_xattrs(&self) -> Result<Vec<String>, i64> {
+        Ok(self.load_xattrs()?.iter().filter_map(xattr_name).collect())
+    }
+    // inodes that only changed through their metadata
+    fn sync_fs(&self) {
+        if !self.sb.writable {
+            return;
+        }
+        let nodes = self
+            .sb
+            .nodes
+            .lock()
+            .iter()
+            .filter(|&(&ino, _)| ino != self.ino)
+            .filter_map(|(_, node)| node.upgrade())
+            .collect::<Vec<InodeRef>>();
+        for inode in &nodes {
+            let node = inode.node();
+            let any: &dyn core::any::Any = node.as_ref();
+            if let Some(node) = any.downcast_ref::<Ext2Node>()
+                && node.dirty.load(Ordering::Relaxed)
+            {
+                let _ = node.write_inode();
+            }
+        }
+        // the last reference to one of them could be here, which takes the lock again
+        drop(nodes);
+        if self.dirty.load(Ordering::Relaxed) {
+            let _ = self.write_inode();
+        }
+        self.sb.write_counts();
+    }
+}
+
+// revision 0 and 1, without any of ext3's journal or ext4's extents
+pub struct Ext2Fs;
+
+impl FileSystemType for Ext2Fs {
+    fn name(&self) -> &'static str {
+        "ext2"
+    }
+    fn nodev(&self) -> bool {
+        false
+    }
+    fn magic(&self) -> u64 {
+        EXT2_SUPER_MAGIC
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Err(ENOTBLK)
+    }
+    fn mount_device(&self, rdev: u64, flags: MountFlags, data: &str) -> Result<InodeRef, i64> {
+        if !data.is_empty() {
+            return Err(EINVAL);
+        }
+        let sb = Arc::new(Ext2Sb::new(rdev, !flags.contains(MountFlags::RDONLY))?);
+        info!(
+            "ext2: {} blocks of {} bytes in {} groups, {} free",
+            sb.blocks,
+            sb.block_size,
+            sb.groups.len(),
+            sb.counts.lock().free_blocks
+        );
+        let root = sb.get_inode(ROOT_INO)?;
+        let mut guard = root.node_mut();
+        let node = as_ext2(&mut guard).ok_or(EINVAL)?;
+        if !node.is_dir() {
+            return Err(EINVAL);
+        }
+        // its ".." is counted on disk, the mount adds that back
+        node.metadata.nlink -= 1;
+        drop(guard);
+        Ok(root)
+    }
+}
+
+pub static EXT2: Ext2Fs = Ext2Fs;
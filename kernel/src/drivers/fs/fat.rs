@@ -0,0 +1,1344 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// fat12, fat16 and fat32 with vfat long names, everything goes through the buffer cache.
+// there are no inodes on disk, a node is wherever its directory entry is. directories keep
+// the nodes they handed out, so a file is the same inode however it's looked up
+
+use crate::{
+    arch::drivers::time::rtc::RtcTime,
+    device::block::{block_device, cache},
+    utils::{
+        errno::*,
+        mutex::Mutex,
+        time::{days_in_month, is_leap_year},
+    },
+};
+
+use super::*;
+
+pub const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+// read only, hidden, system and volume id all at once is a long name piece
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+// the case of a short name's base and extension, from windows nt
+const NT_LOWER_BASE: u8 = 0x08;
+const NT_LOWER_EXT: u8 = 0x10;
+
+const ENTRY: usize = 32;
+const DELETED: u8 = 0xE5;
+const LONG_LAST: u8 = 0x40;
+// utf-16 units in each long name entry and where they go
+const LONG_CHARS: usize = 13;
+const LONG_OFFSETS: [usize; LONG_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const MAX_NAME: usize = 255;
+// 2MiB of entries, what the spec allows a directory
+const MAX_DIR_ENTRIES: usize = 65536;
+// sizes are 32 bits
+const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+
+const FSINFO_LEAD: u32 = 0x4161_5252;
+const FSINFO_STRUCT: u32 = 0x6141_7272;
+const FSINFO_FREE: u64 = 488;
+const FSINFO_NEXT: u64 = 492;
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+fn put_u16(data: &mut [u8], pos: usize, value: u16) {
+    data[pos..pos + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(data: &mut [u8], pos: usize, value: u32) {
+    data[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// fat timestamps are local time with two second steps, the rtc doesn't know any better
+fn from_dos(date: u16, time: u16) -> u64 {
+    if date == 0 {
+        return 0;
+    }
+    RtcTime {
+        second: (time & 0x1F) as u8 * 2,
+        minute: (time >> 5 & 0x3F) as u8,
+        hour: (time >> 11) as u8,
+        day: (date & 0x1F) as u8,
+        month: (date >> 5 & 0xF) as u8,
+        year: 1980 + (date >> 9),
+        timezone_offset_minutes: 0,
+    }
+    .to_epoch()
+    .unwrap_or_default()
+}
+
+// (date, time), anything before 1980 is 1980
+fn to_dos(epoch: u64) -> (u16, u16) {
+    let (mut days, seconds) = (epoch / 86400, epoch % 86400);
+    let mut year = 1970;
+    loop {
+        let len = if is_leap_year(year) { 366 } else { 365 };
+        if days < len {
+            break;
+        }
+        days -= len;
+        year += 1;
+    }
+    let mut month = 1;
+    while days >= days_in_month(year, month) as u64 {
+        days -= days_in_month(year, month) as u64;
+        month += 1;
+    }
+    if year < 1980 {
+        return (1 << 5 | 1, 0);
+    }
+    let date = (year.min(2107) - 1980) << 9 | (month as u16) << 5 | (days as u16 + 1);
+    let time = (seconds / 3600) << 11 | (seconds / 60 % 60) << 5 | (seconds % 60 / 2);
+    (date, time as u16)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FatKind {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatKind {
+    // the type is only ever decided by how many clusters there are
+    fn from_clusters(clusters: u32) -> Self {
+        match clusters {
+            0..4085 => Self::Fat12,
+            4085..65525 => Self::Fat16,
+            _ => Self::Fat32,
+        }
+    }
+
+    fn end_of_chain(self) -> u32 {
+        match self {
+            Self::Fat12 => 0xFFF,
+            Self::Fat16 => 0xFFFF,
+            Self::Fat32 => 0x0FFF_FFFF,
+        }
+    }
+
+    fn is_end(self, value: u32) -> bool {
+        value >= self.end_of_chain() - 7
+    }
+
+    // where cluster `n`'s entry is in the table and how many bytes to read for it
+    fn entry_pos(self, n: u32) -> (u64, usize) {
+        let n = n as u64;
+        match self {
+            Self::Fat12 => (n + n / 2, 2),
+            Self::Fat16 => (n * 2, 2),
+            Self::Fat32 => (n * 4, 4),
+        }
+    }
+
+    fn decode(self, n: u32, bytes: &[u8]) -> u32 {
+        match self {
+            Self::Fat12 if n % 2 == 1 => (u16_at(bytes, 0) >> 4) as u32,
+            Self::Fat12 => (u16_at(bytes, 0) & 0xFFF) as u32,
+            Self::Fat16 => u16_at(bytes, 0) as u32,
+            Self::Fat32 => u32_at(bytes, 0) & 0x0FFF_FFFF,
+        }
+    }
+
+    // fat12 entries share a byte with their neighbour and fat32 ones keep their top bits
+    fn encode(self, n: u32, bytes: &mut [u8], value: u32) {
+        match self {
+            Self::Fat12 => {
+                let old = u16_at(bytes, 0);
+                let new = if n % 2 == 1 {
+                    old & 0x000F | (value as u16) << 4
+                } else {
+                    old & 0xF000 | value as u16 & 0xFFF
+                };
+                put_u16(bytes, 0, new);
+            }
+            Self::Fat16 => put_u16(bytes, 0, value as u16),
+            Self::Fat32 => {
+                let old = u32_at(bytes, 0);
+                put_u32(bytes, 0, old & 0xF000_0000 | value & 0x0FFF_FFFF);
+            }
+        }
+    }
+}
+
+struct Alloc {
+    // where looking for a free cluster starts
+    next: u32,
+    free: u32,
+    // fsinfo is out of date
+    dirty: bool,
+}
+
+// one per mount, what the boot sector says and the free cluster bookkeeping
+pub struct FatSb {
+    rdev: u64,
+    kind: FatKind,
+    cluster_size: u64,
+    // the table that's read, and every copy that's written
+    fat: u64,
+    fats: Vec<u64>,
+    // fat12 and fat16 have a fixed root directory before the clusters
+    root_start: u64,
+    root_entries: usize,
+    root_cluster: u32,
+    data_start: u64,
+    clusters: u32,
+    fsinfo: Option<u64>,
+    file_mode: NodeMode,
+    dir_mode: NodeMode,
+    alloc: Mutex<Alloc>,
+}
+
+impl core::fmt::Debug for FatSb {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?} on {:#x}", self.kind, self.rdev)
+    }
+}
+
+impl FatSb {
+    fn new(rdev: u64, fmask: i32, dmask: i32) -> Result<Self, i64> {
+        let device = block_device(rdev).ok_or(ENXIO)?;
+        let mut boot = [0u8; 512];
+        if cache::read(rdev, 0, &mut boot)? != boot.len() {
+            return Err(EINVAL);
+        }
+        if u16_at(&boot, 510) != 0xAA55 || !matches!(boot[0], 0xEB | 0xE9) {
+            return Err(EINVAL);
+        }
+
+        let sector_size = u16_at(&boot, 11) as u64;
+        let per_cluster = boot[13] as u64;
+        let reserved = u16_at(&boot, 14) as u64;
+        let copies = boot[16] as u64;
+        let root_entries = u16_at(&boot, 17) as usize;
+        let fat_size = match u16_at(&boot, 22) {
+            0 => u32_at(&boot, 36) as u64,
+            size => size as u64,
+        };
+        let total = match u16_at(&boot, 19) {
+            0 => u32_at(&boot, 32) as u64,
+            total => total as u64,
+        };
+        if !sector_size.is_power_of_two()
+            || !(512..=4096).contains(&sector_size)
+            || !per_cluster.is_power_of_two()
+            || reserved == 0
+            || copies == 0
+            || fat_size == 0
+        {
+            return Err(EINVAL);
+        }
+
+        let root_sectors = (root_entries * ENTRY).div_ceil(sector_size as usize) as u64;
+        let data_sector = reserved + copies * fat_size + root_sectors;
+        let clusters = total.checked_sub(data_sector).ok_or(EINVAL)? / per_cluster;
+        let clusters = clusters.min(0x0FFF_FFF5) as u32;
+        let kind = FatKind::from_clusters(clusters);
+        // the table has to have an entry for every cluster
+        let (last, len) = kind.entry_pos(clusters + 1);
+        if clusters == 0 || last + len as u64 > fat_size * sector_size {
+            return Err(EINVAL);
+        }
+        if total * sector_size > device.capacity() {
+            warn!(
+                "{}: fat filesystem is bigger than the device",
+                device.name()
+            );
+            return Err(EINVAL);
+        }
+
+        let (mut fat, mut fats) = (reserved * sector_size, Vec::new());
+        let mut root_cluster = 0;
+        let mut fsinfo = None;
+        if kind == FatKind::Fat32 {
+            if root_entries != 0 {
+                return Err(EINVAL);
+            }
+            root_cluster = u32_at(&boot, 44);
+            // with mirroring off only the active table is used
+            let flags = u16_at(&boot, 40);
+            if flags & 0x80 != 0 {
+                fat += (flags & 0xF) as u64 * fat_size * sector_size;
+                fats.push(fat);
+            }
+            let sector = u16_at(&boot, 48) as u64;
+            if (1..reserved).contains(&sector) {
+                let mut info = [0u8; 512];
+                cache::read(rdev, sector * sector_size, &mut info)?;
+                if u32_at(&info, 0) == FSINFO_LEAD && u32_at(&info, 484) == FSINFO_STRUCT {
+                    fsinfo = Some(sector * sector_size);
+                }
+            }
+        }
+        if fats.is_empty() {
+            fats = (0..copies)
+                .map(|i| (reserved + i * fat_size) * sector_size)
+                .collect();
+        }
+
+        let sb = Self {
+            rdev,
+            kind,
+            cluster_size: per_cluster * sector_size,
+            fat,
+            fats,
+            root_start: (reserved + copies * fat_size) * sector_size,
+            root_entries,
+            root_cluster,
+            data_start: data_sector * sector_size,
+            clusters,
+            fsinfo,
+            file_mode: NodeMode::from_bits_truncate(0o777 & !fmask),
+            dir_mode: NodeMode::from_bits_truncate(0o777 & !dmask),
+            alloc: Mutex::new(Alloc {
+                next: 2,
+                free: 0,
+                dirty: false,
+            }),
+        };
+        if kind == FatKind::Fat32 && !sb.is_cluster(root_cluster) {
+            return Err(EINVAL);
+        }
+        sb.count_free()?;
+        Ok(sb)
+    }
+
+    // fsinfo's count can't be trusted, so the table is read once through
+    fn count_free(&self) -> Result<(), i64> {
+        let (last, len) = self.kind.entry_pos(self.clusters + 1);
+        let mut table = vec![0u8; last as usize + len];
+        self.read(self.fat, &mut table)?;
+        let mut alloc = self.alloc.lock();
+        for n in 2..self.clusters + 2 {
+            let (pos, _) = self.kind.entry_pos(n);
+            if self.kind.decode(n, &table[pos as usize..]) == 0 {
+                if alloc.free == 0 {
+                    alloc.next = n;
+                }
+                alloc.free += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), i64> {
+        match cache::read(self.rdev, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(EIO),
+        }
+    }
+
+    fn write(&self, offset: u64, buf: &[u8]) -> Result<(), i64> {
+        match cache::write(self.rdev, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(EIO),
+        }
+    }
+
+    fn is_cluster(&self, n: u32) -> bool {
+        (2..self.clusters + 2).contains(&n)
+    }
+
+    fn cluster_offset(&self, n: u32) -> u64 {
+        self.data_start + (n - 2) as u64 * self.cluster_size
+    }
+
+    fn get(&self, n: u32) -> Result<u32, i64> {
+        let (pos, len) = self.kind.entry_pos(n);
+        let mut bytes = [0u8; 4];
+        self.read(self.fat + pos, &mut bytes[..len])?;
+        Ok(self.kind.decode(n, &bytes))
+    }
+
+    fn set(&self, n: u32, value: u32) -> Result<(), i64> {
+        let (pos, len) = self.kind.entry_pos(n);
+        let mut bytes = [0u8; 4];
+        self.read(self.fat + pos, &mut bytes[..len])?;
+        self.kind.encode(n, &mut bytes, value);
+        for &fat in &self.fats {
+            self.write(fat + pos, &bytes[..len])?;
+        }
+        Ok(())
+    }
+
+    // every cluster of the chain starting at `first`
+    fn chain(&self, first: u32) -> Result<Vec<u32>, i64> {
+        let mut chain = Vec::new();
+        let mut n = first;
+        while !self.kind.is_end(n) {
+            // a free or bad cluster in the middle, or a loop
+            if !self.is_cluster(n) || chain.len() >= self.clusters as usize {
+                return Err(EIO);
+            }
+            chain.push(n);
+            n = self.get(n)?;
+        }
+        Ok(chain)
+    }
+
+    // up to `count` free clusters chained together, ENOSPC if there's none at all
+    fn allocate(&self, count: usize) -> Result<Vec<u32>, i64> {
+        let mut alloc = self.alloc.lock();
+        let count = count.min(alloc.free as usize);
+        let mut found = Vec::new();
+        let mut n = alloc.next;
+        for _ in 0..self.clusters {
+            if found.len() == count {
+                break;
+            }
+            if !self.is_cluster(n) {
+                n = 2;
+            }
+            if self.get(n)? == 0 {
+                found.push(n);
+            }
+            n += 1;
+        }
+        if found.is_empty() {
+            return Err(ENOSPC);
+        }
+        // back to front, so it's never a chain with a free cluster in it
+        for (i, &cluster) in found.iter().enumerate().rev() {
+            let next = found
+                .get(i + 1)
+                .copied()
+                .unwrap_or(self.kind.end_of_chain());
+            self.set(cluster, next)?;
+        }
+        alloc.next = n;
+        alloc.free -= found.len() as u32;
+        alloc.dirty = true;
+        Ok(found)
+    }
+
+    fn release(&self, clusters: &[u32]) {
+        let mut alloc = self.alloc.lock();
+        for &n in clusters {
+            if self.set(n, 0).is_ok() {
+                alloc.free += 1;
+                alloc.next = alloc.next.min(n);
+            }
+        }
+        alloc.dirty = true;
+    }
+
+    fn stats(&self) -> FsStats {
+        let free = self.alloc.lock().free as u64 * self.cluster_size / 4096;
+        FsStats {
+            blocks: self.clusters as u64 * self.cluster_size / 4096,
+            bfree: free,
+            bavail: free,
+        }
+    }
+
+    // only a hint for whoever mounts it next
+    fn write_fsinfo(&self) {
+        let mut alloc = self.alloc.lock();
+        let Some(fsinfo) = self.fsinfo.filter(|_| alloc.dirty) else {
+            return;
+        };
+        let _ = self.write(fsinfo + FSINFO_FREE, &alloc.free.to_le_bytes());
+        let _ = self.write(fsinfo + FSINFO_NEXT, &alloc.next.to_le_bytes());
+        alloc.dirty = false;
+    }
+
+    fn mode(&self, dir: bool, read_only: bool) -> NodeMode {
+        let mode = if dir { &self.dir_mode } else { &self.file_mode };
+        let mut mode = NodeMode::from_bits_truncate(mode.bits());
+        if read_only {
+            mode.remove(NodeMode::WRITE);
+        }
+        mode
+    }
+}
+
+// the last node is gone, which is after the umount
+impl Drop for FatSb {
+    fn drop(&mut self) {
+        let alloc = self.alloc.lock();
+        let fsinfo = self
+            .fsinfo
+            .filter(|_| alloc.dirty)
+            .map(|at| (at, alloc.free, alloc.next));
+        drop(alloc);
+        let rdev = self.rdev;
+        // the last node can go anywhere, the fsinfo is written from flush_deferred
+        defer(move || {
+            if let Some((at, free, next)) = fsinfo {
+                let _ = cache::write(rdev, at + FSINFO_FREE, &free.to_le_bytes());
+                let _ = cache::write(rdev, at + FSINFO_NEXT, &next.to_le_bytes());
+            }
+            let _ = cache::sync_device(rdev);
+        });
+    }
+}
+
+// the 8.3 name as it's shown, lowercase where the nt bits say so
+fn short_name(entry: &[u8]) -> String {
+    let part = |bytes: &[u8], lower: bool| {
+        bytes
+            .iter()
+            .take_while(|&&b| b != b' ')
+            .map(|&b| match lower {
+                true => b.to_ascii_lowercase() as char,
+                false => b as char,
+            })
+            .collect::<String>()
+    };
+    let mut base = [0u8; 8];
+    base.copy_from_slice(&entry[..8]);
+    // a real e5 would look deleted
+    if base[0] == 0x05 {
+        base[0] = DELETED;
+    }
+    let base = part(&base, entry[12] & NT_LOWER_BASE != 0);
+    let ext = part(&entry[8..11], entry[12] & NT_LOWER_EXT != 0);
+    match ext.is_empty() {
+        true => base,
+        false => format!("{base}.{ext}"),
+    }
+}
+
+fn checksum(short: &[u8]) -> u8 {
+    short[..11]
+        .iter()
+        .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b))
+}
+
+fn is_short_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || b"!#$%&'()-@^_`{}~".contains(&c)
+}
+
+// the name itself if it's already a valid uppercase 8.3 one, those don't need a long name
+fn exact_short(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+    if !(1..=8).contains(&base.len())
+        || ext.len() > 3
+        || name.ends_with('.')
+        || !base.bytes().chain(ext.bytes()).all(is_short_char)
+    {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short)
+}
+
+// "Long File Name.txt" -> "LONGFI~1TXT", the first number that isn't taken
+fn generate_short(name: &str, taken: &[[u8; 11]]) -> Result<[u8; 11], i64> {
+    let clean = |part: &str| {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| match c.to_ascii_uppercase() {
+                c if c.is_ascii() && is_short_char(c as u8) => c as u8,
+                _ => b'_',
+            })
+            .collect::<Vec<u8>>()
+    };
+    let (base, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (clean(&name[..i]), clean(&name[i + 1..])),
+        _ => (clean(name), Vec::new()),
+    };
+    let mut short = [b' '; 11];
+    let ext = &ext[..ext.len().min(3)];
+    short[8..8 + ext.len()].copy_from_slice(ext);
+    for n in 1..1_000_000 {
+        let tail = format!("~{n}");
+        let keep = base.len().min(8 - tail.len());
+        short[..8].fill(b' ');
+        short[..keep].copy_from_slice(&base[..keep]);
+        short[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+        if !taken.contains(&short) {
+            return Ok(short);
+        }
+    }
+    Err(EEXIST)
+}
+
+// the utf-16 a long name is stored as, EINVAL for what fat can't hold
+fn long_name(name: &str) -> Result<Vec<u16>, i64> {
+    let units = name.encode_utf16().collect::<Vec<u16>>();
+    let bad = |c: char| c < ' ' || "\"*/:<>?\\|".contains(c);
+    if name.is_empty() || name == "." || name == ".." || units.len() > MAX_NAME {
+        return Err(EINVAL);
+    }
+    if name.chars().any(bad) {
+        return Err(EINVAL);
+    }
+    Ok(units)
+}
+
+// pieces of a long name, they come last to first right before their short entry
+#[derive(Default)]
+struct LongName {
+    units: Vec<u16>,
+    // the piece that should come next, 0 once the whole name is in
+    next: u8,
+    checksum: u8,
+    slots: usize,
+}
+
+impl LongName {
+    fn push(&mut self, entry: &[u8]) {
+        let order = entry[0] & !LONG_LAST;
+        if entry[0] & LONG_LAST != 0 {
+            self.units = vec![0xFFFF; order as usize * LONG_CHARS];
+            self.next = order;
+            self.checksum = entry[13];
+            self.slots = 0;
+        }
+        if order == 0 || order != self.next || entry[13] != self.checksum {
+            self.reset();
+            return;
+        }
+        let start = (order as usize - 1) * LONG_CHARS;
+        for (i, &pos) in LONG_OFFSETS.iter().enumerate() {
+            self.units[start + i] = u16_at(entry, pos);
+        }
+        self.next -= 1;
+        self.slots += 1;
+    }
+
+    fn reset(&mut self) {
+        self.units.clear();
+        self.slots = 0;
+    }
+
+    // the name and how many entries it took, if it belongs to the short entry
+    fn take(&mut self, short: &[u8]) -> Option<(String, usize)> {
+        let slots = core::mem::take(&mut self.slots);
+        let units = core::mem::take(&mut self.units);
+        if slots == 0 || self.next != 0 || checksum(short) != self.checksum {
+            return None;
+        }
+        let units = units.into_iter().take_while(|&c| c != 0 && c != 0xFFFF);
+        let name = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>();
+        (!name.is_empty()).then_some((name, slots))
+    }
+}
+
+// where the node's short entry is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Root,
+    // byte offset on the device
+    Entry(u64),
+    // the clusters go with the last reference
+    Unlinked,
+}
+
+struct Child {
+    name: String,
+    // it can be looked up by that too
+    short: String,
+    inode: InodeRef,
+    // of the short entry, the long name's ones come right before it
+    index: usize,
+    slots: usize,
+}
+
+impl Child {
+    // fat doesn't care about case
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.short.eq_ignore_ascii_case(name)
+    }
+}
+
+// a file or directory, whatever's in its entry is kept here and written back as it changes
+pub struct FatNode {
+    sb: Arc<FatSb>,
+    location: Location,
+    attr: u8,
+    clusters: Vec<u32>,
+    // directories only, read in the first time someone looks
+    children: Spin<Option<Vec<Child>>>,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for FatNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:?}, {} clusters, {:?}",
+            self.location,
+            self.clusters.len(),
+            self.metadata
+        )
+    }
+}
+
+impl FatNode {
+    fn new(sb: Arc<FatSb>, attr: u8) -> Self {
+        let dir = attr & ATTR_DIRECTORY != 0;
+        let mut metadata = VfsNodeMetadata::new(match dir {
+            true => VfsNodeType::Directory,
+            false => VfsNodeType::File,
+        });
+        metadata.permissions = sb.mode(dir, false);
+        // a directory's "."
+        metadata.nlink = dir as u64;
+        Self {
+            sb,
+            location: Location::Unlinked,
+            attr,
+            clusters: Vec::new(),
+            children: Spin::new(dir.then(Vec::new)),
+            metadata,
+        }
+    }
+
+    fn root(sb: Arc<FatSb>) -> Result<Self, i64> {
+        let mut root = Self::new(sb, ATTR_DIRECTORY);
+        root.location = Location::Root;
+        root.children = Spin::new(None);
+        if root.sb.kind == FatKind::Fat32 {
+            root.clusters = root.sb.chain(root.sb.root_cluster)?;
+        }
+        root.metadata.size = root.capacity();
+        Ok(root)
+    }
+
+    fn from_entry(sb: &Arc<FatSb>, entry: &[u8], location: u64) -> Result<Self, i64> {
+        let attr = entry[11];
+        let mut first = u16_at(entry, 26) as u32;
+        if sb.kind == FatKind::Fat32 {
+            first |= (u16_at(entry, 20) as u32) << 16;
+        }
+        let mut node = Self::new(sb.clone(), attr);
+        node.location = Location::Entry(location);
+        if first != 0 {
+            node.clusters = sb.chain(first)?;
+        }
+        let read_only = attr & ATTR_READ_ONLY != 0;
+        let metadata = &mut node.metadata;
+        metadata.permissions = sb.mode(attr & ATTR_DIRECTORY != 0, read_only);
+        metadata.created_at = from_dos(u16_at(entry, 16), u16_at(entry, 14));
+        metadata.modified_at = from_dos(u16_at(entry, 24), u16_at(entry, 22));
+        metadata.changed_at = metadata.modified_at;
+        metadata.set_accessed_at(from_dos(u16_at(entry, 18), 0));
+        metadata.nlink += 1;
+        if node.is_dir() {
+            node.children = Spin::new(None);
+            node.metadata.size = node.capacity();
+        } else {
+            // a chain shorter than the size is broken, don't read past it
+            node.metadata.size = (u32_at(entry, 28) as u64).min(node.capacity());
+        }
+        Ok(node)
+    }
+
+    fn first(&self) -> u32 {
+        self.clusters.first().copied().unwrap_or(0)
+    }
+
+    fn fixed_root(&self) -> bool {
+        self.location == Location::Root && self.sb.kind != FatKind::Fat32
+    }
+
+    // what a subdirectory's ".." says, the root is always 0
+    fn dotdot(&self) -> u32 {
+        match self.location {
+            Location::Root => 0,
+            _ => self.first(),
+        }
+    }
+
+    fn capacity(&self) -> u64 {
+        match self.fixed_root() {
+            true => (self.sb.root_entries * ENTRY) as u64,
+            false => self.clusters.len() as u64 * self.sb.cluster_size,
+        }
+    }
+
+    // where byte `pos` is on the device and how much is contiguous from there
+    fn locate(&self, pos: u64) -> Result<(u64, u64), i64> {
+        if self.fixed_root() {
+            let len = self.capacity();
+            return match pos < len {
+                true => Ok((self.sb.root_start + pos, len - pos)),
+                false => Err(EIO),
+            };
+        }
+        let size = self.sb.cluster_size;
+        let &cluster = self.clusters.get((pos / size) as usize).ok_or(EIO)?;
+        Ok((
+            self.sb.cluster_offset(cluster) + pos % size,
+            size - pos % size,
+        ))
+    }
+
+    fn read_data(&self, pos: u64, buf: &mut [u8]) -> Result<(), i64> {
+        let mut done = 0;
+        while done < buf.len() {
+            let (offset, run) = self.locate(pos + done as u64)?;
+            let n = (run as usize).min(buf.len() - done);
+            self.sb.read(offset, &mut buf[done..done + n])?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn write_data(&self, pos: u64, buf: &[u8]) -> Result<(), i64> {
+        let mut done = 0;
+        while done < buf.len() {
+            let (offset, run) = self.locate(pos + done as u64)?;
+            let n = (run as usize).min(buf.len() - done);
+            self.sb.write(offset, &buf[done..done + n])?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    // there are no holes, whatever's past the old end has to read as zeroes
+    fn zero(&self, from: u64, to: u64) -> Result<(), i64> {
+        let zeroes = [0u8; 4096];
+        let mut pos = from;
+        while pos < to {
+            let n = (to - pos).min(zeroes.len() as u64);
+            self.write_data(pos, &zeroes[..n as usize])?;
+            pos += n;
+        }
+        Ok(())
+    }
+
+    // adds up to `count` clusters to the end, how many it got
+    fn extend(&mut self, count: usize, zero: bool) -> Result<usize, i64> {
+        let new = self.sb.allocate(count)?;
+        if zero {
+            let zeroes = vec![0u8; self.sb.cluster_size as usize];
+            for &n in &new {
+                self.sb.write(self.sb.cluster_offset(n), &zeroes)?;
+            }
+        }
+        if let Some(&last) = self.clusters.last() {
+            self.sb.set(last, new[0])?;
+        }
+        self.clusters.extend(&new);
+        Ok(new.len())
+    }
+
+    // as many clusters as `len` bytes need, fewer if the disk fills up
+    fn reserve(&mut self, len: u64) -> Result<(), i64> {
+        let needed = len.div_ceil(self.sb.cluster_size) as usize;
+        if needed > self.clusters.len() {
+            self.extend(needed - self.clusters.len(), false)?;
+        }
+        Ok(())
+    }
+
+    fn shrink(&mut self, len: u64) -> Result<(), i64> {
+        let keep = len.div_ceil(self.sb.cluster_size) as usize;
+        if keep >= self.clusters.len() {
+            return Ok(());
+        }
+        let dropped = self.clusters.split_off(keep);
+        if let Some(&last) = self.clusters.last() {
+            self.sb.set(last, self.sb.kind.end_of_chain())?;
+        }
+        self.sb.release(&dropped);
+        Ok(())
+    }
+
+    // everything but the name
+    fn fill_entry(&self, entry: &mut [u8]) {
+        let mut attr = self.attr & !ATTR_READ_ONLY;
+        if !self.metadata.permissions.contains(NodeMode::S_IWUSR) {
+            attr |= ATTR_READ_ONLY;
+        }
+        entry[11] = attr;
+        let first = self.first();
+        put_u16(entry, 20, (first >> 16) as u16);
+        put_u16(entry, 26, first as u16);
+        let (date, time) = to_dos(self.metadata.created_at);
+        put_u16(entry, 14, time);
+        put_u16(entry, 16, date);
+        put_u16(entry, 18, to_dos(self.metadata.accessed_at()).0);
+        let (date, time) = to_dos(self.metadata.modified_at);
+        put_u16(entry, 22, time);
+        put_u16(entry, 24, date);
+        let size = if self.is_dir() { 0 } else { self.metadata.size };
+        put_u32(entry, 28, size as u32);
+    }
+
+    fn write_entry(&self) -> Result<(), i64> {
+        let Location::Entry(pos) = self.location else {
+            return Ok(());
+        };
+        let mut entry = [0u8; ENTRY];
+        self.sb.read(pos, &mut entry)?;
+        self.fill_entry(&mut entry);
+        self.sb.write(pos, &entry)
+    }
+
+    // a moved directory's ".." follows it
+    fn set_dotdot(&self, cluster: u32) -> Result<(), i64> {
+        let mut entry = [0u8; ENTRY];
+        self.read_data(ENTRY as u64, &mut entry)?;
+        if &entry[..11] != b"..         " {
+            return Ok(());
+        }
+        put_u16(&mut entry, 20, (cluster >> 16) as u16);
+        put_u16(&mut entry, 26, cluster as u16);
+        self.write_data(ENTRY as u64, &entry)
+    }
+
+    fn raw_entries(&self) -> Result<Vec<u8>, i64> {
+        let mut raw = vec![0u8; self.capacity() as usize];
+        self.read_data(0, &mut raw)?;
+        Ok(raw)
+    }
+
+    fn scan(&self) -> Result<Vec<Child>, i64> {
+        let raw = self.raw_entries()?;
+        let mut children = Vec::new();
+        let mut long = LongName::default();
+        for (index, entry) in raw.as_chunks::<ENTRY>().0.iter().enumerate() {
+            match entry[0] {
+                0 => break,
+                DELETED => {
+                    long.reset();
+                    continue;
+                }
+                _ => {}
+            }
+            if entry[11] & 0x3F == ATTR_LONG_NAME {
+                long.push(entry);
+                continue;
+            }
+            let name = long.take(entry);
+            // "." and ".." are the only short names starting with a dot
+            if entry[11] & ATTR_VOLUME_ID != 0 || entry[0] == b'.' {
+                continue;
+            }
+            let short = short_name(entry);
+            let (name, slots) = name.unwrap_or((short.clone(), 0));
+            let location = self.locate((index * ENTRY) as u64)?.0;
+            let node = match FatNode::from_entry(&self.sb, entry, location) {
+                Ok(node) => node,
+                Err(_) => {
+                    warn!("fat: {name} has a broken cluster chain, skipping it");
+                    continue;
+                }
+            };
+            children.push(Child {
+                name,
+                short,
+                inode: Inode::new(node),
+                index,
+                slots: slots + 1,
+            });
+        }
+        Ok(children)
+    }
+
+    // the entries are read in outside the lock, someone who got there first wins
+    fn with_children<T>(&self, f: impl FnOnce(&mut Vec<Child>) -> T) -> T {
+        if self.children.lock().is_none() {
+            match self.scan() {
+                Ok(scanned) => {
+                    let mut children = self.children.lock();
+                    if children.is_none() {
+                        *children = Some(scanned);
+                    }
+                }
+                Err(e) => {
+                    warn!("fat: couldn't read a directory, errno {e}");
+                    return f(&mut Vec::new());
+                }
+            }
+        }
+        f(self.children.lock().as_mut().unwrap())
+    }
+
+    // a directory with room for `slots` more entries at the end, where they start
+    fn grow(&mut self, start: usize, slots: usize) -> Result<usize, i64> {
+        if self.fixed_root() || start + slots > MAX_DIR_ENTRIES {
+            return Err(ENOSPC);
+        }
+        let bytes = ((start + slots) * ENTRY) as u64;
+        let needed = bytes.div_ceil(self.sb.cluster_size) as usize - self.clusters.len();
+        if self.extend(needed, true)? < needed {
+            return Err(ENOSPC);
+        }
+        self.metadata.size = self.capacity();
+        Ok(start)
+    }
+
+    // writes entries naming `inode`, which has to be one of ours without a name yet
+    fn add_entry(&mut self, name: &str, inode: &InodeRef) -> Result<(), i64> {
+        let units = long_name(name)?;
+        if self.with_children(|c| c.iter().any(|c| c.matches(name))) {
+            return Err(EEXIST);
+        }
+        let raw = self.raw_entries()?;
+        let taken = raw
+            .as_chunks::<ENTRY>()
+            .0
+            .iter()
+            .take_while(|e| e[0] != 0)
+            .filter(|e| e[0] != DELETED && e[11] & 0x3F != ATTR_LONG_NAME)
+            .map(|e| e[..11].try_into().unwrap())
+            .collect::<Vec<[u8; 11]>>();
+        let (short, long) = match exact_short(name) {
+            Some(short) => (short, 0),
+            None => (
+                generate_short(name, &taken)?,
+                units.len().div_ceil(LONG_CHARS),
+            ),
+        };
+        let slots = long + 1;
+        let start = match free_run(&raw, slots) {
+            Ok(start) => start,
+            Err(end) => self.grow(end, slots)?,
+        };
+
+        let mut entries = vec![0u8; slots * ENTRY];
+        let sum = checksum(&short);
+        for (i, entry) in entries
+            .as_chunks_mut::<ENTRY>()
+            .0
+            .iter_mut()
+            .take(long)
+            .enumerate()
+        {
+            let order = long - i;
+            entry[0] = order as u8 | if i == 0 { LONG_LAST } else { 0 };
+            entry[11] = ATTR_LONG_NAME;
+            entry[13] = sum;
+            // a nul after the name and 0xffff padding the rest
+            for (j, &pos) in LONG_OFFSETS.iter().enumerate() {
+                let unit = match (order - 1) * LONG_CHARS + j {
+                    k if k < units.len() => units[k],
+                    k if k == units.len() => 0,
+                    _ => 0xFFFF,
+                };
+                put_u16(entry, pos, unit);
+            }
+        }
+        let entry = &mut entries[long * ENTRY..];
+        entry[..11].copy_from_slice(&short);
+        let display = short_name(entry);
+
+        let index = start + long;
+        let location = self.locate((index * ENTRY) as u64)?.0;
+        let mut guard = inode.node_mut();
+        let node = as_fat(&mut guard).ok_or(EXDEV)?;
+        if !Arc::ptr_eq(&node.sb, &self.sb) {
+            return Err(EXDEV);
+        }
+        // no hard links on fat
+        if node.location != Location::Unlinked {
+            return Err(EPERM);
+        }
+        node.fill_entry(entry);
+        self.write_data((start * ENTRY) as u64, &entries)?;
+        node.location = Location::Entry(location);
+        node.metadata.nlink += 1;
+        node.metadata.touch_changed();
+        if node.is_dir() {
+            node.set_dotdot(self.dotdot())?;
+        }
+        drop(guard);
+
+        self.with_children(|children| {
+            children.push(Child {
+                name: name.to_string(),
+                short: display,
+                inode: inode.clone(),
+                index,
+                slots,
+            })
+        });
+        self.metadata.touch_modified();
+        self.write_entry()
+    }
+}
+
+// the first `slots` free entries in a row, or where the free ones at the end start
+fn free_run(raw: &[u8], slots: usize) -> Result<usize, usize> {
+    let mut run = 0;
+    let entries = raw.len() / ENTRY;
+    for (i, entry) in raw.as_chunks::<ENTRY>().0.iter().enumerate() {
+        // everything after the end marker is free too
+        if entry[0] == 0 && i + slots <= entries {
+            return Ok(i - run);
+        }
+        if entry[0] == 0 {
+            return Err(i - run);
+        }
+        run = if entry[0] == DELETED { run + 1 } else { 0 };
+        if run == slots {
+            return Ok(i + 1 - run);
+        }
+    }
+    Err(entries - run)
+}
+
+fn as_fat(node: &mut Box<dyn VfsNode>) -> Option<&mut FatNode> {
+    let node: &mut dyn core::any::Any = node.as_mut();
+    node.downcast_mut()
+}
+
+impl Drop for FatNode {
+    fn drop(&mut self) {
+        if self.location == Location::Unlinked && !self.clusters.is_empty() {
+            // freeing them writes the fat, which waits until no locks are held
+            let (sb, clusters) = (self.sb.clone(), core::mem::take(&mut self.clusters));
+            defer(move || sb.release(&clusters));
+        }
+    }
+}
+
+impl VfsNode for FatNode {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn get_child(&self, name: &str) -> Option<InodeRef> {
+        if !self.is_dir() {
+            return None;
+        }
+        self.with_children(|children| {
+            children
+                .iter()
+                .find(|c| c.matches(name))
+                .map(|c| c.inode.clone())
+        })
+    }
+    fn get_children(&self) -> Vec<DirEntry> {
+        if !self.is_dir() {
+            return Vec::new();
+        }
+        self.with_children(|children| {
+            children
+                .iter()
+                .map(|c| DirEntry {
+                    name: c.name.clone(),
+                    inode: c.inode.clone(),
+                })
+                .collect()
+        })
+    }
+    fn create_dir(&mut self, name: &str) -> Result<InodeRef, i64> {
+        let mut dir = FatNode::new(self.sb.clone(), ATTR_DIRECTORY);
+        dir.extend(1, true)?;
+        dir.metadata.size = dir.capacity();
+        // ".." gets its cluster once it's linked
+        let mut dots = [0u8; 2 * ENTRY];
+        dots[..11].copy_from_slice(b".          ");
+        dots[ENTRY..ENTRY + 11].copy_from_slice(b"..         ");
+        dir.fill_entry(&mut dots[..ENTRY]);
+        dir.fill_entry(&mut dots[ENTRY..]);
+        dir.write_data(0, &dots)?;
+        let inode = Inode::new(dir);
+        self.add_entry(name, &inode)?;
+        Ok(inode)
+    }
+    fn create_file(&mut self, name: &str) -> Result<InodeRef, i64> {
+        let inode = Inode::new(FatNode::new(self.sb.clone(), ATTR_ARCHIVE));
+        self.add_entry(name, &inode)?;
+        Ok(inode)
+    }
+    fn link(&mut self, name: &str, inode: InodeRef) -> Result<(), i64> {
+        if !self.is_dir() {
+            return Err(ENOTDIR);
+        }
+        self.add_entry(name, &inode)
+    }
+    fn unlink(&mut self, name: &str) -> Option<InodeRef> {
+        if !self.is_dir() {
+            return None;
+        }
+        let (index, slots) = self.with_children(|children| {
+            let child = children.iter().find(|c| c.matches(name))?;
+            Some((child.index, child.slots))
+        })?;
+        for i in index + 1 - slots..=index {
+            let (pos, _) = self.locate((i * ENTRY) as u64).ok()?;
+            self.sb.write(pos, &[DELETED]).ok()?;
+        }
+        let child = self.with_children(|children| {
+            let pos = children.iter().position(|c| c.index == index)?;
+            Some(children.remove(pos))
+        })?;
+
+        let mut guard = child.inode.node_mut();
+        if let Some(node) = as_fat(&mut guard) {
+            node.location = Location::Unlinked;
+        }
+        guard.get_metadata_mut().nlink -= 1;
+        guard.get_metadata_mut().touch_changed();
+        drop(guard);
+        self.metadata.touch_modified();
+        let _ = self.write_entry();
+        Some(child.inode)
+    }
+    fn size(&self) -> u64 {
+        self.metadata.size
+    }
+    // clusters aren't contiguous in memory
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        if self.is_dir() {
+            return None;
+        }
+        self.metadata.touch_accessed();
+        let n = self
+            .metadata
+            .size
+            .saturating_sub(offset)
+            .min(buf.len() as u64) as usize;
+        self.read_data(offset, &mut buf[..n]).ok()?;
+        Some(n)
+    }
+    // short if the disk fills up halfway, ENOSPC if nothing fit
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        if self.is_dir() {
+            return Err(EISDIR);
+        }
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or(EFBIG)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Err(e) = self.reserve(end)
+            && e != ENOSPC
+        {
+            return Err(e);
+        }
+        let end = end.min(self.capacity());
+        if end <= offset {
+            return Err(ENOSPC);
+        }
+        let size = self.metadata.size;
+        if offset > size {
+            self.zero(size, offset)?;
+        }
+        let n = (end - offset) as usize;
+        self.write_data(offset, &buf[..n])?;
+        self.metadata.size = size.max(end);
+        self.metadata.touch_modified();
+        self.write_entry()?;
+        Ok(n)
+    }
+    fn truncate(&mut self, len: u64) -> Result<(), i64> {
+        if self.is_dir() {
+            return Err(EISDIR);
+        }
+        if len > MAX_FILE_SIZE {
+            return Err(EFBIG);
+        }
+        let size = self.metadata.size;
+        if len > size {
+            self.reserve(len)?;
+            if self.capacity() < len {
+                self.shrink(size)?;
+                return Err(ENOSPC);
+            }
+            self.zero(size, len)?;
+        } else {
+            self.shrink(len)?;
+        }
+        self.metadata.size = len;
+        self.metadata.touch_modified();
+        self.write_entry()
+    }
+    fn blocks(&self) -> u64 {
+        self.clusters.len() as u64 * self.sb.cluster_size / 512
+    }
+    fn statfs(&self) -> Option<FsStats> {
+        Some(self.sb.stats())
+    }
+    fn sync(&self, _data_only: bool) -> Result<(), i64> {
+        // the size is in the entry, so it goes out even for fdatasync
+        self.write_entry()?;
+        self.sb.write_fsinfo();
+        cache::sync_device(self.sb.rdev)
+    }
+    // a directory entry has no room for them
+    fn set_xattr(&mut self, _name: &str, _value: &[u8]) -> Result<(), i64> {
+        Err(EOPNOTSUPP)
+    }
+}
+
+// "umask=022,dmask=077", octal like on linux. fat has no modes of its own
+fn parse_options(data: &str) -> Result<(i32, i32), i64> {
+    let (mut fmask, mut dmask) = (0o022, 0o022);
+    for option in data.split(',').filter(|o| !o.is_empty()) {
+        let (key, value) = option.split_once('=').ok_or(EINVAL)?;
+        let mask = i32::from_str_radix(value, 8).map_err(|_| EINVAL)? & 0o777;
+        match key {
+            "umask" => (fmask, dmask) = (mask, mask),
+            "fmask" => fmask = mask,
+            "dmask" => dmask = mask,
+            _ => return Err(EINVAL),
+        }
+    }
+    Ok((fmask, dmask))
+}
+
+// fat12, fat16 and fat32, which one is up to the disk
+pub struct VfatFs;
+
+impl FileSystemType for VfatFs {
+    fn name(&self) -> &'static str {
+        "vfat"
+    }
+    fn nodev(&self) -> bool {
+        false
+    }
+    fn magic(&self) -> u64 {
+        MSDOS_SUPER_MAGIC
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Err(ENOTBLK)
+    }
+    fn mount_device(&self, rdev: u64, _flags: MountFlags, data: &str) -> Result<InodeRef, i64> {
+        let (fmask, dmask) = parse_options(data)?;
+        let sb = Arc::new(FatSb::new(rdev, fmask, dmask)?);
+        info!(
+            "vfat: {:?}, {} clusters of {} bytes, {} free",
+            sb.kind,
+            sb.clusters,
+            sb.cluster_size,
+            sb.alloc.lock().free
+        );
+        Ok(Inode::new(FatNode::root(sb)?))
+    }
+}
+
+pub static VFAT: VfatFs = VfatFs;
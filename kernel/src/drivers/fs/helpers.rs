@@ -3,71 +3,59 @@
     Released under EUPL 1.2 License
 */
 
-use core::mem::ManuallyDrop;
-
-use crate::utils::{
-    asm::{int_status, toggle_ints},
-    spinlock::SpinGuard,
-};
-
 use super::*;
 
-pub struct VfsGuard {
-    guard: ManuallyDrop<SpinGuard<'static, Vfs>>,
-    int_status: bool,
+// the mount table. paths are looked up under a read guard, only mount and umount need to
+// write. interrupts stay on, lookups wait on inode locks that sleeping threads can hold
+pub fn get_vfs() -> RwLockReadGuard<'static, Vfs> {
+    VFS.read()
 }
 
-impl core::ops::Deref for VfsGuard {
-    type Target = Vfs;
-    fn deref(&self) -> &Vfs {
-        &self.guard
-    }
+pub fn get_vfs_mut() -> RwLockWriteGuard<'static, Vfs> {
+    VFS.write()
 }
 
-impl core::ops::DerefMut for VfsGuard {
-    fn deref_mut(&mut self) -> &mut Vfs {
-        &mut self.guard
-    }
-}
+// disk writes from drops. the last reference to a node or a superblock can go with inode
+// locks or a process lock held, so what it still has to write waits here
+static DEFERRED: Spin<Vec<Box<dyn FnOnce() + Send>>> = Spin::new(Vec::new());
 
-impl Drop for VfsGuard {
-    fn drop(&mut self) {
-        unsafe { ManuallyDrop::drop(&mut self.guard) };
-        if self.int_status {
-            toggle_ints(true);
-        }
-    }
+pub fn defer(work: impl FnOnce() + Send + 'static) {
+    DEFERRED.lock().push(Box::new(work));
 }
 
-pub fn get_vfs() -> VfsGuard {
-    let ints_were_enabled = int_status();
-    if ints_were_enabled {
-        toggle_ints(false);
-    }
-    let guard = VFS.lock();
-    VfsGuard {
-        guard: ManuallyDrop::new(guard),
-        int_status: ints_were_enabled,
+// runs with nothing locked, after every syscall and before sync and umount. the work can
+// drop the last reference to something else, which defers more
+pub fn flush_deferred() {
+    loop {
+        let work = core::mem::take(&mut *DEFERRED.lock());
+        if work.is_empty() {
+            return;
+        }
+        for work in work {
+            work();
+        }
     }
 }
 
 pub fn ls(path: Path) -> Vec<alloc::string::String> {
     get_vfs()
-        .resolve_path(path)
+        .resolve(path)
         .unwrap()
+        .node()
         .get_children()
         .iter()
-        .map(|node| alloc::string::String::from(node.get_name()))
+        .map(|entry| entry.name.clone())
         .collect()
 }
 
 pub fn cat(path: Path) -> Option<alloc::string::String> {
     get_vfs()
-        .resolve_path(path)?
-        .read()
-        .map(|data| alloc::string::String::from(str::from_utf8(data).unwrap()))
+        .resolve(path)?
+        .node()
+        .read_all()
+        .map(|data| alloc::string::String::from_utf8_lossy(&data).into_owned())
 }
 
 pub fn rm(path: Path, name: &str) {
-    get_vfs().resolve_path_mut(path).unwrap().remove_child(name);
+    get_vfs().resolve(path).unwrap().node_mut().unlink(name);
 }
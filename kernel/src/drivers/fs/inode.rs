@@ -0,0 +1,62 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::*;
+
+// st_ino, never reused
+static NEXT_INO: AtomicU64 = AtomicU64::new(1);
+
+// a node and its number. directory entries and open files hold these, so a node
+// stays alive until the last name is unlinked and the last descriptor is closed
+pub struct Inode {
+    pub ino: u64,
+    // st_dev of the mount it was last looked up through, 0 until then
+    dev: AtomicU64,
+    // each node has its own lock, so i/o on unrelated files doesn't wait on each other.
+    // when two are held it's parent before child
+    node: RwLock<Box<dyn VfsNode>>,
+}
+
+pub type InodeRef = Arc<Inode>;
+pub type NodeGuard<'a> = RwLockReadGuard<'a, Box<dyn VfsNode>>;
+pub type NodeGuardMut<'a> = RwLockWriteGuard<'a, Box<dyn VfsNode>>;
+
+impl Inode {
+    pub fn new(node: impl VfsNode + 'static) -> InodeRef {
+        Arc::new(Self {
+            ino: NEXT_INO.fetch_add(1, Ordering::Relaxed),
+            dev: AtomicU64::new(0),
+            node: RwLock::new(Box::new(node)),
+        })
+    }
+
+    pub fn node(&self) -> NodeGuard<'_> {
+        self.node.read()
+    }
+
+    pub fn node_mut(&self) -> NodeGuardMut<'_> {
+        self.node.write()
+    }
+
+    pub fn dev(&self) -> u64 {
+        self.dev.load(Ordering::Relaxed)
+    }
+
+    pub fn set_dev(&self, dev: u64) {
+        self.dev.store(dev, Ordering::Relaxed);
+    }
+
+    pub fn nlink(&self) -> u64 {
+        self.node().get_metadata().nlink
+    }
+}
+
+impl core::fmt::Debug for Inode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "#{} {:?}", self.ino, self.node)
+    }
+}
@@ -0,0 +1,718 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// iso9660 cds, read only. rock ridge gives the unix names, modes, owners, times, symlinks
+// and devices, joliet at least long names for discs made without it. plain 8.3 names are
+// lowercased and lose their ";1" like on linux. directories hold on to what they've handed
+// out like on fat
+
+use crate::{
+    arch::drivers::time::rtc::RtcTime,
+    device::block::{block_device, cache},
+    utils::errno::*,
+};
+
+use super::*;
+
+pub const ISOFS_SUPER_MAGIC: u64 = 0x9660;
+
+// the logical block size, nothing else is ever used
+const BLOCK: u64 = 2048;
+// after the system area
+const FIRST_DESCRIPTOR: u64 = 16;
+const MAX_DESCRIPTORS: u64 = 64;
+const VD_PRIMARY: u8 = 1;
+const VD_SUPPLEMENTARY: u8 = 2;
+const VD_END: u8 = 255;
+// the root's directory record in a volume descriptor
+const VD_ROOT: usize = 156;
+
+const FLAG_DIRECTORY: u8 = 0x02;
+// the file goes on in the next record, which has the same name
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+// a directory record up to its name
+const RECORD: usize = 33;
+// bigger than anything mkisofs writes, so a broken size isn't read in whole
+const MAX_DIR_SIZE: u64 = 16 << 20;
+// continuation areas followed for one record, there's no other way out of a loop
+const MAX_CONTINUATIONS: usize = 16;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFBLK: u32 = 0o060000;
+
+// NM flags, the name of "." and ".." isn't wanted
+const NM_CURRENT: u8 = 0x2;
+const NM_PARENT: u8 = 0x4;
+// SL component flags
+const SL_CONTINUE: u8 = 0x1;
+const SL_CURRENT: u8 = 0x2;
+const SL_PARENT: u8 = 0x4;
+const SL_ROOT: u8 = 0x8;
+// TF, the 17 byte timestamps instead of the 7 byte ones
+const TF_LONG: u8 = 0x80;
+
+// both-endian fields have the little endian half first
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+// the offset is from gmt in 15 minute steps
+fn to_epoch(year: u16, date: [u8; 5], offset: i8) -> u64 {
+    let [month, day, hour, minute, second] = date;
+    let local = RtcTime {
+        second,
+        minute,
+        hour,
+        day,
+        month,
+        year,
+        timezone_offset_minutes: 0,
+    }
+    .to_epoch()
+    .unwrap_or_default();
+    local.saturating_add_signed(-(offset as i64) * 15 * 60)
+}
+
+// the 7 byte form directory records use, years since 1900
+fn from_short(stamp: &[u8]) -> u64 {
+    let date = [stamp[1], stamp[2], stamp[3], stamp[4], stamp[5]];
+    to_epoch(1900 + stamp[0] as u16, date, stamp[6] as i8)
+}
+
+// the 17 byte form, "YYYYMMDDhhmmsscc" in ascii and the offset
+fn from_long(stamp: &[u8]) -> u64 {
+    let number = |range: core::ops::Range<usize>| {
+        core::str::from_utf8(&stamp[range])
+            .ok()
+            .and_then(|digits| digits.parse::<u16>().ok())
+            .unwrap_or_default()
+    };
+    let date = [
+        number(4..6),
+        number(6..8),
+        number(8..10),
+        number(10..12),
+        number(12..14),
+    ];
+    to_epoch(number(0..4), date.map(|n| n as u8), stamp[16] as i8)
+}
+
+// where a record's data starts on the device, past its extended attributes, and how long
+fn extent(record: &[u8]) -> (u64, u64) {
+    let start = (u32_at(record, 2) as u64 + record[1] as u64) * BLOCK;
+    (start, u32_at(record, 10) as u64)
+}
+
+fn record_name(record: &[u8]) -> &[u8] {
+    &record[RECORD..RECORD + record[32] as usize]
+}
+
+// the rest of the record after the name, padded to an even offset
+fn system_use(record: &[u8]) -> &[u8] {
+    let start = (RECORD + record[32] as usize).next_multiple_of(2);
+    record.get(start..).unwrap_or(&[])
+}
+
+// "." and ".." are a single 0 and 1
+fn is_dot(record: &[u8]) -> bool {
+    matches!(record_name(record), [0 | 1])
+}
+
+// the descriptor's escape sequences say ucs-2 at one of the three levels
+fn is_joliet(descriptor: &[u8]) -> bool {
+    descriptor[88..90] == *b"%/" && matches!(descriptor[90], b'@' | b'C' | b'E')
+}
+
+// a rock ridge symlink comes in components, any of which can go on in the next one
+#[derive(Default)]
+struct Link {
+    target: String,
+    joined: bool,
+}
+
+impl Link {
+    fn push(&mut self, mut components: &[u8]) {
+        while let [flags, len, rest @ ..] = components {
+            let Some(content) = rest.get(..*len as usize) else {
+                break;
+            };
+            if !self.joined && !self.target.is_empty() && !self.target.ends_with('/') {
+                self.target.push('/');
+            }
+            match flags & (SL_CURRENT | SL_PARENT | SL_ROOT) {
+                SL_CURRENT => self.target.push('.'),
+                SL_PARENT => self.target.push_str(".."),
+                SL_ROOT => self.target.push('/'),
+                _ => self.target.push_str(&String::from_utf8_lossy(content)),
+            }
+            self.joined = flags & SL_CONTINUE != 0;
+            components = &rest[*len as usize..];
+        }
+    }
+}
+
+// what the rock ridge entries of one record say
+#[derive(Default)]
+struct Rock {
+    mode: Option<u32>,
+    nlink: u64,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    name: Option<Vec<u8>>,
+    link: Option<Link>,
+    // creation, modification, access and attribute change
+    times: [Option<u64>; 4],
+    // moved somewhere shallower, the CL entry where it used to be stands in for it
+    relocated: bool,
+    // the block a relocated directory is at now
+    child: Option<u64>,
+}
+
+impl Rock {
+    // the entries of one area, and the continuation area if there is one
+    fn parse(&mut self, area: &[u8]) -> Option<(u64, usize)> {
+        let mut next = None;
+        let mut pos = 0;
+        while pos + 4 <= area.len() {
+            let (signature, len) = (&area[pos..pos + 2], area[pos + 2] as usize);
+            if len < 4 || pos + len > area.len() {
+                break;
+            }
+            let data = &area[pos + 4..pos + len];
+            pos += len;
+            match signature {
+                b"PX" if data.len() >= 32 => {
+                    self.mode = Some(u32_at(data, 0));
+                    self.nlink = u32_at(data, 8) as u64;
+                    self.uid = u32_at(data, 16);
+                    self.gid = u32_at(data, 24);
+                }
+                // old images put the whole dev_t in the low half
+                b"PN" if data.len() >= 16 => {
+                    let (high, low) = (u32_at(data, 0), u32_at(data, 8));
+                    self.rdev = match high {
+                        0 if low & !0xFF != 0 => makedev(low >> 8, low & 0xFF),
+                        _ => makedev(high, low),
+                    };
+                }
+                b"SL" if !data.is_empty() => {
+                    self.link.get_or_insert_default().push(&data[1..]);
+                }
+                b"NM" if !data.is_empty() && data[0] & (NM_CURRENT | NM_PARENT) == 0 => {
+                    self.name
+                        .get_or_insert_default()
+                        .extend_from_slice(&data[1..]);
+                }
+                b"TF" if !data.is_empty() => {
+                    let size = if data[0] & TF_LONG != 0 { 17 } else { 7 };
+                    let mut stamps = data[1..].chunks_exact(size);
+                    for (bit, time) in self.times.iter_mut().enumerate() {
+                        if data[0] & 1 << bit == 0 {
+                            continue;
+                        }
+                        let Some(stamp) = stamps.next() else {
+                            break;
+                        };
+                        *time = Some(match size {
+                            17 => from_long(stamp),
+                            _ => from_short(stamp),
+                        });
+                    }
+                }
+                b"CE" if data.len() >= 24 => {
+                    let offset = u32_at(data, 0) as u64 * BLOCK + u32_at(data, 8) as u64;
+                    next = Some((offset, u32_at(data, 16) as usize));
+                }
+                b"RE" => self.relocated = true,
+                b"CL" if data.len() >= 8 => self.child = Some(u32_at(data, 0) as u64),
+                b"ST" => break,
+                _ => {}
+            }
+        }
+        next
+    }
+}
+
+struct Options {
+    rock: bool,
+    joliet: bool,
+    // lowercase plain names and drop their version
+    map: bool,
+}
+
+// "norock,nojoliet,map=off" like on linux
+fn parse_options(data: &str) -> Result<Options, i64> {
+    let mut options = Options {
+        rock: true,
+        joliet: true,
+        map: true,
+    };
+    for option in data.split(',').filter(|o| !o.is_empty()) {
+        match option {
+            "norock" => options.rock = false,
+            "nojoliet" => options.joliet = false,
+            "map=normal" | "map=n" => options.map = true,
+            "map=off" | "map=o" => options.map = false,
+            _ => return Err(EINVAL),
+        }
+    }
+    Ok(options)
+}
+
+// one per mount, which tree is read and how its names look
+pub struct IsoSb {
+    rdev: u64,
+    label: String,
+    blocks: u64,
+    // rock ridge entries start this far into each record's system use area, None when
+    // there aren't any or they're not wanted
+    rock: Option<usize>,
+    joliet: bool,
+    map: bool,
+}
+
+impl core::fmt::Debug for IsoSb {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "iso9660 {:?} on {:#x}", self.label, self.rdev)
+    }
+}
+
+impl IsoSb {
+    // the superblock and the root's directory record out of the volume descriptors
+    fn new(rdev: u64, options: Options) -> Result<(Self, Vec<u8>), i64> {
+        block_device(rdev).ok_or(ENXIO)?;
+        let (mut primary, mut joliet) = (None, None);
+        for n in FIRST_DESCRIPTOR..FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+            let mut descriptor = vec![0u8; BLOCK as usize];
+            if cache::read(rdev, n * BLOCK, &mut descriptor)? != descriptor.len()
+                || descriptor[1..6] != *b"CD001"
+            {
+                return Err(EINVAL);
+            }
+            match descriptor[0] {
+                VD_PRIMARY if primary.is_none() => primary = Some(descriptor),
+                VD_SUPPLEMENTARY if is_joliet(&descriptor) => joliet = Some(descriptor),
+                VD_END => break,
+                _ => {}
+            }
+        }
+        let primary = primary.ok_or(EINVAL)?;
+        if u16_at(&primary, 128) as u64 != BLOCK {
+            return Err(EINVAL);
+        }
+
+        let mut sb = Self {
+            rdev,
+            label: String::from_utf8_lossy(&primary[40..72]).trim_end().into(),
+            blocks: u32_at(&primary, 80) as u64,
+            rock: None,
+            joliet: false,
+            map: options.map,
+        };
+        let root = primary[VD_ROOT..VD_ROOT + 34].to_vec();
+        // rock ridge starts the root's "." with an SP entry saying where the others are
+        if options.rock {
+            let dot = sb.first_record(extent(&root).0)?;
+            let area = system_use(&dot);
+            if area.len() >= 7 && area[..2] == *b"SP" && area[4..6] == [0xBE, 0xEF] {
+                sb.rock = Some(area[6] as usize);
+                return Ok((sb, root));
+            }
+        }
+        match joliet {
+            Some(descriptor) if options.joliet => {
+                sb.joliet = true;
+                Ok((sb, descriptor[VD_ROOT..VD_ROOT + 34].to_vec()))
+            }
+            _ => Ok((sb, root)),
+        }
+    }
+
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), i64> {
+        match cache::read(self.rdev, offset, buf)? {
+            n if n == buf.len() => Ok(()),
+            _ => Err(EIO),
+        }
+    }
+
+    // a directory's own "." record, the first one at `offset`
+    fn first_record(&self, offset: u64) -> Result<Vec<u8>, i64> {
+        let mut block = vec![0u8; BLOCK as usize];
+        self.read(offset, &mut block)?;
+        let len = block[0] as usize;
+        if len <= RECORD || RECORD + block[32] as usize > len {
+            return Err(EIO);
+        }
+        block.truncate(len);
+        Ok(block)
+    }
+
+    // everything rock ridge says about a record, following continuation areas
+    fn rock(&self, record: &[u8], skip: usize) -> Result<Rock, i64> {
+        let mut rock = Rock::default();
+        if self.rock.is_none() {
+            return Ok(rock);
+        }
+        let mut next = rock.parse(system_use(record).get(skip..).unwrap_or(&[]));
+        for _ in 0..MAX_CONTINUATIONS {
+            let Some((offset, len)) = next else {
+                break;
+            };
+            let mut area = vec![0u8; len.min(BLOCK as usize)];
+            self.read(offset, &mut area)?;
+            next = rock.parse(&area);
+        }
+        Ok(rock)
+    }
+
+    // what a record without rock ridge is called
+    fn name(&self, raw: &[u8]) -> String {
+        if self.joliet {
+            let units = raw.as_chunks().0.iter().map(|&c| u16::from_be_bytes(c));
+            let name: String = char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect();
+            return match name.rsplit_once(';') {
+                Some((base, _)) => base.into(),
+                None => name,
+            };
+        }
+        let name = String::from_utf8_lossy(raw);
+        if !self.map {
+            return name.into_owned();
+        }
+        let base = name.rsplit_once(';').map_or(&*name, |(base, _)| base);
+        base.strip_suffix('.').unwrap_or(base).to_ascii_lowercase()
+    }
+
+    // only plain names are looked up without caring about case
+    fn matches(&self, name: &str, wanted: &str) -> bool {
+        match self.rock.is_none() && !self.joliet {
+            true => name.eq_ignore_ascii_case(wanted),
+            false => name == wanted,
+        }
+    }
+
+    fn stats(&self) -> FsStats {
+        FsStats {
+            blocks: self.blocks * BLOCK / 4096,
+            bfree: 0,
+            bavail: 0,
+        }
+    }
+}
+
+// anything on the disc, files can be in several extents one after another
+pub struct IsoNode {
+    sb: Arc<IsoSb>,
+    extents: Vec<(u64, u64)>,
+    target: String,
+    rdev: u64,
+    // directories only, read in the first time someone looks
+    children: Spin<Option<Vec<DirEntry>>>,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for IsoNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}, {:?}", self.extents, self.metadata)
+    }
+}
+
+impl IsoNode {
+    // None for fifos and sockets, which have nothing behind them here
+    fn new(sb: &Arc<IsoSb>, record: &[u8], rock: Rock, extents: Vec<(u64, u64)>) -> Option<Self> {
+        let dir = record[25] & FLAG_DIRECTORY != 0 || rock.child.is_some();
+        let type_ = match rock.mode.map(|mode| mode & S_IFMT) {
+            _ if rock.child.is_some() => VfsNodeType::Directory,
+            Some(S_IFREG) => VfsNodeType::File,
+            Some(S_IFDIR) => VfsNodeType::Directory,
+            Some(S_IFLNK) => VfsNodeType::Symlink,
+            Some(S_IFCHR) => VfsNodeType::CharDevice,
+            Some(S_IFBLK) => VfsNodeType::BlockDevice,
+            Some(_) => return None,
+            None if dir => VfsNodeType::Directory,
+            None => VfsNodeType::File,
+        };
+        let target = rock.link.map(|link| link.target).unwrap_or_default();
+
+        let mut metadata = VfsNodeMetadata::new(type_);
+        metadata.size = match metadata.type_ {
+            VfsNodeType::Symlink => target.len() as u64,
+            VfsNodeType::CharDevice | VfsNodeType::BlockDevice => 0,
+            _ => extents.iter().map(|&(_, len)| len).sum(),
+        };
+        metadata.permissions = NodeMode::from_bits_truncate(match rock.mode {
+            Some(mode) => (mode & 0o777) as i32,
+            None => 0o555,
+        });
+        metadata.uid = rock.uid;
+        metadata.gid = rock.gid;
+        // without rock ridge nobody counted, a directory has at least its "."
+        metadata.nlink = match rock.nlink {
+            0 => 1 + (metadata.type_ == VfsNodeType::Directory) as u64,
+            nlink => nlink,
+        };
+        let [created, modified, accessed, changed] = rock.times;
+        metadata.modified_at = modified.unwrap_or_else(|| from_short(&record[18..25]));
+        metadata.created_at = created.unwrap_or(metadata.modified_at);
+        metadata.changed_at = changed.unwrap_or(metadata.modified_at);
+        metadata.set_accessed_at(accessed.unwrap_or(metadata.modified_at));
+
+        Some(Self {
+            sb: sb.clone(),
+            extents,
+            target,
+            rdev: rock.rdev,
+            children: Spin::new(None),
+            metadata,
+        })
+    }
+
+    // from its own "." record, which has the root's rock ridge entries too
+    fn root(sb: &Arc<IsoSb>, record: &[u8]) -> Result<Self, i64> {
+        let dot = sb.first_record(extent(record).0)?;
+        let rock = sb.rock(&dot, 0)?;
+        let root = Self::new(sb, &dot, rock, vec![extent(&dot)]).ok_or(EINVAL)?;
+        match root.is_dir() {
+            true => Ok(root),
+            false => Err(EINVAL),
+        }
+    }
+
+    // `buf` from byte `pos` of the data, wherever the extents put it
+    fn read_data(&self, mut pos: u64, buf: &mut [u8]) -> Result<(), i64> {
+        let mut done = 0;
+        for &(start, len) in &self.extents {
+            if done == buf.len() {
+                break;
+            }
+            if pos >= len {
+                pos -= len;
+                continue;
+            }
+            let n = ((len - pos) as usize).min(buf.len() - done);
+            self.sb.read(start + pos, &mut buf[done..done + n])?;
+            done += n;
+            pos = 0;
+        }
+        match done == buf.len() {
+            true => Ok(()),
+            false => Err(EIO),
+        }
+    }
+
+    // a record never crosses into the next block, the rest of a block is zeroes then
+    fn scan(&self) -> Result<Vec<DirEntry>, i64> {
+        if self.metadata.size > MAX_DIR_SIZE {
+            return Err(EIO);
+        }
+        let mut raw = vec![0u8; self.metadata.size as usize];
+        self.read_data(0, &mut raw)?;
+        let skip = self.sb.rock.unwrap_or_default();
+        let mut children = Vec::new();
+        let mut extents = Vec::new();
+        for block in raw.chunks(BLOCK as usize) {
+            let mut pos = 0;
+            while pos < block.len() && block[pos] != 0 {
+                let len = block[pos] as usize;
+                let Some(record) = block
+                    .get(pos..pos + len)
+                    .filter(|r| r.len() > RECORD && RECORD + r[32] as usize <= r.len())
+                else {
+                    warn!("iso9660: broken directory record");
+                    break;
+                };
+                pos += len;
+                if is_dot(record) {
+                    continue;
+                }
+                extents.push(extent(record));
+                if record[25] & FLAG_MULTI_EXTENT != 0 {
+                    continue;
+                }
+                let extents = core::mem::take(&mut extents);
+                let mut rock = self.sb.rock(record, skip)?;
+                if rock.relocated {
+                    continue;
+                }
+                let name = match rock.name.take() {
+                    Some(name) => String::from_utf8_lossy(&name).into_owned(),
+                    None => self.sb.name(record_name(record)),
+                };
+                // a directory moved away to keep the tree shallow, its own "." says where
+                // it is and how big
+                let extents = match rock.child {
+                    Some(block) => vec![extent(&self.sb.first_record(block * BLOCK)?)],
+                    None => extents,
+                };
+                if let Some(node) = IsoNode::new(&self.sb, record, rock, extents) {
+                    children.push(DirEntry {
+                        name,
+                        inode: Inode::new(node),
+                    });
+                }
+            }
+        }
+        Ok(children)
+    }
+
+    // the entries are read in outside the lock, someone who got there first wins
+    fn with_children<T>(&self, f: impl FnOnce(&[DirEntry]) -> T) -> T {
+        if self.children.lock().is_none() {
+            match self.scan() {
+                Ok(scanned) => {
+                    let mut children = self.children.lock();
+                    if children.is_none() {
+                        *children = Some(scanned);
+                    }
+                }
+                Err(e) => {
+                    warn!("iso9660: couldn't read a directory, errno {e}");
+                    return f(&[]);
+                }
+            }
+        }
+        f(self.children.lock().as_ref().unwrap())
+    }
+}
+
+impl VfsNode for IsoNode {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn get_child(&self, name: &str) -> Option<InodeRef> {
+        if !self.is_dir() {
+            return None;
+        }
+        self.with_children(|children| {
+            children
+                .iter()
+                .find(|c| self.sb.matches(&c.name, name))
+                .map(|c| c.inode.clone())
+        })
+    }
+    fn get_children(&self) -> Vec<DirEntry> {
+        match self.is_dir() {
+            true => self.with_children(|children| children.to_vec()),
+            false => Vec::new(),
+        }
+    }
+    fn size(&self) -> u64 {
+        self.metadata.size
+    }
+    // extents aren't in memory
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        if !self.is_file() {
+            return None;
+        }
+        let n = self
+            .metadata
+            .size
+            .saturating_sub(offset)
+            .min(buf.len() as u64) as usize;
+        self.read_data(offset, &mut buf[..n]).ok()?;
+        Some(n)
+    }
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EROFS)
+    }
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EROFS)
+    }
+    fn readlink(&self) -> Option<&str> {
+        match self.metadata.type_ {
+            VfsNodeType::Symlink => Some(&self.target),
+            _ => None,
+        }
+    }
+    fn rdev(&self) -> u64 {
+        self.rdev
+    }
+    fn blocks(&self) -> u64 {
+        match self.metadata.type_ {
+            VfsNodeType::File | VfsNodeType::Directory => {
+                self.metadata.size.div_ceil(BLOCK) * (BLOCK / 512)
+            }
+            _ => 0,
+        }
+    }
+    fn statfs(&self) -> Option<FsStats> {
+        Some(self.sb.stats())
+    }
+    fn open(&self) -> Result<Option<Arc<dyn FileObject>>, i64> {
+        match self.metadata.type_ {
+            VfsNodeType::CharDevice | VfsNodeType::BlockDevice => {
+                open_device(&self.metadata.type_, self.rdev).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+// cds and their images, rock ridge over joliet over plain names
+pub struct IsoFs;
+
+impl FileSystemType for IsoFs {
+    fn name(&self) -> &'static str {
+        "iso9660"
+    }
+    fn nodev(&self) -> bool {
+        false
+    }
+    fn magic(&self) -> u64 {
+        ISOFS_SUPER_MAGIC
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Err(ENOTBLK)
+    }
+    fn mount_device(&self, rdev: u64, _flags: MountFlags, data: &str) -> Result<InodeRef, i64> {
+        let (sb, record) = IsoSb::new(rdev, parse_options(data)?)?;
+        let sb = Arc::new(sb);
+        let names = match (sb.rock.is_some(), sb.joliet) {
+            (true, _) => "rock ridge",
+            (false, true) => "joliet",
+            (false, false) => "plain",
+        };
+        info!(
+            "iso9660: {:?}, {} blocks, {names} names",
+            sb.label, sb.blocks
+        );
+        let mut root = IsoNode::root(&sb, &record)?;
+        // its ".." is counted already, the mount adds that back
+        root.metadata.nlink -= 1;
+        Ok(Inode::new(root))
+    }
+}
+
+pub static ISO9660: IsoFs = IsoFs;
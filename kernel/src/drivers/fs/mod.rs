@@ -7,38 +7,76 @@ use alloc::{
     boxed::Box,
     format,
     string::{String, ToString},
+    sync::Arc,
+    vec,
     vec::Vec,
 };
 
-use crate::{arch::drivers::time::rtc::read_rtc, debug, info, utils::spinlock::Spin};
+use crate::{
+    arch::drivers::time::rtc::read_rtc,
+    debug, info,
+    memory::shared::SharedMemory,
+    utils::{
+        errno::{EEXIST, EFBIG, EINVAL, EISDIR, ENODATA, ENOSPC, ENXIO, EPERM},
+        rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+        spinlock::Spin,
+    },
+    warn,
+};
 
 pub use types::*;
+pub mod devfs;
+pub mod ext2;
+pub mod fat;
 pub mod helpers;
+pub mod inode;
+pub mod iso9660;
+pub mod mount;
+pub mod object;
+pub mod procfs;
+pub mod resolve;
+pub mod tarfs;
+pub mod tmpfs;
 pub mod types;
+pub use devfs::*;
+pub use ext2::*;
+pub use fat::*;
 pub use helpers::*;
+pub use inode::*;
+pub use iso9660::*;
+pub use mount::*;
+pub use object::*;
+pub use procfs::*;
+pub use resolve::*;
+pub use tarfs::*;
+pub use tmpfs::*;
+
+// only the mount table, every inode has its own lock. lookups take this for reading
+pub static VFS: RwLock<Vfs> = RwLock::new(Vfs { mounts: Vec::new() });
 
-pub static VFS: Spin<Vfs> = Spin::new(Vfs { root: None });
+// held across a rename between two directories so their locks are always taken in the
+// same order, like linux's s_vfs_rename_mutex
+pub static RENAME_LOCK: Spin<()> = Spin::new(());
 
 impl Vfs {
-    pub fn new(root: Box<dyn VfsNode>) -> Self {
-        Self { root: Some(root) }
-    }
-    pub fn get_root(&self) -> &dyn VfsNode {
-        self.root.as_ref().unwrap().as_ref()
+    pub fn new(root: InodeRef) -> Self {
+        Self {
+            mounts: vec![Mount::rootfs(root)],
+        }
     }
-    pub fn get_root_mut(&mut self) -> &mut Box<dyn VfsNode> {
-        self.root.as_mut().unwrap()
+    pub fn get_root(&self) -> &InodeRef {
+        self.mounts[0].root()
     }
-    pub fn resolve_path(&self, path: Path) -> Option<&dyn VfsNode> {
-        self.root.as_ref().unwrap().resolve_path(path)
+    // crosses into whatever is mounted along the way and follows symlinks
+    pub fn resolve(&self, path: Path) -> Option<InodeRef> {
+        self.walk(&path, true).ok()
     }
-    pub fn resolve_path_mut(&mut self, path: Path) -> Option<&mut dyn VfsNode> {
-        self.root.as_mut().unwrap().resolve_path_mut(path)
+    // the symlink itself if that's what the path ends in
+    pub fn resolve_nofollow(&self, path: Path) -> Option<InodeRef> {
+        self.walk(&path, false).ok()
     }
 }
 
-unsafe impl Send for Vfs {}
-
 fn canonicalize_components(path: &Path) -> Option<Vec<&str>> {
     let mut out: Vec<&str> = Vec::new();
     for part in path.as_str().split('/') {
@@ -54,60 +92,110 @@ fn canonicalize_components(path: &Path) -> Option<Vec<&str>> {
     Some(out)
 }
 
+#[derive(Clone)]
+enum Backing {
+    Node(InodeRef),
+    Object(Arc<dyn FileObject>),
+    // a node that opened into an object (VfsNode::open), io goes to the object but
+    // stat still sees the node
+    Device(InodeRef, Arc<dyn FileObject>),
+}
+
 pub struct FileDescriptor {
-    node: *mut dyn VfsNode,
+    backing: Backing,
+    // what it was opened as, for *at syscalls. may be stale after a rename
+    pub path: Option<Path>,
     pub permissions: Permissions,
     pub offset: u64,
     pub append: bool,
+    pub nonblock: bool,
 }
 
 impl FileDescriptor {
-    pub fn new(node: &mut dyn VfsNode, permissions: Permissions) -> FileDescriptor {
+    pub fn new(inode: InodeRef, permissions: Permissions) -> FileDescriptor {
+        FileDescriptor {
+            backing: Backing::Node(inode),
+            path: None,
+            permissions,
+            offset: 0,
+            append: false,
+            nonblock: false,
+        }
+    }
+    pub fn from_object(object: Arc<dyn FileObject>, permissions: Permissions) -> FileDescriptor {
+        FileDescriptor {
+            backing: Backing::Object(object),
+            path: None,
+            permissions,
+            offset: 0,
+            append: false,
+            nonblock: false,
+        }
+    }
+    pub fn device(
+        inode: InodeRef,
+        object: Arc<dyn FileObject>,
+        permissions: Permissions,
+    ) -> FileDescriptor {
         FileDescriptor {
-            node: unsafe { core::mem::transmute(node) },
+            backing: Backing::Device(inode, object),
+            path: None,
             permissions,
             offset: 0,
             append: false,
+            nonblock: false,
         }
     }
     pub fn with_append(mut self, append: bool) -> Self {
         self.append = append;
         self
     }
-    pub fn node(&self) -> &dyn VfsNode {
-        unsafe { &*self.node }
+    pub fn with_nonblock(mut self, nonblock: bool) -> Self {
+        self.nonblock = nonblock;
+        self
     }
-    pub fn node_mut(&mut self) -> &mut dyn VfsNode {
-        unsafe { &mut *self.node }
+    pub fn with_path(mut self, path: Path) -> Self {
+        self.path = Some(path);
+        self
+    }
+    pub fn inode(&self) -> Option<&InodeRef> {
+        match &self.backing {
+            Backing::Node(inode) | Backing::Device(inode, _) => Some(inode),
+            Backing::Object(_) => None,
+        }
+    }
+    // only the node is locked, i/o on other files goes on meanwhile
+    pub fn node(&self) -> Option<NodeGuard<'_>> {
+        self.inode().map(|inode| inode.node())
+    }
+    pub fn node_mut(&self) -> Option<NodeGuardMut<'_>> {
+        self.inode().map(|inode| inode.node_mut())
+    }
+    pub fn object(&self) -> Option<&Arc<dyn FileObject>> {
+        match &self.backing {
+            Backing::Node(_) => None,
+            Backing::Object(object) | Backing::Device(_, object) => Some(object),
+        }
+    }
+    // downcasts the object to a concrete type, e.g. to get at a socket
+    pub fn object_as<T: FileObject>(&self) -> Option<Arc<T>> {
+        let object: Arc<dyn core::any::Any + Send + Sync> = self.object()?.clone();
+        object.downcast::<T>().ok()
     }
     pub fn dup(&self) -> FileDescriptor {
         FileDescriptor {
-            node: self.node,
+            backing: self.backing.clone(),
+            path: self.path.clone(),
             permissions: self.permissions,
             offset: self.offset,
             append: self.append,
+            nonblock: self.nonblock,
         }
     }
-    pub fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
-        let offset = self.offset;
-        self.node_mut().read_at(offset, buf).inspect(|&n| {
-            self.offset += n as u64;
-        })
-    }
-    pub fn write(&mut self, buf: &[u8]) -> Option<usize> {
-        let write_offset = if self.append {
-            self.node().size()
-        } else {
-            self.offset
-        };
-
-        self.node_mut().write_at(write_offset, buf).inspect(|&n| {
-            self.offset = write_offset + n as u64;
-        })
-    }
 }
 
-pub trait VfsNode: core::fmt::Debug {
+// Any so a filesystem can get its own nodes back out of an InodeRef
+pub trait VfsNode: core::any::Any + core::fmt::Debug + Send + Sync {
     fn get_permissions(&self) -> &NodeMode;
     fn get_permissions_mut(&mut self) -> &mut NodeMode;
     fn get_metadata(&self) -> &VfsNodeMetadata;
@@ -119,34 +207,108 @@ pub trait VfsNode: core::fmt::Debug {
     fn is_file(&self) -> bool {
         self.get_type() == &VfsNodeType::File
     }
-    fn get_path(&self) -> &Path;
-    fn get_name(&self) -> &'_ str;
-    fn get_child(&self, name: &str) -> Option<&dyn VfsNode>; // folder-only
-    fn get_child_mut(&mut self, name: &str) -> Option<&mut Box<dyn VfsNode>>; // folder-only
-    fn get_children(&self) -> Vec<&dyn VfsNode>; // folder-only
-    fn resolve_path(&self, path: Path) -> Option<&dyn VfsNode>; // folder-only
-    fn resolve_path_mut(&mut self, path: Path) -> Option<&mut dyn VfsNode>; // folder-only
-    fn create_dir(&mut self, name: &str) -> Option<&mut Box<dyn VfsNode>>; // folder-only
-    fn create_file(&mut self, name: &str) -> Option<&mut Box<dyn VfsNode>>; // folder-only
-    fn remove_child(&mut self, name: &str) -> bool; // folder-only
-    fn take_child(&mut self, name: &str) -> Option<Box<dyn VfsNode>>; // folder-only
-    fn add_child(&mut self, child: Box<dyn VfsNode>) -> bool; // folder-only
-    fn set_path(&mut self, path: Path);
+
+    // Folder-only ops. Everything else keeps these defaults.
+    // owned, so directories can make their entries up as they're asked (procfs)
+    fn get_child(&self, _name: &str) -> Option<InodeRef> {
+        None
+    }
+    fn get_children(&self) -> Vec<DirEntry> {
+        Vec::new()
+    }
+    fn create_dir(&mut self, _name: &str) -> Result<InodeRef, i64> {
+        Err(EPERM)
+    }
+    fn create_file(&mut self, _name: &str) -> Result<InodeRef, i64> {
+        Err(EPERM)
+    }
+    // adds another name for `inode`, EEXIST if `name` is taken or whatever the
+    // filesystem has against the name
+    fn link(&mut self, _name: &str, _inode: InodeRef) -> Result<(), i64> {
+        Err(EPERM)
+    }
+    // drops the name, the inode goes away with its last reference
+    fn unlink(&mut self, _name: &str) -> Option<InodeRef> {
+        None
+    }
 
     // File-only ops. Directories implement these as no-ops / None.
     fn size(&self) -> u64;
     fn read(&self) -> Option<&[u8]>;
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize>;
-    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Option<usize>;
-    fn truncate(&mut self, len: u64) -> bool;
-    fn write_all(&mut self, data: &[u8]) -> bool {
-        if !self.truncate(0) {
-            return false;
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64>;
+    fn truncate(&mut self, len: u64) -> Result<(), i64>;
+    // socket-only, the unix socket bound to this node if it's still around
+    fn bound_socket(&self) -> Option<Arc<dyn FileObject>> {
+        None
+    }
+    // symlink-only, where it points
+    fn readlink(&self) -> Option<&str> {
+        None
+    }
+    // frames to map for mmap(MAP_SHARED), only shm files have them
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        None
+    }
+    // device-only, the st_rdev of a device node
+    fn rdev(&self) -> u64 {
+        0
+    }
+    // st_blocks in 512 byte units, sparse files take up less than their size
+    fn blocks(&self) -> u64 {
+        self.size().div_ceil(512)
+    }
+    // how full the filesystem holding this node is, for ones that have a limit
+    fn statfs(&self) -> Option<FsStats> {
+        None
+    }
+    // nodes whose open files do their io somewhere else (devices, procfs) return that
+    // here, everything else is read and written through the node itself
+    fn open(&self) -> Result<Option<Arc<dyn FileObject>>, i64> {
+        Ok(None)
+    }
+    // fsync, nodes of disk-backed filesystems write themselves out. `data_only` is
+    // fdatasync, metadata that doesn't matter for reading the data back can wait
+    fn sync(&self, _data_only: bool) -> Result<(), i64> {
+        Ok(())
+    }
+    // sync(2) on the root of every mount, for whatever the filesystem keeps that isn't
+    // in the buffer cache yet
+    fn sync_fs(&self) {}
+    // a copy of the whole file, for files that aren't one slice in memory too
+    fn read_all(&self) -> Option<Vec<u8>> {
+        if let Some(data) = self.read() {
+            return Some(data.to_vec());
         }
+        let mut data = vec![0; self.size() as usize];
+        let n = self.read_at(0, &mut data)?;
+        data.truncate(n);
+        Some(data)
+    }
+    fn write_all(&mut self, data: &[u8]) -> Result<(), i64> {
+        self.truncate(0)?;
         if data.is_empty() {
-            return true;
+            return Ok(());
         }
-        self.write_at(0, data).is_some()
+        self.write_at(0, data).map(|_| ())
+    }
+    // extended attributes by their full name, "user.foo". the name has been checked
+    // already, ENODATA if the node doesn't have it. filesystems that keep them on disk
+    // replace all four, ones that can't keep them at all only set_xattr
+    fn get_xattr(&self, name: &str) -> Result<Vec<u8>, i64> {
+        self.get_metadata().xattrs.get(name).cloned().ok_or(ENODATA)
+    }
+    fn set_xattr(&mut self, name: &str, value: &[u8]) -> Result<(), i64> {
+        let xattrs = &mut self.get_metadata_mut().xattrs;
+        xattrs.insert(name.to_string(), value.to_vec());
+        Ok(())
+    }
+    fn remove_xattr(&mut self, name: &str) -> Result<(), i64> {
+        let xattrs = &mut self.get_metadata_mut().xattrs;
+        xattrs.remove(name).map(|_| ()).ok_or(ENODATA)
+    }
+    fn list_xattrs(&self) -> Result<Vec<String>, i64> {
+        Ok(self.get_metadata().xattrs.keys().cloned().collect())
     }
 }
 
@@ -157,48 +319,48 @@ pub trait VfsNodeMetadataExt {
     fn with_modified_at(self, modified_at: u64) -> Self;
 }
 
-impl VfsNodeMetadataExt for Option<&mut Box<dyn VfsNode>> {
-    fn with_permissions(mut self, permissions: NodeMode) -> Self {
-        if let Some(x) = self.as_mut() {
-            x.get_metadata_mut().permissions = permissions;
+impl VfsNodeMetadataExt for Option<InodeRef> {
+    fn with_permissions(self, permissions: NodeMode) -> Self {
+        if let Some(x) = self.as_ref() {
+            x.node_mut().get_metadata_mut().permissions = permissions;
         }
         self
     }
-    fn with_size(mut self, size: u64) -> Self {
-        if let Some(x) = self.as_mut() {
-            x.get_metadata_mut().size = size;
+    fn with_size(self, size: u64) -> Self {
+        if let Some(x) = self.as_ref() {
+            x.node_mut().get_metadata_mut().size = size;
         }
         self
     }
-    fn with_created_at(mut self, created_at: u64) -> Self {
-        if let Some(x) = self.as_mut() {
-            x.get_metadata_mut().created_at = created_at;
+    fn with_created_at(self, created_at: u64) -> Self {
+        if let Some(x) = self.as_ref() {
+            x.node_mut().get_metadata_mut().created_at = created_at;
         }
         self
     }
-    fn with_modified_at(mut self, modified_at: u64) -> Self {
-        if let Some(x) = self.as_mut() {
-            x.get_metadata_mut().modified_at = modified_at;
+    fn with_modified_at(self, modified_at: u64) -> Self {
+        if let Some(x) = self.as_ref() {
+            x.node_mut().get_metadata_mut().modified_at = modified_at;
         }
         self
     }
 }
 
-impl VfsNodeMetadataExt for &mut Box<dyn VfsNode> {
+impl VfsNodeMetadataExt for InodeRef {
     fn with_permissions(self, permissions: NodeMode) -> Self {
-        self.get_metadata_mut().permissions = permissions;
+        self.node_mut().get_metadata_mut().permissions = permissions;
         self
     }
     fn with_size(self, size: u64) -> Self {
-        self.get_metadata_mut().size = size;
+        self.node_mut().get_metadata_mut().size = size;
         self
     }
     fn with_created_at(self, created_at: u64) -> Self {
-        self.get_metadata_mut().created_at = created_at;
+        self.node_mut().get_metadata_mut().created_at = created_at;
         self
     }
     fn with_modified_at(self, modified_at: u64) -> Self {
-        self.get_metadata_mut().modified_at = modified_at;
+        self.node_mut().get_metadata_mut().modified_at = modified_at;
         self
     }
 }
@@ -219,83 +381,71 @@ impl VfsNode for Directory {
     fn get_type(&self) -> &VfsNodeType {
         &self.get_metadata().type_
     }
-    fn get_path(&self) -> &Path {
-        &self.path
-    }
-    fn get_name(&self) -> &'_ str {
-        self.path.get_name()
-    }
-    fn resolve_path(&self, path: Path) -> Option<&dyn VfsNode> {
-        let components = canonicalize_components(&path)?;
-        let mut current: &dyn VfsNode = self;
-        for part in components {
-            current = current.get_child(part)?;
-        }
-        Some(current)
-    }
-    fn resolve_path_mut(&mut self, path: Path) -> Option<&mut dyn VfsNode> {
-        let components = canonicalize_components(&path)?;
-        let mut current: &mut dyn VfsNode = self;
-        for part in components {
-            let child = current.get_child_mut(part)?;
-            current = child.as_mut();
-        }
-        Some(current)
-    }
-    fn get_child(&self, name: &str) -> Option<&dyn VfsNode> {
+    fn get_child(&self, name: &str) -> Option<InodeRef> {
         self.children
             .iter()
-            .map(|c| c.as_ref())
-            .find(|c| c.get_name() == name)
-    }
-    fn get_child_mut(&mut self, name: &str) -> Option<&mut Box<dyn VfsNode>> {
-        self.children.iter_mut().find(|c| c.get_name() == name)
-    }
-    fn get_children(&self) -> Vec<&dyn VfsNode> {
-        self.children.iter().map(|c| c.as_ref()).collect()
+            .find(|c| c.name == name)
+            .map(|c| c.inode.clone())
+    }
+    fn get_children(&self) -> Vec<DirEntry> {
+        self.children.clone()
+    }
+    fn create_dir(&mut self, name: &str) -> Result<InodeRef, i64> {
+        let inode = Inode::new(Directory::new().with_storage(self.storage.clone()));
+        self.link(name, inode.clone())?;
+        Ok(inode)
+    }
+    fn create_file(&mut self, name: &str) -> Result<InodeRef, i64> {
+        let inode = match &self.storage {
+            Storage::Heap => Inode::new(File::new(Vec::new())),
+            Storage::Shm(sb) => Inode::new(ShmFile::new(sb.clone())),
+            Storage::Tmpfs(sb) => Inode::new(TmpFile::new(sb.clone())),
+        };
+        self.link(name, inode.clone())?;
+        Ok(inode)
     }
-    fn create_dir(&mut self, name: &str) -> Option<&mut Box<dyn VfsNode>> {
-        if self.children.iter().any(|c| c.get_name() == name) {
-            return None;
+    fn link(&mut self, name: &str, inode: InodeRef) -> Result<(), i64> {
+        if self.children.iter().any(|c| c.name == name) {
+            return Err(EEXIST);
         }
-        self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
-        self.children
-            .push(Box::new(Directory::new(self.path.join(name))));
-        self.children.last_mut()
-    }
-    fn create_file(&mut self, name: &str) -> Option<&mut Box<dyn VfsNode>> {
-        if self.children.iter().any(|c| c.get_name() == name) {
-            return None;
+        // parent before child, the same order lookups go in
+        let mut node = inode.node_mut();
+        if let Some(sb) = self.storage.sb() {
+            let cost = TmpfsSb::entry_cost(name, &**node);
+            sb.charge_meta(cost)?;
+            self.charged += cost;
         }
-        self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
-        self.children
-            .push(Box::new(File::new(Vec::new(), self.path.join(name))));
-        self.children.last_mut()
-    }
-    fn remove_child(&mut self, name: &str) -> bool {
-        let before = self.children.len();
-        self.children.retain(|c| c.get_name() != name);
-        let removed = self.children.len() != before;
-        if removed {
-            self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
+        node.get_metadata_mut().nlink += 1;
+        node.get_metadata_mut().touch_changed();
+        // the new subdirectory's ".."
+        if node.is_dir() {
+            self.metadata.nlink += 1;
         }
-        removed
-    }
-    fn take_child(&mut self, name: &str) -> Option<Box<dyn VfsNode>> {
-        let pos = self.children.iter().position(|c| c.get_name() == name)?;
-        self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
-        Some(self.children.remove(pos))
-    }
-    fn add_child(&mut self, child: Box<dyn VfsNode>) -> bool {
-        if self.children.iter().any(|c| c.get_name() == child.get_name()) {
-            return false;
+        drop(node);
+        self.metadata.touch_modified();
+        self.children.push(DirEntry {
+            name: name.to_string(),
+            inode,
+        });
+        Ok(())
+    }
+    fn unlink(&mut self, name: &str) -> Option<InodeRef> {
+        let pos = self.children.iter().position(|c| c.name == name)?;
+        let inode = self.children.remove(pos).inode;
+        let mut node = inode.node_mut();
+        if let Some(sb) = self.storage.sb() {
+            let cost = TmpfsSb::entry_cost(name, &**node);
+            sb.uncharge_meta(cost);
+            self.charged -= cost;
         }
-        self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
-        self.children.push(child);
-        true
-    }
-    fn set_path(&mut self, path: Path) {
-        self.path = path;
+        node.get_metadata_mut().nlink -= 1;
+        node.get_metadata_mut().touch_changed();
+        if node.is_dir() {
+            self.metadata.nlink -= 1;
+        }
+        drop(node);
+        self.metadata.touch_modified();
+        Some(inode)
     }
     fn size(&self) -> u64 {
         self.metadata.size
@@ -306,12 +456,37 @@ impl VfsNode for Directory {
     fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Option<usize> {
         None
     }
-    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Option<usize> {
-        None
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EISDIR)
     }
-    fn truncate(&mut self, _len: u64) -> bool {
-        false
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EISDIR)
     }
+    fn statfs(&self) -> Option<FsStats> {
+        self.storage.sb().map(|sb| sb.stats())
+    }
+    fn set_xattr(&mut self, name: &str, value: &[u8]) -> Result<(), i64> {
+        match self.storage.sb() {
+            Some(sb) => sb.set_xattr(&mut self.metadata.xattrs, name, value),
+            None => {
+                self.metadata.xattrs.insert(name.to_string(), value.to_vec());
+                Ok(())
+            }
+        }
+    }
+    fn remove_xattr(&mut self, name: &str) -> Result<(), i64> {
+        match self.storage.sb() {
+            Some(sb) => sb.remove_xattr(&mut self.metadata.xattrs, name),
+            None => self.metadata.xattrs.remove(name).map(|_| ()).ok_or(ENODATA),
+        }
+    }
+}
+
+// ramfs has no limit, so a file only stops growing when the heap can't take it
+fn grow(data: &mut Vec<u8>, len: usize) -> Result<(), i64> {
+    data.try_reserve_exact(len - data.len()).map_err(|_| ENOSPC)?;
+    data.resize(len, 0);
+    Ok(())
 }
 
 impl VfsNode for File {
@@ -330,55 +505,6 @@ impl VfsNode for File {
     fn get_type(&self) -> &VfsNodeType {
         &self.get_metadata().type_
     }
-    fn get_path(&self) -> &Path {
-        &self.path
-    }
-    fn get_name(&self) -> &'_ str {
-        self.path.get_name()
-    }
-    fn resolve_path(&self, path: Path) -> Option<&dyn VfsNode> {
-        let components = canonicalize_components(&path)?;
-        if components.is_empty() {
-            Some(self)
-        } else {
-            None
-        }
-    }
-    fn resolve_path_mut(&mut self, path: Path) -> Option<&mut dyn VfsNode> {
-        let components = canonicalize_components(&path)?;
-        if components.is_empty() {
-            Some(self)
-        } else {
-            None
-        }
-    }
-    fn get_child(&self, _name: &str) -> Option<&dyn VfsNode> {
-        None
-    }
-    fn get_child_mut(&mut self, _name: &str) -> Option<&mut Box<dyn VfsNode>> {
-        None
-    }
-    fn get_children(&self) -> Vec<&dyn VfsNode> {
-        Vec::new()
-    }
-    fn create_dir(&mut self, _name: &str) -> Option<&mut Box<dyn VfsNode>> {
-        None
-    }
-    fn create_file(&mut self, _name: &str) -> Option<&mut Box<dyn VfsNode>> {
-        None
-    }
-    fn remove_child(&mut self, _name: &str) -> bool {
-        false
-    }
-    fn take_child(&mut self, _name: &str) -> Option<Box<dyn VfsNode>> {
-        None
-    }
-    fn add_child(&mut self, _child: Box<dyn VfsNode>) -> bool {
-        false
-    }
-    fn set_path(&mut self, path: Path) {
-        self.path = path;
-    }
     fn size(&self) -> u64 {
         self.metadata.size
     }
@@ -386,36 +512,216 @@ impl VfsNode for File {
         Some(&self.data)
     }
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        self.metadata.touch_accessed();
         let offset = offset as usize;
-        if offset > self.data.len() {
-            return None;
+        if offset >= self.data.len() {
+            return Some(0);
         }
         let available = self.data.len() - offset;
         let n = available.min(buf.len());
         buf[..n].copy_from_slice(&self.data[offset..offset + n]);
         Some(n)
     }
-    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Option<usize> {
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
         let offset = offset as usize;
-        let end = offset.checked_add(buf.len())?;
+        let end = offset.checked_add(buf.len()).ok_or(EFBIG)?;
         if self.data.len() < end {
-            self.data.resize(end, 0);
+            grow(&mut self.data, end)?;
         }
         self.data[offset..end].copy_from_slice(buf);
         self.metadata.size = self.data.len() as u64;
-        self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
-        Some(buf.len())
+        self.metadata.touch_modified();
+        Ok(buf.len())
     }
-    fn truncate(&mut self, len: u64) -> bool {
+    fn truncate(&mut self, len: u64) -> Result<(), i64> {
         let len = len as usize;
         if len <= self.data.len() {
             self.data.truncate(len);
         } else {
-            self.data.resize(len, 0);
+            grow(&mut self.data, len)?;
         }
         self.metadata.size = self.data.len() as u64;
-        self.metadata.modified_at = read_rtc().to_epoch().unwrap_or_default();
-        true
+        self.metadata.touch_modified();
+        Ok(())
+    }
+}
+
+impl VfsNode for ShmFile {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn size(&self) -> u64 {
+        self.memory.size() as u64
+    }
+    // not contiguous, so no borrowed view of the whole thing
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        self.metadata.touch_accessed();
+        Some(self.memory.read_at(offset as usize, buf))
+    }
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        let size = self.memory.size() as u64;
+        let end = offset.checked_add(buf.len() as u64).ok_or(EINVAL)?.max(size);
+        self.sb.recharge(size, end)?;
+        let n = self
+            .memory
+            .write_at(offset as usize, buf)
+            .inspect_err(|_| _ = self.sb.recharge(end, size))?;
+        self.metadata.touch_modified();
+        Ok(n)
+    }
+    fn truncate(&mut self, len: u64) -> Result<(), i64> {
+        let size = self.memory.size() as u64;
+        self.sb.recharge(size, len)?;
+        self.memory
+            .resize(len as usize)
+            .inspect_err(|_| _ = self.sb.recharge(len, size))?;
+        self.metadata.touch_modified();
+        Ok(())
+    }
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        Some(self.memory.clone())
+    }
+    fn set_xattr(&mut self, name: &str, value: &[u8]) -> Result<(), i64> {
+        self.sb.set_xattr(&mut self.metadata.xattrs, name, value)
+    }
+    fn remove_xattr(&mut self, name: &str) -> Result<(), i64> {
+        self.sb.remove_xattr(&mut self.metadata.xattrs, name)
+    }
+}
+
+impl VfsNode for SocketNode {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EINVAL)
+    }
+    fn bound_socket(&self) -> Option<Arc<dyn FileObject>> {
+        self.endpoint.upgrade()
+    }
+}
+
+impl VfsNode for Symlink {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn size(&self) -> u64 {
+        self.target.len() as u64
+    }
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EINVAL)
+    }
+    fn readlink(&self) -> Option<&str> {
+        Some(&self.target)
+    }
+}
+
+impl VfsNode for DeviceNode {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    // the data lives in the driver, open files talk to that instead
+    fn size(&self) -> u64 {
+        0
+    }
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EINVAL)
+    }
+    fn rdev(&self) -> u64 {
+        self.rdev
+    }
+    fn open(&self) -> Result<Option<Arc<dyn FileObject>>, i64> {
+        open_device(&self.metadata.type_, self.rdev).map(Some)
+    }
+}
+
+// the driver behind a device node, wherever the node itself lives
+pub fn open_device(type_: &VfsNodeType, rdev: u64) -> Result<Arc<dyn FileObject>, i64> {
+    match type_ {
+        VfsNodeType::CharDevice => chrdev(rdev).ok_or(ENXIO),
+        VfsNodeType::BlockDevice => crate::device::block::open(rdev).ok_or(ENXIO),
+        _ => Err(ENXIO),
     }
 }
 
@@ -464,39 +770,46 @@ impl Path {
 
 pub fn init() {
     info!("initializing vfs...");
-    let mut vfs = Vfs::new(Box::new(Directory::new(Path::new("/"))));
-
-    for module in crate::utils::limine::get_modules() {
-        let tar = module.data();
-        for item in crate::utils::ustar::TarIter::new(tar) {
-            if let Some(data) = crate::utils::ustar::tar_lookup(tar, item.name) {
-                debug!("creating {:?} - {}", item.type_, &item.name[1..]);
-                match item.type_ {
-                    VfsNodeType::Directory => {
-                        if &item.name[1..] == "/" {
-                            continue;
-                        }
-                        let path = Path::new(&item.name[1..item.name.len() - 1]);
-
-                        if let Some(parent) = vfs.resolve_path_mut(path.get_parent()) {
-                            parent.create_dir(path.get_name()).unwrap();
-                        }
-                    }
-                    VfsNodeType::File => {
-                        let path = Path::new(&item.name[1..]);
-
-                        if let Some(parent) = vfs.resolve_path_mut(path.get_parent())
-                            && let Some(file) = parent.create_file(path.get_name())
-                        {
-                            file.write_all(data);
-                        }
-                    }
-                }
-            }
+    mount::init();
+    // the modules are layered in order, so a later one can replace files of an earlier one.
+    // the root is a tmpfs, /tmp and anything else written to it counts against its size
+    let sb = TmpfsSb::new(TmpfsSb::default_size(), TmpfsSb::default_inodes());
+    let root = Inode::new(Directory::new().with_storage(Storage::Tmpfs(sb.clone())));
+    for &module in crate::utils::limine::get_modules() {
+        let unpacked = module_archive(module)
+            .and_then(|archive| populate(&root, archive, Some(sb.clone())));
+        if let Err(e) = unpacked {
+            warn!("couldn't unpack {}: {}", module.path(), e);
         }
     }
+    let mut node = root.node_mut();
+    for dir in ["dev", "proc"] {
+        if node.get_child(dir).is_none() {
+            let _ = node.create_dir(dir);
+        }
+    }
+    drop(node);
+    *VFS.write() = Vfs::new(root);
 
-    *VFS.lock() = vfs;
+    for (source, target, fs_type, flags) in [
+        (
+            "devfs",
+            "/dev",
+            "devfs",
+            MountFlags::NOSUID | MountFlags::NOEXEC,
+        ),
+        (
+            "proc",
+            "/proc",
+            "proc",
+            MountFlags::NOSUID | MountFlags::NODEV | MountFlags::NOEXEC,
+        ),
+    ] {
+        if let Err(e) = mount(source, &Path::new(target), fs_type, flags, "") {
+            warn!("couldn't mount {} on {}: {}", fs_type, target, e);
+        }
+    }
+    crate::device::register_chrdevs();
 
     info!("done");
 }
@@ -0,0 +1,576 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    device::{
+        ahci::SR_MAJOR,
+        block::{block_device, block_devices},
+    },
+    utils::{config::get_config, errno::*},
+};
+
+use super::*;
+
+bitflags::bitflags! {
+    // mount(2) flags, the MS_ constants
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MountFlags: u64 {
+        const RDONLY = 1;
+        const NOSUID = 2;
+        const NODEV = 4;
+        const NOEXEC = 8;
+        const SYNCHRONOUS = 16;
+        const REMOUNT = 32;
+        const MANDLOCK = 64;
+        const DIRSYNC = 128;
+        const NOATIME = 1024;
+        const NODIRATIME = 2048;
+        const BIND = 4096;
+        const MOVE = 8192;
+        const REC = 16384;
+        const SILENT = 32768;
+        const RELATIME = 1 << 21;
+        const STRICTATIME = 1 << 24;
+        const LAZYTIME = 1 << 25;
+        // what a remount is allowed to change
+        const PER_MOUNT = Self::RDONLY.bits()
+            | Self::NOSUID.bits()
+            | Self::NODEV.bits()
+            | Self::NOEXEC.bits()
+            | Self::NOATIME.bits()
+            | Self::NODIRATIME.bits()
+            | Self::RELATIME.bits();
+    }
+
+    // umount2(2) flags
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct UmountFlags: u64 {
+        const FORCE = 1;
+        const DETACH = 2;
+        const EXPIRE = 4;
+        const NOFOLLOW = 8;
+    }
+}
+
+// a filesystem driver, registered by name so mount(2) can find it
+pub trait FileSystemType: Send + Sync {
+    fn name(&self) -> &'static str;
+    // no backing device (ramfs, procfs...), the mount source is just a label then
+    fn nodev(&self) -> bool {
+        true
+    }
+    // f_type in statfs
+    fn magic(&self) -> u64 {
+        0
+    }
+    // can't be written to at all, every mount of it is MS_RDONLY
+    fn read_only(&self) -> bool {
+        false
+    }
+    // builds the root of a new superblock
+    fn mount(&self, source: &str, flags: MountFlags, data: &str) -> Result<InodeRef, i64>;
+    // the same for filesystems on a disk, with the block device the source named
+    fn mount_device(&self, _rdev: u64, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Err(ENOTBLK)
+    }
+}
+
+static FILESYSTEMS: Spin<Vec<&'static dyn FileSystemType>> = Spin::new(Vec::new());
+
+pub fn register_filesystem(fs: &'static dyn FileSystemType) -> bool {
+    let mut filesystems = FILESYSTEMS.lock();
+    if filesystems.iter().any(|f| f.name() == fs.name()) {
+        return false;
+    }
+    filesystems.push(fs);
+    true
+}
+
+pub fn get_filesystem(name: &str) -> Option<&'static dyn FileSystemType> {
+    FILESYSTEMS
+        .lock()
+        .iter()
+        .find(|f| f.name() == name)
+        .copied()
+}
+
+pub fn filesystems() -> Vec<&'static dyn FileSystemType> {
+    FILESYSTEMS.lock().clone()
+}
+
+pub const RAMFS_MAGIC: u64 = 0x8584_58f6;
+
+// plain in-memory directories with no limit
+pub struct RamFs;
+
+impl FileSystemType for RamFs {
+    fn name(&self) -> &'static str {
+        "ramfs"
+    }
+    fn magic(&self) -> u64 {
+        RAMFS_MAGIC
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Ok(Inode::new(Directory::new()))
+    }
+}
+
+pub static RAMFS: RamFs = RamFs;
+
+// usage for statfs in 4k blocks, from VfsNode::statfs. filesystems without a limit
+// report all zeroes like linux's ramfs
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsStats {
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+}
+
+// the device numbers in st_dev, one per superblock
+static NEXT_DEV: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug)]
+pub struct Superblock {
+    pub dev: u64,
+    pub fs_type: &'static str,
+    pub magic: u64,
+    pub source: String,
+    // the block device it's on, None for the ones without
+    pub device: Option<u64>,
+    pub flags: MountFlags,
+    pub data: String,
+}
+
+#[derive(Debug)]
+pub struct Mount {
+    // canonical, no trailing slash
+    pub target: Path,
+    pub sb: Superblock,
+    root: InodeRef,
+}
+
+impl Mount {
+    pub fn new(
+        fs: &dyn FileSystemType,
+        source: &str,
+        device: Option<u64>,
+        target: Path,
+        flags: MountFlags,
+        data: &str,
+    ) -> Result<Self, i64> {
+        let root = match device {
+            Some(rdev) => fs.mount_device(rdev, flags, data)?,
+            None => fs.mount(source, flags, data)?,
+        };
+        let mut flags = flags & MountFlags::PER_MOUNT;
+        // a write protected disk can only be mounted read-only
+        if fs.read_only() || device.and_then(block_device).is_some_and(|d| d.read_only()) {
+            flags |= MountFlags::RDONLY;
+        }
+        // the root is its own parent
+        root.node_mut().get_metadata_mut().nlink += 1;
+        Ok(Self {
+            target,
+            sb: Superblock {
+                dev: NEXT_DEV.fetch_add(1, Ordering::Relaxed),
+                fs_type: fs.name(),
+                magic: fs.magic(),
+                source: String::from(source),
+                device,
+                flags,
+                data: String::from(data),
+            },
+            root,
+        })
+    }
+
+    // the initial root filesystem, not backed by any registered type
+    pub fn rootfs(root: InodeRef) -> Self {
+        root.node_mut().get_metadata_mut().nlink += 1;
+        Self {
+            target: Path::new("/"),
+            sb: Superblock {
+                dev: NEXT_DEV.fetch_add(1, Ordering::Relaxed),
+                fs_type: "rootfs",
+                magic: TMPFS_MAGIC,
+                source: String::from("rootfs"),
+                device: None,
+                flags: MountFlags::empty(),
+                data: String::new(),
+            },
+            root,
+        }
+    }
+
+    pub fn root(&self) -> &InodeRef {
+        &self.root
+    }
+
+    pub fn is_readonly(&self) -> bool {
+        self.sb.flags.contains(MountFlags::RDONLY)
+    }
+
+    // whether `path` (canonical) is this mount's target or below it
+    pub fn covers(&self, path: &Path) -> bool {
+        match path.as_str().strip_prefix(self.target.as_str()) {
+            Some(rest) => self.target.is_root() || rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        }
+    }
+}
+
+// "/a/./b/../c/" -> "/a/c", None if it climbs above the root
+pub fn canonicalize(path: &Path) -> Option<Path> {
+    let components = canonicalize_components(path)?;
+    Some(Path::new(&format!("/{}", components.join("/"))))
+}
+
+impl Vfs {
+    // the mount `path` ends up on, symlinks along the way are followed
+    fn lookup(&self, path: &Path) -> Option<usize> {
+        let path = self.physical(path)?;
+        // deepest target wins, and the newest of those since mounts stack
+        self.mounts
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.covers(&path))
+            .max_by_key(|(i, m)| (m.target.as_str().len(), *i))
+            .map(|(idx, _)| idx)
+    }
+
+    // the mount a path lives on
+    pub fn mount_of(&self, path: &Path) -> Option<&Mount> {
+        self.lookup(path).map(|idx| &self.mounts[idx])
+    }
+
+    pub fn is_mountpoint(&self, path: &Path) -> bool {
+        self.physical(path)
+            .is_some_and(|path| self.mounts.iter().any(|m| m.target == path))
+    }
+
+    pub fn is_readonly(&self, path: &Path) -> bool {
+        self.mount_of(path).is_some_and(|m| m.is_readonly())
+    }
+
+    pub fn same_mount(&self, a: &Path, b: &Path) -> bool {
+        match (self.lookup(a), self.lookup(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    pub fn device_of(&self, path: &Path) -> u64 {
+        self.mount_of(path).map_or(0, |m| m.sb.dev)
+    }
+
+    // for open files, which may have been renamed or unlinked since. None once the
+    // mount is gone
+    pub fn mount_of_inode(&self, inode: &Inode) -> Option<&Mount> {
+        self.mounts.iter().find(|m| m.sb.dev == inode.dev())
+    }
+
+    pub fn device_of_inode(&self, inode: &Inode) -> u64 {
+        inode.dev()
+    }
+
+    // what every filesystem keeps to itself goes into the buffer cache, and that to disk
+    pub fn sync(&self) {
+        for mount in &self.mounts {
+            mount.root().node().sync_fs();
+        }
+        crate::device::block::cache::sync_all();
+    }
+
+    // where a new mount goes and the disk under it, checked against the table. the
+    // superblock is read later, with the table unlocked
+    fn mount_point(
+        &self,
+        source: &str,
+        target: &Path,
+        fs_type: &str,
+        flags: MountFlags,
+    ) -> Result<(Path, &'static dyn FileSystemType, Option<u64>), i64> {
+        let target = self.realpath(target, true)?;
+        let node = self.resolve(target.clone()).ok_or(ENOENT)?;
+        if !node.node().is_dir() {
+            return Err(ENOTDIR);
+        }
+        // mounts are found by path, a tree can't show up in two places yet
+        if flags.intersects(MountFlags::BIND | MountFlags::MOVE) {
+            return Err(EINVAL);
+        }
+        let fs = get_filesystem(fs_type).ok_or(ENODEV)?;
+        let device = if fs.nodev() {
+            None
+        } else {
+            Some(self.block_source(source)?)
+        };
+        Ok((target, fs, device))
+    }
+
+    fn remount(&mut self, target: &Path, flags: MountFlags) -> Result<(), i64> {
+        let target = self.realpath(target, true)?;
+        let mount = self
+            .mounts
+            .iter_mut()
+            .rev()
+            .find(|m| m.target == target)
+            .ok_or(EINVAL)?;
+        if !flags.contains(MountFlags::RDONLY)
+            && get_filesystem(mount.sb.fs_type).is_some_and(|fs| fs.read_only())
+        {
+            return Err(EROFS);
+        }
+        mount.sb.flags = flags & MountFlags::PER_MOUNT;
+        Ok(())
+    }
+
+    // the device behind a block filesystem's source. there's one superblock per disk, so
+    // it can't be mounted twice
+    fn block_source(&self, source: &str) -> Result<u64, i64> {
+        if source.is_empty() {
+            return Err(ENOTBLK);
+        }
+        let node = self.walk(&Path::new(source), true)?;
+        let node = node.node();
+        if node.get_type() != &VfsNodeType::BlockDevice {
+            return Err(ENOTBLK);
+        }
+        let rdev = node.rdev();
+        block_device(rdev).ok_or(ENXIO)?;
+        if self.mounts.iter().any(|m| m.sb.device == Some(rdev)) {
+            return Err(EBUSY);
+        }
+        Ok(rdev)
+    }
+
+    // `busy` tells if something still has the mount open. the mounts taken out are
+    // handed back, dropping them can write to a disk so it's left for after the lock
+    fn detach(
+        &mut self,
+        target: &Path,
+        flags: UmountFlags,
+        busy: impl Fn(&Mount) -> bool,
+    ) -> Result<Vec<Mount>, i64> {
+        if flags.contains(UmountFlags::EXPIRE)
+            && flags.intersects(UmountFlags::FORCE | UmountFlags::DETACH)
+        {
+            return Err(EINVAL);
+        }
+        let follow = !flags.contains(UmountFlags::NOFOLLOW);
+        let target = self.realpath(target, follow)?;
+        let idx = self
+            .mounts
+            .iter()
+            .rposition(|m| m.target == target)
+            .ok_or(EINVAL)?;
+        if idx == 0 {
+            return Err(EBUSY);
+        }
+
+        let mount = &self.mounts[idx];
+        let nested: Vec<usize> = (idx + 1..self.mounts.len())
+            .filter(|&i| mount.covers(&self.mounts[i].target))
+            .collect();
+        let busy = !nested.is_empty() || busy(mount);
+        if busy && !flags.contains(UmountFlags::DETACH) {
+            return Err(EBUSY);
+        }
+
+        // whatever was mounted below goes along with it. open files keep their own
+        // references to the inodes, so a lazily detached tree lives on until they're closed
+        let mut detached: Vec<Mount> = nested
+            .into_iter()
+            .rev()
+            .map(|i| self.mounts.remove(i))
+            .collect();
+        detached.push(self.mounts.remove(idx));
+        Ok(detached)
+    }
+
+    // pivot_root(2), the mount on `new_root` becomes "/" and everything that isn't part
+    // of it moves under `put_old`, which has to be somewhere inside it
+    pub fn pivot_root(&mut self, new_root: &Path, put_old: &Path) -> Result<Pivot, i64> {
+        let new_root = self.realpath(new_root, true)?;
+        let put_old = self.realpath(put_old, true)?;
+        for path in [&new_root, &put_old] {
+            if !self.walk(path, true)?.node().is_dir() {
+                return Err(ENOTDIR);
+            }
+        }
+        if new_root.is_root() {
+            return Err(EBUSY);
+        }
+        let idx = self
+            .mounts
+            .iter()
+            .rposition(|m| m.target == new_root)
+            .ok_or(EINVAL)?;
+        let pivot = Pivot {
+            put_old: Path::new(&put_old.as_str()[new_root.as_str().len()..]),
+            new_root,
+        };
+        if !self.mounts[idx].covers(&put_old) {
+            return Err(EINVAL);
+        }
+
+        // the new root and what's mounted on top of it keep their order, whatever it
+        // was hiding goes along with the old root
+        let mut inside = Vec::new();
+        let mut outside = Vec::new();
+        for (i, mut mount) in core::mem::take(&mut self.mounts).into_iter().enumerate() {
+            let moved = i >= idx && pivot.is_inside(&mount.target);
+            mount.target = pivot.apply(&mount.target, moved);
+            if moved {
+                inside.push(mount);
+            } else {
+                outside.push(mount);
+            }
+        }
+        inside.append(&mut outside);
+        self.mounts = inside;
+        Ok(pivot)
+    }
+}
+
+// mount(2). the table is locked to check the request and again to add the mount, the
+// filesystem reads its superblock in between without it
+pub fn mount(
+    source: &str,
+    target: &Path,
+    fs_type: &str,
+    flags: MountFlags,
+    data: &str,
+) -> Result<(), i64> {
+    if flags.contains(MountFlags::REMOUNT) {
+        return get_vfs_mut().remount(target, flags);
+    }
+    let (target, fs, device) = get_vfs().mount_point(source, target, fs_type, flags)?;
+    let mount = Mount::new(fs, source, device, target, flags, data)?;
+
+    let mut vfs = get_vfs_mut();
+    // the disk may have been mounted by someone else meanwhile
+    if device.is_some() && vfs.mounts.iter().any(|m| m.sb.device == device) {
+        drop(vfs);
+        return Err(EBUSY);
+    }
+    vfs.mounts.push(mount);
+    Ok(())
+}
+
+// umount2(2), what comes off is dropped once the table is unlocked again
+pub fn umount(target: &Path, flags: UmountFlags, busy: impl Fn(&Mount) -> bool) -> Result<(), i64> {
+    let detached = get_vfs_mut().detach(target, flags, busy)?;
+    drop(detached);
+    // a superblock with nothing open on it just went, it writes itself out from here
+    flush_deferred();
+    Ok(())
+}
+
+// how paths changed in a pivot_root, for the working directories and open files
+pub struct Pivot {
+    new_root: Path,
+    // relative to the new root, empty if it's the new root itself
+    put_old: Path,
+}
+
+impl Pivot {
+    fn is_inside(&self, path: &Path) -> bool {
+        let rest = path.as_str().strip_prefix(self.new_root.as_str());
+        rest.is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    fn apply(&self, path: &Path, inside: bool) -> Path {
+        match (inside, path.as_str()) {
+            (true, _) => match &path.as_str()[self.new_root.as_str().len()..] {
+                "" => Path::new("/"),
+                rest => Path::new(rest),
+            },
+            (false, "/") if self.put_old.as_str().is_empty() => Path::new("/"),
+            (false, "/") => self.put_old.clone(),
+            (false, path) => Path::new(&format!("{}{path}", self.put_old.as_str())),
+        }
+    }
+
+    // where a canonical path is now. whatever was at the old root itself follows to the
+    // new one like on linux
+    pub fn moved(&self, path: &Path) -> Path {
+        match path.is_root() {
+            true => Path::new("/"),
+            false => self.apply(path, self.is_inside(path)),
+        }
+    }
+}
+
+pub fn init() {
+    register_filesystem(&RAMFS);
+    register_filesystem(&TMPFS);
+    register_filesystem(&DEVFS);
+    register_filesystem(&PROCFS);
+    register_filesystem(&TARFS);
+    register_filesystem(&VFAT);
+    register_filesystem(&EXT2);
+    register_filesystem(&ISO9660);
+}
+
+// /dev/root on /sysroot, for the initramfs to pivot_root into when it's done. the
+// config's rootfstype says what's on it, otherwise every disk filesystem gets a try
+pub fn mount_root() {
+    if get_vfs().resolve(Path::new("/dev/root")).is_none() {
+        return;
+    }
+    let config = get_config();
+    let fs_type = config.rootfstype.to_str();
+    let candidates: Vec<&str> = match fs_type {
+        "" => filesystems()
+            .into_iter()
+            .filter(|fs| !fs.nodev())
+            .map(|fs| fs.name())
+            .collect(),
+        fs_type => vec![fs_type],
+    };
+
+    let target = Path::new("/sysroot");
+    make_mountpoint(&target);
+    let mut last = ENODEV;
+    for fs_type in candidates {
+        match mount("/dev/root", &target, fs_type, MountFlags::empty(), "") {
+            Ok(()) => {
+                info!("mounted /dev/root ({fs_type}) on /sysroot");
+                return;
+            }
+            Err(e) => last = e,
+        }
+    }
+    warn!("couldn't mount /dev/root on /sysroot, errno {last}");
+}
+
+// the first cd drive on /cdrom, for whatever the iso carries that isn't packed into the
+// initramfs
+pub fn mount_cdrom() {
+    let Some((_, device)) = block_devices()
+        .into_iter()
+        .find(|&(rdev, _)| major(rdev) == SR_MAJOR)
+    else {
+        return;
+    };
+    let source = format!("/dev/{}", device.name());
+    let target = Path::new("/cdrom");
+    make_mountpoint(&target);
+    match mount(&source, &target, "iso9660", MountFlags::RDONLY, "") {
+        Ok(()) => info!("mounted {source} on /cdrom"),
+        Err(e) => warn!("couldn't mount {source} on /cdrom, errno {e}"),
+    }
+}
+
+// a directory in the root for the boot mounts to go on, if there isn't one
+fn make_mountpoint(target: &Path) {
+    let root = get_vfs().get_root().clone();
+    if root.node().get_child(target.get_name()).is_none() {
+        let _ = root.node_mut().create_dir(target.get_name());
+    }
+}
@@ -0,0 +1,78 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::any::Any;
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{memory::shared::SharedMemory, scheduler::wait::WaitQueue, utils::errno::*};
+
+bitflags::bitflags! {
+    // same bits as POLLIN and friends, epoll uses them too
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PollEvents: u32 {
+        const IN = 0x001;
+        const PRI = 0x002;
+        const OUT = 0x004;
+        const ERR = 0x008;
+        const HUP = 0x010;
+        const NVAL = 0x020;
+        const RDHUP = 0x2000;
+    }
+}
+
+// open files that aren't backed by a vfs node (sockets and friends)
+// errors are positive errno values from utils::errno
+pub trait FileObject: Any + Send + Sync {
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64>;
+    fn write(&self, buf: &[u8], nonblock: bool) -> Result<usize, i64>;
+    // S_IFMT bits reported by fstat, anonymous objects have none
+    fn mode(&self) -> u32 {
+        0
+    }
+    // current readiness, must not block
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+    // queues that get woken when poll() might have changed
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        Vec::new()
+    }
+    // for timers: when poll() changes on its own, in preferred_timer_ns time
+    fn deadline(&self) -> Option<u64> {
+        None
+    }
+    // seekable objects (memfd, block devices) have a size and get positional io with the
+    // fd offset instead of read/write. they don't wait on other processes, only on disks
+    fn size(&self) -> Option<u64> {
+        None
+    }
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Result<usize, i64> {
+        Err(ESPIPE)
+    }
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(ESPIPE)
+    }
+    fn truncate(&self, _len: u64) -> Result<(), i64> {
+        Err(EINVAL)
+    }
+    // fsync, only seekable objects have anywhere to sync to
+    fn sync(&self) -> Result<(), i64> {
+        if self.size().is_some() {
+            Ok(())
+        } else {
+            Err(EINVAL)
+        }
+    }
+    // frames to map for mmap(MAP_SHARED)
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        None
+    }
+    // device specific requests, `arg` is whatever the request says it is (often a user
+    // pointer). ENOTTY for everything the object doesn't know
+    fn ioctl(&self, _request: u64, _arg: u64) -> Result<u64, i64> {
+        Err(ENOTTY)
+    }
+}
@@ -0,0 +1,576 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::{fmt::Write, sync::atomic::Ordering};
+
+use alloc::collections::btree_map::BTreeMap;
+
+use crate::{
+    arch::{CPU_FREQ, drivers::time::preferred_timer_ns},
+    memory::{
+        get_heap_stats, get_reserved_memory, get_usable_memory,
+        mmap::Backing as MapBacking,
+        vmm::{flag, page_size},
+    },
+    scheduler::{Process, current_process, get_proc_by_pid, get_scheduler, thread::Status},
+    utils::{asm::without_ints, errno::*},
+};
+
+use super::*;
+
+// nothing in here is stored, every lookup and listing asks the scheduler again
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirKind {
+    Root,
+    Pid(u64),
+    Fd(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    Status(u64),
+    Cmdline(u64),
+    Stat(u64),
+    Maps(u64),
+    Meminfo,
+    Cpuinfo,
+    Uptime,
+    Mounts,
+    Partitions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    Dir(DirKind),
+    File(FileKind),
+    Link(String),
+}
+
+fn processes() -> Vec<Arc<Spin<Process>>> {
+    without_ints(|| get_scheduler().processes.clone())
+}
+
+// what ps shows, the program last exec'd rather than what the process was spawned as
+fn comm(proc: &Process) -> &str {
+    match proc.get_cmdline().first() {
+        Some(arg0) => arg0.rsplit('/').next().unwrap_or(arg0),
+        None => proc.get_name(),
+    }
+}
+
+fn state(proc: &Process) -> (char, &'static str) {
+    if proc.get_exit_status().is_some() {
+        return ('Z', "zombie");
+    }
+    let statuses: Vec<Status> = proc
+        .get_children()
+        .iter()
+        .map(|t| t.lock().get_status())
+        .collect();
+    if statuses
+        .iter()
+        .any(|s| matches!(s, Status::Running | Status::Ready))
+    {
+        ('R', "running")
+    } else if statuses
+        .iter()
+        .any(|s| matches!(s, Status::Sleeping(_) | Status::Blocked))
+    {
+        ('S', "sleeping")
+    } else {
+        ('Z', "zombie")
+    }
+}
+
+// in clock ticks, USER_HZ is 100
+fn runtime_ticks(proc: &Process) -> u64 {
+    let ns: u64 = proc.get_children().iter().map(|t| t.lock().runtime).sum();
+    ns / 10_000_000
+}
+
+// only mmap'd memory is tracked, the elf and the stacks aren't in here
+fn vm_size(proc: &Process) -> u64 {
+    proc.get_mappings().iter().map(|m| m.len).sum()
+}
+
+fn rss_pages(proc: &Process) -> u64 {
+    proc.get_mappings()
+        .iter()
+        .filter(|m| m.flags & flag::PRESENT != 0)
+        .map(|m| m.len / page_size::SMALL)
+        .sum()
+}
+
+fn fd_target(file: &FileDescriptor) -> String {
+    if let Some(path) = &file.path {
+        return path.to_string();
+    }
+    match (file.inode(), file.object()) {
+        (Some(inode), _) => format!("anon_inode:[{}]", inode.ino),
+        (None, Some(object)) => {
+            let id = Arc::as_ptr(object) as *const () as usize;
+            match object.mode() & 0o170000 {
+                0o140000 => format!("socket:[{id}]"),
+                0o010000 => format!("pipe:[{id}]"),
+                _ => format!("anon_inode:[{id}]"),
+            }
+        }
+        (None, None) => String::new(),
+    }
+}
+
+fn status(proc: &Process) -> String {
+    let (state, state_name) = state(proc);
+    format!(
+        "Name:\t{}\nState:\t{} ({})\nTgid:\t{}\nPid:\t{}\nPPid:\t{}\nFDSize:\t{}\nVmSize:\t{} kB\nVmRSS:\t{} kB\nThreads:\t{}\n",
+        comm(proc),
+        state,
+        state_name,
+        proc.get_pid(),
+        proc.get_pid(),
+        proc.get_ppid(),
+        proc.fdt.len(),
+        vm_size(proc) / 1024,
+        rss_pages(proc) * page_size::SMALL / 1024,
+        proc.get_children().len(),
+    )
+}
+
+// the fields up to rss, what ps and top read. no process groups or sessions yet
+fn stat(proc: &Process) -> String {
+    let pid = proc.get_pid();
+    format!(
+        "{} ({}) {} {} {} {} 0 -1 0 0 0 0 0 {} 0 0 0 20 0 {} 0 0 {} {}\n",
+        pid,
+        comm(proc),
+        state(proc).0,
+        proc.get_ppid(),
+        pid,
+        pid,
+        runtime_ticks(proc),
+        proc.get_children().len(),
+        vm_size(proc),
+        rss_pages(proc),
+    )
+}
+
+fn maps(proc: &Process) -> String {
+    let mut out = String::new();
+    for m in proc.get_mappings().iter() {
+        let present = m.flags & flag::PRESENT != 0;
+        let (shared, offset) = match &m.backing {
+            MapBacking::Private => ('p', 0),
+            MapBacking::Shared { offset, .. } => ('s', *offset),
+        };
+        let _ = writeln!(
+            out,
+            "{:08x}-{:08x} {}{}{}{} {:08x} 00:00 0",
+            m.start,
+            m.end(),
+            if present { 'r' } else { '-' },
+            if present && m.flags & flag::WRITE != 0 {
+                'w'
+            } else {
+                '-'
+            },
+            if present && m.flags & flag::NO_EXEC == 0 {
+                'x'
+            } else {
+                '-'
+            },
+            shared,
+            offset,
+        );
+    }
+    out
+}
+
+fn meminfo() -> String {
+    let heap = get_heap_stats();
+    let (buffers, dirty) = crate::device::block::cache::stats();
+    let mut out = String::new();
+    for (name, bytes) in [
+        ("MemTotal:", get_usable_memory()),
+        ("MemFree:", heap.available_bytes as u64),
+        ("MemAvailable:", heap.available_bytes as u64),
+        ("Buffers:", buffers),
+        ("Dirty:", dirty),
+        ("Reserved:", get_reserved_memory()),
+        ("HeapTotal:", heap.claimed_bytes as u64),
+        ("HeapUsed:", heap.allocated_bytes as u64),
+    ] {
+        let _ = writeln!(out, "{:<16}{:>8} kB", name, bytes / 1024);
+    }
+    out
+}
+
+fn cpuinfo() -> String {
+    let freq = CPU_FREQ.load(Ordering::Relaxed);
+    format!(
+        "processor\t: 0\nmodel name\t: {}\ncpu MHz\t\t: {}.{:03}\n\n",
+        crate::utils::asm::get_cpu(),
+        freq / 1_000_000,
+        (freq % 1_000_000) / 1_000,
+    )
+}
+
+fn uptime() -> String {
+    let up = preferred_timer_ns() / 10_000_000;
+    let idle = crate::scheduler::thread::idle0().lock().runtime / 10_000_000;
+    format!(
+        "{}.{:02} {}.{:02}\n",
+        up / 100,
+        up % 100,
+        idle / 100,
+        idle % 100
+    )
+}
+
+// like /proc/mounts: source, target, type, options and two zeroes nobody reads
+fn mounts() -> String {
+    let vfs = get_vfs();
+    let mut out = String::new();
+    for mount in &vfs.mounts {
+        let sb = &mount.sb;
+        let mut options = String::from(if mount.is_readonly() { "ro" } else { "rw" });
+        for (flag, name) in [
+            (MountFlags::NOSUID, "nosuid"),
+            (MountFlags::NODEV, "nodev"),
+            (MountFlags::NOEXEC, "noexec"),
+            (MountFlags::NOATIME, "noatime"),
+            (MountFlags::NODIRATIME, "nodiratime"),
+            (MountFlags::RELATIME, "relatime"),
+        ] {
+            if sb.flags.contains(flag) {
+                options.push(',');
+                options.push_str(name);
+            }
+        }
+        if !sb.data.is_empty() {
+            options.push(',');
+            options.push_str(&sb.data);
+        }
+        let _ = writeln!(
+            out,
+            "{} {} {} {} 0 0",
+            sb.source, mount.target, sb.fs_type, options
+        );
+    }
+    out
+}
+
+// every block device, sizes in 1k blocks
+fn partitions() -> String {
+    let mut out = String::from("major minor  #blocks  name\n\n");
+    for (rdev, device) in crate::device::block::block_devices() {
+        let _ = writeln!(
+            out,
+            "{:4} {:7} {:10} {}",
+            major(rdev),
+            minor(rdev),
+            device.capacity() / 1024,
+            device.name()
+        );
+    }
+    out
+}
+
+// ESRCH once the process is gone
+fn generate(kind: FileKind) -> Result<String, i64> {
+    let with_proc = |pid: u64, f: fn(&Process) -> String| {
+        get_proc_by_pid(pid)
+            .map(|proc| f(&proc.lock()))
+            .ok_or(ESRCH)
+    };
+    match kind {
+        FileKind::Status(pid) => with_proc(pid, status),
+        FileKind::Cmdline(pid) => with_proc(pid, |proc| {
+            // every argument nul terminated
+            proc.get_cmdline()
+                .iter()
+                .map(|a| format!("{a}\0"))
+                .collect()
+        }),
+        FileKind::Stat(pid) => with_proc(pid, stat),
+        FileKind::Maps(pid) => with_proc(pid, maps),
+        FileKind::Meminfo => Ok(meminfo()),
+        FileKind::Cpuinfo => Ok(cpuinfo()),
+        FileKind::Uptime => Ok(uptime()),
+        FileKind::Mounts => Ok(mounts()),
+        FileKind::Partitions => Ok(partitions()),
+    }
+}
+
+pub struct ProcDir {
+    kind: DirKind,
+    // inodes handed out so far, so the same name keeps its inode number
+    cache: Spin<BTreeMap<String, (Entry, InodeRef)>>,
+    metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for ProcDir {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}, {:?}", self.kind, self.metadata)
+    }
+}
+
+impl ProcDir {
+    fn new(kind: DirKind) -> Self {
+        let epoch = read_rtc().to_epoch().unwrap_or_default();
+        let mut metadata = VfsNodeMetadata::new(VfsNodeType::Directory)
+            .with_permissions(NodeMode::READ | NodeMode::EXECUTE)
+            .with_created_at(epoch)
+            .with_modified_at(epoch);
+        // "." and the entry in the parent, the mount takes care of that for the root
+        metadata.nlink = if kind == DirKind::Root { 1 } else { 2 };
+        Self {
+            kind,
+            cache: Spin::new(BTreeMap::new()),
+            metadata,
+        }
+    }
+
+    fn entries(&self) -> Vec<(String, Entry)> {
+        match self.kind {
+            DirKind::Root => {
+                let mut entries = Vec::new();
+                if let Some(current) = current_process() {
+                    let pid = current.lock().get_pid();
+                    entries.push((String::from("self"), Entry::Link(pid.to_string())));
+                }
+                for (name, kind) in [
+                    ("meminfo", FileKind::Meminfo),
+                    ("cpuinfo", FileKind::Cpuinfo),
+                    ("uptime", FileKind::Uptime),
+                    ("mounts", FileKind::Mounts),
+                    ("partitions", FileKind::Partitions),
+                ] {
+                    entries.push((String::from(name), Entry::File(kind)));
+                }
+                for proc in processes() {
+                    let pid = proc.lock().get_pid();
+                    entries.push((pid.to_string(), Entry::Dir(DirKind::Pid(pid))));
+                }
+                entries
+            }
+            DirKind::Pid(pid) => {
+                let Some(proc) = get_proc_by_pid(pid) else {
+                    return Vec::new();
+                };
+                let cwd = proc.lock().get_cwd().to_string();
+                vec![
+                    (String::from("status"), Entry::File(FileKind::Status(pid))),
+                    (String::from("cmdline"), Entry::File(FileKind::Cmdline(pid))),
+                    (String::from("stat"), Entry::File(FileKind::Stat(pid))),
+                    (String::from("maps"), Entry::File(FileKind::Maps(pid))),
+                    (String::from("cwd"), Entry::Link(cwd)),
+                    (String::from("fd"), Entry::Dir(DirKind::Fd(pid))),
+                ]
+            }
+            DirKind::Fd(pid) => {
+                let Some(proc) = get_proc_by_pid(pid) else {
+                    return Vec::new();
+                };
+                let proc = proc.lock();
+                proc.fdt
+                    .iter()
+                    .map(|(fd, file)| (fd.to_string(), Entry::Link(fd_target(file))))
+                    .collect()
+            }
+        }
+    }
+
+    // a new inode when something else took the name, e.g. an fd that got reopened
+    fn inode_for(&self, name: &str, entry: Entry) -> InodeRef {
+        let mut cache = self.cache.lock();
+        if let Some((cached, inode)) = cache.get(name)
+            && *cached == entry
+        {
+            return inode.clone();
+        }
+        let inode = match &entry {
+            Entry::Dir(kind) => Inode::new(ProcDir::new(*kind)),
+            Entry::File(kind) => Inode::new(ProcFile::new(*kind)),
+            Entry::Link(target) => {
+                let inode = Inode::new(Symlink::new(target));
+                inode.node_mut().get_metadata_mut().nlink = 1;
+                inode
+            }
+        };
+        cache.insert(String::from(name), (entry, inode.clone()));
+        inode
+    }
+}
+
+impl VfsNode for ProcDir {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn get_child(&self, name: &str) -> Option<InodeRef> {
+        match self.entries().into_iter().find(|(n, _)| n == name) {
+            Some((_, entry)) => Some(self.inode_for(name, entry)),
+            None => {
+                self.cache.lock().remove(name);
+                None
+            }
+        }
+    }
+    fn get_children(&self) -> Vec<DirEntry> {
+        let entries = self.entries();
+        // dead processes and closed fds
+        self.cache
+            .lock()
+            .retain(|name, _| entries.iter().any(|(n, _)| n == name));
+        entries
+            .into_iter()
+            .map(|(name, entry)| DirEntry {
+                inode: self.inode_for(&name, entry),
+                name,
+            })
+            .collect()
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EISDIR)
+    }
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EISDIR)
+    }
+    fn set_xattr(&mut self, _name: &str, _value: &[u8]) -> Result<(), i64> {
+        Err(EOPNOTSUPP)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcFile {
+    kind: FileKind,
+    metadata: VfsNodeMetadata,
+}
+
+impl ProcFile {
+    fn new(kind: FileKind) -> Self {
+        let epoch = read_rtc().to_epoch().unwrap_or_default();
+        let mut metadata = VfsNodeMetadata::new(VfsNodeType::File)
+            .with_permissions(NodeMode::READ)
+            .with_created_at(epoch)
+            .with_modified_at(epoch);
+        metadata.nlink = 1;
+        Self { kind, metadata }
+    }
+}
+
+impl VfsNode for ProcFile {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    // like linux, the size isn't known until it's read
+    fn size(&self) -> u64 {
+        0
+    }
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        let data = generate(self.kind).ok()?;
+        let data = data.as_bytes().get(offset as usize..).unwrap_or_default();
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Some(n)
+    }
+    fn write_at(&mut self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EACCES)
+    }
+    fn truncate(&mut self, _len: u64) -> Result<(), i64> {
+        Err(EACCES)
+    }
+    fn set_xattr(&mut self, _name: &str, _value: &[u8]) -> Result<(), i64> {
+        Err(EOPNOTSUPP)
+    }
+    // generated once per open so reading it in pieces sees one consistent version
+    fn open(&self) -> Result<Option<Arc<dyn FileObject>>, i64> {
+        let data = generate(self.kind)?.into_bytes();
+        Ok(Some(Arc::new(Snapshot { data })))
+    }
+}
+
+struct Snapshot {
+    data: Vec<u8>,
+}
+
+impl FileObject for Snapshot {
+    // do_read goes through read_at since we have a size
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(ESPIPE)
+    }
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EACCES)
+    }
+    fn mode(&self) -> u32 {
+        0o100000
+    }
+    fn size(&self) -> Option<u64> {
+        Some(self.data.len() as u64)
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, i64> {
+        let data = self.data.get(offset as usize..).unwrap_or_default();
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> Result<usize, i64> {
+        Err(EACCES)
+    }
+}
+
+pub const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+
+pub struct ProcFs;
+
+impl FileSystemType for ProcFs {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+    fn magic(&self) -> u64 {
+        PROC_SUPER_MAGIC
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, _data: &str) -> Result<InodeRef, i64> {
+        Ok(Inode::new(ProcDir::new(DirKind::Root)))
+    }
+}
+
+pub static PROCFS: ProcFs = ProcFs;
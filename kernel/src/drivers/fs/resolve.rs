@@ -0,0 +1,117 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::collections::vec_deque::VecDeque;
+
+use crate::utils::errno::*;
+
+use super::*;
+
+// links followed in one lookup before giving up with ELOOP, same as linux
+pub const SYMLOOP_MAX: usize = 40;
+
+impl Vfs {
+    // the newest mount sitting right on `path` (canonical)
+    fn mounted_on(&self, path: &str) -> Option<&Mount> {
+        self.mounts.iter().rev().find(|m| m.target.as_str() == path)
+    }
+
+    // one component at a time, crossing into mounts and following symlinks. `follow`
+    // is only about the last component, lstat and friends want the link itself.
+    // also gives the components of the path with every link and ".." resolved.
+    // only one directory is locked at a time, for as long as it takes to find the next.
+    // so never walk while holding a node lock yourself, it may be one on the way.
+    // whatever is found is stamped with the mount it was found through, for fstat and
+    // the like on open files
+    fn walk_path(&self, path: &Path, follow: bool) -> Result<(Vec<String>, InodeRef), i64> {
+        let root_mount = self.mounted_on("/").ok_or(ENOENT)?;
+        let root = (root_mount.root(), root_mount.sb.dev);
+        let mut todo: VecDeque<String> = path
+            .as_str()
+            .split('/')
+            .filter(|p| !p.is_empty())
+            .map(String::from)
+            .collect();
+        // names, inodes and their mounts from the root down to where we are
+        let mut stack: Vec<(String, InodeRef, u64)> = Vec::new();
+        let mut links = 0;
+
+        while let Some(part) = todo.pop_front() {
+            match part.as_str() {
+                "." => continue,
+                ".." => {
+                    stack.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            let (current, dev) = stack.last().map_or(root, |(_, inode, dev)| (inode, *dev));
+
+            let mut here = String::new();
+            for (name, ..) in &stack {
+                here.push('/');
+                here.push_str(name);
+            }
+            here.push('/');
+            here.push_str(&part);
+            let (inode, dev) = {
+                let dir = current.node();
+                if !dir.is_dir() {
+                    return Err(ENOTDIR);
+                }
+                match self.mounted_on(&here) {
+                    Some(mount) => (mount.root().clone(), mount.sb.dev),
+                    None => (dir.get_child(&part).ok_or(ENOENT)?, dev),
+                }
+            };
+
+            let target = inode.node().readlink().map(String::from);
+            if let Some(target) = target
+                && (follow || !todo.is_empty())
+            {
+                links += 1;
+                if links > SYMLOOP_MAX {
+                    return Err(ELOOP);
+                }
+                if target.is_empty() {
+                    return Err(ENOENT);
+                }
+                // relative targets carry on from the link's directory
+                if target.starts_with('/') {
+                    stack.clear();
+                }
+                for p in target.split('/').filter(|p| !p.is_empty()).rev() {
+                    todo.push_front(String::from(p));
+                }
+                continue;
+            }
+            stack.push((part, inode, dev));
+        }
+
+        let (inode, dev) = stack.last().map_or(root, |(_, inode, dev)| (inode, *dev));
+        let inode = inode.clone();
+        inode.set_dev(dev);
+        Ok((stack.into_iter().map(|(name, ..)| name).collect(), inode))
+    }
+
+    pub fn walk(&self, path: &Path, follow: bool) -> Result<InodeRef, i64> {
+        self.walk_path(path, follow).map(|(_, inode)| inode)
+    }
+
+    // the canonical path with no symlinks in it, like realpath(3)
+    pub fn realpath(&self, path: &Path, follow: bool) -> Result<Path, i64> {
+        let (names, _) = self.walk_path(path, follow)?;
+        Ok(Path::new(&format!("/{}", names.join("/"))))
+    }
+
+    // where `path` really lives: its directory resolved, the last component taken as is
+    // and not required to exist. lexical if even the directory is missing
+    pub fn physical(&self, path: &Path) -> Option<Path> {
+        match self.realpath(&path.get_parent(), true) {
+            Ok(parent) => canonicalize(&parent.join(path.get_name())),
+            Err(_) => canonicalize(path),
+        }
+    }
+}
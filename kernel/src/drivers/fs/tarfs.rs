@@ -0,0 +1,290 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::collections::btree_map::BTreeMap;
+
+use limine::file::File as Module;
+
+use crate::utils::{
+    archive::{Entries, Entry, EntryKind},
+    compress::Compression,
+    errno::*,
+    limine::get_modules,
+};
+
+use super::*;
+
+// "tarf", there's no real one for this
+pub const TARFS_MAGIC: u64 = 0x7461_7266;
+
+// a file straight out of a boot module, copied up into the root's tmpfs the first time
+// it's written so the copy counts against its size
+pub struct TarFile {
+    data: &'static [u8],
+    // None on a tarfs mount, the module itself never changes
+    copy_up: Option<Arc<TmpfsSb>>,
+    copied: Option<TmpFile>,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for TarFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "copied up: {}, {:?}",
+            self.copied.is_some(),
+            self.metadata
+        )
+    }
+}
+
+impl TarFile {
+    pub fn new(data: &'static [u8], copy_up: Option<Arc<TmpfsSb>>) -> Self {
+        let mut metadata = VfsNodeMetadata::new(VfsNodeType::File);
+        metadata.size = data.len() as u64;
+        Self {
+            data,
+            copy_up,
+            copied: None,
+            metadata,
+        }
+    }
+
+    // the tmpfs copy with the first `keep` bytes of the module in it, a truncate only
+    // needs what it keeps
+    fn copy(&mut self, keep: u64) -> Result<&mut TmpFile, i64> {
+        if self.copied.is_none() {
+            let sb = self.copy_up.clone().ok_or(EROFS)?;
+            let mut file = TmpFile::new(sb);
+            let keep = &self.data[..(keep as usize).min(self.data.len())];
+            if !keep.is_empty() && file.write_at(0, keep)? < keep.len() {
+                return Err(ENOSPC);
+            }
+            file.truncate(self.data.len() as u64)?;
+            self.copied = Some(file);
+        }
+        Ok(self.copied.as_mut().unwrap())
+    }
+}
+
+impl VfsNode for TarFile {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn size(&self) -> u64 {
+        self.metadata.size
+    }
+    fn read(&self) -> Option<&[u8]> {
+        match self.copied {
+            Some(_) => None,
+            None => Some(self.data),
+        }
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        self.metadata.touch_accessed();
+        if let Some(file) = &self.copied {
+            return file.read_at(offset, buf);
+        }
+        let offset = (offset as usize).min(self.data.len());
+        let n = (self.data.len() - offset).min(buf.len());
+        buf[..n].copy_from_slice(&self.data[offset..offset + n]);
+        Some(n)
+    }
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        let file = self.copy(u64::MAX)?;
+        let n = file.write_at(offset, buf)?;
+        self.metadata.size = file.size();
+        self.metadata.touch_modified();
+        Ok(n)
+    }
+    fn truncate(&mut self, len: u64) -> Result<(), i64> {
+        let file = self.copy(len)?;
+        file.truncate(len)?;
+        self.metadata.size = len;
+        self.metadata.touch_modified();
+        Ok(())
+    }
+    fn blocks(&self) -> u64 {
+        match &self.copied {
+            Some(file) => file.blocks(),
+            None => self.size().div_ceil(512),
+        }
+    }
+}
+
+// makes the directories on the way that the archive didn't list itself
+fn dir_at(root: &InodeRef, path: &str) -> Option<InodeRef> {
+    let mut dir = root.clone();
+    for name in path.split('/').filter(|n| !n.is_empty()) {
+        let child = dir.node().get_child(name);
+        let next = match child {
+            Some(child) => child,
+            None => dir.node_mut().create_dir(name).ok()?,
+        };
+        if !next.node().is_dir() {
+            return None;
+        }
+        dir = next;
+    }
+    Some(dir)
+}
+
+fn lookup(root: &InodeRef, path: &str) -> Option<InodeRef> {
+    let mut inode = root.clone();
+    for name in path.split('/').filter(|n| !n.is_empty()) {
+        let child = inode.node().get_child(name)?;
+        inode = child;
+    }
+    Some(inode)
+}
+
+// "./usr/bin/" -> "usr/bin", "." -> ""
+fn archive_path(name: &str) -> &str {
+    match name.strip_prefix("./").unwrap_or(name).trim_matches('/') {
+        "." => "",
+        path => path,
+    }
+}
+
+fn stamp(inode: &InodeRef, item: &Entry) {
+    let mut node = inode.node_mut();
+    let metadata = node.get_metadata_mut();
+    metadata.permissions = NodeMode::from_bits_truncate(item.mode as i32);
+    metadata.modified_at = item.mtime;
+    metadata.changed_at = item.mtime;
+}
+
+// decompressed modules by where they were loaded, kept for good since files point into them
+static UNPACKED: Spin<BTreeMap<usize, &'static [u8]>> = Spin::new(BTreeMap::new());
+
+// the archive in a boot module, decompressed the first time it's asked for
+pub fn module_archive(module: &'static Module) -> Result<&'static [u8], &'static str> {
+    let data = module.data();
+    let Some(compression) = Compression::detect(data) else {
+        return Ok(data);
+    };
+    let key = data.as_ptr() as usize;
+    if let Some(&unpacked) = UNPACKED.lock().get(&key) {
+        return Ok(unpacked);
+    }
+    let unpacked: &'static [u8] = compression.decompress(data)?.leak();
+    info!(
+        "unpacked {:?} {}, {} -> {} bytes",
+        compression,
+        module.path(),
+        data.len(),
+        unpacked.len()
+    );
+    UNPACKED.lock().insert(key, unpacked);
+    Ok(unpacked)
+}
+
+// one pass over a tar or cpio archive, files point into it instead of being copied out.
+// later entries replace earlier ones with the same name
+pub fn populate(
+    root: &InodeRef,
+    archive: &'static [u8],
+    copy_up: Option<Arc<TmpfsSb>>,
+) -> Result<(), &'static str> {
+    let entries = Entries::new(archive).ok_or("not a tar or cpio archive")?;
+    for item in entries {
+        let path = archive_path(&item.name);
+        if path.is_empty() {
+            continue;
+        }
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (dir_at(root, parent), name),
+            None => (Some(root.clone()), path),
+        };
+        let Some(parent) = parent else {
+            warn!("couldn't unpack {}, a parent isn't a directory", path);
+            continue;
+        };
+
+        debug!("creating {:?} - {}", item.kind, path);
+        let inode = match item.kind {
+            EntryKind::Directory => {
+                let existing = parent.node().get_child(name);
+                let dir = match existing.filter(|c| c.node().is_dir()) {
+                    Some(dir) => dir,
+                    None => {
+                        let mut parent = parent.node_mut();
+                        parent.unlink(name);
+                        let Ok(dir) = parent.create_dir(name) else {
+                            continue;
+                        };
+                        dir
+                    }
+                };
+                stamp(&dir, &item);
+                continue;
+            }
+            EntryKind::File => Inode::new(TarFile::new(item.data, copy_up.clone())),
+            EntryKind::Symlink => Inode::new(Symlink::new(item.link)),
+            // the same inode, times and mode included
+            EntryKind::Hardlink => match lookup(root, archive_path(item.link)) {
+                Some(target) if !target.node().is_dir() => {
+                    let mut parent = parent.node_mut();
+                    parent.unlink(name);
+                    let _ = parent.link(name, target);
+                    continue;
+                }
+                _ => {
+                    warn!("couldn't link {} to {}", path, item.link);
+                    continue;
+                }
+            },
+            EntryKind::CharDevice | EntryKind::BlockDevice | EntryKind::Fifo => continue,
+        };
+        stamp(&inode, &item);
+        let mut parent = parent.node_mut();
+        parent.unlink(name);
+        let _ = parent.link(name, inode);
+    }
+    Ok(())
+}
+
+// mounts a boot module's archive read-only, the source is its path or its cmdline from
+// limine.conf
+pub struct TarFs;
+
+impl FileSystemType for TarFs {
+    fn name(&self) -> &'static str {
+        "tarfs"
+    }
+    fn magic(&self) -> u64 {
+        TARFS_MAGIC
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn mount(&self, source: &str, _flags: MountFlags, data: &str) -> Result<InodeRef, i64> {
+        if !data.is_empty() {
+            return Err(EINVAL);
+        }
+        let module = get_modules()
+            .iter()
+            .find(|m| m.path() == source || m.cmdline() == source)
+            .ok_or(ENOENT)?;
+        let archive = module_archive(module).map_err(|_| EINVAL)?;
+        let root = Inode::new(Directory::new());
+        populate(&root, archive, None).map_err(|_| EINVAL)?;
+        Ok(root)
+    }
+}
+
+pub static TARFS: TarFs = TarFs;
@@ -0,0 +1,348 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::collections::btree_map::{BTreeMap, Entry};
+
+use crate::memory::get_usable_memory;
+
+use super::*;
+
+const PAGE: u64 = 4096;
+
+pub const TMPFS_MAGIC: u64 = 0x0102_1994;
+
+// what one inode costs out of the metadata space, linux uses the same figure
+const BOGO_INODE_SIZE: u64 = 1024;
+
+// one per mount, every file on it charges its pages here
+#[derive(Debug)]
+pub struct TmpfsSb {
+    // in pages
+    limit: u64,
+    used: AtomicU64,
+    // bytes of heap that isn't file data (entries, names, symlinks, xattrs), the nr_inodes
+    // option sets it in BOGO_INODE_SIZE units
+    meta_limit: u64,
+    meta_used: AtomicU64,
+}
+
+impl TmpfsSb {
+    pub fn new(limit: u64, inodes: u64) -> Arc<Self> {
+        Arc::new(Self {
+            limit: limit.div_ceil(PAGE),
+            used: AtomicU64::new(0),
+            meta_limit: inodes.saturating_mul(BOGO_INODE_SIZE),
+            meta_used: AtomicU64::new(0),
+        })
+    }
+
+    fn charge(&self, pages: u64) -> Result<(), i64> {
+        take(&self.used, self.limit, pages)
+    }
+
+    fn uncharge(&self, pages: u64) {
+        self.used.fetch_sub(pages, Ordering::Relaxed);
+    }
+
+    pub(super) fn charge_meta(&self, bytes: u64) -> Result<(), i64> {
+        take(&self.meta_used, self.meta_limit, bytes)
+    }
+
+    pub(super) fn uncharge_meta(&self, bytes: u64) {
+        self.meta_used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    // what a directory entry for `node` costs, the inode behind it and a symlink's target
+    // included
+    pub(super) fn entry_cost(name: &str, node: &dyn VfsNode) -> u64 {
+        BOGO_INODE_SIZE + (name.len() + node.readlink().map_or(0, str::len)) as u64
+    }
+
+    pub(super) fn set_xattr(
+        &self,
+        xattrs: &mut BTreeMap<String, Vec<u8>>,
+        name: &str,
+        value: &[u8],
+    ) -> Result<(), i64> {
+        let old = xattrs.get(name).map_or(0, |v| xattr_cost(name, v));
+        let new = xattr_cost(name, value);
+        if new > old {
+            self.charge_meta(new - old)?;
+        } else {
+            self.uncharge_meta(old - new);
+        }
+        xattrs.insert(name.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    pub(super) fn remove_xattr(
+        &self,
+        xattrs: &mut BTreeMap<String, Vec<u8>>,
+        name: &str,
+    ) -> Result<(), i64> {
+        let value = xattrs.remove(name).ok_or(ENODATA)?;
+        self.uncharge_meta(xattr_cost(name, &value));
+        Ok(())
+    }
+
+    // gives back what a node's xattrs were charged, for when it goes away
+    pub(super) fn drop_xattrs(&self, xattrs: &BTreeMap<String, Vec<u8>>) {
+        let cost = xattrs.iter().map(|(name, v)| xattr_cost(name, v)).sum();
+        self.uncharge_meta(cost);
+    }
+
+    // for files that hold every page up to their size (/dev/shm), going from `old` to `new`
+    // bytes charges or gives back the difference
+    pub fn recharge(&self, old: u64, new: u64) -> Result<(), i64> {
+        let (old, new) = (old.div_ceil(PAGE), new.div_ceil(PAGE));
+        if new > old {
+            self.charge(new - old)
+        } else {
+            self.uncharge(old - new);
+            Ok(())
+        }
+    }
+
+    // half of memory, same as linux. what a mount gets without size= and what the root
+    // and devfs are made with
+    pub fn default_size() -> u64 {
+        get_usable_memory() / 2
+    }
+
+    // half as many inodes as there are pages of memory, also like linux
+    pub fn default_inodes() -> u64 {
+        get_usable_memory() / PAGE / 2
+    }
+
+    pub fn stats(&self) -> FsStats {
+        let free = self.limit - self.used.load(Ordering::Relaxed);
+        FsStats {
+            blocks: self.limit,
+            bfree: free,
+            bavail: free,
+        }
+    }
+}
+
+fn take(used: &AtomicU64, limit: u64, n: u64) -> Result<(), i64> {
+    used.try_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+        used.checked_add(n).filter(|&total| total <= limit)
+    })
+    .map(|_| ())
+    .map_err(|_| ENOSPC)
+}
+
+fn xattr_cost(name: &str, value: &[u8]) -> u64 {
+    (name.len() + value.len()) as u64
+}
+
+// a file in pages, the ones never written are holes that read as zeroes
+pub struct TmpFile {
+    pages: BTreeMap<u64, Box<[u8]>>,
+    sb: Arc<TmpfsSb>,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for TmpFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} pages, {:?}", self.pages.len(), self.metadata)
+    }
+}
+
+impl TmpFile {
+    pub fn new(sb: Arc<TmpfsSb>) -> Self {
+        Self {
+            pages: BTreeMap::new(),
+            sb,
+            metadata: VfsNodeMetadata::new(VfsNodeType::File),
+        }
+    }
+}
+
+impl Drop for TmpFile {
+    fn drop(&mut self) {
+        self.sb.uncharge(self.pages.len() as u64);
+        self.sb.drop_xattrs(&self.metadata.xattrs);
+    }
+}
+
+impl VfsNode for TmpFile {
+    fn get_permissions(&self) -> &NodeMode {
+        &self.get_metadata().permissions
+    }
+    fn get_permissions_mut(&mut self) -> &mut NodeMode {
+        &mut self.get_metadata_mut().permissions
+    }
+    fn get_metadata(&self) -> &VfsNodeMetadata {
+        &self.metadata
+    }
+    fn get_metadata_mut(&mut self) -> &mut VfsNodeMetadata {
+        &mut self.metadata
+    }
+    fn get_type(&self) -> &VfsNodeType {
+        &self.get_metadata().type_
+    }
+    fn size(&self) -> u64 {
+        self.metadata.size
+    }
+    // not contiguous, so no borrowed view of the whole thing
+    fn read(&self) -> Option<&[u8]> {
+        None
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Option<usize> {
+        self.metadata.touch_accessed();
+        let len = self
+            .metadata
+            .size
+            .saturating_sub(offset)
+            .min(buf.len() as u64) as usize;
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let in_page = (pos % PAGE) as usize;
+            let n = (PAGE as usize - in_page).min(len - done);
+            let out = &mut buf[done..done + n];
+            match self.pages.get(&(pos / PAGE)) {
+                Some(page) => out.copy_from_slice(&page[in_page..in_page + n]),
+                None => out.fill(0),
+            }
+            done += n;
+        }
+        Some(len)
+    }
+    // short if the mount fills up halfway, ENOSPC if nothing fit
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        offset.checked_add(buf.len() as u64).ok_or(EFBIG)?;
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let in_page = (pos % PAGE) as usize;
+            let n = (PAGE as usize - in_page).min(buf.len() - done);
+            let data = &buf[done..done + n];
+            let page = match self.pages.entry(pos / PAGE) {
+                Entry::Occupied(page) => Some(page.into_mut()),
+                // zeroes into a hole leave it one
+                Entry::Vacant(_) if data.iter().all(|&b| b == 0) => None,
+                Entry::Vacant(hole) => match self.sb.charge(1) {
+                    Ok(()) => Some(hole.insert(vec![0; PAGE as usize].into_boxed_slice())),
+                    Err(e) if done == 0 => return Err(e),
+                    Err(_) => break,
+                },
+            };
+            if let Some(page) = page {
+                page[in_page..in_page + n].copy_from_slice(data);
+            }
+            done += n;
+        }
+        self.metadata.size = self.metadata.size.max(offset + done as u64);
+        self.metadata.touch_modified();
+        Ok(done)
+    }
+    // growing only moves the size, the new part is a hole
+    fn truncate(&mut self, len: u64) -> Result<(), i64> {
+        if len < self.metadata.size {
+            let dropped = self.pages.split_off(&len.div_ceil(PAGE));
+            self.sb.uncharge(dropped.len() as u64);
+            // what's left of the last page has to read as zeroes if the file grows again
+            if let Some(page) = self.pages.get_mut(&(len / PAGE)) {
+                page[(len % PAGE) as usize..].fill(0);
+            }
+        }
+        self.metadata.size = len;
+        self.metadata.touch_modified();
+        Ok(())
+    }
+    fn blocks(&self) -> u64 {
+        self.pages.len() as u64 * (PAGE / 512)
+    }
+    fn statfs(&self) -> Option<FsStats> {
+        Some(self.sb.stats())
+    }
+    fn set_xattr(&mut self, name: &str, value: &[u8]) -> Result<(), i64> {
+        self.sb.set_xattr(&mut self.metadata.xattrs, name, value)
+    }
+    fn remove_xattr(&mut self, name: &str) -> Result<(), i64> {
+        self.sb.remove_xattr(&mut self.metadata.xattrs, name)
+    }
+}
+
+struct Options {
+    size: u64,
+    inodes: u64,
+    mode: Option<NodeMode>,
+}
+
+// "16m", what linux calls memparse. EINVAL if it doesn't fit in 64 bits
+fn parse_size(value: &str) -> Result<u64, i64> {
+    let (digits, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+    let n: u64 = digits.parse().map_err(|_| EINVAL)?;
+    let shift = match unit {
+        "" => 0,
+        "k" | "K" => 10,
+        "m" | "M" => 20,
+        "g" | "G" => 30,
+        _ => return Err(EINVAL),
+    };
+    n.checked_mul(1 << shift).ok_or(EINVAL)
+}
+
+// "size=16m,nr_inodes=1k,mode=1777", size also takes a percentage of memory
+fn parse_options(data: &str) -> Result<Options, i64> {
+    let mut options = Options {
+        size: TmpfsSb::default_size(),
+        inodes: TmpfsSb::default_inodes(),
+        mode: None,
+    };
+    for option in data.split(',').filter(|o| !o.is_empty()) {
+        let (key, value) = option.split_once('=').ok_or(EINVAL)?;
+        match key {
+            "size" => {
+                options.size = match value.strip_suffix('%') {
+                    Some(percent) => {
+                        let n: u64 = percent.parse().map_err(|_| EINVAL)?;
+                        (get_usable_memory() / 100).checked_mul(n).ok_or(EINVAL)?
+                    }
+                    None => parse_size(value)?,
+                };
+            }
+            "nr_blocks" => options.size = parse_size(value)?.checked_mul(PAGE).ok_or(EINVAL)?,
+            "nr_inodes" => options.inodes = parse_size(value)?,
+            "mode" => {
+                let bits = i32::from_str_radix(value, 8).map_err(|_| EINVAL)?;
+                options.mode = Some(NodeMode::from_bits_truncate(bits));
+            }
+            _ => return Err(EINVAL),
+        }
+    }
+    Ok(options)
+}
+
+pub struct TmpFs;
+
+impl FileSystemType for TmpFs {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+    fn magic(&self) -> u64 {
+        TMPFS_MAGIC
+    }
+    fn mount(&self, _source: &str, _flags: MountFlags, data: &str) -> Result<InodeRef, i64> {
+        let options = parse_options(data)?;
+        let sb = TmpfsSb::new(options.size, options.inodes);
+        let root = Inode::new(Directory::new().with_storage(Storage::Tmpfs(sb)));
+        if let Some(mode) = options.mode {
+            *root.node_mut().get_permissions_mut() = mode;
+        }
+        Ok(root)
+    }
+}
+
+pub static TMPFS: TmpFs = TmpFs;
@@ -3,7 +3,11 @@
     Released under EUPL 1.2 License
 */
 
-use crate::arch::drivers::time::rtc::read_rtc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{collections::btree_map::BTreeMap, sync::Weak};
+
+use crate::{arch::drivers::time::rtc::read_rtc, memory::shared::SharedMemory};
 
 use super::*;
 
@@ -39,19 +43,39 @@ bitflags::bitflags! {
 pub struct VfsNodeMetadata {
     pub size: u64,
     pub created_at: u64,
+    // the data, what st_mtime shows
     pub modified_at: u64,
+    // the data or anything in here, st_ctime
+    pub changed_at: u64,
+    // reads only hold the node's read lock, so this one is atomic
+    pub accessed_at: AtomicU64,
     pub type_: VfsNodeType,
     pub permissions: NodeMode,
+    // directory entries naming it, plus "." and the subdirectories' ".." for directories
+    pub nlink: u64,
+    // the owner, only disk filesystems keep anything but root here
+    pub uid: u32,
+    pub gid: u32,
+    // extended attributes by their full name, posix acls included. only in-memory
+    // filesystems keep them here, disk ones have their own place for them
+    pub xattrs: BTreeMap<String, Vec<u8>>,
 }
 
 impl VfsNodeMetadata {
     pub fn new(type_: VfsNodeType) -> Self {
+        let now = now();
         VfsNodeMetadata {
             size: 0,
-            created_at: 0,
-            modified_at: 0,
+            created_at: now,
+            modified_at: now,
+            changed_at: now,
+            accessed_at: AtomicU64::new(now),
             type_,
             permissions: NodeMode::RW,
+            nlink: 0,
+            uid: 0,
+            gid: 0,
+            xattrs: BTreeMap::new(),
         }
     }
 
@@ -74,20 +98,54 @@ impl VfsNodeMetadata {
         self.modified_at = modified_at;
         self
     }
+
+    pub fn accessed_at(&self) -> u64 {
+        self.accessed_at.load(Ordering::Relaxed)
+    }
+
+    pub fn set_accessed_at(&self, accessed_at: u64) {
+        self.accessed_at.store(accessed_at, Ordering::Relaxed);
+    }
+
+    pub fn touch_accessed(&self) {
+        self.set_accessed_at(now());
+    }
+
+    // the data changed, which counts as a change to the node too
+    pub fn touch_modified(&mut self) {
+        let now = now();
+        self.modified_at = now;
+        self.changed_at = now;
+    }
+
+    // only the metadata: links, permissions, times
+    pub fn touch_changed(&mut self) {
+        self.changed_at = now();
+    }
+}
+
+// seconds since the epoch, the rtc doesn't do better
+pub fn now() -> u64 {
+    read_rtc().to_epoch().unwrap_or_default()
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum VfsNodeType {
     File,
     Directory,
+    Socket,
+    Symlink,
+    CharDevice,
+    BlockDevice,
 }
 
 #[derive(Debug)]
 pub struct Vfs {
-    pub root: Option<Box<dyn VfsNode>>,
+    // the first one is the root filesystem
+    pub mounts: Vec<Mount>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Path {
     pub path: String,
 }
@@ -107,31 +165,81 @@ pub enum FsError {
     InvalidPath,
 }
 
+// a name in a directory, several can point at the same inode
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub inode: InodeRef,
+}
+
+// where files created in a directory keep their data, subdirectories get the same
+#[derive(Debug, Clone)]
+pub enum Storage {
+    // one vec per file, what the initial root is made of
+    Heap,
+    // ShmFiles so they can be mmapped MAP_SHARED (/dev/shm), counted like Tmpfs
+    Shm(Arc<TmpfsSb>),
+    // TmpFiles, counted against the mount's size
+    Tmpfs(Arc<TmpfsSb>),
+}
+
+impl Storage {
+    // the tmpfs its entries and files are counted against, if any
+    pub fn sb(&self) -> Option<&Arc<TmpfsSb>> {
+        match self {
+            Storage::Heap => None,
+            Storage::Shm(sb) | Storage::Tmpfs(sb) => Some(sb),
+        }
+    }
+}
+
 pub struct Directory {
-    pub children: Vec<Box<dyn VfsNode>>,
+    pub children: Vec<DirEntry>,
     pub metadata: VfsNodeMetadata,
-    pub path: Path,
+    pub storage: Storage,
+    // what the entries cost the tmpfs, given back when it goes away
+    pub(super) charged: u64,
 }
 
 impl core::fmt::Debug for Directory {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(
-            f,
-            "{:?}, {:?}, {:?}",
-            self.path, self.children, self.metadata
-        )
+        write!(f, "{:?}, {:?}", self.children, self.metadata)
+    }
+}
+
+impl Default for Directory {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Directory {
-    pub fn new(path: Path) -> Self {
+    pub fn new() -> Self {
         let epoch = read_rtc().to_epoch().unwrap_or_default();
+        let mut metadata = VfsNodeMetadata::new(VfsNodeType::Directory)
+            .with_created_at(epoch)
+            .with_modified_at(epoch);
+        // its own "."
+        metadata.nlink = 1;
         Self {
             children: Vec::new(),
-            metadata: VfsNodeMetadata::new(VfsNodeType::Directory)
-                .with_created_at(epoch)
-                .with_modified_at(epoch),
-            path,
+            metadata,
+            storage: Storage::Heap,
+            charged: 0,
+        }
+    }
+
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        if let Some(sb) = self.storage.sb() {
+            sb.uncharge_meta(self.charged);
+            sb.drop_xattrs(&self.metadata.xattrs);
         }
     }
 }
@@ -140,11 +248,10 @@ impl Directory {
 pub struct File {
     pub data: Vec<u8>,
     pub metadata: VfsNodeMetadata,
-    pub path: Path,
 }
 
 impl File {
-    pub fn new(data: Vec<u8>, path: Path) -> Self {
+    pub fn new(data: Vec<u8>) -> Self {
         let epoch = read_rtc().to_epoch().unwrap_or_default();
         let size = data.len() as u64;
         Self {
@@ -153,7 +260,98 @@ impl File {
                 .with_created_at(epoch)
                 .with_modified_at(epoch)
                 .with_size(size),
-            path,
+        }
+    }
+}
+
+// a file living in shared memory frames so it can be mmapped MAP_SHARED
+pub struct ShmFile {
+    pub memory: Arc<SharedMemory>,
+    pub sb: Arc<TmpfsSb>,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl core::fmt::Debug for ShmFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} bytes, {:?}", self.memory.size(), self.metadata)
+    }
+}
+
+impl ShmFile {
+    pub fn new(sb: Arc<TmpfsSb>) -> Self {
+        let epoch = read_rtc().to_epoch().unwrap_or_default();
+        Self {
+            memory: SharedMemory::new(0).unwrap(),
+            sb,
+            metadata: VfsNodeMetadata::new(VfsNodeType::File)
+                .with_created_at(epoch)
+                .with_modified_at(epoch),
+        }
+    }
+}
+
+impl Drop for ShmFile {
+    fn drop(&mut self) {
+        let _ = self.sb.recharge(self.memory.size() as u64, 0);
+        self.sb.drop_xattrs(&self.metadata.xattrs);
+    }
+}
+
+// a bound unix socket, connecting to the path reaches the socket through `endpoint`
+#[derive(Debug)]
+pub struct SocketNode {
+    pub endpoint: Weak<dyn FileObject>,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl SocketNode {
+    pub fn new(endpoint: Weak<dyn FileObject>) -> Self {
+        let epoch = read_rtc().to_epoch().unwrap_or_default();
+        Self {
+            endpoint,
+            metadata: VfsNodeMetadata::new(VfsNodeType::Socket)
+                .with_created_at(epoch)
+                .with_modified_at(epoch),
+        }
+    }
+}
+
+// a symbolic link, path lookups continue at `target`
+#[derive(Debug)]
+pub struct Symlink {
+    pub target: String,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl Symlink {
+    pub fn new(target: &str) -> Self {
+        let epoch = read_rtc().to_epoch().unwrap_or_default();
+        Self {
+            target: String::from(target),
+            metadata: VfsNodeMetadata::new(VfsNodeType::Symlink)
+                .with_permissions(NodeMode::all())
+                .with_created_at(epoch)
+                .with_modified_at(epoch)
+                .with_size(target.len() as u64),
+        }
+    }
+}
+
+// a device special file, opening it reaches the driver registered for `rdev`
+#[derive(Debug)]
+pub struct DeviceNode {
+    pub rdev: u64,
+    pub metadata: VfsNodeMetadata,
+}
+
+impl DeviceNode {
+    pub fn new(type_: VfsNodeType, rdev: u64) -> Self {
+        let epoch = read_rtc().to_epoch().unwrap_or_default();
+        Self {
+            rdev,
+            metadata: VfsNodeMetadata::new(type_)
+                .with_created_at(epoch)
+                .with_modified_at(epoch),
         }
     }
 }
@@ -0,0 +1,207 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{
+    collections::btree_map::BTreeMap,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    arch::drivers::time::preferred_timer_ns,
+    drivers::fs::{FileObject, PollEvents},
+    scheduler::wait::{WaitQueue, wait_any},
+    utils::{errno::*, spinlock::Spin},
+};
+
+pub const EPOLL_CTL_ADD: u64 = 1;
+pub const EPOLL_CTL_DEL: u64 = 2;
+pub const EPOLL_CTL_MOD: u64 = 3;
+
+// edge triggering isn't implemented, EPOLLET is accepted and behaves level triggered
+pub const EPOLLET: u32 = 1 << 31;
+pub const EPOLLONESHOT: u32 = 1 << 30;
+
+struct Interest {
+    // closing the last fd of an object drops it from the set
+    object: Weak<dyn FileObject>,
+    events: u32,
+    data: u64,
+    // oneshot interests go quiet after firing until EPOLL_CTL_MOD
+    disabled: bool,
+}
+
+pub struct EventPoll {
+    interests: Spin<BTreeMap<i32, Interest>>,
+    // bumped on every ctl so waiters pick up the new set of queues
+    generation: AtomicU64,
+    wq: Arc<WaitQueue>,
+}
+
+impl EventPoll {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            interests: Spin::new(BTreeMap::new()),
+            generation: AtomicU64::new(0),
+            wq: Arc::new(WaitQueue::new()),
+        })
+    }
+
+    pub fn ctl(
+        &self,
+        op: u64,
+        fd: i32,
+        object: Arc<dyn FileObject>,
+        events: u32,
+        data: u64,
+    ) -> Result<(), i64> {
+        if core::ptr::addr_eq(Arc::as_ptr(&object), self) {
+            return Err(EINVAL);
+        }
+        {
+            let mut interests = self.interests.lock();
+            interests.retain(|_, i| i.object.strong_count() > 0);
+            match op {
+                EPOLL_CTL_ADD => {
+                    if interests.contains_key(&fd) {
+                        return Err(EEXIST);
+                    }
+                    interests.insert(
+                        fd,
+                        Interest {
+                            object: Arc::downgrade(&object),
+                            events,
+                            data,
+                            disabled: false,
+                        },
+                    );
+                }
+                EPOLL_CTL_DEL => {
+                    interests.remove(&fd).ok_or(ENOENT)?;
+                }
+                EPOLL_CTL_MOD => {
+                    let interest = interests.get_mut(&fd).ok_or(ENOENT)?;
+                    interest.events = events;
+                    interest.data = data;
+                    interest.disabled = false;
+                }
+                _ => return Err(EINVAL),
+            }
+        }
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.wq.wake_all();
+        Ok(())
+    }
+
+    // waits for up to `max` ready events, gives up with nothing once `deadline` passes
+    pub fn wait(&self, max: usize, deadline: Option<u64>) -> Vec<(u32, u64)> {
+        loop {
+            let generation = self.generation.load(Ordering::Relaxed);
+            let queues = self.wait_queues();
+            let queues: Vec<&WaitQueue> = queues.iter().map(|q| &**q).collect();
+            let wake_at = match (deadline, self.deadline()) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            let ret = wait_any(&queues, wake_at, || {
+                let ready = self.collect(max);
+                if !ready.is_empty() {
+                    Some(Some(ready))
+                } else if self.generation.load(Ordering::Relaxed) != generation {
+                    Some(None)
+                } else {
+                    None
+                }
+            });
+            match ret {
+                Some(Some(ready)) => return ready,
+                // interest set changed, go again with fresh queues
+                Some(None) => {}
+                None if deadline.is_some_and(|d| preferred_timer_ns() >= d) => return Vec::new(),
+                // a timer in the set expired
+                None => {}
+            }
+        }
+    }
+
+    fn collect(&self, max: usize) -> Vec<(u32, u64)> {
+        let mut ready = Vec::new();
+        let mut interests = self.interests.lock();
+        for interest in interests.values_mut() {
+            if ready.len() >= max {
+                break;
+            }
+            if interest.disabled {
+                continue;
+            }
+            let Some(object) = interest.object.upgrade() else {
+                continue;
+            };
+            // errors and hangups are always reported
+            let wanted = interest.events | (PollEvents::ERR | PollEvents::HUP).bits();
+            let events = object.poll().bits() & wanted;
+            if events != 0 {
+                ready.push((events, interest.data));
+                if interest.events & EPOLLONESHOT != 0 {
+                    interest.disabled = true;
+                }
+            }
+        }
+        ready
+    }
+
+    fn objects(&self) -> Vec<Arc<dyn FileObject>> {
+        self.interests
+            .lock()
+            .values()
+            .filter(|i| !i.disabled)
+            .filter_map(|i| i.object.upgrade())
+            .collect()
+    }
+}
+
+impl FileObject for EventPoll {
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let interests: Vec<_> = self
+            .interests
+            .lock()
+            .values()
+            .filter(|i| !i.disabled)
+            .filter_map(|i| Some((i.object.upgrade()?, i.events)))
+            .collect();
+        let ready = interests
+            .iter()
+            .any(|(object, events)| object.poll().bits() & events != 0);
+        if ready {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        let mut queues = vec![self.wq.clone()];
+        for object in self.objects() {
+            queues.extend(object.wait_queues());
+        }
+        queues
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.objects().iter().filter_map(|o| o.deadline()).min()
+    }
+}
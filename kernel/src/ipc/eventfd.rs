@@ -0,0 +1,87 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use crate::{
+    drivers::fs::{FileObject, PollEvents},
+    scheduler::wait::WaitQueue,
+    utils::{errno::*, spinlock::Spin},
+};
+
+const COUNTER_MAX: u64 = u64::MAX - 1;
+
+// a 64 bit counter, writes add to it and reads drain it (or take 1 in semaphore mode)
+pub struct EventFd {
+    counter: Spin<u64>,
+    semaphore: bool,
+    wq: Arc<WaitQueue>,
+}
+
+impl EventFd {
+    pub fn new(initval: u64, semaphore: bool) -> Arc<Self> {
+        Arc::new(Self {
+            counter: Spin::new(initval),
+            semaphore,
+            wq: Arc::new(WaitQueue::new()),
+        })
+    }
+}
+
+impl FileObject for EventFd {
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64> {
+        if buf.len() < 8 {
+            return Err(EINVAL);
+        }
+        let value = self.wq.wait_until(|| {
+            let mut counter = self.counter.lock();
+            if *counter == 0 {
+                return if nonblock { Some(Err(EAGAIN)) } else { None };
+            }
+            let value = if self.semaphore { 1 } else { *counter };
+            *counter -= value;
+            Some(Ok(value))
+        })?;
+        self.wq.wake_all();
+        buf[..8].copy_from_slice(&value.to_ne_bytes());
+        Ok(8)
+    }
+
+    fn write(&self, buf: &[u8], nonblock: bool) -> Result<usize, i64> {
+        if buf.len() < 8 {
+            return Err(EINVAL);
+        }
+        let value = u64::from_ne_bytes(buf[..8].try_into().unwrap());
+        if value == u64::MAX {
+            return Err(EINVAL);
+        }
+        self.wq.wait_until(|| {
+            let mut counter = self.counter.lock();
+            if *counter > COUNTER_MAX - value {
+                return if nonblock { Some(Err(EAGAIN)) } else { None };
+            }
+            *counter += value;
+            Some(Ok(()))
+        })?;
+        self.wq.wake_all();
+        Ok(8)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let counter = *self.counter.lock();
+        let mut events = PollEvents::empty();
+        if counter > 0 {
+            events |= PollEvents::IN;
+        }
+        if counter < COUNTER_MAX {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        vec![self.wq.clone()]
+    }
+}
@@ -0,0 +1,13 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+pub mod epoll;
+pub mod eventfd;
+pub mod mqueue;
+pub mod sem;
+pub mod shm;
+pub mod signalfd;
+pub mod timerfd;
+pub mod unix;
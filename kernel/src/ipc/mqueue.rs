@@ -0,0 +1,272 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::{
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    string::String,
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    drivers::fs::{FileObject, PollEvents},
+    scheduler::{
+        current_process,
+        signal::{SI_MESGQ, SigInfo, send_signal},
+        wait::WaitQueue,
+    },
+    utils::{errno::*, spinlock::Spin},
+};
+
+pub const MQ_PRIO_MAX: u32 = 32768;
+pub const MQ_NAME_MAX: usize = 255;
+pub const MQ_MAXMSG_DEFAULT: usize = 10;
+pub const MQ_MSGSIZE_DEFAULT: usize = 8192;
+pub const MQ_MAXMSG_MAX: usize = 65536;
+pub const MQ_MSGSIZE_MAX: usize = 1 << 20;
+// what every queue together may hold at most (maxmsg * msgsize each), linux's default
+// RLIMIT_MSGQUEUE. there's only one user so it's the one budget
+pub const MQ_BYTES_MAX: usize = 819200;
+
+static MQ_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+// who gets told when a message lands in an empty queue
+#[derive(Clone, Copy)]
+pub struct Notify {
+    pub pid: u64,
+    // zero for SIGEV_NONE, the registration is still taken
+    pub signo: u32,
+    pub value: u64,
+}
+
+#[derive(Default)]
+struct State {
+    // by priority, highest gets received first and fifo within one priority
+    messages: BTreeMap<u32, VecDeque<Vec<u8>>>,
+    count: usize,
+    // blocked in receive, notifications only fire when nobody is
+    receivers: usize,
+    notify: Option<Notify>,
+}
+
+// a posix message queue, the fd from mq_open refers to one of these
+pub struct MessageQueue {
+    pub maxmsg: usize,
+    pub msgsize: usize,
+    state: Spin<State>,
+    wq: Arc<WaitQueue>,
+}
+
+impl MessageQueue {
+    pub fn new(maxmsg: usize, msgsize: usize) -> Result<Arc<Self>, i64> {
+        if maxmsg == 0 || msgsize == 0 || maxmsg > MQ_MAXMSG_MAX || msgsize > MQ_MSGSIZE_MAX {
+            return Err(EINVAL);
+        }
+        MQ_BYTES
+            .try_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                (used + maxmsg * msgsize <= MQ_BYTES_MAX).then_some(used + maxmsg * msgsize)
+            })
+            .map_err(|_| EMFILE)?;
+        Ok(Arc::new(Self {
+            maxmsg,
+            msgsize,
+            state: Spin::new(State::default()),
+            wq: Arc::new(WaitQueue::new()),
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // `deadline` is in preferred_timer_ns, ETIMEDOUT once it passes
+    pub fn send(
+        &self,
+        msg: &[u8],
+        prio: u32,
+        nonblock: bool,
+        deadline: Option<u64>,
+    ) -> Result<(), i64> {
+        if msg.len() > self.msgsize {
+            return Err(EMSGSIZE);
+        }
+        if prio >= MQ_PRIO_MAX {
+            return Err(EINVAL);
+        }
+        let notify = self
+            .wq
+            .wait_until_deadline(deadline, || {
+                let mut state = self.state.lock();
+                if state.count >= self.maxmsg {
+                    return if nonblock { Some(Err(EAGAIN)) } else { None };
+                }
+                state
+                    .messages
+                    .entry(prio)
+                    .or_default()
+                    .push_back(msg.to_vec());
+                state.count += 1;
+                let notify = match state.count == 1 && state.receivers == 0 {
+                    true => state.notify.take(),
+                    false => None,
+                };
+                Some(Ok(notify))
+            })
+            .ok_or(ETIMEDOUT)??;
+        self.wq.wake_all();
+
+        if let Some(notify) = notify
+            && notify.signo != 0
+        {
+            let pid = current_process().map_or(0, |p| p.lock().get_pid());
+            send_signal(
+                notify.pid,
+                SigInfo {
+                    signo: notify.signo,
+                    code: SI_MESGQ,
+                    pid,
+                    status: 0,
+                    value: notify.value,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // returns the message and its priority
+    pub fn receive(
+        &self,
+        len: usize,
+        nonblock: bool,
+        deadline: Option<u64>,
+    ) -> Result<(Vec<u8>, u32), i64> {
+        if len < self.msgsize {
+            return Err(EMSGSIZE);
+        }
+        self.state.lock().receivers += 1;
+        let ret = self.wq.wait_until_deadline(deadline, || {
+            let mut state = self.state.lock();
+            let Some(mut entry) = state.messages.last_entry() else {
+                return if nonblock { Some(Err(EAGAIN)) } else { None };
+            };
+            let prio = *entry.key();
+            let msg = entry.get_mut().pop_front().unwrap();
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+            state.count -= 1;
+            Some(Ok((msg, prio)))
+        });
+        self.state.lock().receivers -= 1;
+
+        let ret = ret.ok_or(ETIMEDOUT)??;
+        self.wq.wake_all();
+        Ok(ret)
+    }
+
+    // mq_notify, None unregisters if `pid` is the one registered
+    pub fn notify(&self, pid: u64, request: Option<Notify>) -> Result<(), i64> {
+        let mut state = self.state.lock();
+        match request {
+            Some(_) if state.notify.is_some() => Err(EBUSY),
+            Some(request) => {
+                state.notify = Some(request);
+                Ok(())
+            }
+            None => {
+                if state.notify.is_some_and(|n| n.pid == pid) {
+                    state.notify = None;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// the queue's share of MQ_BYTES_MAX is free again once the last descriptor is closed
+impl Drop for MessageQueue {
+    fn drop(&mut self) {
+        MQ_BYTES.fetch_sub(self.maxmsg * self.msgsize, Ordering::Relaxed);
+    }
+}
+
+impl FileObject for MessageQueue {
+    // only mq_timedsend/mq_timedreceive move messages
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+    fn poll(&self) -> PollEvents {
+        let count = self.len();
+        let mut events = PollEvents::empty();
+        if count > 0 {
+            events |= PollEvents::IN;
+        }
+        if count < self.maxmsg {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        vec![self.wq.clone()]
+    }
+}
+
+// named queues, unlinking only drops the name and open descriptors keep the queue
+pub static QUEUES: Spin<BTreeMap<String, Arc<MessageQueue>>> = Spin::new(BTreeMap::new());
+
+// names look like "/queue", the slash is optional like in the raw syscall
+pub fn queue_name(name: &str) -> Result<&str, i64> {
+    let name = name.strip_prefix('/').unwrap_or(name);
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(EINVAL);
+    }
+    if name.len() > MQ_NAME_MAX {
+        return Err(ENAMETOOLONG);
+    }
+    if name.contains('/') {
+        return Err(EACCES);
+    }
+    Ok(name)
+}
+
+// `attr` is (maxmsg, msgsize) for new queues
+pub fn open(
+    name: &str,
+    create: bool,
+    excl: bool,
+    attr: Option<(usize, usize)>,
+) -> Result<Arc<MessageQueue>, i64> {
+    let name = queue_name(name)?;
+    let mut queues = QUEUES.lock();
+    if let Some(queue) = queues.get(name) {
+        if create && excl {
+            return Err(EEXIST);
+        }
+        return Ok(queue.clone());
+    }
+    if !create {
+        return Err(ENOENT);
+    }
+
+    let (maxmsg, msgsize) = attr.unwrap_or((MQ_MAXMSG_DEFAULT, MQ_MSGSIZE_DEFAULT));
+    let queue = MessageQueue::new(maxmsg, msgsize)?;
+    queues.insert(String::from(name), queue.clone());
+    Ok(queue)
+}
+
+pub fn unlink(name: &str) -> Result<(), i64> {
+    let name = queue_name(name)?;
+    QUEUES.lock().remove(name).map(|_| ()).ok_or(ENOENT)
+}
@@ -0,0 +1,252 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{collections::btree_map::BTreeMap, sync::Arc, vec, vec::Vec};
+
+use crate::{
+    scheduler::wait::WaitQueue,
+    utils::{errno::*, spinlock::Spin},
+};
+
+use super::shm::{IPC_PRIVATE, now};
+
+// linux defaults
+pub const SEMMSL: usize = 32000;
+pub const SEMOPM: usize = 500;
+pub const SEMVMX: i32 = 32767;
+
+pub const IPC_NOWAIT: i16 = 0o4000;
+pub const SEM_UNDO: i16 = 0x1000;
+
+// struct sembuf
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SemOp {
+    pub num: u16,
+    pub op: i16,
+    pub flags: i16,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct Semaphore {
+    pub value: i32,
+    // last pid to change it
+    pub pid: u64,
+    // blocked waiting for it to go up / to hit zero
+    pub ncnt: usize,
+    pub zcnt: usize,
+}
+
+impl Semaphore {
+    fn waiter(&mut self, zero: bool, add: bool) {
+        let count = if zero { &mut self.zcnt } else { &mut self.ncnt };
+        if add {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+    }
+}
+
+enum Blocked {
+    // index of the op we'd have to wait on
+    On(usize),
+    Error(i64),
+}
+
+pub struct SemSet {
+    pub key: i64,
+    pub mode: u32,
+    pub sems: Vec<Semaphore>,
+    pub op_time: u64,
+    pub change_time: u64,
+    // SEM_UNDO adjustments by pid, applied when that process exits
+    undo: BTreeMap<u64, Vec<i32>>,
+    pub wq: Arc<WaitQueue>,
+}
+
+impl SemSet {
+    // applies all of `ops` or none of them
+    fn apply(&mut self, ops: &[SemOp], pid: u64) -> Result<(), Blocked> {
+        let mut values: Vec<i32> = self.sems.iter().map(|s| s.value).collect();
+        for (i, op) in ops.iter().enumerate() {
+            let value = &mut values[op.num as usize];
+            match op.op {
+                0 if *value != 0 => return Err(Blocked::On(i)),
+                0 => {}
+                op if *value + (op as i32) < 0 => return Err(Blocked::On(i)),
+                op if *value + (op as i32) > SEMVMX => return Err(Blocked::Error(ERANGE)),
+                op => *value += op as i32,
+            }
+        }
+
+        for op in ops {
+            let sem = &mut self.sems[op.num as usize];
+            sem.value = values[op.num as usize];
+            sem.pid = pid;
+            if op.flags & SEM_UNDO != 0 && op.op != 0 {
+                let len = self.sems.len();
+                self.undo.entry(pid).or_insert_with(|| vec![0; len])[op.num as usize] -=
+                    op.op as i32;
+            }
+        }
+        self.op_time = now();
+        Ok(())
+    }
+
+    // SETVAL/SETALL, pending undos for it no longer make sense
+    pub fn set_value(&mut self, num: usize, value: i32, pid: u64) -> Result<(), i64> {
+        if !(0..=SEMVMX).contains(&value) {
+            return Err(ERANGE);
+        }
+        let sem = self.sems.get_mut(num).ok_or(EINVAL)?;
+        sem.value = value;
+        sem.pid = pid;
+        for adj in self.undo.values_mut() {
+            adj[num] = 0;
+        }
+        self.change_time = now();
+        Ok(())
+    }
+}
+
+// SysV semaphore sets by id, ids aren't reused so a missing one was removed
+pub struct SemSets {
+    sets: BTreeMap<i32, SemSet>,
+    next_id: i32,
+}
+
+pub static SEMAPHORES: Spin<SemSets> = Spin::new(SemSets {
+    sets: BTreeMap::new(),
+    next_id: 0,
+});
+
+impl SemSets {
+    pub fn get(
+        &mut self,
+        key: i64,
+        nsems: usize,
+        mode: u32,
+        create: bool,
+        excl: bool,
+    ) -> Result<i32, i64> {
+        if nsems > SEMMSL {
+            return Err(EINVAL);
+        }
+        if key != IPC_PRIVATE
+            && let Some((&id, set)) = self.sets.iter().find(|(_, s)| s.key == key)
+        {
+            if create && excl {
+                return Err(EEXIST);
+            }
+            if nsems > set.sems.len() {
+                return Err(EINVAL);
+            }
+            return Ok(id);
+        }
+
+        if key != IPC_PRIVATE && !create {
+            return Err(ENOENT);
+        }
+        if nsems == 0 {
+            return Err(EINVAL);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.sets.insert(
+            id,
+            SemSet {
+                key,
+                mode: mode & 0o777,
+                sems: vec![Semaphore::default(); nsems],
+                op_time: 0,
+                change_time: now(),
+                undo: BTreeMap::new(),
+                wq: Arc::new(WaitQueue::new()),
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn set(&mut self, id: i32) -> Result<&mut SemSet, i64> {
+        self.sets.get_mut(&id).ok_or(EINVAL)
+    }
+
+    // sleepers wake up to EIDRM
+    pub fn remove(&mut self, id: i32) -> Result<(), i64> {
+        let set = self.sets.remove(&id).ok_or(EINVAL)?;
+        set.wq.wake_all();
+        Ok(())
+    }
+
+    // process exit, rolls back whatever it did with SEM_UNDO
+    pub fn exit(&mut self, pid: u64) {
+        for set in self.sets.values_mut() {
+            let Some(adj) = set.undo.remove(&pid) else {
+                continue;
+            };
+            for (sem, adj) in set.sems.iter_mut().zip(adj) {
+                if adj != 0 {
+                    sem.value = (sem.value + adj).clamp(0, SEMVMX);
+                    sem.pid = pid;
+                }
+            }
+            set.op_time = now();
+            set.wq.wake_all();
+        }
+    }
+}
+
+// semop/semtimedop, EAGAIN once `deadline` passes like on linux
+pub fn semop(id: i32, ops: &[SemOp], pid: u64, deadline: Option<u64>) -> Result<(), i64> {
+    if ops.is_empty() {
+        return Err(EINVAL);
+    }
+    if ops.len() > SEMOPM {
+        return Err(E2BIG);
+    }
+    let wq = {
+        let mut sets = SEMAPHORES.lock();
+        let set = sets.set(id)?;
+        if ops.iter().any(|op| op.num as usize >= set.sems.len()) {
+            return Err(EFBIG);
+        }
+        set.wq.clone()
+    };
+
+    // the semaphore we're counted in semncnt/semzcnt of while asleep
+    let mut waiting: Option<(usize, bool)> = None;
+    let ret = wq.wait_until_deadline(deadline, || {
+        let mut sets = SEMAPHORES.lock();
+        let Ok(set) = sets.set(id) else {
+            return Some(Err(EIDRM));
+        };
+        if let Some((num, zero)) = waiting.take() {
+            set.sems[num].waiter(zero, false);
+        }
+        match set.apply(ops, pid) {
+            Ok(()) => Some(Ok(())),
+            Err(Blocked::Error(e)) => Some(Err(e)),
+            Err(Blocked::On(i)) if ops[i].flags & IPC_NOWAIT != 0 => Some(Err(EAGAIN)),
+            Err(Blocked::On(i)) => {
+                let num = ops[i].num as usize;
+                let zero = ops[i].op == 0;
+                set.sems[num].waiter(zero, true);
+                waiting = Some((num, zero));
+                None
+            }
+        }
+    });
+
+    if let Some((num, zero)) = waiting
+        && let Ok(set) = SEMAPHORES.lock().set(id)
+    {
+        set.sems[num].waiter(zero, false);
+    }
+    ret.ok_or(EAGAIN)??;
+    wq.wake_all();
+    Ok(())
+}
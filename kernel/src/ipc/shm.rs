@@ -0,0 +1,182 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{collections::btree_map::BTreeMap, sync::Arc};
+
+use crate::{
+    arch::drivers::time::rtc::read_rtc,
+    drivers::fs::{FileObject, PollEvents},
+    memory::shared::{SHARED_MAX_SIZE, SharedMemory},
+    utils::{errno::*, spinlock::Spin},
+};
+
+// anonymous file in shared memory, from memfd_create
+pub struct MemFd {
+    memory: Arc<SharedMemory>,
+}
+
+impl MemFd {
+    pub fn new() -> Result<Arc<Self>, i64> {
+        Ok(Arc::new(Self {
+            memory: SharedMemory::new(0)?,
+        }))
+    }
+}
+
+impl FileObject for MemFd {
+    // do_read/do_write go through read_at/write_at since we have a size
+    fn read(&self, _buf: &mut [u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(ESPIPE)
+    }
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(ESPIPE)
+    }
+    fn mode(&self) -> u32 {
+        0o100000
+    }
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
+    fn size(&self) -> Option<u64> {
+        Some(self.memory.size() as u64)
+    }
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, i64> {
+        Ok(self.memory.read_at(offset as usize, buf))
+    }
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize, i64> {
+        if offset as usize + buf.len() > SHARED_MAX_SIZE {
+            return Err(EFBIG);
+        }
+        self.memory.write_at(offset as usize, buf)
+    }
+    fn truncate(&self, len: u64) -> Result<(), i64> {
+        if len as usize > SHARED_MAX_SIZE {
+            return Err(EFBIG);
+        }
+        self.memory.resize(len as usize)
+    }
+    fn shared_memory(&self) -> Option<Arc<SharedMemory>> {
+        Some(self.memory.clone())
+    }
+}
+
+pub const IPC_PRIVATE: i64 = 0;
+
+// linux's SHMMNI, and SHMALL in pages. ENOSPC past either
+const SHMMNI: usize = 4096;
+const SHMALL: usize = 1 << 19;
+const PAGE: usize = 4096;
+
+pub struct Segment {
+    pub key: i64,
+    pub size: usize,
+    pub mode: u32,
+    pub memory: Arc<SharedMemory>,
+    pub creator: u64,
+    pub last_pid: u64,
+    pub attach_time: u64,
+    pub detach_time: u64,
+    pub change_time: u64,
+    // IPC_RMID'd, goes away once the last attachment does
+    pub removed: bool,
+}
+
+impl Segment {
+    // every attachment holds a reference to the memory, we hold the other one
+    pub fn attached(&self) -> usize {
+        Arc::strong_count(&self.memory) - 1
+    }
+}
+
+// SysV shared memory segments by id
+pub struct Segments {
+    segments: BTreeMap<i32, Segment>,
+    next_id: i32,
+}
+
+pub static SEGMENTS: Spin<Segments> = Spin::new(Segments {
+    segments: BTreeMap::new(),
+    next_id: 0,
+});
+
+impl Segments {
+    // removed segments are dropped lazily, attachments can vanish with their process
+    fn reap(&mut self) {
+        self.segments.retain(|_, s| !s.removed || s.attached() > 0);
+    }
+
+    pub fn get(
+        &mut self,
+        key: i64,
+        size: usize,
+        mode: u32,
+        create: bool,
+        excl: bool,
+        pid: u64,
+    ) -> Result<i32, i64> {
+        self.reap();
+        if key != IPC_PRIVATE
+            && let Some((&id, segment)) = self
+                .segments
+                .iter()
+                .find(|(_, s)| s.key == key && !s.removed)
+        {
+            if create && excl {
+                return Err(EEXIST);
+            }
+            if size > segment.size {
+                return Err(EINVAL);
+            }
+            return Ok(id);
+        }
+
+        if key != IPC_PRIVATE && !create {
+            return Err(ENOENT);
+        }
+        if size == 0 || size > SHARED_MAX_SIZE {
+            return Err(EINVAL);
+        }
+        let pages: usize = self.segments.values().map(|s| s.size.div_ceil(PAGE)).sum();
+        if self.segments.len() >= SHMMNI || pages + size.div_ceil(PAGE) > SHMALL {
+            return Err(ENOSPC);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.segments.insert(
+            id,
+            Segment {
+                key,
+                size,
+                mode: mode & 0o777,
+                memory: SharedMemory::new(size)?,
+                creator: pid,
+                last_pid: 0,
+                attach_time: 0,
+                detach_time: 0,
+                change_time: now(),
+                removed: false,
+            },
+        );
+        Ok(id)
+    }
+
+    pub fn segment(&mut self, id: i32) -> Result<&mut Segment, i64> {
+        self.reap();
+        self.segments.get_mut(&id).ok_or(EINVAL)
+    }
+
+    pub fn remove(&mut self, id: i32) -> Result<(), i64> {
+        let segment = self.segment(id)?;
+        segment.removed = true;
+        segment.change_time = now();
+        self.reap();
+        Ok(())
+    }
+}
+
+pub fn now() -> u64 {
+    read_rtc().to_epoch().unwrap_or_default()
+}
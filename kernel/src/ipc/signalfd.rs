@@ -0,0 +1,122 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use crate::{
+    drivers::fs::{FileObject, PollEvents},
+    scheduler::{
+        current_process,
+        signal::{SigInfo, Signals},
+        wait::WaitQueue,
+    },
+    utils::{errno::*, spinlock::Spin},
+};
+
+pub const SIGINFO_SIZE: usize = 128;
+
+// struct signalfd_siginfo, only the fields we can fill in
+#[repr(C)]
+struct SignalfdSiginfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    ssi_pid: u32,
+    ssi_uid: u32,
+    ssi_fd: i32,
+    ssi_tid: u32,
+    ssi_band: u32,
+    ssi_overrun: u32,
+    ssi_trapno: u32,
+    ssi_status: i32,
+    ssi_int: i32,
+    ssi_ptr: u64,
+    _pad: [u8; 72],
+}
+
+// reads the pending signals of whoever reads it, like on linux
+pub struct SignalFd {
+    mask: Spin<u64>,
+}
+
+impl SignalFd {
+    pub fn new(mask: u64) -> Arc<Self> {
+        Arc::new(Self {
+            mask: Spin::new(mask),
+        })
+    }
+
+    pub fn set_mask(&self, mask: u64) {
+        *self.mask.lock() = mask;
+    }
+}
+
+fn signals() -> Result<Arc<Signals>, i64> {
+    let proc = current_process().ok_or(EINVAL)?;
+    Ok(proc.lock().get_signals().clone())
+}
+
+fn encode(info: &SigInfo, buf: &mut [u8]) {
+    let raw = SignalfdSiginfo {
+        ssi_signo: info.signo,
+        ssi_errno: 0,
+        ssi_code: info.code,
+        ssi_pid: info.pid as u32,
+        ssi_uid: 0,
+        ssi_fd: 0,
+        ssi_tid: 0,
+        ssi_band: 0,
+        ssi_overrun: 0,
+        ssi_trapno: 0,
+        ssi_status: info.status,
+        ssi_int: info.value as i32,
+        ssi_ptr: info.value,
+        _pad: [0; 72],
+    };
+    let bytes = unsafe { core::slice::from_raw_parts(&raw as *const _ as *const u8, SIGINFO_SIZE) };
+    buf[..SIGINFO_SIZE].copy_from_slice(bytes);
+}
+
+impl FileObject for SignalFd {
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64> {
+        if buf.len() < SIGINFO_SIZE {
+            return Err(EINVAL);
+        }
+        let signals = signals()?;
+        let mask = *self.mask.lock();
+
+        // block for the first one, then grab whatever else fits
+        let first = signals.wq.wait_until(|| match signals.take(mask) {
+            Some(info) => Some(Ok(info)),
+            None if nonblock => Some(Err(EAGAIN)),
+            None => None,
+        })?;
+        encode(&first, buf);
+        let mut len = SIGINFO_SIZE;
+        while buf.len() - len >= SIGINFO_SIZE
+            && let Some(info) = signals.take(mask)
+        {
+            encode(&info, &mut buf[len..]);
+            len += SIGINFO_SIZE;
+        }
+        Ok(len)
+    }
+
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let mask = *self.mask.lock();
+        match signals() {
+            Ok(signals) if signals.pending_mask() & mask != 0 => PollEvents::IN,
+            _ => PollEvents::empty(),
+        }
+    }
+
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        signals().map(|s| vec![s.wq.clone()]).unwrap_or_default()
+    }
+}
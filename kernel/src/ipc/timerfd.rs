@@ -0,0 +1,155 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+use crate::{
+    arch::drivers::time::{preferred_timer_ns, rtc::read_rtc},
+    drivers::fs::{FileObject, PollEvents},
+    scheduler::wait::WaitQueue,
+    utils::{errno::*, spinlock::Spin},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    Realtime,
+    Monotonic,
+}
+
+struct State {
+    // next expiry in preferred_timer_ns time, None when disarmed
+    next: Option<u64>,
+    interval: u64,
+    expirations: u64,
+}
+
+impl State {
+    // expirations are only counted when someone looks. huge intervals saturate, the
+    // next one is then never
+    fn update(&mut self, now: u64) {
+        let Some(next) = self.next else {
+            return;
+        };
+        if now < next {
+            return;
+        }
+        match (now - next).checked_div(self.interval) {
+            Some(periods) => {
+                let n = periods + 1;
+                self.expirations = self.expirations.saturating_add(n);
+                self.next = Some(next.saturating_add(n.saturating_mul(self.interval)));
+            }
+            // one-shot
+            None => {
+                self.expirations += 1;
+                self.next = None;
+            }
+        }
+    }
+}
+
+pub struct TimerFd {
+    clock: Clock,
+    state: Spin<State>,
+    wq: Arc<WaitQueue>,
+}
+
+impl TimerFd {
+    pub fn new(clock: Clock) -> Arc<Self> {
+        Arc::new(Self {
+            clock,
+            state: Spin::new(State {
+                next: None,
+                interval: 0,
+                expirations: 0,
+            }),
+            wq: Arc::new(WaitQueue::new()),
+        })
+    }
+
+    // (time until the next expiry, interval) in ns, zero means disarmed
+    pub fn get(&self) -> (u64, u64) {
+        let now = preferred_timer_ns();
+        let mut state = self.state.lock();
+        state.update(now);
+        let remaining = state.next.map(|next| next - now).unwrap_or(0);
+        (remaining, state.interval)
+    }
+
+    // arms the timer, a zero value disarms it. returns the old setting like get()
+    pub fn set(&self, value: u64, interval: u64, absolute: bool) -> (u64, u64) {
+        let now = preferred_timer_ns();
+        let next = match value {
+            0 => None,
+            _ if !absolute => Some(now.saturating_add(value)),
+            _ => Some(match self.clock {
+                Clock::Monotonic => value,
+                // the rtc only has second granularity, good enough for timeouts
+                Clock::Realtime => {
+                    let realtime = read_rtc().to_epoch().unwrap_or(0) * 1_000_000_000;
+                    now.saturating_add(value.saturating_sub(realtime))
+                }
+            }),
+        };
+
+        let old = self.get();
+        {
+            let mut state = self.state.lock();
+            state.next = next;
+            state.interval = if next.is_some() { interval } else { 0 };
+            state.expirations = 0;
+        }
+        // readers might be sleeping towards the old deadline
+        self.wq.wake_all();
+        old
+    }
+}
+
+impl FileObject for TimerFd {
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64> {
+        if buf.len() < 8 {
+            return Err(EINVAL);
+        }
+        let count = loop {
+            let deadline = self.deadline();
+            let ret = self.wq.wait_until_deadline(deadline, || {
+                let mut state = self.state.lock();
+                state.update(preferred_timer_ns());
+                if state.expirations == 0 {
+                    return if nonblock { Some(Err(EAGAIN)) } else { None };
+                }
+                Some(Ok(core::mem::take(&mut state.expirations)))
+            });
+            // the deadline moving under us just means another round
+            if let Some(ret) = ret {
+                break ret?;
+            }
+        };
+        buf[..8].copy_from_slice(&count.to_ne_bytes());
+        Ok(8)
+    }
+
+    fn write(&self, _buf: &[u8], _nonblock: bool) -> Result<usize, i64> {
+        Err(EINVAL)
+    }
+
+    fn poll(&self) -> PollEvents {
+        let mut state = self.state.lock();
+        state.update(preferred_timer_ns());
+        if state.expirations > 0 {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        vec![self.wq.clone()]
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.state.lock().next
+    }
+}
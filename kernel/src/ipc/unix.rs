@@ -0,0 +1,576 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{
+    collections::vec_deque::VecDeque,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    drivers::fs::{
+        FileDescriptor, FileObject, Inode, Path, PollEvents, SocketNode, VfsNodeType, get_vfs,
+    },
+    scheduler::wait::WaitQueue,
+    utils::{errno::*, spinlock::Spin},
+};
+
+// receive buffer size, also the largest datagram we accept
+pub const SOCK_BUF_SIZE: usize = 64 * 1024;
+pub const BACKLOG_MAX: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Stream,
+    Datagram,
+}
+
+enum State {
+    Unconnected,
+    Listening {
+        backlog: VecDeque<Arc<UnixSocket>>,
+        max: usize,
+    },
+    Connected,
+}
+
+struct Message {
+    data: Vec<u8>,
+    read: usize,
+    rights: Vec<FileDescriptor>,
+    from: Option<String>,
+}
+
+struct Inner {
+    state: State,
+    local: Option<String>,
+    // stream peer, or the default destination of a connected datagram socket
+    peer: Option<Weak<UnixSocket>>,
+    rx: VecDeque<Message>,
+    rx_bytes: usize,
+    // nothing more will arrive, reads return 0 once rx is drained
+    eof: bool,
+    read_shutdown: bool,
+    write_shutdown: bool,
+}
+
+unsafe impl Send for Inner {}
+
+pub struct UnixSocket {
+    type_: SocketType,
+    inner: Spin<Inner>,
+    // woken whenever rx changes (data in, space freed), the peer goes away or a
+    // connection shows up. senders hold on to the wq, not the socket, while blocked
+    wq: Arc<WaitQueue>,
+}
+
+pub struct Received {
+    pub len: usize,
+    pub rights: Vec<FileDescriptor>,
+    pub from: Option<String>,
+    // datagram didn't fit, the rest of it was dropped
+    pub truncated: bool,
+}
+
+impl UnixSocket {
+    pub fn new(type_: SocketType) -> Arc<Self> {
+        Arc::new(Self {
+            type_,
+            inner: Spin::new(Inner {
+                state: State::Unconnected,
+                local: None,
+                peer: None,
+                rx: VecDeque::new(),
+                rx_bytes: 0,
+                eof: false,
+                read_shutdown: false,
+                write_shutdown: false,
+            }),
+            wq: Arc::new(WaitQueue::new()),
+        })
+    }
+
+    pub fn pair(type_: SocketType) -> (Arc<Self>, Arc<Self>) {
+        let a = Self::new(type_);
+        let b = Self::new(type_);
+        connect_pair(&a, &b);
+        (a, b)
+    }
+
+    pub fn socket_type(&self) -> SocketType {
+        self.type_
+    }
+
+    pub fn local_path(&self) -> Option<String> {
+        self.inner.lock().local.clone()
+    }
+
+    pub fn peer_path(&self) -> Result<Option<String>, i64> {
+        let peer = self.inner.lock().peer.clone().ok_or(ENOTCONN)?;
+        Ok(peer.upgrade().and_then(|p| p.local_path()))
+    }
+
+    pub fn bind(self: &Arc<Self>, path: Path) -> Result<(), i64> {
+        let mut inner = self.inner.lock();
+        if inner.local.is_some() {
+            return Err(EINVAL);
+        }
+
+        let vfs = get_vfs();
+        if vfs.resolve(path.clone()).is_some() {
+            return Err(EADDRINUSE);
+        }
+        if vfs.is_readonly(&path) {
+            return Err(EROFS);
+        }
+        let parent = vfs.resolve(path.get_parent()).ok_or(ENOENT)?;
+        let mut parent = parent.node_mut();
+        if !parent.is_dir() {
+            return Err(ENOTDIR);
+        }
+
+        let endpoint: Weak<dyn FileObject> = Arc::downgrade(self) as Weak<UnixSocket>;
+        parent
+            .link(path.get_name(), Inode::new(SocketNode::new(endpoint)))
+            .map_err(|e| if e == EEXIST { EADDRINUSE } else { e })?;
+
+        inner.local = Some(path.as_str().to_string());
+        Ok(())
+    }
+
+    pub fn listen(&self, backlog: usize) -> Result<(), i64> {
+        if self.type_ != SocketType::Stream {
+            return Err(EOPNOTSUPP);
+        }
+        let mut inner = self.inner.lock();
+        if inner.local.is_none() {
+            return Err(EINVAL);
+        }
+        let max = backlog.clamp(1, BACKLOG_MAX);
+        match &mut inner.state {
+            State::Unconnected => {
+                inner.state = State::Listening {
+                    backlog: VecDeque::new(),
+                    max,
+                };
+            }
+            State::Listening { max: old, .. } => *old = max,
+            State::Connected => return Err(EINVAL),
+        }
+        Ok(())
+    }
+
+    pub fn accept(&self, nonblock: bool) -> Result<Arc<UnixSocket>, i64> {
+        let conn = self.wq.wait_until(|| {
+            let mut inner = self.inner.lock();
+            let State::Listening { backlog, .. } = &mut inner.state else {
+                return Some(Err(EINVAL));
+            };
+            match backlog.pop_front() {
+                Some(conn) => Some(Ok(conn)),
+                None if nonblock => Some(Err(EAGAIN)),
+                None => None,
+            }
+        })?;
+        // room in the backlog again
+        self.wq.wake_all();
+        Ok(conn)
+    }
+
+    pub fn connect(self: &Arc<Self>, path: Path, nonblock: bool) -> Result<(), i64> {
+        let target = lookup(path)?;
+        if target.type_ != self.type_ {
+            return Err(EPROTOTYPE);
+        }
+
+        if self.type_ == SocketType::Datagram {
+            self.inner.lock().peer = Some(Arc::downgrade(&target));
+            return Ok(());
+        }
+
+        match self.inner.lock().state {
+            State::Unconnected => {}
+            State::Listening { .. } => return Err(EINVAL),
+            State::Connected => return Err(EISCONN),
+        }
+
+        let wq = target.wq.clone();
+        let target = Arc::downgrade(&target);
+        wq.wait_until(|| {
+            let Some(target) = target.upgrade() else {
+                return Some(Err(ECONNREFUSED));
+            };
+            let mut t = target.inner.lock();
+            let local = t.local.clone();
+            let State::Listening { backlog, max } = &mut t.state else {
+                return Some(Err(ECONNREFUSED));
+            };
+            if backlog.len() >= *max {
+                return if nonblock { Some(Err(EAGAIN)) } else { None };
+            }
+
+            // the server side end is created right away, accept() just hands it out
+            let server = UnixSocket::new(SocketType::Stream);
+            server.inner.lock().local = local;
+            connect_pair(self, &server);
+            backlog.push_back(server);
+            Some(Ok(()))
+        })?;
+        wq.wake_all();
+        Ok(())
+    }
+
+    pub fn send(
+        &self,
+        data: &[u8],
+        rights: Vec<FileDescriptor>,
+        dest: Option<Path>,
+        nonblock: bool,
+    ) -> Result<usize, i64> {
+        match self.type_ {
+            SocketType::Stream => {
+                if dest.is_some() {
+                    return Err(match self.inner.lock().state {
+                        State::Connected => EISCONN,
+                        _ => EOPNOTSUPP,
+                    });
+                }
+                self.send_stream(data, rights, nonblock)
+            }
+            SocketType::Datagram => self.send_datagram(data, rights, dest, nonblock),
+        }
+    }
+
+    fn send_stream(
+        &self,
+        data: &[u8],
+        rights: Vec<FileDescriptor>,
+        nonblock: bool,
+    ) -> Result<usize, i64> {
+        let peer = {
+            let inner = self.inner.lock();
+            if inner.write_shutdown {
+                return Err(EPIPE);
+            }
+            let State::Connected = inner.state else {
+                return Err(ENOTCONN);
+            };
+            inner.peer.clone().ok_or(ENOTCONN)?
+        };
+        let wq = peer.upgrade().ok_or(EPIPE)?.wq.clone();
+
+        // rights ride along with the first chunk
+        let mut rights = Some(rights);
+        let mut sent = 0;
+        while sent < data.len() {
+            let ret = wq.wait_until(|| {
+                let Some(peer) = peer.upgrade() else {
+                    return Some(Err(EPIPE));
+                };
+                let mut p = peer.inner.lock();
+                if p.read_shutdown {
+                    return Some(Err(EPIPE));
+                }
+                let space = SOCK_BUF_SIZE.saturating_sub(p.rx_bytes);
+                if space == 0 {
+                    return if nonblock { Some(Err(EAGAIN)) } else { None };
+                }
+                let n = space.min(data.len() - sent);
+                p.rx.push_back(Message {
+                    data: data[sent..sent + n].to_vec(),
+                    read: 0,
+                    rights: rights.take().unwrap_or_default(),
+                    from: None,
+                });
+                p.rx_bytes += n;
+                Some(Ok(n))
+            });
+            match ret {
+                Ok(n) => {
+                    sent += n;
+                    wq.wake_all();
+                }
+                Err(_) if sent > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sent)
+    }
+
+    fn send_datagram(
+        &self,
+        data: &[u8],
+        rights: Vec<FileDescriptor>,
+        dest: Option<Path>,
+        nonblock: bool,
+    ) -> Result<usize, i64> {
+        if data.len() > SOCK_BUF_SIZE {
+            return Err(EMSGSIZE);
+        }
+
+        let (target, from) = {
+            let inner = self.inner.lock();
+            if inner.write_shutdown {
+                return Err(EPIPE);
+            }
+            let target = match dest {
+                Some(_) => None,
+                None => Some(inner.peer.clone().ok_or(ENOTCONN)?),
+            };
+            (target, inner.local.clone())
+        };
+        let target = match (target, dest) {
+            (Some(target), _) => target,
+            (None, Some(dest)) => {
+                let target = lookup(dest)?;
+                if target.type_ != SocketType::Datagram {
+                    return Err(EPROTOTYPE);
+                }
+                Arc::downgrade(&target)
+            }
+            (None, None) => unreachable!(),
+        };
+        let wq = target.upgrade().ok_or(ECONNREFUSED)?.wq.clone();
+
+        let mut msg = Some(Message {
+            data: data.to_vec(),
+            read: 0,
+            rights,
+            from,
+        });
+        wq.wait_until(|| {
+            let Some(target) = target.upgrade() else {
+                return Some(Err(ECONNREFUSED));
+            };
+            let mut t = target.inner.lock();
+            if t.read_shutdown {
+                return Some(Err(EPIPE));
+            }
+            // an empty queue always takes the datagram so big ones can't get stuck
+            if !t.rx.is_empty() && t.rx_bytes + data.len() > SOCK_BUF_SIZE {
+                return if nonblock { Some(Err(EAGAIN)) } else { None };
+            }
+            t.rx.push_back(msg.take().unwrap());
+            t.rx_bytes += data.len();
+            Some(Ok(()))
+        })?;
+        wq.wake_all();
+        Ok(data.len())
+    }
+
+    pub fn recv(&self, buf: &mut [u8], peek: bool, nonblock: bool) -> Result<Received, i64> {
+        let received = self.wq.wait_until(|| {
+            let mut inner = self.inner.lock();
+            if inner.rx.is_empty() {
+                if let State::Listening { .. } = inner.state {
+                    return Some(Err(EINVAL));
+                }
+                if inner.eof || inner.read_shutdown {
+                    return Some(Ok(Received {
+                        len: 0,
+                        rights: Vec::new(),
+                        from: None,
+                        truncated: false,
+                    }));
+                }
+                if self.type_ == SocketType::Stream && !matches!(inner.state, State::Connected) {
+                    return Some(Err(ENOTCONN));
+                }
+                return if nonblock { Some(Err(EAGAIN)) } else { None };
+            }
+
+            Some(Ok(match self.type_ {
+                SocketType::Stream => recv_stream(&mut inner, buf, peek),
+                SocketType::Datagram => recv_datagram(&mut inner, buf, peek),
+            }))
+        })?;
+        if !peek {
+            // senders might be waiting for space
+            self.wq.wake_all();
+        }
+        Ok(received)
+    }
+
+    pub fn shutdown(&self, read: bool, write: bool) -> Result<(), i64> {
+        let peer = {
+            let mut inner = self.inner.lock();
+            if self.type_ == SocketType::Stream && !matches!(inner.state, State::Connected) {
+                return Err(ENOTCONN);
+            }
+            inner.read_shutdown |= read;
+            inner.write_shutdown |= write;
+            inner.peer.clone()
+        };
+        self.wq.wake_all();
+
+        if write
+            && self.type_ == SocketType::Stream
+            && let Some(peer) = peer.and_then(|p| p.upgrade())
+        {
+            peer.inner.lock().eof = true;
+            peer.wq.wake_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        // blocked senders find the socket gone and bail out
+        self.wq.wake_all();
+
+        let peer = self.inner.lock().peer.take();
+        if self.type_ == SocketType::Stream
+            && let Some(peer) = peer.and_then(|p| p.upgrade())
+        {
+            peer.inner.lock().eof = true;
+            peer.wq.wake_all();
+        }
+    }
+}
+
+impl FileObject for UnixSocket {
+    fn read(&self, buf: &mut [u8], nonblock: bool) -> Result<usize, i64> {
+        // passed fds are simply closed when read() is used
+        self.recv(buf, false, nonblock).map(|r| r.len)
+    }
+    fn write(&self, buf: &[u8], nonblock: bool) -> Result<usize, i64> {
+        self.send(buf, Vec::new(), None, nonblock)
+    }
+    fn mode(&self) -> u32 {
+        0o140000
+    }
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        let (peer, write_shutdown) = {
+            let inner = self.inner.lock();
+            if let State::Listening { backlog, .. } = &inner.state {
+                return if backlog.is_empty() {
+                    PollEvents::empty()
+                } else {
+                    PollEvents::IN
+                };
+            }
+            if !inner.rx.is_empty() {
+                events |= PollEvents::IN;
+            }
+            if inner.eof || inner.read_shutdown {
+                events |= PollEvents::IN | PollEvents::RDHUP;
+            }
+            (inner.peer.clone(), inner.write_shutdown)
+        };
+
+        match peer.map(|p| p.upgrade()) {
+            Some(Some(peer)) => {
+                let p = peer.inner.lock();
+                if !write_shutdown && !p.read_shutdown && p.rx_bytes < SOCK_BUF_SIZE {
+                    events |= PollEvents::OUT;
+                }
+            }
+            Some(None) if self.type_ == SocketType::Stream => events |= PollEvents::HUP,
+            // unconnected datagram sockets only fail at send time
+            _ if self.type_ == SocketType::Datagram => events |= PollEvents::OUT,
+            _ => {}
+        }
+        events
+    }
+    fn wait_queues(&self) -> Vec<Arc<WaitQueue>> {
+        let mut queues = vec![self.wq.clone()];
+        if let Some(peer) = self.inner.lock().peer.as_ref().and_then(|p| p.upgrade()) {
+            queues.push(peer.wq.clone());
+        }
+        queues
+    }
+}
+
+fn connect_pair(a: &Arc<UnixSocket>, b: &Arc<UnixSocket>) {
+    for (this, other) in [(a, b), (b, a)] {
+        let mut inner = this.inner.lock();
+        inner.peer = Some(Arc::downgrade(other));
+        if this.type_ == SocketType::Stream {
+            inner.state = State::Connected;
+        }
+    }
+}
+
+fn lookup(path: Path) -> Result<Arc<UnixSocket>, i64> {
+    let endpoint = {
+        let inode = get_vfs().resolve(path).ok_or(ENOENT)?;
+        let node = inode.node();
+        if node.get_type() != &VfsNodeType::Socket {
+            return Err(ECONNREFUSED);
+        }
+        node.bound_socket().ok_or(ECONNREFUSED)?
+    };
+    let endpoint: Arc<dyn core::any::Any + Send + Sync> = endpoint;
+    endpoint.downcast::<UnixSocket>().map_err(|_| ECONNREFUSED)
+}
+
+// stream reads may span several writes, but never merge past a message carrying fds
+fn recv_stream(inner: &mut Inner, buf: &mut [u8], peek: bool) -> Received {
+    let mut len = 0;
+    let mut rights = Vec::new();
+
+    if peek {
+        for (i, msg) in inner.rx.iter().enumerate() {
+            if len == buf.len() || (i > 0 && !msg.rights.is_empty()) {
+                break;
+            }
+            let n = (buf.len() - len).min(msg.data.len() - msg.read);
+            buf[len..len + n].copy_from_slice(&msg.data[msg.read..msg.read + n]);
+            len += n;
+        }
+    } else {
+        while len < buf.len()
+            && let Some(msg) = inner.rx.front_mut()
+        {
+            if len > 0 && !msg.rights.is_empty() {
+                break;
+            }
+            if len == 0 {
+                rights = core::mem::take(&mut msg.rights);
+            }
+            let n = (buf.len() - len).min(msg.data.len() - msg.read);
+            buf[len..len + n].copy_from_slice(&msg.data[msg.read..msg.read + n]);
+            msg.read += n;
+            len += n;
+            if msg.read == msg.data.len() {
+                inner.rx.pop_front();
+            }
+        }
+        inner.rx_bytes -= len;
+    }
+
+    Received {
+        len,
+        rights,
+        from: None,
+        truncated: false,
+    }
+}
+
+fn recv_datagram(inner: &mut Inner, buf: &mut [u8], peek: bool) -> Received {
+    let msg = inner.rx.front().unwrap();
+    let len = buf.len().min(msg.data.len());
+    buf[..len].copy_from_slice(&msg.data[..len]);
+    let truncated = len < msg.data.len();
+    let from = msg.from.clone();
+
+    let mut rights = Vec::new();
+    if !peek {
+        let msg = inner.rx.pop_front().unwrap();
+        inner.rx_bytes -= msg.data.len();
+        rights = msg.rights;
+    }
+
+    Received {
+        len,
+        rights,
+        from,
+        truncated,
+    }
+}
@@ -25,6 +25,8 @@ pub const NOOO: &str = include_str!("../res/nooo.txt");
 pub mod arch;
 pub mod device;
 pub mod drivers;
+#[cfg(target_arch = "x86_64")]
+pub mod ipc;
 pub mod memory;
 pub mod scheduler;
 pub mod utils;
@@ -0,0 +1,208 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::vec::Vec;
+
+use crate::utils::{errno::*, limine::get_hhdm_offset};
+
+use super::{
+    shared::MappedMemory,
+    vmm::{Pagemap, alloc_frame, flag, free_pages, page_size},
+};
+
+const PAGE: u64 = page_size::SMALL;
+
+// where mmap looks for room when no address is forced, well below the thread stacks
+pub const MMAP_BASE: u64 = 0x0000_2000_0000_0000;
+pub const MMAP_END: u64 = 0x0000_7000_0000_0000;
+
+#[derive(Clone)]
+pub enum Backing {
+    // zeroed pages owned by the pagemap, fork copies them
+    Private,
+    // frames of a shared memory object starting at `offset` bytes into it
+    Shared { memory: MappedMemory, offset: u64 },
+}
+
+#[derive(Clone)]
+pub struct Mapping {
+    pub start: u64,
+    pub len: u64,
+    // page table flags, without PRESENT for PROT_NONE
+    pub flags: u64,
+    pub backing: Backing,
+    // SysV segment id for shmat attachments, shmdt only takes those
+    pub shmid: Option<i32>,
+}
+
+impl Mapping {
+    pub fn end(&self) -> u64 {
+        self.start + self.len
+    }
+}
+
+// the mmapped regions of a process, sorted by address
+#[derive(Clone, Default)]
+pub struct Mappings {
+    list: Vec<Mapping>,
+}
+
+impl Mappings {
+    pub fn find(&self, addr: u64) -> Option<&Mapping> {
+        self.list.iter().find(|m| m.start <= addr && addr < m.end())
+    }
+
+    // sorted by address
+    pub fn iter(&self) -> impl Iterator<Item = &Mapping> {
+        self.list.iter()
+    }
+
+    // first gap in [MMAP_BASE, MMAP_END) that fits `len` bytes
+    pub fn find_free(&self, len: u64) -> Option<u64> {
+        let mut candidate = MMAP_BASE;
+        for mapping in &self.list {
+            if mapping.end() <= candidate {
+                continue;
+            }
+            if mapping.start >= candidate + len {
+                break;
+            }
+            candidate = mapping.end();
+        }
+        (candidate + len <= MMAP_END).then_some(candidate)
+    }
+
+    // maps every page of `mapping` and records it, mappings already there are replaced.
+    // private pages start out with `data` (a MAP_PRIVATE file snapshot), zeroes after it.
+    // ENOMEM over pages mmap didn't make (program text, the stack), their frames aren't
+    // ours to free
+    pub fn map(&mut self, pagemap: &mut Pagemap, mapping: Mapping, data: &[u8]) -> Result<(), i64> {
+        let foreign = (mapping.start..mapping.end())
+            .step_by(PAGE as usize)
+            .any(|virt| self.find(virt).is_none() && pagemap.is_mapped(virt));
+        if foreign {
+            return Err(ENOMEM);
+        }
+        self.unmap(pagemap, mapping.start, mapping.len);
+
+        let pages = mapping.len / PAGE;
+        let frames = match &mapping.backing {
+            Backing::Private => Vec::new(),
+            Backing::Shared { memory, offset } => {
+                memory.frames((offset / PAGE) as usize, pages as usize)
+            }
+        };
+
+        if mapping.flags & flag::PRESENT != 0 {
+            for i in 0..pages {
+                let virt = mapping.start + i * PAGE;
+                let (phys, flags) = match mapping.backing {
+                    Backing::Private => match alloc_frame() {
+                        Some(frame) => {
+                            fill(frame, data, (i * PAGE) as usize);
+                            (frame, mapping.flags)
+                        }
+                        None => {
+                            // undo what we did so far
+                            unmap_pages(pagemap, &mapping.backing, mapping.start, i * PAGE);
+                            return Err(ENOMEM);
+                        }
+                    },
+                    // past the end of the object, touching it faults like SIGBUS would
+                    Backing::Shared { .. } => match frames.get(i as usize) {
+                        Some(&frame) => (frame, mapping.flags | flag::SHARED),
+                        None => break,
+                    },
+                };
+                pagemap
+                    .map(virt, phys, flags, page_size::SMALL)
+                    .map_err(|_| ENOMEM)?;
+            }
+        }
+
+        let idx = self.list.partition_point(|m| m.start < mapping.start);
+        self.list.insert(idx, mapping);
+        Ok(())
+    }
+
+    // unmaps [start, start + len), splitting mappings that only partly overlap
+    pub fn unmap(&mut self, pagemap: &mut Pagemap, start: u64, len: u64) {
+        let end = start + len;
+        let mut kept = Vec::with_capacity(self.list.len());
+        for mapping in self.list.drain(..) {
+            if mapping.end() <= start || mapping.start >= end {
+                kept.push(mapping);
+                continue;
+            }
+
+            let cut_start = mapping.start.max(start);
+            let cut_end = mapping.end().min(end);
+            unmap_pages(pagemap, &mapping.backing, cut_start, cut_end - cut_start);
+
+            if mapping.start < cut_start {
+                kept.push(Mapping {
+                    len: cut_start - mapping.start,
+                    ..mapping.clone()
+                });
+            }
+            if cut_end < mapping.end() {
+                let skipped = cut_end - mapping.start;
+                let backing = match &mapping.backing {
+                    Backing::Private => Backing::Private,
+                    Backing::Shared { memory, offset } => Backing::Shared {
+                        memory: memory.clone(),
+                        offset: offset + skipped,
+                    },
+                };
+                kept.push(Mapping {
+                    start: cut_end,
+                    len: mapping.end() - cut_end,
+                    backing,
+                    ..mapping
+                });
+            }
+        }
+        kept.sort_by_key(|m| m.start);
+        self.list = kept;
+    }
+
+    // unmaps the shmat attachment starting at `start`, for shmdt
+    pub fn detach(&mut self, pagemap: &mut Pagemap, start: u64) -> Option<Mapping> {
+        let idx = self
+            .list
+            .iter()
+            .position(|m| m.start == start && m.shmid.is_some())?;
+        let mapping = self.list.remove(idx);
+        unmap_pages(pagemap, &mapping.backing, mapping.start, mapping.len);
+        Some(mapping)
+    }
+}
+
+fn unmap_pages(pagemap: &mut Pagemap, backing: &Backing, start: u64, len: u64) {
+    for virt in (start..start + len).step_by(PAGE as usize) {
+        let old = pagemap.unmap(virt);
+        if let Backing::Private = backing {
+            release(old);
+        }
+    }
+}
+
+fn fill(frame: u64, data: &[u8], from: usize) {
+    if from >= data.len() {
+        return;
+    }
+    let chunk = &data[from..data.len().min(from + PAGE as usize)];
+    let dst = (frame + get_hhdm_offset()) as *mut u8;
+    unsafe { core::ptr::copy_nonoverlapping(chunk.as_ptr(), dst, chunk.len()) };
+}
+
+// frees the frame behind an old entry unless a shared object owns it
+fn release(entry: Option<u64>) {
+    if let Some(entry) = entry
+        && entry & flag::SHARED == 0
+    {
+        free_pages(entry & flag::PADDR_MASK, PAGE as usize);
+    }
+}
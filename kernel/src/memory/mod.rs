@@ -11,6 +11,8 @@ use crate::{
     utils::limine::{get_hhdm_offset, get_memory_map},
 };
 
+pub mod mmap;
+pub mod shared;
 pub mod vmm;
 
 pub const KERNEL_STACK_SIZE: usize = 64 * 1024;
@@ -69,3 +71,8 @@ pub fn get_usable_memory() -> u64 {
 pub fn get_reserved_memory() -> u64 {
     RESERVED_MEMORY.load(Ordering::Relaxed)
 }
+
+// what the kernel heap looks like right now, frames come out of it too
+pub fn get_heap_stats() -> talc::base::Counters {
+    *ALLOCATOR.lock().counters()
+}
@@ -0,0 +1,225 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::{
+    ops::Deref,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::utils::{errno::*, limine::get_hhdm_offset, spinlock::Spin};
+
+use super::{
+    get_usable_memory,
+    vmm::{alloc_frame, free_pages, page_size},
+};
+
+const PAGE: usize = page_size::SMALL as usize;
+
+// largest object we'll back with frames, anything above is ENOMEM up front
+pub const SHARED_MAX_SIZE: usize = 1 << 30;
+
+// frames held by every object together (memfd, shm, /dev/shm, shared anonymous maps)
+static FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+// half of memory, the same as a default tmpfs. ENOMEM past it
+fn charge(frames: usize) -> Result<(), i64> {
+    let max = get_usable_memory() as usize / PAGE / 2;
+    FRAMES
+        .try_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(frames).filter(|&total| total <= max)
+        })
+        .map(|_| ())
+        .map_err(|_| ENOMEM)
+}
+
+fn uncharge(frames: usize) {
+    FRAMES.fetch_sub(frames, Ordering::Relaxed);
+}
+
+struct Inner {
+    frames: Vec<u64>,
+    size: usize,
+    // live mappings, see MappedMemory
+    maps: usize,
+}
+
+impl Inner {
+    // gives back the frames past the end, only safe once no pagemap can still point at them
+    fn trim(&mut self) {
+        let pages = self.size.div_ceil(PAGE);
+        if self.frames.len() > pages {
+            uncharge(self.frames.len() - pages);
+            for frame in self.frames.drain(pages..) {
+                free_pages(frame, PAGE);
+            }
+        }
+    }
+}
+
+// a run of physical pages that several pagemaps can map at once (memfd, shm, /dev/shm)
+// mappings hold an Arc so the frames outlive every fd and segment that named them
+pub struct SharedMemory {
+    inner: Spin<Inner>,
+}
+
+impl SharedMemory {
+    pub fn new(size: usize) -> Result<Arc<Self>, i64> {
+        let memory = Arc::new(Self {
+            inner: Spin::new(Inner {
+                frames: Vec::new(),
+                size: 0,
+                maps: 0,
+            }),
+        });
+        memory.resize(size)?;
+        Ok(memory)
+    }
+
+    pub fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+
+    // shrinking frees the frames past the end, unless someone has it mapped. then they're
+    // kept (zeroed, so they read back as zeroes if it grows again) until the last mapping goes
+    pub fn resize(&self, size: usize) -> Result<(), i64> {
+        if size > SHARED_MAX_SIZE {
+            return Err(ENOMEM);
+        }
+        let mut inner = self.inner.lock();
+        let pages = size.div_ceil(PAGE);
+        if inner.frames.len() < pages {
+            let missing = pages - inner.frames.len();
+            charge(missing)?;
+            inner.frames.try_reserve(missing).map_err(|_| {
+                uncharge(missing);
+                ENOMEM
+            })?;
+            while inner.frames.len() < pages {
+                let Some(frame) = alloc_frame() else {
+                    uncharge(pages - inner.frames.len());
+                    return Err(ENOMEM);
+                };
+                inner.frames.push(frame);
+            }
+        }
+        if size < inner.size {
+            zero(&inner.frames, size, inner.size - size);
+        }
+        inner.size = size;
+        if inner.maps == 0 {
+            inner.trim();
+        }
+        Ok(())
+    }
+
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let inner = self.inner.lock();
+        if offset >= inner.size {
+            return 0;
+        }
+        let len = buf.len().min(inner.size - offset);
+        copy(&inner.frames, offset, len, |frame, chunk, done| unsafe {
+            core::ptr::copy_nonoverlapping(frame, buf[done..].as_mut_ptr(), chunk)
+        });
+        len
+    }
+
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, i64> {
+        let end = offset.checked_add(buf.len()).ok_or(EINVAL)?;
+        if end > self.size() {
+            self.resize(end)?;
+        }
+        let inner = self.inner.lock();
+        copy(
+            &inner.frames,
+            offset,
+            buf.len(),
+            |frame, chunk, done| unsafe {
+                core::ptr::copy_nonoverlapping(buf[done..].as_ptr(), frame, chunk)
+            },
+        );
+        Ok(buf.len())
+    }
+
+    // physical frames backing pages [first, first + count), short if the object is smaller
+    pub fn frames(&self, first: usize, count: usize) -> Vec<u64> {
+        let inner = self.inner.lock();
+        let pages = inner.size.div_ceil(PAGE);
+        inner
+            .frames
+            .iter()
+            .take(pages)
+            .skip(first)
+            .take(count)
+            .copied()
+            .collect()
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        let inner = self.inner.lock();
+        uncharge(inner.frames.len());
+        for &frame in inner.frames.iter() {
+            free_pages(frame, PAGE);
+        }
+    }
+}
+
+// what a mapping holds on to, frames past the end aren't freed while any of these exist
+pub struct MappedMemory(Arc<SharedMemory>);
+
+impl MappedMemory {
+    pub fn new(memory: Arc<SharedMemory>) -> Self {
+        memory.inner.lock().maps += 1;
+        Self(memory)
+    }
+}
+
+impl Clone for MappedMemory {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for MappedMemory {
+    fn drop(&mut self) {
+        let mut inner = self.0.inner.lock();
+        inner.maps -= 1;
+        if inner.maps == 0 {
+            inner.trim();
+        }
+    }
+}
+
+impl Deref for MappedMemory {
+    type Target = SharedMemory;
+
+    fn deref(&self) -> &SharedMemory {
+        &self.0
+    }
+}
+
+// walks the frames covering [offset, offset + len) a page at a time
+fn copy(frames: &[u64], offset: usize, len: usize, mut f: impl FnMut(*mut u8, usize, usize)) {
+    let hhdm = get_hhdm_offset();
+    let mut done = 0;
+    while done < len {
+        let pos = offset + done;
+        let in_page = pos % PAGE;
+        let chunk = (PAGE - in_page).min(len - done);
+        let frame = (frames[pos / PAGE] + hhdm) as *mut u8;
+        f(unsafe { frame.add(in_page) }, chunk, done);
+        done += chunk;
+    }
+}
+
+fn zero(frames: &[u64], offset: usize, len: usize) {
+    copy(frames, offset, len, |frame, chunk, _| unsafe {
+        core::ptr::write_bytes(frame, 0, chunk)
+    });
+}
@@ -25,6 +25,9 @@ pub mod flag {
     pub const WRITE: u64 = 1 << 1;
     pub const USER: u64 = 1 << 2;
     pub const LPAGES: u64 = 1 << 7;
+    // available to software: the frame belongs to a shared memory object, not to this
+    // pagemap, so fork shares it instead of copying and teardown leaves it alone
+    pub const SHARED: u64 = 1 << 9;
     pub const NO_EXEC: u64 = 1 << 63;
 
     pub const RW: u64 = PRESENT | WRITE;
@@ -136,6 +139,28 @@ impl Pagemap {
         }
     }
 
+    // clears a 4KiB mapping and returns the old entry, large pages are left alone
+    pub fn unmap(&mut self, virt: u64) -> Option<u64> {
+        let hhdm = get_hhdm_offset();
+        let mut table = (self.top_level as u64 + hhdm) as *mut u64;
+
+        for shift in [39, 30, 21] {
+            let entry = unsafe { *table.add(((virt >> shift) & 0x1ff) as usize) };
+            if !is_table(entry) {
+                return None;
+            }
+            table = ((entry & flag::PADDR_MASK) + hhdm) as *mut u64;
+        }
+
+        let pte = unsafe { &mut *table.add(((virt >> 12) & 0x1ff) as usize) };
+        if *pte & flag::PRESENT == 0 {
+            return None;
+        }
+        let old = core::mem::take(pte);
+        unsafe { core::arch::asm!("invlpg [{}]", in(reg) virt, options(nostack)) };
+        Some(old)
+    }
+
     pub fn new_user() -> Pagemap {
         let hhdm = get_hhdm_offset();
         let new = Pagemap::new();
@@ -227,6 +252,10 @@ impl Pagemap {
                         if pml1e & flag::PRESENT == 0 {
                             continue;
                         }
+                        if pml1e & flag::SHARED != 0 {
+                            unsafe { *dst_pml1.add(i1 as usize) = pml1e };
+                            continue;
+                        }
                         let old_phys = pml1e & flag::PADDR_MASK;
                         let new_phys = alloc_pages(page_size::SMALL as usize);
                         unsafe {
@@ -283,7 +312,7 @@ impl Pagemap {
 
                     for i1 in 0..512u64 {
                         let pml1e = unsafe { *pml1.add(i1 as usize) };
-                        if pml1e & flag::PRESENT == 0 {
+                        if pml1e & flag::PRESENT == 0 || pml1e & flag::SHARED != 0 {
                             continue;
                         }
                         free_pages(pml1e & flag::PADDR_MASK, page_size::SMALL as usize);
@@ -368,7 +397,14 @@ fn alloc_pages(size: usize) -> u64 {
     }
 }
 
-fn free_pages(phys: u64, size: usize) {
+// a zeroed page for user memory, unlike alloc_pages running out isn't fatal
+pub fn alloc_frame() -> Option<u64> {
+    let ptr =
+        unsafe { alloc::alloc::alloc_zeroed(Layout::from_size_align(0x1000, 0x1000).unwrap()) };
+    (!ptr.is_null()).then(|| ptr as u64 - get_hhdm_offset())
+}
+
+pub fn free_pages(phys: u64, size: usize) {
     let hhdm = get_hhdm_offset();
     unsafe {
         alloc::alloc::dealloc(
@@ -8,7 +8,11 @@ pub mod preemptive;
 #[cfg(target_arch = "x86_64")]
 pub use preemptive::*;
 #[cfg(target_arch = "x86_64")]
+pub mod signal;
+#[cfg(target_arch = "x86_64")]
 pub mod thread;
+#[cfg(target_arch = "x86_64")]
+pub mod wait;
 
 pub mod cooperative;
 #[cfg(target_arch = "aarch64")]
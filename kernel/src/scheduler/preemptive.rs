@@ -11,12 +11,15 @@ use core::{
 
 use crate::{
     drivers::fs::FileDescriptor,
-    memory::{KERNEL_STACK_SIZE, USER_STACK_SIZE},
+    ipc::sem::SEMAPHORES,
+    memory::{KERNEL_STACK_SIZE, USER_STACK_SIZE, mmap::Mappings},
     utils::{asm::without_ints, spinlock::Spin},
 };
 use alloc::{
     collections::{btree_map::BTreeMap, vec_deque::VecDeque},
+    string::String,
     sync::Arc,
+    vec,
     vec::Vec,
 };
 
@@ -27,7 +30,7 @@ use crate::{
     utils::asm::halt_loop,
 };
 
-use super::thread::*;
+use super::{signal::*, thread::*};
 
 pub static mut SCHEDULER: OnceCell<Scheduler> = OnceCell::new();
 
@@ -57,6 +60,8 @@ impl Default for Scheduler {
 
 pub struct Process {
     name: &'static str,
+    // argv of the last execve, /proc/<pid>/cmdline
+    cmdline: Vec<String>,
     pid: u64,
     ppid: u64,
     next_tid: AtomicU64,
@@ -67,6 +72,8 @@ pub struct Process {
     pub pagemap: Arc<Spin<Pagemap>>,
     children: Vec<Arc<Spin<Thread>>>,
     exit_status: Option<i32>,
+    signals: Arc<Signals>,
+    mappings: Mappings,
 }
 
 unsafe impl Send for Process {}
@@ -76,6 +83,7 @@ impl Process {
         let pid = next_pid();
         Self {
             name,
+            cmdline: vec![String::from(name)],
             pid,
             ppid,
             next_tid: AtomicU64::new(1),
@@ -86,6 +94,8 @@ impl Process {
             pagemap,
             children: Vec::new(),
             exit_status: None,
+            signals: Arc::new(Signals::default()),
+            mappings: Mappings::default(),
         }
     }
 
@@ -93,6 +103,14 @@ impl Process {
         self.name
     }
 
+    pub fn get_cmdline(&self) -> &[String] {
+        &self.cmdline
+    }
+
+    pub fn set_cmdline(&mut self, cmdline: Vec<String>) {
+        self.cmdline = cmdline;
+    }
+
     pub fn get_pid(&self) -> u64 {
         self.pid
     }
@@ -121,6 +139,18 @@ impl Process {
         &mut self.children
     }
 
+    pub fn get_signals(&self) -> &Arc<Signals> {
+        &self.signals
+    }
+
+    pub fn get_mappings(&self) -> &Mappings {
+        &self.mappings
+    }
+
+    pub fn get_mappings_mut(&mut self) -> &mut Mappings {
+        &mut self.mappings
+    }
+
     pub fn get_cwd(&self) -> &fs::Path {
         &self.cwd
     }
@@ -288,6 +318,26 @@ pub fn kill_process(pid: u64) -> bool {
             if lock.exit_status.is_none() {
                 lock.set_exit_status(0);
             }
+            let status = lock.exit_status.unwrap();
+            let ppid = lock.ppid;
+            // close everything now so peers see eof without waiting for the reap,
+            // shared memory goes too so shm segments see the detach
+            let fdt = core::mem::take(&mut lock.fdt);
+            let mappings = core::mem::take(&mut lock.mappings);
+            drop(lock);
+            drop(fdt);
+            drop(mappings);
+            SEMAPHORES.lock().exit(pid);
+            send_signal(
+                ppid,
+                SigInfo {
+                    signo: SIGCHLD,
+                    code: CLD_EXITED,
+                    pid,
+                    status,
+                    value: 0,
+                },
+            );
             true
         } else {
             false
@@ -325,6 +375,7 @@ pub fn fork_process(parent_regs: &Registers) -> u64 {
         let child_pid = next_pid();
         let mut child = Process {
             name: parent_lock.name,
+            cmdline: parent_lock.cmdline.clone(),
             pid: child_pid,
             ppid: parent_pid,
             next_tid: AtomicU64::new(parent_lock.next_tid.load(Ordering::Relaxed)),
@@ -335,6 +386,8 @@ pub fn fork_process(parent_regs: &Registers) -> u64 {
             pagemap: new_pagemap,
             children: Vec::new(),
             exit_status: None,
+            signals: Arc::new(Signals::default()),
+            mappings: parent_lock.mappings.clone(),
         };
 
         for (&fd_num, fd) in &parent_lock.fdt {
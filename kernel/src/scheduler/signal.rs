@@ -0,0 +1,96 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{collections::vec_deque::VecDeque, sync::Arc};
+
+use crate::utils::{asm::without_ints, spinlock::Spin};
+
+use super::{get_scheduler, wait::WaitQueue};
+
+pub const SIGCHLD: u32 = 17;
+// first realtime signal, everything below it only gets queued once
+pub const SIGRTMIN: u32 = 32;
+pub const NSIG: u32 = 64;
+
+// si_code values for SIGCHLD
+pub const CLD_EXITED: i32 = 1;
+// si_code for a message queue notification
+pub const SI_MESGQ: i32 = -3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: u32,
+    pub code: i32,
+    pub pid: u64,
+    pub status: i32,
+    // sigval from whoever asked for the signal
+    pub value: u64,
+}
+
+// there's no delivery to handlers yet, pending signals are only consumed through signalfd
+pub struct Signals {
+    pending: Spin<VecDeque<SigInfo>>,
+    // woken when a signal is raised
+    pub wq: Arc<WaitQueue>,
+}
+
+impl Default for Signals {
+    fn default() -> Self {
+        Self {
+            pending: Spin::new(VecDeque::new()),
+            wq: Arc::new(WaitQueue::new()),
+        }
+    }
+}
+
+impl Signals {
+    pub fn raise(&self, info: SigInfo) {
+        without_ints(|| {
+            let mut pending = self.pending.lock();
+            if info.signo < SIGRTMIN && pending.iter().any(|s| s.signo == info.signo) {
+                return;
+            }
+            pending.push_back(info);
+        });
+        self.wq.wake_all();
+    }
+
+    // bit n - 1 of the mask stands for signal n, like sigset_t
+    pub fn pending_mask(&self) -> u64 {
+        without_ints(|| {
+            self.pending
+                .lock()
+                .iter()
+                .fold(0, |mask, s| mask | 1 << (s.signo - 1))
+        })
+    }
+
+    pub fn take(&self, mask: u64) -> Option<SigInfo> {
+        without_ints(|| {
+            let mut pending = self.pending.lock();
+            let idx = pending
+                .iter()
+                .position(|s| mask & (1 << (s.signo - 1)) != 0)?;
+            pending.remove(idx)
+        })
+    }
+}
+
+pub fn send_signal(pid: u64, info: SigInfo) -> bool {
+    let signals = without_ints(|| {
+        get_scheduler()
+            .processes
+            .iter()
+            .find(|p| p.lock().get_pid() == pid)
+            .map(|p| p.lock().get_signals().clone())
+    });
+    match signals {
+        Some(signals) => {
+            signals.raise(info);
+            true
+        }
+        None => false,
+    }
+}
@@ -215,7 +215,7 @@ pub fn sleep(ns: u64) {
     if let Some(thread) = current_thread() {
         thread
             .lock()
-            .set_status(Status::Sleeping(preferred_timer_ns() + ns));
+            .set_status(Status::Sleeping(preferred_timer_ns().saturating_add(ns)));
     }
     yield_();
     toggle_ints(was_enabled);
@@ -249,10 +249,15 @@ pub fn block() {
 pub fn wake(thread: &Arc<Spin<Thread>>) {
     without_ints(|| {
         let mut t = thread.lock();
-        if t.get_status() == Status::Blocked {
-            t.set_status(Status::Ready);
-            drop(t);
-            get_scheduler().queue.push_back(thread.clone());
+        match t.get_status() {
+            Status::Blocked => {
+                t.set_status(Status::Ready);
+                drop(t);
+                get_scheduler().queue.push_back(thread.clone());
+            }
+            // timed waits sleep instead of blocking, they're still queued
+            Status::Sleeping(_) => t.set_status(Status::Ready),
+            _ => {}
         }
     });
 }
@@ -0,0 +1,115 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::{sync::Arc, vec::Vec};
+
+use crate::{
+    arch::drivers::time::preferred_timer_ns,
+    utils::{asm::without_ints, spinlock::Spin},
+};
+
+use super::thread::*;
+
+// threads blocked on some condition, whoever changes that condition calls wake_all()
+pub struct WaitQueue {
+    waiters: Spin<Vec<Arc<Spin<Thread>>>>,
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: Spin::new(Vec::new()),
+        }
+    }
+
+    // blocks until `cond` returns Some, it's re-checked after every wakeup
+    pub fn wait_until<T>(&self, mut cond: impl FnMut() -> Option<T>) -> T {
+        loop {
+            if let Some(value) = wait_any(&[self], None, &mut cond) {
+                return value;
+            }
+        }
+    }
+
+    // same as wait_until but gives up with None once `deadline` (preferred_timer_ns) passes
+    pub fn wait_until_deadline<T>(
+        &self,
+        deadline: Option<u64>,
+        cond: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        wait_any(&[self], deadline, cond)
+    }
+
+    pub fn wake_all(&self) {
+        let waiters = without_ints(|| core::mem::take(&mut *self.waiters.lock()));
+        for thread in waiters.iter() {
+            wake(thread);
+        }
+    }
+
+    fn remove(&self, thread: &Arc<Spin<Thread>>) {
+        self.waiters.lock().retain(|t| !Arc::ptr_eq(t, thread));
+    }
+}
+
+// waits on several queues at once (poll and friends), any of them waking us re-checks `cond`
+pub fn wait_any<T>(
+    queues: &[&WaitQueue],
+    deadline: Option<u64>,
+    mut cond: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    let Some(thread) = current_thread() else {
+        return spin_until(deadline, cond);
+    };
+    loop {
+        let ready = without_ints(|| {
+            if let Some(value) = cond() {
+                return Some(Some(value));
+            }
+            if deadline.is_some_and(|d| preferred_timer_ns() >= d) {
+                return Some(None);
+            }
+            for queue in queues {
+                queue.waiters.lock().push(thread.clone());
+            }
+            thread.lock().set_status(match deadline {
+                Some(d) => Status::Sleeping(d),
+                None => Status::Blocked,
+            });
+            None
+        });
+        if let Some(ready) = ready {
+            return ready;
+        }
+
+        yield_();
+
+        // only the queue that woke us dropped us, clean up the rest
+        without_ints(|| {
+            for queue in queues {
+                queue.remove(&thread);
+            }
+        });
+    }
+}
+
+// nobody to put to sleep yet (early init, before the scheduler runs), so just poll
+fn spin_until<T>(deadline: Option<u64>, mut cond: impl FnMut() -> Option<T>) -> Option<T> {
+    loop {
+        if let Some(value) = without_ints(&mut cond) {
+            return Some(value);
+        }
+        if deadline.is_some_and(|d| preferred_timer_ns() >= d) {
+            return None;
+        }
+        core::hint::spin_loop();
+    }
+}
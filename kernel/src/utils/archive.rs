@@ -0,0 +1,65 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// what tar and cpio have in common, so either can be unpacked the same way
+
+use alloc::borrow::Cow;
+
+use super::{
+    cpio::{CpioIter, is_cpio},
+    ustar::{TarIter, is_tar},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    // another name for an earlier entry, `link` is its path
+    Hardlink,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Directory,
+    Fifo,
+}
+
+pub struct Entry<'a> {
+    pub name: Cow<'a, str>,
+    pub kind: EntryKind,
+    // symlink target, or the path a hardlink points at
+    pub link: &'a str,
+    // the permission bits, no type
+    pub mode: u32,
+    pub mtime: u64,
+    pub data: &'a [u8],
+}
+
+pub enum Entries<'a> {
+    Tar(TarIter<'a>),
+    Cpio(CpioIter<'a>),
+}
+
+impl<'a> Entries<'a> {
+    // by the magic, None if it's neither
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        if is_cpio(data) {
+            Some(Self::Cpio(CpioIter::new(data)))
+        } else if is_tar(data) {
+            Some(Self::Tar(TarIter::new(data)))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Tar(iter) => iter.next(),
+            Self::Cpio(iter) => iter.next(),
+        }
+    }
+}
@@ -0,0 +1,316 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// deflate (rfc 1951) and the gzip wrapper around it (rfc 1952)
+
+use alloc::{vec, vec::Vec};
+
+use crate::utils::crc32::crc32;
+
+// lsb first, runs out into zeroes that can't be consumed
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u64,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buf: 0,
+            count: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.count <= 56 && self.pos < self.data.len() {
+            self.buf |= (self.data[self.pos] as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+    }
+
+    fn consume(&mut self, n: u32) -> Result<(), &'static str> {
+        if n > self.count {
+            return Err("deflate stream truncated");
+        }
+        self.buf >>= n;
+        self.count -= n;
+        Ok(())
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, &'static str> {
+        self.refill();
+        let value = (self.buf & ((1 << n) - 1)) as u32;
+        self.consume(n)?;
+        Ok(value)
+    }
+
+    // drops what's left of the current byte and hands back the bytes still buffered
+    fn align(&mut self) {
+        self.pos -= (self.count / 8) as usize;
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+// indexed by the next `bits` bits of input, each entry is symbol << 4 | code length
+struct Huffman {
+    table: Vec<u16>,
+    bits: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, &'static str> {
+        let bits = lengths.iter().copied().max().unwrap_or(0) as u32;
+        if bits == 0 {
+            // a distance tree with no codes is fine as long as nothing uses it
+            return Ok(Self {
+                table: vec![0; 1],
+                bits: 0,
+            });
+        }
+        let mut count = [0u16; 16];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        count[0] = 0;
+        let mut next = [0u16; 16];
+        let mut code = 0u16;
+        for len in 1..16 {
+            code = (code + count[len - 1]) << 1;
+            next[len] = code;
+        }
+
+        let mut table = vec![0u16; 1 << bits];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            if code >= 1 << len {
+                return Err("oversubscribed huffman code");
+            }
+            let reversed = code.reverse_bits() >> (16 - len);
+            let entry = (symbol as u16) << 4 | len as u16;
+            let mut i = reversed as usize;
+            while i < table.len() {
+                table[i] = entry;
+                i += 1 << len;
+            }
+        }
+        Ok(Self { table, bits })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, &'static str> {
+        reader.refill();
+        let entry = self.table[(reader.buf & ((1 << self.bits) - 1)) as usize];
+        let len = (entry & 15) as u32;
+        if len == 0 {
+            return Err("invalid huffman code");
+        }
+        reader.consume(len)?;
+        Ok(entry >> 4)
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// the order code length code lengths come in
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn fixed_trees() -> Result<(Huffman, Huffman), &'static str> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_trees(reader: &mut BitReader) -> Result<(Huffman, Huffman), &'static str> {
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("bad code counts");
+    }
+
+    let mut clens = [0u8; 19];
+    for &i in &CLEN_ORDER[..ncode] {
+        clens[i] = reader.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clens)?;
+
+    let mut lengths = [0u8; 286 + 30];
+    let mut i = 0;
+    while i < nlen + ndist {
+        let (len, repeat) = match clen.decode(reader)? {
+            sym @ 0..16 => (sym as u8, 1),
+            16 => {
+                let prev = *lengths[..i]
+                    .last()
+                    .ok_or("repeat with no previous length")?;
+                (prev, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        let end = i + repeat as usize;
+        if end > nlen + ndist {
+            return Err("code lengths overrun");
+        }
+        lengths[i..end].fill(len);
+        i = end;
+    }
+    if lengths[256] == 0 {
+        return Err("no end of block code");
+    }
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..nlen + ndist])?,
+    ))
+}
+
+fn block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    start: usize,
+    lit: &Huffman,
+    dist: &Huffman,
+) -> Result<(), &'static str> {
+    loop {
+        let sym = lit.decode(reader)? as usize;
+        match sym {
+            0..256 => out.push(sym as u8),
+            256 => return Ok(()),
+            257..286 => {
+                let i = sym - 257;
+                let len = LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = dist.decode(reader)? as usize;
+                if d >= 30 {
+                    return Err("bad distance code");
+                }
+                let distance = DIST_BASE[d] as usize + reader.bits(DIST_EXTRA[d] as u32)? as usize;
+                if distance > out.len() - start {
+                    return Err("distance too far back");
+                }
+                // can overlap what it's writing, so byte by byte
+                let from = out.len() - distance;
+                out.reserve(len);
+                for j in 0..len {
+                    out.push(out[from + j]);
+                }
+            }
+            _ => return Err("bad length code"),
+        }
+    }
+}
+
+// appends the raw deflate stream at the start of `data` to `out`, returns how much of
+// `data` it took
+pub fn inflate(data: &[u8], out: &mut Vec<u8>) -> Result<usize, &'static str> {
+    let start = out.len();
+    let mut reader = BitReader::new(data);
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or("deflate stream truncated")?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err("stored block length mismatch");
+                }
+                let from = reader.pos + 4;
+                let stored = data
+                    .get(from..from + len as usize)
+                    .ok_or("deflate stream truncated")?;
+                out.extend_from_slice(stored);
+                reader.pos = from + len as usize;
+            }
+            1 => {
+                let (lit, dist) = fixed_trees()?;
+                block(&mut reader, out, start, &lit, &dist)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_trees(&mut reader)?;
+                block(&mut reader, out, start, &lit, &dist)?;
+            }
+            _ => return Err("reserved block type"),
+        }
+        if last {
+            reader.align();
+            return Ok(reader.pos);
+        }
+    }
+}
+
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// every member one after the other, `gzip -c a b` and what the linux build makes
+pub fn gunzip(mut data: &[u8], out: &mut Vec<u8>) -> Result<(), &'static str> {
+    while data.starts_with(&GZIP_MAGIC) {
+        let truncated = "gzip header truncated";
+        if data.len() < 10 || data[2] != 8 {
+            return Err("not deflate");
+        }
+        let flags = data[3];
+        let mut pos = 10;
+        // FEXTRA
+        if flags & 4 != 0 {
+            let xlen = data.get(pos..pos + 2).ok_or(truncated)?;
+            pos += 2 + u16::from_le_bytes([xlen[0], xlen[1]]) as usize;
+        }
+        // FNAME and FCOMMENT, both nul terminated
+        for flag in [8, 16] {
+            if flags & flag != 0 {
+                let rest = data.get(pos..).ok_or(truncated)?;
+                pos += rest.iter().position(|&b| b == 0).ok_or(truncated)? + 1;
+            }
+        }
+        // FHCRC
+        if flags & 2 != 0 {
+            pos += 2;
+        }
+
+        let start = out.len();
+        pos += inflate(data.get(pos..).ok_or(truncated)?, out)?;
+        let trailer = data.get(pos..pos + 8).ok_or("gzip trailer truncated")?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+        let size = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+        if crc32(&out[start..]) != crc || (out.len() - start) as u32 != size {
+            return Err("gzip checksum mismatch");
+        }
+        data = &data[pos + 8..];
+        // the linux build pads between members
+        while let [0, rest @ ..] = data {
+            data = rest;
+        }
+    }
+    Ok(())
+}
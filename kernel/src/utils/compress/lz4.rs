@@ -0,0 +1,138 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// the lz4 frame format, plus the legacy one `lz4 -l` makes for linux initramfs images.
+// block and content checksums are skipped over, not checked
+
+use alloc::vec::Vec;
+
+pub const LZ4_MAGIC: u32 = 0x184d_2204;
+pub const LZ4_LEGACY_MAGIC: u32 = 0x184c_2102;
+// 0x184d2a50 to 0x184d2a5f, a length and that many bytes to ignore
+const SKIPPABLE_MASK: u32 = 0xffff_fff0;
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+
+const LEGACY_BLOCK: usize = 8 << 20;
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+// one block, matches can reach back into the blocks before it
+fn block(data: &[u8], out: &mut Vec<u8>, start: usize) -> Result<(), &'static str> {
+    let truncated = "lz4 block truncated";
+    let mut pos = 0;
+    // 15 in a nibble means more length bytes follow, each 255 one meaning another
+    let length = |pos: &mut usize, nibble: usize| -> Result<usize, &'static str> {
+        let mut len = nibble;
+        if nibble == 15 {
+            loop {
+                let b = *data.get(*pos).ok_or(truncated)?;
+                *pos += 1;
+                len += b as usize;
+                if b != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    };
+
+    loop {
+        let token = *data.get(pos).ok_or(truncated)? as usize;
+        pos += 1;
+        let literals = length(&mut pos, token >> 4)?;
+        out.extend_from_slice(data.get(pos..pos + literals).ok_or(truncated)?);
+        pos += literals;
+        // the last sequence is only literals
+        if pos == data.len() {
+            return Ok(());
+        }
+
+        let offset = data.get(pos..pos + 2).ok_or(truncated)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        pos += 2;
+        let len = length(&mut pos, token & 15)? + 4;
+        if offset == 0 || offset > out.len() - start {
+            return Err("lz4 offset out of range");
+        }
+        let from = out.len() - offset;
+        out.reserve(len);
+        for i in 0..len {
+            out.push(out[from + i]);
+        }
+    }
+}
+
+fn frame(data: &[u8], out: &mut Vec<u8>) -> Result<usize, &'static str> {
+    let truncated = "lz4 frame truncated";
+    let flags = *data.get(4).ok_or(truncated)?;
+    if flags >> 6 != 1 {
+        return Err("unknown lz4 frame version");
+    }
+    let block_checksum = flags & 0x10 != 0;
+    let content_size = flags & 0x08 != 0;
+    let content_checksum = flags & 0x04 != 0;
+    let dict_id = flags & 0x01 != 0;
+    // flags, block descriptor, then the optionals and the header checksum
+    let mut pos = 6 + 8 * content_size as usize + 4 * dict_id as usize + 1;
+
+    let start = out.len();
+    loop {
+        let size = u32_at(data, pos).ok_or(truncated)?;
+        pos += 4;
+        if size == 0 {
+            break;
+        }
+        let len = (size & 0x7fff_ffff) as usize;
+        let payload = data.get(pos..pos + len).ok_or(truncated)?;
+        if size & 0x8000_0000 != 0 {
+            out.extend_from_slice(payload);
+        } else {
+            block(payload, out, start)?;
+        }
+        pos += len + 4 * block_checksum as usize;
+    }
+    Ok(pos + 4 * content_checksum as usize)
+}
+
+fn legacy(data: &[u8], out: &mut Vec<u8>) -> Result<usize, &'static str> {
+    let mut pos = 4;
+    // ends with the data or wherever another frame starts
+    while let Some(size) = u32_at(data, pos) {
+        if size == LZ4_MAGIC || size == LZ4_LEGACY_MAGIC || size & SKIPPABLE_MASK == SKIPPABLE_MAGIC
+        {
+            break;
+        }
+        let payload = data
+            .get(pos + 4..pos + 4 + size as usize)
+            .ok_or("lz4 block truncated")?;
+        // every block stands alone here
+        let start = out.len();
+        block(payload, out, start)?;
+        if out.len() - start > LEGACY_BLOCK {
+            return Err("lz4 legacy block too big");
+        }
+        pos += 4 + size as usize;
+    }
+    Ok(pos)
+}
+
+// frames back to back until something that isn't one, trailing zeroes are padding
+pub fn unlz4(mut data: &[u8], out: &mut Vec<u8>) -> Result<(), &'static str> {
+    while let Some(magic) = u32_at(data, 0) {
+        let used = match magic {
+            LZ4_MAGIC => frame(data, out)?,
+            LZ4_LEGACY_MAGIC => legacy(data, out)?,
+            _ if magic & SKIPPABLE_MASK == SKIPPABLE_MAGIC => {
+                8 + u32_at(data, 4).ok_or("lz4 frame truncated")? as usize
+            }
+            0 => 4,
+            _ => return Err("unknown lz4 frame"),
+        };
+        data = data.get(used..).ok_or("lz4 frame truncated")?;
+    }
+    Ok(())
+}
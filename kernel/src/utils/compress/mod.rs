@@ -0,0 +1,41 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use alloc::vec::Vec;
+
+pub mod inflate;
+pub mod lz4;
+pub mod zstd;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    // by the magic at the start, None for anything else
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&inflate::GZIP_MAGIC) {
+            return Some(Self::Gzip);
+        }
+        match u32::from_le_bytes(data.get(..4)?.try_into().ok()?) {
+            zstd::ZSTD_MAGIC => Some(Self::Zstd),
+            lz4::LZ4_MAGIC | lz4::LZ4_LEGACY_MAGIC => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, &'static str> {
+        let mut out = Vec::new();
+        match self {
+            Self::Gzip => inflate::gunzip(data, &mut out)?,
+            Self::Zstd => zstd::unzstd(data, &mut out)?,
+            Self::Lz4 => lz4::unlz4(data, &mut out)?,
+        }
+        Ok(out)
+    }
+}
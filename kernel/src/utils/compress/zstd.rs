@@ -0,0 +1,704 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// zstandard frames (rfc 8878) without dictionaries. the content checksum is skipped
+
+use alloc::{vec, vec::Vec};
+
+pub const ZSTD_MAGIC: u32 = 0xfd2f_b528;
+// 0x184d2a50 to 0x184d2a5f, a length and that many bytes to ignore
+const SKIPPABLE_MASK: u32 = 0xffff_fff0;
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+
+const TRUNCATED: &str = "zstd data truncated";
+const CORRUPT: &str = "zstd data corrupt";
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+// lsb first from the start, for fse table descriptions. past the end reads zeroes
+struct ForwardBits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl ForwardBits<'_> {
+    fn peek(&self, n: u32) -> u32 {
+        let mut window = 0u64;
+        for i in 0..5 {
+            if let Some(&b) = self.data.get(self.pos / 8 + i) {
+                window |= (b as u64) << (8 * i);
+            }
+        }
+        ((window >> (self.pos % 8)) & ((1 << n) - 1)) as u32
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.pos += n as usize;
+    }
+
+    fn bytes_used(&self) -> Result<usize, &'static str> {
+        let used = self.pos.div_ceil(8);
+        if used > self.data.len() {
+            return Err(TRUNCATED);
+        }
+        Ok(used)
+    }
+}
+
+// read from the end towards the start. the highest set bit of the last byte marks where it
+// begins, past the start reads zeroes and leaves `pos` negative
+struct BackwardBits<'a> {
+    data: &'a [u8],
+    pos: isize,
+}
+
+impl<'a> BackwardBits<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, &'static str> {
+        let last = *data.last().ok_or(TRUNCATED)?;
+        if last == 0 {
+            return Err(CORRUPT);
+        }
+        let marker = 7 - last.leading_zeros() as isize;
+        Ok(Self {
+            data,
+            pos: (data.len() as isize - 1) * 8 + marker,
+        })
+    }
+
+    // the `n` bits below `pos`, up to 32 of them
+    fn peek(&self, n: u32) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        let low = self.pos - n as isize;
+        let (low, n, shift) = match low {
+            0.. => (low as usize, n, 0),
+            _ if -low >= n as isize => return 0,
+            _ => (0, (n as isize + low) as u32, (-low) as u32),
+        };
+        let byte = low / 8;
+        let window = match self.data.get(byte..byte + 8) {
+            Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            None => {
+                let mut window = 0u64;
+                for (i, &b) in self.data[byte.min(self.data.len())..].iter().enumerate() {
+                    window |= (b as u64) << (8 * i);
+                }
+                window
+            }
+        };
+        ((window >> (low % 8)) & ((1 << n) - 1)) << shift
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.pos -= n as isize;
+    }
+
+    fn read(&mut self, n: u32) -> u64 {
+        let value = self.peek(n);
+        self.consume(n);
+        value
+    }
+
+    fn overflowed(&self) -> bool {
+        self.pos < 0
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct FseEntry {
+    symbol: u8,
+    bits: u8,
+    base: u16,
+}
+
+#[derive(Clone)]
+struct Fse {
+    log: u32,
+    table: Vec<FseEntry>,
+}
+
+impl Fse {
+    // `norm` is the normalized counts, -1 for "less than one"
+    fn new(norm: &[i16], log: u32) -> Result<Self, &'static str> {
+        let size = 1usize << log;
+        let mut table = vec![FseEntry::default(); size];
+        let mut high = size - 1;
+        for (symbol, &count) in norm.iter().enumerate() {
+            if count == -1 {
+                table[high].symbol = symbol as u8;
+                high = high.wrapping_sub(1);
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut pos = 0;
+        for (symbol, &count) in norm.iter().enumerate() {
+            for _ in 0..count.max(0) {
+                table[pos].symbol = symbol as u8;
+                pos = (pos + step) & (size - 1);
+                while pos > high {
+                    pos = (pos + step) & (size - 1);
+                }
+            }
+        }
+        if pos != 0 {
+            return Err(CORRUPT);
+        }
+
+        let mut next: Vec<u32> = norm.iter().map(|&c| c.max(1) as u32).collect();
+        for entry in table.iter_mut() {
+            let x = &mut next[entry.symbol as usize];
+            let bits = log - x.ilog2();
+            entry.bits = bits as u8;
+            entry.base = ((*x << bits) as usize - size) as u16;
+            *x += 1;
+        }
+        Ok(Self { log, table })
+    }
+
+    // one symbol everywhere, for rle mode
+    fn rle(symbol: u8) -> Self {
+        Self {
+            log: 0,
+            table: vec![FseEntry {
+                symbol,
+                bits: 0,
+                base: 0,
+            }],
+        }
+    }
+
+    // the table description at the start of `data`, and how many bytes it took
+    fn read(data: &[u8], max_log: u32, max_symbol: usize) -> Result<(Self, usize), &'static str> {
+        let mut bits = ForwardBits { data, pos: 0 };
+        let log = bits.peek(4) + 5;
+        bits.consume(4);
+        if log > max_log {
+            return Err(CORRUPT);
+        }
+
+        let mut norm: Vec<i16> = Vec::new();
+        let mut remaining = (1i32 << log) + 1;
+        let mut threshold = 1i32 << log;
+        let mut nbits = log + 1;
+        let mut previous_zero = false;
+        while remaining > 1 && norm.len() <= max_symbol {
+            if previous_zero {
+                // 2 bit repeat flags, 3 means keep going
+                loop {
+                    let repeat = bits.peek(2);
+                    bits.consume(2);
+                    norm.extend(core::iter::repeat_n(0, repeat as usize));
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+                if norm.len() > max_symbol {
+                    return Err(CORRUPT);
+                }
+            }
+            let max = (2 * threshold - 1) - remaining;
+            let raw = bits.peek(nbits) as i32;
+            let mut count = if (raw & (threshold - 1)) < max {
+                bits.consume(nbits - 1);
+                raw & (threshold - 1)
+            } else {
+                bits.consume(nbits);
+                let count = raw & (2 * threshold - 1);
+                if count >= threshold {
+                    count - max
+                } else {
+                    count
+                }
+            };
+            count -= 1;
+            remaining -= count.abs();
+            norm.push(count as i16);
+            previous_zero = count == 0;
+            while remaining < threshold {
+                nbits -= 1;
+                threshold >>= 1;
+            }
+        }
+        if remaining != 1 || norm.len() > max_symbol + 1 {
+            return Err(CORRUPT);
+        }
+        Ok((Self::new(&norm, log)?, bits.bytes_used()?))
+    }
+}
+
+// a decoder walking an fse table
+struct FseState<'a> {
+    table: &'a Fse,
+    state: usize,
+}
+
+impl<'a> FseState<'a> {
+    fn new(table: &'a Fse, bits: &mut BackwardBits) -> Self {
+        let state = bits.read(table.log) as usize;
+        Self { table, state }
+    }
+
+    fn symbol(&self) -> u8 {
+        self.table.table[self.state].symbol
+    }
+
+    fn update(&mut self, bits: &mut BackwardBits) {
+        let entry = self.table.table[self.state];
+        self.state = entry.base as usize + bits.read(entry.bits as u32) as usize;
+    }
+}
+
+// indexed by the next `max_bits` bits, (symbol, code length)
+struct Huffman {
+    table: Vec<(u8, u8)>,
+    max_bits: u32,
+}
+
+impl Huffman {
+    // the tree description at the start of `data`, and how many bytes it took
+    fn read(data: &[u8]) -> Result<(Self, usize), &'static str> {
+        let header = *data.first().ok_or(TRUNCATED)? as usize;
+        let mut weights: Vec<u8> = Vec::with_capacity(256);
+        let used = if header < 128 {
+            // fse compressed weights, two states taking turns
+            let data = data.get(1..1 + header).ok_or(TRUNCATED)?;
+            let (fse, table_len) = Fse::read(data, 6, 255)?;
+            let mut bits = BackwardBits::new(&data[table_len..])?;
+            let mut first = FseState::new(&fse, &mut bits);
+            let mut second = FseState::new(&fse, &mut bits);
+            loop {
+                weights.push(first.symbol());
+                first.update(&mut bits);
+                if bits.overflowed() {
+                    weights.push(second.symbol());
+                    break;
+                }
+                weights.push(second.symbol());
+                second.update(&mut bits);
+                if bits.overflowed() {
+                    weights.push(first.symbol());
+                    break;
+                }
+                if weights.len() > 255 {
+                    return Err(CORRUPT);
+                }
+            }
+            1 + header
+        } else {
+            // 4 bits each, high nibble first
+            let count = header - 127;
+            let packed = data.get(1..1 + count.div_ceil(2)).ok_or(TRUNCATED)?;
+            for i in 0..count {
+                let byte = packed[i / 2];
+                weights.push(if i % 2 == 0 { byte >> 4 } else { byte & 15 });
+            }
+            1 + count.div_ceil(2)
+        };
+
+        // the last weight is whatever makes the total a power of two
+        let mut total = 0u32;
+        for &w in &weights {
+            if w > 11 {
+                return Err(CORRUPT);
+            }
+            if w > 0 {
+                total += 1 << (w - 1);
+            }
+        }
+        if total == 0 {
+            return Err(CORRUPT);
+        }
+        let max_bits = total.ilog2() + 1;
+        let left = (1 << max_bits) - total;
+        if !left.is_power_of_two() || max_bits > 11 {
+            return Err(CORRUPT);
+        }
+        weights.push(left.ilog2() as u8 + 1);
+
+        // symbols of a weight get consecutive ranges, the lightest weights first
+        let mut counts = [0usize; 13];
+        for &w in &weights {
+            counts[w as usize] += 1;
+        }
+        let mut start = [0usize; 13];
+        let mut pos = 0;
+        for (w, start) in start.iter_mut().enumerate().skip(1) {
+            *start = pos;
+            pos += counts[w] << (w - 1);
+        }
+        let mut table = vec![(0, 0); 1 << max_bits];
+        for (symbol, &w) in weights.iter().enumerate() {
+            if w == 0 {
+                continue;
+            }
+            let len = 1 << (w - 1);
+            let bits = (max_bits + 1 - w as u32) as u8;
+            table[start[w as usize]..start[w as usize] + len].fill((symbol as u8, bits));
+            start[w as usize] += len;
+        }
+        Ok((Self { table, max_bits }, used))
+    }
+
+    fn stream(&self, data: &[u8], count: usize, out: &mut Vec<u8>) -> Result<(), &'static str> {
+        let mut bits = BackwardBits::new(data)?;
+        for _ in 0..count {
+            let (symbol, len) = self.table[bits.peek(self.max_bits) as usize];
+            bits.consume(len as u32);
+            out.push(symbol);
+        }
+        if bits.pos != 0 {
+            return Err(CORRUPT);
+        }
+        Ok(())
+    }
+}
+
+const LL_BASE: [u32; 36] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 18, 20, 22, 24, 28, 32, 40, 48, 64,
+    128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536,
+];
+const LL_BITS: [u8; 36] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 4, 6, 7, 8, 9, 10, 11,
+    12, 13, 14, 15, 16,
+];
+const ML_BASE: [u32; 53] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+    28, 29, 30, 31, 32, 33, 34, 35, 37, 39, 41, 43, 47, 51, 59, 67, 83, 99, 131, 259, 515, 1027,
+    2051, 4099, 8195, 16387, 32771, 65539,
+];
+const ML_BITS: [u8; 53] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    1, 1, 1, 1, 2, 2, 3, 3, 4, 4, 5, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+];
+
+// the predefined distributions
+const LL_DEFAULT: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+];
+const ML_DEFAULT: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+const OF_DEFAULT: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+// what carries over from one block to the next within a frame
+struct Frame {
+    start: usize,
+    repeat: [usize; 3],
+    huffman: Option<Huffman>,
+    ll: Option<Fse>,
+    of: Option<Fse>,
+    ml: Option<Fse>,
+    literals: Vec<u8>,
+}
+
+// the table for one of the three sequence symbols, by the 2 bit mode
+fn sequence_table(
+    mode: u8,
+    data: &[u8],
+    previous: &mut Option<Fse>,
+    default: (&[i16], u32),
+    max: (u32, usize),
+) -> Result<usize, &'static str> {
+    let used = match mode {
+        0 => {
+            *previous = Some(Fse::new(default.0, default.1)?);
+            0
+        }
+        1 => {
+            *previous = Some(Fse::rle(*data.first().ok_or(TRUNCATED)?));
+            1
+        }
+        2 => {
+            let (fse, used) = Fse::read(data, max.0, max.1)?;
+            *previous = Some(fse);
+            used
+        }
+        _ => {
+            if previous.is_none() {
+                return Err(CORRUPT);
+            }
+            0
+        }
+    };
+    Ok(used)
+}
+
+impl Frame {
+    fn literals(&mut self, data: &[u8]) -> Result<usize, &'static str> {
+        let b = |i: usize| data.get(i).map(|&b| b as usize).ok_or(TRUNCATED);
+        let b0 = b(0)?;
+        self.literals.clear();
+        match b0 & 3 {
+            // raw and rle
+            kind @ (0 | 1) => {
+                let (size, header) = match (b0 >> 2) & 3 {
+                    0 | 2 => (b0 >> 3, 1),
+                    1 => ((b0 >> 4) + (b(1)? << 4), 2),
+                    _ => ((b0 >> 4) + (b(1)? << 4) + (b(2)? << 12), 3),
+                };
+                if kind == 0 {
+                    let raw = data.get(header..header + size).ok_or(TRUNCATED)?;
+                    self.literals.extend_from_slice(raw);
+                    Ok(header + size)
+                } else {
+                    let byte = b(header)? as u8;
+                    self.literals.resize(size, byte);
+                    Ok(header + 1)
+                }
+            }
+            // compressed, and treeless reusing the last block's tree
+            kind => {
+                let (regenerated, compressed, header, streams) = match (b0 >> 2) & 3 {
+                    format @ (0 | 1) => {
+                        let v = b0 | b(1)? << 8 | b(2)? << 16;
+                        let streams = if format == 0 { 1 } else { 4 };
+                        ((v >> 4) & 0x3ff, (v >> 14) & 0x3ff, 3, streams)
+                    }
+                    2 => {
+                        let v = b0 | b(1)? << 8 | b(2)? << 16 | b(3)? << 24;
+                        ((v >> 4) & 0x3fff, (v >> 18) & 0x3fff, 4, 4)
+                    }
+                    _ => {
+                        let v = b0 | b(1)? << 8 | b(2)? << 16 | b(3)? << 24 | b(4)? << 32;
+                        ((v >> 4) & 0x3ffff, (v >> 22) & 0x3ffff, 5, 4)
+                    }
+                };
+                let mut payload = data.get(header..header + compressed).ok_or(TRUNCATED)?;
+                if kind == 2 {
+                    let (huffman, used) = Huffman::read(payload)?;
+                    self.huffman = Some(huffman);
+                    payload = &payload[used..];
+                }
+                let huffman = self.huffman.as_ref().ok_or(CORRUPT)?;
+                self.literals.reserve(regenerated);
+                if streams == 1 {
+                    huffman.stream(payload, regenerated, &mut self.literals)?;
+                } else {
+                    let jump = payload.get(..6).ok_or(TRUNCATED)?;
+                    let sizes = [
+                        u16::from_le_bytes([jump[0], jump[1]]) as usize,
+                        u16::from_le_bytes([jump[2], jump[3]]) as usize,
+                        u16::from_le_bytes([jump[4], jump[5]]) as usize,
+                    ];
+                    let each = regenerated.div_ceil(4);
+                    let mut rest = &payload[6..];
+                    let last = regenerated.checked_sub(3 * each).ok_or(CORRUPT)?;
+                    for size in sizes {
+                        let stream = rest.get(..size).ok_or(TRUNCATED)?;
+                        rest = &rest[size..];
+                        huffman.stream(stream, each, &mut self.literals)?;
+                    }
+                    huffman.stream(rest, last, &mut self.literals)?;
+                }
+                Ok(header + compressed)
+            }
+        }
+    }
+
+    fn sequences(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), &'static str> {
+        let b = |i: usize| data.get(i).map(|&b| b as usize).ok_or(TRUNCATED);
+        let (count, mut pos) = match b(0)? {
+            0 => (0, 1),
+            n @ 1..128 => (n, 1),
+            n @ 128..255 => (((n - 128) << 8) + b(1)?, 2),
+            _ => (b(1)? + (b(2)? << 8) + 0x7f00, 3),
+        };
+        if count == 0 {
+            out.extend_from_slice(&self.literals);
+            return Ok(());
+        }
+
+        let modes = b(pos)? as u8;
+        pos += 1;
+        let rest = |pos: usize| data.get(pos..).ok_or(TRUNCATED);
+        pos += sequence_table(
+            modes >> 6,
+            rest(pos)?,
+            &mut self.ll,
+            (&LL_DEFAULT, 6),
+            (9, 35),
+        )?;
+        pos += sequence_table(
+            (modes >> 4) & 3,
+            rest(pos)?,
+            &mut self.of,
+            (&OF_DEFAULT, 5),
+            (8, 31),
+        )?;
+        pos += sequence_table(
+            (modes >> 2) & 3,
+            rest(pos)?,
+            &mut self.ml,
+            (&ML_DEFAULT, 6),
+            (9, 52),
+        )?;
+
+        let (Some(ll), Some(of), Some(ml)) = (&self.ll, &self.of, &self.ml) else {
+            return Err(CORRUPT);
+        };
+        let mut bits = BackwardBits::new(rest(pos)?)?;
+        let mut ll = FseState::new(ll, &mut bits);
+        let mut of = FseState::new(of, &mut bits);
+        let mut ml = FseState::new(ml, &mut bits);
+        let mut literals = &self.literals[..];
+        for i in 0..count {
+            let (ll_code, of_code, ml_code) = (
+                ll.symbol() as usize,
+                of.symbol() as u32,
+                ml.symbol() as usize,
+            );
+            if ll_code >= LL_BASE.len() || ml_code >= ML_BASE.len() || of_code > 31 {
+                return Err(CORRUPT);
+            }
+            let offset_value = (1usize << of_code) + bits.read(of_code) as usize;
+            let match_len = ML_BASE[ml_code] as usize + bits.read(ML_BITS[ml_code] as u32) as usize;
+            let lit_len = LL_BASE[ll_code] as usize + bits.read(LL_BITS[ll_code] as u32) as usize;
+
+            let repeat = &mut self.repeat;
+            let offset = if offset_value > 3 {
+                let offset = offset_value - 3;
+                *repeat = [offset, repeat[0], repeat[1]];
+                offset
+            } else {
+                // a literal length of 0 shifts which repeat offset is meant
+                match offset_value - 1 + (lit_len == 0) as usize {
+                    0 => repeat[0],
+                    1 => {
+                        let offset = repeat[1];
+                        *repeat = [offset, repeat[0], repeat[2]];
+                        offset
+                    }
+                    2 => {
+                        let offset = repeat[2];
+                        *repeat = [offset, repeat[0], repeat[1]];
+                        offset
+                    }
+                    _ => {
+                        let offset = repeat[0].checked_sub(1).filter(|&o| o > 0).ok_or(CORRUPT)?;
+                        *repeat = [offset, repeat[0], repeat[1]];
+                        offset
+                    }
+                }
+            };
+
+            if i + 1 < count {
+                ll.update(&mut bits);
+                ml.update(&mut bits);
+                of.update(&mut bits);
+            }
+
+            out.extend_from_slice(literals.get(..lit_len).ok_or(CORRUPT)?);
+            literals = &literals[lit_len..];
+            if offset > out.len() - self.start {
+                return Err(CORRUPT);
+            }
+            let from = out.len() - offset;
+            out.reserve(match_len);
+            for j in 0..match_len {
+                out.push(out[from + j]);
+            }
+        }
+        if bits.pos != 0 {
+            return Err(CORRUPT);
+        }
+        out.extend_from_slice(literals);
+        Ok(())
+    }
+
+    fn block(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<(), &'static str> {
+        let used = self.literals(data)?;
+        self.sequences(&data[used..], out)
+    }
+}
+
+// one frame at the start of `data`, returns its length
+fn frame(data: &[u8], out: &mut Vec<u8>) -> Result<usize, &'static str> {
+    let descriptor = *data.get(4).ok_or(TRUNCATED)?;
+    let single_segment = descriptor & 0x20 != 0;
+    if descriptor & 0x08 != 0 {
+        return Err(CORRUPT);
+    }
+    let checksum = descriptor & 0x04 != 0;
+    let dict_len = [0, 1, 2, 4][(descriptor & 3) as usize];
+    let content_len = match descriptor >> 6 {
+        0 => single_segment as usize,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let mut pos = 5 + !single_segment as usize;
+    let dict = data.get(pos..pos + dict_len).ok_or(TRUNCATED)?;
+    if dict.iter().any(|&b| b != 0) {
+        return Err("zstd dictionaries aren't supported");
+    }
+    pos += dict_len;
+    let content = data.get(pos..pos + content_len).ok_or(TRUNCATED)?;
+    let mut size = [0u8; 8];
+    size[..content_len].copy_from_slice(content);
+    let size = u64::from_le_bytes(size) + if content_len == 2 { 256 } else { 0 };
+    pos += content_len;
+    // only a hint, don't trust it with all of memory
+    out.reserve((size as usize).min(256 << 20));
+
+    let mut frame = Frame {
+        start: out.len(),
+        repeat: [1, 4, 8],
+        huffman: None,
+        ll: None,
+        of: None,
+        ml: None,
+        literals: Vec::new(),
+    };
+    loop {
+        let header = data.get(pos..pos + 3).ok_or(TRUNCATED)?;
+        let header = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+        pos += 3;
+        let last = header & 1 != 0;
+        let len = header >> 3;
+        match (header >> 1) & 3 {
+            0 => {
+                out.extend_from_slice(data.get(pos..pos + len).ok_or(TRUNCATED)?);
+                pos += len;
+            }
+            1 => {
+                let byte = *data.get(pos).ok_or(TRUNCATED)?;
+                out.resize(out.len() + len, byte);
+                pos += 1;
+            }
+            2 => {
+                frame.block(data.get(pos..pos + len).ok_or(TRUNCATED)?, out)?;
+                pos += len;
+            }
+            _ => return Err(CORRUPT),
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(pos + 4 * checksum as usize)
+}
+
+// frames back to back until something that isn't one, trailing zeroes are padding
+pub fn unzstd(mut data: &[u8], out: &mut Vec<u8>) -> Result<(), &'static str> {
+    while let Some(magic) = u32_at(data, 0) {
+        let used = match magic {
+            ZSTD_MAGIC => frame(data, out)?,
+            _ if magic & SKIPPABLE_MASK == SKIPPABLE_MAGIC => {
+                8 + u32_at(data, 4).ok_or(TRUNCATED)? as usize
+            }
+            0 => 4,
+            _ => return Err("unknown zstd frame"),
+        };
+        data = data.get(used..).ok_or(TRUNCATED)?;
+    }
+    Ok(())
+}
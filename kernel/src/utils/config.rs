@@ -12,6 +12,13 @@ pub const CONFIG_PATH: &str = include_str!("../../res/kernel.cfg");
 
 pub struct Config {
     pub timezone_offset: PropertyValue,
+    // the device to publish as /dev/root: PARTUUID=, PARTLABEL= or a device name
+    pub root: PropertyValue,
+    // what's on it, every disk filesystem is tried when it's empty
+    pub rootfstype: PropertyValue,
+    // how many /dev/ramN there are and how big each one is, in KiB
+    pub ramdisks: PropertyValue,
+    pub ramdisk_size: PropertyValue,
 }
 
 #[derive(Debug, Clone)]
@@ -56,7 +63,33 @@ pub fn get_config() -> Config {
         .cloned()
         .unwrap_or(PropertyValue::Integer(0));
 
-    Config { timezone_offset }
+    let root = props
+        .get("root")
+        .cloned()
+        .unwrap_or(PropertyValue::String(String::new()));
+
+    let rootfstype = props
+        .get("rootfstype")
+        .cloned()
+        .unwrap_or(PropertyValue::String(String::new()));
+
+    let ramdisks = props
+        .get("ramdisks")
+        .cloned()
+        .unwrap_or(PropertyValue::Integer(1));
+
+    let ramdisk_size = props
+        .get("ramdisk_size")
+        .cloned()
+        .unwrap_or(PropertyValue::Integer(16384));
+
+    Config {
+        timezone_offset,
+        root,
+        rootfstype,
+        ramdisks,
+        ramdisk_size,
+    }
 }
 
 fn parse_config(config: &str) -> BTreeMap<String, PropertyValue> {
@@ -0,0 +1,147 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+// the "newc" cpio format, what linux initramfs images and most tooling use
+
+use alloc::{borrow::Cow, collections::btree_map::BTreeMap};
+
+use super::archive::{Entry, EntryKind};
+
+const HEADER: usize = 110;
+const TRAILER: &str = "TRAILER!!!";
+
+// 070702 is the same with a checksum of the data, which we don't check
+pub fn is_cpio(data: &[u8]) -> bool {
+    data.starts_with(b"070701") || data.starts_with(b"070702")
+}
+
+// hardlinks are told apart by device and inode number
+type Key = (u32, u32, u32);
+
+struct Raw<'a> {
+    name: &'a str,
+    key: Key,
+    mode: u32,
+    nlink: u32,
+    mtime: u32,
+    data: &'a [u8],
+}
+
+fn hex(field: &[u8]) -> Option<u32> {
+    u32::from_str_radix(core::str::from_utf8(field).ok()?, 16).ok()
+}
+
+// the header at `offset` and where the one after it starts
+fn parse(archive: &[u8], offset: usize) -> Option<(Raw<'_>, usize)> {
+    let header = archive.get(offset..offset + HEADER)?;
+    if !is_cpio(header) {
+        return None;
+    }
+    let field = |i: usize| hex(&header[6 + i * 8..14 + i * 8]);
+    let size = field(6)? as usize;
+    let name_size = field(11)? as usize;
+
+    let name_start = offset + HEADER;
+    let name = archive.get(name_start..name_start + name_size)?;
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+    // the name and the data are both padded to 4 bytes
+    let data_start = (name_start + name_size).next_multiple_of(4);
+    let data = archive.get(data_start..data_start + size)?;
+    let raw = Raw {
+        name: core::str::from_utf8(name).ok()?,
+        key: (field(7)?, field(8)?, field(0)?),
+        mode: field(1)?,
+        nlink: field(4)?,
+        mtime: field(5)?,
+        data,
+    };
+    Some((raw, (data_start + size).next_multiple_of(4)))
+}
+
+pub struct CpioIter<'a> {
+    archive: &'a [u8],
+    offset: usize,
+    // a hardlinked file's data comes with only one of its names, usually the last
+    contents: BTreeMap<Key, &'a [u8]>,
+    // the first name of every hardlinked file so far
+    seen: BTreeMap<Key, &'a str>,
+}
+
+impl<'a> CpioIter<'a> {
+    pub fn new(archive: &'a [u8]) -> Self {
+        let mut iter = Self {
+            archive,
+            offset: 0,
+            contents: BTreeMap::new(),
+            seen: BTreeMap::new(),
+        };
+        let mut contents = BTreeMap::new();
+        while let Some(raw) = iter.next_raw() {
+            if raw.nlink > 1 && !raw.data.is_empty() {
+                contents.insert(raw.key, raw.data);
+            }
+        }
+        iter.offset = 0;
+        iter.contents = contents;
+        iter
+    }
+
+    // archives can follow each other, with zeroes in between
+    fn next_raw(&mut self) -> Option<Raw<'a>> {
+        loop {
+            let (raw, next) = parse(self.archive, self.offset)?;
+            self.offset = next;
+            if raw.name != TRAILER {
+                return Some(raw);
+            }
+            let rest = self.archive.get(self.offset..)?;
+            self.offset += rest.iter().position(|&b| b != 0)?;
+        }
+    }
+}
+
+impl<'a> Iterator for CpioIter<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let raw = self.next_raw()?;
+            let mut data = raw.data;
+            let mut link = "";
+            let kind = match raw.mode & 0o170000 {
+                0o100000 if raw.nlink > 1 => match self.seen.get(&raw.key) {
+                    Some(&first) => {
+                        link = first;
+                        EntryKind::Hardlink
+                    }
+                    None => {
+                        self.seen.insert(raw.key, raw.name);
+                        data = self.contents.get(&raw.key).copied().unwrap_or(data);
+                        EntryKind::File
+                    }
+                },
+                0o100000 => EntryKind::File,
+                0o040000 => EntryKind::Directory,
+                0o120000 => {
+                    link = core::str::from_utf8(data).unwrap_or("");
+                    EntryKind::Symlink
+                }
+                0o020000 => EntryKind::CharDevice,
+                0o060000 => EntryKind::BlockDevice,
+                0o010000 => EntryKind::Fifo,
+                // sockets
+                _ => continue,
+            };
+            return Some(Entry {
+                name: Cow::Borrowed(raw.name),
+                kind,
+                link,
+                mode: raw.mode & 0o7777,
+                mtime: raw.mtime as u64,
+                data,
+            });
+        }
+    }
+}
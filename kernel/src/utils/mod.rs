@@ -3,13 +3,19 @@
     Released under EUPL 1.2 License
 */
 
+pub mod archive;
 pub mod asm;
+pub mod compress;
 pub mod config;
+pub mod cpio;
+pub mod crc32;
 pub mod elf;
+pub mod errno;
 pub mod heapless;
 pub mod limine;
 pub mod logger;
 pub mod mutex;
+pub mod rwlock;
 pub mod shell;
 pub mod spinlock;
 pub mod term;
@@ -0,0 +1,152 @@
+/*
+    Copyright (C) 2025 bugo07
+    Released under EUPL 1.2 License
+*/
+
+use core::{
+    cell::UnsafeCell,
+    fmt::Debug,
+    sync::atomic::{AtomicIsize, Ordering},
+};
+
+// a reader-writer lock that gives the cpu away while it waits, like Mutex. the holder may
+// be asleep on a disk. readers don't wait on each other and a waiting writer doesn't hold
+// new readers back, so taking a read lock while holding one is fine
+pub struct RwLock<T: ?Sized> {
+    // readers holding it, or WRITER
+    state: AtomicIsize,
+    data: UnsafeCell<T>,
+}
+
+const WRITER: isize = -1;
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T: Sized> RwLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicIsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        let mut backoff = 0;
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            wait(&mut backoff);
+        }
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let readers = self.state.load(Ordering::Relaxed);
+        if readers == WRITER {
+            return None;
+        }
+        self.state
+            .compare_exchange_weak(readers, readers + 1, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockReadGuard { lock: self })
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        let mut backoff = 0;
+        while self
+            .state
+            .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            wait(&mut backoff);
+        }
+        RwLockWriteGuard { lock: self }
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockWriteGuard { lock: self })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) != 0
+    }
+}
+
+// the same backoff as Mutex, yield a few times and then sleep longer and longer
+fn wait(backoff: &mut u32) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::scheduler::is_initialized() {
+        if *backoff < 10 {
+            crate::scheduler::thread::yield_();
+        } else {
+            crate::scheduler::thread::sleep((1u64 << (*backoff - 10)).min(1_000_000));
+        }
+        *backoff += 1;
+        return;
+    }
+    core::hint::spin_loop();
+}
+
+impl<T: ?Sized + Debug> Debug for RwLock<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.try_read() {
+            Some(guard) => f.debug_struct("RwLock").field("inner", &&*guard).finish(),
+            None => f
+                .debug_struct("RwLock")
+                .field("inner", &"<locked>")
+                .finish(),
+        }
+    }
+}
+
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T: ?Sized> core::ops::Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T: ?Sized> core::ops::Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> core::ops::DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
@@ -315,8 +315,10 @@ impl Shell {
                         cwd.join(args[0])
                     };
                     let vfs = fs::get_vfs();
-                    if let Some(new_p) = vfs.resolve_path(full_path) {
-                        new_p.get_path().clone()
+                    if vfs.resolve(full_path.clone()).is_some()
+                        && let Ok(new_p) = vfs.realpath(&full_path, true)
+                    {
+                        new_p
                     } else {
                         println!("cd: {}: No such file or directory", p);
                         return;
@@ -345,31 +347,31 @@ impl Shell {
                     };
 
                     let vfs = fs::get_vfs();
-                    if let Some(new_p) = vfs.resolve_path(full_path) {
-                        new_p.get_path().clone()
+                    if vfs.resolve(full_path.clone()).is_some() {
+                        full_path
                     } else {
                         println!("ls: {}: No such file or directory", p);
                         return;
                     }
                 };
                 let vfs = fs::get_vfs();
-                for child in vfs
-                    .resolve_path(path)
-                    .unwrap_or_else(|| vfs.resolve_path(cwd).unwrap())
-                    .get_children()
-                {
+                let dir = vfs
+                    .resolve(path)
+                    .unwrap_or_else(|| vfs.resolve(cwd).unwrap());
+                for child in dir.node().get_children() {
                     use crate::drivers::fs::NodeMode;
 
+                    let node = child.inode.node();
                     print!(
                         "{}{}{} ",
-                        if child.is_dir() {
+                        if node.is_dir() {
                             color::BLUE
-                        } else if child.get_permissions().contains(NodeMode::EXECUTE) {
+                        } else if node.get_permissions().contains(NodeMode::EXECUTE) {
                             color::GREEN
                         } else {
                             color::WHITE_BRIGHT
                         },
-                        child.get_name(),
+                        child.name,
                         color::RESET
                     );
                 }
@@ -386,10 +388,10 @@ impl Shell {
                     .lock()
                     .get_cwd()
                     .clone();
-                let mut vfs = fs::get_vfs();
-                let dir = vfs.resolve_path_mut(cwd).unwrap();
+                let dir = fs::get_vfs().resolve(cwd).unwrap();
+                let mut dir = dir.node_mut();
                 for arg in args {
-                    if dir.create_dir(arg).is_none() {
+                    if dir.create_dir(arg).is_err() {
                         println!("mkdir: {arg}: already exists");
                     }
                 }
@@ -405,10 +407,10 @@ impl Shell {
                     .lock()
                     .get_cwd()
                     .clone();
-                let mut vfs = fs::get_vfs();
-                let dir = vfs.resolve_path_mut(cwd).unwrap();
+                let dir = fs::get_vfs().resolve(cwd).unwrap();
+                let mut dir = dir.node_mut();
                 for arg in args {
-                    if dir.create_file(arg).is_none() {
+                    if dir.create_file(arg).is_err() {
                         println!("touch: {arg}: already exists");
                     }
                 }
@@ -430,9 +432,8 @@ impl Shell {
                 } else {
                     cwd.join(args[0])
                 };
-                let vfs = fs::get_vfs();
-                if let Some(node) = vfs.resolve_path(full_path) {
-                    match node.read() {
+                if let Some(inode) = fs::get_vfs().resolve(full_path) {
+                    match inode.node().read() {
                         Some(data) => println!("{}", str::from_utf8(data).unwrap()),
                         None => println!("cat: {}: is a directory", path),
                     }
@@ -457,21 +458,21 @@ impl Shell {
                 } else {
                     cwd.join(args[0])
                 };
-                let mut vfs = fs::get_vfs();
-                let target_path = match vfs.resolve_path(full_path) {
-                    Some(node) => node.get_path().clone(),
-                    None => {
+                let vfs = fs::get_vfs();
+                let target_path = match vfs.realpath(&full_path, false) {
+                    Ok(path) => path,
+                    Err(_) => {
                         println!("rm: no such item");
                         return;
                     }
                 };
                 let name = target_path.get_name().to_string();
                 let parent_path = target_path.get_parent();
-                let Some(parent) = vfs.resolve_path_mut(parent_path) else {
+                let Some(parent) = vfs.resolve(parent_path) else {
                     println!("rm: no such item");
                     return;
                 };
-                if !parent.remove_child(&name) {
+                if parent.node_mut().unlink(&name).is_none() {
                     println!("rm: no such item");
                 }
             }
@@ -1,89 +1,211 @@
-use crate::drivers::fs::VfsNodeType;
+use alloc::{borrow::Cow, format};
 
-pub struct TarHeader<'a> {
-    pub name: &'a str,
-    pub type_: VfsNodeType,
-    pub start: usize,
-    pub end: usize,
-}
+use super::archive::{Entry, EntryKind};
 
-fn oct2bin(s: &[u8]) -> usize {
-    let mut n = 0;
+fn oct2bin(s: &[u8]) -> u64 {
+    let mut n: u64 = 0;
     for &c in s {
         if (b'0'..=b'7').contains(&c) {
-            n = n * 8 + (c - b'0') as usize;
+            n = n.wrapping_mul(8) + (c - b'0') as u64;
         }
     }
     n
 }
 
-fn parse_header<'a>(archive: &'a [u8], offset: usize) -> Option<TarHeader<'a>> {
-    if offset + 512 > archive.len() {
-        return None;
+// octal, or gnu's base-256 with the top bit set for values octal can't fit
+fn number(field: &[u8]) -> u64 {
+    match field.first() {
+        Some(&first) if first & 0x80 != 0 => field[1..]
+            .iter()
+            .fold((first & 0x7f) as u64, |n, &b| n.wrapping_shl(8) | b as u64),
+        _ => oct2bin(field),
     }
+}
 
-    let header = &archive[offset..offset + 512];
-    if &header[257..257 + 5] != b"ustar" {
-        return None;
+// a checksum that adds up is all there is to go on for pre-posix tars
+pub fn is_tar(data: &[u8]) -> bool {
+    data.get(..512).is_some_and(checksum_ok)
+}
+
+// nul padded unless it fills the whole field
+fn field_str(raw: &[u8]) -> Option<&str> {
+    let nul_pos = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+    core::str::from_utf8(&raw[..nul_pos]).ok()
+}
+
+// the checksum field counts as spaces, old tars summed signed bytes
+fn checksum_ok(header: &[u8]) -> bool {
+    let expected = number(&header[148..156]);
+    let (mut unsigned, mut signed) = (0u64, 0i64);
+    for (i, &b) in header.iter().enumerate() {
+        let b = if (148..156).contains(&i) { b' ' } else { b };
+        unsigned += b as u64;
+        signed += b as i8 as i64;
     }
+    expected == unsigned || expected as i64 == signed
+}
+
+// what pax headers override, 'x' for the next entry and 'g' for all of them
+#[derive(Default, Clone, Copy)]
+struct Pax<'a> {
+    path: Option<&'a str>,
+    linkpath: Option<&'a str>,
+    size: Option<usize>,
+    mtime: Option<u64>,
+}
 
-    let type_ = match header[156] {
-        b'0' => VfsNodeType::File,
-        b'5' => VfsNodeType::Directory,
-        _ => return None,
-    };
-
-    let size = oct2bin(&header[0x7c..0x7c + 11]);
-    let start = offset + 512;
-    let end = start + size;
-    if end > archive.len() {
-        return None;
+impl<'a> Pax<'a> {
+    // "<len> <key>=<value>\n" records, <len> counting the whole line
+    fn parse(&mut self, mut data: &'a [u8]) {
+        while let Some(space) = data.iter().position(|&b| b == b' ') {
+            let Some(len) = core::str::from_utf8(&data[..space])
+                .ok()
+                .and_then(|len| len.parse::<usize>().ok())
+                .filter(|&len| len > space + 1 && len <= data.len())
+            else {
+                break;
+            };
+            let record = &data[space + 1..len];
+            data = &data[len..];
+            let record = record.strip_suffix(b"\n").unwrap_or(record);
+            let Some(eq) = record.iter().position(|&b| b == b'=') else {
+                continue;
+            };
+            let Ok(value) = core::str::from_utf8(&record[eq + 1..]) else {
+                continue;
+            };
+            match &record[..eq] {
+                b"path" => self.path = Some(value),
+                b"linkpath" => self.linkpath = Some(value),
+                b"size" => self.size = value.parse().ok(),
+                // seconds, the fraction is dropped
+                b"mtime" => self.mtime = value.split('.').next().and_then(|s| s.parse().ok()),
+                _ => {}
+            }
+        }
     }
 
-    let raw_name = &header[..100];
-    let nul_pos = raw_name
-        .iter()
-        .position(|&b| b == 0)
-        .unwrap_or(raw_name.len());
-    let name = core::str::from_utf8(&raw_name[..nul_pos]).ok()?;
-
-    Some(TarHeader {
-        name,
-        type_,
-        start,
-        end,
-    })
+    fn or(self, other: Self) -> Self {
+        Self {
+            path: self.path.or(other.path),
+            linkpath: self.linkpath.or(other.linkpath),
+            size: self.size.or(other.size),
+            mtime: self.mtime.or(other.mtime),
+        }
+    }
 }
 
 pub struct TarIter<'a> {
     archive: &'a [u8],
     offset: usize,
+    global: Pax<'a>,
+    // from the 'x', 'L' and 'K' entries in front of the next real one
+    next: Pax<'a>,
+    long_name: Option<&'a str>,
+    long_link: Option<&'a str>,
 }
 
 impl<'a> TarIter<'a> {
     pub fn new(archive: &'a [u8]) -> Self {
-        Self { archive, offset: 0 }
+        Self {
+            archive,
+            offset: 0,
+            global: Pax::default(),
+            next: Pax::default(),
+            long_name: None,
+            long_link: None,
+        }
     }
 }
 
 impl<'a> Iterator for TarIter<'a> {
-    type Item = TarHeader<'a>;
+    type Item = Entry<'a>;
 
+    // ends at the zero blocks, the end of the module or anything that isn't a header
     fn next(&mut self) -> Option<Self::Item> {
-        let header = parse_header(self.archive, self.offset)?;
-        // move to next header
-        let size = header.end - header.start;
-        let blocks = size.div_ceil(512);
-        self.offset += 512 + blocks * 512;
-        Some(header)
-    }
-}
+        loop {
+            let header = self.archive.get(self.offset..self.offset + 512)?;
+            if header.iter().all(|&b| b == 0) || !checksum_ok(header) {
+                return None;
+            }
+
+            let pax = self.next.or(self.global);
+            let size = match header[156] {
+                b'x' | b'g' | b'L' | b'K' => number(&header[124..136]) as usize,
+                _ => pax.size.unwrap_or(number(&header[124..136]) as usize),
+            };
+            let start = self.offset + 512;
+            let data = self.archive.get(start..start.checked_add(size)?)?;
+            // move to next header
+            self.offset = start + size.div_ceil(512) * 512;
+
+            let kind = match header[156] {
+                b'0' | b'\0' | b'7' => EntryKind::File,
+                b'1' => EntryKind::Hardlink,
+                b'2' => EntryKind::Symlink,
+                b'3' => EntryKind::CharDevice,
+                b'4' => EntryKind::BlockDevice,
+                b'5' => EntryKind::Directory,
+                b'6' => EntryKind::Fifo,
+                b'x' => {
+                    self.next.parse(data);
+                    continue;
+                }
+                b'g' => {
+                    self.global.parse(data);
+                    continue;
+                }
+                b'L' => {
+                    self.long_name = field_str(data);
+                    continue;
+                }
+                b'K' => {
+                    self.long_link = field_str(data);
+                    continue;
+                }
+                // gnu sparse files, volume labels and such, skipped with whatever led up to them
+                _ => {
+                    self.next = Pax::default();
+                    self.long_name = None;
+                    self.long_link = None;
+                    continue;
+                }
+            };
+            self.next = Pax::default();
+            let long_name = self.long_name.take();
+            let long_link = self.long_link.take();
+
+            let name = match pax.path.or(long_name) {
+                Some(name) => Cow::Borrowed(name),
+                None => {
+                    let Some(name) = field_str(&header[..100]) else {
+                        continue;
+                    };
+                    // only posix ustar has a prefix there, gnu keeps times in it
+                    let prefix = match &header[257..263] {
+                        b"ustar\0" => field_str(&header[345..500]).unwrap_or(""),
+                        _ => "",
+                    };
+                    if prefix.is_empty() {
+                        Cow::Borrowed(name)
+                    } else {
+                        Cow::Owned(format!("{}/{}", prefix, name))
+                    }
+                }
+            };
+            let link = match pax.linkpath.or(long_link) {
+                Some(link) => link,
+                None => field_str(&header[157..257]).unwrap_or(""),
+            };
 
-pub fn tar_lookup<'a>(archive: &'a [u8], filename: &str) -> Option<&'a [u8]> {
-    for header in TarIter::new(archive) {
-        if header.name == filename && header.end <= archive.len() {
-            return Some(&archive[header.start..header.end]);
+            return Some(Entry {
+                name,
+                kind,
+                link,
+                mode: number(&header[100..108]) as u32 & 0o7777,
+                mtime: pax.mtime.unwrap_or(number(&header[136..148])),
+                data,
+            });
         }
     }
-    None
 }